@@ -0,0 +1,304 @@
+use trunk_parser::{
+    walk_expression, walk_statement, Arg, Diagnostic, Expression, ExpressionKind, Identifier, Param, PhpVersion, Program, Severity, Span,
+    Statement, StatementKind, Visitor,
+};
+
+use crate::names::Imports;
+use crate::NameKind;
+
+/// Functions that were deprecated in one version and, for some, removed in a later one.
+const FUNCTIONS: &[(&str, &str, Option<&str>)] = &[
+    ("call_user_method", "4.1", Some("7.0")),
+    ("call_user_method_array", "4.1", Some("7.0")),
+    ("ereg", "5.3", Some("7.0")),
+    ("ereg_replace", "5.3", Some("7.0")),
+    ("eregi", "5.3", Some("7.0")),
+    ("eregi_replace", "5.3", Some("7.0")),
+    ("split", "5.3", Some("7.0")),
+    ("spliti", "5.3", Some("7.0")),
+    ("sql_regcase", "5.3", Some("7.0")),
+    ("create_function", "7.2", Some("8.0")),
+    ("each", "7.2", Some("8.0")),
+    ("gmp_random", "7.2", Some("8.0")),
+    ("read_exif_data", "7.2", Some("8.0")),
+    ("fgetss", "7.3", Some("8.0")),
+    ("image2wbmp", "7.3", Some("8.0")),
+    ("convert_cyr_string", "7.4", Some("8.0")),
+    ("ezmlm_hash", "7.4", Some("8.0")),
+    ("get_magic_quotes_gpc", "7.4", Some("8.0")),
+    ("get_magic_quotes_runtime", "7.4", Some("8.0")),
+    ("hebrevc", "7.4", Some("8.0")),
+    ("is_real", "7.4", Some("8.0")),
+    ("money_format", "7.4", Some("8.0")),
+    ("restore_include_path", "7.4", Some("8.0")),
+    ("libxml_disable_entity_loader", "8.0", None),
+    ("date_sunrise", "8.1", None),
+    ("date_sunset", "8.1", None),
+    ("gmstrftime", "8.1", None),
+    ("mhash", "8.1", None),
+    ("odbc_result_all", "8.1", None),
+    ("strftime", "8.1", None),
+    ("strptime", "8.1", None),
+    ("utf8_decode", "8.2", None),
+    ("utf8_encode", "8.2", None),
+    ("assert_options", "8.3", None),
+];
+
+/// Constants that were deprecated, and for some removed, in the same way.
+const CONSTANTS: &[(&str, &str, Option<&str>)] = &[
+    ("FILTER_FLAG_HOST_REQUIRED", "7.3", Some("8.0")),
+    ("FILTER_FLAG_SCHEME_REQUIRED", "7.3", Some("8.0")),
+    ("FILTER_SANITIZE_STRING", "8.1", None),
+    ("FILTER_SANITIZE_STRIPPED", "8.1", None),
+];
+
+/// Find functions, constants and constructs that are deprecated in the targeted PHP version, or
+/// that have been removed by it, such as `each()`, PHP 4 style constructors and optional parameters
+/// before required ones. These complement the parser's own warnings about syntax, such as curly
+/// brace offsets and dynamic properties, which need the program to be parsed for the same version.
+///
+/// Each is reported with the same codes and messages as the parser's warnings: `deprecated`, or
+/// `removed` if the targeted version no longer supports it.
+pub fn check_compatibility(program: &Program, target: PhpVersion) -> Vec<Diagnostic> {
+    let mut checker = Compat { target, imports: Imports::default(), diagnostics: Vec::new() };
+    checker.visit_program(program);
+
+    let mut diagnostics = checker.diagnostics;
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.map(|span| span.start));
+    diagnostics
+}
+
+struct Compat {
+    target: PhpVersion,
+    imports: Imports,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Compat {
+    /// Report a feature that was deprecated in `since` and, if it has been, removed in `removed`,
+    /// as long as the targeted version is affected.
+    fn deprecated(&mut self, feature: &str, since: &str, removed: Option<&str>, span: Span) {
+        let (code, message) = match removed {
+            Some(version) if affects(version, self.target) => ("removed", format!("{} is no longer supported as of PHP {}", feature, version)),
+            _ if affects(since, self.target) => ("deprecated", format!("{} is deprecated since PHP {}", feature, since)),
+            _ => return,
+        };
+
+        self.diagnostics.push(Diagnostic::new(Severity::Warning, code, message, Some(span)));
+    }
+
+    /// The global name that a name as written refers to, falling back from a namespace as PHP does.
+    fn global(&self, name: &str, kind: NameKind) -> String {
+        let (qualified, fallback) = self.imports.expand(name, kind);
+
+        fallback.unwrap_or(qualified)
+    }
+
+    fn call(&mut self, name: &str, args: &[Arg], span: Span) {
+        let function = self.global(name, NameKind::Function).to_ascii_lowercase();
+
+        if let Some((_, since, removed)) = FUNCTIONS.iter().find(|(deprecated, ..)| *deprecated == function) {
+            return self.deprecated(&format!("the `{}()` function", function), since, *removed, span);
+        }
+
+        if function.starts_with("mysql_") {
+            return self.deprecated("the `mysql` extension", "5.5", Some("7.0"), span);
+        }
+
+        match (function.as_str(), args) {
+            ("define", [_, _, insensitive]) if !matches!(insensitive.value.kind, ExpressionKind::Bool { value: false }) => {
+                self.deprecated("declaring case-insensitive constants with `define()`", "7.3", Some("8.0"), span);
+            },
+            ("assert", [assertion, ..]) if matches!(assertion.value.kind, ExpressionKind::ConstantString { .. } | ExpressionKind::InterpolatedString { .. }) => {
+                self.deprecated("passing a string to `assert()`", "7.2", Some("8.0"), span);
+            },
+            ("get_class" | "get_parent_class", []) => self.deprecated(&format!("calling `{}()` without arguments", function), "8.3", None, span),
+            ("mb_check_encoding", []) => self.deprecated("calling `mb_check_encoding()` without arguments", "8.1", None, span),
+            _ => {},
+        }
+    }
+
+    /// Optional parameters that are followed by a required one are treated as required, unless they
+    /// only make a typed parameter nullable with a default of `null`.
+    fn params(&mut self, params: &[Param]) {
+        let Some(last_required) = params.iter().rposition(|param| param.default.is_none() && !param.variadic) else {
+            return;
+        };
+
+        for param in &params[..last_required] {
+            match &param.default {
+                Some(default) if !(param.r#type.is_some() && matches!(default.kind, ExpressionKind::Null)) => {
+                    self.deprecated("declaring an optional parameter before a required one", "8.0", None, param.span);
+                },
+                _ => {},
+            }
+        }
+    }
+
+    fn class(&mut self, name: &str, implements: &[Identifier], body: &[Statement], span: Span) {
+        let has_method = |method: &str| body.iter().any(|member| matches!(&member.kind, StatementKind::Method { name, .. } if name.name.eq_ignore_ascii_case(method)));
+
+        // Namespaced classes never treated a method named after the class as a constructor.
+        if self.imports.qualify(name) == name && has_method(name) && !has_method("__construct") {
+            self.deprecated("declaring a constructor as a method named after its class", "7.0", Some("8.0"), span);
+        }
+
+        let serializable = implements.iter().any(|interface| self.global(&interface.name, NameKind::Class).eq_ignore_ascii_case("Serializable"));
+
+        if serializable && !(has_method("__serialize") && has_method("__unserialize")) {
+            self.deprecated("implementing `Serializable` without `__serialize()` and `__unserialize()`", "8.1", None, span);
+        }
+    }
+}
+
+impl Visitor for Compat {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::Namespace { name, body } => {
+                self.imports.enter_namespace(name);
+
+                if !body.is_empty() {
+                    walk_statement(self, statement);
+                    self.imports.enter_namespace("");
+                }
+
+                return;
+            },
+            StatementKind::Use { uses, kind } => {
+                for r#use in uses {
+                    self.imports.import(r#use, kind);
+                }
+            },
+            StatementKind::Function { name, params, .. } => {
+                if self.imports.qualify(&name.name).eq_ignore_ascii_case("__autoload") {
+                    self.deprecated("the `__autoload()` function", "7.2", Some("8.0"), name.span);
+                }
+
+                self.params(params);
+            },
+            StatementKind::Method { params, .. } => self.params(params),
+            StatementKind::Class { name, implements, body, .. } => {
+                self.class(&name.name, implements, body, name.span);
+            },
+            StatementKind::Enum { name, implements, body, .. } => {
+                self.class(&name.name, implements, body, name.span);
+            },
+            _ => {},
+        }
+
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::Call { target, args } => {
+                if let ExpressionKind::Identifier { name } = &target.kind {
+                    self.call(name, args, expression.span);
+
+                    // The name isn't a constant, so only the arguments are left to check.
+                    return args.iter().for_each(|arg| self.visit_expression(&arg.value));
+                }
+            },
+            ExpressionKind::Identifier { name } => {
+                let constant = self.global(name, NameKind::Constant);
+
+                if let Some((constant, since, removed)) = CONSTANTS.iter().find(|(deprecated, ..)| *deprecated == constant) {
+                    self.deprecated(&format!("the `{}` constant", constant), since, *removed, expression.span);
+                }
+            },
+            ExpressionKind::Closure { params, .. } | ExpressionKind::ArrowFunction { params, .. } => self.params(params),
+            ExpressionKind::AnonymousClass { implements, body, .. } => {
+                self.class("class@anonymous", implements, body, expression.span);
+            },
+            _ => {},
+        }
+
+        walk_expression(self, expression);
+    }
+}
+
+/// Whether a change made in a version affects the targeted one. Versions older than any that can be
+/// targeted, such as `7.2`, affect them all.
+fn affects(version: &str, target: PhpVersion) -> bool {
+    version.parse::<PhpVersion>().ok().is_none_or(|version| target >= version)
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_parser::{parse, PhpVersion};
+
+    use super::check_compatibility;
+
+    fn check(source: &str, target: PhpVersion) -> Vec<String> {
+        check_compatibility(&parse(source).unwrap(), target)
+            .iter()
+            .map(|diagnostic| format!("{}: {} on line {}", diagnostic.code, diagnostic.message, diagnostic.span.unwrap().line))
+            .collect()
+    }
+
+    #[test]
+    fn functions_and_constants() {
+        let source = "<?php
+namespace App;
+
+while (list($key, $value) = each($array)) {}
+$s = utf8_encode(strftime('%Y'));
+mysql_query('SELECT 1');
+define('GREETING', 'hi', true);
+define('FAREWELL', 'bye');
+assert('$a > 1');
+echo get_class(), get_class($this);
+filter_var($s, FILTER_SANITIZE_STRING);
+";
+
+        assert_eq!(check(source, PhpVersion::Php74), [
+            "deprecated: the `each()` function is deprecated since PHP 7.2 on line 4",
+            "removed: the `mysql` extension is no longer supported as of PHP 7.0 on line 6",
+            "deprecated: declaring case-insensitive constants with `define()` is deprecated since PHP 7.3 on line 7",
+            "deprecated: passing a string to `assert()` is deprecated since PHP 7.2 on line 9",
+        ]);
+
+        assert_eq!(check(source, PhpVersion::Php83), [
+            "removed: the `each()` function is no longer supported as of PHP 8.0 on line 4",
+            "deprecated: the `utf8_encode()` function is deprecated since PHP 8.2 on line 5",
+            "deprecated: the `strftime()` function is deprecated since PHP 8.1 on line 5",
+            "removed: the `mysql` extension is no longer supported as of PHP 7.0 on line 6",
+            "removed: declaring case-insensitive constants with `define()` is no longer supported as of PHP 8.0 on line 7",
+            "removed: passing a string to `assert()` is no longer supported as of PHP 8.0 on line 9",
+            "deprecated: calling `get_class()` without arguments is deprecated since PHP 8.3 on line 10",
+            "deprecated: the `FILTER_SANITIZE_STRING` constant is deprecated since PHP 8.1 on line 11",
+        ]);
+
+        // A function of the same name that's imported from elsewhere isn't PHP's.
+        assert!(check("<?php use function Polyfill\\each; each($a);", PhpVersion::Php83).is_empty());
+    }
+
+    #[test]
+    fn declarations() {
+        let source = "<?php
+function __autoload($class) {}
+
+function f($a = 1, $b, ?int $c = null, $d, $e = 2, ...$f) {}
+
+class Legacy {
+    public function legacy() {}
+}
+
+class Modern implements \\Serializable {
+    public function serialize() {}
+    public function unserialize($data) {}
+}
+
+$g = fn (int $a = null, $b) => 1;
+";
+
+        assert_eq!(check(source, PhpVersion::Php83), [
+            "removed: the `__autoload()` function is no longer supported as of PHP 8.0 on line 2",
+            "deprecated: declaring an optional parameter before a required one is deprecated since PHP 8.0 on line 4",
+            "removed: declaring a constructor as a method named after its class is no longer supported as of PHP 8.0 on line 6",
+            "deprecated: implementing `Serializable` without `__serialize()` and `__unserialize()` is deprecated since PHP 8.1 on line 10",
+        ]);
+
+        assert!(check("<?php namespace App; class Legacy { public function legacy() {} }", PhpVersion::Php83).is_empty());
+    }
+}
@@ -0,0 +1,554 @@
+use std::collections::HashMap;
+
+use trunk_interpreter::{binary, constants, unary, Array, Key, Value};
+use trunk_lexer::Float;
+use trunk_parser::{
+    walk_expression_mut, walk_statement_mut, ArrayItem, Expression, ExpressionKind, InfixOp, Program, Statement, StatementKind, StringPart,
+    VisitMut,
+};
+
+use crate::names::Imports;
+use crate::NameKind;
+
+/// Works out the values of constant expressions, the kind that PHP allows as the values of
+/// constants and the defaults of parameters and properties: literals, operators, arrays, constants
+/// and `::class`.
+///
+/// An expression only has a value if evaluating it can't fail or warn, so `1 / 0` and `'a' + 1`
+/// don't have one. An unqualified constant in a namespace is taken to be the global one, unless the
+/// namespaced one is known.
+#[derive(Debug, Clone)]
+pub struct ConstantEvaluator {
+    /// Global constants, by their fully qualified names.
+    constants: HashMap<String, Value>,
+    /// Class constants, by the lowercase fully qualified name of their class and their name.
+    class_constants: HashMap<(String, String), Value>,
+}
+
+/// Where an expression is, which decides what the names in it refer to.
+#[derive(Default, Clone)]
+struct Scope {
+    imports: Imports,
+    /// The fully qualified name of the class that the expression is in, if it isn't in a trait.
+    class: Option<String>,
+}
+
+impl Default for ConstantEvaluator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConstantEvaluator {
+    /// An evaluator that knows PHP's built-in constants, such as `PHP_INT_MAX` and `PHP_EOL`.
+    pub fn new() -> Self {
+        Self {
+            constants: constants().into_iter().map(|(name, value)| (name.to_string(), value)).collect(),
+            class_constants: HashMap::new(),
+        }
+    }
+
+    /// An evaluator that also knows the constants that a program declares unconditionally: with
+    /// `const` or `define()` outside of any function or block, and in classes, interfaces and
+    /// enums. Constants that are declared more than once are left out, since which one applies
+    /// depends on what runs.
+    pub fn for_program(program: &Program) -> Self {
+        let mut declarations = Vec::new();
+        declare(program, &mut Scope::default(), &mut declarations);
+
+        let mut counts: HashMap<&Target, usize> = HashMap::new();
+        for (target, ..) in &declarations {
+            *counts.entry(target).or_default() += 1;
+        }
+
+        let mut pending: Vec<_> = declarations.iter().filter(|(target, ..)| counts[target] == 1).collect();
+        let mut evaluator = Self::new();
+
+        // Constants can refer to ones declared after them, so keep going until nothing changes.
+        loop {
+            let before = pending.len();
+
+            pending.retain(|(target, expression, scope)| match evaluator.value(expression, scope) {
+                Some(value) => {
+                    match target {
+                        Target::Constant(name) => evaluator.constants.insert(name.clone(), value),
+                        Target::ClassConstant(class, name) => evaluator.class_constants.insert((class.clone(), name.clone()), value),
+                    };
+
+                    false
+                },
+                None => true,
+            });
+
+            if pending.len() == before {
+                return evaluator;
+            }
+        }
+    }
+
+    /// Add a global constant, by its fully qualified name without a leading `\`.
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.constants.insert(name.to_string(), value);
+    }
+
+    /// Add a class constant, by the fully qualified name of its class without a leading `\`.
+    pub fn define_class_constant(&mut self, class: &str, name: &str, value: Value) {
+        self.class_constants.insert((class.to_ascii_lowercase(), name.to_string()), value);
+    }
+
+    /// The value of an expression, or `None` if it isn't constant, it refers to a constant that
+    /// isn't known, or it would fail or warn. Names are taken to be fully qualified.
+    pub fn evaluate(&self, expression: &Expression) -> Option<Value> {
+        self.value(expression, &Scope::default())
+    }
+
+    fn value(&self, expression: &Expression, scope: &Scope) -> Option<Value> {
+        match &expression.kind {
+            ExpressionKind::Int { i } => Some(Value::Int(*i)),
+            ExpressionKind::Float { f } => Some(Value::Float(f.0)),
+            ExpressionKind::Bool { value } => Some(Value::Bool(*value)),
+            ExpressionKind::Null => Some(Value::Null),
+            ExpressionKind::ConstantString { value } | ExpressionKind::Nowdoc { value, .. } => Some(Value::String(value.clone())),
+            ExpressionKind::InterpolatedString { parts } | ExpressionKind::Heredoc { parts, .. } => parts.iter()
+                .map(|part| match part {
+                    StringPart::Const(value) => Some(value.as_str()),
+                    StringPart::Expr(_) => None,
+                })
+                .collect::<Option<String>>()
+                .map(Value::String),
+            ExpressionKind::Array { items } => self.array(items, scope).map(Value::Array),
+            ExpressionKind::Identifier { name } => self.constant(name, scope),
+            ExpressionKind::ConstFetch { target, constant } => {
+                let ExpressionKind::Identifier { name: class } = &target.kind else { return None };
+
+                let class = match class.to_ascii_lowercase().as_str() {
+                    "self" => scope.class.clone()?,
+                    "static" | "parent" => return None,
+                    _ => scope.imports.expand(class, NameKind::Class).0,
+                };
+
+                match constant.name.to_ascii_lowercase().as_str() {
+                    "class" => Some(Value::String(class)),
+                    _ => self.class_constants.get(&(class.to_ascii_lowercase(), constant.name.clone())).cloned(),
+                }
+            },
+            ExpressionKind::Infix { op, .. } if op.is_assignment() => None,
+            ExpressionKind::Infix { lhs, op, rhs } => {
+                let lhs = self.value(lhs, scope)?;
+                let rhs = self.value(rhs, scope)?;
+
+                // Arrays become `"Array"` with a warning.
+                if *op == InfixOp::Concat && [&lhs, &rhs].iter().any(|value| matches!(value, Value::Array(_))) {
+                    return None;
+                }
+
+                match binary(op, &lhs, &rhs) {
+                    Ok((value, false)) => Some(value),
+                    _ => None,
+                }
+            },
+            ExpressionKind::Negate { value } | ExpressionKind::UnaryPlus { value } | ExpressionKind::BitwiseNot { value } => {
+                match unary(&expression.kind, &self.value(value, scope)?) {
+                    Ok((value, false)) => Some(value),
+                    _ => None,
+                }
+            },
+            ExpressionKind::BooleanNot { value } => Some(Value::Bool(! self.value(value, scope)?.to_bool())),
+            ExpressionKind::Ternary { condition, then, r#else } => {
+                let condition = self.value(condition, scope)?;
+
+                match (condition.to_bool(), then) {
+                    (true, Some(then)) => self.value(then, scope),
+                    (true, None) => Some(condition),
+                    (false, _) => self.value(r#else, scope),
+                }
+            },
+            ExpressionKind::Coalesce { lhs, rhs } => match self.value(lhs, scope)? {
+                Value::Null => self.value(rhs, scope),
+                value => Some(value),
+            },
+            ExpressionKind::ArrayIndex { array, index: Some(index) } => {
+                let Value::Array(array) = self.value(array, scope)? else { return None };
+
+                array.get(&key(&self.value(index, scope)?)?).cloned()
+            },
+            _ => None,
+        }
+    }
+
+    fn array(&self, items: &[ArrayItem], scope: &Scope) -> Option<Array> {
+        let mut array = Array::new();
+
+        for item in items {
+            if item.by_ref {
+                return None;
+            }
+
+            let value = self.value(&item.value, scope)?;
+
+            if item.unpack {
+                let Value::Array(unpacked) = value else { return None };
+
+                // Integer keys are renumbered, while string keys are kept.
+                for (key, value) in unpacked.iter() {
+                    match key {
+                        Key::Int(_) => { array.push(value.clone()); },
+                        Key::String(_) => array.insert(key.clone(), value.clone()),
+                    }
+                }
+
+                continue;
+            }
+
+            match &item.key {
+                Some(item_key) => array.insert(key(&self.value(item_key, scope)?)?, value),
+                None => { array.push(value); },
+            }
+        }
+
+        Some(array)
+    }
+
+    fn constant(&self, name: &str, scope: &Scope) -> Option<Value> {
+        let (name, fallback) = scope.imports.expand(name, NameKind::Constant);
+
+        if let Some(value) = self.constants.get(&name).or_else(|| self.constants.get(fallback.as_ref()?)) {
+            return Some(value.clone());
+        }
+
+        // `true`, `false` and `null` written as names, in any case, as long as they're global.
+        let global = fallback.as_deref().unwrap_or(name.as_str());
+        if global.contains('\\') {
+            return None;
+        }
+
+        match global.to_ascii_lowercase().as_str() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            "null" => Some(Value::Null),
+            _ => None,
+        }
+    }
+}
+
+/// Replace the constant expressions in a program with their values, using the constants that
+/// it declares, so that `60 * 60 * 24` becomes `86400` and `Foo::class` becomes `'App\Foo'`.
+/// Expressions that evaluate to arrays are left as they are unless they're made of other
+/// expressions, such as `A + [1]`, since an array that's written out is usually better left as
+/// written. Names of classes and functions are never taken for constants.
+pub fn fold_constants(mut program: Program) -> Program {
+    let mut folder = Folder { evaluator: ConstantEvaluator::for_program(&program), scope: Scope::default() };
+    folder.visit_program_mut(&mut program);
+
+    program
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum Target {
+    Constant(String),
+    /// The lowercase fully qualified name of the class, and the constant's name.
+    ClassConstant(String, String),
+}
+
+/// Find the constants that a block declares unconditionally, with the scope of each one's value.
+fn declare(block: &[Statement], scope: &mut Scope, declarations: &mut Vec<(Target, Expression, Scope)>) {
+    for statement in block {
+        match &statement.kind {
+            StatementKind::Namespace { name, body } => {
+                scope.imports.enter_namespace(name);
+
+                if ! body.is_empty() {
+                    declare(body, scope, declarations);
+                    scope.imports.enter_namespace("");
+                }
+            },
+            StatementKind::Use { uses, kind } => {
+                for r#use in uses {
+                    scope.imports.import(r#use, kind);
+                }
+            },
+            StatementKind::Const { constants, .. } => {
+                for constant in constants {
+                    let target = Target::Constant(scope.imports.qualify(&constant.name.name));
+                    declarations.push((target, constant.value.clone(), scope.clone()));
+                }
+            },
+            StatementKind::Expression { expr: Expression { kind: ExpressionKind::Call { target, args }, .. } } => {
+                let is_define = matches!(&target.kind, ExpressionKind::Identifier { name } if name.trim_start_matches('\\').eq_ignore_ascii_case("define"));
+
+                if let (true, [name, value]) = (is_define, args.as_slice()) {
+                    if let ExpressionKind::ConstantString { value: name } = &name.value.kind {
+                        let target = Target::Constant(name.trim_start_matches('\\').to_string());
+                        declarations.push((target, value.value.clone(), scope.clone()));
+                    }
+                }
+            },
+            StatementKind::Class { name, body, .. } | StatementKind::Interface { name, body, .. } | StatementKind::Enum { name, body, .. } => {
+                let class = scope.imports.qualify(&name.name);
+                let scope = Scope { imports: scope.imports.clone(), class: Some(class.clone()) };
+
+                for statement in body {
+                    if let StatementKind::Constant { name, value, .. } = &statement.kind {
+                        let target = Target::ClassConstant(class.to_ascii_lowercase(), name.name.clone());
+                        declarations.push((target, value.clone(), scope.clone()));
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+struct Folder {
+    evaluator: ConstantEvaluator,
+    scope: Scope,
+}
+
+impl VisitMut for Folder {
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        match &mut statement.kind {
+            StatementKind::Namespace { name, body } => {
+                self.scope.imports.enter_namespace(name);
+
+                if ! body.is_empty() {
+                    walk_statement_mut(self, statement);
+                    self.scope.imports.enter_namespace("");
+                }
+            },
+            StatementKind::Use { uses, kind } => {
+                for r#use in uses.iter() {
+                    self.scope.imports.import(r#use, kind);
+                }
+            },
+            StatementKind::Class { name, .. } | StatementKind::Interface { name, .. } | StatementKind::Enum { name, .. } => {
+                let class = Some(self.scope.imports.qualify(&name.name));
+                let outer = std::mem::replace(&mut self.scope.class, class);
+                walk_statement_mut(self, statement);
+                self.scope.class = outer;
+            },
+            // `self` in a trait is the class that uses it.
+            StatementKind::Trait { .. } => {
+                let outer = self.scope.class.take();
+                walk_statement_mut(self, statement);
+                self.scope.class = outer;
+            },
+            _ => walk_statement_mut(self, statement),
+        }
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        let folds = match &expression.kind {
+            ExpressionKind::Infix { op, .. } => ! op.is_assignment(),
+            ExpressionKind::Identifier { .. } | ExpressionKind::ConstFetch { .. } | ExpressionKind::Negate { .. } |
+            ExpressionKind::UnaryPlus { .. } | ExpressionKind::BitwiseNot { .. } | ExpressionKind::BooleanNot { .. } |
+            ExpressionKind::Ternary { .. } | ExpressionKind::Coalesce { .. } | ExpressionKind::ArrayIndex { .. } => true,
+            _ => false,
+        };
+
+        if folds {
+            let literal = self.evaluator.value(expression, &self.scope)
+                .filter(|value| ! matches!(value, Value::Array(_)) || matches!(expression.kind, ExpressionKind::Infix { .. }))
+                .and_then(|value| literal(&value));
+
+            if let Some(literal) = literal {
+                expression.kind = literal;
+                return;
+            }
+        }
+
+        match &mut expression.kind {
+            ExpressionKind::Call { target, args } | ExpressionKind::New { target, args } | ExpressionKind::StaticMethodCall { target, args, .. } => {
+                self.visit_name_mut(target);
+                args.iter_mut().for_each(|arg| self.visit_expression_mut(&mut arg.value));
+            },
+            ExpressionKind::MethodCall { target, method, args } | ExpressionKind::NullsafeMethodCall { target, method, args } => {
+                self.visit_expression_mut(target);
+                self.visit_name_mut(method);
+                args.iter_mut().for_each(|arg| self.visit_expression_mut(&mut arg.value));
+            },
+            ExpressionKind::DynamicStaticMethodCall { target, method, args } => {
+                self.visit_name_mut(target);
+                self.visit_expression_mut(method);
+                args.iter_mut().for_each(|arg| self.visit_expression_mut(&mut arg.value));
+            },
+            ExpressionKind::PropertyFetch { target, property } | ExpressionKind::NullsafePropertyFetch { target, property } => {
+                self.visit_expression_mut(target);
+                self.visit_name_mut(property);
+            },
+            ExpressionKind::StaticPropertyFetch { target, property } | ExpressionKind::DynamicConstFetch { target, constant: property } => {
+                self.visit_name_mut(target);
+                self.visit_expression_mut(property);
+            },
+            ExpressionKind::ConstFetch { target, .. } => self.visit_name_mut(target),
+            ExpressionKind::Instanceof { lhs, rhs } => {
+                self.visit_expression_mut(lhs);
+                self.visit_name_mut(rhs);
+            },
+            _ => walk_expression_mut(self, expression),
+        }
+    }
+}
+
+impl Folder {
+    /// Visit an expression that's a class, function, method or property name when it's an
+    /// identifier, as in `new Foo` and `$a->foo`, rather than a constant.
+    fn visit_name_mut(&mut self, expression: &mut Expression) {
+        if ! matches!(expression.kind, ExpressionKind::Identifier { .. }) {
+            self.visit_expression_mut(expression);
+        }
+    }
+}
+
+/// A value as an array key, leaving out floats with a fraction, which PHP warns about.
+fn key(value: &Value) -> Option<Key> {
+    match value {
+        Value::Float(f) if f.fract() != 0.0 => None,
+        value => value.to_key(),
+    }
+}
+
+/// The literal that gives a value, if there is one. Infinity, NaN and the smallest integer can only
+/// be written as expressions.
+fn literal(value: &Value) -> Option<ExpressionKind> {
+    Some(match value {
+        Value::Null => ExpressionKind::Null,
+        Value::Bool(value) => ExpressionKind::Bool { value: *value },
+        Value::Int(i) if *i == i64::MIN => return None,
+        Value::Int(i) => ExpressionKind::Int { i: *i },
+        Value::Float(f) if ! f.is_finite() => return None,
+        Value::Float(f) => ExpressionKind::Float { f: Float(*f) },
+        Value::String(value) => ExpressionKind::ConstantString { value: value.clone() },
+        Value::Array(array) => {
+            // Keys are left out while they count up from 0, as they would be in a list.
+            let mut listed = true;
+            let mut items = Vec::new();
+
+            for (i, (key, value)) in array.iter().enumerate() {
+                listed &= *key == Key::Int(i as i64);
+
+                let key = match (listed, key) {
+                    (true, _) => None,
+                    (false, Key::Int(i)) => Some(ExpressionKind::Int { i: *i }.into()),
+                    (false, Key::String(key)) => Some(ExpressionKind::ConstantString { value: key.clone() }.into()),
+                };
+
+                items.push(ArrayItem { key, value: literal(value)?.into(), unpack: false, by_ref: false });
+            }
+
+            ExpressionKind::Array { items }
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_interpreter::Value;
+    use trunk_parser::{parse, ExpressionKind, StatementKind};
+    use trunk_printer::print;
+
+    use super::{fold_constants, ConstantEvaluator};
+
+    fn folded(source: &str) -> String {
+        print(&fold_constants(parse(source).unwrap()))
+    }
+
+    #[test]
+    fn evaluate() {
+        let program = parse("<?php [1 + 2 * 3, 'a' . 'b', PHP_INT_MAX + 1, \\Foo\\Bar::class, [1, 2][1], 7 <=> 3, -'2', 1 / 0, 'a' + 1, $a, [...[1, 2], 'k' => ~1]];").unwrap();
+        let StatementKind::Expression { expr } = &program[0].kind else { unreachable!() };
+        let ExpressionKind::Array { items } = &expr.kind else { unreachable!() };
+
+        let mut evaluator = ConstantEvaluator::new();
+        let values: Vec<String> = items.iter().map(|item| format!("{:?}", evaluator.evaluate(&item.value))).collect();
+
+        assert_eq!(values[..7], [
+            "Some(Int(7))", "Some(String(\"ab\"))", "Some(Float(9.223372036854776e18))", "Some(String(\"Foo\\\\Bar\"))", "Some(Int(2))",
+            "Some(Int(1))", "Some(Int(-2))",
+        ]);
+        assert_eq!(values[7..10], ["None", "None", "None"]);
+        assert!(matches!(evaluator.evaluate(&items[10].value), Some(Value::Array(array)) if array.len() == 3));
+
+        let name = parse("<?php VERSION;").unwrap();
+        let StatementKind::Expression { expr } = &name[0].kind else { unreachable!() };
+        assert!(evaluator.evaluate(expr).is_none());
+        evaluator.define("VERSION", Value::Int(3));
+        assert!(matches!(evaluator.evaluate(expr), Some(Value::Int(3))));
+    }
+
+    #[test]
+    fn folding() {
+        assert_eq!(folded("<?php
+namespace App;
+
+use Lib\\Clock;
+
+const DAY = 60 * 60 * HOURS;
+const HOURS = 24;
+define('GREETING', 'Hello' . ', ' . 'world');
+
+interface Sizes {
+    const SMALL = 1 << 2;
+}
+
+class Cache {
+    const TTL = DAY * 7;
+    const KEYS = ['a' => Sizes::SMALL, 'b' => self::TTL];
+
+    public function ttl($days = self::TTL / DAY, $clock = Clock::class) {
+        return [self::class, static::TTL, self::KEYS['b'] + 1, GREETING, PHP_INT_SIZE * 8];
+    }
+}
+
+echo HOURS(), new HOURS(), HOURS::TTL, $a instanceof HOURS, $a->HOURS, $a->HOURS(), $a + 1 * 2, 'x' . -(1 / 0), self::KEYS;
+echo 1 + TRUE, 1 + \\True, 1 + \\Lib\\true, 1 + namespace\\true;
+"), "\
+<?php
+
+namespace App;
+
+use Lib\\Clock;
+const DAY = 86400;
+const HOURS = 24;
+define('GREETING', 'Hello, world');
+
+interface Sizes
+{
+    const SMALL = 4;
+}
+
+class Cache
+{
+    const TTL = 604800;
+    const KEYS = ['a' => 4, 'b' => 604800];
+
+    public function ttl($days = 7, $clock = 'Lib\\\\Clock')
+    {
+        return ['App\\\\Cache', static::TTL, 604801, 'Hello, world', 64];
+    }
+}
+
+echo HOURS(), new HOURS(), HOURS::TTL, $a instanceof HOURS, $a->HOURS, $a->HOURS(), $a + 2, 'x' . -(1 / 0), self::KEYS;
+echo 2, 2, 1 + \\Lib\\true, 1 + namespace\\true;
+");
+    }
+
+    #[test]
+    fn conflicts() {
+        // A constant that depends on what runs isn't known, nor is a constant declared in a block.
+        assert_eq!(folded("<?php
+const A = 1;
+const A = 2;
+if ($debug) { const B = 3; }
+echo A, B, [1, 2 => 3] + [4], PHP_INT_MIN, [A][0];
+"), "\
+<?php
+
+const A = 1;
+const A = 2;
+if ($debug) {
+    const B = 3;
+}
+echo A, B, [1, 2 => 3], PHP_INT_MIN, [A][0];
+");
+    }
+}
@@ -0,0 +1,316 @@
+use trunk_parser::{
+    walk_expression, walk_program, walk_statement, Block, Diagnostic, Expression, ExpressionKind, MethodFlag, Program, PropertyFlag, Severity,
+    Span, Statement, StatementKind, Visitor,
+};
+
+/// Find code that can never run or never be used: statements that follow a `return`, `throw`,
+/// `exit`, `break` or `continue` in the same block, conditions that are literals which are always
+/// false, and private methods and properties that nothing in their class refers to.
+///
+/// Classes that use traits aren't checked for unused members, since the trait's methods can use
+/// them, and neither are classes that call methods or fetch properties by dynamic names.
+pub fn find_dead_code(program: &Program) -> Vec<Diagnostic> {
+    let mut finder = DeadCode::default();
+    finder.block(program);
+    walk_program(&mut finder, program);
+
+    let mut diagnostics = finder.diagnostics;
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.map(|span| span.start));
+    diagnostics
+}
+
+#[derive(Default)]
+struct DeadCode {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl DeadCode {
+    fn warn(&mut self, code: &'static str, message: impl Into<String>, span: Span) {
+        self.diagnostics.push(Diagnostic::new(Severity::Warning, code, message, Some(span)));
+    }
+
+    /// Report the first statement in a block that can't be reached because of a statement before
+    /// it. Declarations of functions and classes are skipped over, since at the top level of a file
+    /// they're declared before any code runs, whether or not they're reached.
+    fn block(&mut self, block: &[Statement]) {
+        let Some((index, terminator)) = block.iter().enumerate().find_map(|(index, statement)| Some((index, terminator(statement)?))) else {
+            return;
+        };
+
+        if let Some(unreachable) = block[index + 1..].iter().find(|statement| is_executable(statement)) {
+            self.warn("unreachable-code", format!("unreachable code after `{}`", terminator), unreachable.span);
+        }
+    }
+
+    fn condition(&mut self, condition: &Expression) {
+        if truthiness(condition) == Some(false) {
+            self.warn("always-false-condition", "this condition is always false", condition.span);
+        }
+    }
+
+    fn class(&mut self, body: &Block) {
+        let mut members = Members::default();
+        members.visit_program(body);
+
+        if members.dynamic || body.iter().any(|member| matches!(member.kind, StatementKind::TraitUse { .. })) {
+            return;
+        }
+
+        let is_used_method = |name: &str| members.methods.iter().any(|method| method.eq_ignore_ascii_case(name)) || members.strings.iter().any(|string| string == name);
+
+        for member in body {
+            match &member.kind {
+                StatementKind::Method { name, flags, .. } if flags.contains(&MethodFlag::Private) && !name.name.starts_with("__") && !is_used_method(&name.name) => {
+                    self.warn("unused-private-method", format!("private method `{}` is never used", name.name), name.span);
+                },
+                StatementKind::Property { var, flags, .. } if flags.contains(&PropertyFlag::Private) && !members.properties.contains(var) => {
+                    self.warn("unused-private-property", format!("private property `${}` is never used", var), member.span);
+                },
+                // Private properties can also be declared by promoting constructor parameters.
+                StatementKind::Method { params, .. } => {
+                    for param in params.iter().filter(|param| param.flags.contains(&PropertyFlag::Private)) {
+                        if let Some(var) = param.variable_name().filter(|var| !members.properties.iter().any(|property| property == var)) {
+                            self.warn("unused-private-property", format!("private property `${}` is never used", var), param.span);
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+impl Visitor for DeadCode {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::Function { body, .. } | StatementKind::Method { body, .. } | StatementKind::Namespace { body, .. } |
+            StatementKind::Foreach { body, .. } => self.block(body),
+            StatementKind::While { condition, body } => {
+                self.condition(condition);
+                self.block(body);
+            },
+            StatementKind::For { condition, then, .. } => {
+                if let Some(condition) = condition {
+                    self.condition(condition);
+                }
+
+                self.block(then);
+            },
+            StatementKind::If { condition, then, else_ifs, r#else } => {
+                self.condition(condition);
+                self.block(then);
+
+                for else_if in else_ifs {
+                    self.condition(&else_if.condition);
+                    self.block(&else_if.body);
+                }
+
+                if let Some(r#else) = r#else {
+                    self.block(r#else);
+                }
+            },
+            StatementKind::Switch { cases, .. } => {
+                for case in cases {
+                    self.block(&case.body);
+                }
+            },
+            StatementKind::Try { body, catches, finally } => {
+                self.block(body);
+
+                for catch in catches {
+                    self.block(&catch.body);
+                }
+
+                if let Some(finally) = finally {
+                    self.block(finally);
+                }
+            },
+            StatementKind::Class { body, .. } | StatementKind::Enum { body, .. } => self.class(body),
+            _ => {},
+        }
+
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::Closure { body, .. } => self.block(body),
+            ExpressionKind::AnonymousClass { body, .. } => self.class(body),
+            ExpressionKind::Ternary { condition, .. } => self.condition(condition),
+            _ => {},
+        }
+
+        walk_expression(self, expression);
+    }
+}
+
+/// What a statement ends its block with, if nothing after it can run.
+fn terminator(statement: &Statement) -> Option<&'static str> {
+    match &statement.kind {
+        StatementKind::Return { .. } => Some("return"),
+        StatementKind::Break { .. } => Some("break"),
+        StatementKind::Continue { .. } => Some("continue"),
+        StatementKind::Expression { expr } => match expr.kind {
+            ExpressionKind::Throw { .. } => Some("throw"),
+            ExpressionKind::Exit { .. } => Some("exit"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn is_executable(statement: &Statement) -> bool {
+    !matches!(statement.kind,
+        StatementKind::Function { .. } | StatementKind::Class { .. } | StatementKind::Interface { .. } | StatementKind::Trait { .. } |
+        StatementKind::Enum { .. } | StatementKind::Comment { .. } | StatementKind::Noop | StatementKind::HaltCompiler { .. })
+}
+
+/// Whether a literal is truthy or falsy, if the expression is one.
+fn truthiness(expression: &Expression) -> Option<bool> {
+    match &expression.kind {
+        ExpressionKind::Bool { value } => Some(*value),
+        ExpressionKind::Null => Some(false),
+        ExpressionKind::Int { i } => Some(*i != 0),
+        ExpressionKind::Float { f } => Some(f.0 != 0.0),
+        ExpressionKind::ConstantString { value } => Some(!value.is_empty() && value != "0"),
+        ExpressionKind::Array { items } if items.is_empty() => Some(false),
+        ExpressionKind::BooleanNot { value } => truthiness(value).map(|truthy| !truthy),
+        _ => None,
+    }
+}
+
+/// The names of the methods and properties that a class refers to, and the strings in it, which
+/// can name methods in callables such as `[$this, 'method']`.
+#[derive(Default)]
+struct Members {
+    methods: Vec<String>,
+    properties: Vec<String>,
+    strings: Vec<String>,
+    /// Whether the class calls a method or fetches a property by a name that's only known at runtime.
+    dynamic: bool,
+}
+
+impl Members {
+    fn member(&mut self, name: &Expression, is_method: bool) {
+        match &name.kind {
+            ExpressionKind::Identifier { name } if is_method => self.methods.push(name.to_string()),
+            ExpressionKind::Identifier { name } => self.properties.push(name.to_string()),
+            _ => self.dynamic = true,
+        }
+    }
+}
+
+impl Visitor for Members {
+    fn visit_expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::MethodCall { method, .. } | ExpressionKind::NullsafeMethodCall { method, .. } => self.member(method, true),
+            ExpressionKind::StaticMethodCall { method, .. } => self.methods.push(method.name.to_string()),
+            ExpressionKind::PropertyFetch { property, .. } | ExpressionKind::NullsafePropertyFetch { property, .. } => self.member(property, false),
+            ExpressionKind::StaticPropertyFetch { property, .. } => match &property.kind {
+                ExpressionKind::Variable { name } => self.properties.push(name.to_string()),
+                _ => self.dynamic = true,
+            },
+            ExpressionKind::ConstantString { value } => self.strings.push(value.to_string()),
+            _ => {},
+        }
+
+        walk_expression(self, expression);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_parser::parse;
+
+    use super::find_dead_code;
+
+    fn check(source: &str) -> Vec<String> {
+        find_dead_code(&parse(source).unwrap())
+            .iter()
+            .map(|diagnostic| format!("{}: {} on line {}", diagnostic.code, diagnostic.message, diagnostic.span.unwrap().line))
+            .collect()
+    }
+
+    #[test]
+    fn unreachable_code() {
+        assert_eq!(check("<?php
+function f($a) {
+    foreach ($a as $b) {
+        continue;
+        echo $b;
+    }
+
+    switch ($a) {
+        case 1:
+            throw new Exception();
+        default:
+            exit(1);
+            // Only the first unreachable statement is reported.
+            echo 1;
+            echo 2;
+    }
+
+    return $a;
+
+    function g() {}
+    $c = fn () => 1;
+}
+"), [
+            "unreachable-code: unreachable code after `continue` on line 5",
+            "unreachable-code: unreachable code after `exit` on line 14",
+            "unreachable-code: unreachable code after `return` on line 21",
+        ]);
+    }
+
+    #[test]
+    fn always_false_conditions() {
+        assert_eq!(check("<?php
+if (false) {} elseif ('0') {} elseif (!1) {} elseif ('false') {}
+while (null) {}
+for (; []; ) {}
+echo 0 ? 'a' : 'b';
+if (true) {}
+"), [
+            "always-false-condition: this condition is always false on line 2",
+            "always-false-condition: this condition is always false on line 2",
+            "always-false-condition: this condition is always false on line 2",
+            "always-false-condition: this condition is always false on line 3",
+            "always-false-condition: this condition is always false on line 4",
+            "always-false-condition: this condition is always false on line 5",
+        ]);
+    }
+
+    #[test]
+    fn unused_private_members() {
+        assert_eq!(check("<?php
+class A {
+    private $used;
+    private static $counter;
+    private $unused;
+
+    public function __construct(private $promoted, private $ignored) {}
+
+    public function run() {
+        self::$counter++;
+        array_map([$this, 'callback'], []);
+        return $this->helper() + $this->used + $this->promoted;
+    }
+
+    private function helper() {}
+    private function callback() {}
+    private function unusedMethod() {}
+    private function __clone() {}
+}
+
+class B {
+    use T;
+
+    private function maybeUsedByTrait() {}
+}
+"), [
+            "unused-private-property: private property `$unused` is never used on line 5",
+            "unused-private-property: private property `$ignored` is never used on line 7",
+            "unused-private-method: private method `unusedMethod` is never used on line 17",
+        ]);
+    }
+}
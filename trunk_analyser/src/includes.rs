@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use trunk_parser::{
+    parse_files, walk_expression, Error, Expression, ExpressionKind, InfixOp, MagicConst, Program, Span, StringPart, Visitor,
+};
+
+use crate::SourceFile;
+
+/// A script and the files that it includes, found by following `include` and `require`
+/// expressions whose paths can be worked out without running any code, and linked into a graph
+/// for analysing the script as a whole.
+#[derive(Debug)]
+pub struct Script {
+    /// The entry file first, followed by the files that it includes in the order they were found.
+    /// Paths are canonical, so a file included in different ways only appears once.
+    pub files: Vec<SourceFile>,
+    pub includes: Vec<Include>,
+    /// Includes whose file couldn't be worked out or found.
+    pub unresolved: Vec<UnresolvedInclude>,
+    /// Included files that couldn't be read or parsed.
+    pub errors: Vec<Error>,
+}
+
+/// An edge of the graph, from an `include` or `require` to the file that it loads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Include {
+    /// The index in `Script::files` of the file that includes the other.
+    pub from: usize,
+    /// The index in `Script::files` of the included file.
+    pub to: usize,
+    pub kind: IncludeKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncludeKind {
+    Include,
+    IncludeOnce,
+    Require,
+    RequireOnce,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedInclude {
+    /// The index in `Script::files` of the file with the include.
+    pub file: usize,
+    /// The path that was worked out but doesn't exist, or `None` if it depends on something that's
+    /// only known at runtime, such as a variable.
+    pub path: Option<String>,
+    pub kind: IncludeKind,
+    pub span: Span,
+}
+
+impl Script {
+    /// Parse an entry file and every file that it includes, directly or through other files. Paths
+    /// can be made of string literals, `__DIR__`, `__FILE__`, `DIRECTORY_SEPARATOR`, `dirname()`
+    /// and concatenation.
+    ///
+    /// Paths are found the way PHP finds them, taking the entry file's directory as the working
+    /// directory: absolute paths as they are, paths starting with `./` or `../` from the working
+    /// directory, and others from each of `include_paths` in turn, then the including file's
+    /// directory, then the working directory. Each round of newly found files is parsed in
+    /// parallel.
+    ///
+    /// Only the entry file failing to read or parse is an error. Other files that fail are left out
+    /// and kept in `errors`.
+    pub fn load(entry: impl AsRef<Path>, include_paths: &[PathBuf]) -> Result<Self, Error> {
+        let entry = entry.as_ref();
+        let entry = entry.canonicalize().map_err(|error| Error::Io(error).in_file(entry))?;
+        let working_directory = entry.parent().map(Path::to_path_buf).unwrap_or_default();
+        let include_paths: Vec<PathBuf> = include_paths.iter().map(|path| working_directory.join(path)).collect();
+
+        let mut script = Self { files: Vec::new(), includes: Vec::new(), unresolved: Vec::new(), errors: Vec::new() };
+        // Every file that's been found, with its index in `files` once it's parsed.
+        let mut found: HashMap<PathBuf, Option<usize>> = HashMap::from([(entry.clone(), None)]);
+        // Includes that resolved to a file, waiting for it to be parsed.
+        let mut edges = Vec::new();
+        let mut batch = vec![entry];
+
+        while ! batch.is_empty() {
+            let mut next = Vec::new();
+
+            for (path, result) in parse_files(batch) {
+                let program = match result {
+                    Ok(file) => file.program,
+                    Err(error) if script.files.is_empty() => return Err(error),
+                    Err(error) => {
+                        script.errors.push(error);
+                        continue;
+                    },
+                };
+
+                let index = script.files.len();
+                found.insert(path.clone(), Some(index));
+
+                for (kind, expression, span) in includes(&program) {
+                    let resolved = evaluate(&expression, &path)
+                        .ok_or(None)
+                        .and_then(|target| resolve(&target, &path, &working_directory, &include_paths).ok_or(Some(target)));
+
+                    match resolved {
+                        Ok(target) => {
+                            if ! found.contains_key(&target) {
+                                found.insert(target.clone(), None);
+                                next.push(target.clone());
+                            }
+
+                            edges.push((index, target, kind, span));
+                        },
+                        Err(path) => script.unresolved.push(UnresolvedInclude { file: index, path, kind, span }),
+                    }
+                }
+
+                script.files.push(SourceFile { path, program });
+            }
+
+            batch = next;
+        }
+
+        // Includes of files that failed to parse are left out, since they're in `errors`.
+        script.includes = edges.into_iter()
+            .filter_map(|(from, target, kind, span)| Some(Include { from, to: found[&target]?, kind, span }))
+            .collect();
+
+        Ok(script)
+    }
+
+    /// The index in `files` of a file, by its path.
+    pub fn file(&self, path: impl AsRef<Path>) -> Option<usize> {
+        let path = path.as_ref().canonicalize().ok()?;
+
+        self.files.iter().position(|file| file.path == path)
+    }
+
+    /// The includes in a file, in the order that they appear.
+    pub fn includes_of(&self, file: usize) -> impl Iterator<Item = &Include> {
+        self.includes.iter().filter(move |include| include.from == file)
+    }
+
+    /// The includes of a file from other files.
+    pub fn included_by(&self, file: usize) -> impl Iterator<Item = &Include> {
+        self.includes.iter().filter(move |include| include.to == file)
+    }
+
+    /// The files in the order that PHP would first load them, if every include ran where it
+    /// appears: depth first from the entry file, with each file only loaded once.
+    pub fn order(&self) -> Vec<usize> {
+        let mut order = Vec::new();
+
+        if ! self.files.is_empty() {
+            self.visit(0, &mut order);
+        }
+
+        order
+    }
+
+    fn visit(&self, file: usize, order: &mut Vec<usize>) {
+        if order.contains(&file) {
+            return;
+        }
+
+        order.push(file);
+
+        for include in self.includes_of(file) {
+            self.visit(include.to, order);
+        }
+    }
+}
+
+/// The includes in a program, anywhere that they appear, with the expression for each one's path.
+fn includes(program: &Program) -> Vec<(IncludeKind, Expression, Span)> {
+    #[derive(Default)]
+    struct Includes {
+        found: Vec<(IncludeKind, Expression, Span)>,
+    }
+
+    impl Visitor for Includes {
+        fn visit_expression(&mut self, expression: &Expression) {
+            let include = match &expression.kind {
+                ExpressionKind::Include { path } => Some((IncludeKind::Include, path)),
+                ExpressionKind::IncludeOnce { path } => Some((IncludeKind::IncludeOnce, path)),
+                ExpressionKind::Require { path } => Some((IncludeKind::Require, path)),
+                ExpressionKind::RequireOnce { path } => Some((IncludeKind::RequireOnce, path)),
+                _ => None,
+            };
+
+            if let Some((kind, path)) = include {
+                self.found.push((kind, path.as_ref().clone(), expression.span));
+            }
+
+            walk_expression(self, expression);
+        }
+    }
+
+    let mut includes = Includes::default();
+    includes.visit_program(program);
+    includes.found
+}
+
+/// Work out the value of a path expression in a file, if it doesn't depend on anything that's
+/// only known at runtime.
+fn evaluate(expression: &Expression, file: &Path) -> Option<String> {
+    match &expression.kind {
+        ExpressionKind::ConstantString { value } => Some(value.clone()),
+        ExpressionKind::InterpolatedString { parts } => parts.iter().map(|part| match part {
+            StringPart::Const(value) => Some(value.as_str()),
+            StringPart::Expr(_) => None,
+        }).collect(),
+        ExpressionKind::MagicConst { constant: MagicConst::Dir } => Some(file.parent()?.to_string_lossy().into_owned()),
+        ExpressionKind::MagicConst { constant: MagicConst::File } => Some(file.to_string_lossy().into_owned()),
+        ExpressionKind::Identifier { name } if name.trim_start_matches('\\') == "DIRECTORY_SEPARATOR" => Some(std::path::MAIN_SEPARATOR.to_string()),
+        ExpressionKind::Infix { lhs, op: InfixOp::Concat, rhs } => Some(evaluate(lhs, file)? + &evaluate(rhs, file)?),
+        ExpressionKind::Call { target, args } if matches!(&target.kind, ExpressionKind::Identifier { name } if name.trim_start_matches('\\').eq_ignore_ascii_case("dirname")) => {
+            let mut path = PathBuf::from(evaluate(&args.first()?.value, file)?);
+            let levels = match args.get(1).map(|arg| &arg.value.kind) {
+                None => 1,
+                Some(ExpressionKind::Int { i }) if *i >= 1 => *i,
+                Some(_) => return None,
+            };
+
+            for _ in 0..levels {
+                path = path.parent()?.to_path_buf();
+            }
+
+            Some(path.to_string_lossy().into_owned())
+        },
+        _ => None,
+    }
+}
+
+/// Find the file that an include of `path` from `file` loads, as a canonical path.
+fn resolve(path: &str, file: &Path, working_directory: &Path, include_paths: &[PathBuf]) -> Option<PathBuf> {
+    let relative = Path::new(path);
+
+    let candidates: Vec<PathBuf> = if relative.is_absolute() {
+        vec![relative.to_path_buf()]
+    } else if path.starts_with("./") || path.starts_with("../") {
+        vec![working_directory.join(relative)]
+    } else {
+        include_paths.iter()
+            .map(PathBuf::as_path)
+            .chain(file.parent())
+            .chain([working_directory])
+            .map(|directory| directory.join(relative))
+            .collect()
+    };
+
+    candidates.into_iter().find(|candidate| candidate.is_file())?.canonicalize().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{IncludeKind, Script};
+
+    #[test]
+    fn load() {
+        let dir = std::env::temp_dir().join(format!("trunk_includes_{}", std::process::id()));
+        fs::create_dir_all(dir.join("app/lib")).unwrap();
+        fs::create_dir_all(dir.join("shared")).unwrap();
+
+        fs::write(dir.join("app/index.php"), "<?php
+            require_once __DIR__ . '/lib/a.php';
+            $config = include 'config.php';
+            function load() { require_once dirname(__DIR__) . DIRECTORY_SEPARATOR . \"shared/b.php\"; }
+            include $page;
+            require 'missing.php';
+            include './lib/broken.php';
+        ").unwrap();
+        fs::write(dir.join("app/lib/a.php"), "<?php require_once __DIR__ . '/../index.php'; include 'c.php';").unwrap();
+        fs::write(dir.join("app/lib/c.php"), "<?php echo 'c';").unwrap();
+        fs::write(dir.join("app/lib/broken.php"), "<?php class {").unwrap();
+        fs::write(dir.join("shared/config.php"), "<?php return [];").unwrap();
+        fs::write(dir.join("shared/b.php"), "<?php include dirname(__FILE__, 2) . '/shared/config.php';").unwrap();
+
+        let script = Script::load(dir.join("app/index.php"), &["../shared".into()]);
+        let missing = Script::load(dir.join("app/missing.php"), &[]);
+        let dir = dir.canonicalize().unwrap();
+        let file = |path: &str| script.as_ref().unwrap().file(dir.join(path)).unwrap();
+        let (index, a, c, config, b) = (file("app/index.php"), file("app/lib/a.php"), file("app/lib/c.php"), file("shared/config.php"), file("shared/b.php"));
+        fs::remove_dir_all(&dir).unwrap();
+        let script = script.unwrap();
+
+        assert_eq!(index, 0);
+        assert_eq!(script.files.len(), 5);
+
+        let edges: Vec<_> = script.includes_of(index).map(|include| (include.to, include.kind)).collect();
+        assert_eq!(edges, [(a, IncludeKind::RequireOnce), (config, IncludeKind::Include), (b, IncludeKind::RequireOnce)]);
+        assert_eq!(script.includes_of(a).map(|include| include.to).collect::<Vec<_>>(), [index, c]);
+        assert_eq!(script.included_by(config).map(|include| include.from).collect::<Vec<_>>(), [index, b]);
+
+        let unresolved: Vec<_> = script.unresolved.iter().map(|include| include.path.as_deref()).collect();
+        assert_eq!(unresolved, [None, Some("missing.php")]);
+        assert_eq!(script.unresolved[0].span.line, 5);
+
+        assert_eq!(script.errors.len(), 1);
+        assert_eq!(script.errors[0].path(), Some(dir.join("app/lib/broken.php").as_path()));
+
+        assert_eq!(script.order(), [index, a, c, config, b]);
+
+        assert!(missing.is_err());
+    }
+}
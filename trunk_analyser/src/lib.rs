@@ -0,0 +1,21 @@
+mod compat;
+mod constants;
+mod dead_code;
+mod includes;
+mod lower;
+mod metrics;
+mod names;
+mod project;
+mod scope;
+mod types;
+
+pub use compat::check_compatibility;
+pub use constants::{fold_constants, ConstantEvaluator};
+pub use dead_code::find_dead_code;
+pub use includes::{Include, IncludeKind, Script, UnresolvedInclude};
+pub use lower::lower;
+pub use metrics::{measure, Metrics, UnitKind};
+pub use names::{resolve_names, Declaration, NameKind, Reference, SymbolTable};
+pub use project::{Project, ProjectError, SourceFile, Symbol};
+pub use scope::check_scopes;
+pub use types::{infer_types, InferredType, NodeId, TypeTable};
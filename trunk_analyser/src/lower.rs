@@ -0,0 +1,422 @@
+use trunk_parser::{
+    walk_expression, walk_expression_mut, walk_statement_mut, Arg, ArrayItem, Block, ClosureUse, Expression, ExpressionKind, InfixOp,
+    MatchArm, PhpVersion, Program, Statement, StatementKind, VisitMut, Visitor,
+};
+
+use crate::scope::SUPERGLOBALS;
+
+/// Rewrite syntax that's newer than the `target` version of PHP into older equivalents, so that the
+/// program can be printed for it:
+///
+/// * Promoted constructor parameters (8.0) become properties that the constructor assigns.
+/// * `match` (8.0) becomes a chain of ternaries, which throws an `Error` for an unhandled value.
+/// * `throw` used as an expression (8.0) becomes a closure that throws, called straight away.
+/// * Arrow functions (7.4) become closures that `use` the variables they read.
+///
+/// A `match` whose subject could have side effects stores it in a `$__match` variable, numbered to
+/// keep nested ones apart, so that it's only evaluated once. Other newer syntax is left as it is,
+/// so the result should still be parsed for the target to check that it's supported.
+pub fn lower(program: Program, target: PhpVersion) -> Program {
+    let mut program = program;
+
+    if target < PhpVersion::Php80 {
+        program = PromotedProperties { typed: target >= PhpVersion::Php74 }.transform(program);
+        program = Matches::default().transform(program);
+        program = Throws.transform(program);
+    }
+
+    if target < PhpVersion::Php74 {
+        program = ArrowFunctions.transform(program);
+    }
+
+    program
+}
+
+/// Moves promoted constructor parameters into properties, untyped before typed properties existed.
+struct PromotedProperties {
+    typed: bool,
+}
+
+impl PromotedProperties {
+    fn promote(&self, body: &mut Block) {
+        let Some(index) = body.iter().position(|statement| matches!(&statement.kind, StatementKind::Method { name, .. } if name.name.eq_ignore_ascii_case("__construct"))) else {
+            return;
+        };
+
+        let StatementKind::Method { params, body: constructor, .. } = &mut body[index].kind else {
+            return;
+        };
+
+        let mut properties = Vec::new();
+        let mut assignments = Vec::new();
+
+        for param in params.iter_mut().filter(|param| ! param.flags.is_empty()) {
+            let ExpressionKind::Variable { name } = &param.name.kind else {
+                continue;
+            };
+
+            properties.push(Statement::from(StatementKind::Property {
+                var: name.clone(),
+                value: None,
+                r#type: if self.typed { param.r#type.clone() } else { None },
+                flags: std::mem::take(&mut param.flags),
+                hooks: vec![],
+                attributes: param.attributes.clone(),
+                doc_comment: None,
+            }));
+
+            let var = Box::new(ExpressionKind::PropertyFetch {
+                target: Box::new(variable("this")),
+                property: Box::new(ExpressionKind::Identifier { name: name.clone() }.into()),
+            }.into());
+            let value = Box::new(variable(name));
+
+            assignments.push(Statement::from(StatementKind::Expression {
+                expr: if param.by_ref { ExpressionKind::AssignRef { var, value } } else { ExpressionKind::Assign { var, value } }.into(),
+            }));
+        }
+
+        constructor.splice(0..0, assignments);
+        body.splice(index..index, properties);
+    }
+}
+
+impl VisitMut for PromotedProperties {
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        if let StatementKind::Class { body, .. } | StatementKind::Trait { body, .. } = &mut statement.kind {
+            self.promote(body);
+        }
+
+        walk_statement_mut(self, statement);
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        if let ExpressionKind::AnonymousClass { body, .. } = &mut expression.kind {
+            self.promote(body);
+        }
+
+        walk_expression_mut(self, expression);
+    }
+}
+
+/// Turns `match` into ternaries that compare the subject with each arm's conditions in turn.
+#[derive(Default)]
+struct Matches {
+    temporaries: usize,
+}
+
+impl VisitMut for Matches {
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+
+        let ExpressionKind::Match { condition, arms } = &mut expression.kind else {
+            return;
+        };
+
+        let subject = std::mem::replace(condition.as_mut(), ExpressionKind::Null.into());
+        let arms = std::mem::take(arms);
+
+        // The first comparison to run stores a subject that can't be evaluated more than once,
+        // and the rest read it back.
+        let (mut first, reference) = if has_side_effects(&subject) {
+            let temporary = format!("__match{}", self.temporaries);
+            self.temporaries += 1;
+
+            let store = ExpressionKind::Assign { var: Box::new(variable(&temporary)), value: Box::new(subject) }.into();
+            (Some(store), variable(&temporary))
+        } else {
+            (None, subject)
+        };
+
+        let mut default = None;
+        let mut branches = Vec::new();
+
+        for MatchArm { conditions, body } in arms {
+            let Some(conditions) = conditions else {
+                default = Some(body);
+                continue;
+            };
+
+            let test = conditions.into_iter().map(|condition| {
+                let lhs = first.take().unwrap_or_else(|| reference.clone());
+                infix(lhs, InfixOp::Identical, condition)
+            }).reduce(|lhs, rhs| infix(lhs, InfixOp::Or, rhs));
+
+            if let Some(test) = test {
+                branches.push((test, body));
+            }
+        }
+
+        let mut lowered = default.unwrap_or_else(|| unhandled(reference.clone()));
+
+        for (test, body) in branches.into_iter().rev() {
+            lowered = ExpressionKind::Ternary { condition: Box::new(test), then: Some(Box::new(body)), r#else: Box::new(lowered) }.into();
+        }
+
+        // With only a default arm, the subject still has to be evaluated first.
+        if let Some(store) = first {
+            lowered = ExpressionKind::ArrayIndex {
+                array: Box::new(ExpressionKind::Array { items: vec![item(store), item(lowered)] }.into()),
+                index: Some(Box::new(ExpressionKind::Int { i: 1 }.into())),
+            }.into();
+        }
+
+        lowered.span = expression.span;
+        *expression = lowered;
+    }
+}
+
+/// `throw new \Error('Unhandled match case ' . var_export($subject, true))`, which is what PHP 8
+/// throws as an `UnhandledMatchError`.
+fn unhandled(subject: Expression) -> Expression {
+    let export = call("var_export", vec![subject, ExpressionKind::Bool { value: true }.into()]);
+    let message = infix(ExpressionKind::ConstantString { value: "Unhandled match case ".into() }.into(), InfixOp::Concat, export);
+
+    ExpressionKind::Throw {
+        value: Box::new(ExpressionKind::New {
+            target: Box::new(ExpressionKind::Identifier { name: "\\Error".into() }.into()),
+            args: vec![argument(message)],
+        }.into()),
+    }.into()
+}
+
+/// Turns a `throw` that isn't a statement of its own into a call to a closure that throws.
+struct Throws;
+
+impl VisitMut for Throws {
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        match &mut statement.kind {
+            StatementKind::Expression { expr: Expression { kind: ExpressionKind::Throw { value }, .. } } => self.visit_expression_mut(value),
+            _ => walk_statement_mut(self, statement),
+        }
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+
+        if ! matches!(expression.kind, ExpressionKind::Throw { .. }) {
+            return;
+        }
+
+        let span = expression.span;
+        let throw = std::mem::replace(expression, ExpressionKind::Null.into());
+        let uses = captures(&throw, &[]);
+
+        *expression = call_closure(uses, vec![StatementKind::Expression { expr: throw }.into()]);
+        expression.span = span;
+    }
+}
+
+/// Turns arrow functions into closures that return their expression.
+struct ArrowFunctions;
+
+impl VisitMut for ArrowFunctions {
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+
+        let ExpressionKind::ArrowFunction { params, return_type, returns_by_ref, expr, attributes } = &mut expression.kind else {
+            return;
+        };
+
+        let names: Vec<&str> = params.iter().filter_map(|param| match &param.name.kind {
+            ExpressionKind::Variable { name } => Some(name.as_str()),
+            _ => None,
+        }).collect();
+        let uses = captures(expr, &names);
+        let expr = std::mem::replace(expr.as_mut(), ExpressionKind::Null.into());
+
+        expression.kind = ExpressionKind::Closure {
+            params: std::mem::take(params),
+            uses,
+            return_type: return_type.take(),
+            returns_by_ref: *returns_by_ref,
+            body: vec![StatementKind::Return { value: Some(expr) }.into()],
+            attributes: std::mem::take(attributes),
+        };
+    }
+}
+
+/// The variables that a closure made from `expression` has to `use`, in the order that they're
+/// first read, leaving out `params` and those that are defined everywhere.
+fn captures(expression: &Expression, params: &[&str]) -> Vec<ClosureUse> {
+    let mut variables = Variables::default();
+    variables.visit_expression(expression);
+
+    variables.names.into_iter()
+        .filter(|name| ! params.contains(&name.as_str()) && ! SUPERGLOBALS.contains(&name.as_str()))
+        .map(|name| ClosureUse { var: variable(&name), by_ref: false })
+        .collect()
+}
+
+/// Collects the variables that an expression reads from its scope, which for a nested closure are
+/// only the ones it uses.
+#[derive(Default)]
+struct Variables {
+    names: Vec<String>,
+}
+
+impl Variables {
+    fn add(&mut self, name: &str) {
+        if ! self.names.iter().any(|existing| existing == name) {
+            self.names.push(name.to_string());
+        }
+    }
+}
+
+impl Visitor for Variables {
+    fn visit_expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::Variable { name } => self.add(name),
+            ExpressionKind::Closure { uses, .. } => {
+                for r#use in uses {
+                    self.visit_expression(&r#use.var);
+                }
+            },
+            ExpressionKind::ArrowFunction { params, expr, .. } => {
+                let names: Vec<&str> = params.iter().filter_map(|param| match &param.name.kind {
+                    ExpressionKind::Variable { name } => Some(name.as_str()),
+                    _ => None,
+                }).collect();
+
+                for name in captures(expr, &names) {
+                    self.visit_expression(&name.var);
+                }
+            },
+            ExpressionKind::AnonymousClass { .. } => {},
+            _ => walk_expression(self, expression),
+        }
+    }
+}
+
+/// Whether evaluating an expression more than once could give a different result or do more than
+/// once.
+fn has_side_effects(expression: &Expression) -> bool {
+    ! matches!(expression.kind,
+        ExpressionKind::Variable { .. } | ExpressionKind::Int { .. } | ExpressionKind::Float { .. } | ExpressionKind::ConstantString { .. } |
+        ExpressionKind::Bool { .. } | ExpressionKind::Null | ExpressionKind::Identifier { .. } | ExpressionKind::MagicConst { .. }
+    )
+}
+
+fn variable(name: &str) -> Expression {
+    ExpressionKind::Variable { name: name.to_string() }.into()
+}
+
+fn infix(lhs: Expression, op: InfixOp, rhs: Expression) -> Expression {
+    ExpressionKind::Infix { lhs: Box::new(lhs), op, rhs: Box::new(rhs) }.into()
+}
+
+fn argument(value: Expression) -> Arg {
+    Arg { name: None, value, unpack: false }
+}
+
+fn item(value: Expression) -> ArrayItem {
+    ArrayItem { key: None, value, unpack: false, by_ref: false }
+}
+
+fn call(function: &str, args: Vec<Expression>) -> Expression {
+    ExpressionKind::Call {
+        target: Box::new(ExpressionKind::Identifier { name: function.into() }.into()),
+        args: args.into_iter().map(argument).collect(),
+    }.into()
+}
+
+/// `(function () use (...) { ... })()`
+fn call_closure(uses: Vec<ClosureUse>, body: Block) -> Expression {
+    ExpressionKind::Call {
+        target: Box::new(ExpressionKind::Closure { params: vec![], uses, return_type: None, returns_by_ref: false, body, attributes: vec![] }.into()),
+        args: vec![],
+    }.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_lexer::Lexer;
+    use trunk_parser::{parse, Parser, ParserConfig, PhpVersion};
+    use trunk_printer::print;
+
+    use super::lower;
+
+    /// Lower and print a program, checking that the result parses for the target.
+    fn lowered(source: &str, target: PhpVersion) -> String {
+        let printed = print(&lower(parse(source).unwrap(), target));
+        let tokens = Lexer::new(None).tokenize(&printed).unwrap();
+
+        let mut parser = Parser::new(Some(ParserConfig { php_version: target, ..Default::default() }));
+        assert!(parser.parse(&tokens).is_ok(), "{}", printed);
+
+        printed
+    }
+
+    #[test]
+    fn promoted_properties() {
+        let source = "<?php class A { public function __construct(private int $a, protected ?B &$b = null, $c = 1) { $this->c = $c; } }";
+
+        assert_eq!(lowered(source, PhpVersion::Php74), "\
+<?php
+
+class A
+{
+    private int $a;
+    protected ?B $b;
+
+    public function __construct(int $a, ?B &$b = null, $c = 1)
+    {
+        $this->a = $a;
+        $this->b = &$b;
+        $this->c = $c;
+    }
+}
+");
+
+        assert!(lowered(source, PhpVersion::Php73).contains("    private $a;\n"));
+        assert_eq!(lowered(source, PhpVersion::Php80), print(&parse(source).unwrap()));
+    }
+
+    #[test]
+    fn matches() {
+        assert_eq!(lowered("<?php echo match ($a) { 1, 2 => 'low', default => 'high', 3 => 'three' };", PhpVersion::Php74), "\
+<?php
+
+echo $a === 1 || $a === 2 ? 'low' : ($a === 3 ? 'three' : 'high');
+");
+
+        assert_eq!(lowered("<?php $b = match (f()) { 1 => match (g()) { default => 2 } };", PhpVersion::Php74), "\
+<?php
+
+$b = ($__match1 = f()) === 1 ? [$__match0 = g(), 2][1] : (function () use ($__match1) {
+    throw new \\Error('Unhandled match case ' . var_export($__match1, true));
+})();
+");
+    }
+
+    #[test]
+    fn throw_expressions() {
+        assert_eq!(lowered("<?php throw $e; $a = $b ?? throw new E($c, $this);", PhpVersion::Php74), "\
+<?php
+
+throw $e;
+$a = $b ?? (function () use ($c) {
+    throw new E($c, $this);
+})();
+");
+    }
+
+    #[test]
+    fn arrow_functions() {
+        let source = "<?php $f = fn(int $x): int => $x + $y + (fn($z) => $z * $w)(1) + (function () use ($v) { return $u; })();";
+
+        assert_eq!(lowered(source, PhpVersion::Php73), "\
+<?php
+
+$f = function (int $x) use ($y, $w, $v): int {
+    return $x + $y + (function ($z) use ($w) {
+        return $z * $w;
+    })(1) + (function () use ($v) {
+        return $u;
+    })();
+};
+");
+
+        assert_eq!(lowered(source, PhpVersion::Php74), print(&parse(source).unwrap()));
+    }
+}
@@ -0,0 +1,240 @@
+use trunk_parser::{
+    walk_expression, walk_statement, Block, Expression, ExpressionKind, InfixOp, MethodFlag, Param, Program, Span, Statement, StatementKind,
+    Visitor,
+};
+
+use crate::names::Imports;
+
+/// What a set of metrics was measured for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnitKind {
+    Function,
+    Method,
+    /// A class, trait or enum.
+    Class,
+}
+
+impl UnitKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Function => "function",
+            Self::Method => "method",
+            Self::Class => "class",
+        }
+    }
+}
+
+/// Measurements of a function, method or class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metrics {
+    pub kind: UnitKind,
+    /// The name of a function or class along with its namespace, or of a method as `Class::method`.
+    pub name: String,
+    pub span: Span,
+    /// How many lines the declaration takes up, including any blank lines and comments in it.
+    pub lines: usize,
+    /// The cyclomatic complexity, which is one more than the number of places where the code
+    /// branches. For a class, it's the sum over its methods.
+    pub complexity: usize,
+    /// How deeply control structures and closures are nested. For a class, it's the deepest of its
+    /// methods.
+    pub depth: usize,
+    /// The number of parameters, or `None` for a class.
+    pub params: Option<usize>,
+}
+
+/// Measure every function, method, class, trait and enum in a program, in the order that they
+/// appear, with each class before its methods. `source` has to be the source that the program was
+/// parsed from.
+///
+/// Closures and arrow functions count towards the function that they're in. Interfaces and abstract
+/// methods aren't measured, since they have no code.
+pub fn measure(program: &Program, source: &str) -> Vec<Metrics> {
+    let mut measurer = Measurer { source, imports: Imports::default(), class: None, metrics: Vec::new() };
+    measurer.visit_program(program);
+
+    measurer.metrics
+}
+
+struct Measurer<'a> {
+    source: &'a str,
+    imports: Imports,
+    class: Option<String>,
+    metrics: Vec<Metrics>,
+}
+
+impl Measurer<'_> {
+    fn lines(&self, span: Span) -> usize {
+        self.source.get(span.start..span.end).map_or(1, |source| source.matches('\n').count() + 1)
+    }
+
+    fn function(&mut self, kind: UnitKind, name: String, params: &[Param], body: &Block, span: Span) {
+        let mut complexity = Complexity { complexity: 1, depth: 0, max_depth: 0 };
+        body.iter().for_each(|statement| complexity.visit_statement(statement));
+
+        self.metrics.push(Metrics {
+            kind,
+            name,
+            span,
+            lines: self.lines(span),
+            complexity: complexity.complexity,
+            depth: complexity.max_depth,
+            params: Some(params.len()),
+        });
+    }
+
+    /// Measure a class from its methods, which are measured on their own as well.
+    fn class(&mut self, name: String, statement: &Statement) {
+        let index = self.metrics.len();
+        self.metrics.push(Metrics { kind: UnitKind::Class, name: name.clone(), span: statement.span, lines: self.lines(statement.span), complexity: 0, depth: 0, params: None });
+
+        let outer = self.class.replace(name);
+        walk_statement(self, statement);
+        self.class = outer;
+
+        let methods: Vec<_> = self.metrics[index + 1..].iter().filter(|metrics| metrics.kind == UnitKind::Method).map(|metrics| (metrics.complexity, metrics.depth)).collect();
+        let class = &mut self.metrics[index];
+        class.complexity = methods.iter().map(|(complexity, _)| complexity).sum();
+        class.depth = methods.iter().map(|(_, depth)| *depth).max().unwrap_or(0);
+    }
+}
+
+impl Visitor for Measurer<'_> {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::Namespace { name, body } => {
+                self.imports.enter_namespace(name);
+
+                if !body.is_empty() {
+                    walk_statement(self, statement);
+                    self.imports.enter_namespace("");
+                }
+            },
+            StatementKind::Class { name, .. } | StatementKind::Trait { name, .. } | StatementKind::Enum { name, .. } => {
+                self.class(self.imports.qualify(&name.name), statement);
+            },
+            StatementKind::Interface { .. } => {},
+            StatementKind::Function { name, params, body, .. } => {
+                self.function(UnitKind::Function, self.imports.qualify(&name.name), params, body, statement.span);
+                walk_statement(self, statement);
+            },
+            StatementKind::Method { name, params, body, flags, .. } if !flags.contains(&MethodFlag::Abstract) => {
+                let class = self.class.as_deref().unwrap_or("class@anonymous");
+                self.function(UnitKind::Method, format!("{}::{}", class, name.name), params, body, statement.span);
+                walk_statement(self, statement);
+            },
+            _ => walk_statement(self, statement),
+        }
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::AnonymousClass { body, .. } => {
+                let outer = self.class.replace("class@anonymous".to_string());
+                body.iter().for_each(|statement| self.visit_statement(statement));
+                self.class = outer;
+            },
+            _ => walk_expression(self, expression),
+        }
+    }
+}
+
+/// Counts the branches in a function, and how deeply its control structures nest, leaving out any
+/// functions and classes that it declares.
+struct Complexity {
+    complexity: usize,
+    depth: usize,
+    max_depth: usize,
+}
+
+impl Complexity {
+    fn nested(&mut self, walk: impl FnOnce(&mut Self)) {
+        self.depth += 1;
+        self.max_depth = self.max_depth.max(self.depth);
+        walk(self);
+        self.depth -= 1;
+    }
+}
+
+impl Visitor for Complexity {
+    fn visit_statement(&mut self, statement: &Statement) {
+        self.complexity += match &statement.kind {
+            StatementKind::Function { .. } | StatementKind::Class { .. } | StatementKind::Interface { .. } | StatementKind::Trait { .. } |
+            StatementKind::Enum { .. } => return,
+            StatementKind::If { else_ifs, .. } => 1 + else_ifs.len(),
+            StatementKind::While { .. } | StatementKind::For { .. } | StatementKind::Foreach { .. } => 1,
+            StatementKind::Switch { cases, .. } => cases.iter().filter(|case| case.condition.is_some()).count(),
+            StatementKind::Try { catches, .. } => catches.len(),
+            _ => return walk_statement(self, statement),
+        };
+
+        self.nested(|complexity| walk_statement(complexity, statement));
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        self.complexity += match &expression.kind {
+            ExpressionKind::AnonymousClass { .. } => return,
+            ExpressionKind::Closure { .. } | ExpressionKind::ArrowFunction { .. } => {
+                return self.nested(|complexity| walk_expression(complexity, expression));
+            },
+            ExpressionKind::Ternary { .. } | ExpressionKind::Coalesce { .. } | ExpressionKind::CoalesceAssign { .. } => 1,
+            ExpressionKind::Infix { op: InfixOp::And | InfixOp::Or | InfixOp::LogicalAnd | InfixOp::LogicalOr, .. } => 1,
+            ExpressionKind::Match { arms, .. } => arms.iter().filter(|arm| arm.conditions.is_some()).count(),
+            _ => 0,
+        };
+
+        walk_expression(self, expression);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_parser::parse;
+
+    use super::{measure, UnitKind};
+
+    #[test]
+    fn metrics() {
+        let source = "<?php
+namespace App;
+
+function simple($a, $b) {
+    return $a + $b;
+}
+
+function branchy($items) {
+    foreach ($items as $item) {
+        if ($item > 1 && $item < 10) {
+            $f = fn ($x) => $x ?? 0;
+        } elseif ($item) {
+            return match ($item) { 1, 2 => 'a', default => 'b' };
+        }
+    }
+
+    function nested() {}
+}
+
+trait Greets {
+    public function greet(string $name): string {
+        return $name ? \"Hello, $name\" : 'Hello';
+    }
+}
+
+interface Shape {
+    public function area(): float;
+}
+";
+        let metrics: Vec<_> = measure(&parse(source).unwrap(), source)
+            .into_iter()
+            .map(|metrics| (metrics.kind, metrics.name, metrics.span.line, metrics.lines, metrics.complexity, metrics.depth, metrics.params))
+            .collect();
+
+        assert_eq!(metrics, [
+            (UnitKind::Function, "App\\simple".to_string(), 4, 3, 1, 0, Some(2)),
+            (UnitKind::Function, "App\\branchy".to_string(), 8, 11, 7, 3, Some(1)),
+            (UnitKind::Function, "App\\nested".to_string(), 17, 1, 1, 0, Some(0)),
+            (UnitKind::Class, "App\\Greets".to_string(), 20, 5, 2, 0, None),
+            (UnitKind::Method, "App\\Greets::greet".to_string(), 21, 3, 2, 0, Some(1)),
+        ]);
+    }
+}
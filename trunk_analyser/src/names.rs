@@ -0,0 +1,483 @@
+use std::collections::HashMap;
+
+use trunk_parser::{
+    walk_expression, walk_statement, Arg, AttributeGroup, Expression, ExpressionKind, Identifier, Param, Program, Span, Statement, StatementKind,
+    Type, Use, UseKind, Visitor, relative_name,
+};
+
+/// Which table a name lives in. PHP resolves class, function and constant names by different rules,
+/// and interfaces, traits and enums share the table of classes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NameKind {
+    Class,
+    Function,
+    Constant,
+}
+
+/// A class, interface, trait, enum, function or constant that the program declares.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Declaration {
+    /// The fully qualified name, without a leading `\`.
+    pub name: String,
+    pub kind: NameKind,
+    /// The span of the declared name.
+    pub span: Span,
+}
+
+/// A use of a name, expanded to its fully qualified form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    /// The fully qualified name, without a leading `\`.
+    pub name: String,
+    /// The global name that an unqualified function or constant falls back to at runtime when
+    /// `name`, in the current namespace, isn't declared.
+    pub fallback: Option<String>,
+    pub kind: NameKind,
+    /// The span of the name as written. Names in type declarations don't have spans of their own,
+    /// so they're given the span of the parameter, or of the function or property that they belong
+    /// to.
+    pub span: Span,
+}
+
+/// The names that a program declares and refers to, in the order that they appear.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    pub declarations: Vec<Declaration>,
+    pub references: Vec<Reference>,
+}
+
+impl SymbolTable {
+    /// Find a declaration by its fully qualified name. Class and function names are matched
+    /// ignoring case, as PHP does.
+    pub fn declaration(&self, kind: NameKind, name: &str) -> Option<&Declaration> {
+        self.declarations.iter().find(|declaration| declaration.kind == kind && same_name(kind, &declaration.name, name))
+    }
+
+    /// The declaration that a reference resolves to, trying the global fallback if the name in the
+    /// current namespace isn't declared.
+    pub fn resolve(&self, reference: &Reference) -> Option<&Declaration> {
+        self.declaration(reference.kind, &reference.name)
+            .or_else(|| self.declaration(reference.kind, reference.fallback.as_deref()?))
+    }
+
+    /// The references that resolve to a declaration.
+    pub fn references_to<'a>(&'a self, declaration: &'a Declaration) -> impl Iterator<Item = &'a Reference> {
+        self.references.iter().filter(move |reference| self.resolve(reference) == Some(declaration))
+    }
+}
+
+fn same_name(kind: NameKind, a: &str, b: &str) -> bool {
+    match kind {
+        NameKind::Constant => a == b,
+        NameKind::Class | NameKind::Function => a.eq_ignore_ascii_case(b),
+    }
+}
+
+/// Walk a program, expanding every class, function and constant name that it declares or refers to
+/// into its fully qualified form by applying `namespace` declarations and `use` imports.
+///
+/// `self`, `static` and `parent` aren't resolved, since they depend on the class that they're used
+/// in, and neither are names built at runtime, such as `new $class`.
+pub fn resolve_names(program: &Program) -> SymbolTable {
+    let mut resolver = Resolver::default();
+    resolver.visit_program(program);
+
+    resolver.table
+}
+
+/// Names of types that aren't classes.
+const BUILTIN_TYPES: [&str; 17] = [
+    "array", "bool", "callable", "false", "float", "int", "iterable", "mixed", "never", "null", "object", "parent", "self", "static",
+    "string", "true", "void",
+];
+
+/// Language constructs that parse as calls to functions with these names.
+const CONSTRUCTS: [&str; 5] = ["empty", "eval", "isset", "list", "unset"];
+
+/// The namespace that code is in and the names that it imports, which decide what the names in it
+/// refer to.
+#[derive(Default, Clone)]
+pub(crate) struct Imports {
+    namespace: String,
+    /// Imported classes and namespaces, by their alias in lowercase.
+    classes: HashMap<String, String>,
+    /// Imported functions, by their alias in lowercase.
+    functions: HashMap<String, String>,
+    /// Imported constants, by their alias, which is case sensitive.
+    constants: HashMap<String, String>,
+}
+
+impl Imports {
+    pub(crate) fn enter_namespace(&mut self, name: &str) {
+        self.namespace = name.trim_start_matches('\\').to_string();
+        self.classes.clear();
+        self.functions.clear();
+        self.constants.clear();
+    }
+
+    pub(crate) fn qualify(&self, name: &str) -> String {
+        match self.namespace.as_str() {
+            "" => name.to_string(),
+            namespace => format!("{}\\{}", namespace, name),
+        }
+    }
+
+    /// Expand a name as written to its fully qualified form, along with the global name to fall back
+    /// to, if there is one.
+    pub(crate) fn expand(&self, name: &str, kind: NameKind) -> (String, Option<String>) {
+        if let Some(name) = name.strip_prefix('\\') {
+            return (name.to_string(), None);
+        }
+
+        if let Some(name) = relative_name(name) {
+            return (self.qualify(name), None);
+        }
+
+        // Qualified names of any kind start with an imported namespace, if they start with an alias.
+        if let Some((first, rest)) = name.split_once('\\') {
+            return match self.classes.get(&first.to_ascii_lowercase()) {
+                Some(namespace) => (format!("{}\\{}", namespace, rest), None),
+                None => (self.qualify(name), None),
+            };
+        }
+
+        let imported = match kind {
+            NameKind::Class => self.classes.get(&name.to_ascii_lowercase()),
+            NameKind::Function => self.functions.get(&name.to_ascii_lowercase()),
+            NameKind::Constant => self.constants.get(name),
+        };
+
+        match (imported, kind) {
+            (Some(imported), _) => (imported.clone(), None),
+            (None, NameKind::Function | NameKind::Constant) if ! self.namespace.is_empty() => (self.qualify(name), Some(name.to_string())),
+            (None, _) => (self.qualify(name), None),
+        }
+    }
+
+    /// Import a name with a `use` statement, giving back the fully qualified name that was imported.
+    pub(crate) fn import(&mut self, r#use: &Use, kind: &UseKind) -> (String, NameKind) {
+        let target = r#use.name.name.trim_start_matches('\\').to_string();
+        let alias = r#use.alias.as_ref().map_or_else(|| target.rsplit('\\').next().unwrap_or_default(), |alias| &alias.name).to_string();
+
+        let (table, kind) = match kind {
+            UseKind::Normal => (&mut self.classes, NameKind::Class),
+            UseKind::Function => (&mut self.functions, NameKind::Function),
+            UseKind::Const => (&mut self.constants, NameKind::Constant),
+        };
+
+        table.insert(if kind == NameKind::Constant { alias } else { alias.to_ascii_lowercase() }, target.clone());
+
+        (target, kind)
+    }
+}
+
+#[derive(Default)]
+struct Resolver {
+    imports: Imports,
+    table: SymbolTable,
+}
+
+impl Resolver {
+
+    fn declare(&mut self, name: &Identifier, kind: NameKind) {
+        self.table.declarations.push(Declaration { name: self.imports.qualify(&name.name), kind, span: name.span });
+    }
+
+    fn refer(&mut self, name: &str, kind: NameKind, span: Span) {
+        if kind == NameKind::Class && ["self", "static", "parent"].iter().any(|special| name.eq_ignore_ascii_case(special)) {
+            return;
+        }
+
+        let (name, fallback) = self.imports.expand(name, kind);
+        self.table.references.push(Reference { name, fallback, kind, span });
+    }
+
+    fn classes(&mut self, names: &[Identifier]) {
+        for name in names {
+            self.refer(&name.name, NameKind::Class, name.span);
+        }
+    }
+
+    fn r#type(&mut self, r#type: Option<&Type>, span: Span) {
+        match r#type {
+            Some(Type::Plain(name) | Type::Nullable(name)) if ! BUILTIN_TYPES.iter().any(|builtin| name.eq_ignore_ascii_case(builtin)) => {
+                self.refer(name, NameKind::Class, span);
+            },
+            Some(Type::Union(types) | Type::Intersection(types)) => {
+                for r#type in types {
+                    self.r#type(Some(r#type), span);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn params(&mut self, params: &[Param]) {
+        for param in params {
+            self.attributes(&param.attributes);
+            self.r#type(param.r#type.as_ref(), param.span);
+        }
+    }
+
+    fn attributes(&mut self, attributes: &[AttributeGroup]) {
+        for attribute in attributes.iter().flat_map(|group| &group.members) {
+            self.refer(&attribute.name.name, NameKind::Class, attribute.name.span);
+        }
+    }
+
+    /// Visit an expression that names a class when it's a bare name, such as the target of `new`.
+    fn class_expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::Identifier { name } => self.refer(name, NameKind::Class, expression.span),
+            _ => self.visit_expression(expression),
+        }
+    }
+
+    /// Visit an expression that names a method or property when it's a bare name, rather than a
+    /// constant.
+    fn member_expression(&mut self, expression: &Expression) {
+        if ! matches!(expression.kind, ExpressionKind::Identifier { .. }) {
+            self.visit_expression(expression);
+        }
+    }
+
+    fn args(&mut self, args: &[Arg]) {
+        for arg in args {
+            self.visit_expression(&arg.value);
+        }
+    }
+}
+
+impl Visitor for Resolver {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::Namespace { name, body } => {
+                self.imports.enter_namespace(name);
+
+                // A braced namespace ends with its body, while any other lasts until the next one.
+                if ! body.is_empty() {
+                    walk_statement(self, statement);
+                    self.imports.enter_namespace("");
+                }
+
+                return;
+            },
+            StatementKind::Use { uses, kind } => {
+                for r#use in uses {
+                    let (name, kind) = self.imports.import(r#use, kind);
+                    self.table.references.push(Reference { name, fallback: None, kind, span: r#use.name.span });
+                }
+            },
+            StatementKind::Class { name, extends, implements, attributes, .. } => {
+                self.declare(name, NameKind::Class);
+                self.attributes(attributes);
+                self.classes(extends.as_slice());
+                self.classes(implements);
+            },
+            StatementKind::Interface { name, extends, attributes, .. } => {
+                self.declare(name, NameKind::Class);
+                self.attributes(attributes);
+                self.classes(extends);
+            },
+            StatementKind::Trait { name, attributes, .. } => {
+                self.declare(name, NameKind::Class);
+                self.attributes(attributes);
+            },
+            StatementKind::Enum { name, implements, attributes, .. } => {
+                self.declare(name, NameKind::Class);
+                self.attributes(attributes);
+                self.classes(implements);
+            },
+            StatementKind::Function { name, params, return_type, attributes, .. } => {
+                self.declare(name, NameKind::Function);
+                self.attributes(attributes);
+                self.params(params);
+                self.r#type(return_type.as_ref(), statement.span);
+            },
+            StatementKind::Method { params, return_type, attributes, .. } => {
+                self.attributes(attributes);
+                self.params(params);
+                self.r#type(return_type.as_ref(), statement.span);
+            },
+            StatementKind::Property { r#type, attributes, .. } | StatementKind::Var { r#type, attributes, .. } => {
+                self.attributes(attributes);
+                self.r#type(r#type.as_ref(), statement.span);
+            },
+            StatementKind::Constant { attributes, .. } | StatementKind::EnumCase { attributes, .. } => self.attributes(attributes),
+            StatementKind::Const { constants, .. } => {
+                for constant in constants {
+                    self.declare(&constant.name, NameKind::Constant);
+                }
+            },
+            StatementKind::TraitUse { traits } => self.classes(traits),
+            StatementKind::Try { catches, .. } => {
+                for catch in catches {
+                    self.classes(&catch.types);
+                }
+            },
+            _ => {},
+        }
+
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::Identifier { name } => self.refer(name, NameKind::Constant, expression.span),
+            ExpressionKind::Call { target, args } => {
+                match &target.kind {
+                    ExpressionKind::Identifier { name } if CONSTRUCTS.iter().any(|construct| name.eq_ignore_ascii_case(construct)) => {},
+                    ExpressionKind::Identifier { name } => self.refer(name, NameKind::Function, target.span),
+                    _ => self.visit_expression(target),
+                }
+
+                self.args(args);
+            },
+            ExpressionKind::New { target, args } | ExpressionKind::StaticMethodCall { target, args, .. } => {
+                self.class_expression(target);
+                self.args(args);
+            },
+            ExpressionKind::DynamicStaticMethodCall { target, method, args } => {
+                self.class_expression(target);
+                self.visit_expression(method);
+                self.args(args);
+            },
+            ExpressionKind::ConstFetch { target, .. } => self.class_expression(target),
+            ExpressionKind::StaticPropertyFetch { target, property } | ExpressionKind::DynamicConstFetch { target, constant: property } => {
+                self.class_expression(target);
+                self.visit_expression(property);
+            },
+            ExpressionKind::Instanceof { lhs, rhs } => {
+                self.visit_expression(lhs);
+                self.class_expression(rhs);
+            },
+            ExpressionKind::MethodCall { target, method, args } | ExpressionKind::NullsafeMethodCall { target, method, args } => {
+                self.visit_expression(target);
+                self.member_expression(method);
+                self.args(args);
+            },
+            ExpressionKind::PropertyFetch { target, property } | ExpressionKind::NullsafePropertyFetch { target, property } => {
+                self.visit_expression(target);
+                self.member_expression(property);
+            },
+            ExpressionKind::Closure { params, return_type, attributes, .. } | ExpressionKind::ArrowFunction { params, return_type, attributes, .. } => {
+                self.attributes(attributes);
+                self.params(params);
+                self.r#type(return_type.as_ref(), expression.span);
+                walk_expression(self, expression);
+            },
+            ExpressionKind::AnonymousClass { extends, implements, attributes, .. } => {
+                self.attributes(attributes);
+                self.classes(extends.as_slice());
+                self.classes(implements);
+                walk_expression(self, expression);
+            },
+            _ => walk_expression(self, expression),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_parser::parse;
+
+    use super::{resolve_names, NameKind, SymbolTable};
+
+    fn table(source: &str) -> SymbolTable {
+        resolve_names(&parse(source).unwrap())
+    }
+
+    fn references(table: &SymbolTable) -> Vec<(NameKind, &str, Option<&str>)> {
+        table.references.iter().map(|reference| (reference.kind, reference.name.as_str(), reference.fallback.as_deref())).collect()
+    }
+
+    #[test]
+    fn expansion() {
+        let table = table("<?php
+namespace App\\Models;
+
+use Foo\\Bar, Foo\\Baz as Q;
+use function Lib\\helper;
+use const Lib\\MAX;
+
+$a = new \\Top\\Cls(namespace\\sub(), Rel\\X::C, strlen('x'), MAX, FOO, q\\Deep::class, isset($a));
+$a->method()->property instanceof Bar;
+");
+
+        assert_eq!(references(&table), [
+            (NameKind::Class, "Foo\\Bar", None),
+            (NameKind::Class, "Foo\\Baz", None),
+            (NameKind::Function, "Lib\\helper", None),
+            (NameKind::Constant, "Lib\\MAX", None),
+            (NameKind::Class, "Top\\Cls", None),
+            (NameKind::Function, "App\\Models\\sub", None),
+            (NameKind::Class, "App\\Models\\Rel\\X", None),
+            (NameKind::Function, "App\\Models\\strlen", Some("strlen")),
+            (NameKind::Constant, "Lib\\MAX", None),
+            (NameKind::Constant, "App\\Models\\FOO", Some("FOO")),
+            (NameKind::Class, "Foo\\Baz\\Deep", None),
+            (NameKind::Class, "Foo\\Bar", None),
+        ]);
+
+        let table = self::table("<?php namespace App; new abcdefghiéx; new namespace\\Café;");
+        assert_eq!(references(&table), [
+            (NameKind::Class, "App\\abcdefghiéx", None),
+            (NameKind::Class, "App\\Café", None),
+        ]);
+    }
+
+    #[test]
+    fn declarations() {
+        let table = table("<?php
+namespace A {
+    use B\\Base;
+
+    #[Attr]
+    final class C extends Base implements \\Countable {
+        use T;
+
+        public ?Base $base;
+
+        public function count(self $other): int|Missing { return helper(); }
+    }
+
+    function helper() { try {} catch (\\Exception | Error $e) {} }
+    const LIMIT = 1;
+}
+
+namespace B {
+    use A\\LIMIT;
+
+    echo \\A\\helper(), namespace\\LIMIT, LIMIT;
+}
+");
+
+        let declarations: Vec<_> = table.declarations.iter().map(|declaration| (declaration.kind, declaration.name.as_str())).collect();
+        assert_eq!(declarations, [(NameKind::Class, "A\\C"), (NameKind::Function, "A\\helper"), (NameKind::Constant, "A\\LIMIT")]);
+
+        assert_eq!(references(&table), [
+            (NameKind::Class, "B\\Base", None),
+            (NameKind::Class, "A\\Attr", None),
+            (NameKind::Class, "B\\Base", None),
+            (NameKind::Class, "Countable", None),
+            (NameKind::Class, "A\\T", None),
+            (NameKind::Class, "B\\Base", None),
+            (NameKind::Class, "A\\Missing", None),
+            (NameKind::Function, "A\\helper", Some("helper")),
+            (NameKind::Class, "Exception", None),
+            (NameKind::Class, "A\\Error", None),
+            (NameKind::Class, "A\\LIMIT", None),
+            (NameKind::Function, "A\\helper", None),
+            (NameKind::Constant, "B\\LIMIT", None),
+            (NameKind::Constant, "B\\LIMIT", Some("LIMIT")),
+        ]);
+
+        let lines: Vec<_> = table.references.iter().map(|reference| reference.span.line).collect();
+        assert_eq!(lines, [3, 5, 6, 6, 7, 9, 11, 11, 14, 14, 19, 21, 21, 21]);
+
+        let helper = table.declaration(NameKind::Function, "a\\HELPER").unwrap();
+        assert_eq!(helper.span.line, 14);
+        assert_eq!(table.references_to(helper).count(), 2);
+        assert_eq!(table.references_to(table.declaration(NameKind::Constant, "A\\LIMIT").unwrap()).count(), 0);
+    }
+}
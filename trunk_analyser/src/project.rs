@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+use trunk_parser::{parse_files, Error, Program, Statement, StatementKind};
+
+use crate::NameKind;
+
+/// A Composer project: the PHP files that its autoload rules cover, parsed, along with an index of
+/// the classes, functions and constants that they declare.
+#[derive(Debug)]
+pub struct Project {
+    pub root: PathBuf,
+    pub files: Vec<SourceFile>,
+    /// Files that couldn't be read or parsed, and directories that couldn't be searched.
+    pub errors: Vec<Error>,
+    /// The PSR-4 rules, as pairs of a namespace prefix, with a trailing `\`, and a directory.
+    pub psr4: Vec<(String, PathBuf)>,
+    symbols: HashMap<(NameKind, String), Symbol>,
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub program: Program,
+}
+
+/// A declaration that a project indexes, found by `Project::symbol`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    /// The fully qualified name, without a leading `\`.
+    pub name: String,
+    pub kind: NameKind,
+    /// The index of the file in `Project::files`.
+    pub file: usize,
+    /// The index of the declaring statement in the file, followed by its index in a braced
+    /// namespace's body if it's in one.
+    pub statement: Vec<usize>,
+}
+
+/// Why a project couldn't be loaded at all.
+#[derive(Debug)]
+pub enum ProjectError {
+    /// `composer.json` couldn't be read.
+    Io(PathBuf, std::io::Error),
+    /// `composer.json` isn't JSON, or its autoload rules aren't shaped as Composer expects.
+    Composer(PathBuf, String),
+}
+
+impl Display for ProjectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            Self::Composer(path, message) => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl std::error::Error for ProjectError {}
+
+impl Project {
+    /// Load the project in a directory with a `composer.json`. The files to parse are those below
+    /// the directories of the `psr-4`, `psr-0` and `classmap` rules in both `autoload` and
+    /// `autoload-dev`, along with every file listed in `files`. They're parsed in parallel, and
+    /// any that fail are left out and kept in `errors`. Dependencies in `vendor` aren't loaded.
+    ///
+    /// Only declarations at the top level of a file or of a namespace are indexed, and not those
+    /// made conditionally, such as in an `if`.
+    pub fn load(root: impl AsRef<Path>) -> Result<Self, ProjectError> {
+        let root = root.as_ref().to_path_buf();
+        let manifest = root.join("composer.json");
+        let source = std::fs::read_to_string(&manifest).map_err(|error| ProjectError::Io(manifest.clone(), error))?;
+        let composer: Value = serde_json::from_str(&source).map_err(|error| ProjectError::Composer(manifest.clone(), error.to_string()))?;
+
+        let mut psr4 = Vec::new();
+        let mut directories = Vec::new();
+        let mut paths = Vec::new();
+
+        for section in ["autoload", "autoload-dev"] {
+            let rules = &composer[section];
+
+            for (prefix, dirs) in object(rules, "psr-4", &manifest)? {
+                for dir in strings(dirs, &manifest)? {
+                    psr4.push((prefix.to_string(), root.join(dir)));
+                    directories.push(root.join(dir));
+                }
+            }
+
+            for (_, dirs) in object(rules, "psr-0", &manifest)? {
+                for dir in strings(dirs, &manifest)? {
+                    directories.push(root.join(dir));
+                }
+            }
+
+            for path in strings(&rules["classmap"], &manifest)? {
+                directories.push(root.join(path));
+            }
+
+            for path in strings(&rules["files"], &manifest)? {
+                paths.push(root.join(path));
+            }
+        }
+
+        let mut errors = Vec::new();
+        for directory in directories {
+            if directory.is_file() {
+                paths.push(directory);
+            } else if let Err(error) = discover(&directory, &mut paths) {
+                errors.push(Error::Io(error).in_file(directory));
+            }
+        }
+
+        paths.sort();
+        paths.dedup();
+
+        // Longer prefixes are more specific, so they're tried first.
+        psr4.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+
+        let mut project = Self { root, files: Vec::new(), errors, psr4, symbols: HashMap::new() };
+
+        for (path, result) in parse_files(paths) {
+            match result {
+                Ok(file) => project.add(SourceFile { path, program: file.program }),
+                Err(error) => project.errors.push(error),
+            }
+        }
+
+        Ok(project)
+    }
+
+    fn add(&mut self, file: SourceFile) {
+        let index = self.files.len();
+        let mut namespace = String::new();
+
+        for (i, statement) in file.program.iter().enumerate() {
+            match &statement.kind {
+                StatementKind::Namespace { name, body } if body.is_empty() => namespace = name.trim_start_matches('\\').to_string(),
+                StatementKind::Namespace { name, body } => {
+                    let namespace = name.trim_start_matches('\\');
+
+                    for (j, statement) in body.iter().enumerate() {
+                        self.index(namespace, statement, index, vec![i, j]);
+                    }
+                },
+                _ => self.index(&namespace, statement, index, vec![i]),
+            }
+        }
+
+        self.files.push(file);
+    }
+
+    fn index(&mut self, namespace: &str, statement: &Statement, file: usize, position: Vec<usize>) {
+        let qualify = |name: &str| match namespace {
+            "" => name.to_string(),
+            namespace => format!("{}\\{}", namespace, name),
+        };
+
+        let names = match &statement.kind {
+            StatementKind::Class { name, .. } | StatementKind::Interface { name, .. } | StatementKind::Trait { name, .. } |
+            StatementKind::Enum { name, .. } => vec![(NameKind::Class, qualify(&name.name))],
+            StatementKind::Function { name, .. } => vec![(NameKind::Function, qualify(&name.name))],
+            StatementKind::Const { constants, .. } => constants.iter().map(|constant| (NameKind::Constant, qualify(&constant.name.name))).collect(),
+            _ => return,
+        };
+
+        for (kind, name) in names {
+            // The first declaration of a name wins, as it would when PHP loads the files in order.
+            self.symbols.entry((kind, key(kind, &name))).or_insert(Symbol { name, kind, file, statement: position.clone() });
+        }
+    }
+
+    /// Find a declaration by its fully qualified name, with or without a leading `\`. Class and
+    /// function names are matched ignoring case, as PHP does.
+    pub fn symbol(&self, kind: NameKind, name: &str) -> Option<&Symbol> {
+        self.symbols.get(&(kind, key(kind, name.trim_start_matches('\\'))))
+    }
+
+    /// The file and statement that declare a class, interface, trait or enum.
+    pub fn class(&self, name: &str) -> Option<(&SourceFile, &Statement)> {
+        self.declaration(self.symbol(NameKind::Class, name)?)
+    }
+
+    /// The file and statement of a declaration.
+    pub fn declaration(&self, symbol: &Symbol) -> Option<(&SourceFile, &Statement)> {
+        let file = self.files.get(symbol.file)?;
+        let statement = match symbol.statement.as_slice() {
+            [i] => file.program.get(*i)?,
+            [i, j] => match &file.program.get(*i)?.kind {
+                StatementKind::Namespace { body, .. } => body.get(*j)?,
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some((file, statement))
+    }
+
+    /// Every indexed declaration, in no particular order.
+    pub fn symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.values()
+    }
+
+    /// The file that Composer's PSR-4 rules would load a class from, whether or not it exists.
+    pub fn autoload_path(&self, class: &str) -> Option<PathBuf> {
+        let class = class.trim_start_matches('\\');
+
+        self.psr4.iter().find_map(|(prefix, dir)| {
+            let relative = class.strip_prefix(prefix.as_str())?;
+
+            Some(dir.join(format!("{}.php", relative.replace('\\', "/"))))
+        })
+    }
+}
+
+fn key(kind: NameKind, name: &str) -> String {
+    match kind {
+        NameKind::Constant => name.to_string(),
+        NameKind::Class | NameKind::Function => name.to_ascii_lowercase(),
+    }
+}
+
+/// The entries of an object in a section of the autoload rules, which can be left out.
+fn object<'a>(rules: &'a Value, field: &str, manifest: &Path) -> Result<Vec<(&'a str, &'a Value)>, ProjectError> {
+    match &rules[field] {
+        Value::Null => Ok(Vec::new()),
+        Value::Object(entries) => Ok(entries.iter().map(|(key, value)| (key.as_str(), value)).collect()),
+        _ => Err(ProjectError::Composer(manifest.to_path_buf(), format!("`{}` must be an object", field))),
+    }
+}
+
+/// A path or a list of paths, which can be left out.
+fn strings<'a>(value: &'a Value, manifest: &Path) -> Result<Vec<&'a str>, ProjectError> {
+    let invalid = || ProjectError::Composer(manifest.to_path_buf(), format!("expected a path or a list of paths, found `{}`", value));
+
+    match value {
+        Value::Null => Ok(Vec::new()),
+        Value::String(path) => Ok(vec![path]),
+        Value::Array(paths) => paths.iter().map(|path| path.as_str().ok_or_else(invalid)).collect(),
+        _ => Err(invalid()),
+    }
+}
+
+/// Find the `.php` files below a directory.
+fn discover(directory: &Path, paths: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(directory)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        // Symbolic links to directories aren't followed, so that a loop can't be walked forever.
+        if entry.file_type()?.is_dir() {
+            discover(&path, paths)?;
+        } else if path.extension().is_some_and(|extension| extension == "php") {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use trunk_parser::StatementKind;
+
+    use super::{Project, ProjectError};
+    use crate::NameKind;
+
+    #[test]
+    fn load() {
+        let dir = std::env::temp_dir().join(format!("trunk_project_{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/Models")).unwrap();
+        fs::create_dir_all(dir.join("tests")).unwrap();
+        fs::create_dir_all(dir.join("vendor/lib")).unwrap();
+
+        fs::write(dir.join("composer.json"), r#"{
+            "autoload": { "psr-4": { "App\\": "src/", "App\\Models\\": ["src/Models"] }, "files": ["helpers.php"] },
+            "autoload-dev": { "psr-4": { "Tests\\": "tests/" } }
+        }"#).unwrap();
+        fs::write(dir.join("src/Models/User.php"), "<?php namespace App\\Models; class User {}").unwrap();
+        fs::write(dir.join("src/Kernel.php"), "<?php namespace App { interface Kernel {} const VERSION = 1; }").unwrap();
+        fs::write(dir.join("src/Broken.php"), "<?php class {").unwrap();
+        fs::write(dir.join("tests/UserTest.php"), "<?php namespace Tests; final class UserTest {}").unwrap();
+        fs::write(dir.join("helpers.php"), "<?php function helper() {}").unwrap();
+        fs::write(dir.join("vendor/lib/Lib.php"), "<?php class Lib {}").unwrap();
+
+        let project = Project::load(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        let project = project.unwrap();
+
+        let mut files: Vec<_> = project.files.iter().map(|file| file.path.strip_prefix(&dir).unwrap().to_path_buf()).collect();
+        files.sort();
+        assert_eq!(files, ["helpers.php", "src/Kernel.php", "src/Models/User.php", "tests/UserTest.php"].map(std::path::PathBuf::from));
+        assert_eq!(project.errors.len(), 1);
+        assert_eq!(project.errors[0].path(), Some(dir.join("src/Broken.php").as_path()));
+
+        let (file, statement) = project.class("\\app\\models\\user").unwrap();
+        assert_eq!(file.path, dir.join("src/Models/User.php"));
+        assert!(matches!(&statement.kind, StatementKind::Class { name, .. } if name.name == "User"));
+
+        assert!(matches!(project.class("App\\Kernel").unwrap().1.kind, StatementKind::Interface { .. }));
+        assert_eq!(project.symbol(NameKind::Constant, "App\\VERSION").unwrap().statement, [0, 1]);
+        assert!(project.symbol(NameKind::Constant, "App\\version").is_none());
+        assert!(project.symbol(NameKind::Function, "HELPER").is_some());
+        assert!(project.class("Lib").is_none());
+        assert_eq!(project.symbols().count(), 5);
+
+        assert_eq!(project.autoload_path("App\\Models\\Post"), Some(dir.join("src/Models").join("Post.php")));
+        assert_eq!(project.autoload_path("App\\Http\\Controller"), Some(dir.join("src/").join("Http/Controller.php")));
+        assert_eq!(project.autoload_path("Other\\Thing"), None);
+
+        assert!(matches!(Project::load(std::env::temp_dir().join("trunk_missing_project")), Err(ProjectError::Io(..))));
+    }
+}
@@ -0,0 +1,994 @@
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+use trunk_parser::{
+    walk_expression, walk_statement, Arg, Block, Diagnostic, Expression, ExpressionKind, InfixOp, MethodFlag, Param, Program, Severity, Span,
+    Statement, StatementKind, Visitor,
+};
+
+/// Check every function, method and closure in a program for variables that are read before they're
+/// assigned, and for parameters that are never used.
+///
+/// Variables are followed through branches and loops, so one that's only assigned on some paths is
+/// reported as one that might not be defined. Code at the top level of a file isn't checked, since
+/// its variables can come from a file that includes it, and neither is a scope that calls `extract`,
+/// `eval` or `include`, since they can define any variable.
+///
+/// Unused parameters are only reported for functions, closures and private methods, whose callers
+/// are all in view, and only after the last parameter that is used, since the ones before it are
+/// needed to reach it.
+pub fn check_scopes(program: &Program) -> Vec<Diagnostic> {
+    let mut signatures = Signatures::default();
+    signatures.visit_program(program);
+
+    let mut checker = Checker { signatures, scope: Scope { dynamic: true, ..Scope::default() }, diagnostics: Vec::new() };
+    checker.visit_program(program);
+
+    let mut diagnostics = checker.diagnostics;
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.map(|span| span.start));
+    diagnostics
+}
+
+/// Variables that are defined in every scope.
+pub(crate) const SUPERGLOBALS: [&str; 10] = ["this", "GLOBALS", "_SERVER", "_GET", "_POST", "_FILES", "_COOKIE", "_SESSION", "_REQUEST", "_ENV"];
+
+/// Functions from the standard library that assign to some of their arguments, with the positions
+/// of those arguments.
+const BY_REF_FUNCTIONS: [(&str, &[usize]); 13] = [
+    ("exec", &[1, 2]),
+    ("fsockopen", &[2, 3]),
+    ("headers_sent", &[0, 1]),
+    ("is_callable", &[2]),
+    ("mb_parse_str", &[1]),
+    ("parse_str", &[1]),
+    ("preg_match", &[2]),
+    ("preg_match_all", &[2]),
+    ("preg_replace", &[4]),
+    ("preg_replace_callback", &[4]),
+    ("similar_text", &[2]),
+    ("str_ireplace", &[3]),
+    ("str_replace", &[3]),
+];
+
+/// Functions that can define variables that the program doesn't name.
+const DEFINING_FUNCTIONS: [&str; 3] = ["eval", "extract", "parse_str"];
+
+/// The positions of the by-reference parameters of the functions and methods that a program
+/// declares, by name in lowercase. Methods are matched by name alone, whatever they're called on.
+#[derive(Default)]
+struct Signatures {
+    functions: HashMap<String, Vec<usize>>,
+    methods: HashMap<String, Vec<usize>>,
+}
+
+impl Signatures {
+    fn function(&self, name: &str) -> Vec<usize> {
+        match self.functions.get(name) {
+            Some(positions) => positions.clone(),
+            None => BY_REF_FUNCTIONS.iter().find(|(function, _)| *function == name).map(|(_, positions)| positions.to_vec()).unwrap_or_default(),
+        }
+    }
+
+    fn method(&self, name: &str) -> Vec<usize> {
+        self.methods.get(&name.to_ascii_lowercase()).cloned().unwrap_or_default()
+    }
+}
+
+impl Visitor for Signatures {
+    fn visit_statement(&mut self, statement: &Statement) {
+        let (table, name, params) = match &statement.kind {
+            StatementKind::Function { name, params, .. } => (&mut self.functions, name, params),
+            StatementKind::Method { name, params, .. } => (&mut self.methods, name, params),
+            _ => return walk_statement(self, statement),
+        };
+
+        let positions = params.iter().enumerate().filter(|(_, param)| param.by_ref).map(|(position, _)| position);
+        table.entry(name.name.to_ascii_lowercase()).or_default().extend(positions);
+
+        walk_statement(self, statement);
+    }
+}
+
+/// The variables that are defined at a point in a scope.
+#[derive(Debug, Clone, Default)]
+struct State {
+    /// Variables that are defined on every path to this point.
+    defined: HashSet<String>,
+    /// Variables that are defined on some paths to this point, but not all.
+    maybe: HashSet<String>,
+    /// Whether every path to this point returned, threw or jumped elsewhere first.
+    unreachable: bool,
+}
+
+impl State {
+    fn unreachable() -> Self {
+        Self { unreachable: true, ..Self::default() }
+    }
+
+    fn define(&mut self, name: &str) {
+        self.maybe.remove(name);
+        self.defined.insert(name.to_string());
+    }
+
+    fn might_define(&mut self, name: &str) {
+        if !self.defined.contains(name) {
+            self.maybe.insert(name.to_string());
+        }
+    }
+
+    fn undefine(&mut self, name: &str) {
+        self.maybe.remove(name);
+        self.defined.remove(name);
+    }
+
+    /// Define the variables that a condition proves are set when it has the given outcome, such as
+    /// `$x` when `isset($x)` is true.
+    fn narrow(&mut self, condition: &Expression, outcome: bool) {
+        let mut names = Vec::new();
+        narrowed(condition, outcome, &mut names);
+
+        for name in names {
+            self.define(name);
+        }
+    }
+
+    /// The state after paths from either of two states join.
+    fn merge(self, other: State) -> State {
+        if self.unreachable {
+            return other;
+        }
+
+        if other.unreachable {
+            return self;
+        }
+
+        let defined = self.defined.intersection(&other.defined).cloned().collect();
+        let maybe = self.maybe.union(&other.maybe).chain(self.defined.symmetric_difference(&other.defined)).cloned().collect();
+
+        State { defined, maybe, unreachable: false }
+    }
+}
+
+/// The states that leave a loop or a `switch` from a `break` or `continue` inside it.
+#[derive(Default)]
+struct Jumps {
+    breaks: Vec<State>,
+    continues: Vec<State>,
+}
+
+/// What's known about the function that's being checked.
+#[derive(Default)]
+struct Scope {
+    state: State,
+    /// The enclosing loops and `switch` statements, innermost last.
+    jumps: Vec<Jumps>,
+    /// Variables that have been read, or written through a reference.
+    used: HashSet<String>,
+    /// Whether every parameter counts as used, because the function reads them with
+    /// `func_get_args` or `get_defined_vars`.
+    all_used: bool,
+    by_ref_params: HashSet<String>,
+    /// Variables that have been reported, so that each is only reported once.
+    reported: HashSet<String>,
+    /// Whether the scope can define variables without naming them, so none can be reported.
+    dynamic: bool,
+}
+
+enum Body<'a> {
+    Block(&'a Block),
+    Expression(&'a Expression),
+}
+
+struct Checker {
+    signatures: Signatures,
+    scope: Scope,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    /// Check a function, method or closure in a scope of its own, starting with the variables in
+    /// `state`, and give back that scope.
+    fn function(&mut self, params: &[Param], body: Body, state: State, dynamic: bool, report_unused: bool) -> Scope {
+        let mut scope = Scope { state, dynamic: dynamic || defines_any(&body), ..Scope::default() };
+
+        for param in params {
+            if let Some(name) = param.variable_name() {
+                scope.state.define(name);
+
+                if param.by_ref {
+                    scope.by_ref_params.insert(name.to_string());
+                }
+            }
+        }
+
+        let outer = mem::replace(&mut self.scope, scope);
+
+        match body {
+            Body::Block(block) => self.block(block),
+            Body::Expression(expression) => self.visit_expression(expression),
+        }
+
+        let scope = mem::replace(&mut self.scope, outer);
+
+        if report_unused && !scope.all_used {
+            let is_used = |param: &Param| match param.variable_name() {
+                Some(name) => scope.used.contains(name) || name.starts_with('_') || !param.flags.is_empty(),
+                None => true,
+            };
+            let first = params.iter().rposition(is_used).map_or(0, |last| last + 1);
+
+            for param in &params[first..] {
+                if let Some(name) = param.variable_name() {
+                    let message = format!("parameter `${}` is never used", name);
+                    self.diagnostics.push(Diagnostic::new(Severity::Warning, "unused-parameter", message, Some(param.span)));
+                }
+            }
+        }
+
+        scope
+    }
+
+    fn block(&mut self, block: &[Statement]) {
+        for statement in block {
+            self.visit_statement(statement);
+        }
+    }
+
+    fn read(&mut self, name: &str, span: Span) {
+        let scope = &mut self.scope;
+        scope.used.insert(name.to_string());
+
+        if scope.dynamic || scope.state.unreachable || scope.state.defined.contains(name) || SUPERGLOBALS.contains(&name) {
+            return;
+        }
+
+        if !scope.reported.insert(name.to_string()) {
+            return;
+        }
+
+        let message = if scope.state.maybe.contains(name) {
+            format!("variable `${}` might not be defined", name)
+        } else {
+            format!("undefined variable `${}`", name)
+        };
+
+        self.diagnostics.push(Diagnostic::new(Severity::Warning, "undefined-variable", message, Some(span)));
+    }
+
+    /// Check an expression that's allowed to read undefined variables, such as an argument to
+    /// `isset`. Only the variable that's being tested is exempt, and not any in an index or property
+    /// name.
+    fn quietly(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::Variable { name } => {
+                self.scope.used.insert(name.to_string());
+            },
+            ExpressionKind::ArrayIndex { array, index } => {
+                self.quietly(array);
+
+                if let Some(index) = index {
+                    self.visit_expression(index);
+                }
+            },
+            ExpressionKind::PropertyFetch { target, property } | ExpressionKind::NullsafePropertyFetch { target, property } => {
+                self.quietly(target);
+                self.visit_expression(property);
+            },
+            _ => self.visit_expression(expression),
+        }
+    }
+
+    /// Assign to a variable, an element of an array or a property, or destructure into several.
+    fn assign(&mut self, target: &Expression) {
+        match &target.kind {
+            ExpressionKind::Variable { name } => {
+                if self.scope.by_ref_params.contains(name) {
+                    self.scope.used.insert(name.to_string());
+                }
+
+                self.scope.state.define(name);
+            },
+            // Writing to an element of an undefined variable creates an array.
+            ExpressionKind::ArrayIndex { array, index } => {
+                if let Some(index) = index {
+                    self.visit_expression(index);
+                }
+
+                if let Some(name) = base_variable(array) {
+                    self.scope.used.insert(name.to_string());
+                }
+
+                self.assign(array);
+            },
+            ExpressionKind::Array { items } => {
+                for item in items {
+                    if let Some(key) = &item.key {
+                        self.visit_expression(key);
+                    }
+
+                    if item.by_ref {
+                        self.reference(&item.value);
+                    } else {
+                        self.assign(&item.value);
+                    }
+                }
+            },
+            ExpressionKind::Call { target: function, args } if is_call_to(function, "list") => {
+                for arg in args {
+                    self.assign(&arg.value);
+                }
+            },
+            _ => self.visit_expression(target),
+        }
+    }
+
+    /// Take a reference to a variable, which defines it if it wasn't already.
+    fn reference(&mut self, target: &Expression) {
+        if let Some(name) = base_variable(target) {
+            self.scope.used.insert(name.to_string());
+        }
+
+        self.assign(target);
+    }
+
+    fn arguments(&mut self, args: &[Arg], by_ref: &[usize]) {
+        for (position, arg) in args.iter().enumerate() {
+            if by_ref.contains(&position) && arg.name.is_none() && !arg.unpack {
+                self.reference(&arg.value);
+            } else {
+                self.visit_expression(&arg.value);
+            }
+        }
+    }
+
+    /// Split the state after a condition into the state for when it's false, which is given back,
+    /// and the state for when it's true, which is carried on with.
+    fn branch(&mut self, condition: &Expression) -> State {
+        let mut otherwise = self.scope.state.clone();
+        otherwise.narrow(condition, false);
+        self.scope.state.narrow(condition, true);
+
+        otherwise
+    }
+
+    /// Check an expression that might not be evaluated, such as the right of `??`.
+    fn maybe(&mut self, expression: &Expression) {
+        let skipped = self.scope.state.clone();
+        self.visit_expression(expression);
+        self.scope.state = mem::take(&mut self.scope.state).merge(skipped);
+    }
+
+    fn take_state(&mut self) -> State {
+        mem::replace(&mut self.scope.state, State::unreachable())
+    }
+
+    /// Check a loop. Variables that the loop assigns might already have been assigned by an earlier
+    /// iteration, and are only possibly defined after it, unless the loop can only be left with a
+    /// `break`.
+    fn r#loop(&mut self, condition: Option<&Expression>, bindings: &[&Expression], step: Option<&Expression>, body: &Block) {
+        let mut assignments = Assignments::default();
+        assignments.block(body);
+        if let Some(step) = step {
+            assignments.visit_expression(step);
+        }
+
+        for name in &assignments.0 {
+            self.scope.state.might_define(name);
+        }
+
+        let skipped = match condition {
+            Some(condition) => {
+                self.visit_expression(condition);
+                self.branch(condition)
+            },
+            None => self.scope.state.clone(),
+        };
+        let endless = bindings.is_empty() && condition.is_none_or(|condition| matches!(condition.kind, ExpressionKind::Bool { value: true }));
+
+        for binding in bindings {
+            self.assign(binding);
+        }
+
+        self.scope.jumps.push(Jumps::default());
+        self.block(body);
+        let jumps = self.scope.jumps.pop().unwrap();
+
+        // `continue` goes on to the next iteration, the same as reaching the end of the body.
+        self.scope.state = jumps.continues.into_iter().fold(self.take_state(), State::merge);
+
+        if let Some(step) = step {
+            self.visit_expression(step);
+        }
+
+        let after = if endless { State::unreachable() } else { skipped.merge(self.take_state()) };
+        self.scope.state = jumps.breaks.into_iter().fold(after, State::merge);
+    }
+
+    fn jump(&mut self, num: &Option<Expression>, is_break: bool) {
+        let levels = match num.as_ref().map(|num| &num.kind) {
+            Some(ExpressionKind::Int { i }) => (*i).max(1) as usize,
+            _ => 1,
+        };
+
+        let state = self.take_state();
+        let depth = self.scope.jumps.len();
+
+        if let Some(jumps) = depth.checked_sub(levels).and_then(|index| self.scope.jumps.get_mut(index)) {
+            if is_break {
+                jumps.breaks.push(state);
+            } else {
+                jumps.continues.push(state);
+            }
+        }
+    }
+}
+
+impl Visitor for Checker {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::Function { params, body, .. } => {
+                self.function(params, Body::Block(body), State::default(), false, true);
+            },
+            StatementKind::Method { params, body, flags, .. } => {
+                self.function(params, Body::Block(body), State::default(), false, flags.contains(&MethodFlag::Private));
+            },
+            StatementKind::If { condition, then, else_ifs, r#else } => {
+                self.visit_expression(condition);
+                let mut otherwise = self.branch(condition);
+                self.block(then);
+                let mut after = self.take_state();
+
+                for else_if in else_ifs {
+                    self.scope.state = otherwise;
+                    self.visit_expression(&else_if.condition);
+                    otherwise = self.branch(&else_if.condition);
+                    self.block(&else_if.body);
+                    after = after.merge(self.take_state());
+                }
+
+                self.scope.state = otherwise;
+                if let Some(r#else) = r#else {
+                    self.block(r#else);
+                }
+
+                self.scope.state = after.merge(self.take_state());
+            },
+            StatementKind::While { condition, body } => self.r#loop(Some(condition), &[], None, body),
+            StatementKind::For { init, condition, r#loop, then } => {
+                if let Some(init) = init {
+                    self.visit_expression(init);
+                }
+
+                self.r#loop(condition.as_ref(), &[], r#loop.as_ref(), then);
+            },
+            StatementKind::Foreach { expr, key_var, value_var, body, .. } => {
+                self.visit_expression(expr);
+
+                let bindings: Vec<&Expression> = key_var.iter().chain([value_var]).collect();
+                self.r#loop(None, &bindings, None, body);
+            },
+            StatementKind::Switch { condition, cases } => {
+                self.visit_expression(condition);
+                let entry = self.scope.state.clone();
+                self.scope.jumps.push(Jumps::default());
+
+                // A case is entered by matching, or by falling through from the case before it.
+                let mut falling = State::unreachable();
+                for case in cases {
+                    self.scope.state = entry.clone();
+                    if let Some(condition) = &case.condition {
+                        self.visit_expression(condition);
+                    }
+
+                    self.scope.state = self.take_state().merge(falling);
+                    self.block(&case.body);
+                    falling = self.take_state();
+                }
+
+                let jumps = self.scope.jumps.pop().unwrap();
+                let after = match cases.iter().any(|case| case.condition.is_none()) {
+                    true => falling,
+                    false => falling.merge(entry),
+                };
+
+                self.scope.state = jumps.breaks.into_iter().chain(jumps.continues).fold(after, State::merge);
+            },
+            StatementKind::Try { body, catches, finally } => {
+                let mut entry = self.scope.state.clone();
+                self.block(body);
+
+                // A `catch` can be reached from anywhere in the `try`, after any of its assignments.
+                let mut assignments = Assignments::default();
+                assignments.block(body);
+                for name in &assignments.0 {
+                    entry.might_define(name);
+                }
+
+                let mut after = self.take_state();
+                for catch in catches {
+                    self.scope.state = entry.clone();
+                    self.assign(&catch.var);
+                    self.block(&catch.body);
+                    after = after.merge(self.take_state());
+                }
+
+                self.scope.state = after;
+
+                // A `finally` block runs even when the `try` or a `catch` returns.
+                if let Some(finally) = finally {
+                    let unreachable = mem::replace(&mut self.scope.state.unreachable, false);
+                    self.block(finally);
+                    self.scope.state.unreachable |= unreachable;
+                }
+            },
+            StatementKind::Return { value } => {
+                if let Some(value) = value {
+                    self.visit_expression(value);
+                }
+
+                self.scope.state = State::unreachable();
+            },
+            StatementKind::Break { num } => self.jump(num, true),
+            StatementKind::Continue { num } => self.jump(num, false),
+            StatementKind::Static { vars } => {
+                for var in vars {
+                    if let Some(default) = &var.default {
+                        self.visit_expression(default);
+                    }
+
+                    self.assign(&var.var);
+                }
+            },
+            StatementKind::Global { vars } => {
+                for var in vars {
+                    self.assign(var);
+                }
+            },
+            _ => walk_statement(self, statement),
+        }
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::Variable { name } => self.read(name, expression.span),
+            ExpressionKind::Assign { var, value } => {
+                self.visit_expression(value);
+                self.assign(var);
+            },
+            ExpressionKind::AssignRef { var, value } => {
+                self.reference(value);
+                self.assign(var);
+            },
+            ExpressionKind::CoalesceAssign { var, value } => {
+                self.quietly(var);
+                self.maybe(value);
+                self.assign(var);
+            },
+            ExpressionKind::Infix { lhs, op, rhs } => match op {
+                InfixOp::And | InfixOp::LogicalAnd | InfixOp::Or | InfixOp::LogicalOr => {
+                    self.visit_expression(lhs);
+
+                    // The right of `&&` is only evaluated when the left is true, and of `||` when it's false.
+                    let mut skipped = self.branch(lhs);
+                    if matches!(op, InfixOp::Or | InfixOp::LogicalOr) {
+                        mem::swap(&mut skipped, &mut self.scope.state);
+                    }
+
+                    self.visit_expression(rhs);
+                    self.scope.state = self.take_state().merge(skipped);
+                },
+                op if op.is_assignment() => {
+                    self.visit_expression(lhs);
+                    self.visit_expression(rhs);
+                    self.assign(lhs);
+                },
+                _ => walk_expression(self, expression),
+            },
+            ExpressionKind::Coalesce { lhs, rhs } => {
+                self.quietly(lhs);
+                self.maybe(rhs);
+            },
+            ExpressionKind::Ternary { condition, then, r#else } => {
+                self.visit_expression(condition);
+
+                match then {
+                    Some(then) => {
+                        let otherwise = self.branch(condition);
+                        self.visit_expression(then);
+                        let after = mem::replace(&mut self.scope.state, otherwise);
+                        self.visit_expression(r#else);
+                        self.scope.state = after.merge(self.take_state());
+                    },
+                    None => self.maybe(r#else),
+                }
+            },
+            ExpressionKind::Match { condition, arms } => {
+                self.visit_expression(condition);
+                let entry = self.scope.state.clone();
+
+                // A value that no arm matches throws an error, so there's no path past the `match`
+                // that doesn't go through an arm.
+                let mut after = State::unreachable();
+                for arm in arms {
+                    self.scope.state = entry.clone();
+                    for condition in arm.conditions.iter().flatten() {
+                        self.visit_expression(condition);
+                    }
+
+                    self.visit_expression(&arm.body);
+                    after = after.merge(self.take_state());
+                }
+
+                self.scope.state = after;
+            },
+            ExpressionKind::Call { target, args } => match &target.kind {
+                ExpressionKind::Identifier { name } => {
+                    let function = name.rsplit('\\').next().unwrap_or(name).to_ascii_lowercase();
+
+                    match function.as_str() {
+                        "isset" | "empty" => return args.iter().for_each(|arg| self.quietly(&arg.value)),
+                        "unset" => {
+                            for arg in args {
+                                match &arg.value.kind {
+                                    ExpressionKind::Variable { name } => self.scope.state.undefine(name),
+                                    _ => self.quietly(&arg.value),
+                                }
+                            }
+
+                            return;
+                        },
+                        "compact" => {
+                            for arg in args {
+                                if let ExpressionKind::ConstantString { value } = &arg.value.kind {
+                                    self.read(value, arg.value.span);
+                                }
+                            }
+                        },
+                        "func_get_args" | "func_get_arg" | "get_defined_vars" => self.scope.all_used = true,
+                        _ => {},
+                    }
+
+                    let by_ref = self.signatures.function(&function);
+                    self.arguments(args, &by_ref);
+                },
+                _ => {
+                    self.visit_expression(target);
+                    self.arguments(args, &[]);
+                },
+            },
+            ExpressionKind::MethodCall { target, method, args } | ExpressionKind::NullsafeMethodCall { target, method, args } => {
+                self.visit_expression(target);
+
+                let by_ref = match &method.kind {
+                    ExpressionKind::Identifier { name } => self.signatures.method(name),
+                    _ => {
+                        self.visit_expression(method);
+                        Vec::new()
+                    },
+                };
+
+                self.arguments(args, &by_ref);
+            },
+            ExpressionKind::StaticMethodCall { target, method, args } => {
+                self.visit_expression(target);
+
+                let by_ref = self.signatures.method(&method.name);
+                self.arguments(args, &by_ref);
+            },
+            ExpressionKind::New { target, args } => {
+                self.visit_expression(target);
+
+                let by_ref = self.signatures.method("__construct");
+                self.arguments(args, &by_ref);
+            },
+            // The property of `Foo::$bar` is a name, not a variable.
+            ExpressionKind::StaticPropertyFetch { target, property } => {
+                self.visit_expression(target);
+
+                if !matches!(property.kind, ExpressionKind::Variable { .. }) {
+                    self.visit_expression(property);
+                }
+            },
+            ExpressionKind::Array { items } => {
+                for item in items {
+                    if let Some(key) = &item.key {
+                        self.visit_expression(key);
+                    }
+
+                    if item.by_ref {
+                        self.reference(&item.value);
+                    } else {
+                        self.visit_expression(&item.value);
+                    }
+                }
+            },
+            ExpressionKind::ErrorSuppress { value } => self.quietly(value),
+            ExpressionKind::Throw { value } => {
+                self.visit_expression(value);
+                self.scope.state = State::unreachable();
+            },
+            ExpressionKind::Exit { value } => {
+                if let Some(value) = value {
+                    self.visit_expression(value);
+                }
+
+                self.scope.state = State::unreachable();
+            },
+            ExpressionKind::Closure { params, uses, body, .. } => {
+                let mut state = State::default();
+
+                for r#use in uses {
+                    if r#use.by_ref {
+                        self.reference(&r#use.var);
+                    } else {
+                        self.visit_expression(&r#use.var);
+                    }
+
+                    if let ExpressionKind::Variable { name } = &r#use.var.kind {
+                        state.define(name);
+                    }
+                }
+
+                self.function(params, Body::Block(body), state, false, true);
+            },
+            // Arrow functions capture the whole of the scope that they're in, by value.
+            ExpressionKind::ArrowFunction { params, expr, .. } => {
+                let state = State { unreachable: false, ..self.scope.state.clone() };
+                let scope = self.function(params, Body::Expression(expr), state, self.scope.dynamic, true);
+
+                self.scope.used.extend(scope.used);
+            },
+            ExpressionKind::AnonymousClass { body, .. } => self.block(body),
+            _ => walk_expression(self, expression),
+        }
+    }
+}
+
+/// Whether a function's body can define variables without naming them.
+fn defines_any(body: &Body) -> bool {
+    #[derive(Default)]
+    struct Finder(bool);
+
+    impl Visitor for Finder {
+        fn visit_expression(&mut self, expression: &Expression) {
+            match &expression.kind {
+                ExpressionKind::Include { .. } | ExpressionKind::IncludeOnce { .. } | ExpressionKind::Require { .. } | ExpressionKind::RequireOnce { .. } => {
+                    self.0 = true;
+                },
+                ExpressionKind::Call { target, .. } if DEFINING_FUNCTIONS.iter().any(|function| is_call_to(target, function)) => self.0 = true,
+                _ => {},
+            }
+
+            walk_expression(self, expression);
+        }
+    }
+
+    let mut finder = Finder::default();
+    match body {
+        Body::Block(block) => block.iter().for_each(|statement| finder.visit_statement(statement)),
+        Body::Expression(expression) => finder.visit_expression(expression),
+    }
+
+    finder.0
+}
+
+/// The variables that some code assigns to, outside of any functions that it declares.
+#[derive(Default)]
+struct Assignments(HashSet<String>);
+
+impl Assignments {
+    fn block(&mut self, block: &[Statement]) {
+        for statement in block {
+            self.visit_statement(statement);
+        }
+    }
+
+    fn target(&mut self, target: &Expression) {
+        match &target.kind {
+            ExpressionKind::Variable { name } => {
+                self.0.insert(name.to_string());
+            },
+            ExpressionKind::ArrayIndex { array, .. } => self.target(array),
+            ExpressionKind::Array { items } => items.iter().for_each(|item| self.target(&item.value)),
+            ExpressionKind::Call { target: function, args } if is_call_to(function, "list") => args.iter().for_each(|arg| self.target(&arg.value)),
+            _ => {},
+        }
+    }
+}
+
+impl Visitor for Assignments {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::Function { .. } | StatementKind::Class { .. } | StatementKind::Trait { .. } | StatementKind::Interface { .. } |
+            StatementKind::Enum { .. } => return,
+            StatementKind::Foreach { key_var, value_var, .. } => {
+                key_var.iter().chain([value_var]).for_each(|var| self.target(var));
+            },
+            StatementKind::Static { vars } => vars.iter().for_each(|var| self.target(&var.var)),
+            StatementKind::Global { vars } => vars.iter().for_each(|var| self.target(var)),
+            StatementKind::Try { catches, .. } => catches.iter().for_each(|catch| self.target(&catch.var)),
+            _ => {},
+        }
+
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::Closure { .. } | ExpressionKind::ArrowFunction { .. } | ExpressionKind::AnonymousClass { .. } => return,
+            ExpressionKind::Assign { var, .. } | ExpressionKind::CoalesceAssign { var, .. } => self.target(var),
+            ExpressionKind::AssignRef { var, value } => {
+                self.target(var);
+                self.target(value);
+            },
+            ExpressionKind::Infix { lhs, op, .. } if op.is_assignment() => {
+                self.target(lhs);
+            },
+            _ => {},
+        }
+
+        walk_expression(self, expression);
+    }
+}
+
+fn is_call_to(target: &Expression, function: &str) -> bool {
+    matches!(&target.kind, ExpressionKind::Identifier { name } if name.trim_start_matches('\\').eq_ignore_ascii_case(function))
+}
+
+/// The variable at the bottom of a chain of array and property accesses, such as `$a` in
+/// `$a['b']->c`.
+fn base_variable(expression: &Expression) -> Option<&str> {
+    match &expression.kind {
+        ExpressionKind::Variable { name } => Some(name),
+        ExpressionKind::ArrayIndex { array: target, .. } |
+        ExpressionKind::PropertyFetch { target, .. } |
+        ExpressionKind::NullsafePropertyFetch { target, .. } => base_variable(target),
+        _ => None,
+    }
+}
+
+/// The variables that a condition proves are set when it has the given outcome.
+fn narrowed<'a>(condition: &'a Expression, outcome: bool, names: &mut Vec<&'a str>) {
+    match &condition.kind {
+        ExpressionKind::Call { target, args } if (outcome && is_call_to(target, "isset")) || (!outcome && is_call_to(target, "empty")) => {
+            names.extend(args.iter().filter_map(|arg| base_variable(&arg.value)));
+        },
+        ExpressionKind::BooleanNot { value } => narrowed(value, !outcome, names),
+        ExpressionKind::Infix { lhs, op: InfixOp::And | InfixOp::LogicalAnd, rhs } if outcome => {
+            narrowed(lhs, outcome, names);
+            narrowed(rhs, outcome, names);
+        },
+        ExpressionKind::Infix { lhs, op: InfixOp::Or | InfixOp::LogicalOr, rhs } if !outcome => {
+            narrowed(lhs, outcome, names);
+            narrowed(rhs, outcome, names);
+        },
+        _ => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_parser::parse;
+
+    use super::check_scopes;
+
+    /// Check a program, giving each diagnostic as its code, message and line.
+    fn check(source: &str) -> Vec<String> {
+        let program = parse(source).unwrap();
+
+        check_scopes(&program)
+            .iter()
+            .map(|diagnostic| format!("{}: {} on line {}", diagnostic.code, diagnostic.message, diagnostic.span.unwrap().line))
+            .collect()
+    }
+
+    #[test]
+    fn undefined_variables() {
+        assert_eq!(check("<?php
+function f($a) {
+    echo $a, $b;
+    $c = 1;
+    echo $c, $b;
+    if ($a) {
+        $d = 1;
+    } else {
+        $e = 1;
+        return;
+    }
+    echo $d, $e;
+}
+"), [
+            "undefined-variable: undefined variable `$b` on line 3",
+            "undefined-variable: undefined variable `$e` on line 12",
+        ]);
+
+        assert_eq!(check("<?php
+function f($a) {
+    if ($a) { $b = 1; }
+    echo $b;
+    foreach ($a as $k => $v) { $last = $v; }
+    echo $k, $last;
+    while (true) { $found = 1; break; }
+    echo $found;
+    try { $r = g(); } catch (Exception $e) { echo $e, $r; }
+}
+"), [
+            "undefined-variable: variable `$b` might not be defined on line 4",
+            "undefined-variable: variable `$k` might not be defined on line 6",
+            "undefined-variable: variable `$last` might not be defined on line 6",
+            "undefined-variable: variable `$r` might not be defined on line 9",
+        ]);
+    }
+
+    #[test]
+    fn tests_and_definitions() {
+        assert_eq!(check("<?php
+function f($a) {
+    echo isset($x) ? $x : 0, $y ?? 1, @$z, empty($w['k']) ? '' : $w['k'];
+    if (!isset($v)) { return; }
+    echo $v;
+    preg_match('/a/', $a, $matches);
+    [$p, [$q]] = $a;
+    $list[] = $p + $q;
+    static $count = 0;
+    global $config;
+    $u ??= 1;
+    echo $matches, $list, $count, $config, $u, $this, $_GET;
+    unset($u);
+    echo $u;
+}
+
+class A {
+    public function m() {
+        return compact('missing') + A::$property;
+    }
+}
+"), [
+            "undefined-variable: undefined variable `$u` on line 14",
+            "undefined-variable: undefined variable `$missing` on line 19",
+        ]);
+
+        assert_eq!(check("<?php echo $top; function f() { extract([]); echo $a; }"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn closures() {
+        assert_eq!(check("<?php
+function f($a) {
+    $g = function ($x) use ($a, &$out) { echo $x, $a, $b; $out = 1; };
+    echo $out;
+    $h = fn ($y) => $a + $y + $c;
+    $i = function () { echo $a; };
+}
+"), [
+            "undefined-variable: undefined variable `$b` on line 3",
+            "undefined-variable: undefined variable `$c` on line 5",
+            "undefined-variable: undefined variable `$a` on line 6",
+        ]);
+    }
+
+    #[test]
+    fn by_ref_functions() {
+        assert_eq!(check("<?php
+function fill(&$into) { $into = 1; }
+function f() { fill($filled); echo $filled; }
+"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn unused_parameters() {
+        assert_eq!(check("<?php
+function f($a, $b, $c) { return $b; }
+function g($_unused, ...$rest) { return func_get_args(); }
+$h = fn ($x) => 1;
+class A {
+    public function __construct(private $promoted, $public) {}
+    private function m($used, $unused) { return $used; }
+}
+"), [
+            "unused-parameter: parameter `$c` is never used on line 2",
+            "unused-parameter: parameter `$x` is never used on line 4",
+            "unused-parameter: parameter `$unused` is never used on line 7",
+        ]);
+    }
+}
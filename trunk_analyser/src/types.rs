@@ -0,0 +1,862 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::mem;
+
+use trunk_docblock::{DocBlock, DocType, Template};
+use trunk_parser::{
+    walk_expression, walk_statement, CastKind, Expression, ExpressionKind, InfixOp, MagicConst, Param, Program, Statement, StatementKind, Type,
+    Visitor,
+};
+
+use crate::names::Imports;
+use crate::NameKind;
+
+/// Identifies an expression in a program by the source that it spans. Parentheses don't make nodes
+/// of their own, so no two expressions that are parsed from the same source span the same range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl NodeId {
+    pub fn of(expression: &Expression) -> Self {
+        Self { start: expression.span.start, end: expression.span.end }
+    }
+}
+
+/// The type of a value, as far as it can be worked out without running the program.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InferredType {
+    Int,
+    Float,
+    String,
+    Bool,
+    Null,
+    Array,
+    Callable,
+    /// An object of a class that isn't known.
+    Object,
+    /// An object of a class, by its fully qualified name without a leading `\`.
+    Class(String),
+    Void,
+    Never,
+    Mixed,
+    /// A value of any of several types. Unions are kept flat, without duplicates.
+    Union(Vec<InferredType>),
+}
+
+impl InferredType {
+    /// The type of a value of any of the given types.
+    pub fn union(types: impl IntoIterator<Item = InferredType>) -> Self {
+        let mut members: Vec<InferredType> = Vec::new();
+
+        for r#type in types {
+            let flattened = match r#type {
+                Self::Union(types) => types,
+                r#type => vec![r#type],
+            };
+
+            for r#type in flattened {
+                if r#type == Self::Mixed {
+                    return Self::Mixed;
+                }
+
+                if !members.contains(&r#type) {
+                    members.push(r#type);
+                }
+            }
+        }
+
+        match members.len() {
+            1 => members.remove(0),
+            _ => Self::Union(members),
+        }
+    }
+
+    /// This type, without `null`.
+    pub fn without_null(self) -> Self {
+        match self {
+            Self::Union(types) => Self::union(types.into_iter().filter(|r#type| *r#type != Self::Null)),
+            r#type => r#type,
+        }
+    }
+
+    /// The class that every value of this type is an object of, if there is one.
+    pub fn class(&self) -> Option<&str> {
+        match self {
+            Self::Class(class) => Some(class),
+            _ => None,
+        }
+    }
+}
+
+impl Display for InferredType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int => write!(f, "int"),
+            Self::Float => write!(f, "float"),
+            Self::String => write!(f, "string"),
+            Self::Bool => write!(f, "bool"),
+            Self::Null => write!(f, "null"),
+            Self::Array => write!(f, "array"),
+            Self::Callable => write!(f, "callable"),
+            Self::Object => write!(f, "object"),
+            Self::Class(class) => write!(f, "{}", class),
+            Self::Void => write!(f, "void"),
+            Self::Never => write!(f, "never"),
+            Self::Mixed => write!(f, "mixed"),
+            Self::Union(types) => {
+                for (i, r#type) in types.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "|")?;
+                    }
+
+                    write!(f, "{}", r#type)?;
+                }
+
+                Ok(())
+            },
+        }
+    }
+}
+
+/// The types that were inferred for the expressions of a program. Expressions whose types couldn't
+/// be worked out aren't in the table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypeTable {
+    types: HashMap<NodeId, InferredType>,
+}
+
+impl TypeTable {
+    pub fn get(&self, expression: &Expression) -> Option<&InferredType> {
+        self.types.get(&NodeId::of(expression))
+    }
+
+    pub fn get_by_id(&self, id: NodeId) -> Option<&InferredType> {
+        self.types.get(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&NodeId, &InferredType)> {
+        self.types.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+}
+
+/// Infer the types of the expressions in a program from literals, parameter and return type
+/// declarations, `new` expressions and assignments, along with operators and casts whose results
+/// have a fixed type.
+///
+/// Variables are followed through each function in source order. A variable that's assigned in a
+/// branch or a loop has the union of the types it's been given so far, rather than the type of the
+/// latest assignment. Methods and properties are only found on the class that declares them, and
+/// not on its parents.
+pub fn infer_types(program: &Program) -> TypeTable {
+    let mut declarations = Declarations::default();
+    declarations.visit_program(program);
+
+    let mut inferrer = Inferrer { declarations, ..Inferrer::default() };
+    inferrer.visit_program(program);
+
+    inferrer.table
+}
+
+/// Convert a type declaration, in a class if it's in one.
+fn declared(r#type: &Type, imports: &Imports, class: Option<&str>) -> InferredType {
+    match r#type {
+        Type::Plain(name) => named(name, imports, class),
+        Type::Nullable(name) => InferredType::union([named(name, imports, class), InferredType::Null]),
+        Type::Union(types) => InferredType::union(types.iter().map(|r#type| declared(r#type, imports, class))),
+        Type::Intersection(_) => InferredType::Object,
+    }
+}
+
+fn named(name: &str, imports: &Imports, class: Option<&str>) -> InferredType {
+    match name.to_ascii_lowercase().as_str() {
+        "int" => InferredType::Int,
+        "float" => InferredType::Float,
+        "string" => InferredType::String,
+        "bool" | "false" | "true" => InferredType::Bool,
+        "null" => InferredType::Null,
+        "array" => InferredType::Array,
+        "iterable" => InferredType::union([InferredType::Array, InferredType::Object]),
+        "callable" => InferredType::Callable,
+        "object" | "parent" => InferredType::Object,
+        "void" => InferredType::Void,
+        "never" => InferredType::Never,
+        "mixed" => InferredType::Mixed,
+        "self" | "static" => class.map_or(InferredType::Object, |class| InferredType::Class(class.to_string())),
+        _ => InferredType::Class(imports.expand(name, NameKind::Class).0),
+    }
+}
+
+/// Convert a type from a docblock, for a declaration that doesn't have a type of its own.
+fn documented(r#type: &DocType, templates: &[Template], imports: &Imports, class: Option<&str>) -> InferredType {
+    declared(&r#type.to_type(templates), imports, class)
+}
+
+/// The templates that a function or method's docblock can use: its class's and its own.
+fn templates(class: &[Template], docblock: Option<&DocBlock>) -> Vec<Template> {
+    class.iter().chain(docblock.into_iter().flat_map(DocBlock::templates)).cloned().collect()
+}
+
+/// The type of a parameter from its declaration, or from the `@param` tag in its function's
+/// docblock if it doesn't have one.
+fn param_type(param: &Param, docblock: Option<&DocBlock>, templates: &[Template], imports: &Imports, class: Option<&str>) -> Option<InferredType> {
+    if param.r#type.is_none() && ! param.variadic {
+        let r#type = docblock?.param(param.variable_name()?)?;
+        return Some(documented(r#type, templates, imports, class));
+    }
+
+    declared_param_type(param, imports, class)
+}
+
+fn declared_param_type(param: &Param, imports: &Imports, class: Option<&str>) -> Option<InferredType> {
+    if param.variadic {
+        return Some(InferredType::Array);
+    }
+
+    let r#type = declared(param.r#type.as_ref()?, imports, class);
+
+    match param.default.as_ref().map(|default| &default.kind) {
+        Some(ExpressionKind::Null) => Some(InferredType::union([r#type, InferredType::Null])),
+        _ => Some(r#type),
+    }
+}
+
+/// The declared types of the functions, methods and properties of a program. Names are fully
+/// qualified and in lowercase, apart from the names of properties.
+#[derive(Default)]
+struct Declarations {
+    imports: Imports,
+    class: Option<String>,
+    /// The templates of the class that's being looked at, from its docblock.
+    templates: Vec<Template>,
+    functions: HashMap<String, InferredType>,
+    methods: HashMap<(String, String), InferredType>,
+    properties: HashMap<(String, String), InferredType>,
+}
+
+impl Declarations {
+    fn function(&self, name: &str, fallback: Option<&str>) -> Option<InferredType> {
+        self.functions.get(&name.to_ascii_lowercase())
+            .or_else(|| self.functions.get(&fallback?.to_ascii_lowercase()))
+            .cloned()
+    }
+
+    fn method(&self, class: &str, method: &str) -> Option<InferredType> {
+        self.methods.get(&(class.to_ascii_lowercase(), method.to_ascii_lowercase())).cloned()
+    }
+
+    fn property(&self, class: &str, property: &str) -> Option<InferredType> {
+        self.properties.get(&(class.to_ascii_lowercase(), property.to_string())).cloned()
+    }
+}
+
+impl Visitor for Declarations {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::Namespace { name, body } => {
+                self.imports.enter_namespace(name);
+
+                if !body.is_empty() {
+                    walk_statement(self, statement);
+                    self.imports.enter_namespace("");
+                }
+
+                return;
+            },
+            StatementKind::Use { uses, kind } => {
+                for r#use in uses {
+                    self.imports.import(r#use, kind);
+                }
+            },
+            StatementKind::Class { name, .. } | StatementKind::Trait { name, .. } | StatementKind::Interface { name, .. } |
+            StatementKind::Enum { name, .. } => {
+                let outer = self.class.replace(self.imports.qualify(&name.name));
+                let templates = DocBlock::of(statement).map(|docblock| docblock.templates().cloned().collect()).unwrap_or_default();
+                let outer_templates = mem::replace(&mut self.templates, templates);
+                walk_statement(self, statement);
+                self.class = outer;
+                self.templates = outer_templates;
+
+                return;
+            },
+            StatementKind::Function { name, return_type, .. } => {
+                let docblock = DocBlock::of(statement);
+                let templates = templates(&[], docblock.as_ref());
+                let r#type = match (return_type, docblock.as_ref().and_then(DocBlock::return_type)) {
+                    (Some(return_type), _) => Some(declared(return_type, &self.imports, None)),
+                    (None, Some(documented_type)) => Some(documented(documented_type, &templates, &self.imports, None)),
+                    (None, None) => None,
+                };
+
+                if let Some(r#type) = r#type {
+                    self.functions.insert(self.imports.qualify(&name.name).to_ascii_lowercase(), r#type);
+                }
+            },
+            StatementKind::Method { name, params, return_type, .. } => {
+                if let Some(class) = &self.class {
+                    let class = class.to_ascii_lowercase();
+                    let docblock = DocBlock::of(statement);
+                    let templates = templates(&self.templates, docblock.as_ref());
+                    let r#type = match (return_type, docblock.as_ref().and_then(DocBlock::return_type)) {
+                        (Some(return_type), _) => Some(declared(return_type, &self.imports, self.class.as_deref())),
+                        (None, Some(documented_type)) => Some(documented(documented_type, &templates, &self.imports, self.class.as_deref())),
+                        (None, None) => None,
+                    };
+
+                    if let Some(r#type) = r#type {
+                        self.methods.insert((class.clone(), name.name.to_ascii_lowercase()), r#type);
+                    }
+
+                    // Promoted constructor parameters declare properties.
+                    for param in params.iter().filter(|param| !param.flags.is_empty()) {
+                        let r#type = param_type(param, docblock.as_ref(), &templates, &self.imports, self.class.as_deref());
+
+                        if let (Some(name), Some(r#type)) = (param.variable_name(), r#type) {
+                            self.properties.insert((class.clone(), name.to_string()), r#type);
+                        }
+                    }
+                }
+            },
+            StatementKind::Property { var, r#type: Some(r#type), .. } | StatementKind::Var { var, r#type: Some(r#type), .. } => {
+                if let Some(class) = &self.class {
+                    let r#type = declared(r#type, &self.imports, Some(class));
+                    self.properties.insert((class.to_ascii_lowercase(), var.to_string()), r#type);
+                }
+            },
+            StatementKind::Property { var, r#type: None, .. } => {
+                let documented_type = DocBlock::of(statement).and_then(|docblock| docblock.var(var).cloned());
+
+                if let (Some(class), Some(documented_type)) = (&self.class, documented_type) {
+                    let r#type = documented(&documented_type, &self.templates, &self.imports, Some(class));
+                    self.properties.insert((class.to_ascii_lowercase(), var.to_string()), r#type);
+                }
+            },
+            _ => {},
+        }
+
+        walk_statement(self, statement);
+    }
+}
+
+#[derive(Default)]
+struct Inferrer {
+    declarations: Declarations,
+    imports: Imports,
+    class: Option<String>,
+    /// The templates of the class that's being looked at, from its docblock.
+    templates: Vec<Template>,
+    /// The variables of the function that's being looked at, with their types, or `None` for a
+    /// variable whose type isn't known.
+    variables: HashMap<String, Option<InferredType>>,
+    /// How many branches and loops the current code is in.
+    branches: usize,
+    table: TypeTable,
+}
+
+impl Inferrer {
+    fn infer(&mut self, expression: &Expression) -> Option<InferredType> {
+        let r#type = match &expression.kind {
+            ExpressionKind::Int { .. } => Some(InferredType::Int),
+            ExpressionKind::Float { .. } => Some(InferredType::Float),
+            ExpressionKind::Bool { .. } => Some(InferredType::Bool),
+            ExpressionKind::Null => Some(InferredType::Null),
+            ExpressionKind::ConstantString { .. } | ExpressionKind::Nowdoc { .. } => Some(InferredType::String),
+            ExpressionKind::MagicConst { constant: MagicConst::Line } => Some(InferredType::Int),
+            ExpressionKind::MagicConst { .. } => Some(InferredType::String),
+            ExpressionKind::InterpolatedString { .. } | ExpressionKind::Heredoc { .. } => self.walk(expression, InferredType::String),
+            ExpressionKind::Array { .. } => self.walk(expression, InferredType::Array),
+            ExpressionKind::Variable { name } if name == "this" => self.class.clone().map(InferredType::Class),
+            ExpressionKind::Variable { name } => self.variables.get(name).cloned().flatten(),
+            ExpressionKind::Assign { var, value } | ExpressionKind::AssignRef { var, value } => {
+                let r#type = self.infer(value);
+                self.assign(var, r#type.clone());
+
+                r#type
+            },
+            ExpressionKind::CoalesceAssign { var, value } => {
+                let r#type = union(self.infer(var).map(InferredType::without_null), self.infer(value));
+                self.assign(var, r#type.clone());
+
+                r#type
+            },
+            ExpressionKind::Infix { lhs, op, rhs } => {
+                let r#type = infix(op, self.infer(lhs), self.infer(rhs));
+
+                if op.is_assignment() {
+                    self.assign(lhs, r#type.clone());
+                }
+
+                r#type
+            },
+            ExpressionKind::Cast { kind, .. } => self.walk(expression, match kind {
+                CastKind::String => InferredType::String,
+                CastKind::Object => InferredType::Object,
+                CastKind::Bool => InferredType::Bool,
+                CastKind::Int => InferredType::Int,
+                CastKind::Double => InferredType::Float,
+                CastKind::Array => InferredType::Array,
+            }),
+            ExpressionKind::BooleanNot { .. } | ExpressionKind::Instanceof { .. } => self.walk(expression, InferredType::Bool),
+            ExpressionKind::BitwiseNot { .. } | ExpressionKind::Print { .. } => self.walk(expression, InferredType::Int),
+            ExpressionKind::Negate { value } | ExpressionKind::UnaryPlus { value } | ExpressionKind::PreIncrement { value } |
+            ExpressionKind::PostIncrement { value } | ExpressionKind::PreDecrement { value } | ExpressionKind::PostDecrement { value } |
+            ExpressionKind::Clone { target: value } => self.infer(value),
+            ExpressionKind::Ternary { condition, then, r#else } => {
+                let condition = self.infer(condition);
+                let then = match then {
+                    Some(then) => self.infer(then),
+                    None => condition,
+                };
+
+                union(then, self.infer(r#else))
+            },
+            ExpressionKind::Coalesce { lhs, rhs } => union(self.infer(lhs).map(InferredType::without_null), self.infer(rhs)),
+            ExpressionKind::Match { condition, arms } => {
+                self.infer(condition);
+
+                let mut r#type = Some(InferredType::Never);
+                for arm in arms {
+                    for condition in arm.conditions.iter().flatten() {
+                        self.infer(condition);
+                    }
+
+                    r#type = union(r#type, self.infer(&arm.body));
+                }
+
+                r#type
+            },
+            ExpressionKind::Throw { .. } | ExpressionKind::Exit { .. } => self.walk(expression, InferredType::Never),
+            ExpressionKind::Call { target, args } => match &target.kind {
+                ExpressionKind::Identifier { name } if ["isset", "empty"].iter().any(|construct| name.eq_ignore_ascii_case(construct)) => {
+                    self.walk(expression, InferredType::Bool)
+                },
+                ExpressionKind::Identifier { name } => {
+                    args.iter().for_each(|arg| self.visit_expression(&arg.value));
+
+                    let (name, fallback) = self.imports.expand(name, NameKind::Function);
+                    self.declarations.function(&name, fallback.as_deref())
+                },
+                _ => {
+                    walk_expression(self, expression);
+                    None
+                },
+            },
+            ExpressionKind::New { target, args } => {
+                args.iter().for_each(|arg| self.visit_expression(&arg.value));
+
+                match &target.kind {
+                    ExpressionKind::Identifier { name } => self.class_named(name),
+                    _ => {
+                        self.visit_expression(target);
+                        Some(InferredType::Object)
+                    },
+                }
+            },
+            ExpressionKind::MethodCall { target, method, args } | ExpressionKind::NullsafeMethodCall { target, method, args } => {
+                let class = self.infer(target).map(InferredType::without_null);
+                self.visit_expression(method);
+                args.iter().for_each(|arg| self.visit_expression(&arg.value));
+
+                let r#type = match (class.as_ref().and_then(InferredType::class), &method.kind) {
+                    (Some(class), ExpressionKind::Identifier { name }) => self.declarations.method(class, name),
+                    _ => None,
+                };
+
+                match &expression.kind {
+                    ExpressionKind::NullsafeMethodCall { .. } => r#type.map(|r#type| InferredType::union([r#type, InferredType::Null])),
+                    _ => r#type,
+                }
+            },
+            ExpressionKind::StaticMethodCall { target, method, args } => {
+                args.iter().for_each(|arg| self.visit_expression(&arg.value));
+
+                match &target.kind {
+                    ExpressionKind::Identifier { name } => {
+                        let class = self.class_named(name);
+                        self.declarations.method(class.as_ref()?.class()?, &method.name)
+                    },
+                    _ => {
+                        self.visit_expression(target);
+                        None
+                    },
+                }
+            },
+            ExpressionKind::PropertyFetch { target, property } | ExpressionKind::NullsafePropertyFetch { target, property } => {
+                let class = self.infer(target).map(InferredType::without_null);
+                self.visit_expression(property);
+
+                let r#type = match (class.as_ref().and_then(InferredType::class), &property.kind) {
+                    (Some(class), ExpressionKind::Identifier { name }) => self.declarations.property(class, name),
+                    _ => None,
+                };
+
+                match &expression.kind {
+                    ExpressionKind::NullsafePropertyFetch { .. } => r#type.map(|r#type| InferredType::union([r#type, InferredType::Null])),
+                    _ => r#type,
+                }
+            },
+            ExpressionKind::Closure { params, uses, body, .. } => {
+                let mut variables = HashMap::new();
+                for r#use in uses {
+                    if let ExpressionKind::Variable { name } = &r#use.var.kind {
+                        let r#type = self.infer(&r#use.var);
+                        variables.insert(name.to_string(), r#type);
+                    }
+                }
+
+                self.function(params, None, variables, |inferrer| body.iter().for_each(|statement| inferrer.visit_statement(statement)));
+
+                Some(InferredType::Class("Closure".to_string()))
+            },
+            // Arrow functions capture the whole of the scope that they're in, by value.
+            ExpressionKind::ArrowFunction { params, expr, .. } => {
+                self.function(params, None, self.variables.clone(), |inferrer| inferrer.visit_expression(expr));
+
+                Some(InferredType::Class("Closure".to_string()))
+            },
+            ExpressionKind::AnonymousClass { .. } => self.walk(expression, InferredType::Object),
+            _ => {
+                walk_expression(self, expression);
+                None
+            },
+        };
+
+        if let Some(r#type) = &r#type {
+            self.table.types.insert(NodeId::of(expression), r#type.clone());
+        }
+
+        r#type
+    }
+
+    /// Visit the parts of an expression whose type doesn't depend on them.
+    fn walk(&mut self, expression: &Expression, r#type: InferredType) -> Option<InferredType> {
+        walk_expression(self, expression);
+
+        Some(r#type)
+    }
+
+    fn class_named(&self, name: &str) -> Option<InferredType> {
+        match name.to_ascii_lowercase().as_str() {
+            "self" | "static" => self.class.clone().map(InferredType::Class),
+            "parent" => Some(InferredType::Object),
+            _ => Some(InferredType::Class(self.imports.expand(name, NameKind::Class).0)),
+        }
+    }
+
+    fn assign(&mut self, target: &Expression, r#type: Option<InferredType>) {
+        match &target.kind {
+            ExpressionKind::Variable { name } => {
+                // An assignment in a branch or a loop might not happen, so the variable could still
+                // have the type it had before.
+                let r#type = match self.variables.get(name) {
+                    Some(previous) if self.branches > 0 => union(previous.clone(), r#type),
+                    _ => r#type,
+                };
+
+                if let Some(r#type) = &r#type {
+                    self.table.types.insert(NodeId::of(target), r#type.clone());
+                }
+
+                self.variables.insert(name.to_string(), r#type);
+            },
+            ExpressionKind::Array { items } => {
+                for item in items {
+                    if let Some(key) = &item.key {
+                        self.visit_expression(key);
+                    }
+
+                    self.assign(&item.value, None);
+                }
+            },
+            ExpressionKind::Call { target: function, args } if matches!(&function.kind, ExpressionKind::Identifier { name } if name.eq_ignore_ascii_case("list")) => {
+                for arg in args {
+                    self.assign(&arg.value, None);
+                }
+            },
+            _ => self.visit_expression(target),
+        }
+    }
+
+    /// Look at a function, method or closure with variables of its own, starting with `variables`.
+    fn function(
+        &mut self,
+        params: &[Param],
+        docblock: Option<&DocBlock>,
+        mut variables: HashMap<String, Option<InferredType>>,
+        body: impl FnOnce(&mut Self),
+    ) {
+        let templates = templates(&self.templates, docblock);
+
+        for param in params {
+            if let Some(name) = param.variable_name() {
+                let r#type = param_type(param, docblock, &templates, &self.imports, self.class.as_deref());
+
+                if let Some(r#type) = &r#type {
+                    self.table.types.insert(NodeId::of(&param.name), r#type.clone());
+                }
+
+                variables.insert(name.to_string(), r#type);
+            }
+        }
+
+        let outer = mem::replace(&mut self.variables, variables);
+        let branches = mem::take(&mut self.branches);
+
+        body(self);
+
+        self.variables = outer;
+        self.branches = branches;
+    }
+}
+
+impl Visitor for Inferrer {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::Namespace { name, body } => {
+                self.imports.enter_namespace(name);
+
+                if !body.is_empty() {
+                    walk_statement(self, statement);
+                    self.imports.enter_namespace("");
+                }
+            },
+            StatementKind::Use { uses, kind } => {
+                for r#use in uses {
+                    self.imports.import(r#use, kind);
+                }
+            },
+            StatementKind::Class { name, .. } | StatementKind::Trait { name, .. } | StatementKind::Interface { name, .. } |
+            StatementKind::Enum { name, .. } => {
+                let outer = self.class.replace(self.imports.qualify(&name.name));
+                let templates = DocBlock::of(statement).map(|docblock| docblock.templates().cloned().collect()).unwrap_or_default();
+                let outer_templates = mem::replace(&mut self.templates, templates);
+                walk_statement(self, statement);
+                self.class = outer;
+                self.templates = outer_templates;
+            },
+            StatementKind::Function { params, body, .. } | StatementKind::Method { params, body, .. } => {
+                let docblock = DocBlock::of(statement);
+                self.function(params, docblock.as_ref(), HashMap::new(), |inferrer| body.iter().for_each(|statement| inferrer.visit_statement(statement)));
+            },
+            StatementKind::Foreach { expr, key_var, value_var, body, .. } => {
+                self.visit_expression(expr);
+                self.branches += 1;
+
+                if let Some(key_var) = key_var {
+                    self.assign(key_var, Some(InferredType::union([InferredType::Int, InferredType::String])));
+                }
+
+                self.assign(value_var, None);
+                body.iter().for_each(|statement| self.visit_statement(statement));
+                self.branches -= 1;
+            },
+            StatementKind::Try { body, catches, finally } => {
+                self.branches += 1;
+                body.iter().for_each(|statement| self.visit_statement(statement));
+
+                for catch in catches {
+                    let types = catch.types.iter().map(|r#type| InferredType::Class(self.imports.expand(&r#type.name, NameKind::Class).0));
+                    self.assign(&catch.var, Some(InferredType::union(types.collect::<Vec<_>>())));
+                    catch.body.iter().for_each(|statement| self.visit_statement(statement));
+                }
+
+                self.branches -= 1;
+                finally.iter().flatten().for_each(|statement| self.visit_statement(statement));
+            },
+            StatementKind::Static { vars } => {
+                for var in vars {
+                    let r#type = var.default.as_ref().and_then(|default| self.infer(default));
+                    self.assign(&var.var, r#type);
+                }
+            },
+            StatementKind::Global { vars } => {
+                for var in vars {
+                    self.assign(var, None);
+                }
+            },
+            StatementKind::If { .. } | StatementKind::While { .. } | StatementKind::For { .. } | StatementKind::Switch { .. } => {
+                self.branches += 1;
+                walk_statement(self, statement);
+                self.branches -= 1;
+            },
+            _ => walk_statement(self, statement),
+        }
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        self.infer(expression);
+    }
+}
+
+/// The union of two types, if both are known.
+fn union(a: Option<InferredType>, b: Option<InferredType>) -> Option<InferredType> {
+    Some(InferredType::union([a?, b?]))
+}
+
+fn infix(op: &InfixOp, lhs: Option<InferredType>, rhs: Option<InferredType>) -> Option<InferredType> {
+    match op {
+        InfixOp::Concat | InfixOp::ConcatAssign => Some(InferredType::String),
+        InfixOp::LessThan | InfixOp::GreaterThan | InfixOp::LessThanEquals | InfixOp::GreaterThanEquals | InfixOp::Equals |
+        InfixOp::Identical | InfixOp::NotEquals | InfixOp::NotIdentical | InfixOp::And | InfixOp::Or | InfixOp::LogicalAnd |
+        InfixOp::LogicalOr | InfixOp::LogicalXor => Some(InferredType::Bool),
+        InfixOp::Spaceship | InfixOp::Mod | InfixOp::LeftShift | InfixOp::RightShift | InfixOp::BitwiseAnd | InfixOp::BitwiseOr |
+        InfixOp::BitwiseXor | InfixOp::ModAssign | InfixOp::LeftShiftAssign | InfixOp::RightShiftAssign | InfixOp::BitwiseAndAssign |
+        InfixOp::BitwiseOrAssign | InfixOp::BitwiseXorAssign => Some(InferredType::Int),
+        InfixOp::Div | InfixOp::DivAssign => Some(InferredType::union([InferredType::Int, InferredType::Float])),
+        InfixOp::Add | InfixOp::Sub | InfixOp::Mul | InfixOp::Pow | InfixOp::AddAssign | InfixOp::SubAssign | InfixOp::MulAssign |
+        InfixOp::PowAssign => {
+            match (lhs?, rhs?) {
+                (InferredType::Int, InferredType::Int) => Some(InferredType::Int),
+                (InferredType::Int | InferredType::Float, InferredType::Int | InferredType::Float) => Some(InferredType::Float),
+                (InferredType::Array, InferredType::Array) if matches!(op, InfixOp::Add | InfixOp::AddAssign) => Some(InferredType::Array),
+                _ => None,
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_parser::{parse, walk_expression, Expression, ExpressionKind, Visitor};
+
+    use super::{infer_types, InferredType, TypeTable};
+
+    /// The inferred type of every variable in a program, in the order they appear, with `?` for
+    /// variables whose types aren't known.
+    fn variables(source: &str) -> Vec<String> {
+        struct Collector<'a>(&'a TypeTable, Vec<String>);
+
+        impl Visitor for Collector<'_> {
+            fn visit_expression(&mut self, expression: &Expression) {
+                if let ExpressionKind::Variable { name } = &expression.kind {
+                    let r#type = self.0.get(expression).map_or("?".to_string(), InferredType::to_string);
+                    self.1.push(format!("${}: {}", name, r#type));
+                }
+
+                walk_expression(self, expression);
+            }
+        }
+
+        let program = parse(source).unwrap();
+        let table = infer_types(&program);
+        let mut collector = Collector(&table, Vec::new());
+        collector.visit_program(&program);
+
+        collector.1
+    }
+
+    #[test]
+    fn literals_and_operators() {
+        assert_eq!(variables("<?php
+$a = 1;
+$b = $a + 2.5;
+$c = $a . 'x';
+$d = [$a] + [];
+$e = (bool) $c;
+$f = $g ?? null;
+$h = $a > 1 ? 'yes' : null;
+$i = $a / 2;
+$a .= '';
+"), [
+            "$a: int", "$b: float", "$a: int", "$c: string", "$a: int", "$d: array", "$a: int", "$e: bool", "$c: string",
+            "$f: ?", "$g: ?", "$h: string|null", "$a: int", "$i: int|float", "$a: int", "$a: string",
+        ]);
+    }
+
+    #[test]
+    fn declarations() {
+        assert_eq!(variables("<?php
+namespace App;
+
+use Lib\\Clock;
+
+class User {
+    public ?Clock $clock;
+
+    public function __construct(private string $name) {}
+
+    public function name(): string { return $this->name; }
+
+    public function copy(): static { return $this; }
+}
+
+function make(int $id, ?User $user = null, ...$rest): User {
+    $made = new User('a');
+    $name = $made->copy()->name();
+    $clock = $made->clock;
+    $fn = fn ($x) => $id + $x;
+    $result = make($id);
+    return $made;
+}
+"), [
+            "$name: string", "$this: App\\User", "$this: App\\User", "$id: int", "$user: App\\User|null", "$rest: array", "$made: App\\User",
+            "$name: string", "$made: App\\User", "$clock: Lib\\Clock|null", "$made: App\\User", "$fn: Closure", "$x: ?", "$id: int",
+            "$x: ?", "$result: App\\User", "$id: int", "$made: App\\User",
+        ]);
+    }
+
+    #[test]
+    fn docblocks() {
+        assert_eq!(variables("<?php
+/** @template TModel of Model */
+class Repository {
+    /** @var array<int, TModel> */
+    private $cache;
+
+    /**
+     * @param int|string $id
+     * @param non-empty-string $column
+     * @return TModel|null
+     */
+    public function find($id, $column, int $limit) {
+        $cached = $this->cache;
+        return null;
+    }
+}
+
+/**
+ * @template T
+ * @param T $value
+ * @param callable(T): bool $check
+ * @return list<T>
+ */
+function filter($value, $check, $other) {
+    $found = (new Repository)->find(1, 'id', 1);
+    $filtered = filter($found, 'is_object', null);
+}
+"), [
+            "$id: int|string", "$column: string", "$limit: int", "$cached: array", "$this: Repository", "$value: mixed", "$check: callable",
+            "$other: ?", "$found: Model|null", "$filtered: array", "$found: Model|null",
+        ]);
+    }
+
+    #[test]
+    fn branches() {
+        assert_eq!(variables("<?php
+function f(bool $flag) {
+    $a = 1;
+    if ($flag) {
+        $a = 'one';
+        $b = 2;
+    }
+    echo $a, $b;
+    foreach ([] as $key => $value) { $c = $value; }
+    echo $key;
+    try {} catch (\\RuntimeException | \\LogicException $e) {}
+}
+"), [
+            "$flag: bool", "$a: int", "$flag: bool", "$a: int|string", "$b: int", "$a: int|string", "$b: int", "$key: int|string",
+            "$value: ?", "$c: ?", "$value: ?", "$key: int|string", "$e: RuntimeException|LogicException",
+        ]);
+    }
+}
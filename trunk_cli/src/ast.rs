@@ -0,0 +1,86 @@
+use std::path::Path;
+use std::process::ExitCode;
+
+use trunk_parser::{dump, DumpOptions};
+
+use crate::{read, report, usage_error, FAILURE, USAGE_ERROR};
+
+/// `trunk ast <file> [--filter <kinds>] [--depth <n>]`
+pub fn run(args: &[String]) -> ExitCode {
+    let (path, options) = match arguments(args) {
+        Ok(arguments) => arguments,
+        Err(message) => return usage_error(&message),
+    };
+
+    let source = match read(path) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("error: couldn't read {}: {}", path.display(), error);
+            return ExitCode::from(USAGE_ERROR);
+        },
+    };
+
+    let (program, diagnostics) = crate::parse(&source);
+
+    report(&diagnostics, &source, path, false);
+
+    match program {
+        Some(program) => {
+            print!("{}", dump(&program, &options));
+            ExitCode::SUCCESS
+        },
+        None => ExitCode::from(FAILURE),
+    }
+}
+
+fn arguments(args: &[String]) -> Result<(&Path, DumpOptions), String> {
+    let mut path = None;
+    let mut options = DumpOptions::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--filter" => {
+                let kinds = args.next().ok_or("--filter needs a list of node kinds")?;
+                options.kinds.extend(kinds.split(',').map(str::trim).filter(|kind| ! kind.is_empty()).map(String::from));
+            },
+            "--depth" => {
+                let depth = args.next().ok_or("--depth needs a number")?;
+                options.depth = Some(depth.parse().map_err(|_| format!("invalid depth `{}`", depth))?);
+            },
+            flag if flag.starts_with("--") => return Err(format!("unknown option `{}`", flag)),
+            file if path.is_none() => path = Some(Path::new(file)),
+            _ => return Err("only one file can be dumped at a time".into()),
+        }
+    }
+
+    match path {
+        Some(path) => Ok((path, options)),
+        None => Err("no file given".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use trunk_parser::DumpOptions;
+
+    use super::arguments;
+    use crate::options::args;
+
+    #[test]
+    fn options() {
+        assert_eq!(arguments(&args(&["a.php"])), Ok((Path::new("a.php"), DumpOptions::default())));
+        assert_eq!(arguments(&args(&["a.php", "--filter", "Class, Method", "--depth", "2"])), Ok((Path::new("a.php"), DumpOptions {
+            kinds: vec!["Class".into(), "Method".into()],
+            depth: Some(2),
+        })));
+
+        assert!(arguments(&args(&[])).is_err());
+        assert!(arguments(&args(&["a.php", "b.php"])).is_err());
+        assert!(arguments(&args(&["a.php", "--depth", "deep"])).is_err());
+        assert!(arguments(&args(&["a.php", "--filter"])).is_err());
+        assert!(arguments(&args(&["a.php", "--kinds", "Class"])).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use trunk_parser::{Diagnostic, Severity};
+
+use crate::glob::excludes;
+use crate::options::exclude;
+use crate::{read, report, usage_error, FAILURE, USAGE_ERROR};
+
+/// The stack given to each checking thread, the same as a main thread's rather than the 2MB that
+/// spawned threads get by default.
+const STACK_SIZE: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Options {
+    paths: Vec<PathBuf>,
+    excludes: Vec<String>,
+    quiet: bool,
+    json: bool,
+}
+
+/// `trunk check <paths...> [--exclude <glob>]... [--quiet] [--json]`
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match arguments(args) {
+        Ok(options) => options,
+        Err(message) => return usage_error(&message),
+    };
+
+    let mut files = Vec::new();
+
+    for path in &options.paths {
+        if let Err(error) = collect(path, &options.excludes, &mut files) {
+            eprintln!("error: couldn't read {}: {}", path.display(), error);
+            return ExitCode::from(USAGE_ERROR);
+        }
+    }
+
+    let (mut errors, mut warnings) = (0, 0);
+
+    for (path, result) in files.iter().zip(check(&files)) {
+        let (source, mut diagnostics) = match result {
+            Ok(result) => result,
+            Err(error) => {
+                eprintln!("error: couldn't read {}: {}", path.display(), error);
+                errors += 1;
+                continue;
+            },
+        };
+
+        if options.quiet {
+            diagnostics.retain(|diagnostic| diagnostic.severity == Severity::Error);
+        }
+
+        errors += diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error).count();
+        warnings += diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Warning).count();
+
+        report(&diagnostics, &source, path, options.json);
+    }
+
+    if ! options.quiet && ! options.json {
+        eprintln!("Checked {}: {}, {}", plural(files.len(), "file"), plural(errors, "error"), plural(warnings, "warning"));
+    }
+
+    if errors > 0 {
+        ExitCode::from(FAILURE)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn arguments(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if let Some(glob) = exclude(arg, &mut args)? {
+            options.excludes.push(glob);
+            continue;
+        }
+
+        match arg.as_str() {
+            "--quiet" | "-q" => options.quiet = true,
+            "--json" => options.json = true,
+            flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option `{}`", flag)),
+            path => options.paths.push(path.into()),
+        }
+    }
+
+    if options.paths.is_empty() {
+        return Err("no files or directories given".into());
+    }
+
+    Ok(options)
+}
+
+/// Find the files to check below a path. Directories are searched for `.php` files, in order of
+/// name, but a file that's named directly, or `-` for standard input, is always checked unless
+/// it's excluded.
+pub(crate) fn collect(path: &Path, globs: &[String], files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if globs.iter().any(|glob| excludes(glob, path)) {
+        return Ok(());
+    }
+
+    if path == Path::new("-") {
+        files.push(path.to_path_buf());
+
+        return Ok(());
+    }
+
+    if ! path.is_dir() {
+        // Fail early on paths that don't exist, rather than after checking everything else.
+        std::fs::metadata(path)?;
+        files.push(path.to_path_buf());
+
+        return Ok(());
+    }
+
+    let mut entries = std::fs::read_dir(path)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+
+        // Symbolic links to directories aren't followed, so that a loop can't be walked forever.
+        if entry.file_type()?.is_dir() {
+            collect(&path, globs, files)?;
+        } else if path.extension().is_some_and(|extension| extension == "php") && ! globs.iter().any(|glob| excludes(glob, &path)) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// A file's source and diagnostics, or the error from reading it.
+type Checked = std::io::Result<(String, Vec<Diagnostic>)>;
+
+/// Read and parse the files, spread across one thread per available core, returning the results in
+/// the same order as the files.
+fn check(files: &[PathBuf]) -> Vec<Checked> {
+    let results: Vec<Mutex<Option<Checked>>> = files.iter().map(|_| Mutex::new(None)).collect();
+    let next = AtomicUsize::new(0);
+
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()).min(files.len());
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let worker = || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = files.get(index) else { break };
+
+                let result = read(path).map(|source| {
+                    let (_, diagnostics) = crate::parse(&source);
+                    (source, diagnostics)
+                });
+
+                *results[index].lock().unwrap_or_else(|err| err.into_inner()) = Some(result);
+            };
+
+            thread::Builder::new().stack_size(STACK_SIZE).spawn_scoped(scope, worker).expect("threads can be spawned");
+        }
+    });
+
+    results.into_iter()
+        .map(|result| result.into_inner().unwrap_or_else(|err| err.into_inner()).expect("every file is checked"))
+        .collect()
+}
+
+pub(crate) fn plural(n: usize, noun: &str) -> String {
+    if n == 1 {
+        format!("{} {}", n, noun)
+    } else {
+        format!("{} {}s", n, noun)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{arguments, check, collect, Options};
+    use crate::options::args;
+
+    #[test]
+    fn options() {
+        assert_eq!(arguments(&args(&["src", "--exclude", "vendor", "-q", "--exclude=*.blade.php", "index.php"])), Ok(Options {
+            paths: vec!["src".into(), "index.php".into()],
+            excludes: vec!["vendor".into(), "*.blade.php".into()],
+            quiet: true,
+            json: false,
+        }));
+
+        assert_eq!(arguments(&args(&["-", "--json"])), Ok(Options { paths: vec!["-".into()], json: true, ..Options::default() }));
+        assert!(arguments(&args(&[])).is_err());
+        assert!(arguments(&args(&["src", "--exclude"])).is_err());
+        assert!(arguments(&args(&["src", "--fix"])).is_err());
+    }
+
+    #[test]
+    fn directories() {
+        let dir = std::env::temp_dir().join(format!("trunk_check_{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/vendor")).unwrap();
+
+        fs::write(dir.join("src/b.php"), "<?php $a = (real) 1;").unwrap();
+        fs::write(dir.join("src/a.php"), "<?php if (;").unwrap();
+        fs::write(dir.join("src/a.txt"), "not php").unwrap();
+        fs::write(dir.join("src/a.blade.php"), "{{ $a }}").unwrap();
+        fs::write(dir.join("src/vendor/c.php"), "<?php echo 1;").unwrap();
+        fs::write(dir.join("script"), "#!/usr/bin/env php\n<?php echo 1;").unwrap();
+
+        let mut files = Vec::new();
+        collect(&dir.join("src"), &["vendor".into(), "*.blade.php".into()], &mut files).unwrap();
+        collect(&dir.join("script"), &[], &mut files).unwrap();
+        collect("-".as_ref(), &[], &mut files).unwrap();
+        let stdin = files.pop();
+        let missing = collect(&dir.join("missing"), &[], &mut Vec::new());
+        let results = check(&files);
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(files, [dir.join("src/a.php"), dir.join("src/b.php"), dir.join("script")]);
+        assert!(missing.is_err());
+        assert_eq!(stdin, Some("-".into()));
+
+        let codes: Vec<Vec<&str>> = results.iter().map(|result| result.as_ref().unwrap().1.iter().map(|d| d.code).collect()).collect();
+        assert_eq!(codes, [vec!["unexpected-token"], vec!["removed"], vec![]]);
+    }
+}
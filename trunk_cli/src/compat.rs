@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use trunk_analyser::check_compatibility;
+use trunk_parser::{ParserConfig, PhpVersion};
+
+use crate::check::{collect, plural};
+use crate::options::exclude;
+use crate::{parse_with_config, read, report, usage_error, FAILURE, USAGE_ERROR};
+
+#[derive(Debug, PartialEq, Eq)]
+struct Options {
+    paths: Vec<PathBuf>,
+    excludes: Vec<String>,
+    target: PhpVersion,
+    json: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self { paths: Vec::new(), excludes: Vec::new(), target: PhpVersion::Php84, json: false }
+    }
+}
+
+/// `trunk compat <paths...> [--target <version>] [--exclude <glob>]... [--json]`
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match arguments(args) {
+        Ok(options) => options,
+        Err(message) => return usage_error(&message),
+    };
+
+    let mut files = Vec::new();
+
+    for path in &options.paths {
+        if let Err(error) = collect(path, &options.excludes, &mut files) {
+            eprintln!("error: couldn't read {}: {}", path.display(), error);
+            return ExitCode::from(USAGE_ERROR);
+        }
+    }
+
+    let mut problems = 0;
+
+    for path in &files {
+        let source = match read(path) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("error: couldn't read {}: {}", path.display(), error);
+                problems += 1;
+                continue;
+            },
+        };
+
+        // Parsing for the targeted version reports syntax that it doesn't support, and warns about
+        // syntax that it deprecates.
+        let (program, mut diagnostics) = parse_with_config(&source, ParserConfig { php_version: options.target, ..Default::default() });
+
+        if let Some(program) = program {
+            diagnostics.extend(check_compatibility(&program, options.target));
+            diagnostics.sort_by_key(|diagnostic| diagnostic.span.map(|span| span.start));
+        }
+
+        problems += diagnostics.len();
+        report(&diagnostics, &source, path, options.json);
+    }
+
+    if ! options.json {
+        eprintln!("Checked {} for PHP {}: {}", plural(files.len(), "file"), options.target, plural(problems, "problem"));
+    }
+
+    if problems > 0 {
+        ExitCode::from(FAILURE)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn arguments(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if let Some(glob) = exclude(arg, &mut args)? {
+            options.excludes.push(glob);
+            continue;
+        }
+
+        match arg.as_str() {
+            "--json" => options.json = true,
+            "--target" => match args.next() {
+                Some(version) => options.target = version.parse()?,
+                None => return Err("--target needs a PHP version".into()),
+            },
+            flag if flag.starts_with("--target=") => options.target = flag["--target=".len()..].parse()?,
+            flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option `{}`", flag)),
+            path => options.paths.push(path.into()),
+        }
+    }
+
+    if options.paths.is_empty() {
+        return Err("no files or directories given".into());
+    }
+
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_parser::PhpVersion;
+
+    use super::{arguments, Options};
+    use crate::options::args;
+
+    #[test]
+    fn options() {
+        assert_eq!(arguments(&args(&["src", "--target", "7.4", "--json"])), Ok(Options {
+            paths: vec!["src".into()],
+            excludes: vec![],
+            target: PhpVersion::Php74,
+            json: true,
+        }));
+
+        assert_eq!(arguments(&args(&["src", "--target=8.1"])).map(|options| options.target), Ok(PhpVersion::Php81));
+        assert_eq!(arguments(&args(&["src"])).map(|options| options.target), Ok(PhpVersion::Php84));
+        assert!(arguments(&args(&["src", "--target", "7.2"])).is_err());
+        assert!(arguments(&args(&["src", "--target"])).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+/// The number of unchanged lines shown around each change.
+const CONTEXT: usize = 3;
+
+/// Past this many cells, the lines between the common prefix and suffix are shown as one
+/// replacement, rather than spending time and memory on the smallest diff.
+const MAX_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// A unified diff between two texts, with `path` in the file headers, or an empty string if they
+/// have the same lines.
+pub fn unified(path: &str, old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let lines = diff(&old, &new);
+
+    if lines.iter().all(|line| matches!(line, Line::Same(_))) {
+        return String::new();
+    }
+
+    let mut output = format!("--- {}\n+++ {}\n", path, path);
+
+    for (start, end) in hunks(&lines) {
+        // Line numbers in the header count from one, and an empty range names the line before it.
+        let (mut old_start, mut new_start) = (1, 1);
+
+        for line in &lines[..start] {
+            match line {
+                Line::Same(_) => { old_start += 1; new_start += 1; },
+                Line::Removed(_) => old_start += 1,
+                Line::Added(_) => new_start += 1,
+            }
+        }
+
+        let hunk = &lines[start..end];
+        let old_len = hunk.iter().filter(|line| ! matches!(line, Line::Added(_))).count();
+        let new_len = hunk.iter().filter(|line| ! matches!(line, Line::Removed(_))).count();
+
+        output.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            if old_len == 0 { old_start - 1 } else { old_start }, old_len,
+            if new_len == 0 { new_start - 1 } else { new_start }, new_len,
+        ));
+
+        for line in hunk {
+            let (marker, text) = match line {
+                Line::Same(text) => (' ', text),
+                Line::Removed(text) => ('-', text),
+                Line::Added(text) => ('+', text),
+            };
+
+            output.push(marker);
+            output.push_str(text);
+            output.push('\n');
+        }
+    }
+
+    output
+}
+
+/// The ranges of lines to show, each change with its context, merging changes whose context
+/// would overlap.
+fn hunks(lines: &[Line]) -> Vec<(usize, usize)> {
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        if matches!(line, Line::Same(_)) {
+            continue;
+        }
+
+        let (start, end) = (i.saturating_sub(CONTEXT), (i + 1 + CONTEXT).min(lines.len()));
+
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    hunks
+}
+
+/// The lines of both texts in order, using the longest common subsequence of the lines between
+/// their common prefix and suffix.
+fn diff<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+
+    let (old_middle, new_middle) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let mut lines: Vec<Line> = old[..prefix].iter().map(|line| Line::Same(line)).collect();
+
+    if (old_middle.len() + 1) * (new_middle.len() + 1) > MAX_CELLS {
+        lines.extend(old_middle.iter().map(|line| Line::Removed(line)));
+        lines.extend(new_middle.iter().map(|line| Line::Added(line)));
+    } else {
+        lines.extend(lcs(old_middle, new_middle));
+    }
+
+    lines.extend(old[old.len() - suffix..].iter().map(|line| Line::Same(line)));
+
+    lines
+}
+
+fn lcs<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let width = new.len() + 1;
+    // `table[i * width + j]` is the length of the longest common subsequence of `old[i..]` and `new[j..]`.
+    let mut table = vec![0usize; (old.len() + 1) * width];
+
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            table[i * width + j] = if old[i] == new[j] {
+                table[(i + 1) * width + j + 1] + 1
+            } else {
+                table[(i + 1) * width + j].max(table[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if table[(i + 1) * width + j] >= table[i * width + j + 1] {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+
+    lines.extend(old[i..].iter().map(|line| Line::Removed(line)));
+    lines.extend(new[j..].iter().map(|line| Line::Added(line)));
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unified;
+
+    #[test]
+    fn diffs() {
+        assert_eq!(unified("a.php", "a\nb\n", "a\nb\n"), "");
+
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n10\n11\n12\n13\n";
+
+        assert_eq!(unified("a.php", old, new), "\
+--- a.php
++++ a.php
+@@ -2,7 +2,7 @@
+ 2
+ 3
+ 4
+-5
++five
+ 6
+ 7
+ 8
+@@ -10,3 +10,4 @@
+ 10
+ 11
+ 12
++13
+");
+
+        assert_eq!(unified("a.php", "", "a\n"), "--- a.php\n+++ a.php\n@@ -0,0 +1,1 @@\n+a\n");
+    }
+}
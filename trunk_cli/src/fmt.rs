@@ -0,0 +1,254 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use trunk_lexer::{Lexer, Token, TokenKind};
+use trunk_parser::{Diagnostic, Parser, ParserConfig, Program, Statement, StatementKind};
+use trunk_printer::{BraceStyle, Style};
+
+use crate::check::collect;
+use crate::diff::unified;
+use crate::options::exclude;
+use crate::{read, report, usage_error, FAILURE, USAGE_ERROR};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Mode {
+    /// Rewrite files that aren't formatted, or print standard input formatted.
+    #[default]
+    Write,
+    /// List the files that aren't formatted.
+    Check,
+    /// Print what would change in the files that aren't formatted.
+    Diff,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Options {
+    paths: Vec<PathBuf>,
+    excludes: Vec<String>,
+    mode: Mode,
+    style: Style,
+}
+
+/// Why a file couldn't be formatted.
+#[derive(Debug)]
+enum Unformattable {
+    /// The source has syntax errors.
+    Invalid(Vec<Diagnostic>),
+    /// The formatted source would mean something else, or have fewer comments, so it's left alone.
+    Unsafe(&'static str),
+}
+
+/// `trunk fmt <paths...> [--check|--diff] [--indent <n>|--tabs] [--same-line-braces] [--exclude <glob>]...`
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match arguments(args) {
+        Ok(options) => options,
+        Err(message) => return usage_error(&message),
+    };
+
+    let mut files = Vec::new();
+
+    for path in &options.paths {
+        if let Err(error) = collect(path, &options.excludes, &mut files) {
+            eprintln!("error: couldn't read {}: {}", path.display(), error);
+            return ExitCode::from(USAGE_ERROR);
+        }
+    }
+
+    let mut failed = false;
+
+    for path in &files {
+        let source = match read(path) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("error: couldn't read {}: {}", path.display(), error);
+                failed = true;
+                continue;
+            },
+        };
+
+        let formatted = match format(&source, &options.style) {
+            Ok(formatted) => formatted,
+            Err(Unformattable::Invalid(diagnostics)) => {
+                report(&diagnostics, &source, path, false);
+                failed = true;
+                continue;
+            },
+            Err(Unformattable::Unsafe(reason)) => {
+                eprintln!("error: couldn't format {}: {}", path.display(), reason);
+                failed = true;
+                continue;
+            },
+        };
+
+        let stdin = path == Path::new("-");
+
+        if stdin && options.mode == Mode::Write {
+            print!("{}", formatted);
+            continue;
+        }
+
+        if formatted == source {
+            continue;
+        }
+
+        match options.mode {
+            Mode::Write => if let Err(error) = std::fs::write(path, &formatted) {
+                eprintln!("error: couldn't write {}: {}", path.display(), error);
+                failed = true;
+            },
+            Mode::Check => {
+                println!("{}", path.display());
+                failed = true;
+            },
+            Mode::Diff => {
+                let name = if stdin { "<stdin>".into() } else { path.to_string_lossy() };
+
+                print!("{}", unified(&name, &source, &formatted));
+                failed = true;
+            },
+        }
+    }
+
+    if failed {
+        ExitCode::from(FAILURE)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn arguments(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut mode = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if let Some(glob) = exclude(arg, &mut args)? {
+            options.excludes.push(glob);
+            continue;
+        }
+
+        let flag = match arg.as_str() {
+            "--check" => Mode::Check,
+            "--diff" => Mode::Diff,
+            "--tabs" => {
+                options.style.indent = "\t".into();
+                continue;
+            },
+            "--indent" => {
+                let width = args.next().ok_or("--indent needs a number of spaces")?;
+                let width: usize = width.parse().map_err(|_| format!("invalid indent `{}`", width))?;
+
+                options.style.indent = " ".repeat(width);
+                continue;
+            },
+            "--same-line-braces" => {
+                options.style.braces = BraceStyle::SameLine;
+                continue;
+            },
+            flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option `{}`", flag)),
+            path => {
+                options.paths.push(path.into());
+                continue;
+            },
+        };
+
+        if mode.replace(flag).is_some() {
+            return Err("only one of --check and --diff can be given".into());
+        }
+    }
+
+    if options.paths.is_empty() {
+        return Err("no files or directories given".into());
+    }
+
+    options.mode = mode.unwrap_or_default();
+
+    Ok(options)
+}
+
+/// Format source code, making sure that the result parses to the same program with the same
+/// comments.
+fn format(source: &str, style: &Style) -> Result<String, Unformattable> {
+    let (tokens, program) = parse(source).map_err(Unformattable::Invalid)?;
+    let formatted = trunk_printer::format(&program, source, style);
+
+    match parse(&formatted) {
+        Ok((formatted_tokens, formatted_program)) => {
+            if comparable(formatted_program) != comparable(program) {
+                return Err(Unformattable::Unsafe("the formatted code would behave differently"));
+            }
+
+            if comments(&formatted_tokens) != comments(&tokens) {
+                return Err(Unformattable::Unsafe("the formatted code would lose comments"));
+            }
+        },
+        Err(_) => return Err(Unformattable::Unsafe("the formatted code wouldn't parse")),
+    }
+
+    Ok(formatted)
+}
+
+fn parse(source: &str) -> Result<(Vec<Token>, Program), Vec<Diagnostic>> {
+    let tokens = Lexer::new(None).tokenize(source).map_err(|error| vec![Diagnostic::from(&error)])?;
+    let program = Parser::new(Some(ParserConfig { comments: true, ..Default::default() }))
+        .parse(&tokens)
+        .map_err(|error| vec![Diagnostic::from(&error)])?;
+
+    Ok((tokens, program))
+}
+
+/// A program with the offset of its `__halt_compiler` data left out, since the data moves whenever
+/// the code before it is reformatted.
+fn comparable(mut program: Program) -> Program {
+    if let Some(Statement { kind: StatementKind::HaltCompiler { offset, .. }, .. }) = program.last_mut() {
+        *offset = 0;
+    }
+
+    program
+}
+
+fn comments(tokens: &[Token]) -> Vec<String> {
+    tokens.iter()
+        .filter(|token| matches!(token.kind, TokenKind::Comment(_) | TokenKind::DocComment(_)))
+        .map(|token| token.kind.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_printer::{BraceStyle, Style};
+
+    use super::{arguments, format, Mode, Options, Unformattable};
+    use crate::options::args;
+
+    #[test]
+    fn options() {
+        assert_eq!(arguments(&args(&["src", "--check", "--tabs", "--same-line-braces", "--exclude", "vendor"])), Ok(Options {
+            paths: vec!["src".into()],
+            excludes: vec!["vendor".into()],
+            mode: Mode::Check,
+            style: Style { indent: "\t".into(), braces: BraceStyle::SameLine },
+        }));
+
+        assert_eq!(arguments(&args(&["-", "--indent", "2"])).map(|options| options.style.indent), Ok("  ".into()));
+
+        assert!(arguments(&args(&[])).is_err());
+        assert!(arguments(&args(&["src", "--check", "--diff"])).is_err());
+        assert!(arguments(&args(&["src", "--indent", "two"])).is_err());
+        assert!(arguments(&args(&["src", "--fix"])).is_err());
+    }
+
+    #[test]
+    fn formatting() {
+        let style = Style::default();
+
+        assert_eq!(format("<?php\n// Hello\nif($a){echo 1;} // done\n", &style).unwrap(), "<?php\n\n// Hello\nif ($a) {\n    echo 1;\n} // done\n");
+        assert!(matches!(format("<?php if (", &style), Err(Unformattable::Invalid(_))));
+        assert_eq!(format("<?php\nif($a){foo(/* a */ 1);}\n", &style).unwrap(), "<?php\n\nif ($a) {\n    foo(/* a */ 1);\n}\n");
+        assert_eq!(format("<?php  echo  1;\n__halt_compiler();data", &style).unwrap(), "<?php\n\necho 1;\n__halt_compiler();data");
+
+        // Formatting is idempotent.
+        let formatted = format("<?php class A { /** B */ const B = 1; public function c() { return 1; } }", &style).unwrap();
+        assert_eq!(format(&formatted, &style).unwrap(), formatted);
+    }
+}
@@ -0,0 +1,59 @@
+use std::path::Path;
+
+/// Whether a path is excluded by a glob. A glob without a `/` is matched against the name of the
+/// file or directory, so `vendor` excludes every directory called that, and one with a `/` is
+/// matched against the whole path.
+pub fn excludes(glob: &str, path: &Path) -> bool {
+    let glob = glob.trim_start_matches("./").trim_end_matches('/');
+
+    if glob.contains('/') {
+        let path = path.to_string_lossy();
+
+        matches(&chars(glob), &chars(path.trim_start_matches("./")))
+    } else {
+        path.file_name().is_some_and(|name| matches(&chars(glob), &chars(&name.to_string_lossy())))
+    }
+}
+
+/// Match text against a glob, where `?` matches any character and `*` matches any number of them
+/// other than `/`, and `**` matches across directories too.
+fn matches(glob: &[char], text: &[char]) -> bool {
+    match glob {
+        [] => text.is_empty(),
+        // `**/` matches any number of whole directories, including none.
+        ['*', '*', '/', rest @ ..] => (0..=text.len())
+            .filter(|&i| i == 0 || text[i - 1] == '/')
+            .any(|i| matches(rest, &text[i..])),
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|i| matches(rest, &text[i..])),
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != '/')
+            .any(|i| matches(rest, &text[i..])),
+        ['?', rest @ ..] => matches!(text, [c, ..] if *c != '/') && matches(rest, &text[1..]),
+        [c, rest @ ..] => text.first() == Some(c) && matches(rest, &text[1..]),
+    }
+}
+
+fn chars(s: &str) -> Vec<char> {
+    s.chars().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::excludes;
+
+    #[test]
+    fn globs() {
+        assert!(excludes("vendor", Path::new("src/vendor")));
+        assert!(excludes("*.blade.php", Path::new("views/a.blade.php")));
+        assert!(! excludes("*.blade.php", Path::new("views/a.php")));
+        assert!(excludes("src/*/tests", Path::new("./src/a/tests")));
+        assert!(! excludes("src/*/tests", Path::new("src/a/b/tests")));
+        assert!(excludes("src/**/tests", Path::new("src/a/b/tests")));
+        assert!(excludes("src/**/tests", Path::new("src/tests")));
+        assert!(! excludes("src/**/tests", Path::new("src/atests")));
+        assert!(excludes("cache/?.php", Path::new("cache/1.php")));
+        assert!(! excludes("cache/?.php", Path::new("cache/12.php")));
+    }
+}
@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use serde_json::json;
+use trunk_parser::{Query, QueryMatch};
+
+use crate::check::collect;
+use crate::options::exclude;
+use crate::{read, report, usage_error, FAILURE, USAGE_ERROR};
+
+#[derive(Debug, PartialEq, Eq)]
+struct Options {
+    query: Query,
+    paths: Vec<PathBuf>,
+    excludes: Vec<String>,
+    json: bool,
+    files_with_matches: bool,
+}
+
+/// `trunk grep <pattern> <paths...> [--exclude <glob>]... [--json] [--files-with-matches]`
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match arguments(args) {
+        Ok(options) => options,
+        Err(message) => return usage_error(&message),
+    };
+
+    let mut files = Vec::new();
+
+    for path in &options.paths {
+        if let Err(error) = collect(path, &options.excludes, &mut files) {
+            eprintln!("error: couldn't read {}: {}", path.display(), error);
+            return ExitCode::from(USAGE_ERROR);
+        }
+    }
+
+    let mut found = false;
+
+    for path in &files {
+        let source = match read(path) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("error: couldn't read {}: {}", path.display(), error);
+                continue;
+            },
+        };
+
+        let program = match crate::parse(&source) {
+            (Some(program), _) => program,
+            (None, diagnostics) => {
+                report(&diagnostics, &source, path, options.json);
+                continue;
+            },
+        };
+
+        let matches = options.query.find(&program);
+        found |= ! matches.is_empty();
+
+        if options.files_with_matches {
+            if ! matches.is_empty() {
+                println!("{}", name(path).display());
+            }
+
+            continue;
+        }
+
+        for m in &matches {
+            println!("{}", format(path, &source, m, options.json));
+        }
+    }
+
+    // As with grep, finding nothing is a failure.
+    if found {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(FAILURE)
+    }
+}
+
+fn arguments(args: &[String]) -> Result<Options, String> {
+    let mut pattern = None;
+    let mut paths = Vec::new();
+    let mut excludes = Vec::new();
+    let mut json = false;
+    let mut files_with_matches = false;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if let Some(glob) = exclude(arg, &mut args)? {
+            excludes.push(glob);
+            continue;
+        }
+
+        match arg.as_str() {
+            "--json" => json = true,
+            "--files-with-matches" | "-l" => files_with_matches = true,
+            flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option `{}`", flag)),
+            arg if pattern.is_none() => pattern = Some(arg.to_string()),
+            path => paths.push(path.into()),
+        }
+    }
+
+    let Some(pattern) = pattern else {
+        return Err("no pattern given".into());
+    };
+
+    let query = Query::parse(&pattern).map_err(|error| format!("invalid pattern `{}`: {}", pattern, error))?;
+
+    if paths.is_empty() {
+        return Err("no files or directories given".into());
+    }
+
+    Ok(Options { query, paths, excludes, json, files_with_matches })
+}
+
+fn name(path: &Path) -> &Path {
+    if path == Path::new("-") { Path::new("<stdin>") } else { path }
+}
+
+/// A match as `file:line:column: source`, with the line that the node starts on, or as JSON.
+fn format(path: &Path, source: &str, m: &QueryMatch, json: bool) -> String {
+    let (line, col, text) = match m.span {
+        Some(span) => (span.line, span.col, source.lines().nth(span.line.saturating_sub(1)).unwrap_or_default().trim()),
+        None => (0, 0, ""),
+    };
+
+    if json {
+        json!({ "file": name(path).display().to_string(), "line": line, "col": col, "kind": m.kind, "text": text }).to_string()
+    } else {
+        format!("{}:{}:{}: {}", name(path).display(), line, col, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use trunk_parser::{parse, Query};
+
+    use super::{arguments, format};
+    use crate::options::args;
+
+    #[test]
+    fn options() {
+        let options = arguments(&args(&["Call[target.name=eval]", "src", "-l", "--exclude", "vendor"])).unwrap();
+
+        assert_eq!(options.query, Query::parse("Call[target.name=eval]").unwrap());
+        assert_eq!(options.paths, [Path::new("src")]);
+        assert_eq!(options.excludes, ["vendor"]);
+        assert!(options.files_with_matches && ! options.json);
+
+        assert!(arguments(&args(&[])).is_err());
+        assert!(arguments(&args(&["Call"])).is_err());
+        assert!(arguments(&args(&["Call[", "src"])).is_err());
+    }
+
+    #[test]
+    fn output() {
+        let source = "<?php\nif ($a) {\n    eval($code);\n}\n";
+        let matches = Query::parse("Call").unwrap().find(&parse(source).unwrap());
+
+        assert_eq!(format(Path::new("a.php"), source, &matches[0], false), "a.php:3:5: eval($code);");
+        assert_eq!(
+            format(Path::new("-"), source, &matches[0], true),
+            r#"{"col":5,"file":"<stdin>","kind":"Call","line":3,"text":"eval($code);"}"#,
+        );
+    }
+}
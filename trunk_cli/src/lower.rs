@@ -0,0 +1,162 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use trunk_analyser::lower;
+use trunk_parser::{ParserConfig, PhpVersion};
+use trunk_printer::print;
+
+use crate::check::collect;
+use crate::stubs::destination;
+use crate::options::exclude;
+use crate::{parse_with_config, read, report, usage_error, FAILURE, USAGE_ERROR};
+
+#[derive(Debug, PartialEq, Eq)]
+struct Options {
+    paths: Vec<PathBuf>,
+    excludes: Vec<String>,
+    target: PhpVersion,
+    /// The directory to write each lowered file to, or `None` to print the only file.
+    output: Option<PathBuf>,
+}
+
+/// `trunk lower <paths...> --target <version> [--exclude <glob>]... [--output <dir>]`
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match arguments(args) {
+        Ok(options) => options,
+        Err(message) => return usage_error(&message),
+    };
+
+    let mut files = Vec::new();
+
+    for path in &options.paths {
+        let mut found = Vec::new();
+
+        if let Err(error) = collect(path, &options.excludes, &mut found) {
+            eprintln!("error: couldn't read {}: {}", path.display(), error);
+            return ExitCode::from(USAGE_ERROR);
+        }
+
+        files.extend(found.into_iter().map(|file| (path.as_path(), file)));
+    }
+
+    if options.output.is_none() && files.len() > 1 {
+        return usage_error("--output is needed to lower more than one file");
+    }
+
+    let mut failed = false;
+
+    for (base, path) in &files {
+        let source = match read(path) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("error: couldn't read {}: {}", path.display(), error);
+                failed = true;
+                continue;
+            },
+        };
+
+        let program = match crate::parse(&source) {
+            (Some(program), _) => program,
+            (None, diagnostics) => {
+                report(&diagnostics, &source, path, false);
+                failed = true;
+                continue;
+            },
+        };
+
+        let lowered = print(&lower(program, options.target));
+
+        // Syntax that can't be lowered yet is reported against the lowered code, which is still
+        // written out.
+        if let (None, diagnostics) = parse_with_config(&lowered, ParserConfig { php_version: options.target, ..Default::default() }) {
+            report(&diagnostics, &lowered, path, false);
+            failed = true;
+        }
+
+        let destination = match &options.output {
+            Some(output) if path != Path::new("-") => output.join(destination(base, path)),
+            _ => {
+                print!("{}", lowered);
+                continue;
+            },
+        };
+
+        let written = match destination.parent() {
+            Some(parent) => std::fs::create_dir_all(parent).and_then(|_| std::fs::write(&destination, lowered)),
+            None => std::fs::write(&destination, lowered),
+        };
+
+        if let Err(error) = written {
+            eprintln!("error: couldn't write {}: {}", destination.display(), error);
+            failed = true;
+        }
+    }
+
+    if failed {
+        ExitCode::from(FAILURE)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn arguments(args: &[String]) -> Result<Options, String> {
+    let mut paths = Vec::new();
+    let mut excludes = Vec::new();
+    let mut target = None;
+    let mut output = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if let Some(glob) = exclude(arg, &mut args)? {
+            excludes.push(glob);
+            continue;
+        }
+
+        match arg.as_str() {
+            "--target" => match args.next() {
+                Some(version) => target = Some(version.parse()?),
+                None => return Err("--target needs a PHP version".into()),
+            },
+            flag if flag.starts_with("--target=") => target = Some(flag["--target=".len()..].parse()?),
+            "--output" | "-o" => match args.next() {
+                Some(dir) => output = Some(dir.into()),
+                None => return Err("--output needs a directory".into()),
+            },
+            flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option `{}`", flag)),
+            path => paths.push(path.into()),
+        }
+    }
+
+    if paths.is_empty() {
+        return Err("no files or directories given".into());
+    }
+
+    let Some(target) = target else {
+        return Err("--target is needed to know what to lower".into());
+    };
+
+    Ok(Options { paths, excludes, target, output })
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_parser::PhpVersion;
+
+    use super::{arguments, Options};
+    use crate::options::args;
+
+    #[test]
+    fn options() {
+        assert_eq!(arguments(&args(&["src", "--target", "7.3", "-o", "dist"])), Ok(Options {
+            paths: vec!["src".into()],
+            excludes: vec![],
+            target: PhpVersion::Php73,
+            output: Some("dist".into()),
+        }));
+
+        assert_eq!(arguments(&args(&["a.php", "--target=7.4"])).map(|options| options.target), Ok(PhpVersion::Php74));
+        assert!(arguments(&args(&["src"])).is_err());
+        assert!(arguments(&args(&["--target", "7.4"])).is_err());
+        assert!(arguments(&args(&["src", "--target", "5.6"])).is_err());
+    }
+}
@@ -0,0 +1,566 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::process::ExitCode;
+
+use serde_json::{json, Value};
+use trunk_lexer::{Lexer, Span};
+use trunk_parser::{walk_expression, walk_statement, Diagnostic, Expression, ExpressionKind, Parser, ParserConfig, Program, Severity, Statement, StatementKind, Visitor};
+
+use crate::{usage_error, FAILURE};
+
+// JSON-RPC and LSP error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_NOT_INITIALIZED: i64 = -32002;
+
+// `TextDocumentSyncKind.Incremental`, for changes that only send the edited range.
+const INCREMENTAL_SYNC: u8 = 2;
+
+// `SymbolKind` values.
+const NAMESPACE: u8 = 3;
+const CLASS: u8 = 5;
+const METHOD: u8 = 6;
+const PROPERTY: u8 = 7;
+const CONSTRUCTOR: u8 = 9;
+const ENUM: u8 = 10;
+const INTERFACE: u8 = 11;
+const FUNCTION: u8 = 12;
+const CONSTANT: u8 = 14;
+const ENUM_MEMBER: u8 = 22;
+
+/// An open document, which is parsed again whenever it changes.
+struct Document {
+    text: String,
+    /// The AST, with error nodes where parsing recovered, or `None` if the text couldn't be lexed.
+    program: Option<Program>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Document {
+    fn new(text: String) -> Self {
+        let mut lexer = Lexer::new(None);
+
+        let (program, diagnostics) = match lexer.tokenize(&text) {
+            Ok(tokens) => {
+                // Comments are kept so that they can be folded.
+                let mut parser = Parser::new(Some(ParserConfig { comments: true, ..ParserConfig::default() }));
+                let (program, errors) = parser.parse_tolerant(&tokens);
+
+                let diagnostics = lexer.warnings().iter().map(Diagnostic::from)
+                    .chain(errors.iter().map(Diagnostic::from))
+                    .chain(parser.warnings().iter().map(Diagnostic::from))
+                    .collect();
+
+                (Some(program), diagnostics)
+            },
+            Err(error) => (None, vec![Diagnostic::from(&error)]),
+        };
+
+        Self { text, program, diagnostics }
+    }
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, Document>,
+    initialized: bool,
+    shutdown: bool,
+    /// The status to exit with, once the client has asked the server to exit.
+    exit: Option<u8>,
+}
+
+/// `trunk lsp`, which speaks the Language Server Protocol over standard input and output.
+pub fn run(args: &[String]) -> ExitCode {
+    if let Some(arg) = args.first() {
+        return usage_error(&format!("unexpected argument `{}`", arg));
+    }
+
+    let mut input = std::io::stdin().lock();
+    let mut output = std::io::stdout().lock();
+    let mut server = Server::default();
+
+    loop {
+        let messages = match read_message(&mut input) {
+            Ok(Some(body)) => match serde_json::from_str(&body) {
+                Ok(message) => server.handle(message),
+                Err(error) => vec![failure(Value::Null, PARSE_ERROR, &error.to_string())],
+            },
+            // The client went away without asking the server to exit.
+            Ok(None) => return ExitCode::from(FAILURE),
+            Err(error) => {
+                eprintln!("error: couldn't read message: {}", error);
+                return ExitCode::from(FAILURE);
+            },
+        };
+
+        for message in messages {
+            if let Err(error) = write_message(&mut output, &message) {
+                eprintln!("error: couldn't write message: {}", error);
+                return ExitCode::from(FAILURE);
+            }
+        }
+
+        if let Some(status) = server.exit {
+            return ExitCode::from(status);
+        }
+    }
+}
+
+/// Read the body of the next message, which follows a `Content-Length` header and a blank line, or
+/// `None` at the end of input.
+fn read_message(input: &mut impl BufRead) -> std::io::Result<Option<String>> {
+    let mut length = None;
+
+    loop {
+        let mut header = String::new();
+
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "missing Content-Length header"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+
+    String::from_utf8(body).map(Some).map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> std::io::Result<()> {
+    let body = message.to_string();
+
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+impl Server {
+    /// Handle a request or notification from the client, giving the messages to send back.
+    fn handle(&mut self, message: Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+
+        // Only requests have an ID, and they're the only messages that get a response.
+        let Some(id) = message.get("id").cloned() else {
+            return self.notification(method, params);
+        };
+
+        let result = if self.shutdown {
+            Err((INVALID_REQUEST, "the server has been shut down".to_string()))
+        } else if ! self.initialized && method != "initialize" {
+            Err((SERVER_NOT_INITIALIZED, "the server hasn't been initialized".to_string()))
+        } else {
+            self.request(method, params)
+        };
+
+        vec![match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => failure(id, code, &message),
+        }]
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        match method {
+            "initialize" => {
+                self.initialized = true;
+
+                Ok(json!({
+                    "capabilities": {
+                        "textDocumentSync": { "openClose": true, "change": INCREMENTAL_SYNC },
+                        "documentSymbolProvider": true,
+                        "foldingRangeProvider": true,
+                    },
+                    "serverInfo": { "name": "trunk", "version": env!("CARGO_PKG_VERSION") },
+                }))
+            },
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            },
+            "textDocument/documentSymbol" => {
+                let document = self.document(params)?;
+                let symbols = document.program.as_deref().map(|program| symbols(program, &document.text)).unwrap_or_default();
+
+                Ok(Value::Array(symbols))
+            },
+            "textDocument/foldingRange" => {
+                let document = self.document(params)?;
+                let mut folds = Folds { source: &document.text, ranges: Vec::new() };
+
+                if let Some(program) = &document.program {
+                    folds.visit_program(program);
+                }
+
+                Ok(Value::Array(folds.ranges))
+            },
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method `{}`", method))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_string();
+
+        match method {
+            "exit" => {
+                self.exit = Some(if self.shutdown { 0 } else { FAILURE });
+                Vec::new()
+            },
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.clone(), Document::new(text.to_string()));
+
+                self.publish(&uri)
+            },
+            "textDocument/didChange" => {
+                let Some(document) = self.documents.get_mut(&uri) else {
+                    return Vec::new();
+                };
+
+                let mut text = std::mem::take(&mut document.text);
+
+                for change in params["contentChanges"].as_array().into_iter().flatten() {
+                    let new_text = change["text"].as_str().unwrap_or_default();
+
+                    match change.get("range") {
+                        Some(range) => {
+                            let start = offset(&text, &range["start"]);
+                            let end = offset(&text, &range["end"]).max(start);
+                            text.replace_range(start..end, new_text);
+                        },
+                        None => text = new_text.to_string(),
+                    }
+                }
+
+                *document = Document::new(text);
+
+                self.publish(&uri)
+            },
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+
+                // Clear the document's diagnostics, now that nothing keeps them up to date.
+                vec![json!({ "jsonrpc": "2.0", "method": "textDocument/publishDiagnostics", "params": { "uri": uri, "diagnostics": [] } })]
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    fn document(&self, params: &Value) -> Result<&Document, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().ok_or((INVALID_PARAMS, "missing `textDocument.uri`".to_string()))?;
+
+        self.documents.get(uri).ok_or_else(|| (INVALID_PARAMS, format!("`{}` isn't open", uri)))
+    }
+
+    fn publish(&self, uri: &str) -> Vec<Value> {
+        let document = &self.documents[uri];
+        let diagnostics: Vec<Value> = document.diagnostics.iter().map(|diagnostic| {
+            json!({
+                "range": diagnostic.span.map_or_else(|| range(&document.text, Span::default()), |span| range(&document.text, span)),
+                "severity": match diagnostic.severity {
+                    Severity::Error => 1,
+                    Severity::Warning => 2,
+                },
+                "code": diagnostic.code,
+                "source": "trunk",
+                "message": diagnostic.message,
+            })
+        }).collect();
+
+        vec![json!({ "jsonrpc": "2.0", "method": "textDocument/publishDiagnostics", "params": { "uri": uri, "diagnostics": diagnostics } })]
+    }
+}
+
+fn failure(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// The symbols declared at the top of a program or inside a namespace or class-like body, with the
+/// members of classes, interfaces, traits and enums as their children.
+fn symbols(block: &[Statement], source: &str) -> Vec<Value> {
+    block.iter().filter_map(|statement| {
+        let (kind, detail, children) = match &statement.kind {
+            StatementKind::Namespace { body, .. } => (NAMESPACE, None, Some(body)),
+            StatementKind::Class { body, .. } => (CLASS, None, Some(body)),
+            StatementKind::Trait { body, .. } => (CLASS, Some("trait"), Some(body)),
+            StatementKind::Interface { body, .. } => (INTERFACE, None, Some(body)),
+            StatementKind::Enum { body, .. } => (ENUM, None, Some(body)),
+            StatementKind::Function { .. } => (FUNCTION, None, None),
+            StatementKind::Method { name, .. } if name.name.eq_ignore_ascii_case("__construct") => (CONSTRUCTOR, None, None),
+            StatementKind::Method { .. } => (METHOD, None, None),
+            StatementKind::Property { .. } => (PROPERTY, None, None),
+            StatementKind::Constant { .. } => (CONSTANT, None, None),
+            StatementKind::EnumCase { .. } => (ENUM_MEMBER, None, None),
+            _ => return None,
+        };
+
+        let name = statement.kind.name()?;
+        let name = match statement.kind {
+            StatementKind::Property { .. } => format!("${}", name),
+            _ => name.to_string(),
+        };
+
+        let selection = match &statement.kind {
+            StatementKind::Function { name, .. } | StatementKind::Class { name, .. } | StatementKind::Trait { name, .. } |
+            StatementKind::Interface { name, .. } | StatementKind::Method { name, .. } | StatementKind::Enum { name, .. } |
+            StatementKind::Constant { name, .. } | StatementKind::EnumCase { name, .. } => name.span,
+            _ => statement.span,
+        };
+
+        let mut symbol = json!({ "name": name, "kind": kind, "range": range(source, statement.span), "selectionRange": range(source, selection) });
+
+        if let Some(detail) = detail {
+            symbol["detail"] = detail.into();
+        }
+
+        if let Some(children) = children {
+            symbol["children"] = symbols(children, source).into();
+        }
+
+        Some(symbol)
+    }).collect()
+}
+
+/// Collects folding ranges for declarations and control structures with bodies, closures, arrays
+/// and comments that span more than one line.
+struct Folds<'a> {
+    source: &'a str,
+    ranges: Vec<Value>,
+}
+
+impl Folds<'_> {
+    /// Fold a node's lines. A block keeps its last line, which has the closing brace, visible.
+    fn fold(&mut self, span: Span, kind: Option<&str>) {
+        let start = position(self.source, span.start).0;
+        let end = position(self.source, span.end.saturating_sub(1)).0;
+        let end = if kind.is_some() { end } else { end.saturating_sub(1) };
+
+        if end > start {
+            let mut range = json!({ "startLine": start, "endLine": end });
+
+            if let Some(kind) = kind {
+                range["kind"] = kind.into();
+            }
+
+            self.ranges.push(range);
+        }
+    }
+}
+
+impl Visitor for Folds<'_> {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::Comment { .. } => self.fold(statement.span, Some("comment")),
+            StatementKind::Namespace { body, .. } if body.is_empty() => {},
+            StatementKind::Namespace { .. } | StatementKind::Class { .. } | StatementKind::Trait { .. } | StatementKind::Interface { .. } |
+            StatementKind::Enum { .. } | StatementKind::Function { .. } | StatementKind::Method { .. } | StatementKind::If { .. } |
+            StatementKind::While { .. } | StatementKind::For { .. } | StatementKind::Foreach { .. } |
+            StatementKind::Switch { .. } | StatementKind::Try { .. } => self.fold(statement.span, None),
+            _ => {},
+        }
+
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        if let ExpressionKind::Closure { .. } | ExpressionKind::Array { .. } | ExpressionKind::Match { .. } = &expression.kind {
+            self.fold(expression.span, None);
+        }
+
+        walk_expression(self, expression);
+    }
+}
+
+/// The range that a span covers. Spans that the parser gives for the end of file are on line 0.
+fn range(source: &str, span: Span) -> Value {
+    let (start, end) = match span.line {
+        0 => (source.len(), source.len()),
+        _ => (span.start, span.end),
+    };
+
+    let (start_line, start_character) = position(source, start);
+    let (end_line, end_character) = position(source, end);
+
+    json!({
+        "start": { "line": start_line, "character": start_character },
+        "end": { "line": end_line, "character": end_character },
+    })
+}
+
+/// The 0-based line of a byte offset, and its column in UTF-16 code units, as positions are given
+/// in the protocol.
+fn position(source: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(source.len());
+
+    while ! source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+
+    let start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+
+    (source[..start].matches('\n').count(), source[start..offset].encode_utf16().count())
+}
+
+/// The byte offset of a protocol position, kept within its line and the source.
+fn offset(source: &str, position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or_default() as usize;
+    let character = position["character"].as_u64().unwrap_or_default() as usize;
+
+    let mut start = 0;
+
+    for _ in 0..line {
+        match source[start..].find('\n') {
+            Some(i) => start += i + 1,
+            None => return source.len(),
+        }
+    }
+
+    let text = source[start..].split('\n').next().unwrap_or_default();
+    let mut units = 0;
+
+    for (i, c) in text.char_indices() {
+        if units >= character {
+            return start + i;
+        }
+
+        units += c.len_utf16();
+    }
+
+    start + text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{offset, position, read_message, write_message, Server};
+
+    fn open(server: &mut Server, text: &str) -> Vec<Value> {
+        server.handle(json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }));
+        server.handle(json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": { "textDocument": { "uri": "file:///a.php", "text": text } } }))
+    }
+
+    fn request(server: &mut Server, method: &str) -> Value {
+        server.handle(json!({ "jsonrpc": "2.0", "id": 2, "method": method, "params": { "textDocument": { "uri": "file:///a.php" } } })).remove(0)
+    }
+
+    #[test]
+    fn lifecycle() {
+        let mut server = Server::default();
+
+        let response = server.handle(json!({ "jsonrpc": "2.0", "id": 1, "method": "textDocument/documentSymbol", "params": {} }));
+        assert_eq!(response[0]["error"]["code"], -32002);
+
+        let response = server.handle(json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }));
+        assert_eq!(response[0]["result"]["capabilities"]["textDocumentSync"]["change"], 2);
+
+        let response = server.handle(json!({ "jsonrpc": "2.0", "id": 2, "method": "unknown" }));
+        assert_eq!(response[0]["error"]["code"], -32601);
+
+        assert_eq!(server.handle(json!({ "jsonrpc": "2.0", "id": 3, "method": "shutdown" }))[0]["result"], Value::Null);
+        assert!(server.handle(json!({ "jsonrpc": "2.0", "method": "exit" })).is_empty());
+        assert_eq!(server.exit, Some(0));
+    }
+
+    #[test]
+    fn diagnostics() {
+        let mut server = Server::default();
+        let published = open(&mut server, "<?php\n$a = ;\necho 1;");
+
+        assert_eq!(published[0]["method"], "textDocument/publishDiagnostics");
+        assert_eq!(published[0]["params"]["diagnostics"][0]["range"]["start"], json!({ "line": 1, "character": 5 }));
+        assert_eq!(published[0]["params"]["diagnostics"][0]["severity"], 1);
+
+        let published = server.handle(json!({ "jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+            "textDocument": { "uri": "file:///a.php", "version": 2 },
+            "contentChanges": [{ "range": { "start": { "line": 1, "character": 5 }, "end": { "line": 1, "character": 5 } }, "text": "1" }],
+        } }));
+
+        assert_eq!(published[0]["params"]["diagnostics"], json!([]));
+        assert_eq!(server.documents["file:///a.php"].text, "<?php\n$a = 1;\necho 1;");
+
+        let published = server.handle(json!({ "jsonrpc": "2.0", "method": "textDocument/didClose", "params": { "textDocument": { "uri": "file:///a.php" } } }));
+
+        assert_eq!(published[0]["params"]["diagnostics"], json!([]));
+        assert!(server.documents.is_empty());
+    }
+
+    #[test]
+    fn symbols() {
+        let mut server = Server::default();
+        open(&mut server, "<?php
+namespace App {
+    class User {
+        public $name;
+        public function __construct() {}
+        public function greet() {}
+    }
+    function helper() {}
+}
+");
+
+        let symbols = request(&mut server, "textDocument/documentSymbol")["result"].clone();
+        let names = |symbols: &Value| symbols.as_array().unwrap().iter().map(|symbol| (symbol["name"].as_str().unwrap().to_string(), symbol["kind"].as_u64().unwrap())).collect::<Vec<_>>();
+
+        assert_eq!(names(&symbols), [("App".to_string(), 3)]);
+        assert_eq!(names(&symbols[0]["children"]), [("User".to_string(), 5), ("helper".to_string(), 12)]);
+        assert_eq!(names(&symbols[0]["children"][0]["children"]), [("$name".to_string(), 7), ("__construct".to_string(), 9), ("greet".to_string(), 6)]);
+        assert_eq!(symbols[0]["children"][0]["selectionRange"]["start"], json!({ "line": 2, "character": 10 }));
+    }
+
+    #[test]
+    fn folding() {
+        let mut server = Server::default();
+        open(&mut server, "<?php
+/*
+ * Notes.
+ */
+function f() {
+    $a = [
+        1,
+    ];
+}
+");
+
+        let folds = request(&mut server, "textDocument/foldingRange")["result"].clone();
+
+        assert_eq!(folds, json!([
+            { "startLine": 1, "endLine": 3, "kind": "comment" },
+            { "startLine": 4, "endLine": 7 },
+            { "startLine": 5, "endLine": 6 },
+        ]));
+    }
+
+    #[test]
+    fn positions() {
+        let source = "a\né𝄞b\n";
+
+        assert_eq!(position(source, 4), (1, 1));
+        assert_eq!(position(source, 8), (1, 3));
+        assert_eq!(offset(source, &json!({ "line": 1, "character": 3 })), 8);
+        assert_eq!(offset(source, &json!({ "line": 1, "character": 99 })), 9);
+        assert_eq!(offset(source, &json!({ "line": 5, "character": 0 })), source.len());
+    }
+
+    #[test]
+    fn framing() {
+        let mut output = Vec::new();
+        write_message(&mut output, &json!({ "id": 1 })).unwrap();
+
+        assert_eq!(output, b"Content-Length: 8\r\n\r\n{\"id\":1}");
+        assert_eq!(read_message(&mut output.as_slice()).unwrap().as_deref(), Some("{\"id\":1}"));
+        assert_eq!(read_message(&mut &b""[..]).unwrap(), None);
+    }
+}
@@ -0,0 +1,184 @@
+use std::io::Read;
+use std::path::Path;
+use std::process::ExitCode;
+
+use trunk_lexer::Lexer;
+use trunk_parser::{Diagnostic, Parser, ParserConfig, Program};
+
+mod ast;
+mod check;
+mod compat;
+mod diff;
+mod fmt;
+mod glob;
+mod grep;
+mod lower;
+mod lsp;
+mod metrics;
+mod options;
+mod parse;
+mod repl;
+mod run;
+mod stubs;
+
+const USAGE: &str = "\
+usage: trunk <command> [options]
+
+commands:
+    parse <file> [--json|--pretty|--dot]    print the AST of a file, or its errors
+    ast <file> [--filter <kinds>] [--depth <n>]
+                                            print the AST of a file as an indented tree,
+                                            only showing nodes of the given kinds, such as
+                                            `Class,Method`, and as many levels as asked for
+    check <paths...> [--exclude <glob>]... [--quiet] [--json]
+                                            check files and directories of .php files for
+                                            syntax errors
+    compat <paths...> [--target <version>] [--exclude <glob>]... [--json]
+                                            report syntax, functions and constructs that are
+                                            deprecated or removed in a PHP version, 8.4 by
+                                            default
+    fmt <paths...> [--check|--diff] [--indent <n>|--tabs] [--same-line-braces] [--exclude <glob>]...
+                                            format files and directories of .php files in
+                                            the PSR-12 style, or list or show what would change
+    metrics <paths...> [--exclude <glob>]... [--json] [--max-complexity <n>]
+                                            print the lines, cyclomatic complexity, nesting
+                                            depth and parameters of every function, method
+                                            and class, and fail if any function or method is
+                                            more complex than allowed
+    stubs <paths...> [--exclude <glob>]... [--output <dir>]
+                                            print the declarations of a file without their
+                                            bodies, or write them for every file to a directory
+    lower <paths...> --target <version> [--exclude <glob>]... [--output <dir>]
+                                            rewrite arrow functions, `match`, `throw`
+                                            expressions and promoted constructor parameters
+                                            for an older version of PHP, and print the result
+                                            or write it for every file to a directory
+    grep <pattern> <paths...> [--exclude <glob>]... [--json] [-l]
+                                            find the nodes that match a selector, such as
+                                            `Method[name=__construct] Param[default]`, and
+                                            print where each one is, or only the files
+    lsp                                     run a language server over standard input and
+                                            output, with diagnostics, document symbols and
+                                            folding ranges
+    repl                                    read PHP snippets interactively and run them,
+                                            optionally showing their tokens and AST
+    run <file>                              run a file with the interpreter, which supports
+                                            functions, arrays and control flow but not yet
+                                            classes
+
+A file of `-` is read from standard input.";
+
+/// The source had errors.
+const FAILURE: u8 = 1;
+/// The command couldn't run at all, because of bad arguments or a file that couldn't be read.
+const USAGE_ERROR: u8 = 2;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("parse") => parse::run(&args[1..]),
+        Some("ast") => ast::run(&args[1..]),
+        Some("check") => check::run(&args[1..]),
+        Some("compat") => compat::run(&args[1..]),
+        Some("fmt") => fmt::run(&args[1..]),
+        Some("grep") => grep::run(&args[1..]),
+        Some("lower") => lower::run(&args[1..]),
+        Some("lsp") => lsp::run(&args[1..]),
+        Some("metrics") => metrics::run(&args[1..]),
+        Some("repl") => repl::run(&args[1..]),
+        Some("run") => run::run(&args[1..]),
+        Some("stubs") => stubs::run(&args[1..]),
+        Some("help" | "--help" | "-h") => {
+            println!("{}", USAGE);
+            ExitCode::SUCCESS
+        },
+        Some(command) => usage_error(&format!("unknown command `{}`", command)),
+        None => usage_error("no command given"),
+    }
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    ExitCode::from(USAGE_ERROR)
+}
+
+/// Read a file, or standard input if the path is `-`.
+fn read(path: &Path) -> std::io::Result<String> {
+    if path == Path::new("-") {
+        let mut source = String::new();
+        std::io::stdin().read_to_string(&mut source)?;
+
+        Ok(source)
+    } else {
+        std::fs::read_to_string(path)
+    }
+}
+
+/// Lex and parse the source, returning the program if there were no errors, along with every
+/// error and warning that was found.
+fn parse(source: &str) -> (Option<Program>, Vec<Diagnostic>) {
+    parse_with_config(source, ParserConfig::default())
+}
+
+/// Like `parse`, but for a particular PHP version or with other settings.
+fn parse_with_config(source: &str, config: ParserConfig) -> (Option<Program>, Vec<Diagnostic>) {
+    let mut lexer = Lexer::new(None);
+
+    let tokens = match lexer.tokenize(source) {
+        Ok(tokens) => tokens,
+        Err(error) => return (None, vec![Diagnostic::from(&error)]),
+    };
+
+    let mut diagnostics: Vec<Diagnostic> = lexer.warnings().iter().map(Diagnostic::from).collect();
+    let mut parser = Parser::new(Some(config));
+    let result = parser.parse(&tokens);
+
+    diagnostics.extend(parser.warnings().iter().map(Diagnostic::from));
+
+    match result {
+        Ok(program) => (Some(program), diagnostics),
+        Err(error) => {
+            diagnostics.push(Diagnostic::from(&error));
+            (None, diagnostics)
+        },
+    }
+}
+
+/// Print diagnostics to standard error, either rendered for people or as JSON lines.
+fn report(diagnostics: &[Diagnostic], source: &str, path: &Path, json: bool) {
+    let path = if path == Path::new("-") { Path::new("<stdin>") } else { path };
+
+    for diagnostic in diagnostics {
+        if json {
+            eprintln!("{}", diagnostic.to_json(source, Some(path)));
+        } else {
+            eprintln!("{}", diagnostic.render(source, Some(path)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_parser::Severity;
+
+    use super::parse;
+
+    #[test]
+    fn diagnostics() {
+        let (program, diagnostics) = parse("<?php $a = (real) 99999999999999999999;");
+
+        assert!(program.is_some());
+        assert_eq!(diagnostics.iter().map(|d| d.code).collect::<Vec<_>>(), ["integer-overflow", "removed"]);
+
+        let (program, diagnostics) = parse("<?php $a = (real) 1 2;");
+
+        assert!(program.is_none());
+        assert_eq!(diagnostics.iter().map(|d| (d.severity, d.code)).collect::<Vec<_>>(), [(Severity::Warning, "removed"), (Severity::Error, "expected-token")]);
+
+        let (program, diagnostics) = parse("<?php 1__2;");
+
+        assert!(program.is_none());
+        assert_eq!(diagnostics[0].code, "unexpected-character");
+    }
+}
@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use serde_json::{json, Value};
+use trunk_analyser::{measure, Metrics};
+
+use crate::check::collect;
+use crate::options::exclude;
+use crate::{read, report, usage_error, FAILURE, USAGE_ERROR};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Options {
+    paths: Vec<PathBuf>,
+    excludes: Vec<String>,
+    json: bool,
+    max_complexity: Option<usize>,
+}
+
+/// `trunk metrics <paths...> [--exclude <glob>]... [--json] [--max-complexity <n>]`
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match arguments(args) {
+        Ok(options) => options,
+        Err(message) => return usage_error(&message),
+    };
+
+    let mut files = Vec::new();
+
+    for path in &options.paths {
+        if let Err(error) = collect(path, &options.excludes, &mut files) {
+            eprintln!("error: couldn't read {}: {}", path.display(), error);
+            return ExitCode::from(USAGE_ERROR);
+        }
+    }
+
+    let mut measured = Vec::new();
+    let mut failed = false;
+
+    for path in &files {
+        let source = match read(path) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("error: couldn't read {}: {}", path.display(), error);
+                failed = true;
+                continue;
+            },
+        };
+
+        match crate::parse(&source) {
+            (Some(program), _) => measured.extend(measure(&program, &source).into_iter().map(|metrics| (path.as_path(), metrics))),
+            (None, diagnostics) => {
+                report(&diagnostics, &source, path, options.json);
+                failed = true;
+            },
+        }
+    }
+
+    if options.json {
+        let units: Vec<Value> = measured.iter().map(|(path, metrics)| to_json(path, metrics)).collect();
+        println!("{}", serde_json::to_string_pretty(&units).expect("metrics are serializable"));
+    } else {
+        print!("{}", table(&measured));
+    }
+
+    if let Some(max) = options.max_complexity {
+        for (path, metrics) in measured.iter().filter(|(_, metrics)| metrics.complexity > max && metrics.params.is_some()) {
+            eprintln!("error: {} at {} has a complexity of {}, over the budget of {}", metrics.name, location(path, metrics), metrics.complexity, max);
+            failed = true;
+        }
+    }
+
+    if failed {
+        ExitCode::from(FAILURE)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn arguments(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if let Some(glob) = exclude(arg, &mut args)? {
+            options.excludes.push(glob);
+            continue;
+        }
+
+        match arg.as_str() {
+            "--json" => options.json = true,
+            "--max-complexity" => match args.next().map(|max| max.parse()) {
+                Some(Ok(max)) => options.max_complexity = Some(max),
+                _ => return Err("--max-complexity needs a number".into()),
+            },
+            flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option `{}`", flag)),
+            path => options.paths.push(path.into()),
+        }
+    }
+
+    if options.paths.is_empty() {
+        return Err("no files or directories given".into());
+    }
+
+    Ok(options)
+}
+
+fn location(path: &Path, metrics: &Metrics) -> String {
+    let path = if path == Path::new("-") { Path::new("<stdin>") } else { path };
+
+    format!("{}:{}", path.display(), metrics.span.line)
+}
+
+fn to_json(path: &Path, metrics: &Metrics) -> Value {
+    json!({
+        "file": path.display().to_string(),
+        "line": metrics.span.line,
+        "kind": metrics.kind.as_str(),
+        "name": metrics.name,
+        "lines": metrics.lines,
+        "complexity": metrics.complexity,
+        "depth": metrics.depth,
+        "params": metrics.params,
+    })
+}
+
+/// Lay the metrics out in columns, with the numbers aligned to the right.
+fn table(measured: &[(&Path, Metrics)]) -> String {
+    let header = ["LOCATION", "KIND", "NAME", "LINES", "COMPLEXITY", "DEPTH", "PARAMS"].map(String::from);
+    let rows: Vec<[String; 7]> = measured.iter().map(|(path, metrics)| [
+        location(path, metrics),
+        metrics.kind.as_str().to_string(),
+        metrics.name.clone(),
+        metrics.lines.to_string(),
+        metrics.complexity.to_string(),
+        metrics.depth.to_string(),
+        metrics.params.map_or("-".to_string(), |params| params.to_string()),
+    ]).collect();
+
+    let mut widths = [0; 7];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells: Vec<String> = row.iter().enumerate().map(|(column, cell)| match column {
+            0..=2 => format!("{:<width$}", cell, width = widths[column]),
+            _ => format!("{:>width$}", cell, width = widths[column]),
+        }).collect();
+
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use trunk_analyser::measure;
+    use trunk_parser::parse;
+
+    use super::{arguments, table, to_json, Options};
+    use crate::options::args;
+
+    #[test]
+    fn options() {
+        assert_eq!(arguments(&args(&["src", "--json", "--max-complexity", "10", "--exclude=vendor"])), Ok(Options {
+            paths: vec!["src".into()],
+            excludes: vec!["vendor".into()],
+            json: true,
+            max_complexity: Some(10),
+        }));
+
+        assert!(arguments(&args(&[])).is_err());
+        assert!(arguments(&args(&["src", "--max-complexity", "many"])).is_err());
+        assert!(arguments(&args(&["src", "--sort"])).is_err());
+    }
+
+    #[test]
+    fn output() {
+        let source = "<?php
+class Counter {
+    private int $count = 0;
+
+    public function add(int $n): void {
+        if ($n > 0) {
+            $this->count += $n;
+        }
+    }
+}
+";
+        let path = Path::new("src/Counter.php");
+        let measured: Vec<_> = measure(&parse(source).unwrap(), source).into_iter().map(|metrics| (path, metrics)).collect();
+
+        assert_eq!(table(&measured), "\
+LOCATION           KIND    NAME          LINES  COMPLEXITY  DEPTH  PARAMS
+src/Counter.php:2  class   Counter           9           2      1       -
+src/Counter.php:5  method  Counter::add      5           2      1       1
+");
+
+        let json = to_json(path, &measured[1].1);
+        assert_eq!(json["name"], "Counter::add");
+        assert_eq!(json["params"], 1);
+        assert_eq!(to_json(path, &measured[0].1)["params"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,39 @@
+use std::slice::Iter;
+
+/// Take the glob of an `--exclude <glob>` or `--exclude=<glob>` option, if that's what `arg` is.
+/// The first form takes the glob from the next argument.
+pub fn exclude(arg: &str, args: &mut Iter<String>) -> Result<Option<String>, String> {
+    if arg == "--exclude" {
+        return args.next().cloned().map(Some).ok_or_else(|| "--exclude needs a glob".into());
+    }
+
+    Ok(arg.strip_prefix("--exclude=").map(String::from))
+}
+
+/// Arguments as they'd be given on the command line.
+#[cfg(test)]
+pub fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{args, exclude};
+
+    #[test]
+    fn excludes() {
+        let args = args(&["--exclude", "vendor", "--exclude=*.blade.php", "src", "--exclude"]);
+        let mut args = args.iter();
+        let mut excludes = Vec::new();
+
+        while let Some(arg) = args.next() {
+            match exclude(arg, &mut args) {
+                Ok(Some(glob)) => excludes.push(glob),
+                Ok(None) => assert_eq!(arg, "src"),
+                Err(message) => assert_eq!(message, "--exclude needs a glob"),
+            }
+        }
+
+        assert_eq!(excludes, ["vendor", "*.blade.php"]);
+    }
+}
@@ -0,0 +1,109 @@
+use std::path::Path;
+use std::process::ExitCode;
+
+use trunk_parser::{to_dot, Program};
+
+use crate::{read, report, usage_error, FAILURE, USAGE_ERROR};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// Rust's pretty-printed `Debug` output.
+    Pretty,
+    Json,
+    Dot,
+}
+
+/// `trunk parse <file> [--json|--pretty|--dot]`
+pub fn run(args: &[String]) -> ExitCode {
+    let (path, format) = match arguments(args) {
+        Ok(arguments) => arguments,
+        Err(message) => return usage_error(&message),
+    };
+
+    let source = match read(path) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("error: couldn't read {}: {}", path.display(), error);
+            return ExitCode::from(USAGE_ERROR);
+        },
+    };
+
+    let (program, diagnostics) = crate::parse(&source);
+
+    report(&diagnostics, &source, path, format == Format::Json);
+
+    match program {
+        Some(program) => {
+            println!("{}", output(&program, format));
+            ExitCode::SUCCESS
+        },
+        None => ExitCode::from(FAILURE),
+    }
+}
+
+fn arguments(args: &[String]) -> Result<(&Path, Format), String> {
+    let mut path = None;
+    let mut format = None;
+
+    for arg in args {
+        let flag = match arg.as_str() {
+            "--pretty" => Format::Pretty,
+            "--json" => Format::Json,
+            "--dot" => Format::Dot,
+            flag if flag.starts_with("--") => return Err(format!("unknown option `{}`", flag)),
+            file if path.is_none() => {
+                path = Some(Path::new(file));
+                continue;
+            },
+            _ => return Err("only one file can be parsed at a time".into()),
+        };
+
+        if format.replace(flag).is_some() {
+            return Err("only one of --json, --pretty and --dot can be given".into());
+        }
+    }
+
+    match path {
+        Some(path) => Ok((path, format.unwrap_or(Format::Pretty))),
+        None => Err("no file given".into()),
+    }
+}
+
+fn output(program: &Program, format: Format) -> String {
+    match format {
+        Format::Pretty => format!("{:#?}", program),
+        Format::Json => serde_json::to_string_pretty(program).expect("the AST is serializable"),
+        Format::Dot => to_dot(program),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{arguments, output, Format};
+    use crate::options::args;
+
+    #[test]
+    fn options() {
+        assert_eq!(arguments(&args(&["a.php"])), Ok((Path::new("a.php"), Format::Pretty)));
+        assert_eq!(arguments(&args(&["--json", "a.php"])), Ok((Path::new("a.php"), Format::Json)));
+        assert_eq!(arguments(&args(&["-", "--dot"])), Ok((Path::new("-"), Format::Dot)));
+
+        assert!(arguments(&args(&[])).is_err());
+        assert!(arguments(&args(&["a.php", "b.php"])).is_err());
+        assert!(arguments(&args(&["a.php", "--json", "--dot"])).is_err());
+        assert!(arguments(&args(&["a.php", "--yaml"])).is_err());
+    }
+
+    #[test]
+    fn formats() {
+        let program = trunk_parser::parse("<?php echo 1;").unwrap();
+
+        assert!(output(&program, Format::Pretty).starts_with("[\n    Statement {"));
+        assert!(output(&program, Format::Dot).starts_with("digraph ast {"));
+
+        let json: serde_json::Value = serde_json::from_str(&output(&program, Format::Json)).unwrap();
+        assert_eq!(json[0]["kind"]["Echo"]["values"][0]["kind"]["Int"]["i"], 1);
+    }
+}
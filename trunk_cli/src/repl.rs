@@ -0,0 +1,324 @@
+use std::io::{BufRead, Write};
+use std::process::ExitCode;
+
+use trunk_interpreter::Interpreter;
+use trunk_lexer::{Lexer, LexerErrorKind, Token, TokenKind};
+use trunk_parser::{dump, Arg, Diagnostic, DumpOptions, Expression, ExpressionKind, Parser, Program, StatementKind};
+
+use crate::run::STACK_SIZE;
+use crate::usage_error;
+
+/// Snippets are written without an open tag, so one is added in front of them.
+const OPEN_TAG: &str = "<?php ";
+
+const HELP: &str = "\
+Type PHP statements without an open tag. A snippet that's missing its closing brackets, or ends
+inside a string or comment, continues on the next line, and an empty line ends it anyway.
+
+Each snippet runs after the ones before it, so their variables and functions are still there. A
+snippet that leaves out its last semicolon shows the value of its last expression.
+
+    :tokens    show or hide the tokens of each snippet
+    :ast       show or hide the AST of each snippet
+    :help      show this message
+    :quit      leave, as does the end of input";
+
+/// What the REPL does with a line of input.
+#[derive(Debug, PartialEq, Eq)]
+enum Step {
+    /// The snippet continues on the next line.
+    Continue,
+    Output(String),
+    Quit,
+}
+
+struct Repl {
+    /// The lines of the snippet so far.
+    snippet: String,
+    tokens: bool,
+    ast: bool,
+    interpreter: Interpreter<'static, Vec<u8>>,
+    /// How many of the interpreter's warnings have been shown.
+    warnings: usize,
+}
+
+/// `trunk repl`
+pub fn run(args: &[String]) -> ExitCode {
+    if let Some(arg) = args.first() {
+        return usage_error(&format!("unexpected argument `{}`", arg));
+    }
+
+    // Snippets run on a thread with plenty of stack, as `trunk run` does.
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(read_eval_print)
+        .expect("the REPL thread can be started")
+        .join()
+        .expect("the REPL doesn't panic")
+}
+
+fn read_eval_print() -> ExitCode {
+    let mut repl = Repl::new(false, false);
+    let mut lines = std::io::stdin().lock().lines();
+
+    println!("Trunk REPL. Type :help for help.");
+
+    loop {
+        print!("{}", repl.prompt());
+        let _ = std::io::stdout().flush();
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            Some(Err(error)) => {
+                eprintln!("error: couldn't read input: {}", error);
+                break;
+            },
+            None => {
+                println!();
+                break;
+            },
+        };
+
+        match repl.input(&line) {
+            Step::Continue => {},
+            Step::Output(output) if output.is_empty() => {},
+            Step::Output(output) => println!("{}", output),
+            Step::Quit => break,
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+impl Repl {
+    fn new(tokens: bool, ast: bool) -> Self {
+        Self { snippet: String::new(), tokens, ast, interpreter: Interpreter::new(Vec::new()), warnings: 0 }
+    }
+
+    fn prompt(&self) -> &'static str {
+        if self.snippet.is_empty() { "php> " } else { "...> " }
+    }
+
+    fn input(&mut self, line: &str) -> Step {
+        if self.snippet.is_empty() {
+            match line.trim() {
+                "" => return Step::Continue,
+                ":quit" | ":q" | ":exit" => return Step::Quit,
+                ":help" | ":h" => return Step::Output(HELP.into()),
+                ":tokens" => {
+                    self.tokens = ! self.tokens;
+                    return Step::Output(format!("tokens {}", if self.tokens { "on" } else { "off" }));
+                },
+                ":ast" => {
+                    self.ast = ! self.ast;
+                    return Step::Output(format!("AST {}", if self.ast { "on" } else { "off" }));
+                },
+                command if command.starts_with(':') => return Step::Output(format!("unknown command `{}`, try :help", command)),
+                _ => {},
+            }
+        }
+
+        let forced = line.trim().is_empty();
+
+        self.snippet.push_str(line);
+        self.snippet.push('\n');
+
+        let source = format!("{}{}", OPEN_TAG, self.snippet);
+
+        if ! forced && ! complete(&source) {
+            return Step::Continue;
+        }
+
+        let output = self.evaluate(&source);
+        self.snippet.clear();
+
+        Step::Output(output)
+    }
+
+    fn evaluate(&mut self, source: &str) -> String {
+        let tokens = match Lexer::new(None).tokenize(source) {
+            Ok(tokens) => tokens,
+            Err(error) => return Diagnostic::from(&error).render(source, None),
+        };
+
+        let mut output = Vec::new();
+
+        if self.tokens {
+            // Leave out the open tag that was added, and the end of the input.
+            let shown: Vec<String> = tokens.iter()
+                .filter(|token| ! matches!(token.kind, TokenKind::OpenTag(_) | TokenKind::Eof))
+                .map(|token| format!("{:?}", token.kind))
+                .collect();
+
+            output.push(shown.join(" "));
+        }
+
+        let mut parser = Parser::new(None);
+
+        let (mut program, show_value) = match parser.parse(&tokens) {
+            Ok(program) => (program, false),
+            // Let the last statement leave out its semicolon, as in `1 + 2`.
+            Err(error) => match with_semicolon(source).and_then(|source| parse(&source)) {
+                Some(program) => (program, true),
+                None => {
+                    output.push(Diagnostic::from(&error).render(source, None));
+                    return output.join("\n");
+                },
+            },
+        };
+
+        if self.ast {
+            output.push(dump(&program, &DumpOptions::default()).trim_end().to_string());
+        }
+
+        if show_value {
+            dump_last_value(&mut program);
+        }
+
+        // Later snippets can call the functions that this one declares, so the interpreter borrows
+        // every snippet's program for as long as the REPL runs.
+        let program: &'static Program = Box::leak(Box::new(program));
+        let result = self.interpreter.run(program);
+
+        let printed = std::mem::take(self.interpreter.output_mut());
+
+        if ! printed.is_empty() {
+            output.push(String::from_utf8_lossy(&printed).trim_end_matches('\n').to_string());
+        }
+
+        // A runtime problem can be in code from an earlier snippet, such as a function that it
+        // declared, so it's shown without the source around it.
+        let mut diagnostics: Vec<Diagnostic> = self.interpreter.warnings()[self.warnings..].iter().map(Diagnostic::from).collect();
+        self.warnings = self.interpreter.warnings().len();
+
+        if let Err(error) = &result {
+            diagnostics.push(Diagnostic::from(error));
+        }
+
+        for mut diagnostic in diagnostics {
+            diagnostic.span = None;
+            output.push(diagnostic.render(source, None).trim_end().to_string());
+        }
+
+        output.join("\n")
+    }
+}
+
+/// Whether the source could be a whole snippet, rather than one whose brackets, string or comment
+/// continue on a later line.
+fn complete(source: &str) -> bool {
+    let tokens = match Lexer::new(None).tokenize(source) {
+        Ok(tokens) => tokens,
+        Err(error) => return error.kind != LexerErrorKind::UnexpectedEndOfFile,
+    };
+
+    depth(&tokens) <= 0 && ! unterminated_string(source, &tokens)
+}
+
+fn depth(tokens: &[Token]) -> isize {
+    tokens.iter()
+        .map(|token| match token.kind {
+            TokenKind::LeftBrace | TokenKind::LeftBracket | TokenKind::LeftParen | TokenKind::Attribute => 1,
+            TokenKind::RightBrace | TokenKind::RightBracket | TokenKind::RightParen => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Whether the source ends inside a quoted string, which the lexer ends at the end of the input.
+fn unterminated_string(source: &str, tokens: &[Token]) -> bool {
+    let Some(last) = tokens.iter().rev().find(|token| token.kind != TokenKind::Eof) else { return false };
+    let text = source.get(last.span.start..last.span.end).unwrap_or_default();
+
+    match text.chars().next() {
+        Some(quote @ ('"' | '\'')) => text.len() < 2 || ! text.ends_with(quote) || text.ends_with(&format!("\\{}", quote)),
+        _ => false,
+    }
+}
+
+/// The source with a semicolon after it, unless it already ends a statement.
+fn with_semicolon(source: &str) -> Option<String> {
+    let tokens = Lexer::new(None).tokenize(source).ok()?;
+    let last = tokens.iter().rev().find(|token| ! matches!(token.kind, TokenKind::Eof | TokenKind::Comment(_) | TokenKind::DocComment(_)))?;
+
+    if matches!(last.kind, TokenKind::SemiColon | TokenKind::RightBrace | TokenKind::OpenTag(_)) {
+        return None;
+    }
+
+    Some(format!("{};", source.trim_end()))
+}
+
+/// Replace a program's last statement with a `var_dump` of it, if it's an expression.
+fn dump_last_value(program: &mut Program) {
+    let Some(statement) = program.last_mut() else { return };
+    let StatementKind::Expression { expr } = &mut statement.kind else { return };
+
+    let span = expr.span;
+    let value = std::mem::replace(expr, ExpressionKind::Null.into());
+    let target = Expression::new(ExpressionKind::Identifier { name: "var_dump".into() }, span);
+
+    *expr = Expression::new(ExpressionKind::Call { target: Box::new(target), args: vec![Arg { name: None, value, unpack: false }] }, span);
+}
+
+fn parse(source: &str) -> Option<Program> {
+    let tokens = Lexer::new(None).tokenize(source).ok()?;
+
+    Parser::new(None).parse(&tokens).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Repl, Step};
+
+    fn repl() -> Repl {
+        Repl::new(true, false)
+    }
+
+    #[test]
+    fn snippets() {
+        let mut repl = repl();
+
+        assert_eq!(repl.input("$a = 1;"), Step::Output("Variable(\"a\") Equals Int(1) SemiColon".into()));
+        assert_eq!(repl.input("   "), Step::Continue);
+
+        assert_eq!(repl.input(":ast"), Step::Output("AST on".into()));
+        assert_eq!(repl.input(":tokens"), Step::Output("tokens off".into()));
+        assert_eq!(repl.input("1 + 2"), Step::Output("[0]: Expression 1:7\n  expr: Infix 1:7 op=\"Add\"\n    lhs: Int 1:7 i=1\n    rhs: Int 1:11 i=2\nint(3)".into()));
+
+        assert!(matches!(repl.input("1 2"), Step::Output(output) if output.starts_with("error: expected `;`")));
+        assert_eq!(repl.input(":quit"), Step::Quit);
+    }
+
+    #[test]
+    fn continuation() {
+        let mut repl = repl();
+
+        assert_eq!(repl.input("function f() {"), Step::Continue);
+        assert_eq!(repl.prompt(), "...> ");
+        assert_eq!(repl.input("    return ['a',"), Step::Continue);
+        assert_eq!(repl.input("        'b'];"), Step::Continue);
+        assert!(matches!(repl.input("}"), Step::Output(_)));
+        assert_eq!(repl.prompt(), "php> ");
+
+        assert_eq!(repl.input("$a = \"multi"), Step::Continue);
+        assert!(matches!(repl.input("line\";"), Step::Output(_)));
+
+        // An empty line ends a snippet that would otherwise continue.
+        assert_eq!(repl.input("if ($a) {"), Step::Continue);
+        assert!(matches!(repl.input(""), Step::Output(output) if output.contains("error: ")));
+        assert_eq!(repl.prompt(), "php> ");
+    }
+    #[test]
+    fn evaluation() {
+        let mut repl = Repl::new(false, false);
+
+        assert_eq!(repl.input("$a = 2;"), Step::Output("".into()));
+        assert_eq!(repl.input("function double($n) { return $n * 2; }"), Step::Output("".into()));
+        assert_eq!(repl.input("echo double($a), \"\\n\";"), Step::Output("4".into()));
+        assert_eq!(repl.input("double($a) + 1"), Step::Output("int(5)".into()));
+
+        assert_eq!(repl.input("$b"), Step::Output("NULL\nwarning: Undefined variable $b".into()));
+        assert_eq!(repl.input("echo 1;"), Step::Output("1".into()));
+        assert!(matches!(repl.input("new Foo;"), Step::Output(output) if output.starts_with("error: not supported yet")));
+    }
+}
@@ -0,0 +1,60 @@
+use std::io::BufWriter;
+use std::path::Path;
+use std::process::ExitCode;
+
+use trunk_interpreter::Interpreter;
+use trunk_parser::Diagnostic;
+
+use crate::{read, report, usage_error, FAILURE, USAGE_ERROR};
+
+/// The exit status of a program that ended with an uncaught error, as with PHP itself.
+const UNCAUGHT: u8 = 255;
+
+/// PHP code that recurses deeply recurses in the interpreter too, so programs run on a thread with
+/// plenty of stack.
+pub(crate) const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// `trunk run <file>`
+pub fn run(args: &[String]) -> ExitCode {
+    let path = match args {
+        [flag] if flag.starts_with("--") => return usage_error(&format!("unknown option `{}`", flag)),
+        [path] => Path::new(path),
+        [] => return usage_error("no file given"),
+        _ => return usage_error("only one file can be run at a time"),
+    };
+
+    let source = match read(path) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("error: couldn't read {}: {}", path.display(), error);
+            return ExitCode::from(USAGE_ERROR);
+        },
+    };
+
+    let (program, diagnostics) = crate::parse(&source);
+
+    report(&diagnostics, &source, path, false);
+
+    let Some(program) = program else { return ExitCode::from(FAILURE) };
+
+    let (result, warnings) = std::thread::scope(|scope| {
+        let thread = std::thread::Builder::new().stack_size(STACK_SIZE).spawn_scoped(scope, || {
+            let mut interpreter = Interpreter::new(BufWriter::new(std::io::stdout().lock()));
+            let result = interpreter.run(&program);
+
+            (result, interpreter.warnings().iter().map(Diagnostic::from).collect::<Vec<_>>())
+        });
+
+        thread.expect("the interpreter thread can be started").join().expect("the interpreter doesn't panic")
+    });
+
+    report(&warnings, &source, path, false);
+
+    match result {
+        Ok(status) => ExitCode::from(status as u8),
+        Err(error) => {
+            report(&[Diagnostic::from(&error)], &source, path, false);
+            ExitCode::from(UNCAUGHT)
+        },
+    }
+}
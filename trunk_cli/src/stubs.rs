@@ -0,0 +1,153 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use trunk_printer::{print, stub};
+
+use crate::check::collect;
+use crate::options::exclude;
+use crate::{read, report, usage_error, FAILURE, USAGE_ERROR};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Options {
+    paths: Vec<PathBuf>,
+    excludes: Vec<String>,
+    /// The directory to write a stub for each file to, or `None` to print the only file's stub.
+    output: Option<PathBuf>,
+}
+
+/// `trunk stubs <paths...> [--exclude <glob>]... [--output <dir>]`
+pub fn run(args: &[String]) -> ExitCode {
+    let options = match arguments(args) {
+        Ok(options) => options,
+        Err(message) => return usage_error(&message),
+    };
+
+    // Each file is kept with the path that it was found under, to lay out the output the same way.
+    let mut files = Vec::new();
+
+    for path in &options.paths {
+        let mut found = Vec::new();
+
+        if let Err(error) = collect(path, &options.excludes, &mut found) {
+            eprintln!("error: couldn't read {}: {}", path.display(), error);
+            return ExitCode::from(USAGE_ERROR);
+        }
+
+        files.extend(found.into_iter().map(|file| (path.as_path(), file)));
+    }
+
+    if options.output.is_none() && files.len() > 1 {
+        return usage_error("--output is needed to write the stubs of more than one file");
+    }
+
+    let mut failed = false;
+
+    for (base, path) in &files {
+        let source = match read(path) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("error: couldn't read {}: {}", path.display(), error);
+                failed = true;
+                continue;
+            },
+        };
+
+        let program = match crate::parse(&source) {
+            (Some(program), _) => program,
+            (None, diagnostics) => {
+                report(&diagnostics, &source, path, false);
+                failed = true;
+                continue;
+            },
+        };
+
+        let stubbed = print(&stub(&program));
+
+        let destination = match &options.output {
+            Some(output) if path != Path::new("-") => output.join(destination(base, path)),
+            _ => {
+                print!("{}", stubbed);
+                continue;
+            },
+        };
+
+        let written = match destination.parent() {
+            Some(parent) => std::fs::create_dir_all(parent).and_then(|_| std::fs::write(&destination, stubbed)),
+            None => std::fs::write(&destination, stubbed),
+        };
+
+        if let Err(error) = written {
+            eprintln!("error: couldn't write {}: {}", destination.display(), error);
+            failed = true;
+        }
+    }
+
+    if failed {
+        ExitCode::from(FAILURE)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn arguments(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if let Some(glob) = exclude(arg, &mut args)? {
+            options.excludes.push(glob);
+            continue;
+        }
+
+        match arg.as_str() {
+            "--output" | "-o" => match args.next() {
+                Some(dir) => options.output = Some(dir.into()),
+                None => return Err("--output needs a directory".into()),
+            },
+            flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option `{}`", flag)),
+            path => options.paths.push(path.into()),
+        }
+    }
+
+    if options.paths.is_empty() {
+        return Err("no files or directories given".into());
+    }
+
+    Ok(options)
+}
+
+/// Where the output for a file goes under the output directory: the same place as the file is
+/// under the directory that it was found in, or just its name if it was given on its own.
+pub(crate) fn destination(base: &Path, path: &Path) -> PathBuf {
+    match path.strip_prefix(base) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative.to_path_buf(),
+        _ => path.file_name().map_or_else(|| path.to_path_buf(), PathBuf::from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{arguments, destination, Options};
+    use crate::options::args;
+
+    #[test]
+    fn options() {
+        assert_eq!(arguments(&args(&["src", "--exclude", "vendor", "--output", "stubs"])), Ok(Options {
+            paths: vec!["src".into()],
+            excludes: vec!["vendor".into()],
+            output: Some("stubs".into()),
+        }));
+
+        assert!(arguments(&args(&[])).is_err());
+        assert!(arguments(&args(&["src", "--output"])).is_err());
+        assert!(arguments(&args(&["src", "--bodies"])).is_err());
+    }
+
+    #[test]
+    fn destinations() {
+        assert_eq!(destination(Path::new("src"), Path::new("src/Models/User.php")), PathBuf::from("Models/User.php"));
+        assert_eq!(destination(Path::new("lib/helpers.php"), Path::new("lib/helpers.php")), PathBuf::from("helpers.php"));
+    }
+}
@@ -0,0 +1,320 @@
+use trunk_parser::{Statement, StatementKind};
+
+mod types;
+
+pub use types::{CallableParam, DocType, ShapeItem, TypeError};
+
+/// A `/** */` comment, split into its summary, its description and its tags.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocBlock {
+    /// The text up to the first blank line or tag.
+    pub summary: String,
+    /// The text after the summary, up to the first tag.
+    pub description: String,
+    pub tags: Vec<Tag>,
+}
+
+/// A tag such as `@param int $id`. Tags with a `@phpstan-` or `@psalm-` prefix are read the same
+/// way as the tag without it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tag {
+    /// `@param Type $name`, where the type can be left out. The name is without the `$`.
+    Param { r#type: Option<DocType>, name: String, by_ref: bool, variadic: bool, description: String },
+    Return { r#type: DocType, description: String },
+    /// `@var Type`, with the name of the variable or property without the `$` if it's given.
+    Var { r#type: DocType, name: Option<String>, description: String },
+    Template(Template),
+    Throws { r#type: DocType, description: String },
+    /// Any other tag, by its name without the `@`, with the rest of its text.
+    Other { name: String, body: String },
+    /// A tag that should have a type or a name, but whose text couldn't be parsed.
+    Invalid { name: String, body: String, error: TypeError },
+}
+
+/// `@template T of Bound`, along with `@template-covariant` and `@template-contravariant`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pub name: String,
+    pub bound: Option<DocType>,
+    pub variance: Variance,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variance {
+    Invariant,
+    Covariant,
+    Contravariant,
+}
+
+impl DocBlock {
+    /// Parse a doc comment, with or without its `/**` and `*/`.
+    pub fn parse(comment: &str) -> Self {
+        let comment = comment.trim();
+        let comment = comment.strip_prefix("/**").unwrap_or(comment);
+        let comment = comment.strip_suffix("*/").unwrap_or(comment);
+
+        let lines = comment.lines().map(|line| {
+            let line = line.trim_start();
+            let line = line.strip_prefix('*').unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line).trim_end()
+        });
+
+        let mut text: Vec<&str> = Vec::new();
+        let mut tags: Vec<(&str, String)> = Vec::new();
+
+        for line in lines {
+            if let Some(tag) = line.trim_start().strip_prefix('@') {
+                let (name, body) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+                tags.push((name, body.trim().to_string()));
+            } else if let Some((_, body)) = tags.last_mut() {
+                body.push('\n');
+                body.push_str(line);
+            } else {
+                text.push(line);
+            }
+        }
+
+        let text = text.join("\n");
+        let text = text.trim();
+        let (summary, description) = text.split_once("\n\n").unwrap_or((text, ""));
+
+        Self {
+            summary: summary.to_string(),
+            description: description.trim().to_string(),
+            tags: tags.iter().map(|(name, body)| tag(name, body.trim())).collect(),
+        }
+    }
+
+    /// The docblock of a function, method, class, property or constant, if it has one.
+    pub fn of(statement: &Statement) -> Option<Self> {
+        match &statement.kind {
+            StatementKind::Function { doc_comment, .. } | StatementKind::Method { doc_comment, .. } |
+            StatementKind::Class { doc_comment, .. } | StatementKind::Property { doc_comment, .. } |
+            StatementKind::Constant { doc_comment, .. } => doc_comment.as_deref().map(Self::parse),
+            _ => None,
+        }
+    }
+
+    /// The type of a parameter, by its name without the `$`. As with every lookup, if there's
+    /// more than one tag, the last one wins.
+    pub fn param(&self, name: &str) -> Option<&DocType> {
+        self.tags.iter().rev().find_map(|tag| match tag {
+            Tag::Param { r#type, name: param, .. } if param == name => r#type.as_ref(),
+            _ => None,
+        })
+    }
+
+    pub fn return_type(&self) -> Option<&DocType> {
+        self.tags.iter().rev().find_map(|tag| match tag {
+            Tag::Return { r#type, .. } => Some(r#type),
+            _ => None,
+        })
+    }
+
+    /// The type of a property or variable, from a `@var` tag with its name or without a name.
+    pub fn var(&self, name: &str) -> Option<&DocType> {
+        self.tags.iter().rev().find_map(|tag| match tag {
+            Tag::Var { r#type, name: var, .. } if var.as_deref().is_none_or(|var| var == name) => Some(r#type),
+            _ => None,
+        })
+    }
+
+    pub fn templates(&self) -> impl Iterator<Item = &Template> {
+        self.tags.iter().filter_map(|tag| match tag {
+            Tag::Template(template) => Some(template),
+            _ => None,
+        })
+    }
+
+    pub fn throws(&self) -> impl Iterator<Item = &DocType> {
+        self.tags.iter().filter_map(|tag| match tag {
+            Tag::Throws { r#type, .. } => Some(r#type),
+            _ => None,
+        })
+    }
+}
+
+fn tag(name: &str, body: &str) -> Tag {
+    let kind = name.strip_prefix("phpstan-").or_else(|| name.strip_prefix("psalm-")).unwrap_or(name);
+
+    let tag = match kind {
+        "param" => param(body),
+        "return" => typed(body).map(|(r#type, description)| Tag::Return { r#type, description }),
+        "var" => var(body),
+        "throws" => typed(body).map(|(r#type, description)| Tag::Throws { r#type, description }),
+        "template" => template(body, Variance::Invariant),
+        "template-covariant" => template(body, Variance::Covariant),
+        "template-contravariant" => template(body, Variance::Contravariant),
+        _ => return Tag::Other { name: name.to_string(), body: body.to_string() },
+    };
+
+    tag.unwrap_or_else(|error| Tag::Invalid { name: name.to_string(), body: body.to_string(), error })
+}
+
+/// A type at the start of a tag's text, and the description after it.
+fn typed(body: &str) -> Result<(DocType, String), TypeError> {
+    let (r#type, end) = types::parse_at(body, 0)?;
+
+    Ok((r#type, description(body, end)))
+}
+
+fn param(body: &str) -> Result<Tag, TypeError> {
+    let (r#type, end) = match body.starts_with(['$', '&']) || body.starts_with("...") {
+        true => (None, 0),
+        false => {
+            let (r#type, end) = types::parse_at(body, 0)?;
+            (Some(r#type), end)
+        },
+    };
+
+    let mut offset = skip_space(body, end);
+    let by_ref = body[offset..].starts_with('&');
+    offset = skip_space(body, offset + by_ref as usize);
+    let variadic = body[offset..].starts_with("...");
+    offset = skip_space(body, offset + if variadic { 3 } else { 0 });
+
+    let Some((name, end)) = variable(body, offset) else {
+        return Err(TypeError { message: "expected a parameter name".into(), offset });
+    };
+
+    Ok(Tag::Param { r#type, name, by_ref, variadic, description: description(body, end) })
+}
+
+fn var(body: &str) -> Result<Tag, TypeError> {
+    let (r#type, end) = types::parse_at(body, 0)?;
+
+    match variable(body, skip_space(body, end)) {
+        Some((name, end)) => Ok(Tag::Var { r#type, name: Some(name), description: description(body, end) }),
+        None => Ok(Tag::Var { r#type, name: None, description: description(body, end) }),
+    }
+}
+
+fn template(body: &str, variance: Variance) -> Result<Tag, TypeError> {
+    let end = body.find(|c: char| ! (c.is_alphanumeric() || c == '_')).unwrap_or(body.len());
+
+    if end == 0 {
+        return Err(TypeError { message: "expected a template name".into(), offset: 0 });
+    }
+
+    let name = body[..end].to_string();
+    let offset = skip_space(body, end);
+    let rest = &body[offset..];
+
+    let bound = match rest.starts_with("of ") || rest.starts_with("as ") {
+        true => Some(types::parse_at(body, skip_space(body, offset + 2))?.0),
+        false => None,
+    };
+
+    Ok(Tag::Template(Template { name, bound, variance }))
+}
+
+/// A `$name` at `offset`, without the `$`, and the offset after it.
+fn variable(body: &str, offset: usize) -> Option<(String, usize)> {
+    let name = body[offset..].strip_prefix('$')?;
+    let end = name.find(|c: char| ! (c.is_alphanumeric() || c == '_')).unwrap_or(name.len());
+
+    match end {
+        0 => None,
+        _ => Some((name[..end].to_string(), offset + 1 + end)),
+    }
+}
+
+fn description(body: &str, offset: usize) -> String {
+    body[offset..].trim().to_string()
+}
+
+fn skip_space(body: &str, offset: usize) -> usize {
+    body.len() - body[offset..].trim_start().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_parser::parse;
+
+    use super::{DocBlock, DocType, Tag, Template, Variance};
+
+    fn doc_type(source: &str) -> DocType {
+        DocType::parse(source).unwrap()
+    }
+
+    #[test]
+    fn tags() {
+        let doc = DocBlock::parse("/**
+             * Find users by their ids.
+             *
+             * Users that don't exist are left out,
+             * rather than being null.
+             *
+             * @template T of Model
+             * @template-covariant TKey
+             * @param int[] $ids The ids to look for.
+             * @param array{
+             *     active?: bool,
+             *     roles: list<string>,
+             * } $filters
+             * @param bool &$found
+             * @param string ...$columns
+             * @param $untyped
+             * @psalm-param non-empty-list<int> $ids
+             * @return array<int, T>|null Or null if nothing matched.
+             * @throws \\InvalidArgumentException
+             * @deprecated Use findMany().
+             * @param array< $broken
+             */");
+
+        assert_eq!(doc.summary, "Find users by their ids.");
+        assert_eq!(doc.description, "Users that don't exist are left out,\nrather than being null.");
+
+        assert_eq!(doc.tags[0], Tag::Template(Template { name: "T".into(), bound: Some(doc_type("Model")), variance: Variance::Invariant }));
+        assert_eq!(doc.templates().map(|template| template.variance).collect::<Vec<_>>(), [Variance::Invariant, Variance::Covariant]);
+
+        assert_eq!(doc.tags[2], Tag::Param {
+            r#type: Some(doc_type("int[]")),
+            name: "ids".into(),
+            by_ref: false,
+            variadic: false,
+            description: "The ids to look for.".into(),
+        });
+        assert_eq!(doc.param("ids"), Some(&doc_type("non-empty-list<int>")));
+        assert_eq!(doc.param("filters").unwrap().to_string(), "array{active?: bool, roles: list<string>}");
+        assert!(matches!(&doc.tags[4], Tag::Param { name, by_ref: true, .. } if name == "found"));
+        assert!(matches!(&doc.tags[5], Tag::Param { name, variadic: true, .. } if name == "columns"));
+        assert!(matches!(&doc.tags[6], Tag::Param { r#type: None, name, .. } if name == "untyped"));
+        assert_eq!(doc.param("missing"), None);
+
+        assert_eq!(doc.return_type(), Some(&doc_type("array<int, T>|null")));
+        assert!(matches!(&doc.tags[8], Tag::Return { description, .. } if description == "Or null if nothing matched."));
+        assert_eq!(doc.throws().collect::<Vec<_>>(), [&doc_type("\\InvalidArgumentException")]);
+        assert_eq!(doc.tags[10], Tag::Other { name: "deprecated".into(), body: "Use findMany().".into() });
+        assert!(matches!(&doc.tags[11], Tag::Invalid { name, error, .. } if name == "param" && error.offset == 7));
+    }
+
+    #[test]
+    fn vars() {
+        assert_eq!(DocBlock::parse("/** @var int */").var("count"), Some(&doc_type("int")));
+        assert_eq!(DocBlock::parse("/** @var string $name The name */").var("name"), Some(&doc_type("string")));
+        assert_eq!(DocBlock::parse("/** @var string $name */").var("other"), None);
+        assert_eq!(DocBlock::parse("/** Just text. */"), DocBlock { summary: "Just text.".into(), ..DocBlock::default() });
+    }
+
+    #[test]
+    fn attached() {
+        let program = parse("<?php
+            /** @return list<string> */
+            function names() {}
+
+            class User {
+                /** @var array<string, mixed> */
+                private $data;
+
+                public function save() {}
+            }
+        ").unwrap();
+
+        assert_eq!(DocBlock::of(&program[0]).unwrap().return_type(), Some(&doc_type("list<string>")));
+
+        let trunk_parser::StatementKind::Class { body, .. } = &program[1].kind else { unreachable!() };
+        assert_eq!(DocBlock::of(&body[0]).unwrap().var("data"), Some(&doc_type("array<string, mixed>")));
+        assert_eq!(DocBlock::of(&body[1]), None);
+    }
+}
@@ -0,0 +1,697 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use trunk_parser::Type;
+
+use crate::Template;
+
+/// A type as it's written in a docblock, which can say more than a type declaration, such as
+/// `array<int, User>`, `non-empty-string` or `callable(int): bool`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DocType {
+    /// A class or a built-in type, such as `int`, `Foo\Bar` or `non-empty-string`.
+    Named(String),
+    /// `$this`.
+    This,
+    /// A type with arguments, such as `array<int, string>`, `int<0, max>` or `Collection<User>`.
+    Generic { name: String, args: Vec<DocType> },
+    /// `T[]`.
+    Array(Box<DocType>),
+    /// `array{id: int, name?: string}`, along with `list{...}` and `object{...}`. An unsealed shape
+    /// ends with `...` and can have items that aren't listed.
+    Shape { name: String, items: Vec<ShapeItem>, unsealed: bool },
+    /// `callable(int, string): bool` or `Closure(int): void`.
+    Callable { name: String, params: Vec<CallableParam>, return_type: Option<Box<DocType>> },
+    Nullable(Box<DocType>),
+    Union(Vec<DocType>),
+    Intersection(Vec<DocType>),
+    /// An int, float or string literal, as it's written.
+    Literal(String),
+    /// `Foo::BAR`, or `Foo::BAR_*` for every constant that matches.
+    ClassConstant { class: String, constant: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShapeItem {
+    /// The key without quotes, or `None` for an item that's only a value, as in `array{int, string}`.
+    pub key: Option<String>,
+    pub optional: bool,
+    pub value: DocType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallableParam {
+    pub r#type: DocType,
+    pub by_ref: bool,
+    pub variadic: bool,
+    /// The name without the `$`.
+    pub name: Option<String>,
+    pub optional: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeError {
+    pub message: String,
+    pub offset: usize,
+}
+
+impl Display for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+impl DocType {
+    /// Parse a type on its own, such as `array<string, int>|null`.
+    pub fn parse(source: &str) -> Result<Self, TypeError> {
+        let start = source.len() - source.trim_start().len();
+        let (r#type, end) = parse_at(source, start)?;
+        let rest = &source[end..];
+
+        match rest.trim_start().chars().next() {
+            None => Ok(r#type),
+            Some(c) => Err(TypeError { message: format!("unexpected `{}`", c), offset: source.len() - rest.trim_start().len() }),
+        }
+    }
+
+    /// The closest type that could be declared in PHP, for analysing the type where there's no
+    /// declaration. Pseudo types such as `non-empty-string` become the type that they narrow, and
+    /// templates become their bound, or `mixed` if they don't have one.
+    pub fn to_type(&self, templates: &[Template]) -> Type {
+        match self {
+            Self::Named(name) => named(name, templates),
+            Self::This => Type::Plain("static".into()),
+            Self::Generic { name, .. } => named(name, templates),
+            Self::Array(_) => Type::Plain("array".into()),
+            Self::Shape { name, .. } if name.eq_ignore_ascii_case("object") => Type::Plain("object".into()),
+            Self::Shape { .. } => Type::Plain("array".into()),
+            Self::Callable { name, .. } => match name.trim_start_matches('\\').to_ascii_lowercase().as_str() {
+                "closure" | "pure-closure" => Type::Plain("Closure".into()),
+                _ => Type::Plain("callable".into()),
+            },
+            Self::Nullable(r#type) => match r#type.to_type(templates) {
+                Type::Plain(name) => Type::Nullable(name),
+                r#type => union([r#type, Type::Plain("null".into())]),
+            },
+            Self::Union(types) => union(types.iter().map(|r#type| r#type.to_type(templates))),
+            Self::Intersection(types) => Type::Intersection(types.iter().map(|r#type| r#type.to_type(templates)).collect()),
+            Self::Literal(literal) if literal.starts_with(['\'', '"']) => Type::Plain("string".into()),
+            Self::Literal(literal) if literal.contains('.') => Type::Plain("float".into()),
+            Self::Literal(_) => Type::Plain("int".into()),
+            Self::ClassConstant { .. } => Type::Plain("mixed".into()),
+        }
+    }
+}
+
+impl FromStr for DocType {
+    type Err = TypeError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::parse(source)
+    }
+}
+
+impl Display for DocType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Named(name) | Self::Literal(name) => write!(f, "{}", name),
+            Self::This => write!(f, "$this"),
+            Self::Generic { name, args } => write!(f, "{}<{}>", name, list(args)),
+            Self::Array(r#type) => write!(f, "{}[]", Grouped(r#type, true)),
+            Self::Shape { name, items, unsealed } => {
+                let mut items: Vec<String> = items.iter().map(|item| match &item.key {
+                    Some(key) if key.is_empty() || key.contains(|c: char| ! (c.is_alphanumeric() || c == '_' || c == '-')) => {
+                        format!("'{}'{}: {}", key.replace('\'', "\\'"), if item.optional { "?" } else { "" }, item.value)
+                    },
+                    Some(key) => format!("{}{}: {}", key, if item.optional { "?" } else { "" }, item.value),
+                    None => item.value.to_string(),
+                }).collect();
+
+                if *unsealed {
+                    items.push("...".into());
+                }
+
+                write!(f, "{}{{{}}}", name, items.join(", "))
+            },
+            Self::Callable { name, params, return_type } => {
+                let params: Vec<String> = params.iter().map(|param| {
+                    let mut text = param.r#type.to_string();
+
+                    if param.by_ref || param.variadic || param.name.is_some() {
+                        text.push(' ');
+                    }
+
+                    if param.by_ref {
+                        text.push('&');
+                    }
+
+                    if param.variadic {
+                        text.push_str("...");
+                    }
+
+                    if let Some(name) = &param.name {
+                        text.push('$');
+                        text.push_str(name);
+                    }
+
+                    if param.optional {
+                        text.push('=');
+                    }
+
+                    text
+                }).collect();
+
+                write!(f, "{}({})", name, params.join(", "))?;
+
+                match return_type {
+                    Some(r#type) => write!(f, ": {}", Grouped(r#type, false)),
+                    None => Ok(()),
+                }
+            },
+            Self::Nullable(r#type) => write!(f, "?{}", Grouped(r#type, false)),
+            Self::Union(types) => {
+                let types: Vec<String> = types.iter().map(|r#type| Grouped(r#type, false).to_string()).collect();
+                write!(f, "{}", types.join("|"))
+            },
+            Self::Intersection(types) => {
+                let types: Vec<String> = types.iter().map(|r#type| Grouped(r#type, false).to_string()).collect();
+                write!(f, "{}", types.join("&"))
+            },
+            Self::ClassConstant { class, constant } => write!(f, "{}::{}", class, constant),
+        }
+    }
+}
+
+/// A type in parentheses if it's a union or an intersection, or, when `strict`, anything else
+/// that would take what follows it as part of itself.
+struct Grouped<'a>(&'a DocType, bool);
+
+impl Display for Grouped<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self(r#type, strict) = *self;
+        let grouped = match r#type {
+            DocType::Union(_) | DocType::Intersection(_) => true,
+            DocType::Nullable(_) | DocType::Callable { return_type: Some(_), .. } => strict,
+            _ => false,
+        };
+
+        if grouped {
+            write!(f, "({})", r#type)
+        } else {
+            write!(f, "{}", r#type)
+        }
+    }
+}
+
+fn list(types: &[DocType]) -> String {
+    types.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+}
+
+fn named(name: &str, templates: &[Template]) -> Type {
+    if let Some(template) = templates.iter().rev().find(|template| template.name == name) {
+        return match &template.bound {
+            Some(bound) => bound.to_type(&[]),
+            None => Type::Plain("mixed".into()),
+        };
+    }
+
+    let plain = |name: &str| Type::Plain(name.into());
+
+    match name.to_ascii_lowercase().as_str() {
+        "integer" | "positive-int" | "negative-int" | "non-positive-int" | "non-negative-int" | "non-zero-int" |
+        "int-mask" | "int-mask-of" => plain("int"),
+        "boolean" => plain("bool"),
+        "double" => plain("float"),
+        "class-string" | "interface-string" | "trait-string" | "enum-string" | "callable-string" | "numeric-string" |
+        "non-empty-string" | "non-falsy-string" | "truthy-string" | "literal-string" | "lowercase-string" |
+        "non-empty-lowercase-string" => plain("string"),
+        "list" | "non-empty-list" | "non-empty-array" | "callable-array" => plain("array"),
+        "callable-object" => plain("object"),
+        "number" => union([plain("int"), plain("float")]),
+        "numeric" => union([plain("int"), plain("float"), plain("string")]),
+        "scalar" => union([plain("int"), plain("float"), plain("string"), plain("bool")]),
+        "array-key" => union([plain("int"), plain("string")]),
+        "resource" | "closed-resource" | "open-resource" | "empty" | "key-of" | "value-of" => plain("mixed"),
+        _ => plain(name),
+    }
+}
+
+/// A union of types, flattening unions within it, or the type itself if there's only one.
+fn union(types: impl IntoIterator<Item = Type>) -> Type {
+    let mut members = Vec::new();
+
+    for r#type in types {
+        let flattened = match r#type {
+            Type::Union(types) => types,
+            Type::Nullable(name) => vec![Type::Plain(name), Type::Plain("null".into())],
+            r#type => vec![r#type],
+        };
+
+        for r#type in flattened {
+            if ! members.contains(&r#type) {
+                members.push(r#type);
+            }
+        }
+    }
+
+    match members.len() {
+        1 => members.remove(0),
+        _ => Type::Union(members),
+    }
+}
+
+/// Parse the type that starts at `offset`, giving it and the offset just after it. Outside of
+/// brackets a type can't contain whitespace, so anything after a space is left for the tag.
+pub(crate) fn parse_at(source: &str, offset: usize) -> Result<(DocType, usize), TypeError> {
+    let mut parser = TypeParser { source, offset, depth: 0 };
+    let r#type = parser.r#type()?;
+
+    Ok((r#type, parser.offset))
+}
+
+struct TypeParser<'a> {
+    source: &'a str,
+    offset: usize,
+    /// How many brackets the parser is inside of, where whitespace is allowed.
+    depth: usize,
+}
+
+impl TypeParser<'_> {
+    fn error(&self, message: impl Into<String>) -> TypeError {
+        TypeError { message: message.into(), offset: self.offset }
+    }
+
+    fn rest(&self) -> &str {
+        &self.source[self.offset..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.rest().starts_with(token) {
+            self.offset += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), TypeError> {
+        match self.eat(token) {
+            true => Ok(()),
+            false => Err(match self.peek() {
+                Some(c) => self.error(format!("expected `{}`, found `{}`", token, c)),
+                None => self.error(format!("expected `{}`", token)),
+            }),
+        }
+    }
+
+    /// Skip whitespace, if it's inside brackets.
+    fn space(&mut self) {
+        if self.depth > 0 {
+            self.offset = self.source.len() - self.rest().trim_start().len();
+        }
+    }
+
+    /// Eat an operator between two types, along with any whitespace around it that's allowed.
+    fn operator(&mut self, operator: &str) -> bool {
+        let start = self.offset;
+        self.space();
+
+        if self.eat(operator) {
+            self.space();
+
+            // `int &$x` in a callable is a by-reference parameter, not an intersection.
+            if ! (operator == "&" && (self.rest().starts_with('$') || self.rest().starts_with("..."))) {
+                return true;
+            }
+        }
+
+        self.offset = start;
+        false
+    }
+
+    fn open(&mut self, bracket: &str) -> Result<(), TypeError> {
+        self.expect(bracket)?;
+        self.depth += 1;
+        self.space();
+        Ok(())
+    }
+
+    fn close(&mut self, bracket: &str) -> Result<(), TypeError> {
+        self.space();
+        self.expect(bracket)?;
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn r#type(&mut self) -> Result<DocType, TypeError> {
+        let mut types = vec![self.intersection()?];
+
+        while self.operator("|") {
+            types.push(self.intersection()?);
+        }
+
+        Ok(if types.len() == 1 { types.remove(0) } else { DocType::Union(types) })
+    }
+
+    fn intersection(&mut self) -> Result<DocType, TypeError> {
+        let mut types = vec![self.nullable()?];
+
+        while self.operator("&") {
+            types.push(self.nullable()?);
+        }
+
+        Ok(if types.len() == 1 { types.remove(0) } else { DocType::Intersection(types) })
+    }
+
+    fn nullable(&mut self) -> Result<DocType, TypeError> {
+        if self.eat("?") {
+            return Ok(DocType::Nullable(Box::new(self.postfix()?)));
+        }
+
+        self.postfix()
+    }
+
+    fn postfix(&mut self) -> Result<DocType, TypeError> {
+        let mut r#type = self.atom()?;
+
+        while self.eat("[]") {
+            r#type = DocType::Array(Box::new(r#type));
+        }
+
+        Ok(r#type)
+    }
+
+    fn atom(&mut self) -> Result<DocType, TypeError> {
+        match self.peek() {
+            Some('(') => {
+                self.open("(")?;
+                let r#type = self.r#type()?;
+                self.close(")")?;
+                Ok(r#type)
+            },
+            Some('$') => {
+                self.expect("$this")?;
+                Ok(DocType::This)
+            },
+            Some('\'' | '"') => Ok(DocType::Literal(self.string()?)),
+            Some(c) if c.is_ascii_digit() || c == '-' => {
+                let start = self.offset;
+                self.eat("-");
+
+                if ! self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    return Err(self.error("expected a number"));
+                }
+
+                while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+                    self.offset += 1;
+                }
+
+                Ok(DocType::Literal(self.source[start..self.offset].into()))
+            },
+            Some(c) if is_name_start(c) => {
+                let name = self.name();
+
+                if self.eat("::") {
+                    let start = self.offset;
+
+                    while let Some(c) = self.peek().filter(|c| c.is_alphanumeric() || *c == '_' || *c == '*') {
+                        self.offset += c.len_utf8();
+                    }
+
+                    if self.offset == start {
+                        return Err(self.error("expected a constant name"));
+                    }
+
+                    return Ok(DocType::ClassConstant { class: name, constant: self.source[start..self.offset].into() });
+                }
+
+                let lowercase = name.trim_start_matches('\\').to_ascii_lowercase();
+
+                match self.peek() {
+                    Some('<') => self.generic(name),
+                    Some('{') if matches!(lowercase.as_str(), "array" | "list" | "object" | "non-empty-array" | "non-empty-list") => self.shape(name),
+                    Some('(') if matches!(lowercase.as_str(), "callable" | "closure" | "pure-callable" | "pure-closure") => self.callable(name),
+                    _ => Ok(DocType::Named(name)),
+                }
+            },
+            Some(c) => Err(self.error(format!("expected a type, found `{}`", c))),
+            None => Err(self.error("expected a type")),
+        }
+    }
+
+    fn name(&mut self) -> String {
+        let start = self.offset;
+
+        while let Some(c) = self.peek() {
+            let continues = is_name_start(c) || c.is_ascii_digit()
+                || (c == '-' && self.rest()[1..].starts_with(|c: char| c.is_ascii_alphabetic()));
+
+            if ! continues {
+                break;
+            }
+
+            self.offset += c.len_utf8();
+        }
+
+        self.source[start..self.offset].into()
+    }
+
+    /// A quoted string, including its quotes.
+    fn string(&mut self) -> Result<String, TypeError> {
+        let start = self.offset;
+        let quote = self.peek().unwrap_or('\'');
+        self.offset += 1;
+
+        loop {
+            match self.peek() {
+                Some('\\') => {
+                    self.offset += 1;
+
+                    if let Some(c) = self.peek() {
+                        self.offset += c.len_utf8();
+                    }
+                },
+                Some(c) => {
+                    self.offset += c.len_utf8();
+
+                    if c == quote {
+                        return Ok(self.source[start..self.offset].into());
+                    }
+                },
+                None => return Err(TypeError { message: "unterminated string".into(), offset: start }),
+            }
+        }
+    }
+
+    fn generic(&mut self, name: String) -> Result<DocType, TypeError> {
+        self.open("<")?;
+        let mut args = vec![self.r#type()?];
+
+        while self.operator(",") && self.peek() != Some('>') {
+            args.push(self.r#type()?);
+        }
+
+        self.close(">")?;
+
+        Ok(DocType::Generic { name, args })
+    }
+
+    fn shape(&mut self, name: String) -> Result<DocType, TypeError> {
+        self.open("{")?;
+        let mut items = Vec::new();
+        let mut unsealed = false;
+
+        while self.peek() != Some('}') {
+            if self.eat("...") {
+                unsealed = true;
+                self.operator(",");
+                break;
+            }
+
+            items.push(self.shape_item()?);
+
+            if ! self.operator(",") {
+                break;
+            }
+        }
+
+        self.close("}")?;
+
+        Ok(DocType::Shape { name, items, unsealed })
+    }
+
+    fn shape_item(&mut self) -> Result<ShapeItem, TypeError> {
+        let start = self.offset;
+
+        let key = match self.peek() {
+            Some('\'' | '"') => {
+                let quoted = self.string()?;
+                let quote = &quoted[..1];
+                Some(quoted[1..quoted.len() - 1].replace(&format!("\\{}", quote), quote))
+            },
+            Some(c) if is_name_start(c) || c.is_ascii_digit() => {
+                while let Some(c) = self.peek().filter(|c| is_name_start(*c) || c.is_ascii_digit() || *c == '-') {
+                    self.offset += c.len_utf8();
+                }
+
+                Some(self.source[start..self.offset].to_string())
+            },
+            _ => None,
+        };
+
+        if let Some(key) = key {
+            self.space();
+            let optional = self.eat("?");
+
+            if ! self.rest().starts_with("::") && self.eat(":") {
+                self.space();
+                return Ok(ShapeItem { key: Some(key), optional, value: self.r#type()? });
+            }
+
+            self.offset = start;
+        }
+
+        Ok(ShapeItem { key: None, optional: false, value: self.r#type()? })
+    }
+
+    fn callable(&mut self, name: String) -> Result<DocType, TypeError> {
+        self.open("(")?;
+        let mut params = Vec::new();
+
+        while self.peek() != Some(')') {
+            let r#type = self.r#type()?;
+            self.space();
+            let by_ref = self.eat("&");
+            self.space();
+            let variadic = self.eat("...");
+            self.space();
+
+            let name = match self.eat("$") {
+                true => {
+                    let start = self.offset;
+
+                    while let Some(c) = self.peek().filter(|c| c.is_alphanumeric() || *c == '_') {
+                        self.offset += c.len_utf8();
+                    }
+
+                    Some(self.source[start..self.offset].to_string())
+                },
+                false => None,
+            };
+
+            self.space();
+            let optional = self.eat("=");
+            params.push(CallableParam { r#type, by_ref, variadic, name, optional });
+
+            if ! self.operator(",") {
+                break;
+            }
+        }
+
+        self.close(")")?;
+
+        // The return type is a single type, so `callable(): int|string` is a union with `string`.
+        let after = self.offset;
+        self.offset = self.source.len() - self.rest().trim_start().len();
+
+        let return_type = match self.eat(":") {
+            true => {
+                self.offset = self.source.len() - self.rest().trim_start().len();
+                Some(Box::new(self.nullable()?))
+            },
+            false => {
+                self.offset = after;
+                None
+            },
+        };
+
+        Ok(DocType::Callable { name, params, return_type })
+    }
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == '\\' || ! c.is_ascii()
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_parser::Type;
+
+    use super::DocType;
+    use crate::{Template, Variance};
+
+    #[test]
+    fn round_trip() {
+        for source in [
+            "int",
+            "?Foo\\Bar",
+            "int|string|null",
+            "A&B",
+            "(A&B)|null",
+            "string[][]",
+            "(int|string)[]",
+            "array<int, User>",
+            "array<string, array<int, list<string>>>",
+            "int<0, max>",
+            "class-string<Foo>",
+            "array{id: int, name?: string, 'full name': string, 'it\\'s': int}",
+            "array{int, string, ...}",
+            "list{}",
+            "object{foo: int}",
+            "callable(int, string &...$rest): bool",
+            "Closure(int $a, string=): void",
+            "callable(): (int|string)",
+            "callable(): int|string",
+            "'foo'|\"bar\"|-1|1.5",
+            "Foo::BAR|Foo::BAZ_*",
+            "$this",
+            "static",
+        ] {
+            assert_eq!(DocType::parse(source).unwrap().to_string(), source);
+        }
+
+        assert_eq!(DocType::parse(" array< int ,\n string , > ").unwrap().to_string(), "array<int, string>");
+        assert_eq!(DocType::parse("array{\n  a : int,\n  b?: string,\n}").unwrap().to_string(), "array{a: int, b?: string}");
+    }
+
+    #[test]
+    fn errors() {
+        for source in ["", "int|", "array<int", "array{a: }", "(int", "'foo", "Foo::", "int string", "$that", "-x"] {
+            assert!(DocType::parse(source).is_err(), "{}", source);
+        }
+
+        assert_eq!(DocType::parse("array<int,").unwrap_err().offset, 10);
+        assert_eq!(DocType::parse("int string").unwrap_err().offset, 4);
+    }
+
+    #[test]
+    fn native_types() {
+        let native = |source: &str, templates: &[Template]| DocType::parse(source).unwrap().to_type(templates);
+        let plain = |name: &str| Type::Plain(name.into());
+
+        assert_eq!(native("array<int, User>", &[]), plain("array"));
+        assert_eq!(native("User[]", &[]), plain("array"));
+        assert_eq!(native("non-empty-string", &[]), plain("string"));
+        assert_eq!(native("?positive-int", &[]), Type::Nullable("int".into()));
+        assert_eq!(native("Collection<User>", &[]), plain("Collection"));
+        assert_eq!(native("Closure(int): void", &[]), plain("Closure"));
+        assert_eq!(native("$this", &[]), plain("static"));
+        assert_eq!(native("'a'|'b'", &[]), plain("string"));
+        assert_eq!(native("array-key|null", &[]), Type::Union(vec![plain("int"), plain("string"), plain("null")]));
+        assert_eq!(native("?(int|string)", &[]), Type::Union(vec![plain("int"), plain("string"), plain("null")]));
+
+        let templates = [
+            Template { name: "T".into(), bound: None, variance: Variance::Invariant },
+            Template { name: "TModel".into(), bound: Some(DocType::Named("Model".into())), variance: Variance::Covariant },
+        ];
+
+        assert_eq!(native("T", &templates), plain("mixed"));
+        assert_eq!(native("TModel|null", &templates), Type::Union(vec![plain("Model"), plain("null")]));
+    }
+}
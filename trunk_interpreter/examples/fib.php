@@ -0,0 +1,14 @@
+<?php
+
+function fib(int $n): int
+{
+    if ($n < 2) {
+        return $n;
+    }
+
+    return fib($n - 1) + fib($n - 2);
+}
+
+for ($i = 0; $i <= 10; $i++) {
+    echo "fib($i) = " . fib($i) . PHP_EOL;
+}
@@ -0,0 +1,527 @@
+use std::io::Write;
+
+use crate::value::{format_float, Number};
+use crate::{Array, Error, Key, Value};
+
+/// A function written in Rust that PHP code can call. It's given the values of its arguments, and
+/// where to write any output.
+///
+/// Type errors about an argument, such as `Argument #1 must be of type array, int given`, are
+/// reported with the name of the function in front of them.
+#[derive(Clone, Copy)]
+pub struct Builtin {
+    /// The fewest arguments that the function takes.
+    pub min: usize,
+    /// The most arguments that the function takes, or `None` if there's no limit.
+    pub max: Option<usize>,
+    pub function: fn(&[Value], &mut dyn Write) -> Result<Value, Error>,
+}
+
+fn builtin(min: usize, max: Option<usize>, function: fn(&[Value], &mut dyn Write) -> Result<Value, Error>) -> Builtin {
+    Builtin { min, max, function }
+}
+
+/// The constants that every interpreter starts with, apart from `true`, `false` and `null`.
+pub fn constants() -> Vec<(&'static str, Value)> {
+    vec![
+        ("PHP_EOL", Value::String("\n".into())),
+        ("PHP_INT_MAX", Value::Int(i64::MAX)),
+        ("PHP_INT_MIN", Value::Int(i64::MIN)),
+        ("PHP_INT_SIZE", Value::Int(8)),
+        ("PHP_FLOAT_EPSILON", Value::Float(f64::EPSILON)),
+        ("PHP_FLOAT_MAX", Value::Float(f64::MAX)),
+        ("M_PI", Value::Float(std::f64::consts::PI)),
+        ("INF", Value::Float(f64::INFINITY)),
+        ("NAN", Value::Float(f64::NAN)),
+    ]
+}
+
+/// The functions that every interpreter starts with, a small part of PHP's standard library.
+pub fn builtins() -> Vec<(&'static str, Builtin)> {
+    vec![
+        // Strings.
+        ("strlen", builtin(1, Some(1), |args, _| Ok(Value::Int(string(args, 0)?.len() as i64)))),
+        ("strtoupper", builtin(1, Some(1), |args, _| Ok(Value::String(string(args, 0)?.to_uppercase())))),
+        ("strtolower", builtin(1, Some(1), |args, _| Ok(Value::String(string(args, 0)?.to_lowercase())))),
+        ("ucfirst", builtin(1, Some(1), |args, _| {
+            let s = string(args, 0)?;
+            let mut chars = s.chars();
+
+            Ok(Value::String(chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()))
+        })),
+        ("strrev", builtin(1, Some(1), |args, _| Ok(Value::String(string(args, 0)?.chars().rev().collect())))),
+        ("trim", builtin(1, Some(1), |args, _| Ok(Value::String(string(args, 0)?.trim_matches(|c: char| " \t\n\r\0\x0B".contains(c)).into())))),
+        ("str_repeat", builtin(2, Some(2), |args, _| {
+            let times = usize::try_from(int(args, 1)?)
+                .map_err(|_| Error::thrown("ValueError", "Argument #2 ($times) must be greater than or equal to 0"))?;
+
+            Ok(Value::String(string(args, 0)?.repeat(times)))
+        })),
+        ("substr", builtin(2, Some(3), |args, _| {
+            let s = string(args, 0)?;
+            let (start, end) = range_of(s.len(), int(args, 1)?, args.get(2).map(|_| int(args, 2)).transpose()?);
+
+            Ok(Value::String(String::from_utf8_lossy(&s.as_bytes()[start..end]).into_owned()))
+        })),
+        ("str_contains", builtin(2, Some(2), |args, _| Ok(Value::Bool(string(args, 0)?.contains(&string(args, 1)?))))),
+        ("str_starts_with", builtin(2, Some(2), |args, _| Ok(Value::Bool(string(args, 0)?.starts_with(&string(args, 1)?))))),
+        ("str_ends_with", builtin(2, Some(2), |args, _| Ok(Value::Bool(string(args, 0)?.ends_with(&string(args, 1)?))))),
+        ("implode", builtin(2, Some(2), |args, _| {
+            let parts: Vec<String> = array(args, 1)?.values().map(Value::to_string).collect();
+
+            Ok(Value::String(parts.join(&string(args, 0)?)))
+        })),
+        ("explode", builtin(2, Some(2), |args, _| {
+            let separator = string(args, 0)?;
+
+            if separator.is_empty() {
+                return Err(Error::thrown("ValueError", "Argument #1 ($separator) cannot be empty"));
+            }
+
+            Ok(Value::Array(string(args, 1)?.split(&separator).map(|part| Value::String(part.into())).collect()))
+        })),
+        ("chr", builtin(1, Some(1), |args, _| Ok(Value::String(char::from(int(args, 0)?.rem_euclid(256) as u8).to_string())))),
+        ("ord", builtin(1, Some(1), |args, _| Ok(Value::Int(string(args, 0)?.bytes().next().unwrap_or(0) as i64)))),
+        ("sprintf", builtin(1, None, |args, _| Ok(Value::String(sprintf(args)?)))),
+        ("printf", builtin(1, None, |args, output| {
+            let s = sprintf(args)?;
+            output.write_all(s.as_bytes())?;
+
+            Ok(Value::Int(s.len() as i64))
+        })),
+
+        // Math.
+        ("abs", builtin(1, Some(1), |args, _| Ok(match number(args, 0)? {
+            Number::Int(i) => i.checked_abs().map_or(Value::Float(-(i as f64)), Value::Int),
+            Number::Float(f) => Value::Float(f.abs()),
+        }))),
+        ("max", builtin(1, None, |args, _| extreme(args, std::cmp::Ordering::Greater))),
+        ("min", builtin(1, None, |args, _| extreme(args, std::cmp::Ordering::Less))),
+        ("floor", builtin(1, Some(1), |args, _| Ok(Value::Float(number(args, 0)?.to_f64().floor())))),
+        ("ceil", builtin(1, Some(1), |args, _| Ok(Value::Float(number(args, 0)?.to_f64().ceil())))),
+        ("round", builtin(1, Some(2), |args, _| {
+            let scale = 10f64.powi(args.get(1).map(|_| int(args, 1)).transpose()?.unwrap_or(0) as i32);
+
+            Ok(Value::Float((number(args, 0)?.to_f64() * scale).round() / scale))
+        })),
+        ("sqrt", builtin(1, Some(1), |args, _| Ok(Value::Float(number(args, 0)?.to_f64().sqrt())))),
+        ("intdiv", builtin(2, Some(2), |args, _| match (int(args, 0)?, int(args, 1)?) {
+            (_, 0) => Err(Error::thrown("DivisionByZeroError", "Division by zero")),
+            (i64::MIN, -1) => Err(Error::thrown("ArithmeticError", "Division of PHP_INT_MIN by -1 is not an integer")),
+            (a, b) => Ok(Value::Int(a / b)),
+        })),
+        ("pi", builtin(0, Some(0), |_, _| Ok(Value::Float(std::f64::consts::PI)))),
+
+        // Types.
+        ("is_int", builtin(1, Some(1), |args, _| Ok(Value::Bool(matches!(args[0], Value::Int(_)))))),
+        ("is_float", builtin(1, Some(1), |args, _| Ok(Value::Bool(matches!(args[0], Value::Float(_)))))),
+        ("is_string", builtin(1, Some(1), |args, _| Ok(Value::Bool(matches!(args[0], Value::String(_)))))),
+        ("is_bool", builtin(1, Some(1), |args, _| Ok(Value::Bool(matches!(args[0], Value::Bool(_)))))),
+        ("is_array", builtin(1, Some(1), |args, _| Ok(Value::Bool(matches!(args[0], Value::Array(_)))))),
+        ("is_null", builtin(1, Some(1), |args, _| Ok(Value::Bool(matches!(args[0], Value::Null))))),
+        ("is_numeric", builtin(1, Some(1), |args, _| Ok(Value::Bool(args[0].is_numeric())))),
+        ("gettype", builtin(1, Some(1), |args, _| Ok(Value::String(match args[0] {
+            Value::Null => "NULL",
+            Value::Bool(_) => "boolean",
+            Value::Int(_) => "integer",
+            Value::Float(_) => "double",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+        }.into())))),
+        ("intval", builtin(1, Some(1), |args, _| Ok(Value::Int(args[0].to_int())))),
+        ("floatval", builtin(1, Some(1), |args, _| Ok(Value::Float(args[0].to_float())))),
+        ("strval", builtin(1, Some(1), |args, _| Ok(Value::String(string(args, 0)?)))),
+        ("boolval", builtin(1, Some(1), |args, _| Ok(Value::Bool(args[0].to_bool())))),
+        ("var_dump", builtin(1, None, |args, output| {
+            let mut dumped = String::new();
+
+            for value in args {
+                var_dump(value, 0, &mut dumped);
+            }
+
+            output.write_all(dumped.as_bytes())?;
+
+            Ok(Value::Null)
+        })),
+        ("print_r", builtin(1, Some(2), |args, output| {
+            let mut printed = String::new();
+            print_r(&args[0], 0, &mut printed);
+
+            if args.get(1).is_some_and(Value::to_bool) {
+                return Ok(Value::String(printed));
+            }
+
+            output.write_all(printed.as_bytes())?;
+
+            Ok(Value::Bool(true))
+        })),
+
+        // Arrays.
+        ("count", builtin(1, Some(1), |args, _| match &args[0] {
+            Value::Array(array) => Ok(Value::Int(array.len() as i64)),
+            other => Err(Error::thrown("TypeError", format!("Argument #1 ($value) must be of type Countable|array, {} given", other.type_name()))),
+        })),
+        ("range", builtin(2, Some(3), |args, _| {
+            let step = args.get(2).map(|_| number(args, 2)).transpose()?.unwrap_or(Number::Int(1));
+
+            match (number(args, 0)?, number(args, 1)?, step) {
+                (_, _, step) if step.to_f64() == 0.0 => Err(Error::thrown("ValueError", "Argument #3 ($step) cannot be 0")),
+                (Number::Int(start), Number::Int(end), Number::Int(step)) => {
+                    let step = step.unsigned_abs() as usize;
+                    let values: Vec<i64> = if start <= end { (start..=end).step_by(step).collect() } else { (end..=start).rev().step_by(step).collect() };
+
+                    Ok(Value::Array(values.into_iter().map(Value::Int).collect()))
+                },
+                (start, end, step) => {
+                    let (start, end, step) = (start.to_f64(), end.to_f64(), step.to_f64().abs());
+                    let count = ((end - start).abs() / step).floor() as usize;
+                    let direction = if start <= end { 1.0 } else { -1.0 };
+
+                    Ok(Value::Array((0..=count).map(|i| Value::Float(start + direction * step * i as f64)).collect()))
+                },
+            }
+        })),
+        ("array_keys", builtin(1, Some(1), |args, _| Ok(Value::Array(array(args, 0)?.iter().map(|(key, _)| Value::from(key.clone())).collect())))),
+        ("array_values", builtin(1, Some(1), |args, _| Ok(Value::Array(array(args, 0)?.values().cloned().collect())))),
+        ("array_reverse", builtin(1, Some(1), |args, _| {
+            let mut reversed = Array::new();
+
+            // Integer keys are renumbered, while string keys are kept.
+            for (key, value) in array(args, 0)?.iter().collect::<Vec<_>>().into_iter().rev() {
+                match key {
+                    Key::Int(_) => { reversed.push(value.clone()); },
+                    Key::String(_) => reversed.insert(key.clone(), value.clone()),
+                }
+            }
+
+            Ok(Value::Array(reversed))
+        })),
+        ("array_merge", builtin(0, None, |args, _| {
+            let mut merged = Array::new();
+
+            for i in 0..args.len() {
+                for (key, value) in array(args, i)?.iter() {
+                    match key {
+                        Key::Int(_) => { merged.push(value.clone()); },
+                        Key::String(_) => merged.insert(key.clone(), value.clone()),
+                    }
+                }
+            }
+
+            Ok(Value::Array(merged))
+        })),
+        ("array_sum", builtin(1, Some(1), |args, _| {
+            let mut sum = Value::Int(0);
+
+            for value in array(args, 0)?.values() {
+                sum = crate::operators::binary(&trunk_parser::InfixOp::Add, &sum, &Value::from(value.to_number().0))?.0;
+            }
+
+            Ok(sum)
+        })),
+        ("in_array", builtin(2, Some(3), |args, _| {
+            let strict = args.get(2).is_some_and(Value::to_bool);
+            let equals = if strict { crate::value::strict_equals } else { crate::value::loose_equals };
+
+            Ok(Value::Bool(array(args, 1)?.values().any(|value| equals(value, &args[0]))))
+        })),
+        ("array_key_exists", builtin(2, Some(2), |args, _| {
+            let key = args[0].to_key().ok_or_else(|| Error::thrown("TypeError", "Argument #1 ($key) must be a valid array offset type"))?;
+
+            Ok(Value::Bool(array(args, 1)?.get(&key).is_some()))
+        })),
+    ]
+}
+
+fn type_error(args: &[Value], i: usize, expected: &str) -> Error {
+    Error::thrown("TypeError", format!("Argument #{} must be of type {}, {} given", i + 1, expected, args[i].type_name()))
+}
+
+fn string(args: &[Value], i: usize) -> Result<String, Error> {
+    match &args[i] {
+        Value::Array(_) => Err(type_error(args, i, "string")),
+        value => Ok(value.to_string()),
+    }
+}
+
+fn number(args: &[Value], i: usize) -> Result<Number, Error> {
+    match &args[i] {
+        Value::Array(_) => Err(type_error(args, i, "int|float")),
+        Value::String(_) if ! args[i].is_numeric() => Err(type_error(args, i, "int|float")),
+        value => Ok(value.to_number().0),
+    }
+}
+
+fn int(args: &[Value], i: usize) -> Result<i64, Error> {
+    number(args, i).map(|_| args[i].to_int()).map_err(|_| type_error(args, i, "int"))
+}
+
+fn array(args: &[Value], i: usize) -> Result<&Array, Error> {
+    match &args[i] {
+        Value::Array(array) => Ok(array),
+        _ => Err(type_error(args, i, "array")),
+    }
+}
+
+/// The byte range of a string that `substr` gives, where negative offsets and lengths count from
+/// the end.
+fn range_of(len: usize, start: i64, length: Option<i64>) -> (usize, usize) {
+    let len = len as i64;
+    let start = if start < 0 { (len + start).max(0) } else { start.min(len) };
+    let end = match length {
+        None => len,
+        Some(length) if length < 0 => (len + length).max(start),
+        Some(length) => (start + length).min(len),
+    };
+
+    (start as usize, end as usize)
+}
+
+/// The largest or smallest of either the arguments, or the values of an array that's the only one.
+fn extreme(args: &[Value], wanted: std::cmp::Ordering) -> Result<Value, Error> {
+    let values: Vec<&Value> = match args {
+        [Value::Array(array)] if array.is_empty() => return Err(Error::thrown("ValueError", "Argument #1 ($value) must contain at least one element")),
+        [Value::Array(array)] => array.values().collect(),
+        [_] => return Err(type_error(args, 0, "array")),
+        _ => args.iter().collect(),
+    };
+
+    let mut best = values[0];
+
+    for value in &values[1..] {
+        if crate::value::compare(value, best) == Some(wanted) {
+            best = value;
+        }
+    }
+
+    Ok(best.clone())
+}
+
+/// Format values as PHP's `sprintf` does, for the `%s`, `%d`, `%u`, `%f`, `%x`, `%X`, `%o`, `%b`
+/// and `%c` conversions, with flags, widths and precisions.
+fn sprintf(args: &[Value]) -> Result<String, Error> {
+    let format = string(args, 0)?;
+    let mut output = String::new();
+    let mut chars = format.chars().peekable();
+    let mut next = 1;
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+
+        if chars.next_if_eq(&'%').is_some() {
+            output.push('%');
+            continue;
+        }
+
+        let (mut left, mut plus, mut pad) = (false, false, ' ');
+
+        loop {
+            match chars.peek() {
+                Some('-') => left = true,
+                Some('+') => plus = true,
+                Some('0') => pad = '0',
+                Some('\'') => {
+                    chars.next();
+                    pad = chars.next().unwrap_or(' ');
+                    continue;
+                },
+                _ => break,
+            }
+
+            chars.next();
+        }
+
+        let mut width = 0;
+
+        while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+            width = width * 10 + digit as usize;
+            chars.next();
+        }
+
+        let mut precision = None;
+
+        if chars.next_if_eq(&'.').is_some() {
+            let mut digits = 0;
+
+            while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                digits = digits * 10 + digit as usize;
+                chars.next();
+            }
+
+            precision = Some(digits);
+        }
+
+        let conversion = chars.next().ok_or_else(|| Error::thrown("ValueError", "Missing format specifier at end of string"))?;
+        let value = args.get(next).ok_or_else(|| Error::thrown("ArgumentCountError", format!("{} arguments are required, {} given", next + 1, args.len())))?;
+        next += 1;
+
+        let signed = |s: String| if plus && ! s.starts_with('-') { format!("+{}", s) } else { s };
+
+        let text = match conversion {
+            's' => {
+                let s = value.to_string();
+                precision.map_or(s.clone(), |precision| s.chars().take(precision).collect())
+            },
+            'd' => signed(value.to_int().to_string()),
+            'u' => (value.to_int() as u64).to_string(),
+            'f' | 'F' => signed(format!("{:.*}", precision.unwrap_or(6), value.to_float())),
+            'x' => format!("{:x}", value.to_int()),
+            'X' => format!("{:X}", value.to_int()),
+            'o' => format!("{:o}", value.to_int()),
+            'b' => format!("{:b}", value.to_int()),
+            'c' => char::from(value.to_int().rem_euclid(256) as u8).to_string(),
+            other => return Err(Error::thrown("ValueError", format!("Unknown format specifier \"{}\"", other))),
+        };
+
+        let padding = width.saturating_sub(text.chars().count());
+
+        if left {
+            output.push_str(&text);
+            output.extend(std::iter::repeat_n(if pad == '0' { ' ' } else { pad }, padding));
+        } else if pad == '0' && text.starts_with(['-', '+']) {
+            // Zeros go between the sign and the digits.
+            output.push_str(&text[..1]);
+            output.extend(std::iter::repeat_n('0', padding));
+            output.push_str(&text[1..]);
+        } else {
+            output.extend(std::iter::repeat_n(pad, padding));
+            output.push_str(&text);
+        }
+    }
+
+    Ok(output)
+}
+
+fn var_dump(value: &Value, indent: usize, output: &mut String) {
+    let pad = " ".repeat(indent);
+
+    match value {
+        Value::Null => output.push_str(&format!("{}NULL\n", pad)),
+        Value::Bool(b) => output.push_str(&format!("{}bool({})\n", pad, b)),
+        Value::Int(i) => output.push_str(&format!("{}int({})\n", pad, i)),
+        Value::Float(f) => output.push_str(&format!("{}float({})\n", pad, format_float(*f, 0))),
+        Value::String(s) => output.push_str(&format!("{}string({}) \"{}\"\n", pad, s.len(), s)),
+        Value::Array(array) => {
+            output.push_str(&format!("{}array({}) {{\n", pad, array.len()));
+
+            for (key, value) in array.iter() {
+                match key {
+                    Key::Int(i) => output.push_str(&format!("{}  [{}]=>\n", pad, i)),
+                    Key::String(s) => output.push_str(&format!("{}  [\"{}\"]=>\n", pad, s)),
+                }
+
+                var_dump(value, indent + 2, output);
+            }
+
+            output.push_str(&format!("{}}}\n", pad));
+        },
+    }
+}
+
+fn print_r(value: &Value, indent: usize, output: &mut String) {
+    let Value::Array(array) = value else {
+        output.push_str(&value.to_string());
+        return;
+    };
+
+    let pad = " ".repeat(indent);
+    output.push_str(&format!("Array\n{}(\n", pad));
+
+    for (key, value) in array.iter() {
+        output.push_str(&format!("{}    [{}] => ", pad, key));
+        print_r(value, indent + 8, output);
+
+        // Nested arrays already end with a newline, so they're followed by a blank line.
+        output.push('\n');
+    }
+
+    output.push_str(&format!("{})\n", pad));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{builtins, range_of};
+    use crate::{Array, Value};
+
+    /// Call a built-in function, returning its result, or the error it threw, and its output.
+    fn call(name: &str, args: &[Value]) -> (Result<Value, String>, String) {
+        let (_, builtin) = builtins().into_iter().find(|(builtin, _)| *builtin == name).unwrap();
+        let mut output = Vec::new();
+        let result = (builtin.function)(args, &mut output).map_err(|error| error.to_string());
+
+        (result, String::from_utf8(output).unwrap())
+    }
+
+    fn s(s: &str) -> Value {
+        Value::String(s.into())
+    }
+
+    fn ints(values: &[i64]) -> Value {
+        Value::Array(values.iter().copied().map(Value::Int).collect())
+    }
+
+    #[test]
+    fn strings() {
+        let format = s("%05.2f|%-4d|%'*6s|%+d|%x|%%|%03d");
+        let args = [format, Value::Float(1.23456), Value::Int(7), s("ab"), Value::Int(3), Value::Int(255), Value::Int(-4)];
+
+        assert_eq!(call("sprintf", &args).0, Ok(s("01.23|7   |****ab|+3|ff|%|-04")));
+        assert_eq!(call("sprintf", &[s("%d %d"), Value::Int(1)]).0, Err("uncaught ArgumentCountError: 3 arguments are required, 2 given".into()));
+        assert_eq!(call("printf", &[s("%s!"), s("hi")]), (Ok(Value::Int(3)), "hi!".into()));
+        assert_eq!(call("str_repeat", &[s("ab"), Value::Int(-1)]).0, Err("uncaught ValueError: Argument #2 ($times) must be greater than or equal to 0".into()));
+        assert_eq!(call("implode", &[s(", "), Value::Array([Value::Int(1), Value::Float(2.5)].into_iter().collect())]).0, Ok(s("1, 2.5")));
+        assert_eq!(call("strlen", &[ints(&[])]).0, Err("uncaught TypeError: Argument #1 must be of type string, array given".into()));
+
+        assert_eq!(range_of(5, 1, None), (1, 5));
+        assert_eq!(range_of(5, -2, None), (3, 5));
+        assert_eq!(range_of(5, 1, Some(-1)), (1, 4));
+        assert_eq!(range_of(5, 10, Some(2)), (5, 5));
+    }
+
+    #[test]
+    fn dumping() {
+        let mut array: Array = [Value::Int(1), Value::Float(1.0)].into_iter().collect();
+        array.insert("a".into(), Value::Array([s("x")].into_iter().collect()));
+
+        assert_eq!(call("var_dump", &[Value::Array(array.clone()), Value::Null]).1, "\
+array(3) {
+  [0]=>
+  int(1)
+  [1]=>
+  float(1)
+  [\"a\"]=>
+  array(1) {
+    [0]=>
+    string(1) \"x\"
+  }
+}
+NULL
+");
+
+        assert_eq!(call("print_r", &[Value::Array(array)]).1, "\
+Array
+(
+    [0] => 1
+    [1] => 1
+    [a] => Array
+        (
+            [0] => x
+        )
+
+)
+");
+    }
+
+    #[test]
+    fn arrays() {
+        assert_eq!(call("range", &[Value::Int(5), Value::Int(1), Value::Int(2)]).0, Ok(ints(&[5, 3, 1])));
+        assert_eq!(call("array_sum", &[ints(&[1, 2, 3])]).0, Ok(Value::Int(6)));
+        assert_eq!(call("max", &[ints(&[1, 7, 3])]).0, Ok(Value::Int(7)));
+        assert_eq!(call("min", &[Value::Int(4), s("2"), Value::Float(3.0)]).0, Ok(s("2")));
+        assert_eq!(call("array_reverse", &[ints(&[1, 2])]).0, Ok(ints(&[2, 1])));
+        assert_eq!(call("count", &[Value::Int(1)]).0, Err("uncaught TypeError: Argument #1 ($value) must be of type Countable|array, int given".into()));
+    }
+}
@@ -0,0 +1,102 @@
+use std::fmt::Display;
+
+use trunk_lexer::Span;
+use trunk_parser::{Diagnostic, Severity};
+
+/// Something that ended a program early.
+#[derive(Debug)]
+pub enum Error {
+    /// An error that PHP would throw, such as calling an undefined function or dividing by zero.
+    /// There's no `try` yet, so these always end the program.
+    Thrown {
+        /// The class of the error, such as `DivisionByZeroError`.
+        class: &'static str,
+        message: String,
+        span: Span,
+    },
+    /// Code that the interpreter can't run yet, such as a class declaration.
+    Unsupported {
+        feature: String,
+        span: Span,
+    },
+    /// The program's output couldn't be written.
+    Io(std::io::Error),
+}
+
+impl Error {
+    /// An error to throw, which the interpreter gives the span of the code that threw it.
+    pub fn thrown(class: &'static str, message: impl Into<String>) -> Self {
+        Self::Thrown { class, message: message.into(), span: Span::default() }
+    }
+
+    pub(crate) fn unsupported(feature: impl Into<String>, span: Span) -> Self {
+        Self::Unsupported { feature: feature.into(), span }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::Thrown { span, .. } | Self::Unsupported { span, .. } => Some(*span),
+            Self::Io(_) => None,
+        }
+    }
+
+    /// Give the error a span, unless it already has one.
+    pub(crate) fn at(mut self, at: Span) -> Self {
+        if let Self::Thrown { span, .. } | Self::Unsupported { span, .. } = &mut self {
+            if *span == Span::default() {
+                *span = at;
+            }
+        }
+
+        self
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Thrown { class, message, .. } => write!(f, "uncaught {}: {}", class, message),
+            Self::Unsupported { feature, .. } => write!(f, "not supported yet: {}", feature),
+            Self::Io(error) => write!(f, "couldn't write output: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<&Error> for Diagnostic {
+    fn from(error: &Error) -> Self {
+        let code = match error {
+            Error::Thrown { .. } => "uncaught-error",
+            Error::Unsupported { .. } => "unsupported",
+            Error::Io(_) => "output-error",
+        };
+
+        Diagnostic::new(Severity::Error, code, error.to_string(), error.span())
+    }
+}
+
+/// A problem that PHP warns about but keeps running after, such as reading an undefined variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<&Warning> for Diagnostic {
+    fn from(warning: &Warning) -> Self {
+        Diagnostic::new(Severity::Warning, "runtime-warning", &warning.message, Some(warning.span))
+    }
+}
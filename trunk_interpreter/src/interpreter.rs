@@ -0,0 +1,1008 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::ops::ControlFlow;
+
+use trunk_lexer::Span;
+use trunk_parser::{Arg, ArrayItem, Block, Case, CastKind, Expression, ExpressionKind, InfixOp, MagicConst, MatchArm, Program, Statement, StatementKind, StringPart};
+
+use crate::operators::{binary, decrement, increment, unary};
+use crate::value::{loose_equals, strict_equals};
+use crate::{builtins, constants, Array, Builtin, Error, Key, Value, Warning};
+
+/// Why evaluation stopped before reaching the end of the code.
+enum Unwind {
+    Error(Error),
+    /// `exit` was called with a status.
+    Exit(i32),
+}
+
+impl From<Error> for Unwind {
+    fn from(error: Error) -> Self {
+        Self::Error(error)
+    }
+}
+
+impl From<std::io::Error> for Unwind {
+    fn from(error: std::io::Error) -> Self {
+        Self::Error(Error::Io(error))
+    }
+}
+
+type Eval<T> = Result<T, Unwind>;
+
+/// Where a statement leaves the code around it to carry on from.
+#[derive(Debug)]
+enum Flow {
+    Normal,
+    /// `break` out of the given number of loops.
+    Break(usize),
+    Continue(usize),
+    Return(Value),
+}
+
+/// A variable, or an element of an array in a variable, that can be assigned to. A key of `None`
+/// appends to the array, as in `$a[] = 1`.
+struct Place {
+    variable: String,
+    keys: Vec<Option<Key>>,
+}
+
+/// The variables of a function call.
+struct Frame<'a> {
+    function: &'a str,
+    variables: HashMap<String, Value>,
+}
+
+/// A tree-walking interpreter, which runs a program by evaluating its AST directly and writes
+/// what it prints to `output`.
+///
+/// Scalars, arrays, variables, operators, control flow, user functions and a small set of built-in
+/// functions are supported. Classes, closures, references, exceptions and includes aren't yet, and
+/// running code that uses them is an `Error::Unsupported`. Namespaces are ignored, so functions are
+/// called by their unqualified names.
+pub struct Interpreter<'a, W: Write> {
+    output: W,
+    /// Declared functions, by their lowercase names.
+    functions: HashMap<String, &'a Statement>,
+    builtins: HashMap<String, Builtin>,
+    constants: HashMap<String, Value>,
+    globals: HashMap<String, Value>,
+    frames: Vec<Frame<'a>>,
+    warnings: Vec<Warning>,
+    max_depth: usize,
+}
+
+impl<'a, W: Write> Interpreter<'a, W> {
+    pub fn new(output: W) -> Self {
+        Self {
+            output,
+            functions: HashMap::new(),
+            builtins: builtins().into_iter().map(|(name, builtin)| (name.to_string(), builtin)).collect(),
+            constants: constants().into_iter().map(|(name, value)| (name.to_string(), value)).collect(),
+            globals: HashMap::new(),
+            frames: Vec::new(),
+            warnings: Vec::new(),
+            max_depth: 512,
+        }
+    }
+
+    /// Add a built-in function, or replace one of the same name.
+    pub fn define(&mut self, name: &str, builtin: Builtin) {
+        self.builtins.insert(name.to_ascii_lowercase(), builtin);
+    }
+
+    /// Set how deeply user functions can call each other before the program fails, rather than
+    /// overflowing the stack. The default of 512 needs roughly 2MB of stack in release builds, and
+    /// considerably more in debug builds.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Run a program, returning its exit status: the one it passed to `exit`, or 0 if it ran to
+    /// the end. The program's functions stay declared and its global variables stay set, so more
+    /// programs can be run after it that use them.
+    pub fn run(&mut self, program: &'a Program) -> Result<i32, Error> {
+        let result = self.hoist(program).and_then(|_| self.block(program));
+        self.output.flush()?;
+
+        match result {
+            Ok(_) => Ok(0),
+            Err(Unwind::Exit(status)) => Ok(status),
+            Err(Unwind::Error(error)) => Err(error),
+        }
+    }
+
+    /// The warnings from the programs that have run, in the order they happened.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    pub fn into_output(self) -> W {
+        self.output
+    }
+
+    /// The output written so far, such as to take what one program printed before running the next.
+    pub fn output_mut(&mut self) -> &mut W {
+        &mut self.output
+    }
+
+    /// Declare the top-level functions of a program before running it, so that they can be
+    /// called above where they're declared.
+    fn hoist(&mut self, block: &'a [Statement]) -> Eval<()> {
+        for statement in block {
+            match &statement.kind {
+                StatementKind::Function { .. } => self.declare(statement)?,
+                StatementKind::Namespace { body, .. } => self.hoist(body)?,
+                _ => {},
+            }
+        }
+
+        Ok(())
+    }
+
+    fn declare(&mut self, function: &'a Statement) -> Eval<()> {
+        let StatementKind::Function { name, .. } = &function.kind else { unreachable!() };
+        let key = name.name.to_ascii_lowercase();
+
+        match self.functions.get(&key) {
+            // Functions that were hoisted are declared again when they're reached.
+            Some(declared) if std::ptr::eq(*declared, function) => Ok(()),
+            Some(_) => Err(thrown("Error", format!("Cannot redeclare {}()", name.name), name.span)),
+            None if self.builtins.contains_key(&key) => Err(thrown("Error", format!("Cannot redeclare {}()", name.name), name.span)),
+            None => {
+                self.functions.insert(key, function);
+                Ok(())
+            },
+        }
+    }
+
+    fn block(&mut self, block: &'a Block) -> Eval<Flow> {
+        for statement in block {
+            match self.statement(statement)? {
+                Flow::Normal => {},
+                flow => return Ok(flow),
+            }
+        }
+
+        Ok(Flow::Normal)
+    }
+
+    // The larger statements and expressions are evaluated by methods of their own, since every
+    // nested call goes through `statement` and `evaluate`, and keeping their stack frames small
+    // allows deeper recursion in PHP code.
+    fn statement(&mut self, statement: &'a Statement) -> Eval<Flow> {
+        match &statement.kind {
+            StatementKind::InlineHtml(html) => self.output.write_all(html.as_bytes())?,
+            StatementKind::Echo { values } => {
+                for value in values {
+                    let value = self.evaluate(value)?;
+                    self.echo(&value, statement.span)?;
+                }
+            },
+            StatementKind::Expression { expr } => {
+                self.evaluate(expr)?;
+            },
+            StatementKind::If { condition, then, else_ifs, r#else } => {
+                if self.evaluate(condition)?.to_bool() {
+                    return self.block(then);
+                }
+
+                for else_if in else_ifs {
+                    if self.evaluate(&else_if.condition)?.to_bool() {
+                        return self.block(&else_if.body);
+                    }
+                }
+
+                if let Some(r#else) = r#else {
+                    return self.block(r#else);
+                }
+            },
+            StatementKind::While { condition, body } => {
+                while self.evaluate(condition)?.to_bool() {
+                    if let ControlFlow::Break(flow) = iteration(self.block(body)?) {
+                        return Ok(flow);
+                    }
+                }
+            },
+            StatementKind::For { init, condition, r#loop, then } => return self.for_loop(init.as_ref(), condition.as_ref(), r#loop.as_ref(), then),
+            StatementKind::Foreach { by_ref: true, .. } => return Err(Error::unsupported("iterating by reference", statement.span).into()),
+            StatementKind::Foreach { expr, key_var, value_var, body, .. } => return self.foreach_loop(expr, key_var.as_ref(), value_var, body),
+            StatementKind::Switch { condition, cases } => return self.switch(condition, cases),
+            StatementKind::Break { num } => return Ok(Flow::Break(self.levels(num.as_ref())?)),
+            StatementKind::Continue { num } => return Ok(Flow::Continue(self.levels(num.as_ref())?)),
+            StatementKind::Return { value: Some(value) } => return Ok(Flow::Return(self.evaluate(value)?)),
+            StatementKind::Return { value: None } => return Ok(Flow::Return(Value::Null)),
+            StatementKind::Function { .. } => self.declare(statement)?,
+            StatementKind::Const { constants, .. } => {
+                for constant in constants {
+                    let value = self.evaluate(&constant.value)?;
+
+                    if self.constants.contains_key(&constant.name.name) {
+                        self.warn(format!("Constant {} already defined", constant.name.name), constant.name.span);
+                    } else {
+                        self.constants.insert(constant.name.name.clone(), value);
+                    }
+                }
+            },
+            StatementKind::Namespace { body, .. } => return self.block(body),
+            StatementKind::HaltCompiler { .. } => return Err(Unwind::Exit(0)),
+            StatementKind::Use { .. } | StatementKind::Comment { .. } | StatementKind::Noop => {},
+            other => return Err(Error::unsupported(format!("`{}` statements", variant(other)), statement.span).into()),
+        }
+
+        Ok(Flow::Normal)
+    }
+
+    fn for_loop(&mut self, init: Option<&'a Expression>, condition: Option<&'a Expression>, r#loop: Option<&'a Expression>, body: &'a Block) -> Eval<Flow> {
+        if let Some(init) = init {
+            self.evaluate(init)?;
+        }
+
+        loop {
+            if let Some(condition) = condition {
+                if ! self.evaluate(condition)?.to_bool() {
+                    return Ok(Flow::Normal);
+                }
+            }
+
+            if let ControlFlow::Break(flow) = iteration(self.block(body)?) {
+                return Ok(flow);
+            }
+
+            if let Some(r#loop) = r#loop {
+                self.evaluate(r#loop)?;
+            }
+        }
+    }
+
+    fn foreach_loop(&mut self, expr: &'a Expression, key_var: Option<&'a Expression>, value_var: &'a Expression, body: &'a Block) -> Eval<Flow> {
+        let array = match self.evaluate(expr)? {
+            Value::Array(array) => array,
+            other => {
+                self.warn(format!("foreach() argument must be of type array|object, {} given", other.type_name()), expr.span);
+                return Ok(Flow::Normal);
+            },
+        };
+
+        for (key, value) in array.iter() {
+            if let Some(key_var) = key_var {
+                self.assign(key_var, Value::from(key.clone()))?;
+            }
+
+            self.assign(value_var, value.clone())?;
+
+            if let ControlFlow::Break(flow) = iteration(self.block(body)?) {
+                return Ok(flow);
+            }
+        }
+
+        Ok(Flow::Normal)
+    }
+
+    fn switch(&mut self, condition: &'a Expression, cases: &'a [Case]) -> Eval<Flow> {
+        let value = self.evaluate(condition)?;
+        let mut start = None;
+
+        for (i, case) in cases.iter().enumerate() {
+            match &case.condition {
+                Some(condition) => if loose_equals(&value, &self.evaluate(condition)?) {
+                    start = Some(i);
+                    break;
+                },
+                None => start = start.or(Some(i)),
+            }
+        }
+
+        // Matching a case runs the bodies of the cases after it too, until a `break`, and
+        // `continue` acts like `break`.
+        for case in &cases[start.unwrap_or(cases.len())..] {
+            match self.block(&case.body)? {
+                Flow::Normal => {},
+                Flow::Break(1) | Flow::Continue(1) => break,
+                Flow::Break(n) => return Ok(Flow::Break(n - 1)),
+                Flow::Continue(n) => return Ok(Flow::Continue(n - 1)),
+                flow @ Flow::Return(_) => return Ok(flow),
+            }
+        }
+
+        Ok(Flow::Normal)
+    }
+
+    /// How many loops a `break` or `continue` applies to.
+    fn levels(&mut self, num: Option<&'a Expression>) -> Eval<usize> {
+        let Some(num) = num else { return Ok(1) };
+
+        match self.evaluate(num)?.to_int() {
+            levels if levels >= 1 => Ok(levels as usize),
+            _ => Err(thrown("Error", "Levels to break or continue must be positive", num.span)),
+        }
+    }
+
+    fn evaluate(&mut self, expression: &'a Expression) -> Eval<Value> {
+        let span = expression.span;
+
+        Ok(match &expression.kind {
+            ExpressionKind::Int { i } => Value::Int(*i),
+            ExpressionKind::Float { f } => Value::Float(f.0),
+            ExpressionKind::Bool { value } => Value::Bool(*value),
+            ExpressionKind::Null => Value::Null,
+            ExpressionKind::ConstantString { value } | ExpressionKind::Nowdoc { value, .. } => Value::String(value.clone()),
+            ExpressionKind::InterpolatedString { parts } | ExpressionKind::Heredoc { parts, .. } => self.interpolate(parts)?,
+            ExpressionKind::Array { items } => Value::Array(self.array(items)?),
+            ExpressionKind::Variable { .. } | ExpressionKind::ArrayIndex { .. } => self.element(expression)?,
+            ExpressionKind::Identifier { name } => self.constant(name, span)?,
+            ExpressionKind::MagicConst { constant } => match constant {
+                MagicConst::Line => Value::Int(span.line as i64),
+                MagicConst::Function => Value::String(self.frames.last().map(|frame| frame.function).unwrap_or_default().into()),
+                MagicConst::Class | MagicConst::Method | MagicConst::Trait | MagicConst::Namespace => Value::String(String::new()),
+                MagicConst::File | MagicConst::Dir => return Err(Error::unsupported("`__FILE__` and `__DIR__`", span).into()),
+            },
+            ExpressionKind::Infix { lhs, op: InfixOp::And | InfixOp::LogicalAnd, rhs } => {
+                Value::Bool(self.evaluate(lhs)?.to_bool() && self.evaluate(rhs)?.to_bool())
+            },
+            ExpressionKind::Infix { lhs, op: InfixOp::Or | InfixOp::LogicalOr, rhs } => {
+                Value::Bool(self.evaluate(lhs)?.to_bool() || self.evaluate(rhs)?.to_bool())
+            },
+            ExpressionKind::Infix { lhs, op, rhs } if op.is_assignment() => {
+                self.compound(lhs, op, rhs, span)?
+            },
+            ExpressionKind::Infix { lhs, op, rhs } => {
+                let lhs = self.evaluate(lhs)?;
+                let rhs = self.evaluate(rhs)?;
+
+                self.binary(op, &lhs, &rhs, span)?
+            },
+            ExpressionKind::Assign { var, value } => {
+                let value = self.evaluate(value)?;
+                self.assign(var, value.clone())?;
+
+                value
+            },
+            ExpressionKind::CoalesceAssign { var, value } => self.coalesce_assign(var, value)?,
+            ExpressionKind::PreIncrement { value } => self.step(value, increment, true)?,
+            ExpressionKind::PreDecrement { value } => self.step(value, decrement, true)?,
+            ExpressionKind::PostIncrement { value } => self.step(value, increment, false)?,
+            ExpressionKind::PostDecrement { value } => self.step(value, decrement, false)?,
+            ExpressionKind::BooleanNot { value } => Value::Bool(! self.evaluate(value)?.to_bool()),
+            ExpressionKind::Negate { value } | ExpressionKind::UnaryPlus { value } | ExpressionKind::BitwiseNot { value } => {
+                self.unary(&expression.kind, value, span)?
+            },
+            ExpressionKind::Ternary { condition, then, r#else } => {
+                let condition = self.evaluate(condition)?;
+
+                match (condition.to_bool(), then) {
+                    (true, Some(then)) => self.evaluate(then)?,
+                    (true, None) => condition,
+                    (false, _) => self.evaluate(r#else)?,
+                }
+            },
+            ExpressionKind::Coalesce { lhs, rhs } => match self.quietly(|interpreter| interpreter.evaluate(lhs))? {
+                Value::Null => self.evaluate(rhs)?,
+                value => value,
+            },
+            ExpressionKind::ErrorSuppress { value } => self.quietly(|interpreter| interpreter.evaluate(value))?,
+            ExpressionKind::Call { target, args } => self.call(target, args, span)?,
+            ExpressionKind::Print { value } => {
+                let value = self.evaluate(value)?;
+                self.echo(&value, span)?;
+
+                Value::Int(1)
+            },
+            ExpressionKind::Exit { value } => return Err(self.exit(value.as_deref(), span)),
+            ExpressionKind::Cast { kind, value } => self.cast(kind, value, span)?,
+            ExpressionKind::Match { condition, arms } => self.r#match(condition, arms, span)?,
+            other => return Err(Error::unsupported(format!("`{}` expressions", variant(other)), span).into()),
+        })
+    }
+
+    fn interpolate(&mut self, parts: &'a [StringPart]) -> Eval<Value> {
+        let mut string = String::new();
+
+        for part in parts {
+            match part {
+                StringPart::Const(s) => string.push_str(s),
+                StringPart::Expr(expr) => {
+                    let value = self.evaluate(expr)?;
+                    string.push_str(&self.string(&value, expr.span));
+                },
+            }
+        }
+
+        Ok(Value::String(string))
+    }
+
+    /// Read a variable or array element.
+    fn element(&mut self, expression: &'a Expression) -> Eval<Value> {
+        if let Some(place) = self.place(expression)? {
+            return self.read(&place, expression.span);
+        }
+
+        // Indexing something other than a variable, as in `f()[0]`.
+        let ExpressionKind::ArrayIndex { array, index: Some(index) } = &expression.kind else {
+            return Err(thrown("Error", "Cannot use [] for reading", expression.span));
+        };
+
+        let array = self.evaluate(array)?;
+        let key = self.key(index)?;
+
+        self.lookup(&array, &[Some(key)], expression.span)
+    }
+
+    /// An assignment operator such as `+=`.
+    fn compound(&mut self, lhs: &'a Expression, op: &InfixOp, rhs: &'a Expression, span: Span) -> Eval<Value> {
+        let place = self.assignable(lhs)?;
+        let value = self.read(&place, lhs.span)?;
+        let rhs = self.evaluate(rhs)?;
+        let result = self.binary(op, &value, &rhs, span)?;
+
+        self.write(&place, result.clone(), span)?;
+
+        Ok(result)
+    }
+
+    fn coalesce_assign(&mut self, var: &'a Expression, value: &'a Expression) -> Eval<Value> {
+        let place = self.assignable(var)?;
+
+        match self.quietly(|interpreter| interpreter.read(&place, var.span))? {
+            Value::Null => {
+                let value = self.evaluate(value)?;
+                self.write(&place, value.clone(), var.span)?;
+
+                Ok(value)
+            },
+            value => Ok(value),
+        }
+    }
+
+    fn unary(&mut self, kind: &ExpressionKind, value: &'a Expression, span: Span) -> Eval<Value> {
+        let value = self.evaluate(value)?;
+        let (value, leading) = unary(kind, &value).map_err(|error| error.at(span))?;
+
+        if leading {
+            self.warn("A non-numeric value encountered", span);
+        }
+
+        Ok(value)
+    }
+
+    /// `exit`, which prints its argument unless it's an integer status.
+    fn exit(&mut self, value: Option<&'a Expression>, span: Span) -> Unwind {
+        let Some(value) = value else { return Unwind::Exit(0) };
+
+        match self.evaluate(value) {
+            Ok(Value::Int(status)) => Unwind::Exit(status as i32),
+            Ok(message) => match self.echo(&message, span) {
+                Ok(()) => Unwind::Exit(0),
+                Err(unwind) => unwind,
+            },
+            Err(unwind) => unwind,
+        }
+    }
+
+    fn cast(&mut self, kind: &CastKind, value: &'a Expression, span: Span) -> Eval<Value> {
+        let value = self.evaluate(value)?;
+
+        Ok(match kind {
+            CastKind::Int => Value::Int(value.to_int()),
+            CastKind::Double => Value::Float(value.to_float()),
+            CastKind::Bool => Value::Bool(value.to_bool()),
+            CastKind::String => Value::String(self.string(&value, span)),
+            CastKind::Array => match value {
+                Value::Array(array) => Value::Array(array),
+                Value::Null => Value::Array(Array::new()),
+                scalar => Value::Array([scalar].into_iter().collect()),
+            },
+            CastKind::Object => return Err(Error::unsupported("objects", span).into()),
+        })
+    }
+
+    fn r#match(&mut self, condition: &'a Expression, arms: &'a [MatchArm], span: Span) -> Eval<Value> {
+        let value = self.evaluate(condition)?;
+        let mut default = None;
+
+        for arm in arms {
+            match &arm.conditions {
+                Some(conditions) => {
+                    for condition in conditions {
+                        if strict_equals(&value, &self.evaluate(condition)?) {
+                            return self.evaluate(&arm.body);
+                        }
+                    }
+                },
+                None => default = Some(&arm.body),
+            }
+        }
+
+        if let Some(body) = default {
+            return self.evaluate(body);
+        }
+
+        let case = match &value {
+            Value::String(s) => format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'")),
+            Value::Int(i) => i.to_string(),
+            Value::Float(f) => crate::value::format_float(*f, 0),
+            Value::Bool(b) => b.to_string(),
+            Value::Null => "NULL".into(),
+            Value::Array(_) => "of type array".into(),
+        };
+
+        Err(thrown("UnhandledMatchError", format!("Unhandled match case {}", case), span))
+    }
+
+    fn array(&mut self, items: &'a [ArrayItem]) -> Eval<Array> {
+        let mut array = Array::new();
+
+        for item in items {
+            if item.by_ref {
+                return Err(Error::unsupported("references", item.value.span).into());
+            }
+
+            let value = self.evaluate(&item.value)?;
+
+            if item.unpack {
+                let Value::Array(unpacked) = value else {
+                    return Err(thrown("Error", "Only arrays can be unpacked", item.value.span));
+                };
+
+                // Integer keys are renumbered, while string keys are kept.
+                for (key, value) in unpacked.iter() {
+                    match key {
+                        Key::Int(_) => { array.push(value.clone()); },
+                        Key::String(_) => array.insert(key.clone(), value.clone()),
+                    }
+                }
+
+                continue;
+            }
+
+            match &item.key {
+                Some(key) => {
+                    let key = self.key(key)?;
+                    array.insert(key, value);
+                },
+                None => {
+                    array.push(value);
+                },
+            }
+        }
+
+        Ok(array)
+    }
+
+    fn key(&mut self, expression: &'a Expression) -> Eval<Key> {
+        match self.evaluate(expression)?.to_key() {
+            Some(key) => Ok(key),
+            None => Err(thrown("TypeError", "Illegal offset type", expression.span)),
+        }
+    }
+
+    /// The place that a variable or array element expression refers to, or `None` for other
+    /// expressions, and for elements of something other than a variable, as in `f()[0]`.
+    fn place(&mut self, expression: &'a Expression) -> Eval<Option<Place>> {
+        match &expression.kind {
+            ExpressionKind::Variable { name } => Ok(Some(Place { variable: name.clone(), keys: Vec::new() })),
+            ExpressionKind::ArrayIndex { array, index } => {
+                let Some(mut place) = self.place(array)? else { return Ok(None) };
+
+                let key = match index {
+                    Some(index) => Some(self.key(index)?),
+                    None => None,
+                };
+
+                place.keys.push(key);
+                Ok(Some(place))
+            },
+            _ => Ok(None),
+        }
+    }
+
+    /// The place that an expression assigned to refers to.
+    fn assignable(&mut self, expression: &'a Expression) -> Eval<Place> {
+        match self.place(expression)? {
+            Some(place) => Ok(place),
+            None => Err(Error::unsupported(format!("assigning to `{}` expressions", variant(&expression.kind)), expression.span).into()),
+        }
+    }
+
+    fn variables(&mut self) -> &mut HashMap<String, Value> {
+        match self.frames.last_mut() {
+            Some(frame) => &mut frame.variables,
+            None => &mut self.globals,
+        }
+    }
+
+    fn read(&mut self, place: &Place, span: Span) -> Eval<Value> {
+        let Some(value) = self.variables().get(&place.variable) else {
+            self.warn(format!("Undefined variable ${}", place.variable), span);
+            return Ok(Value::Null);
+        };
+
+        // Only the element that's read is copied, rather than the whole array.
+        match lookup(value, &place.keys) {
+            Ok(value) => Ok(value),
+            Err(Missing::Warning(message)) => {
+                self.warn(message, span);
+                Ok(Value::Null)
+            },
+            Err(Missing::Error(error)) => Err(error.at(span).into()),
+        }
+    }
+
+    fn lookup(&mut self, value: &Value, keys: &[Option<Key>], span: Span) -> Eval<Value> {
+        match lookup(value, keys) {
+            Ok(value) => Ok(value),
+            Err(Missing::Warning(message)) => {
+                self.warn(message, span);
+                Ok(Value::Null)
+            },
+            Err(Missing::Error(error)) => Err(error.at(span).into()),
+        }
+    }
+
+    fn write(&mut self, place: &Place, value: Value, span: Span) -> Eval<()> {
+        let mut slot = self.variables().entry(place.variable.clone()).or_insert(Value::Null);
+
+        for key in &place.keys {
+            if matches!(slot, Value::Null) {
+                *slot = Value::Array(Array::new());
+            }
+
+            let array = match slot {
+                Value::Array(array) => array,
+                Value::String(_) => return Err(Error::unsupported("assigning to string offsets", span).into()),
+                _ => return Err(thrown("Error", "Cannot use a scalar value as an array", span)),
+            };
+
+            let key = match key {
+                Some(key) => key.clone(),
+                None => array.push(Value::Null),
+            };
+
+            if array.get(&key).is_none() {
+                array.insert(key.clone(), Value::Null);
+            }
+
+            slot = array.get_mut(&key).expect("the key was just inserted");
+        }
+
+        *slot = value;
+
+        Ok(())
+    }
+
+    /// Assign to a variable or array element, or destructure an array into several of them, as in
+    /// `[$a, 'b' => $b] = $array`.
+    fn assign(&mut self, target: &'a Expression, value: Value) -> Eval<()> {
+        let items: Vec<(Option<&'a Expression>, &'a Expression)> = match &target.kind {
+            ExpressionKind::Array { items } => items.iter().map(|item| (item.key.as_ref(), &item.value)).collect(),
+            ExpressionKind::Call { target: list, args } if matches!(&list.kind, ExpressionKind::Identifier { name } if name.eq_ignore_ascii_case("list")) => {
+                args.iter().map(|arg| (None, &arg.value)).collect()
+            },
+            _ => {
+                let place = self.assignable(target)?;
+                return self.write(&place, value, target.span);
+            },
+        };
+
+        for (i, (key, item)) in items.into_iter().enumerate() {
+            let key = match key {
+                Some(key) => self.key(key)?,
+                None => Key::Int(i as i64),
+            };
+
+            // Destructuring something other than an array sets every variable to null.
+            let element = match &value {
+                Value::Array(_) => self.lookup(&value, &[Some(key)], item.span)?,
+                _ => Value::Null,
+            };
+
+            self.assign(item, element)?;
+        }
+
+        Ok(())
+    }
+
+    /// `++` or `--`, giving the new value if it's a prefix and the old one if it's a postfix.
+    fn step(&mut self, target: &'a Expression, step: fn(&Value) -> Result<Value, Error>, prefix: bool) -> Eval<Value> {
+        let place = self.assignable(target)?;
+        let old = self.read(&place, target.span)?;
+        let new = step(&old).map_err(|error| error.at(target.span))?;
+
+        self.write(&place, new.clone(), target.span)?;
+
+        Ok(if prefix { new } else { old })
+    }
+
+    fn binary(&mut self, op: &InfixOp, lhs: &Value, rhs: &Value, span: Span) -> Eval<Value> {
+        if matches!(op, InfixOp::Concat | InfixOp::ConcatAssign) {
+            for value in [lhs, rhs] {
+                if matches!(value, Value::Array(_)) {
+                    self.warn("Array to string conversion", span);
+                }
+            }
+        }
+
+        let (value, leading) = binary(op, lhs, rhs).map_err(|error| error.at(span))?;
+
+        if leading {
+            self.warn("A non-numeric value encountered", span);
+        }
+
+        Ok(value)
+    }
+
+    fn constant(&mut self, name: &str, span: Span) -> Eval<Value> {
+        let name = name.rsplit('\\').next().unwrap_or(name);
+
+        if let Some(value) = self.constants.get(name) {
+            return Ok(value.clone());
+        }
+
+        match name.to_ascii_lowercase().as_str() {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            "null" => Ok(Value::Null),
+            _ => Err(thrown("Error", format!("Undefined constant \"{}\"", name), span)),
+        }
+    }
+
+    fn call(&mut self, target: &'a Expression, args: &'a [Arg], span: Span) -> Eval<Value> {
+        let ExpressionKind::Identifier { name } = &target.kind else {
+            return Err(Error::unsupported(format!("calling `{}` expressions", variant(&target.kind)), span).into());
+        };
+
+        let name = name.rsplit('\\').next().unwrap_or(name);
+        let key = name.to_ascii_lowercase();
+
+        // Language constructs that look like functions.
+        match key.as_str() {
+            "isset" => {
+                for arg in args {
+                    if matches!(self.quietly(|interpreter| interpreter.evaluate(&arg.value))?, Value::Null) {
+                        return Ok(Value::Bool(false));
+                    }
+                }
+
+                return Ok(Value::Bool(true));
+            },
+            "empty" if args.len() == 1 => {
+                return Ok(Value::Bool(! self.quietly(|interpreter| interpreter.evaluate(&args[0].value))?.to_bool()));
+            },
+            "unset" => {
+                for arg in args {
+                    let place = self.assignable(&arg.value)?;
+                    self.unset(&place);
+                }
+
+                return Ok(Value::Null);
+            },
+            _ => {},
+        }
+
+        let values = self.arguments(args)?;
+
+        if let Some(function) = self.functions.get(&key).copied() {
+            return self.call_function(function, values, span);
+        }
+
+        let Some(builtin) = self.builtins.get(&key).copied() else {
+            return Err(thrown("Error", format!("Call to undefined function {}()", name), target.span));
+        };
+
+        if values.len() < builtin.min || builtin.max.is_some_and(|max| values.len() > max) {
+            let (bound, count) = match builtin.max {
+                Some(max) if max == builtin.min => ("exactly", max),
+                Some(max) if values.len() > max => ("at most", max),
+                _ => ("at least", builtin.min),
+            };
+
+            let message = format!("{}() expects {} {} argument{}, {} given", name, bound, count, if count == 1 { "" } else { "s" }, values.len());
+
+            return Err(thrown("ArgumentCountError", message, span));
+        }
+
+        match (builtin.function)(&values, &mut self.output) {
+            Ok(value) => Ok(value),
+            // Errors about arguments say which function they're from.
+            Err(Error::Thrown { class: class @ ("TypeError" | "ValueError" | "ArgumentCountError"), message, .. }) => {
+                Err(thrown(class, format!("{}(): {}", name, message), span))
+            },
+            Err(error) => Err(error.at(span).into()),
+        }
+    }
+
+    fn arguments(&mut self, args: &'a [Arg]) -> Eval<Vec<Value>> {
+        let mut values = Vec::new();
+
+        for arg in args {
+            if arg.name.is_some() {
+                return Err(Error::unsupported("named arguments", arg.value.span).into());
+            }
+
+            let value = self.evaluate(&arg.value)?;
+
+            if arg.unpack {
+                let Value::Array(array) = value else {
+                    return Err(thrown("Error", "Only arrays can be unpacked", arg.value.span));
+                };
+
+                values.extend(array.values().cloned());
+            } else {
+                values.push(value);
+            }
+        }
+
+        Ok(values)
+    }
+
+    fn call_function(&mut self, function: &'a Statement, args: Vec<Value>, span: Span) -> Eval<Value> {
+        let StatementKind::Function { name, params, body, .. } = &function.kind else { unreachable!() };
+
+        if self.frames.len() >= self.max_depth {
+            return Err(thrown("Error", format!("Maximum function nesting level of '{}' reached, aborting!", self.max_depth), span));
+        }
+
+        let required = params.iter().rposition(|param| param.default.is_none() && ! param.variadic).map_or(0, |i| i + 1);
+
+        if args.len() < required {
+            let bound = if required == params.len() && ! params.iter().any(|param| param.variadic) { "exactly" } else { "at least" };
+            let message = format!("Too few arguments to function {}(), {} passed and {} {} expected", name.name, args.len(), bound, required);
+
+            return Err(thrown("ArgumentCountError", message, span));
+        }
+
+        self.frames.push(Frame { function: &name.name, variables: HashMap::new() });
+
+        let result = self.parameters(params, args).and_then(|_| self.block(body));
+
+        self.frames.pop();
+
+        match result? {
+            Flow::Return(value) => Ok(value),
+            _ => Ok(Value::Null),
+        }
+    }
+
+    fn parameters(&mut self, params: &'a [trunk_parser::Param], args: Vec<Value>) -> Eval<()> {
+        let mut args = args.into_iter();
+
+        for param in params {
+            if param.by_ref {
+                return Err(Error::unsupported("passing by reference", param.span).into());
+            }
+
+            let value = if param.variadic {
+                Value::Array(args.by_ref().collect())
+            } else {
+                match (args.next(), &param.default) {
+                    (Some(value), _) => value,
+                    (None, Some(default)) => self.evaluate(default)?,
+                    (None, None) => unreachable!("missing arguments are checked before the call"),
+                }
+            };
+
+            if let Some(name) = param.variable_name() {
+                self.variables().insert(name.to_string(), value);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn unset(&mut self, place: &Place) {
+        let variables = self.variables();
+
+        let Some((last, parents)) = place.keys.split_last() else {
+            variables.remove(&place.variable);
+            return;
+        };
+
+        let Some(mut value) = variables.get_mut(&place.variable) else { return };
+
+        for key in parents {
+            match (value, key) {
+                (Value::Array(array), Some(key)) => match array.get_mut(key) {
+                    Some(element) => value = element,
+                    None => return,
+                },
+                _ => return,
+            }
+        }
+
+        if let (Value::Array(array), Some(key)) = (value, last) {
+            array.remove(key);
+        }
+    }
+
+    fn echo(&mut self, value: &Value, span: Span) -> Eval<()> {
+        let string = self.string(value, span);
+        self.output.write_all(string.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Convert a value to a string, warning about arrays, which are just `Array`.
+    fn string(&mut self, value: &Value, span: Span) -> String {
+        if matches!(value, Value::Array(_)) {
+            self.warn("Array to string conversion", span);
+        }
+
+        value.to_string()
+    }
+
+    /// Evaluate something without keeping the warnings it causes, as `@`, `isset` and `??` do.
+    fn quietly<T>(&mut self, evaluate: impl FnOnce(&mut Self) -> Eval<T>) -> Eval<T> {
+        let warnings = self.warnings.len();
+        let result = evaluate(self);
+        self.warnings.truncate(warnings);
+
+        result
+    }
+
+    fn warn(&mut self, message: impl Into<String>, span: Span) {
+        self.warnings.push(Warning { message: message.into(), span });
+    }
+}
+
+/// What a loop does after an iteration of its body: carry on, or stop with a flow for the code
+/// around it.
+fn iteration(flow: Flow) -> ControlFlow<Flow> {
+    match flow {
+        Flow::Normal | Flow::Continue(1) => ControlFlow::Continue(()),
+        Flow::Break(1) => ControlFlow::Break(Flow::Normal),
+        Flow::Break(n) => ControlFlow::Break(Flow::Break(n - 1)),
+        Flow::Continue(n) => ControlFlow::Break(Flow::Continue(n - 1)),
+        Flow::Return(value) => ControlFlow::Break(Flow::Return(value)),
+    }
+}
+
+/// Why an element couldn't be read.
+enum Missing {
+    /// PHP warns and gives `null`, as for an undefined key.
+    Warning(String),
+    Error(Error),
+}
+
+/// Look up an element of a value, as in `$value[1]['a']`.
+fn lookup(mut value: &Value, keys: &[Option<Key>]) -> Result<Value, Missing> {
+    for (i, key) in keys.iter().enumerate() {
+        let Some(key) = key else {
+            return Err(Missing::Error(Error::thrown("Error", "Cannot use [] for reading")));
+        };
+
+        match value {
+            Value::Array(array) => match array.get(key) {
+                Some(element) => value = element,
+                None => return Err(Missing::Warning(match key {
+                    Key::Int(i) => format!("Undefined array key {}", i),
+                    Key::String(s) => format!("Undefined array key \"{}\"", s),
+                })),
+            },
+            Value::String(s) => {
+                let Key::Int(offset) = key else {
+                    return Err(Missing::Error(Error::thrown("TypeError", "Cannot access offset of type string on string")));
+                };
+
+                let index = if *offset < 0 { s.len() as i64 + offset } else { *offset };
+                let Some(byte) = usize::try_from(index).ok().and_then(|index| s.as_bytes().get(index)) else {
+                    return Err(Missing::Warning(format!("Uninitialized string offset {}", offset)));
+                };
+
+                return lookup(&Value::String(String::from_utf8_lossy(&[*byte]).into_owned()), &keys[i + 1..]);
+            },
+            other => return Err(Missing::Warning(format!("Trying to access array offset on value of type {}", other.type_name()))),
+        }
+    }
+
+    Ok(value.clone())
+}
+
+fn thrown(class: &'static str, message: impl Into<String>, span: Span) -> Unwind {
+    Unwind::Error(Error::thrown(class, message).at(span))
+}
+
+/// The name of a statement or expression's variant, such as `Class`, for saying what isn't
+/// supported.
+fn variant(kind: &impl std::fmt::Debug) -> String {
+    format!("{:?}", kind).chars().take_while(char::is_ascii_alphanumeric).collect()
+}
@@ -0,0 +1,233 @@
+mod builtins;
+mod error;
+mod interpreter;
+mod operators;
+mod value;
+
+pub use builtins::{builtins, constants, Builtin};
+pub use error::{Error, Warning};
+pub use interpreter::Interpreter;
+pub use operators::{binary, unary};
+pub use value::{compare, format_float, loose_equals, strict_equals, Array, Key, Number, Value};
+
+#[cfg(test)]
+mod tests {
+    use trunk_parser::parse;
+
+    use super::{Builtin, Error, Interpreter, Value};
+
+    /// Run a program, returning what it printed and either its exit status or the error that
+    /// ended it.
+    fn run(source: &str) -> (String, Result<i32, String>) {
+        let (output, result, _) = run_with_warnings(source);
+
+        (output, result)
+    }
+
+    fn run_with_warnings(source: &str) -> (String, Result<i32, String>, Vec<String>) {
+        let program = parse(source).unwrap();
+        let mut interpreter = Interpreter::new(Vec::new());
+        let result = interpreter.run(&program).map_err(|error| error.to_string());
+        let warnings = interpreter.warnings().iter().map(|warning| format!("{} on line {}", warning, warning.span.line)).collect();
+
+        (String::from_utf8(interpreter.into_output()).unwrap(), result, warnings)
+    }
+
+    fn output(source: &str) -> String {
+        let (output, result) = run(source);
+        assert_eq!(result, Ok(0), "output: {}", output);
+
+        output
+    }
+
+    #[test]
+    fn fib() {
+        assert_eq!(output(include_str!("../examples/fib.php")), "\
+fib(0) = 0
+fib(1) = 1
+fib(2) = 1
+fib(3) = 2
+fib(4) = 3
+fib(5) = 5
+fib(6) = 8
+fib(7) = 13
+fib(8) = 21
+fib(9) = 34
+fib(10) = 55
+");
+    }
+
+    #[test]
+    fn expressions() {
+        assert_eq!(output("<?php echo 1 + 2 * 3, ' ', 7 / 2, ' ', 2 ** 3, ' ', -7 % 3, ' ', 'a' . 1.0;"), "7 3.5 8 -1 a1");
+        assert_eq!(output("<?php echo 0.1 + 0.2, ' ', PHP_INT_MAX + 1, ' ', 10 / 4 * 4;"), "0.3 9.2233720368548E+18 10");
+        assert_eq!(output("<?php echo true, false, null, '|', 1 <=> 2, '|', '10' == '1e1', '|', 0 == 'a' ? 'y' : 'n';"), "1|-1|1|n");
+        assert_eq!(output("<?php $a = null; echo $a ?? 'default', ' ', $b ?? 'unset', ' ', $a ?: 'falsy';"), "default unset falsy");
+        assert_eq!(output("<?php $x = 5; echo match (true) { $x < 3 => 'small', $x < 10 => 'medium', default => 'large' };"), "medium");
+        assert_eq!(output("<?php $s = 'abc'; echo \"{$s[0]}-$s-\", (int) '12abc' + 1, (string) 1.5, strlen(\"x\\ny\");"), "a-abc-131.53");
+        assert_eq!(output("<?php $i = 1; echo $i++ + ++$i, ' ', $i, ' '; $z = 'Az'; $z++; echo $z;"), "4 3 Ba");
+        assert_eq!(output("<?php $s = 'a'; $s .= 'b'; $n = 10; $n -= 3; $n *= 2; echo $s, $n;"), "ab14");
+        assert_eq!(output("<?php $a = 'b'; echo \"$a\\t\\x41\\u{e9}\\\\\\d\\n\";"), "b\tAé\\\\d\n");
+        assert_eq!(output("<?php $n = 7; $n %= 4; $n **= 3; $n &= 25; $n |= 4; $n ^= 1; $n <<= 2; $n >>= 1; echo $n;"), "56");
+    }
+
+    #[test]
+    fn arrays() {
+        assert_eq!(output("<?php
+$a = [1, 2, 'k' => 'v'];
+$a[] = 3;
+$a['nested']['deep'] = true;
+unset($a[0]);
+echo count($a), ' ', implode(',', array_keys($a)), ' ', $a['nested']['deep'] ? 'yes' : 'no', ' ';
+[$x, [$w, $y]] = [1, [2, 3]];
+['k' => $k] = $a;
+list($p, $q) = [4, 5];
+echo $x + $y + $p + $q, $k, ' ', isset($a['k'], $a[1]) ? 'set' : 'unset', empty($a['missing']) ? ' empty' : '';
+"), "4 1,k,2,nested yes 13v set empty");
+
+        assert_eq!(output("<?php $a = [3 => 'a']; $a[] = 'b'; $b = [...$a, ...['x' => 'y']]; print_r($b);"), "\
+Array
+(
+    [0] => a
+    [1] => b
+    [x] => y
+)
+");
+    }
+
+    #[test]
+    fn control_flow() {
+        assert_eq!(output("<?php
+$total = 0;
+foreach ([1, 2, 3, 4, 5, 6] as $i => $n) {
+    if ($n % 2) { continue; }
+    if ($n > 4) { break; }
+    $total += $n * $i;
+}
+echo $total, ' ';
+
+for ($i = 0; $i < 3; $i++) {
+    for ($j = 0; ; $j++) {
+        if ($j == 2) { continue 2; }
+        if ($i == 2) { break 2; }
+        echo $i, $j, ',';
+    }
+}
+
+$n = 0;
+while (true) { if (++$n >= 5) break; }
+echo ' ', $n, ' ';
+
+switch ('2') {
+    case 1: echo 'one';
+    case 2: echo 'two';
+    case 3: echo 'three'; break;
+    default: echo 'other';
+}
+"), "14 00,01,10,11, 5 twothree");
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(output("<?php
+echo greet('World'), greet('PHP', '?'), PHP_EOL;
+
+function greet($name, $punctuation = '!') {
+    return \"Hello, $name$punctuation \";
+}
+
+function sum(...$numbers) { return array_sum($numbers); }
+echo sum(1, 2, ...[3, 4]), ' ';
+
+function counter() { $count = ($count ?? 0) + 1; return $count; }
+counter();
+echo counter(), ' ', __LINE__;
+
+if (true) {
+    function late() { return 'late'; }
+}
+echo ' ', late();
+"), "Hello, World! Hello, PHP? \n10 1 13 late");
+
+        assert_eq!(run("<?php function f($a, $b) {} f(1);").1, Err("uncaught ArgumentCountError: Too few arguments to function f(), 1 passed and exactly 2 expected".into()));
+        assert_eq!(run("<?php echo 'a'; undefined();").1, Err("uncaught Error: Call to undefined function undefined()".into()));
+        assert_eq!(run("<?php strlen();").1, Err("uncaught ArgumentCountError: strlen() expects exactly 1 argument, 0 given".into()));
+        assert_eq!(run("<?php strlen([]);").1, Err("uncaught TypeError: strlen(): Argument #1 must be of type string, array given".into()));
+        assert_eq!(run("<?php function f() {} function F() {}").1, Err("uncaught Error: Cannot redeclare F()".into()));
+    }
+
+    #[test]
+    fn recursion_limit() {
+        // Unoptimised builds use a lot more stack per call, so give the default limit some room.
+        std::thread::Builder::new().stack_size(64 * 1024 * 1024).spawn(|| {
+            assert_eq!(run("<?php function f($n) { return $n ? f($n - 1) + 1 : 0; } echo f(500);"), ("500".into(), Ok(0)));
+            assert_eq!(run("<?php function f($n) { return f($n + 1); } f(0);").1, Err("uncaught Error: Maximum function nesting level of '512' reached, aborting!".into()));
+        }).unwrap().join().unwrap();
+
+        let program = parse("<?php function f($n) { return $n ? f($n - 1) : 0; } f(3);").unwrap();
+        let mut interpreter = Interpreter::new(Vec::new());
+        interpreter.set_max_depth(3);
+
+        assert!(matches!(interpreter.run(&program), Err(Error::Thrown { class: "Error", .. })));
+    }
+
+    #[test]
+    fn errors() {
+        let (output, result) = run("<?php echo 'before '; echo 1 % 0; echo 'after';");
+        assert_eq!((output.as_str(), result), ("before ", Err("uncaught DivisionByZeroError: Modulo by zero".into())));
+
+        assert_eq!(run("<?php echo 'a'; exit(3); echo 'b';"), ("a".into(), Ok(3)));
+        assert_eq!(run("<?php exit('bye');"), ("bye".into(), Ok(0)));
+        assert_eq!(run("<?php echo match (5) { 1 => 'a' };").1, Err("uncaught UnhandledMatchError: Unhandled match case 5".into()));
+        assert_eq!(run("<?php echo UNDEFINED;").1, Err("uncaught Error: Undefined constant \"UNDEFINED\"".into()));
+        assert_eq!(run("<?php class A {}").1, Err("not supported yet: `Class` statements".into()));
+        assert_eq!(run("<?php $f = fn () => 1;").1, Err("not supported yet: `ArrowFunction` expressions".into()));
+        assert_eq!(run("<?php function f(&$a) {} f($b);").1, Err("not supported yet: passing by reference".into()));
+
+        let program = parse("<?php\n\necho 1 / 0;").unwrap();
+        let error = Interpreter::new(Vec::new()).run(&program).unwrap_err();
+
+        assert!(matches!(error, Error::Thrown { class: "DivisionByZeroError", span, .. } if span.line == 3));
+    }
+
+    #[test]
+    fn warnings() {
+        let (output, result, warnings) = run_with_warnings("<?php
+echo $undefined, 'x';
+$a = [];
+echo $a['key'], $a[0] ?? '', @$a[1];
+echo '3 apples' + 1, [], ' ';
+$n = null;
+echo $n[0];
+");
+
+        assert_eq!((output.as_str(), result), ("x4Array ", Ok(0)));
+        assert_eq!(warnings, [
+            "Undefined variable $undefined on line 2",
+            "Undefined array key \"key\" on line 4",
+            "A non-numeric value encountered on line 5",
+            "Array to string conversion on line 5",
+            "Trying to access array offset on value of type null on line 7",
+        ]);
+    }
+
+    #[test]
+    fn builtins() {
+        let program = parse("<?php echo twice(21), ' ', STRTOUPPER('abc');").unwrap();
+        let mut interpreter = Interpreter::new(Vec::new());
+
+        interpreter.define("twice", Builtin { min: 1, max: Some(1), function: |args, _| Ok(Value::Int(args[0].to_int() * 2)) });
+
+        assert_eq!(interpreter.run(&program).unwrap(), 0);
+        assert_eq!(interpreter.into_output(), b"42 ABC");
+
+        assert_eq!(output("<?php var_dump(1.5, 'a', [true]); printf('%05.1f|%s', 1.23456, gettype(1.0));"), "\
+float(1.5)
+string(1) \"a\"
+array(1) {
+  [0]=>
+  bool(true)
+}
+001.2|double");
+    }
+}
@@ -0,0 +1,236 @@
+use std::cmp::Ordering;
+
+use trunk_parser::{ExpressionKind, InfixOp};
+
+use crate::value::{compare, float_to_int, leading_number, loose_equals, numeric, strict_equals, Number};
+use crate::{Error, Value};
+
+/// Apply an operator other than the logical ones, which short circuit and so are evaluated
+/// separately. Compound assignment operators such as `+=` give the result of their operator.
+///
+/// Along with the result is whether an operand was a string that only starts with a number, such
+/// as `"12 apples"`, which PHP warns about.
+pub fn binary(op: &InfixOp, a: &Value, b: &Value) -> Result<(Value, bool), Error> {
+    let op = &op.operator();
+    let result = match op {
+        InfixOp::Concat => Value::String(format!("{}{}", a, b)),
+        InfixOp::Equals => Value::Bool(loose_equals(a, b)),
+        InfixOp::NotEquals => Value::Bool(! loose_equals(a, b)),
+        InfixOp::Identical => Value::Bool(strict_equals(a, b)),
+        InfixOp::NotIdentical => Value::Bool(! strict_equals(a, b)),
+        InfixOp::LessThan => Value::Bool(compare(a, b) == Some(Ordering::Less)),
+        InfixOp::GreaterThan => Value::Bool(compare(a, b) == Some(Ordering::Greater)),
+        InfixOp::LessThanEquals => Value::Bool(matches!(compare(a, b), Some(Ordering::Less | Ordering::Equal))),
+        InfixOp::GreaterThanEquals => Value::Bool(matches!(compare(a, b), Some(Ordering::Greater | Ordering::Equal))),
+        InfixOp::Spaceship => Value::Int(match compare(a, b) {
+            Some(Ordering::Less) => -1,
+            Some(Ordering::Equal) => 0,
+            Some(Ordering::Greater) | None => 1,
+        }),
+        InfixOp::And | InfixOp::LogicalAnd => Value::Bool(a.to_bool() && b.to_bool()),
+        InfixOp::Or | InfixOp::LogicalOr => Value::Bool(a.to_bool() || b.to_bool()),
+        InfixOp::LogicalXor => Value::Bool(a.to_bool() != b.to_bool()),
+        // Adding arrays gives their union, keeping the first array's value for a key in both.
+        InfixOp::Add if matches!((a, b), (Value::Array(_), Value::Array(_))) => {
+            let (Value::Array(a), Value::Array(b)) = (a, b) else { unreachable!() };
+            let mut union = a.clone();
+
+            for (key, value) in b.iter() {
+                if union.get(key).is_none() {
+                    union.insert(key.clone(), value.clone());
+                }
+            }
+
+            Value::Array(union)
+        },
+        _ => return arithmetic(op, a, b),
+    };
+
+    Ok((result, false))
+}
+
+/// Apply `-`, `+` or `~` to a value, given the expression that applies it, along with whether the
+/// value was a string that only starts with a number, as with `binary`.
+pub fn unary(kind: &ExpressionKind, value: &Value) -> Result<(Value, bool), Error> {
+    match (kind, value) {
+        (ExpressionKind::Negate { .. }, Value::Int(i)) => Ok((i.checked_neg().map_or(Value::Float(-(*i as f64)), Value::Int), false)),
+        (ExpressionKind::Negate { .. }, Value::Float(f)) => Ok((Value::Float(-f), false)),
+        (ExpressionKind::Negate { .. }, other) => binary(&InfixOp::Mul, other, &Value::Int(-1)),
+        (ExpressionKind::BitwiseNot { .. }, Value::Int(i)) => Ok((Value::Int(! i), false)),
+        (ExpressionKind::BitwiseNot { .. }, Value::Float(f)) => Ok((Value::Int(! float_to_int(*f)), false)),
+        (ExpressionKind::BitwiseNot { .. }, other) => Err(Error::thrown("TypeError", format!("Cannot perform bitwise not on {}", other.type_name()))),
+        (_, other) => binary(&InfixOp::Mul, other, &Value::Int(1)),
+    }
+}
+
+fn arithmetic(op: &InfixOp, a: &Value, b: &Value) -> Result<(Value, bool), Error> {
+    let unsupported = || Error::thrown("TypeError", format!("Unsupported operand types: {} {} {}", a.type_name(), symbol(op), b.type_name()));
+    let (x, x_leading) = operand(a).ok_or_else(unsupported)?;
+    let (y, y_leading) = operand(b).ok_or_else(unsupported)?;
+
+    let result = match (op, x, y) {
+        (InfixOp::Add, Number::Int(x), Number::Int(y)) => int_or_float(x.checked_add(y), x as f64 + y as f64),
+        (InfixOp::Add, x, y) => Value::Float(x.to_f64() + y.to_f64()),
+        (InfixOp::Sub, Number::Int(x), Number::Int(y)) => int_or_float(x.checked_sub(y), x as f64 - y as f64),
+        (InfixOp::Sub, x, y) => Value::Float(x.to_f64() - y.to_f64()),
+        (InfixOp::Mul, Number::Int(x), Number::Int(y)) => int_or_float(x.checked_mul(y), x as f64 * y as f64),
+        (InfixOp::Mul, x, y) => Value::Float(x.to_f64() * y.to_f64()),
+        (InfixOp::Div, _, y) if y.to_f64() == 0.0 => return Err(Error::thrown("DivisionByZeroError", "Division by zero")),
+        (InfixOp::Div, Number::Int(x), Number::Int(y)) if x.checked_rem(y) == Some(0) => Value::Int(x / y),
+        (InfixOp::Div, x, y) => Value::Float(x.to_f64() / y.to_f64()),
+        (InfixOp::Mod, x, y) => match int(y) {
+            0 => return Err(Error::thrown("DivisionByZeroError", "Modulo by zero")),
+            y => Value::Int(int(x).wrapping_rem(y)),
+        },
+        (InfixOp::Pow, Number::Int(x), Number::Int(y)) if y >= 0 => {
+            int_or_float(u32::try_from(y).ok().and_then(|y| x.checked_pow(y)), (x as f64).powf(y as f64))
+        },
+        (InfixOp::Pow, x, y) => Value::Float(x.to_f64().powf(y.to_f64())),
+        (InfixOp::LeftShift | InfixOp::RightShift, _, y) if int(y) < 0 => {
+            return Err(Error::thrown("ArithmeticError", "Bit shift by negative number"));
+        },
+        (InfixOp::LeftShift, x, y) => Value::Int(if int(y) >= 64 { 0 } else { int(x) << int(y) }),
+        (InfixOp::RightShift, x, y) => Value::Int(int(x) >> int(y).min(63)),
+        (InfixOp::BitwiseAnd, x, y) => Value::Int(int(x) & int(y)),
+        (InfixOp::BitwiseOr, x, y) => Value::Int(int(x) | int(y)),
+        (InfixOp::BitwiseXor, x, y) => Value::Int(int(x) ^ int(y)),
+        _ => unreachable!("{:?} isn't an arithmetic operator", op),
+    };
+
+    Ok((result, x_leading || y_leading))
+}
+
+/// An operand of an arithmetic operator as a number, and whether it was a string that only starts
+/// with one, or `None` if it can't be used as a number at all.
+fn operand(value: &Value) -> Option<(Number, bool)> {
+    match value {
+        Value::Array(_) => None,
+        Value::String(s) if numeric(s).is_none() => leading_number(s).map(|number| (number, true)),
+        _ => Some((value.to_number().0, false)),
+    }
+}
+
+fn int(number: Number) -> i64 {
+    match number {
+        Number::Int(i) => i,
+        Number::Float(f) => float_to_int(f),
+    }
+}
+
+/// Integers that overflow become floats.
+fn int_or_float(int: Option<i64>, float: f64) -> Value {
+    int.map_or(Value::Float(float), Value::Int)
+}
+
+fn symbol(op: &InfixOp) -> &'static str {
+    match op {
+        InfixOp::Add => "+",
+        InfixOp::Sub => "-",
+        InfixOp::Mul => "*",
+        InfixOp::Div => "/",
+        InfixOp::Mod => "%",
+        InfixOp::Pow => "**",
+        InfixOp::LeftShift => "<<",
+        InfixOp::RightShift => ">>",
+        InfixOp::BitwiseAnd => "&",
+        InfixOp::BitwiseOr => "|",
+        InfixOp::BitwiseXor => "^",
+        _ => "?",
+    }
+}
+
+/// `++`, which also increments letters, so that `"a"` becomes `"b"` and `"Az"` becomes `"Ba"`.
+pub(crate) fn increment(value: &Value) -> Result<Value, Error> {
+    Ok(match value {
+        Value::Null => Value::Int(1),
+        Value::Int(i) => int_or_float(i.checked_add(1), *i as f64 + 1.0),
+        Value::Float(f) => Value::Float(f + 1.0),
+        Value::String(s) if s.is_empty() => Value::String("1".into()),
+        Value::String(s) if numeric(s).is_some() => increment(&Value::from(value.to_number().0))?,
+        Value::String(s) => Value::String(increment_string(s)),
+        Value::Bool(_) => value.clone(),
+        Value::Array(_) => return Err(Error::thrown("TypeError", "Cannot increment array")),
+    })
+}
+
+/// `--`, which leaves `null` and non-numeric strings as they are.
+pub(crate) fn decrement(value: &Value) -> Result<Value, Error> {
+    Ok(match value {
+        Value::Int(i) => int_or_float(i.checked_sub(1), *i as f64 - 1.0),
+        Value::Float(f) => Value::Float(f - 1.0),
+        Value::String(s) if s.is_empty() => Value::Int(-1),
+        Value::String(s) if numeric(s).is_some() => decrement(&Value::from(value.to_number().0))?,
+        Value::Null | Value::String(_) | Value::Bool(_) => value.clone(),
+        Value::Array(_) => return Err(Error::thrown("TypeError", "Cannot decrement array")),
+    })
+}
+
+fn increment_string(s: &str) -> String {
+    let mut bytes = s.as_bytes().to_vec();
+
+    for i in (0..bytes.len()).rev() {
+        let (first, last) = match bytes[i] {
+            b'a'..=b'z' => (b'a', b'z'),
+            b'A'..=b'Z' => (b'A', b'Z'),
+            b'0'..=b'9' => (b'0', b'9'),
+            _ => break,
+        };
+
+        if bytes[i] != last {
+            bytes[i] += 1;
+            return String::from_utf8_lossy(&bytes).into_owned();
+        }
+
+        bytes[i] = first;
+
+        // Carrying past the first character adds another, as in `"z"` becoming `"aa"`.
+        if i == 0 {
+            bytes.insert(0, if first == b'0' { b'1' } else { first });
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_parser::InfixOp;
+
+    use super::{binary, increment};
+    use crate::Value;
+
+    fn apply(op: InfixOp, a: Value, b: Value) -> String {
+        match binary(&op, &a, &b) {
+            Ok((value, _)) => format!("{:?}", value),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(apply(InfixOp::Add, Value::Int(1), Value::String("2".into())), "Int(3)");
+        assert_eq!(apply(InfixOp::Add, Value::Int(i64::MAX), Value::Int(1)), "Float(9.223372036854776e18)");
+        assert_eq!(apply(InfixOp::Div, Value::Int(6), Value::Int(3)), "Int(2)");
+        assert_eq!(apply(InfixOp::Div, Value::Int(7), Value::Int(2)), "Float(3.5)");
+        assert_eq!(apply(InfixOp::Div, Value::Int(1), Value::Int(0)), "uncaught DivisionByZeroError: Division by zero");
+        assert_eq!(apply(InfixOp::Mod, Value::Int(-7), Value::Int(3)), "Int(-1)");
+        assert_eq!(apply(InfixOp::Pow, Value::Int(2), Value::Int(10)), "Int(1024)");
+        assert_eq!(apply(InfixOp::Pow, Value::Int(2), Value::Int(-1)), "Float(0.5)");
+        assert_eq!(apply(InfixOp::Mul, Value::Int(2), Value::String("abc".into())), "uncaught TypeError: Unsupported operand types: int * string");
+        assert_eq!(apply(InfixOp::Concat, Value::Float(1.0), Value::Bool(true)), "String(\"11\")");
+
+        assert_eq!(binary(&InfixOp::Add, &Value::String("3 apples".into()), &Value::Int(1)).unwrap(), (Value::Int(4), true));
+    }
+
+    #[test]
+    fn increments() {
+        let incremented = |s: &str| increment(&Value::String(s.into())).unwrap().to_string();
+
+        assert_eq!(incremented("a"), "b");
+        assert_eq!(incremented("Az"), "Ba");
+        assert_eq!(incremented("zz"), "aaa");
+        assert_eq!(incremented("a9"), "b0");
+        assert_eq!(incremented("9"), "10");
+        assert_eq!(incremented("1.5"), "2.5");
+    }
+}
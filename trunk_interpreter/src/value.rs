@@ -0,0 +1,493 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Display;
+
+/// A PHP value. Strings are UTF-8 rather than arbitrary bytes, and there are no objects yet.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Array),
+}
+
+/// An array key. Strings that look like integers, such as `"12"`, are always stored as integers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Int(i64),
+    String(String),
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(i) => write!(f, "{}", i),
+            Self::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl From<&str> for Key {
+    fn from(s: &str) -> Self {
+        match s.parse::<i64>() {
+            // Only the canonical form of an integer is one, so `"012"` and `"+1"` stay strings.
+            Ok(i) if i.to_string() == s => Self::Int(i),
+            _ => Self::String(s.into()),
+        }
+    }
+}
+
+/// An ordered map, which is what PHP's arrays are.
+#[derive(Debug, Clone, Default)]
+pub struct Array {
+    entries: Vec<(Key, Value)>,
+    positions: HashMap<Key, usize>,
+    /// The key that the next pushed value gets.
+    next: i64,
+}
+
+impl Array {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &Key) -> Option<&Value> {
+        self.positions.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn get_mut(&mut self, key: &Key) -> Option<&mut Value> {
+        self.positions.get(key).map(|&i| &mut self.entries[i].1)
+    }
+
+    /// Set the value of a key, keeping its position if it's already in the array.
+    pub fn insert(&mut self, key: Key, value: Value) {
+        if let Key::Int(i) = key {
+            self.next = self.next.max(i.saturating_add(1));
+        }
+
+        match self.positions.get(&key) {
+            Some(&i) => self.entries[i].1 = value,
+            None => {
+                self.positions.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            },
+        }
+    }
+
+    /// Append a value with the next integer key, returning that key.
+    pub fn push(&mut self, value: Value) -> Key {
+        let key = Key::Int(self.next);
+        self.insert(key.clone(), value);
+
+        key
+    }
+
+    pub fn remove(&mut self, key: &Key) -> Option<Value> {
+        let i = self.positions.remove(key)?;
+        let (_, value) = self.entries.remove(i);
+
+        for position in self.positions.values_mut() {
+            if *position > i {
+                *position -= 1;
+            }
+        }
+
+        Some(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &Value)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+}
+
+impl FromIterator<Value> for Array {
+    fn from_iter<T: IntoIterator<Item = Value>>(values: T) -> Self {
+        let mut array = Array::new();
+
+        for value in values {
+            array.push(value);
+        }
+
+        array
+    }
+}
+
+/// The numeric value of an operand, for arithmetic.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    pub fn to_f64(self) -> f64 {
+        match self {
+            Self::Int(i) => i as f64,
+            Self::Float(f) => f,
+        }
+    }
+}
+
+impl From<Number> for Value {
+    fn from(number: Number) -> Self {
+        match number {
+            Number::Int(i) => Value::Int(i),
+            Number::Float(f) => Value::Float(f),
+        }
+    }
+}
+
+impl Value {
+    /// The name of the value's type, as used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Bool(_) => "bool",
+            Self::Int(_) => "int",
+            Self::Float(_) => "float",
+            Self::String(_) => "string",
+            Self::Array(_) => "array",
+        }
+    }
+
+    pub fn to_bool(&self) -> bool {
+        match self {
+            Self::Null => false,
+            Self::Bool(b) => *b,
+            Self::Int(i) => *i != 0,
+            Self::Float(f) => *f != 0.0,
+            Self::String(s) => ! s.is_empty() && s != "0",
+            Self::Array(array) => ! array.is_empty(),
+        }
+    }
+
+    pub fn to_int(&self) -> i64 {
+        match self {
+            Self::Null => 0,
+            Self::Bool(b) => *b as i64,
+            Self::Int(i) => *i,
+            Self::Float(f) => float_to_int(*f),
+            Self::String(s) => match leading_number(s).unwrap_or(Number::Int(0)) {
+                Number::Int(i) => i,
+                Number::Float(f) => float_to_int(f),
+            },
+            Self::Array(array) => ! array.is_empty() as i64,
+        }
+    }
+
+    pub fn to_float(&self) -> f64 {
+        match self {
+            Self::Float(f) => *f,
+            Self::String(s) => leading_number(s).map_or(0.0, Number::to_f64),
+            other => other.to_int() as f64,
+        }
+    }
+
+    /// The value as a number, along with whether it was one. Strings are numeric if they're a
+    /// number, possibly surrounded by whitespace, and otherwise count as their leading number, or
+    /// zero if they don't start with one.
+    pub fn to_number(&self) -> (Number, bool) {
+        match self {
+            Self::Int(i) => (Number::Int(*i), true),
+            Self::Float(f) => (Number::Float(*f), true),
+            Self::String(s) => (leading_number(s).unwrap_or(Number::Int(0)), numeric(s).is_some()),
+            Self::Null | Self::Bool(_) => (Number::Int(self.to_int()), true),
+            Self::Array(_) => (Number::Int(self.to_int()), false),
+        }
+    }
+
+    /// The value as an array key, or `None` if it can't be one.
+    pub fn to_key(&self) -> Option<Key> {
+        match self {
+            Self::Null => Some(Key::String(String::new())),
+            Self::Bool(b) => Some(Key::Int(*b as i64)),
+            Self::Int(i) => Some(Key::Int(*i)),
+            Self::Float(f) => Some(Key::Int(float_to_int(*f))),
+            Self::String(s) => Some(Key::from(s.as_str())),
+            Self::Array(_) => None,
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        match self {
+            Self::Int(_) | Self::Float(_) => true,
+            Self::String(s) => numeric(s).is_some(),
+            _ => false,
+        }
+    }
+}
+
+/// Values are equal if they're identical, as with `===`.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        strict_equals(self, other)
+    }
+}
+
+impl From<Key> for Value {
+    fn from(key: Key) -> Self {
+        match key {
+            Key::Int(i) => Value::Int(i),
+            Key::String(s) => Value::String(s),
+        }
+    }
+}
+
+/// Converting to a string as `echo` does, where arrays are just `Array`.
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null | Self::Bool(false) => Ok(()),
+            Self::Bool(true) => write!(f, "1"),
+            Self::Int(i) => write!(f, "{}", i),
+            Self::Float(float) => write!(f, "{}", format_float(*float, 14)),
+            Self::String(s) => write!(f, "{}", s),
+            Self::Array(_) => write!(f, "Array"),
+        }
+    }
+}
+
+pub(crate) fn float_to_int(f: f64) -> i64 {
+    if f.is_finite() { f as i64 } else { 0 }
+}
+
+/// The number that a string holds, if all of it other than surrounding whitespace is a number.
+pub fn numeric(s: &str) -> Option<Number> {
+    let s = s.trim_matches(|c: char| c.is_ascii_whitespace());
+
+    // Rust also parses words such as `inf` and `NaN`, which PHP doesn't.
+    if s.is_empty() || ! s.chars().all(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | 'e' | 'E')) {
+        return None;
+    }
+
+    match s.parse::<i64>() {
+        Ok(i) => Some(Number::Int(i)),
+        Err(_) => s.parse::<f64>().ok().filter(|_| s.contains(|c: char| c.is_ascii_digit())).map(Number::Float),
+    }
+}
+
+/// The number that a string starts with, if it starts with one.
+pub(crate) fn leading_number(s: &str) -> Option<Number> {
+    if let Some(number) = numeric(s) {
+        return Some(number);
+    }
+
+    let trimmed = s.trim_start_matches(|c: char| c.is_ascii_whitespace());
+
+    // The longest prefix that's a number.
+    (1..=trimmed.len()).rev()
+        .filter(|&end| trimmed.is_char_boundary(end))
+        .find_map(|end| numeric(&trimmed[..end]).filter(|_| ! trimmed[..end].ends_with(char::is_whitespace)))
+}
+
+/// Format a float the way PHP does, with the given number of significant digits, or as few as
+/// it takes to read back the same float if that's zero. Large and small numbers use an exponent,
+/// as in `1.0E+25`.
+pub fn format_float(f: f64, precision: usize) -> String {
+    if f.is_nan() {
+        return "NAN".into();
+    }
+
+    if f.is_infinite() {
+        return if f > 0.0 { "INF".into() } else { "-INF".into() };
+    }
+
+    if f == 0.0 {
+        return if f.is_sign_negative() { "-0".into() } else { "0".into() };
+    }
+
+    let scientific = if precision == 0 { format!("{:e}", f) } else { format!("{:.*e}", precision - 1, f) };
+    let (mantissa, exponent) = scientific.split_once('e').expect("scientific notation has an exponent");
+    let exponent: i32 = exponent.parse().expect("the exponent is an integer");
+
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+
+    let digits = mantissa.replace('.', "");
+    let digits = digits.trim_end_matches('0');
+    let limit = if precision == 0 { 15 } else { precision as i32 };
+
+    if exponent < -4 || exponent >= limit {
+        let (first, rest) = digits.split_at(1);
+
+        format!("{}{}.{}E{}{}", sign, first, if rest.is_empty() { "0" } else { rest }, if exponent < 0 { '-' } else { '+' }, exponent.abs())
+    } else if exponent < 0 {
+        format!("{}0.{}{}", sign, "0".repeat((-exponent - 1) as usize), digits)
+    } else {
+        let point = exponent as usize + 1;
+
+        if digits.len() > point {
+            format!("{}{}.{}", sign, &digits[..point], &digits[point..])
+        } else {
+            format!("{}{}{}", sign, digits, "0".repeat(point - digits.len()))
+        }
+    }
+}
+
+/// `==`, which converts between types.
+pub fn loose_equals(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Null, Value::Null) => true,
+        (Value::Bool(_), _) | (_, Value::Bool(_)) => a.to_bool() == b.to_bool(),
+        (Value::Null, Value::String(s)) | (Value::String(s), Value::Null) => s.is_empty(),
+        (Value::Null, _) | (_, Value::Null) => a.to_bool() == b.to_bool(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().all(|(key, value)| b.get(key).is_some_and(|other| loose_equals(value, other)))
+        },
+        (Value::Array(_), _) | (_, Value::Array(_)) => false,
+        _ => compare(a, b) == Some(Ordering::Equal),
+    }
+}
+
+/// `===`, which needs the same type as well as the same value.
+pub fn strict_equals(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Null, Value::Null) => true,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Int(a), Value::Int(b)) => a == b,
+        (Value::Float(a), Value::Float(b)) => a == b,
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|((ak, av), (bk, bv))| ak == bk && strict_equals(av, bv))
+        },
+        _ => false,
+    }
+}
+
+/// The order of two values for `<`, `>` and `<=>`, or `None` if they can't be ordered, as with
+/// `NAN`.
+pub fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::String(x), Value::String(y)) => match (numeric(x), numeric(y)) {
+            (Some(x), Some(y)) => compare_numbers(x, y),
+            _ => Some(x.cmp(y)),
+        },
+        (Value::Bool(_) | Value::Null, _) | (_, Value::Bool(_) | Value::Null) => match (a, b) {
+            (Value::Null, Value::String(s)) => Some(if s.is_empty() { Ordering::Equal } else { Ordering::Less }),
+            (Value::String(s), Value::Null) => Some(if s.is_empty() { Ordering::Equal } else { Ordering::Greater }),
+            _ => Some(a.to_bool().cmp(&b.to_bool())),
+        },
+        (Value::Array(x), Value::Array(y)) => {
+            if x.len() != y.len() {
+                return Some(x.len().cmp(&y.len()));
+            }
+
+            for (key, value) in x.iter() {
+                match compare(value, y.get(key)?)? {
+                    Ordering::Equal => continue,
+                    ordering => return Some(ordering),
+                }
+            }
+
+            Some(Ordering::Equal)
+        },
+        (Value::Array(_), _) => Some(Ordering::Greater),
+        (_, Value::Array(_)) => Some(Ordering::Less),
+        // A number and a string compare as numbers if the string is numeric, and as strings if not.
+        (Value::String(s), number) | (number, Value::String(s)) => {
+            let ordering = match numeric(s) {
+                Some(n) => compare_numbers(n, number.to_number().0),
+                None => Some(s.as_str().cmp(number.to_string().as_str())),
+            }?;
+
+            Some(if matches!(a, Value::String(_)) { ordering } else { ordering.reverse() })
+        },
+        _ => compare_numbers(a.to_number().0, b.to_number().0),
+    }
+}
+
+fn compare_numbers(a: Number, b: Number) -> Option<Ordering> {
+    match (a, b) {
+        (Number::Int(a), Number::Int(b)) => Some(a.cmp(&b)),
+        (a, b) => a.to_f64().partial_cmp(&b.to_f64()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::{compare, format_float, loose_equals, strict_equals, Array, Key, Value};
+
+    fn s(s: &str) -> Value {
+        Value::String(s.into())
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(format_float(0.1 + 0.2, 14), "0.3");
+        assert_eq!(format_float(0.1 + 0.2, 0), "0.30000000000000004");
+        assert_eq!(format_float(2.0, 14), "2");
+        assert_eq!(format_float(-1.5, 14), "-1.5");
+        assert_eq!(format_float(1e15, 14), "1.0E+15");
+        assert_eq!(format_float(1e14, 0), "100000000000000");
+        assert_eq!(format_float(1e15, 0), "1.0E+15");
+        assert_eq!(format_float(123456.0, 0), "123456");
+        assert_eq!(format_float(0.00001, 14), "1.0E-5");
+        assert_eq!(format_float(0.0001, 14), "0.0001");
+        assert_eq!(format_float(f64::INFINITY, 14), "INF");
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(s("12abc").to_int(), 12);
+        assert_eq!(s(" 1.5 ").to_float(), 1.5);
+        assert_eq!(s("abc").to_int(), 0);
+        assert!(! s("0").to_bool());
+        assert!(s("0.0").to_bool());
+        assert_eq!(s("12").to_key(), Some(Key::Int(12)));
+        assert_eq!(s("012").to_key(), Some(Key::String("012".into())));
+        assert_eq!(Value::Float(1.9).to_key(), Some(Key::Int(1)));
+        assert!(s("1e3").is_numeric());
+        assert!(! s("inf").is_numeric());
+    }
+
+    #[test]
+    fn comparisons() {
+        assert!(loose_equals(&Value::Int(1), &s("1.0")));
+        assert!(loose_equals(&Value::Null, &Value::Bool(false)));
+        assert!(loose_equals(&Value::Null, &Value::Array(Array::new())));
+        assert!(! loose_equals(&Value::Int(0), &s("a")));
+        assert!(loose_equals(&s("abc"), &Value::Bool(true)));
+        assert!(! strict_equals(&Value::Int(1), &Value::Float(1.0)));
+
+        assert_eq!(compare(&s("10"), &s("9")), Some(Ordering::Greater));
+        assert_eq!(compare(&s("10"), &s("9a")), Some(Ordering::Less));
+        assert_eq!(compare(&Value::Int(5), &s("abc")), Some(Ordering::Less));
+        assert_eq!(compare(&Value::Float(f64::NAN), &Value::Int(1)), None);
+
+        let array: Array = [Value::Int(1), Value::Int(2)].into_iter().collect();
+        assert!(loose_equals(&Value::Array(array.clone()), &Value::Array(array)));
+    }
+
+    #[test]
+    fn arrays() {
+        let mut array = Array::new();
+        array.insert(Key::Int(5), Value::Int(1));
+        array.insert(Key::String("a".into()), Value::Int(2));
+        assert_eq!(array.push(Value::Int(3)), Key::Int(6));
+
+        array.insert(Key::Int(5), Value::Int(4));
+        array.remove(&Key::String("a".into()));
+
+        let keys: Vec<String> = array.iter().map(|(key, _)| key.to_string()).collect();
+        assert_eq!(keys, ["5", "6"]);
+        assert!(matches!(array.get(&Key::Int(5)), Some(Value::Int(4))));
+    }
+}
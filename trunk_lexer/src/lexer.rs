@@ -1,9 +1,12 @@
-use crate::{Token, TokenKind, OpenTagKind};
+use crate::{Float, Token, TokenKind, OpenTagKind, Span};
 
 #[derive(Debug)]
 pub enum LexerState {
     Initial,
     Scripting,
+    DoubleQuote,
+    Heredoc,
+    HaltCompiler,
 }
 
 #[allow(dead_code)]
@@ -15,38 +18,107 @@ pub struct LexerConfig {
 #[allow(dead_code)]
 pub struct Lexer {
     config: LexerConfig,
-    state: LexerState,
+    state: Vec<LexerState>,
     chars: Vec<char>,
     cursor: usize,
     current: Option<char>,
     peek: Option<char>,
-    col: usize,
-    line: usize,
+    warnings: Vec<LexerWarning>,
 }
 
 impl Lexer {
     pub fn new(config: Option<LexerConfig>) -> Self {
         Self {
             config: config.unwrap_or_default(),
-            state: LexerState::Initial,
+            state: vec![LexerState::Initial],
             chars: "".chars().collect(),
             cursor: 0,
             current: None,
             peek: None,
-            line: 1,
-            col: 0,
+            warnings: Vec::new(),
         }
     }
 
     pub fn tokenize(&mut self, input: &str) -> Result<Vec<Token>, LexerError> {
-        let mut tokens = Vec::new();
         self.chars = input.chars().collect();
+        self.warnings.clear();
 
         self.next();
         self.next();
 
-        while self.peek.is_some() {
-            match self.state {
+        let mut tokens = self.lex().map_err(|kind| self.error(kind))?;
+        let mut warnings = std::mem::take(&mut self.warnings);
+
+        self.locate(tokens.iter_mut().map(|token| &mut token.span).chain(warnings.iter_mut().map(LexerWarning::span_mut)));
+        self.warnings = warnings;
+
+        Ok(tokens)
+    }
+
+    /// Attach the current position, and an excerpt of the line that it's on, to an error.
+    fn error(&self, kind: LexerErrorKind) -> LexerError {
+        let offset = self.offset();
+
+        // Depending on where it was found, an unexpected character is either at the cursor or on
+        // either side of it.
+        let offset = match kind {
+            LexerErrorKind::UnexpectedEndOfFile => self.chars.len(),
+            LexerErrorKind::UnexpectedCharacter(c) => [offset, offset.saturating_sub(1), offset + 1].into_iter()
+                .find(|i| self.chars.get(*i) == Some(&c))
+                .unwrap_or(offset),
+            LexerErrorKind::InvalidNumber(_) | LexerErrorKind::InvalidEscape(_) | LexerErrorKind::InvalidBodyIndentation(_) => offset,
+        }.min(self.chars.len());
+
+        let mut span = Span { start: offset, end: (offset + 1).min(self.chars.len()), line: 0, col: 0 };
+        self.locate(std::iter::once(&mut span));
+
+        let line_start = self.chars[..offset].iter().rposition(|c| *c == '\n').map_or(0, |i| i + 1);
+        let line_end = self.chars[offset..].iter().position(|c| *c == '\n').map_or(self.chars.len(), |i| offset + i);
+        let excerpt: String = self.chars[line_start.max(offset.saturating_sub(EXCERPT_CONTEXT))..line_end.min(offset + EXCERPT_CONTEXT)].iter().collect();
+
+        LexerError { kind, span, excerpt: excerpt.trim().to_string() }
+    }
+
+    /// Problems with the source from the most recent call to `tokenize` that didn't stop it from
+    /// being lexed.
+    pub fn warnings(&self) -> &[LexerWarning] {
+        &self.warnings
+    }
+
+    /// Spans are tracked as character indices while lexing. Once lexing has finished they're
+    /// converted to byte offsets, along with the line and column that each token starts on.
+    fn locate<'a>(&self, spans: impl Iterator<Item = &'a mut Span>) {
+        let mut positions = Vec::with_capacity(self.chars.len() + 1);
+        let (mut offset, mut line, mut col) = (0, 1, 1);
+
+        for c in &self.chars {
+            positions.push((offset, line, col));
+
+            offset += c.len_utf8();
+
+            if *c == '\n' {
+                line += 1;
+                col = 1;
+            } else {
+                col += 1;
+            }
+        }
+
+        positions.push((offset, line, col));
+
+        for span in spans {
+            let (start, line, col) = positions[span.start.min(self.chars.len())];
+            let (end, ..) = positions[span.end.min(self.chars.len())];
+
+            *span = Span { start, end: end.max(start), line, col };
+        }
+    }
+
+    fn lex(&mut self) -> Result<Vec<Token>, LexerErrorKind> {
+        let mut tokens = Vec::new();
+
+        while self.peek.is_some() || (matches!(self.state.last(), Some(LexerState::Initial)) && self.current.is_some()) {
+            match self.state.last().unwrap() {
                 // The "Initial" state is used to parse inline HTML. It is essentially a catch-all
                 // state that will build up a single token buffer until it encounters an open tag
                 // of some description.
@@ -56,88 +128,80 @@ impl Lexer {
                 // The scripting state is entered when an open tag is encountered in the source code.
                 // This tells the lexer to start analysing characters at PHP tokens instead of inline HTML.
                 LexerState::Scripting => {
-                    while let Some(c) = self.peek {
-                        if ! c.is_whitespace() && ! ['\n', '\t', '\r'].contains(&c) {
-                            break;
-                        }
-                
-                        if c == '\n' {
-                            self.line += 1;
-                            self.col = 0;
-                        } else {
-                            self.col += 1;
-                        }
-
-                        self.next();
-                    }
+                    self.skip_whitespace();
 
                     // If we have consumed whitespace and then reached the end of the file, we should break.
                     if self.peek.is_none() {
                         break;
                     }
 
+                    // Heredocs and nowdocs produce more than one token, so they're handled separately.
+                    if self.try_read("<<<") {
+                        tokens.append(&mut self.heredoc()?);
+                        continue;
+                    }
+
                     tokens.push(self.scripting()?);
                 },
+                // The double quote state is entered when a double quoted string contains interpolation.
+                // The string is broken up into literal parts and the tokens that make up each embedded expression.
+                LexerState::DoubleQuote | LexerState::Heredoc => {
+                    tokens.append(&mut self.double_quote()?);
+                },
+                // Everything after `__halt_compiler();` is raw data that shouldn't be lexed.
+                LexerState::HaltCompiler => {
+                    tokens.append(&mut self.halt_compiler()?);
+                },
             }
         }
 
         Ok(tokens)
     }
 
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek {
+            if ! c.is_whitespace() && ! ['\n', '\t', '\r'].contains(&c) {
+                break;
+            }
+
+            self.next();
+        }
+    }
+
     #[allow(dead_code)]
-    fn initial(&mut self) -> Result<Vec<Token>, LexerError> {
+    fn initial(&mut self) -> Result<Vec<Token>, LexerErrorKind> {
+        let start = self.offset();
         let mut buffer = String::new();
         while let Some(char) = self.current {
             match char {
                 '<' => {
-                    // This is disgusting and can most definitely be tidied up with a multi-peek iterator.
-                    if let Some('?') = self.peek {
-                        self.next();
-
-                        if let Some('p') = self.peek {
-                            self.next();
+                    if self.try_read("?php") {
+                        let tag = self.offset();
 
-                            if let Some('h') = self.peek {
-                                self.next();
-
-                                if let Some('p') = self.peek {
-                                    self.next();
-
-                                    self.col += 4;
+                        self.skip(4);
 
-                                    self.enter_state(LexerState::Scripting);
+                        self.enter_state(LexerState::Scripting);
 
-                                    let mut tokens = vec!();
+                        let mut tokens = vec!();
 
-                                    if !buffer.is_empty() {
-                                        tokens.push(Token {
-                                            kind: TokenKind::InlineHtml(buffer),
-                                            span: (self.line, self.col.saturating_sub(5)),
-                                        });
-                                    }
-                                    
-                                    tokens.push(Token {
-                                        kind: TokenKind::OpenTag(OpenTagKind::Full),
-                                        span: (self.line, self.col)
-                                    });
+                        if !buffer.is_empty() {
+                            tokens.push(Token {
+                                kind: TokenKind::InlineHtml(buffer),
+                                span: self.span_between(start, tag),
+                            });
+                        }
 
-                                    return Ok(tokens);
-                                }
-                            } else {
-                                self.col += 3;
+                        tokens.push(Token {
+                            kind: TokenKind::OpenTag(OpenTagKind::Full),
+                            span: self.span(tag)
+                        });
 
-                                buffer.push('h');
-                            }
-                        } else {
-                            self.col += 2;
+                        return Ok(tokens);
+                    }
 
-                            buffer.push('?');
-                        }
-                    } else {
-                        self.col += 1;
+                    self.next();
 
-                        buffer.push(char);
-                    }
+                    buffer.push(char);
                 },
                 _ => {
                     self.next();
@@ -149,18 +213,20 @@ impl Lexer {
         Ok(vec![
             Token {
                 kind: TokenKind::InlineHtml(buffer),
-                span: (self.line, self.col)
+                span: self.span(start)
             }
         ])
     }
 
-    fn scripting(&mut self) -> Result<Token, LexerError> {
+    fn scripting(&mut self) -> Result<Token, LexerErrorKind> {
         // We should never reach this point since we have the empty checks surrounding
         // the call to this function, but it's better to be safe than sorry.
         if self.peek.is_none() {
-            return Err(LexerError::UnexpectedEndOfFile);
+            return Err(LexerErrorKind::UnexpectedEndOfFile);
         }
 
+        let start = self.offset();
+
         // Since we have the check above, we can safely unwrap the result of `.next()`
         // to help reduce the amount of indentation.
         self.next();
@@ -168,16 +234,10 @@ impl Lexer {
 
         let kind = match char {
             '!' => {
-                self.col += 1;
-
                 if let Some('=') = self.peek {
-                    self.col += 1;
-
                     self.next();
 
                     if let Some('=') = self.peek {
-                        self.col += 1;
-
                         self.next();
 
                         TokenKind::BangDoubleEquals
@@ -189,14 +249,14 @@ impl Lexer {
                 }
             },
             '&' => {
-                self.col += 1;
-
                 if let Some('&') = self.peek {
-                    self.col += 1;
-
                     self.next();
 
                     TokenKind::BooleanAnd
+                } else if let Some('=') = self.peek {
+                    self.next();
+
+                    TokenKind::AndEqual
                 } else {
                     TokenKind::Ampersand
                 }
@@ -204,21 +264,20 @@ impl Lexer {
             '?' => {
                 // This is a close tag, we can enter "Initial" mode again.
                 if let Some('>') = self.peek {
-                    self.next();
+                    self.skip(2);
 
-                    self.col += 2;
+                    // A single newline directly after the close tag is swallowed by PHP.
+                    if let Some('\n') = self.current {
+                        self.next();
+                    }
 
                     self.enter_state(LexerState::Initial);
 
                     TokenKind::CloseTag
                 } else if let Some('?') = self.peek {
-                    self.col += 1;
-
                     self.next();
 
                     if let Some('=') = self.peek {
-                        self.col += 1;
-
                         self.next();
 
                         TokenKind::CoalesceEqual
@@ -226,12 +285,10 @@ impl Lexer {
                         TokenKind::Coalesce
                     }
                 } else if let Some(':') = self.peek {
-                    self.col += 1;
                     self.next();
                     TokenKind::QuestionColon
                 } else if self.try_read("->") {
-                    self.col += 1;
-                    self.skip(3);
+                    self.skip(2);
                     TokenKind::NullsafeArrow
                 } else {
                     TokenKind::Question
@@ -244,28 +301,19 @@ impl Lexer {
                     if let Some('=') = self.peek {
                         self.next();
 
-                        self.col += 3;
-
                         TokenKind::TripleEquals
                     } else {
-                        self.col += 2;
-
                         TokenKind::DoubleEquals
                     }
                 } else if let Some('>') = self.peek {
                     self.next();
-                    self.col += 1;
                     TokenKind::DoubleArrow
                 } else {
-                    self.col += 1;
-
                     TokenKind::Equals
                 }
             },
             // Single quoted string.
             '\'' => {
-                self.col += 1;
-
                 let mut buffer = String::new();
                 let mut escaping = false;
 
@@ -289,13 +337,6 @@ impl Lexer {
                         continue;
                     }
 
-                    if n == '\n' {
-                        self.line += 1;
-                        self.col = 0;
-                    } else {
-                        self.col += 1;
-                    }
-
                     escaping = false;
 
                     buffer.push(n);
@@ -304,74 +345,39 @@ impl Lexer {
 
                 TokenKind::ConstantString(buffer)
             },
-            '"' => {
-                self.col += 1;
-
-                let mut buffer = String::new();
-                let mut escaping = false;
-
-                while let Some(n) = self.peek {
-                    if ! escaping && n == '"' {
-                        self.next();
-
-                        break;
-                    }
-
-                    if n == '\\' && !escaping {
-                        escaping = true;
-                        self.next();
-                        continue;
-                    }
-
-                    if escaping && ['\\', '"'].contains(&n) {
-                        escaping = false;
-                        buffer.push(n);
-                        self.next();
-                        continue;
-                    }
-
-                    if n == '\n' {
-                        self.line += 1;
-                        self.col = 0;
-                    } else {
-                        self.col += 1;
-                    }
-
-                    escaping = false;
-
-                    buffer.push(n);
-                    self.next();
-                }
+            '"' if self.has_interpolation() => {
+                self.push_state(LexerState::DoubleQuote);
 
-                TokenKind::ConstantString(buffer)
+                TokenKind::DoubleQuote
             },
-            '$' => {
+            '"' => {
                 let mut buffer = String::new();
 
-                self.col += 1;
-
                 while let Some(n) = self.peek {
                     match n {
-                        '0'..='9' if buffer.len() > 1 => {
-                            self.col += 1;
-                            buffer.push(n);
+                        '"' => {
                             self.next();
-                        },
-                        'a'..='z' | 'A'..='Z' | '\u{80}'..='\u{ff}' | '_'  => {
-                            self.col += 1;
 
+                            break;
+                        },
+                        '\\' => self.escape_sequence(Some('"'), &mut buffer)?,
+                        _ => {
                             buffer.push(n);
                             self.next();
-                        }
-                        _ => break,
+                        },
                     }
                 }
 
-                TokenKind::Variable(buffer)
+                TokenKind::ConstantString(buffer)
+            },
+            '$' => {
+                // Names can't start with a digit, but can have them anywhere after that.
+                match self.peek {
+                    Some(n) if is_label_start(&n) => TokenKind::Variable(self.label()),
+                    _ => TokenKind::Variable(String::new()),
+                }
             },
             '.' => {
-                self.col += 1;
-
                 if let Some('0'..='9') = self.peek {
                     let mut buffer = String::from("0.");
                     let mut underscore = false;
@@ -382,41 +388,27 @@ impl Lexer {
                                 underscore = false;
                                 buffer.push(n);
                                 self.next();
-    
-                                self.col += 1;
                             },
                             '_' => {
                                 if underscore {
-                                    return Err(LexerError::UnexpectedCharacter(n));
+                                    return Err(LexerErrorKind::UnexpectedCharacter(n));
                                 }
     
                                 underscore = true;
                                 self.next();
-    
-                                self.col += 1;
                             },
                             _ => break,
                         }
                     }
 
-                    TokenKind::Float(buffer.parse().unwrap())
-                } else if let Some('.') = self.peek {
+                    TokenKind::Float(Float(buffer.parse().map_err(|_| LexerErrorKind::InvalidNumber(buffer.clone()))?))
+                } else if self.peek == Some('.') && self.char_at(self.cursor) == Some(&'.') {
+                    self.next();
                     self.next();
 
-                    self.col += 1;
-
-                    if let Some('.') = self.peek {
-                        self.next();
-
-                        self.col += 1;
-
-                        TokenKind::Ellipsis
-                    } else {
-                        todo!("don't know how to handle this case yet, it should just be 2 Dot tokens...")
-                    }
+                    TokenKind::Ellipsis
                 } else if let Some('=') = self.peek {
                     self.next();
-                    self.col += 1;
                     TokenKind::DotEquals
                 } else {
                     TokenKind::Dot
@@ -427,64 +419,61 @@ impl Lexer {
                 let mut underscore = false;
                 let mut is_float = false;
 
-                self.col += 1;
-
                 while let Some(n) = self.peek {
                     match n {
                         '0'..='9' => {
                             underscore = false;
                             buffer.push(n);
                             self.next();
-
-                            self.col += 1;
                         },
                         '.' => {
                             if is_float {
-                                return Err(LexerError::UnexpectedCharacter(n));
+                                return Err(LexerErrorKind::UnexpectedCharacter(n));
                             }
 
                             is_float = true;
                             buffer.push(n);
                             self.next();
-                            self.col += 1;
                         },
                         '_' => {
                             if underscore {
-                                return Err(LexerError::UnexpectedCharacter(n));
+                                return Err(LexerErrorKind::UnexpectedCharacter(n));
                             }
 
                             underscore = true;
                             self.next();
-
-                            self.col += 1;
                         },
                         _ => break,
                     }
                 }
 
                 if is_float {
-                    TokenKind::Float(buffer.parse().unwrap())
+                    TokenKind::Float(Float(buffer.parse().map_err(|_| LexerErrorKind::InvalidNumber(buffer.clone()))?))
+                } else if let Ok(i) = buffer.parse() {
+                    TokenKind::Int(i)
                 } else {
-                    TokenKind::Int(buffer.parse().unwrap())
+                    // Like PHP, integers that are too large for an int become floats.
+                    self.warnings.push(LexerWarning::IntegerOverflow(buffer.clone(), self.span(start)));
+
+                    TokenKind::Float(Float(buffer.parse().map_err(|_| LexerErrorKind::InvalidNumber(buffer.clone()))?))
                 }
             },
             '\\' => {
-                self.col += 1;
+                if matches!(self.peek, Some(n) if is_label_start(&n)) {
+                    let name_start = self.cursor - 1;
 
-                if let Some(n) = self.peek && (n.is_alphabetic() || n == '_') {
                     match self.scripting()? {
                         Token { kind: TokenKind::Identifier(i) | TokenKind::QualifiedIdentifier(i), .. } => {
                             TokenKind::FullyQualifiedIdentifier(format!("\\{}", i))
                         },
-                        s => unreachable!("{:?}", s)
+                        // Keywords are valid names once they're qualified, such as `\true`.
+                        _ => TokenKind::FullyQualifiedIdentifier(format!("\\{}", self.chars[name_start..self.cursor - 1].iter().collect::<String>())),
                     }
                 } else {
                     TokenKind::NamespaceSeparator
                 }
             },
             _ if char.is_alphabetic() || char == '_' => {
-                self.col += 1;
-
                 let mut qualified = false;
                 let mut last_was_slash = false;
 
@@ -493,7 +482,6 @@ impl Lexer {
                     if next.is_alphanumeric() || next == '_' {
                         buffer.push(next);
                         self.next();
-                        self.col += 1;
                         last_was_slash = false;
                         continue;
                     }
@@ -503,7 +491,6 @@ impl Lexer {
                         last_was_slash = true;
                         buffer.push(next);
                         self.next();
-                        self.col += 1;
                         continue;
                     }
 
@@ -513,15 +500,27 @@ impl Lexer {
                 if qualified {
                     TokenKind::QualifiedIdentifier(buffer)
                 } else {
-                    identifier_to_keyword(&buffer).unwrap_or(TokenKind::Identifier(buffer))
+                    let kind = identifier_to_keyword(&buffer).unwrap_or(TokenKind::Identifier(buffer));
+
+                    // Asymmetric visibility modifiers such as `private(set)` are lexed as a single token.
+                    if matches!(kind, TokenKind::Public | TokenKind::Protected | TokenKind::Private) && self.try_read("(set)") {
+                        self.skip(5);
+
+                        match kind {
+                            TokenKind::Public => TokenKind::PublicSet,
+                            TokenKind::Protected => TokenKind::ProtectedSet,
+                            _ => TokenKind::PrivateSet,
+                        }
+                    } else if kind == TokenKind::Readonly && self.peek == Some('(') {
+                        // `readonly` can still be the name of a function that's being called.
+                        TokenKind::Identifier("readonly".into())
+                    } else {
+                        kind
+                    }
                 }
             },
             '/' | '#' => {
-                self.col += 1;
-
                 fn read_till_end_of_line(s: &mut Lexer) -> String {
-                    s.col += 1;
-
                     let mut buffer = String::new();
 
                     while let Some(c) = s.peek {
@@ -536,7 +535,7 @@ impl Lexer {
                     buffer
                 }
 
-                if char == '/' && let Some(t) = self.peek && t == '*' {
+                if char == '/' && self.peek == Some('*') {
                     let mut buffer = String::from(char);
 
                     while self.peek.is_some() {
@@ -547,24 +546,14 @@ impl Lexer {
                         match t {
                             '*' => {                     
                                 if let Some('/') = self.peek {
-                                    self.col += 2;
                                     buffer.push_str("*/");
                                     self.next();
                                     break;
                                 } else {
-                                    self.col += 1;
                                     buffer.push(t);
                                 }
                             },
-                            '\n' => {
-                                self.line += 1;
-                                self.col = 0;
-
-                                buffer.push('\n');
-                            },
                             _ => {
-                                self.col += 1;
-
                                 buffer.push(t);
                             }
                         }
@@ -576,29 +565,38 @@ impl Lexer {
                         TokenKind::Comment(buffer)
                     }
                 } else if let Some('=') = self.peek {
-                    self.col += 1;
                     self.next();
                     TokenKind::SlashEquals
-                } else if char == '/' && let Some(t) = self.peek && t != '/' {
+                } else if char == '/' && self.peek != Some('/') {
                     TokenKind::Slash
-                } else if char == '#' && let Some('[') = self.peek {
+                } else if char == '#' && self.peek == Some('[') {
+                    self.next();
                     TokenKind::Attribute
                 } else {
-                    self.next();
-                    let buffer = format!("{}{}{}", char, &self.current.unwrap(), read_till_end_of_line(self));
+                    let mut buffer = String::from(char);
+
+                    // `//` comments start with two characters, while `#` only needs one.
+                    if char == '/' {
+                        self.next();
+                        buffer.push('/');
+                    }
+
+                    buffer.push_str(&read_till_end_of_line(self));
 
                     TokenKind::Comment(buffer)
                 }
             },
             '*' => {
-                self.col += 1;
-
                 if let Some('*') = self.peek {
-                    self.col += 1;
                     self.next();
-                    TokenKind::Pow
+
+                    if let Some('=') = self.peek {
+                        self.next();
+                        TokenKind::PowEquals
+                    } else {
+                        TokenKind::Pow
+                    }
                 } else if let Some('=') = self.peek {
-                    self.col += 1;
                     self.next();
                     TokenKind::AsteriskEqual
                 } else {
@@ -606,75 +604,71 @@ impl Lexer {
                 }
             },
             '|' => {
-                self.col += 1;
-                
                 if let Some('|') = self.peek {
-                    self.col += 1;
-
                     self.next();
 
                     TokenKind::BooleanOr
+                } else if let Some('=') = self.peek {
+                    self.next();
+
+                    TokenKind::PipeEquals
                 } else {
                     TokenKind::Pipe
                 }
             },
+            '%' => {
+                if let Some('=') = self.peek {
+                    self.next();
+
+                    TokenKind::PercentEquals
+                } else {
+                    TokenKind::Percent
+                }
+            },
+            '^' => {
+                if let Some('=') = self.peek {
+                    self.next();
+
+                    TokenKind::CaretEquals
+                } else {
+                    TokenKind::Caret
+                }
+            },
+            '~' => {
+                TokenKind::Tilde
+            },
+            '@' => {
+                TokenKind::At
+            },
             '{' => {
-                self.col += 1;
+                self.push_state(LexerState::Scripting);
+
                 TokenKind::LeftBrace
             },
             '}' => {
-                self.col += 1;
+                // The closing brace of a `{$...}` interpolation returns us to the string.
+                self.pop_state();
+
                 TokenKind::RightBrace
             },
             '(' => {
-                self.col += 1;
-
-                if self.try_read("string)") {
-                    self.col += 7;
-                    self.skip(8);
-                    
-                    TokenKind::StringCast 
-                } else if self.try_read("object)") {
-                    self.col += 7;
-                    self.skip(8);
-
-                    TokenKind::ObjectCast
-                } else if self.try_read("bool)") {
-                    self.col += 5;
-                    self.skip(6);
-                    TokenKind::BoolCast
-                } else if self.try_read("int)") {
-                    self.col += 4;
-                    self.skip(5);
-                    TokenKind::IntCast
-                } else if self.try_read("float)") {
-                    self.col += 6;
-                    self.skip(7);
-                    TokenKind::DoubleCast
-                } else {
-                    TokenKind::LeftParen
+                match self.cast() {
+                    Some(kind) => kind,
+                    None => TokenKind::LeftParen,
                 }
             },
             ')' => {
-                self.col += 1;
                 TokenKind::RightParen
             },
             ';' => {
-                self.col += 1;
                 TokenKind::SemiColon
             },
             '+' => {
-                self.col += 1;
-
                 if let Some('=') = self.peek {
-                    self.col += 1;
-
                     self.next();
                     
                     TokenKind::PlusEquals
                 } else if let Some('+') = self.peek {
-                    self.col += 1;
-
                     self.next();
 
                     TokenKind::Increment
@@ -683,137 +677,733 @@ impl Lexer {
                 }
             },
             '-' => {
-                self.col += 1;
-                
                 if let Some('>') = self.peek {
-                    self.col += 1;
-
                     self.next();
 
                     TokenKind::Arrow
                 } else if let Some('=') = self.peek {
-                    self.col += 1;
                     self.next();
                     TokenKind::MinusEquals
+                } else if let Some('-') = self.peek {
+                    self.next();
+                    TokenKind::Decrement
                 } else {
                     TokenKind::Minus
                 }
             },
             '<' => {
-                self.col += 1;
-
                 if let Some('=') = self.peek {
                     self.next();
 
-                    self.col += 1;
+                    if let Some('>') = self.peek {
+                        self.next();
 
-                    TokenKind::LessThanEquals
+                        TokenKind::Spaceship
+                    } else {
+                        TokenKind::LessThanEquals
+                    }
                 } else if let Some('<') = self.peek {
                     self.next();
 
-                    if let Some('<') = self.peek {
-                        // TODO: Handle both heredocs and nowdocs.
+                    if let Some('=') = self.peek {
                         self.next();
 
-                        todo!("heredocs & nowdocs");
+                        TokenKind::LeftShiftEquals
                     } else {
                         TokenKind::LeftShift
-                    }                 
+                    }
+                } else if let Some('>') = self.peek {
+                    self.next();
+
+                    TokenKind::LessThanGreaterThan
                 } else {
                     TokenKind::LessThan
                 }
             },
             '>' => {
-                self.col += 1;
-
                 if let Some('=') = self.peek {
                     self.next();
 
-                    self.col += 1;
-
                     TokenKind::GreaterThanEquals
+                } else if let Some('>') = self.peek {
+                    self.next();
+
+                    if let Some('=') = self.peek {
+                        self.next();
+
+                        TokenKind::RightShiftEquals
+                    } else {
+                        TokenKind::RightShift
+                    }
                 } else {
                     TokenKind::GreaterThan
                 }
             },
             ',' => {
-                self.col += 1;
                 TokenKind::Comma
             },
             '[' => {
-                self.col += 1;
                 TokenKind::LeftBracket
             },
             ']' => {
-                self.col += 1;
                 TokenKind::RightBracket
             },
             ':' => {
-                self.col += 1;
-
                 if let Some(':') = self.peek {
-                    self.col += 1;
-                    
                     self.next();
                     TokenKind::DoubleColon
                 } else {
                     TokenKind::Colon
                 }
             },
-            _ => unimplemented!("<scripting> char: {}, line: {}, col: {}", char, self.line, self.col),
+            _ => return Err(LexerErrorKind::UnexpectedCharacter(char)),
         };
 
+        if kind == TokenKind::HaltCompiler {
+            self.enter_state(LexerState::HaltCompiler);
+        }
+
         Ok(Token {
             kind,
-            span: (self.line, self.col)
+            span: self.span(start)
         })
     }
 
-    fn enter_state(&mut self, state: LexerState) {
-        self.state = state;
-    }
+    fn halt_compiler(&mut self) -> Result<Vec<Token>, LexerErrorKind> {
+        let mut tokens = Vec::new();
 
-    fn char_at(&self, idx: usize) -> Option<&char> {
-        self.chars.get(idx)
-    }
+        for expected in ['(', ')', ';'] {
+            self.skip_whitespace();
 
-    fn try_read(&self, search: &'static str) -> bool {
-        if self.current.is_none() || self.peek.is_none() {
-            return false;
+            match self.peek {
+                // A close tag can be used in place of the semi colon.
+                Some('?') if expected == ';' && self.try_read("?>") => {},
+                Some(c) if c == expected => {},
+                Some(c) => return Err(LexerErrorKind::UnexpectedCharacter(c)),
+                None => return Err(LexerErrorKind::UnexpectedEndOfFile),
+            }
+
+            tokens.push(self.scripting()?);
         }
 
-        let start = self.cursor.saturating_sub(1);
-        let mut buffer = String::new();
+        let start = self.offset();
 
-        for i in 0..search.len() {
-            match self.char_at(start + i) {
-                Some(char) => buffer.push(*char),
-                _ => return false,
-            };
-        }
+        let offset = self.chars[..start].iter().map(|c| c.len_utf8()).sum();
+        let data = self.chars[start..].iter().collect();
 
-        buffer.as_str() == search
+        tokens.push(Token { kind: TokenKind::HaltCompilerData(offset, data), span: self.span_between(start, self.chars.len()) });
+
+        self.cursor = self.chars.len();
+        self.current = None;
+        self.peek = None;
+
+        Ok(tokens)
     }
 
-    fn skip(&mut self, count: usize) {
-        for _ in 0..count {
-            self.next();
-        }
+    fn enter_state(&mut self, state: LexerState) {
+        *self.state.last_mut().unwrap() = state;
     }
 
-    fn next(&mut self) {
-        self.current = self.peek;
-        self.peek = self.chars.get(self.cursor).cloned();
-        self.cursor += 1;
+    fn push_state(&mut self, state: LexerState) {
+        self.state.push(state);
     }
-}
 
-#[allow(dead_code)]
-fn identifier_to_keyword(ident: &str) -> Option<TokenKind> {
-    Some(match ident {
-        "match" => TokenKind::Match,
-        "abstract" => TokenKind::Abstract,
-        "array" => TokenKind::Array,
+    fn pop_state(&mut self) {
+        if self.state.len() > 1 {
+            self.state.pop();
+        }
+    }
+
+    fn double_quote(&mut self) -> Result<Vec<Token>, LexerErrorKind> {
+        let mut buffer = String::new();
+        let mut tokens = Vec::new();
+
+        let heredoc = matches!(self.state.last(), Some(LexerState::Heredoc));
+        let mut part = self.offset();
+
+        loop {
+            if buffer.is_empty() {
+                part = self.offset();
+            }
+
+            let start = self.offset();
+
+            match self.peek {
+                Some('"') if ! heredoc => {
+                    self.string_part(part, &mut buffer, &mut tokens);
+
+                    self.next();
+
+                    tokens.push(Token { kind: TokenKind::DoubleQuote, span: self.span(start) });
+
+                    self.pop_state();
+
+                    break;
+                },
+                Some('\\') => self.escape_sequence(if heredoc { None } else { Some('"') }, &mut buffer)?,
+                Some('$') if self.char_at(self.cursor).is_some_and(is_label_start) => {
+                    self.string_part(part, &mut buffer, &mut tokens);
+
+                    tokens.append(&mut self.simple_interpolation()?);
+                },
+                // The deprecated `${foo}` form is the same as `$foo`.
+                Some('$') if self.char_at(self.cursor) == Some(&'{') && self.char_at(self.cursor + 1).is_some_and(is_label_start) => {
+                    self.string_part(part, &mut buffer, &mut tokens);
+
+                    self.skip(2);
+
+                    let name = self.label();
+
+                    match self.peek {
+                        Some('}') => {
+                            self.next();
+                        },
+                        Some(n) => return Err(LexerErrorKind::UnexpectedCharacter(n)),
+                        None => return Err(LexerErrorKind::UnexpectedEndOfFile),
+                    }
+
+                    tokens.push(Token { kind: TokenKind::Variable(name), span: self.span(start) });
+                },
+                Some('{') if self.char_at(self.cursor) == Some(&'$') => {
+                    self.string_part(part, &mut buffer, &mut tokens);
+
+                    self.next();
+
+                    tokens.push(Token { kind: TokenKind::CurlyOpen, span: self.span(start) });
+
+                    // The embedded expression is lexed as regular code until the matching `}`.
+                    self.push_state(LexerState::Scripting);
+
+                    break;
+                },
+                Some(n) => {
+                    buffer.push(n);
+                    self.next();
+                },
+                // The body of a heredoc is lexed on its own, so the end of the input is the end of the string.
+                None if heredoc => {
+                    self.string_part(part, &mut buffer, &mut tokens);
+
+                    break;
+                },
+                None => return Err(LexerErrorKind::UnexpectedEndOfFile),
+            }
+        }
+
+        Ok(tokens)
+    }
+
+    /// Check whether the parenthesis we just read starts a cast such as `(int)` or `( array )`,
+    /// consuming the rest of the cast if it does.
+    fn cast(&mut self) -> Option<TokenKind> {
+        let start = self.cursor - 1;
+        let mut i = start;
+
+        while let Some(' ' | '\t') = self.char_at(i) {
+            i += 1;
+        }
+
+        let mut name = String::new();
+        while let Some(c) = self.char_at(i) {
+            if ! c.is_ascii_alphabetic() {
+                break;
+            }
+
+            name.push(c.to_ascii_lowercase());
+            i += 1;
+        }
+
+        while let Some(' ' | '\t') = self.char_at(i) {
+            i += 1;
+        }
+
+        if self.char_at(i) != Some(&')') {
+            return None;
+        }
+
+        let kind = match &name[..] {
+            "int" | "integer" => TokenKind::IntCast,
+            "bool" | "boolean" => TokenKind::BoolCast,
+            "float" | "double" => TokenKind::DoubleCast,
+            "real" => TokenKind::RealCast,
+            "string" | "binary" => TokenKind::StringCast,
+            "array" => TokenKind::ArrayCast,
+            "object" => TokenKind::ObjectCast,
+            _ => return None,
+        };
+
+        let length = i + 1 - start;
+
+        self.skip(length);
+
+        Some(kind)
+    }
+
+    fn heredoc(&mut self) -> Result<Vec<Token>, LexerErrorKind> {
+        let start = self.offset();
+
+        self.skip(3);
+
+        while let Some(' ' | '\t') = self.peek {
+            self.next();
+        }
+
+        let quote = match self.peek {
+            Some(q @ ('\'' | '"')) => {
+                self.next();
+
+                Some(q)
+            },
+            _ => None,
+        };
+
+        let label = self.label();
+        if label.is_empty() {
+            return Err(self.peek.map_or(LexerErrorKind::UnexpectedEndOfFile, LexerErrorKind::UnexpectedCharacter));
+        }
+
+        if let Some(q) = quote {
+            match self.peek {
+                Some(n) if n == q => {
+                    self.next();
+                },
+                Some(n) => return Err(LexerErrorKind::UnexpectedCharacter(n)),
+                None => return Err(LexerErrorKind::UnexpectedEndOfFile),
+            }
+        }
+
+        let open = Token {
+            kind: if quote == Some('\'') { TokenKind::NowdocOpen(label.clone()) } else { TokenKind::DocOpen(label.clone()) },
+            span: self.span(start),
+        };
+
+        if let Some('\r') = self.peek {
+            self.next();
+        }
+
+        match self.peek {
+            Some('\n') => {
+                self.next();
+            },
+            Some(n) => return Err(LexerErrorKind::UnexpectedCharacter(n)),
+            None => return Err(LexerErrorKind::UnexpectedEndOfFile),
+        }
+
+        let body_start = self.offset();
+
+        // Collect the lines of the body until we reach the closing label, which can be indented.
+        // The indentation of the closing label is removed from every line in the body.
+        let mut lines = Vec::new();
+        let (indentation, body_end) = loop {
+            if let Some(indentation) = self.closing_label(&label) {
+                let body_end = self.offset();
+
+                self.skip(indentation + label.chars().count());
+
+                break (indentation, body_end);
+            }
+
+            let mut line = String::new();
+            loop {
+                match self.peek {
+                    Some('\n') => {
+                        self.next();
+
+                        break;
+                    },
+                    Some(n) => {
+                        line.push(n);
+                        self.next();
+                    },
+                    None => return Err(LexerErrorKind::UnexpectedEndOfFile),
+                }
+            }
+
+            lines.push(line);
+        };
+
+        // The newline before the closing label isn't part of the body, whether it's `\n` or `\r\n`.
+        if let Some(line) = lines.last_mut() {
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+
+        // Where each line starts in the body once its indentation is removed, and where that is in
+        // the source, so that spans inside the body can be mapped back to the source.
+        let mut starts = Vec::new();
+        let (mut stripped_start, mut source_start) = (0, body_start);
+        let mut stripped = Vec::new();
+
+        for line in &lines {
+            let length = line.chars().count();
+            let strip = line.chars().take(indentation).take_while(|c| matches!(c, ' ' | '\t')).count();
+
+            // Lines can only be indented less than the closing label if they're blank.
+            if strip < indentation && line.chars().nth(strip).is_some_and(|c| c != '\r') {
+                return Err(LexerErrorKind::InvalidBodyIndentation(indentation));
+            }
+
+            starts.push((stripped_start, source_start + strip));
+            stripped.push(line.chars().skip(strip).collect::<String>());
+
+            stripped_start += length - strip + 1;
+            source_start += length + 1;
+        }
+
+        let body = stripped.join("\n");
+        let locate = |offset: usize| starts.iter()
+            .rfind(|(stripped_start, _)| *stripped_start <= offset)
+            .map_or(body_start + offset, |(stripped_start, source_start)| source_start + offset - stripped_start);
+
+        let mut tokens = vec![open];
+
+        if quote == Some('\'') {
+            if ! body.is_empty() {
+                tokens.push(Token { kind: TokenKind::StringPart(body), span: self.span_between(body_start, body_end) });
+            }
+        } else {
+            let mut lexer = Lexer::new(None);
+            lexer.state = vec![LexerState::Heredoc];
+            lexer.chars = body.chars().collect();
+            lexer.next();
+
+            for mut token in lexer.lex()? {
+                token.span.start = locate(token.span.start);
+                token.span.end = locate(token.span.end);
+
+                tokens.push(token);
+            }
+        }
+
+        tokens.push(Token { kind: TokenKind::DocClose, span: self.span_between(body_end + indentation, self.offset()) });
+
+        Ok(tokens)
+    }
+
+    /// Check whether the line starting at the next character closes the heredoc, returning
+    /// the indentation of the closing label if it does.
+    fn closing_label(&self, label: &str) -> Option<usize> {
+        let start = self.cursor - 1;
+
+        let mut indentation = 0;
+        while let Some(' ' | '\t') = self.char_at(start + indentation) {
+            indentation += 1;
+        }
+
+        for (i, c) in label.chars().enumerate() {
+            if self.char_at(start + indentation + i) != Some(&c) {
+                return None;
+            }
+        }
+
+        match self.char_at(start + indentation + label.chars().count()) {
+            Some(c) if is_label_start(c) || c.is_ascii_digit() => None,
+            _ => Some(indentation),
+        }
+    }
+
+    /// Lex the "simple" interpolation syntax, which is a variable optionally followed
+    /// by a single array offset or property fetch, e.g. `$foo`, `$foo[0]` or `$foo->bar`.
+    fn simple_interpolation(&mut self) -> Result<Vec<Token>, LexerErrorKind> {
+        let mut tokens = Vec::new();
+        let start = self.offset();
+
+        self.next();
+
+        let name = self.label();
+        tokens.push(Token { kind: TokenKind::Variable(name), span: self.span(start) });
+
+        match self.peek {
+            Some('[') => {
+                let start = self.offset();
+
+                self.next();
+
+                tokens.push(Token { kind: TokenKind::LeftBracket, span: self.span(start) });
+
+                let start = self.offset();
+                let kind = match self.peek {
+                    Some('$') if self.char_at(self.cursor).is_some_and(is_label_start) => {
+                        self.next();
+
+                        TokenKind::Variable(self.label())
+                    },
+                    Some('-' | '0'..='9') => {
+                        let mut buffer = String::new();
+
+                        if let Some('-') = self.peek {
+                            buffer.push('-');
+
+                            self.next();
+                        }
+
+                        while let Some(n @ '0'..='9') = self.peek {
+                            buffer.push(n);
+
+                            self.next();
+                        }
+
+                        TokenKind::Int(buffer.parse().map_err(|_| LexerErrorKind::UnexpectedCharacter(self.peek.unwrap_or('-')))?)
+                    },
+                    // Unquoted keys are treated as strings, e.g. `$foo[bar]` is `$foo['bar']`.
+                    Some(n) if is_label_start(&n) => TokenKind::ConstantString(self.label()),
+                    Some(n) => return Err(LexerErrorKind::UnexpectedCharacter(n)),
+                    None => return Err(LexerErrorKind::UnexpectedEndOfFile),
+                };
+
+                tokens.push(Token { kind, span: self.span(start) });
+
+                match self.peek {
+                    Some(']') => {
+                        let start = self.offset();
+
+                        self.next();
+
+                        tokens.push(Token { kind: TokenKind::RightBracket, span: self.span(start) });
+                    },
+                    Some(n) => return Err(LexerErrorKind::UnexpectedCharacter(n)),
+                    None => return Err(LexerErrorKind::UnexpectedEndOfFile),
+                }
+            },
+            Some('-') if self.char_at(self.cursor) == Some(&'>') && self.char_at(self.cursor + 1).is_some_and(is_label_start) => {
+                let start = self.offset();
+
+                self.skip(2);
+
+                tokens.push(Token { kind: TokenKind::Arrow, span: self.span(start) });
+
+                let start = self.offset();
+                let property = self.label();
+                tokens.push(Token { kind: TokenKind::Identifier(property), span: self.span(start) });
+            },
+            Some('?') if self.try_read("?->") && self.char_at(self.cursor + 2).is_some_and(is_label_start) => {
+                let start = self.offset();
+
+                self.skip(3);
+
+                tokens.push(Token { kind: TokenKind::NullsafeArrow, span: self.span(start) });
+
+                let start = self.offset();
+                let property = self.label();
+                tokens.push(Token { kind: TokenKind::Identifier(property), span: self.span(start) });
+            },
+            _ => {},
+        }
+
+        Ok(tokens)
+    }
+
+    fn string_part(&self, start: usize, buffer: &mut String, tokens: &mut Vec<Token>) {
+        if buffer.is_empty() {
+            return;
+        }
+
+        tokens.push(Token { kind: TokenKind::StringPart(std::mem::take(buffer)), span: self.span(start) });
+    }
+
+    /// Decode the escape sequence that starts with the backslash at the next character into
+    /// `buffer`. Only double quoted strings can escape their `quote`. As in PHP, a backslash
+    /// that doesn't start an escape sequence is kept.
+    fn escape_sequence(&mut self, quote: Option<char>, buffer: &mut String) -> Result<(), LexerErrorKind> {
+        // Runs of octal and hexadecimal escapes are decoded together, since they usually spell
+        // out the bytes of UTF-8 characters.
+        let mut bytes = Vec::new();
+
+        while self.peek == Some('\\') {
+            let Some(byte) = self.byte_escape() else { break };
+
+            bytes.push(byte);
+        }
+
+        if ! bytes.is_empty() {
+            buffer.push_str(&String::from_utf8_lossy(&bytes));
+
+            return Ok(());
+        }
+
+        self.next();
+
+        let Some(n) = self.peek else {
+            buffer.push('\\');
+
+            return Ok(());
+        };
+
+        let decoded = match n {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'v' => '\u{b}',
+            'e' => '\u{1b}',
+            'f' => '\u{c}',
+            '\\' | '$' => n,
+            _ if Some(n) == quote => n,
+            'u' if self.char_at(self.cursor) == Some(&'{') => return self.unicode_escape(buffer),
+            // The escaped character is still taken, so that `\{$a}` isn't the start of an interpolation.
+            _ => {
+                buffer.push('\\');
+
+                n
+            },
+        };
+
+        buffer.push(decoded);
+        self.next();
+
+        Ok(())
+    }
+
+    /// Decode an octal escape such as `\101` or a hexadecimal one such as `\x41`, starting with the
+    /// backslash at the next character. Nothing is consumed if there isn't one.
+    fn byte_escape(&mut self) -> Option<u8> {
+        let (radix, prefix, digits) = match self.char_at(self.cursor) {
+            Some('0'..='7') => (8, 1, 3),
+            Some('x') if self.char_at(self.cursor + 1).is_some_and(char::is_ascii_hexdigit) => (16, 2, 2),
+            _ => return None,
+        };
+
+        self.skip(prefix);
+
+        let mut value = 0;
+
+        for _ in 0..digits {
+            let Some(digit) = self.peek.and_then(|c| c.to_digit(radix)) else { break };
+
+            value = value * radix + digit;
+            self.next();
+        }
+
+        // Octal escapes above `\377` overflow, as they do in PHP.
+        Some(value as u8)
+    }
+
+    /// Decode a `\u{...}` escape, starting with the `u` at the next character.
+    fn unicode_escape(&mut self, buffer: &mut String) -> Result<(), LexerErrorKind> {
+        self.skip(2);
+
+        let mut digits = String::new();
+
+        while let Some(c) = self.peek.filter(char::is_ascii_hexdigit) {
+            digits.push(c);
+            self.next();
+        }
+
+        let codepoint = u32::from_str_radix(&digits, 16).ok().filter(|codepoint| *codepoint <= 0x10ffff);
+
+        match (codepoint, self.peek) {
+            (Some(codepoint), Some('}')) => {
+                self.next();
+
+                // Surrogates can't be held in a string, so they're replaced as invalid UTF-8 would be.
+                buffer.push(char::from_u32(codepoint).unwrap_or(char::REPLACEMENT_CHARACTER));
+
+                Ok(())
+            },
+            _ => Err(LexerErrorKind::InvalidEscape(format!("\\u{{{}", digits))),
+        }
+    }
+
+    /// Read a label, such as a variable or property name, starting at the next character.
+    fn label(&mut self) -> String {
+        let mut buffer = String::new();
+
+        while let Some(n) = self.peek {
+            if ! is_label_start(&n) && ! n.is_ascii_digit() {
+                break;
+            }
+
+            buffer.push(n);
+
+            self.next();
+        }
+
+        buffer
+    }
+
+    /// Check whether the double quoted string starting at the next character contains
+    /// any interpolation. Strings without interpolation are lexed as a single token.
+    fn has_interpolation(&self) -> bool {
+        let mut i = self.cursor - 1;
+
+        while let Some(c) = self.char_at(i) {
+            match c {
+                '"' => return false,
+                '\\' => i += 1,
+                '$' if self.char_at(i + 1).is_some_and(|n| is_label_start(n) || *n == '{') => return true,
+                '{' if self.char_at(i + 1) == Some(&'$') => return true,
+                _ => {},
+            }
+
+            i += 1;
+        }
+
+        false
+    }
+
+    /// The index of the next character that hasn't been consumed yet. In the initial state that's
+    /// the current character, otherwise it's the peeked character.
+    fn offset(&self) -> usize {
+        match self.state.last() {
+            Some(LexerState::Initial) => self.cursor.saturating_sub(2),
+            _ => self.cursor.saturating_sub(1),
+        }.min(self.chars.len())
+    }
+
+    fn span(&self, start: usize) -> Span {
+        self.span_between(start, self.offset())
+    }
+
+    fn span_between(&self, start: usize, end: usize) -> Span {
+        Span { start, end, line: 0, col: 0 }
+    }
+
+    fn char_at(&self, idx: usize) -> Option<&char> {
+        self.chars.get(idx)
+    }
+
+    fn try_read(&self, search: &'static str) -> bool {
+        if self.current.is_none() || self.peek.is_none() {
+            return false;
+        }
+
+        let start = self.cursor.saturating_sub(1);
+        let mut buffer = String::new();
+
+        for i in 0..search.len() {
+            match self.char_at(start + i) {
+                Some(char) => buffer.push(*char),
+                _ => return false,
+            };
+        }
+
+        buffer.as_str() == search
+    }
+
+    fn skip(&mut self, count: usize) {
+        for _ in 0..count {
+            self.next();
+        }
+    }
+
+    fn next(&mut self) {
+        self.current = self.peek;
+        self.peek = self.chars.get(self.cursor).cloned();
+        self.cursor += 1;
+    }
+}
+
+fn is_label_start(c: &char) -> bool {
+    matches!(c, 'a'..='z' | 'A'..='Z' | '_' | '\u{80}'..='\u{ff}')
+}
+
+#[allow(dead_code)]
+fn identifier_to_keyword(ident: &str) -> Option<TokenKind> {
+    Some(match ident {
+        "match" => TokenKind::Match,
+        "abstract" => TokenKind::Abstract,
+        "and" | "AND" => TokenKind::LogicalAnd,
+        "array" => TokenKind::Array,
         "as" => TokenKind::As,
         "break" => TokenKind::Break,
         "case" => TokenKind::Case,
@@ -824,28 +1414,40 @@ fn identifier_to_keyword(ident: &str) -> Option<TokenKind> {
         "const" => TokenKind::Const,
         "declare" => TokenKind::Declare,
         "default" => TokenKind::Default,
+        "die" => TokenKind::Die,
         "echo" => TokenKind::Echo,
         "else" => TokenKind::Else,
         "elseif" => TokenKind::ElseIf,
+        "exit" => TokenKind::Exit,
+        "enddeclare" => TokenKind::EndDeclare,
+        "endfor" => TokenKind::EndFor,
+        "endforeach" => TokenKind::EndForeach,
+        "endif" => TokenKind::EndIf,
+        "endswitch" => TokenKind::EndSwitch,
+        "endwhile" => TokenKind::EndWhile,
         "enum" => TokenKind::Enum,
         "extends" => TokenKind::Extends,
-        "false" | "FALSE" => TokenKind::False,
         "final" => TokenKind::Final,
         "finally" => TokenKind::Finally,
         "fn" => TokenKind::Fn,
         "for" => TokenKind::For,
         "foreach" => TokenKind::Foreach,
         "function" => TokenKind::Function,
+        "global" => TokenKind::Global,
         "if" => TokenKind::If,
         "implements" => TokenKind::Implements,
+        "include" => TokenKind::Include,
+        "include_once" => TokenKind::IncludeOnce,
         "interface" => TokenKind::Interface,
         "instanceof" => TokenKind::Instanceof,
         "namespace" => TokenKind::Namespace,
         "new" => TokenKind::New,
-        "null" | "NULL" => TokenKind::Null,
+        "or" | "OR" => TokenKind::LogicalOr,
+        "print" => TokenKind::Print,
         "private" => TokenKind::Private,
         "protected" => TokenKind::Protected,
         "public" => TokenKind::Public,
+        "readonly" => TokenKind::Readonly,
         "require" => TokenKind::Require,
         "require_once" => TokenKind::RequireOnce,
         "return" => TokenKind::Return,
@@ -853,27 +1455,114 @@ fn identifier_to_keyword(ident: &str) -> Option<TokenKind> {
         "switch" => TokenKind::Switch,
         "throw" => TokenKind::Throw,
         "trait" => TokenKind::Trait,
-        "true" | "TRUE" => TokenKind::True,
         "try" => TokenKind::Try,
         "use" => TokenKind::Use,
         "var" => TokenKind::Var,
         "yield" => TokenKind::Yield,
-        "__DIR__" => TokenKind::DirConstant,
         "while" => TokenKind::While,
+        "xor" | "XOR" => TokenKind::LogicalXor,
+        _ if ident.eq_ignore_ascii_case("__halt_compiler") => TokenKind::HaltCompiler,
+        _ if ident.eq_ignore_ascii_case("__CLASS__") => TokenKind::ClassConstant,
+        _ if ident.eq_ignore_ascii_case("__DIR__") => TokenKind::DirConstant,
+        _ if ident.eq_ignore_ascii_case("__FILE__") => TokenKind::FileConstant,
+        _ if ident.eq_ignore_ascii_case("__FUNCTION__") => TokenKind::FunctionConstant,
+        _ if ident.eq_ignore_ascii_case("__LINE__") => TokenKind::LineConstant,
+        _ if ident.eq_ignore_ascii_case("__METHOD__") => TokenKind::MethodConstant,
+        _ if ident.eq_ignore_ascii_case("__NAMESPACE__") => TokenKind::NamespaceConstant,
+        _ if ident.eq_ignore_ascii_case("__TRAIT__") => TokenKind::TraitConstant,
+        _ if ident.eq_ignore_ascii_case("true") => TokenKind::True,
+        _ if ident.eq_ignore_ascii_case("false") => TokenKind::False,
+        _ if ident.eq_ignore_ascii_case("null") => TokenKind::Null,
         _ => return None,
     })
 }
 
-#[derive(Debug)]
-pub enum LexerError {
+/// How many characters either side of an error are included in its excerpt.
+const EXCERPT_CONTEXT: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexerErrorKind {
     UnexpectedEndOfFile,
     UnexpectedCharacter(char),
+    InvalidNumber(String),
+    InvalidEscape(String),
+    InvalidBodyIndentation(usize),
+}
+
+impl std::fmt::Display for LexerErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedEndOfFile => write!(f, "unexpected end of file"),
+            Self::UnexpectedCharacter(c) => write!(f, "unexpected character `{}`", c),
+            Self::InvalidNumber(n) => write!(f, "invalid numeric literal `{}`", n),
+            Self::InvalidEscape(e) => write!(f, "invalid escape sequence `{}`", e),
+            Self::InvalidBodyIndentation(n) => write!(f, "invalid body indentation level, expecting an indentation level of at least {}", n),
+        }
+    }
+}
+
+/// An error that stopped the source from being lexed, along with where it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexerError {
+    pub kind: LexerErrorKind,
+    pub span: Span,
+    /// The source around the error, from the line that it's on.
+    pub excerpt: String,
+}
+
+impl std::fmt::Display for LexerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Syntax error: {} on line {} column {}", self.kind, self.span.line, self.span.col)?;
+
+        if self.excerpt.is_empty() {
+            Ok(())
+        } else {
+            write!(f, ", near `{}`", self.excerpt)
+        }
+    }
+}
+
+impl std::error::Error for LexerError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexerWarning {
+    /// An integer literal that's too large for an `i64`, which is lexed as a float instead.
+    IntegerOverflow(String, Span),
+}
+
+impl LexerWarning {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::IntegerOverflow(_, span) => *span,
+        }
+    }
+
+    /// A description of the warning, without the location that `Display` includes.
+    pub fn message(&self) -> String {
+        match self {
+            Self::IntegerOverflow(n, _) => format!("integer literal `{}` is too large and is treated as a float", n),
+        }
+    }
+
+    fn span_mut(&mut self) -> &mut Span {
+        match self {
+            Self::IntegerOverflow(_, span) => span,
+        }
+    }
+}
+
+impl std::fmt::Display for LexerWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let span = self.span();
+
+        write!(f, "Warning: {} on line {} column {}", self.message(), span.line, span.col)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{TokenKind, OpenTagKind, Token};
-    use super::Lexer;
+    use crate::{Float, TokenKind, OpenTagKind, Token};
+    use super::{Lexer, LexerErrorKind, LexerWarning};
 
     macro_rules! open {
         () => {
@@ -910,6 +1599,17 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn inline_html_between_tags() {
+        assert_tokens("<?php ?>\n<p><?php ?>", &[
+            open!(),
+            TokenKind::CloseTag,
+            TokenKind::InlineHtml("<p>".into()),
+            open!(),
+            TokenKind::CloseTag,
+        ]);
+    }
+
     #[test]
     fn keywords() {
         assert_tokens("<?php function if else elseif echo return class extends implements public protected private static null NULL true TRUE false FALSE use const namespace interface new foreach instanceof", &[
@@ -943,6 +1643,92 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn literal_keywords_are_case_insensitive() {
+        assert_tokens("<?php True tRUE False fAlse Null nULL", &[
+            open!(),
+            TokenKind::True,
+            TokenKind::True,
+            TokenKind::False,
+            TokenKind::False,
+            TokenKind::Null,
+            TokenKind::Null,
+        ]);
+    }
+
+    #[test]
+    fn magic_constants() {
+        assert_tokens("<?php __CLASS__ __DIR__ __FILE__ __FUNCTION__ __LINE__ __METHOD__ __NAMESPACE__ __TRAIT__", &[
+            open!(),
+            TokenKind::ClassConstant,
+            TokenKind::DirConstant,
+            TokenKind::FileConstant,
+            TokenKind::FunctionConstant,
+            TokenKind::LineConstant,
+            TokenKind::MethodConstant,
+            TokenKind::NamespaceConstant,
+            TokenKind::TraitConstant,
+        ]);
+
+        assert_tokens("<?php __class__ __Dir__", &[
+            open!(),
+            TokenKind::ClassConstant,
+            TokenKind::DirConstant,
+        ]);
+    }
+
+    #[test]
+    fn readonly() {
+        assert_tokens("<?php readonly readonly()", &[
+            open!(),
+            TokenKind::Readonly,
+            TokenKind::Identifier("readonly".into()),
+            TokenKind::LeftParen,
+            TokenKind::RightParen,
+        ]);
+    }
+
+    #[test]
+    fn asymmetric_visibility() {
+        assert_tokens("<?php public private(set) protected(set) public(set) private (set)", &[
+            open!(),
+            TokenKind::Public,
+            TokenKind::PrivateSet,
+            TokenKind::ProtectedSet,
+            TokenKind::PublicSet,
+            TokenKind::Private,
+            TokenKind::LeftParen,
+            TokenKind::Identifier("set".into()),
+            TokenKind::RightParen,
+        ]);
+    }
+
+    #[test]
+    fn logical_keywords() {
+        assert_tokens("<?php and AND or OR xor XOR", &[
+            open!(),
+            TokenKind::LogicalAnd,
+            TokenKind::LogicalAnd,
+            TokenKind::LogicalOr,
+            TokenKind::LogicalOr,
+            TokenKind::LogicalXor,
+            TokenKind::LogicalXor,
+        ]);
+    }
+
+    #[test]
+    fn alternative_syntax_keywords() {
+        assert_tokens("<?php endif endwhile endfor endforeach endswitch enddeclare", &[
+            open!(),
+            TokenKind::EndIf,
+            TokenKind::EndWhile,
+            TokenKind::EndFor,
+            TokenKind::EndForeach,
+            TokenKind::EndSwitch,
+            TokenKind::EndDeclare,
+        ]);
+    }
+
     #[test]
     fn casts() {
         assert_tokens("<?php (object) (string)", &[
@@ -950,6 +1736,20 @@ mod tests {
             TokenKind::ObjectCast,
             TokenKind::StringCast,
         ]);
+
+        assert_tokens("<?php (int)$a ( Array ) (boolean) (double) (integer) (binary) (foo)", &[
+            open!(),
+            TokenKind::IntCast,
+            var!("a"),
+            TokenKind::ArrayCast,
+            TokenKind::BoolCast,
+            TokenKind::DoubleCast,
+            TokenKind::IntCast,
+            TokenKind::StringCast,
+            TokenKind::LeftParen,
+            TokenKind::Identifier("foo".into()),
+            TokenKind::RightParen,
+        ]);
     }
 
     #[test]
@@ -964,6 +1764,144 @@ string.'"#, &[
         ]);
     }
 
+    #[test]
+    fn interpolated_strings() {
+        assert_tokens(r#"<?php "Hello $name!" "$foo[bar] $foo[-1] $foo[$i] $foo->bar" "{$foo->bar()} ${baz} \$qux";"#, &[
+            open!(),
+            TokenKind::DoubleQuote,
+            TokenKind::StringPart("Hello ".into()),
+            var!("name"),
+            TokenKind::StringPart("!".into()),
+            TokenKind::DoubleQuote,
+            TokenKind::DoubleQuote,
+            var!("foo"),
+            TokenKind::LeftBracket,
+            TokenKind::ConstantString("bar".into()),
+            TokenKind::RightBracket,
+            TokenKind::StringPart(" ".into()),
+            var!("foo"),
+            TokenKind::LeftBracket,
+            int!(-1),
+            TokenKind::RightBracket,
+            TokenKind::StringPart(" ".into()),
+            var!("foo"),
+            TokenKind::LeftBracket,
+            var!("i"),
+            TokenKind::RightBracket,
+            TokenKind::StringPart(" ".into()),
+            var!("foo"),
+            TokenKind::Arrow,
+            TokenKind::Identifier("bar".into()),
+            TokenKind::DoubleQuote,
+            TokenKind::DoubleQuote,
+            TokenKind::CurlyOpen,
+            var!("foo"),
+            TokenKind::Arrow,
+            TokenKind::Identifier("bar".into()),
+            TokenKind::LeftParen,
+            TokenKind::RightParen,
+            TokenKind::RightBrace,
+            TokenKind::StringPart(" ".into()),
+            var!("baz"),
+            TokenKind::StringPart(" $qux".into()),
+            TokenKind::DoubleQuote,
+            TokenKind::SemiColon,
+        ]);
+    }
+
+    #[test]
+    fn braces_inside_interpolation() {
+        assert_tokens(r#"<?php if ($a) { "{$b}"; }"#, &[
+            open!(),
+            TokenKind::If,
+            TokenKind::LeftParen,
+            var!("a"),
+            TokenKind::RightParen,
+            TokenKind::LeftBrace,
+            TokenKind::DoubleQuote,
+            TokenKind::CurlyOpen,
+            var!("b"),
+            TokenKind::RightBrace,
+            TokenKind::DoubleQuote,
+            TokenKind::SemiColon,
+            TokenKind::RightBrace,
+        ]);
+    }
+
+    #[test]
+    fn escape_sequences() {
+        assert_tokens(r#"<?php "\n\t\r\v\e\f\\\$\"\101\x41\u{1F418}\d\u\x" "\xc3\xa9\400" "$a\n\"\{$b}";"#, &[
+            open!(),
+            TokenKind::ConstantString("\n\t\r\u{b}\u{1b}\u{c}\\$\"AA\u{1F418}\\d\\u\\x".into()),
+            TokenKind::ConstantString("é\0".into()),
+            TokenKind::DoubleQuote,
+            var!("a"),
+            TokenKind::StringPart("\n\"\\{".into()),
+            var!("b"),
+            TokenKind::StringPart("}".into()),
+            TokenKind::DoubleQuote,
+            TokenKind::SemiColon,
+        ]);
+
+        // Heredocs don't have a quote to escape.
+        assert_tokens("<?php <<<EOT\n\\\"\\t\\$\nEOT;", &[
+            open!(),
+            TokenKind::DocOpen("EOT".into()),
+            TokenKind::StringPart("\\\"\t$".into()),
+            TokenKind::DocClose,
+            TokenKind::SemiColon,
+        ]);
+
+        let err = Lexer::new(None).tokenize(r#"<?php "\u{110000}";"#).unwrap_err();
+        assert_eq!(err.kind, LexerErrorKind::InvalidEscape("\\u{110000".into()));
+    }
+
+    #[test]
+    fn heredocs() {
+        assert_tokens("<?php <<<EOT\n  Hello $name\n    {$a}\n  EOT;", &[
+            open!(),
+            TokenKind::DocOpen("EOT".into()),
+            TokenKind::StringPart("Hello ".into()),
+            var!("name"),
+            TokenKind::StringPart("\n  ".into()),
+            TokenKind::CurlyOpen,
+            var!("a"),
+            TokenKind::RightBrace,
+            TokenKind::DocClose,
+            TokenKind::SemiColon,
+        ]);
+
+        assert_tokens("<?php <<<\"EOT\"\nEOT;", &[
+            open!(),
+            TokenKind::DocOpen("EOT".into()),
+            TokenKind::DocClose,
+            TokenKind::SemiColon,
+        ]);
+
+        assert_tokens("<?php <<<EOT\r\n  a\r\n\r\n  b\r\n  EOT;", &[
+            open!(),
+            TokenKind::DocOpen("EOT".into()),
+            TokenKind::StringPart("a\r\n\r\nb".into()),
+            TokenKind::DocClose,
+            TokenKind::SemiColon,
+        ]);
+
+        let err = Lexer::new(None).tokenize("<?php <<<EOT\n    a\n  b\n    EOT;").unwrap_err();
+        assert_eq!(err.kind, LexerErrorKind::InvalidBodyIndentation(4));
+    }
+
+    #[test]
+    fn nowdocs() {
+        assert_tokens("<?php <<<'EOT'\n  Hello $name\n  EOTX\n  EOT, 1", &[
+            open!(),
+            TokenKind::NowdocOpen("EOT".into()),
+            TokenKind::StringPart("Hello $name\nEOTX".into()),
+            TokenKind::DocClose,
+            TokenKind::Comma,
+            int!(1),
+        ]);
+    }
+
     #[test]
     fn single_line_comments() {
         assert_tokens(r#"<?php
@@ -1002,12 +1940,16 @@ function"#, &[
 
     #[test]
     fn vars() {
-        assert_tokens("<?php $one $_one $One $one_one", &[
+        assert_tokens("<?php $one $_one $One $one_one $n5 $a1b2 $_9 $é1", &[
             open!(),
             var!("one"),
             var!("_one"),
             var!("One"),
             var!("one_one"),
+            var!("n5"),
+            var!("a1b2"),
+            var!("_9"),
+            var!("é1"),
         ]);
     }
 
@@ -1044,13 +1986,138 @@ function"#, &[
         ]);
     }
 
+    #[test]
+    fn nullsafe_arrow() {
+        assert_tokens("<?php $foo?->bar", &[
+            open!(),
+            var!("foo"),
+            TokenKind::NullsafeArrow,
+            TokenKind::Identifier("bar".into()),
+        ]);
+    }
+
+    #[test]
+    fn halt_compiler() {
+        // The offset is in bytes, so the multi-byte character before it counts for three.
+        assert_tokens("<?php '€'; __halt_compiler(); raw €data", &[
+            open!(),
+            TokenKind::ConstantString("€".into()),
+            TokenKind::SemiColon,
+            TokenKind::HaltCompiler,
+            TokenKind::LeftParen,
+            TokenKind::RightParen,
+            TokenKind::SemiColon,
+            TokenKind::HaltCompilerData(31, " raw €data".into()),
+        ]);
+
+        assert_tokens("<?php __Halt_Compiler() ?>\nraw", &[
+            open!(),
+            TokenKind::HaltCompiler,
+            TokenKind::LeftParen,
+            TokenKind::RightParen,
+            TokenKind::CloseTag,
+            TokenKind::HaltCompilerData(27, "raw".into()),
+        ]);
+    }
+
+    #[test]
+    fn includes() {
+        assert_tokens("<?php include include_once require require_once", &[
+            open!(),
+            TokenKind::Include,
+            TokenKind::IncludeOnce,
+            TokenKind::Require,
+            TokenKind::RequireOnce,
+        ]);
+    }
+
+    #[test]
+    fn exit() {
+        assert_tokens("<?php exit die", &[
+            open!(),
+            TokenKind::Exit,
+            TokenKind::Die,
+        ]);
+    }
+
+    #[test]
+    fn print() {
+        assert_tokens("<?php print", &[
+            open!(),
+            TokenKind::Print,
+        ]);
+    }
+
+    #[test]
+    fn error_suppress() {
+        assert_tokens("<?php @foo();", &[
+            open!(),
+            TokenKind::At,
+            TokenKind::Identifier("foo".into()),
+            TokenKind::LeftParen,
+            TokenKind::RightParen,
+            TokenKind::SemiColon,
+        ]);
+    }
+
+    #[test]
+    fn increment_decrement() {
+        assert_tokens("<?php ++ -- + -", &[
+            open!(),
+            TokenKind::Increment,
+            TokenKind::Decrement,
+            TokenKind::Plus,
+            TokenKind::Minus,
+        ]);
+    }
+
+    #[test]
+    fn attributes() {
+        assert_tokens("<?php #[Foo] # comment", &[
+            open!(),
+            TokenKind::Attribute,
+            TokenKind::Identifier("Foo".into()),
+            TokenKind::RightBracket,
+            TokenKind::Comment("# comment".into()),
+        ]);
+    }
+
     #[test]
     fn math() {
-        assert_tokens("<?php + - <", &[
+        assert_tokens("<?php + - < % ** * << >> & | ^ ~", &[
             open!(),
             TokenKind::Plus,
             TokenKind::Minus,
             TokenKind::LessThan,
+            TokenKind::Percent,
+            TokenKind::Pow,
+            TokenKind::Asterisk,
+            TokenKind::LeftShift,
+            TokenKind::RightShift,
+            TokenKind::Ampersand,
+            TokenKind::Pipe,
+            TokenKind::Caret,
+            TokenKind::Tilde,
+        ]);
+    }
+
+    #[test]
+    fn compound_assignments() {
+        assert_tokens("<?php += -= *= /= .= %= **= &= |= ^= <<= >>= ??=", &[
+            open!(),
+            TokenKind::PlusEquals,
+            TokenKind::MinusEquals,
+            TokenKind::AsteriskEqual,
+            TokenKind::SlashEquals,
+            TokenKind::DotEquals,
+            TokenKind::PercentEquals,
+            TokenKind::PowEquals,
+            TokenKind::AndEqual,
+            TokenKind::PipeEquals,
+            TokenKind::CaretEquals,
+            TokenKind::LeftShiftEquals,
+            TokenKind::RightShiftEquals,
+            TokenKind::CoalesceEqual,
         ]);
     }
 
@@ -1074,15 +2141,30 @@ function"#, &[
         ]);
     }
 
+    #[test]
+    fn comparisons() {
+        assert_tokens("<?php != !== <> < <= > >= <=>", &[
+            open!(),
+            TokenKind::BangEquals,
+            TokenKind::BangDoubleEquals,
+            TokenKind::LessThanGreaterThan,
+            TokenKind::LessThan,
+            TokenKind::LessThanEquals,
+            TokenKind::GreaterThan,
+            TokenKind::GreaterThanEquals,
+            TokenKind::Spaceship,
+        ]);
+    }
+
     #[test]
     fn span_tracking() {
         let spans = get_spans("<?php hello_world()");
 
         assert_eq!(spans, &[
-            (1, 4),
-            (1, 16),
-            (1, 17),
+            (1, 1),
+            (1, 7),
             (1, 18),
+            (1, 19),
         ]);
 
         let spans = get_spans(r#"<?php
@@ -1092,9 +2174,9 @@ function hello_world() {
 }"#);
         
         assert_eq!(spans, &[
-            (1, 4),
-            (3, 8),
-            (3, 20),
+            (1, 1),
+            (3, 1),
+            (3, 10),
             (3, 21),
             (3, 22),
             (3, 24),
@@ -1102,12 +2184,113 @@ function hello_world() {
         ]);
     }
 
+    #[test]
+    fn span_offsets() {
+        let spans: Vec<(usize, usize)> = get_tokens("<?php $a = 'é';")
+            .iter()
+            .map(|token| (token.span.start, token.span.end))
+            .collect();
+
+        assert_eq!(spans, &[
+            (0, 5),
+            (6, 8),
+            (9, 10),
+            (11, 15),
+            (15, 16),
+        ]);
+
+        let spans: Vec<(usize, usize)> = get_tokens("<?php \"a $b c\";")
+            .iter()
+            .map(|token| (token.span.start, token.span.end))
+            .collect();
+
+        assert_eq!(spans, &[
+            (0, 5),
+            (6, 7),
+            (7, 9),
+            (9, 11),
+            (11, 13),
+            (13, 14),
+            (14, 15),
+        ]);
+
+        // Spans in an indented heredoc include the indentation that was removed from each line.
+        let spans: Vec<(usize, usize)> = get_tokens("<?php <<<EOT\n  a\n  b $c\n  EOT;")
+            .iter()
+            .map(|token| (token.span.start, token.span.end))
+            .collect();
+
+        assert_eq!(spans, &[
+            (0, 5),
+            (6, 12),
+            (15, 21),
+            (21, 23),
+            (26, 29),
+            (29, 30),
+        ]);
+    }
+
+    #[test]
+    fn integer_overflow() {
+        assert_tokens("<?php 9223372036854775807 9223372036854775808 99999999999999999999", &[
+            open!(),
+            TokenKind::Int(i64::MAX),
+            TokenKind::Float(Float(9223372036854775808.0)),
+            TokenKind::Float(Float(1e20)),
+        ]);
+
+        let mut lexer = Lexer::new(None);
+        lexer.tokenize("<?php\n$a = 1_000_000_000_000_000_000_000;").unwrap();
+
+        match lexer.warnings() {
+            [warning @ LexerWarning::IntegerOverflow(literal, span)] => {
+                assert_eq!(literal, "1000000000000000000000");
+                assert_eq!((span.start, span.end, span.line, span.col), (11, 40, 2, 6));
+                assert_eq!(warning.to_string(), "Warning: integer literal `1000000000000000000000` is too large and is treated as a float on line 2 column 6");
+            },
+            warnings => panic!("unexpected warnings {:?}", warnings),
+        }
+
+        lexer.tokenize("<?php 1;").unwrap();
+        assert!(lexer.warnings().is_empty());
+    }
+
+    #[test]
+    fn errors_have_locations() {
+        let err = Lexer::new(None).tokenize("<?php\n$é = 1;\n  $b = 1 ` 2;").unwrap_err();
+
+        assert_eq!(err.kind, LexerErrorKind::UnexpectedCharacter('`'));
+        assert_eq!((err.span.start, err.span.end, err.span.line, err.span.col), (24, 25, 3, 10));
+        assert_eq!(err.excerpt, "$b = 1 ` 2;");
+        assert_eq!(err.to_string(), "Syntax error: unexpected character ``` on line 3 column 10, near `$b = 1 ` 2;`");
+
+        let err = Lexer::new(None).tokenize("<?php __halt_compiler;").unwrap_err();
+        assert_eq!(err.kind, LexerErrorKind::UnexpectedCharacter(';'));
+        assert_eq!((err.span.line, err.span.col), (1, 22));
+
+        let err = Lexer::new(None).tokenize("<?php\n__halt_compiler(").unwrap_err();
+        assert_eq!(err.kind, LexerErrorKind::UnexpectedEndOfFile);
+        assert_eq!((err.span.start, err.span.end, err.span.line, err.span.col), (22, 22, 2, 17));
+    }
+
+    #[test]
+    fn invalid_input_is_an_error() {
+        assert!(Lexer::new(None).tokenize("<?php $a ` $b").is_err());
+        assert!(Lexer::new(None).tokenize("<?php #").is_ok());
+        assert_tokens("<?php $a..5", &[
+            open!(),
+            TokenKind::Variable("a".into()),
+            TokenKind::Dot,
+            TokenKind::Float(Float(0.5)),
+        ]);
+    }
+
     #[test]
     fn floats() {
         assert_tokens("<?php 200.5 .05", &[
             open!(),
-            TokenKind::Float(200.5),
-            TokenKind::Float(0.05),
+            TokenKind::Float(Float(200.5)),
+            TokenKind::Float(Float(0.05)),
         ]);
     }
 
@@ -1126,7 +2309,7 @@ function hello_world() {
         let mut spans = vec!();
         
         for token in tokens {
-            spans.push(token.span);
+            spans.push((token.span.line, token.span.col));
         }
 
         spans
@@ -1,5 +1,12 @@
 mod token;
 mod lexer;
 
-pub use token::{Token, TokenKind, Span, OpenTagKind};
-pub use lexer::{Lexer, LexerError};
\ No newline at end of file
+pub use token::{Token, TokenKind, Span, Location, OpenTagKind, Float};
+pub use lexer::{Lexer, LexerError, LexerErrorKind, LexerWarning};
+
+/// An entry point for fuzzers, which lexes arbitrary bytes as source code. Bytes that aren't valid
+/// UTF-8 are replaced, as they are when a file is read lossily. Any input gives either tokens or an
+/// error, and never a panic.
+pub fn fuzz_lex(bytes: &[u8]) -> Result<Vec<Token>, LexerError> {
+    Lexer::new(None).tokenize(&String::from_utf8_lossy(bytes))
+}
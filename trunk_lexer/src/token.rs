@@ -1,13 +1,78 @@
-use std::fmt::Display;
+use std::{fmt::Display, hash::{Hash, Hasher}, path::Path};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-pub type Span = (usize, usize);
+/// The location of a token or node in the source code. `start` and `end` are byte offsets,
+/// while `line` and `col` are the 1-based line and column that the span starts on.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Span {
+    /// Attach a file name to the span, for display as `path:line:col`.
+    pub fn in_file(self, path: &Path) -> Location<'_> {
+        Location { path, span: self }
+    }
+}
+
+/// A span in a particular file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Location<'a> {
+    pub path: &'a Path,
+    pub span: Span,
+}
+
+impl Display for Location<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.path.display(), self.span.line, self.span.col)
+    }
+}
+
+/// A floating point number. Floats are compared and hashed by their bits, so that every float is
+/// equal to itself, including `NaN`, and `0.0` and `-0.0` are kept apart.
+#[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct Float(pub f64);
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for Float {}
+
+impl Hash for Float {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+impl From<f64> for Float {
+    fn from(f: f64) -> Self {
+        Self(f)
+    }
+}
+
+impl From<Float> for f64 {
+    fn from(f: Float) -> Self {
+        f.0
+    }
+}
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum OpenTagKind {
     Full,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TokenKind {
     Abstract,
     Ampersand,
@@ -19,6 +84,7 @@ pub enum TokenKind {
     NullsafeArrow,
     As,
     Asterisk,
+    At,
     Attribute,
     Bang,
     BangEquals,
@@ -29,6 +95,7 @@ pub enum TokenKind {
     Break,
     Callable,
     Caret,
+    CaretEquals,
     Case,
     Catch,
     Class,
@@ -50,10 +117,12 @@ pub enum TokenKind {
     Declare,
     Decrement,
     Default,
+    Die,
     DirConstant,
     DivEqual,
     Do,
     DocComment(String),
+    DocClose,
     DocOpen(String),
     Dot,
     DotEquals,
@@ -61,6 +130,7 @@ pub enum TokenKind {
     DoubleCast,
     DoubleColon,
     DoubleEquals,
+    DoubleQuote,
     Echo,
     Ellipsis,
     Else,
@@ -75,21 +145,29 @@ pub enum TokenKind {
     Enum,
     Eof,
     Equals,
+    Exit,
     Extends,
     False,
+    FileConstant,
     Final,
     Finally,
-    Float(f64),
+    Float(Float),
     Fn,
     For,
     Foreach,
     FullyQualifiedIdentifier(String),
     Function,
+    FunctionConstant,
+    Global,
     GreaterThan,
     GreaterThanEquals,
+    HaltCompiler,
+    HaltCompilerData(usize, String),
     Identifier(String),
     If,
     Implements,
+    Include,
+    IncludeOnce,
     Increment,
     InlineHtml(String),
     Instanceof,
@@ -100,41 +178,65 @@ pub enum TokenKind {
     LeftBracket,
     LeftParen,
     LeftShift,
+    LeftShiftEquals,
     LessThan,
     LessThanEquals,
+    LessThanGreaterThan,
+    LineConstant,
+    LogicalAnd,
+    LogicalOr,
+    LogicalXor,
     Match,
+    MethodConstant,
     Minus,
     Namespace,
+    NamespaceConstant,
     NamespaceSeparator,
     New,
+    NowdocOpen(String),
     Null,
     ObjectCast,
     OpenTag(OpenTagKind),
     Percent,
+    PercentEquals,
     Pipe,
+    PipeEquals,
     Plus,
     PlusEquals,
     Pow,
+    PowEquals,
+    Print,
     Private,
+    PrivateSet,
     Protected,
+    ProtectedSet,
     Public,
+    PublicSet,
     QualifiedIdentifier(String),
     Question,
     QuestionColon,
+    Readonly,
+    RealCast,
     Require,
     RequireOnce,
     Return,
     RightBrace,
     RightBracket,
     RightParen,
+    RightShift,
+    RightShiftEquals,
     SemiColon,
     Slash,
     SlashEquals,
+    Spaceship,
     Static,
+    StringPart(String),
     StringCast,
     Switch,
     Throw,
+    Tilde,
     Trait,
+    TraitConstant,
     TripleEquals,
     True,
     Try,
@@ -146,6 +248,7 @@ pub enum TokenKind {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Token {
     pub kind: TokenKind,
     pub span: Span,
@@ -153,7 +256,7 @@ pub struct Token {
 
 impl Default for Token {
     fn default() -> Self {
-        Self { kind: TokenKind::Eof, span: (0, 0) }
+        Self { kind: TokenKind::Eof, span: Span::default() }
     }
 }
 
@@ -164,6 +267,7 @@ impl Display for TokenKind {
             Self::ObjectCast => "(object)",
             Self::Abstract => "abstract",
             Self::Ampersand => "&",
+            Self::At => "@",
             Self::And => "&&",
             Self::AndEqual => "&=",
             Self::Arrow => "->",
@@ -174,12 +278,15 @@ impl Display for TokenKind {
             Self::Asterisk => "*",
             Self::Attribute => "#[",
             Self::Bang => "!",
+            Self::BangEquals => "!=",
+            Self::BangDoubleEquals => "!==",
             Self::BoolCast => "(bool)",
             Self::BooleanAnd => "&&",
             Self::BooleanOr => "||",
             Self::Break => "break",
             Self::Callable => "callable",
             Self::Caret => "^",
+            Self::CaretEquals => "^=",
             Self::Case => "case",
             Self::Catch => "catch",
             Self::Class => "class",
@@ -197,6 +304,8 @@ impl Display for TokenKind {
             Self::Continue => "continue",
             Self::IntCast => "(int)",
             Self::CurlyOpen => "{$",
+            Self::DoubleQuote => "\"",
+            Self::StringPart(part) => &part[..],
             Self::Declare => "declare",
             Self::Decrement => "--",
             Self::Default => "default",
@@ -205,6 +314,8 @@ impl Display for TokenKind {
             Self::Do => "do",
             Self::DocComment(comment) => &comment[..],
             Self::DocOpen(doc_open) => &doc_open[..],
+            Self::NowdocOpen(doc_open) => &doc_open[..],
+            Self::DocClose => "end of heredoc",
             Self::Dot => ".",
             Self::DotEquals => ".=",
             Self::DoubleArrow => "=>",
@@ -227,13 +338,17 @@ impl Display for TokenKind {
             Self::Equals => "=",
             Self::Extends => "extends",
             Self::False => "false",
+            Self::FileConstant => "__FILE__",
             Self::Final => "final",
             Self::Finally => "finally",
             Self::Float(_) => "float",
             Self::Fn => "fn",
             Self::For => "for",
+            Self::Foreach => "foreach",
             Self::FullyQualifiedIdentifier(id) => &id[..],
             Self::Function => "function",
+            Self::FunctionConstant => "__FUNCTION__",
+            Self::Global => "global",
             Self::GreaterThan => ">",
             Self::GreaterThanEquals => ">=",
             Self::Identifier(id) => &id[..],
@@ -246,43 +361,73 @@ impl Display for TokenKind {
             Self::LeftBracket => "[",
             Self::LeftParen => "(",
             Self::LeftShift => "<<",
+            Self::LeftShiftEquals => "<<=",
             Self::LessThan => "<",
             Self::LessThanEquals => "<=",
+            Self::LessThanGreaterThan => "<>",
+            Self::LineConstant => "__LINE__",
+            Self::LogicalAnd => "and",
+            Self::LogicalOr => "or",
+            Self::LogicalXor => "xor",
             Self::Match => "match",
+            Self::MethodConstant => "__METHOD__",
             Self::Minus => "-",
             Self::MinusEquals => "-=",
             Self::Namespace => "namespace",
+            Self::NamespaceConstant => "__NAMESPACE__",
             Self::NamespaceSeparator => "\\",
             Self::New => "new",
             Self::Null => "null",
+            Self::Die => "die",
+            Self::Exit => "exit",
+            Self::Print => "print",
             Self::OpenTag(kind) => match kind {
                 OpenTagKind::Full => "<?php",
             },
             Self::Percent => "%",
+            Self::PercentEquals => "%=",
             Self::Pipe => "|",
+            Self::PipeEquals => "|=",
             Self::Plus => "+",
             Self::PlusEquals => "+=",
             Self::Pow => "**",
+            Self::PowEquals => "**=",
             Self::Private => "private",
+            Self::PrivateSet => "private(set)",
             Self::Protected => "protected",
+            Self::ProtectedSet => "protected(set)",
             Self::Public => "public",
+            Self::PublicSet => "public(set)",
             Self::QualifiedIdentifier(id) => &id[..],
             Self::Question => "?",
             Self::QuestionColon => "?:",
+            Self::Readonly => "readonly",
+            Self::RealCast => "(real)",
+            Self::HaltCompiler => "__halt_compiler",
+            Self::HaltCompilerData(_, data) => &data[..],
+            Self::Include => "include",
+            Self::Instanceof => "instanceof",
+            Self::Interface => "interface",
+            Self::IncludeOnce => "include_once",
             Self::Require => "require",
             Self::RequireOnce => "require_once",
             Self::Return => "return",
             Self::RightBrace => "}",
             Self::RightBracket => "]",
             Self::RightParen => ")",
+            Self::RightShift => ">>",
+            Self::RightShiftEquals => ">>=",
             Self::SemiColon => ";",
             Self::Slash => "/",
             Self::SlashEquals => "/=",
+            Self::Spaceship => "<=>",
             Self::Static => "static",
             Self::StringCast => "(string)",
             Self::Switch => "switch",
             Self::Throw => "throw",
+            Self::Tilde => "~",
             Self::Trait => "trait",
+            Self::TraitConstant => "__TRAIT__",
             Self::TripleEquals => "===",
             Self::True => "true",
             Self::Try => "try",
@@ -291,7 +436,6 @@ impl Display for TokenKind {
             Self::Variable(var) => &var[..],
             Self::Yield => "yield",
             Self::While => "while",
-            _ => todo!("format token: {:?}", self)
         })
     }
 }
\ No newline at end of file
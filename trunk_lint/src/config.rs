@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Off,
+    Warning,
+    Error,
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "warning" => Ok(Self::Warning),
+            "error" => Ok(Self::Error),
+            _ => Err(format!("unknown level `{}`, expected `off`, `warning` or `error`", s)),
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Which rules run, at what level, and with what options. Rules that aren't mentioned run at their
+/// default level with their default options.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    pub rules: BTreeMap<String, RuleConfig>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RuleConfig {
+    pub level: Option<Level>,
+    pub options: Map<String, Value>,
+}
+
+impl Config {
+    /// Read a configuration such as:
+    ///
+    /// ```json
+    /// {
+    ///     "rules": {
+    ///         "unused-use": "off",
+    ///         "assignment-in-condition": { "level": "error", "allow-while": true }
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// A rule is given either a level, or an object with an optional level and the rule's options.
+    pub fn from_json(json: &str) -> Result<Self, ConfigError> {
+        let value: Value = serde_json::from_str(json).map_err(|error| ConfigError::Json(error.to_string()))?;
+        let mut config = Config::default();
+
+        let rules = match value.get("rules") {
+            Some(Value::Object(rules)) => rules,
+            Some(_) => return Err(ConfigError::Json("`rules` must be an object".into())),
+            None => return Ok(config),
+        };
+
+        for (name, value) in rules {
+            let invalid = |message: String| ConfigError::InvalidOption { rule: name.clone(), message };
+
+            let rule = match value {
+                Value::String(level) => RuleConfig { level: Some(level.parse().map_err(invalid)?), options: Map::new() },
+                Value::Object(options) => {
+                    let mut options = options.clone();
+
+                    let level = match options.remove("level") {
+                        Some(Value::String(level)) => Some(level.parse().map_err(invalid)?),
+                        Some(_) => return Err(invalid("`level` must be a string".into())),
+                        None => None,
+                    };
+
+                    RuleConfig { level, options }
+                },
+                _ => return Err(invalid("expected a level or an object of options".into())),
+            };
+
+            config.rules.insert(name.clone(), rule);
+        }
+
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    Json(String),
+    UnknownRule(String),
+    InvalidOption { rule: String, message: String },
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Json(message) => write!(f, "invalid configuration: {}", message),
+            Self::UnknownRule(rule) => write!(f, "unknown rule `{}`", rule),
+            Self::InvalidOption { rule, message } => write!(f, "invalid configuration for `{}`: {}", rule, message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, ConfigError, Level};
+
+    #[test]
+    fn parsing() {
+        let config = Config::from_json(r#"{ "rules": { "a": "off", "b": { "level": "error", "x": 1 }, "c": {} } }"#).unwrap();
+
+        assert_eq!(config.rules["a"].level, Some(Level::Off));
+        assert_eq!(config.rules["b"].level, Some(Level::Error));
+        assert_eq!(config.rules["b"].options["x"], 1);
+        assert_eq!(config.rules["c"].level, None);
+
+        assert_eq!(Config::from_json("{}"), Ok(Config::default()));
+        assert!(matches!(Config::from_json("{"), Err(ConfigError::Json(_))));
+        assert!(matches!(Config::from_json(r#"{ "rules": { "a": "loud" } }"#), Err(ConfigError::InvalidOption { .. })));
+        assert!(matches!(Config::from_json(r#"{ "rules": { "a": 1 } }"#), Err(ConfigError::InvalidOption { .. })));
+    }
+}
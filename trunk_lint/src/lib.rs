@@ -0,0 +1,200 @@
+use trunk_lexer::{Span, Token};
+use trunk_parser::{Diagnostic, Program, Severity};
+
+mod config;
+mod rules;
+mod suppressions;
+
+pub use config::{Config, ConfigError, Level, RuleConfig};
+pub use rules::{rules, AssignmentInCondition, MissingVisibility, UnusedUse};
+
+use suppressions::Suppressions;
+
+/// A check that looks for one kind of problem in a program. Most rules implement
+/// `trunk_parser::Visitor` and walk `Context::program` with it.
+pub trait Rule {
+    /// The rule's name in kebab case, as used in configuration and suppression comments.
+    fn name(&self) -> &'static str;
+
+    /// A one line description of what the rule looks for.
+    fn description(&self) -> &'static str;
+
+    /// The level that the rule runs at unless it's configured otherwise.
+    fn default_level(&self) -> Level {
+        Level::Warning
+    }
+
+    /// Apply the rule's options from the configuration. Rules without options reject any.
+    fn configure(&mut self, options: &serde_json::Map<String, serde_json::Value>) -> Result<(), String> {
+        match options.keys().next() {
+            Some(option) => Err(format!("unknown option `{}`", option)),
+            None => Ok(()),
+        }
+    }
+
+    fn check(&self, context: &mut Context);
+}
+
+/// What a rule is given to check, and where it reports what it finds.
+pub struct Context<'a> {
+    pub program: &'a Program,
+    /// The tokens that the program was parsed from, including comments.
+    pub tokens: &'a [Token],
+    rule: &'static str,
+    level: Level,
+    lints: Vec<Lint>,
+}
+
+impl<'a> Context<'a> {
+    pub fn report(&mut self, span: Span, message: impl Into<String>) {
+        self.lints.push(Lint { rule: self.rule, level: self.level, message: message.into(), span });
+    }
+}
+
+/// A problem found by a rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    pub rule: &'static str,
+    /// Either `Level::Warning` or `Level::Error`, since rules that are off don't run.
+    pub level: Level,
+    pub message: String,
+    pub span: Span,
+}
+
+impl From<&Lint> for Diagnostic {
+    fn from(lint: &Lint) -> Self {
+        let severity = if lint.level == Level::Error { Severity::Error } else { Severity::Warning };
+
+        Diagnostic::new(severity, lint.rule, &lint.message, Some(lint.span))
+    }
+}
+
+/// Runs a set of rules over programs, at the levels and with the options from a configuration.
+pub struct Linter {
+    rules: Vec<(Box<dyn Rule>, Level)>,
+}
+
+impl Linter {
+    /// A linter with every built-in rule.
+    pub fn new(config: &Config) -> Result<Self, ConfigError> {
+        Self::with_rules(rules(), config)
+    }
+
+    /// A linter with a custom set of rules, such as the built-in rules along with some of your own.
+    pub fn with_rules(rules: Vec<Box<dyn Rule>>, config: &Config) -> Result<Self, ConfigError> {
+        if let Some(name) = config.rules.keys().find(|name| ! rules.iter().any(|rule| rule.name() == name.as_str())) {
+            return Err(ConfigError::UnknownRule(name.clone()));
+        }
+
+        let mut configured = Vec::new();
+
+        for mut rule in rules {
+            let level = match config.rules.get(rule.name()) {
+                Some(rule_config) => {
+                    rule.configure(&rule_config.options)
+                        .map_err(|message| ConfigError::InvalidOption { rule: rule.name().into(), message })?;
+
+                    rule_config.level.unwrap_or_else(|| rule.default_level())
+                },
+                None => rule.default_level(),
+            };
+
+            if level != Level::Off {
+                configured.push((rule, level));
+            }
+        }
+
+        Ok(Self { rules: configured })
+    }
+
+    /// Check a program, returning what was found in order of where it is in the source. `tokens`
+    /// have to be the tokens that the program was parsed from, since suppression comments are
+    /// read from them.
+    pub fn lint(&self, tokens: &[Token], program: &Program) -> Vec<Lint> {
+        let suppressions = Suppressions::new(tokens);
+        let mut lints = Vec::new();
+
+        for (rule, level) in &self.rules {
+            let mut context = Context { program, tokens, rule: rule.name(), level: *level, lints: Vec::new() };
+            rule.check(&mut context);
+
+            lints.extend(context.lints.into_iter().filter(|lint| ! suppressions.suppresses(lint)));
+        }
+
+        lints.sort_by_key(|lint| (lint.span.start, lint.rule));
+        lints
+    }
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::new(&Config::default()).expect("the default configuration is valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_lexer::Lexer;
+    use trunk_parser::Parser;
+
+    use super::{Config, ConfigError, Level, Lint, Linter};
+
+    pub(crate) fn lint(linter: &Linter, source: &str) -> Vec<Lint> {
+        let tokens = Lexer::new(None).tokenize(source).unwrap();
+        let program = Parser::new(None).parse(&tokens).unwrap();
+
+        linter.lint(&tokens, &program)
+    }
+
+    fn rules(lints: &[Lint]) -> Vec<(&str, usize)> {
+        lints.iter().map(|lint| (lint.rule, lint.span.line)).collect()
+    }
+
+    const SOURCE: &str = "<?php
+use Foo\\Bar;
+
+if ($a = 1) {}
+
+class A {
+    function b() {}
+}
+";
+
+    #[test]
+    fn configuration() {
+        assert_eq!(rules(&lint(&Linter::default(), SOURCE)), [("unused-use", 2), ("assignment-in-condition", 4), ("missing-visibility", 7)]);
+
+        let config = Config::from_json(r#"{ "rules": { "unused-use": "off", "missing-visibility": { "level": "error" } } }"#).unwrap();
+        let lints = lint(&Linter::new(&config).unwrap(), SOURCE);
+
+        assert_eq!(rules(&lints), [("assignment-in-condition", 4), ("missing-visibility", 7)]);
+        assert_eq!(lints.iter().map(|lint| lint.level).collect::<Vec<_>>(), [Level::Warning, Level::Error]);
+
+        let unknown = Config::from_json(r#"{ "rules": { "unused-imports": "off" } }"#).unwrap();
+        assert!(matches!(Linter::new(&unknown), Err(ConfigError::UnknownRule(rule)) if rule == "unused-imports"));
+
+        let option = Config::from_json(r#"{ "rules": { "unused-use": { "strict": true } } }"#).unwrap();
+        assert!(matches!(Linter::new(&option), Err(ConfigError::InvalidOption { .. })));
+    }
+
+    #[test]
+    fn suppressions() {
+        let source = "<?php
+use Foo\\Bar; // trunk-ignore unused-use
+
+// trunk-ignore
+if ($a = 1) {}
+
+class A {
+    /* trunk-ignore assignment-in-condition */
+    function b() {}
+}
+";
+
+        assert_eq!(rules(&lint(&Linter::default(), source)), [("missing-visibility", 9)]);
+
+        let source = format!("{}// trunk-ignore-file missing-visibility, unused-use\n", SOURCE);
+
+        assert_eq!(rules(&lint(&Linter::default(), &source)), [("assignment-in-condition", 4)]);
+    }
+}
@@ -0,0 +1,107 @@
+use serde_json::{Map, Value};
+use trunk_lexer::Span;
+use trunk_parser::{walk_statement, Expression, ExpressionKind, Statement, StatementKind, Visitor};
+
+use crate::{Context, Rule};
+
+/// Assignments used directly as the condition of an `if`, `elseif` or `while`, which are usually a
+/// typo for `==`. With the `allow-while` option, `while ($row = next())` is allowed.
+#[derive(Debug, Default)]
+pub struct AssignmentInCondition {
+    allow_while: bool,
+}
+
+impl Rule for AssignmentInCondition {
+    fn name(&self) -> &'static str {
+        "assignment-in-condition"
+    }
+
+    fn description(&self) -> &'static str {
+        "assignments used as the condition of an `if`, `elseif` or `while`"
+    }
+
+    fn configure(&mut self, options: &Map<String, Value>) -> Result<(), String> {
+        for (option, value) in options {
+            match (option.as_str(), value) {
+                ("allow-while", Value::Bool(allow)) => self.allow_while = *allow,
+                ("allow-while", _) => return Err("`allow-while` must be a boolean".into()),
+                (option, _) => return Err(format!("unknown option `{}`", option)),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check(&self, context: &mut Context) {
+        let mut conditions = Conditions { allow_while: self.allow_while, assignments: Vec::new() };
+        conditions.visit_program(context.program);
+
+        for span in conditions.assignments {
+            context.report(span, "assignment used as a condition, did you mean `==`?");
+        }
+    }
+}
+
+struct Conditions {
+    allow_while: bool,
+    assignments: Vec<Span>,
+}
+
+impl Conditions {
+    fn condition(&mut self, condition: &Expression) {
+        if is_assignment(condition) {
+            self.assignments.push(condition.span);
+        }
+    }
+}
+
+impl Visitor for Conditions {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::If { condition, else_ifs, .. } => {
+                self.condition(condition);
+
+                for else_if in else_ifs {
+                    self.condition(&else_if.condition);
+                }
+            },
+            StatementKind::While { condition, .. } if ! self.allow_while => self.condition(condition),
+            _ => {},
+        }
+
+        walk_statement(self, statement);
+    }
+}
+
+fn is_assignment(expression: &Expression) -> bool {
+    match &expression.kind {
+        ExpressionKind::Assign { .. } | ExpressionKind::AssignRef { .. } | ExpressionKind::CoalesceAssign { .. } => true,
+        ExpressionKind::Infix { op, .. } => op.is_assignment(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Config, Linter};
+    use crate::tests::lint;
+
+    #[test]
+    fn assignments() {
+        let source = "<?php
+if ($a = 1) {} elseif ($b .= 'c') {} elseif ($a == 1) {}
+while ($row = next()) {
+    if (($c = 1) && $c) {}
+}
+";
+        let lines = |config: &str| -> Vec<usize> {
+            let linter = Linter::new(&Config::from_json(config).unwrap()).unwrap();
+
+            lint(&linter, source).iter().filter(|lint| lint.rule == "assignment-in-condition").map(|lint| lint.span.line).collect()
+        };
+
+        assert_eq!(lines("{}"), [2, 2, 3]);
+        assert_eq!(lines(r#"{ "rules": { "assignment-in-condition": { "allow-while": true } } }"#), [2, 2]);
+        assert!(Linter::new(&Config::from_json(r#"{ "rules": { "assignment-in-condition": { "allow-while": 1 } } }"#).unwrap()).is_err());
+    }
+}
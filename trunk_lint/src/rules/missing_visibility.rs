@@ -0,0 +1,110 @@
+use trunk_lexer::Span;
+use trunk_parser::{walk_expression, walk_statement, ConstFlag, Expression, ExpressionKind, MethodFlag, PropertyFlag, Statement, StatementKind, Visitor};
+
+use crate::{Context, Rule};
+
+/// Methods, properties and constants of classes, traits, interfaces and enums that don't say
+/// whether they're public, protected or private, as PSR-12 requires.
+#[derive(Debug, Default)]
+pub struct MissingVisibility;
+
+impl Rule for MissingVisibility {
+    fn name(&self) -> &'static str {
+        "missing-visibility"
+    }
+
+    fn description(&self) -> &'static str {
+        "class members declared without `public`, `protected` or `private`"
+    }
+
+    fn check(&self, context: &mut Context) {
+        let mut members = Members { missing: Vec::new() };
+        members.visit_program(context.program);
+
+        for (span, message) in members.missing {
+            context.report(span, message);
+        }
+    }
+}
+
+struct Members {
+    missing: Vec<(Span, String)>,
+}
+
+impl Members {
+    fn body(&mut self, body: &[Statement]) {
+        for member in body {
+            let missing = match &member.kind {
+                StatementKind::Method { name, flags, .. }
+                    if ! flags.iter().any(|flag| matches!(flag, MethodFlag::Public | MethodFlag::Protected | MethodFlag::Private)) =>
+                    format!("method `{}` has no visibility", name.name),
+                StatementKind::Property { var, flags, .. }
+                    if ! flags.iter().any(|flag| ! matches!(flag, PropertyFlag::Static)) =>
+                    format!("property `${}` has no visibility", var),
+                StatementKind::Var { var, .. } => format!("property `${}` is declared with `var` instead of a visibility", var),
+                StatementKind::Constant { name, flags, .. }
+                    if ! flags.iter().any(|flag| matches!(flag, ConstFlag::Public | ConstFlag::Protected | ConstFlag::Private)) =>
+                    format!("constant `{}` has no visibility", name.name),
+                _ => continue,
+            };
+
+            self.missing.push((member.span, missing));
+        }
+    }
+}
+
+impl Visitor for Members {
+    fn visit_statement(&mut self, statement: &Statement) {
+        if let StatementKind::Class { body, .. } | StatementKind::Trait { body, .. } | StatementKind::Interface { body, .. } |
+            StatementKind::Enum { body, .. } = &statement.kind {
+            self.body(body);
+        }
+
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        if let ExpressionKind::AnonymousClass { body, .. } = &expression.kind {
+            self.body(body);
+        }
+
+        walk_expression(self, expression);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Linter;
+    use crate::tests::lint;
+
+    #[test]
+    fn members() {
+        let source = "<?php
+class A {
+    const B = 1;
+    public const C = 1;
+    var $d;
+    static $e;
+    public static $f;
+    private(set) string $g;
+    static function h() {}
+    final protected function i() {}
+}
+interface J { function k(); }
+$l = new class { function m() { return new class { private $n; }; } };
+";
+        let messages: Vec<(usize, String)> = lint(&Linter::default(), source).into_iter()
+            .filter(|lint| lint.rule == "missing-visibility")
+            .map(|lint| (lint.span.line, lint.message))
+            .collect();
+
+        assert_eq!(messages, [
+            (3, "constant `B` has no visibility".to_string()),
+            (5, "property `$d` is declared with `var` instead of a visibility".to_string()),
+            (6, "property `$e` has no visibility".to_string()),
+            (9, "method `h` has no visibility".to_string()),
+            (12, "method `k` has no visibility".to_string()),
+            (13, "method `m` has no visibility".to_string()),
+        ]);
+    }
+}
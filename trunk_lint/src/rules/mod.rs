@@ -0,0 +1,18 @@
+use crate::Rule;
+
+mod assignment_in_condition;
+mod missing_visibility;
+mod unused_use;
+
+pub use assignment_in_condition::AssignmentInCondition;
+pub use missing_visibility::MissingVisibility;
+pub use unused_use::UnusedUse;
+
+/// Every built-in rule, with its default options.
+pub fn rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::<UnusedUse>::default(),
+        Box::<AssignmentInCondition>::default(),
+        Box::<MissingVisibility>::default(),
+    ]
+}
@@ -0,0 +1,215 @@
+use std::collections::HashSet;
+
+use trunk_lexer::TokenKind;
+use trunk_parser::{
+    walk_expression, walk_statement, AttributeGroup, Expression, ExpressionKind, Identifier, Param, Statement, StatementKind,
+    Type, Visitor,
+};
+
+use crate::{Context, Rule};
+
+/// `use` statements that import a class, function or constant which is never mentioned. Names in
+/// doc comments count as uses, since they're often only imported for `@param` and `@return` tags.
+#[derive(Debug, Default)]
+pub struct UnusedUse;
+
+impl Rule for UnusedUse {
+    fn name(&self) -> &'static str {
+        "unused-use"
+    }
+
+    fn description(&self) -> &'static str {
+        "`use` statements that import a name which is never used"
+    }
+
+    fn check(&self, context: &mut Context) {
+        let mut names = Names { imports: Vec::new(), used: HashSet::new() };
+        names.visit_program(context.program);
+
+        for token in context.tokens {
+            if let TokenKind::DocComment(comment) = &token.kind {
+                for word in comment.split(|c: char| ! (c.is_alphanumeric() || c == '_' || c == '\\')) {
+                    names.used(word);
+                }
+            }
+        }
+
+        for (import, alias) in &names.imports {
+            if ! names.used.contains(&alias.to_lowercase()) {
+                let name = import.name.trim_start_matches('\\');
+
+                let message = if name.rsplit('\\').next() == Some(alias.as_str()) {
+                    format!("`{}` is imported but never used", name)
+                } else {
+                    format!("`{}` is imported as `{}` but never used", name, alias)
+                };
+
+                context.report(import.span, message);
+            }
+        }
+    }
+}
+
+struct Names {
+    /// Each imported name, along with the name that it's imported as.
+    imports: Vec<(Identifier, String)>,
+    /// The lowercased first part of every name that's used, other than fully qualified ones.
+    used: HashSet<String>,
+}
+
+impl Names {
+    fn used(&mut self, name: &str) {
+        if name.is_empty() || name.starts_with('\\') {
+            return;
+        }
+
+        let first = name.split('\\').next().unwrap_or(name);
+        self.used.insert(first.to_lowercase());
+    }
+
+    fn identifiers<'a>(&mut self, identifiers: impl IntoIterator<Item = &'a Identifier>) {
+        for identifier in identifiers {
+            self.used(&identifier.name);
+        }
+    }
+
+    fn r#type(&mut self, r#type: &Option<Type>) {
+        fn names<'a>(r#type: &'a Type, found: &mut Vec<&'a str>) {
+            match r#type {
+                Type::Plain(name) | Type::Nullable(name) => found.push(name),
+                Type::Union(types) | Type::Intersection(types) => types.iter().for_each(|r#type| names(r#type, found)),
+            }
+        }
+
+        let mut found = Vec::new();
+
+        if let Some(r#type) = r#type {
+            names(r#type, &mut found);
+        }
+
+        for name in found {
+            self.used(name);
+        }
+    }
+
+    fn attributes(&mut self, attributes: &[AttributeGroup]) {
+        self.identifiers(attributes.iter().flat_map(|group| &group.members).map(|attribute| &attribute.name));
+    }
+
+    fn params(&mut self, params: &[Param]) {
+        for param in params {
+            self.r#type(&param.r#type);
+            self.attributes(&param.attributes);
+        }
+    }
+}
+
+impl Visitor for Names {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match &statement.kind {
+            StatementKind::Use { uses, .. } => {
+                for r#use in uses {
+                    let alias = match &r#use.alias {
+                        Some(alias) => alias.name.clone(),
+                        None => r#use.name.name.rsplit('\\').next().unwrap_or_default().to_string(),
+                    };
+
+                    self.imports.push((r#use.name.clone(), alias));
+                }
+            },
+            StatementKind::Function { params, return_type, attributes, .. } | StatementKind::Method { params, return_type, attributes, .. } => {
+                self.params(params);
+                self.r#type(return_type);
+                self.attributes(attributes);
+            },
+            StatementKind::Property { r#type, attributes, .. } | StatementKind::Var { r#type, attributes, .. } => {
+                self.r#type(r#type);
+                self.attributes(attributes);
+            },
+            StatementKind::Class { extends, implements, attributes, .. } => {
+                self.identifiers(extends.iter().chain(implements));
+                self.attributes(attributes);
+            },
+            StatementKind::Interface { extends, attributes, .. } | StatementKind::Enum { implements: extends, attributes, .. } => {
+                self.identifiers(extends);
+                self.attributes(attributes);
+            },
+            StatementKind::Trait { attributes, .. } | StatementKind::Constant { attributes, .. } | StatementKind::EnumCase { attributes, .. } => {
+                self.attributes(attributes);
+            },
+            StatementKind::TraitUse { traits } => self.identifiers(traits),
+            StatementKind::Try { catches, .. } => self.identifiers(catches.iter().flat_map(|catch| &catch.types)),
+            _ => {},
+        }
+
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        match &expression.kind {
+            ExpressionKind::Identifier { name } => self.used(name),
+            ExpressionKind::Closure { params, return_type, attributes, .. } | ExpressionKind::ArrowFunction { params, return_type, attributes, .. } => {
+                self.params(params);
+                self.r#type(return_type);
+                self.attributes(attributes);
+            },
+            ExpressionKind::AnonymousClass { extends, implements, attributes, .. } => {
+                self.identifiers(extends.iter().chain(implements));
+                self.attributes(attributes);
+            },
+            _ => {},
+        }
+
+        walk_expression(self, expression);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Linter;
+    use crate::tests::lint;
+
+    fn unused(source: &str) -> Vec<String> {
+        lint(&Linter::default(), source).into_iter()
+            .filter(|lint| lint.rule == "unused-use")
+            .map(|lint| lint.message)
+            .collect()
+    }
+
+    #[test]
+    fn imports() {
+        assert_eq!(unused("<?php
+use App\\Models\\User;
+use App\\Models\\Post as Article;
+use App\\Contracts;
+use App\\Unused;
+use App\\Unused as Aliased;
+use function App\\helper;
+use const App\\LIMIT;
+
+/** @return Documented */
+function a(user $u, ?Article ...$b): Contracts\\Repository {
+    helper(LIMIT);
+    return \\App\\Unused::find();
+}
+"), ["`App\\Unused` is imported but never used", "`App\\Unused` is imported as `Aliased` but never used"]);
+
+        assert!(unused("<?php
+use A\\B, A\\C, A\\D, A\\E, A\\F, A\\G, A\\H, A\\Documented;
+
+#[B]
+class X extends C implements D {
+    use E;
+
+    public function x() {
+        try { new F; } catch (G $g) {}
+
+        return fn (): H => null;
+    }
+}
+
+/** @var Documented */
+$a = 1;
+").is_empty());
+    }
+}
@@ -0,0 +1,50 @@
+use trunk_lexer::{Token, TokenKind};
+
+use crate::Lint;
+
+/// The lints that comments ask to be left out. `// trunk-ignore` suppresses lints that start on
+/// the comment's last line or the line after it, and `// trunk-ignore-file` suppresses them in the
+/// whole file. Either can be followed by the names of the rules to suppress, separated by spaces or
+/// commas, or suppresses every rule without them.
+pub struct Suppressions {
+    lines: Vec<(usize, Vec<String>)>,
+    file: Vec<Vec<String>>,
+}
+
+impl Suppressions {
+    pub fn new(tokens: &[Token]) -> Self {
+        let mut suppressions = Self { lines: Vec::new(), file: Vec::new() };
+
+        for token in tokens {
+            let TokenKind::Comment(comment) = &token.kind else { continue };
+
+            let text = comment.trim_start_matches("//").trim_start_matches('#').trim_start_matches("/*").trim_end_matches("*/").trim();
+            let rules = |rest: &str| -> Vec<String> {
+                rest.split(|c: char| c == ',' || c.is_whitespace()).filter(|rule| ! rule.is_empty()).map(String::from).collect()
+            };
+
+            if let Some(rest) = directive(text, "trunk-ignore-file") {
+                suppressions.file.push(rules(rest));
+            } else if let Some(rest) = directive(text, "trunk-ignore") {
+                let line = token.span.line + comment.matches('\n').count();
+                suppressions.lines.push((line, rules(rest)));
+            }
+        }
+
+        suppressions
+    }
+
+    pub fn suppresses(&self, lint: &Lint) -> bool {
+        let applies = |rules: &Vec<String>| rules.is_empty() || rules.iter().any(|rule| rule == lint.rule);
+
+        self.file.iter().any(applies)
+            || self.lines.iter().any(|(line, rules)| (*line == lint.span.line || line + 1 == lint.span.line) && applies(rules))
+    }
+}
+
+/// The text after a directive at the start of a comment, as long as the directive is a whole word.
+fn directive<'a>(text: &'a str, directive: &str) -> Option<&'a str> {
+    let rest = text.strip_prefix(directive)?;
+
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then_some(rest)
+}
@@ -1,25 +1,55 @@
-use serde::Serialize;
-use trunk_lexer::TokenKind;
+use std::hash::{Hash, Hasher};
+#[cfg(feature = "serde")]
+use std::collections::BTreeMap;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
+use serde_json::Value;
+use trunk_lexer::{Float, TokenKind, Span};
 
 pub type Block = Vec<Statement>;
 pub type Program = Block;
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Type {
     Plain(String),
     Nullable(String),
-    Union(Vec<String>),
-    Intersection(Vec<String>),
+    Union(Vec<Type>),
+    Intersection(Vec<Type>),
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Identifier {
     pub name: String,
+    pub span: Span,
+}
+
+impl Identifier {
+    pub fn new(name: impl Into<String>, span: Span) -> Self {
+        Self { name: name.into(), span }
+    }
+}
+
+impl PartialEq for Identifier {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for Identifier {}
+
+impl Hash for Identifier {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
 }
 
 impl From<String> for Identifier {
     fn from(name: String) -> Self {
-        Self { name }
+        Self { name, span: Span::default() }
     }
 }
 
@@ -35,20 +65,94 @@ impl From<&str> for Identifier {
     }
 }
 
+/// The byte offset of the data after `__halt_compiler();`, which PHP makes available as
+/// `__COMPILER_HALT_OFFSET__`, if the program has any.
+pub fn halt_offset(program: &Program) -> Option<usize> {
+    match program.last() {
+        Some(Statement { kind: StatementKind::HaltCompiler { offset, .. }, .. }) => Some(*offset),
+        _ => None,
+    }
+}
+
+/// The rest of a name written relative to the current namespace, such as `Foo` in `namespace\Foo`.
+pub fn relative_name(name: &str) -> Option<&str> {
+    let prefix = name.get(..10)?;
+
+    if prefix.eq_ignore_ascii_case("namespace\\") && name.len() > 10 {
+        Some(&name[10..])
+    } else {
+        None
+    }
+}
+
 pub type ParamList = Vec<Param>;
 
-#[derive(Debug, PartialEq, Clone, Serialize)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Param {
     pub name: Expression,
     pub r#type: Option<Type>,
+    pub by_ref: bool,
     pub variadic: bool,
     pub default: Option<Expression>,
-    pub flag: Option<PropertyFlag>,
+    pub flags: Vec<PropertyFlag>,
+    pub attributes: Vec<AttributeGroup>,
+    pub span: Span,
+}
+
+impl Param {
+    /// An untyped parameter without a default value, such as `$name`.
+    pub fn new(name: impl Into<String>, span: Span) -> Self {
+        Self {
+            name: Expression::new(ExpressionKind::Variable { name: name.into() }, span),
+            r#type: None,
+            by_ref: false,
+            variadic: false,
+            default: None,
+            flags: vec![],
+            attributes: vec![],
+            span,
+        }
+    }
+
+    /// The name of the parameter's variable, without the leading `$`.
+    pub fn variable_name(&self) -> Option<&str> {
+        match &self.name.kind {
+            ExpressionKind::Variable { name } => Some(name),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for Param {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.r#type == other.r#type
+            && self.by_ref == other.by_ref
+            && self.variadic == other.variadic
+            && self.default == other.default
+            && self.flags == other.flags
+            && self.attributes == other.attributes
+    }
+}
+
+impl Eq for Param {}
+
+impl Hash for Param {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.r#type.hash(state);
+        self.by_ref.hash(state);
+        self.variadic.hash(state);
+        self.default.hash(state);
+        self.flags.hash(state);
+        self.attributes.hash(state);
+    }
 }
 
 impl From<String> for Param {
     fn from(name: String) -> Self {
-        Self { name: Expression::Variable { name }, r#type: None, variadic: false, default: None, flag: None }
+        Self::new(name, Span::default())
     }
 }
 
@@ -64,27 +168,59 @@ impl From<&str> for Param {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StringPart {
+    Const(String),
+    Expr(Box<Expression>),
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Attribute {
+    pub name: Identifier,
+    pub args: Vec<Arg>,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AttributeGroup {
+    pub members: Vec<Attribute>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PropertyFlag {
     Public,
     Protected,
     Private,
+    PublicSet,
+    ProtectedSet,
+    PrivateSet,
     Static,
+    Readonly,
 }
 
-impl From<TokenKind> for PropertyFlag {
-    fn from(k: TokenKind) -> Self {
+impl TryFrom<TokenKind> for PropertyFlag {
+    type Error = TokenKind;
+
+    fn try_from(k: TokenKind) -> Result<Self, Self::Error> {
         match k {
-            TokenKind::Public => Self::Public,
-            TokenKind::Protected => Self::Protected,
-            TokenKind::Private => Self::Private,
-            TokenKind::Static => Self::Static,
-            _ => unreachable!("token {:?} can't be converted into property flag.", k),
+            TokenKind::Public => Ok(Self::Public),
+            TokenKind::Protected => Ok(Self::Protected),
+            TokenKind::Private => Ok(Self::Private),
+            TokenKind::PublicSet => Ok(Self::PublicSet),
+            TokenKind::ProtectedSet => Ok(Self::ProtectedSet),
+            TokenKind::PrivateSet => Ok(Self::PrivateSet),
+            TokenKind::Static => Ok(Self::Static),
+            TokenKind::Readonly => Ok(Self::Readonly),
+            _ => Err(k),
         }
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum MethodFlag {
     Final,
     Abstract,
@@ -94,55 +230,176 @@ pub enum MethodFlag {
     Static,
 }
 
-impl From<TokenKind> for MethodFlag {
-    fn from(k: TokenKind) -> Self {
+impl TryFrom<TokenKind> for MethodFlag {
+    type Error = TokenKind;
+
+    fn try_from(k: TokenKind) -> Result<Self, Self::Error> {
         match k {
-            TokenKind::Final => Self::Final,
-            TokenKind::Abstract => Self::Abstract,
-            TokenKind::Public => Self::Public,
-            TokenKind::Protected => Self::Protected,
-            TokenKind::Private => Self::Private,
-            TokenKind::Static => Self::Static,
-            _ => unreachable!("token {:?} can't be converted into method flag.", k),
+            TokenKind::Final => Ok(Self::Final),
+            TokenKind::Abstract => Ok(Self::Abstract),
+            TokenKind::Public => Ok(Self::Public),
+            TokenKind::Protected => Ok(Self::Protected),
+            TokenKind::Private => Ok(Self::Private),
+            TokenKind::Static => Ok(Self::Static),
+            _ => Err(k),
         }
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ClassFlag {
     Final,
     Abstract,
 }
 
-impl From<TokenKind> for ClassFlag {
-    fn from(k: TokenKind) -> Self {
+impl TryFrom<TokenKind> for ClassFlag {
+    type Error = TokenKind;
+
+    fn try_from(k: TokenKind) -> Result<Self, Self::Error> {
         match k {
-            TokenKind::Final => Self::Final,
-            TokenKind::Abstract => Self::Abstract,
-            _ => unreachable!("token {:?} can't be converted into class flag.", k),
+            TokenKind::Final => Ok(Self::Final),
+            TokenKind::Abstract => Ok(Self::Abstract),
+            _ => Err(k),
         }
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum UseKind {
     Normal,
     Function,
     Const,
 }
 
-#[derive(Debug, PartialEq, Clone, Serialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct StaticVar {
     pub var: Expression,
     pub default: Option<Expression>,
 }
 
-#[derive(Debug, PartialEq, Clone, Serialize)]
-pub enum Statement {
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Constant {
+    pub name: Identifier,
+    pub value: Expression,
+}
+
+/// A `get` or `set` hook on a property, such as `get => $this->a;`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PropertyHook {
+    pub name: Identifier,
+    pub params: ParamList,
+    /// `None` for a hook that's only declared, such as `get;` in an interface.
+    pub body: Option<PropertyHookBody>,
+    pub by_ref: bool,
+    pub flags: Vec<MethodFlag>,
+    pub attributes: Vec<AttributeGroup>,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PropertyHookBody {
+    /// The short form, such as `=> $this->a`.
+    Expression(Expression),
+    Block(Block),
+}
+
+/// Annotations that passes outside of the parser attach to a node, such as a resolved name or an
+/// inferred type. The parser never sets any, and they're ignored when comparing nodes.
+///
+/// Values are JSON values, so metadata is only available with the `serde` feature.
+#[cfg(feature = "serde")]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Metadata(BTreeMap<String, Value>);
+
+#[cfg(feature = "serde")]
+impl Metadata {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0.get(key)
+    }
+
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<Value>) -> Option<Value> {
+        self.0.insert(key.into(), value.into())
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        self.0.remove(key)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.0.contains_key(key)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.0.iter()
+    }
+}
+
+// Nodes are compared and hashed by their kind only, so that trees parsed from differently formatted code compare equal.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+    #[cfg(feature = "serde")]
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    pub meta: Metadata,
+}
+
+impl Statement {
+    pub fn new(kind: StatementKind, span: Span) -> Self {
+        Self {
+            kind,
+            span,
+            #[cfg(feature = "serde")]
+            meta: Metadata::default(),
+        }
+    }
+}
+
+impl PartialEq for Statement {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl Eq for Statement {}
+
+impl Hash for Statement {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.kind.hash(state);
+    }
+}
+
+impl From<StatementKind> for Statement {
+    fn from(kind: StatementKind) -> Self {
+        Self::new(kind, Span::default())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StatementKind {
     InlineHtml(String),
     Static {
         vars: Vec<StaticVar>,
     },
+    Global {
+        vars: Vec<Expression>,
+    },
+    Const {
+        constants: Vec<Constant>,
+        doc_comment: Option<String>,
+    },
     While {
         condition: Expression,
         body: Block,
@@ -160,33 +417,36 @@ pub enum Statement {
         value_var: Expression,
         body: Block,
     },
-    Require {
-        path: Expression,
-    },
-    RequireOnce {
-        path: Expression,
-    },
     Var {
         var: String,
         value: Option<Expression>,
         r#type: Option<Type>,
+        attributes: Vec<AttributeGroup>,
     },
     Property {
         var: String,
         value: Option<Expression>,
         r#type: Option<Type>,
         flags: Vec<PropertyFlag>,
+        hooks: Vec<PropertyHook>,
+        attributes: Vec<AttributeGroup>,
+        doc_comment: Option<String>,
     },
     Constant {
         name: Identifier,
         value: Expression,
         flags: Vec<ConstFlag>,
+        attributes: Vec<AttributeGroup>,
+        doc_comment: Option<String>,
     },
     Function {
         name: Identifier,
         params: Vec<Param>,
         body: Block,
         return_type: Option<Type>,
+        returns_by_ref: bool,
+        attributes: Vec<AttributeGroup>,
+        doc_comment: Option<String>,
     },
     Class {
         name: Identifier,
@@ -194,10 +454,13 @@ pub enum Statement {
         implements: Vec<Identifier>,
         body: Block,
         flag: Option<ClassFlag>,
+        attributes: Vec<AttributeGroup>,
+        doc_comment: Option<String>,
     },
     Trait {
         name: Identifier,
         body: Block,
+        attributes: Vec<AttributeGroup>,
     },
     TraitUse {
         traits: Vec<Identifier>,
@@ -206,6 +469,7 @@ pub enum Statement {
         name: Identifier,
         extends: Vec<Identifier>,
         body: Block,
+        attributes: Vec<AttributeGroup>,
     },
     Method {
         name: Identifier,
@@ -213,6 +477,9 @@ pub enum Statement {
         body: Block,
         flags: Vec<MethodFlag>,
         return_type: Option<Type>,
+        returns_by_ref: bool,
+        attributes: Vec<AttributeGroup>,
+        doc_comment: Option<String>,
     },
     If {
         condition: Expression,
@@ -260,62 +527,95 @@ pub enum Statement {
         implements: Vec<Identifier>,
         backed_type: Option<BackedEnumType>,
         body: Block,
+        attributes: Vec<AttributeGroup>,
     },
     EnumCase {
         name: Identifier,
         value: Option<Expression>,
+        attributes: Vec<AttributeGroup>,
+    },
+    HaltCompiler {
+        offset: usize,
+        /// Everything after `__halt_compiler();`, such as a phar's archive, which isn't always UTF-8.
+        data: Vec<u8>,
     },
     Noop,
+    Error,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+impl StatementKind {
+    /// The name of a declaration, such as a function, class, property or namespace. Properties are
+    /// named without the leading `$`.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::Function { name, .. } | Self::Class { name, .. } | Self::Trait { name, .. } | Self::Interface { name, .. } |
+            Self::Method { name, .. } | Self::Enum { name, .. } | Self::Constant { name, .. } | Self::EnumCase { name, .. } => Some(&name.name),
+            Self::Property { var, .. } => Some(var),
+            Self::Namespace { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum CastKind {
     String,
     Object,
     Bool,
     Int,
     Double,
+    Array,
 }
 
-impl From<TokenKind> for CastKind {
-    fn from(kind: TokenKind) -> Self {
+impl TryFrom<TokenKind> for CastKind {
+    type Error = TokenKind;
+
+    fn try_from(kind: TokenKind) -> Result<Self, Self::Error> {
         match kind {
-            TokenKind::StringCast => Self::String,
-            TokenKind::ObjectCast => Self::Object,
-            TokenKind::BoolCast => Self::Bool,
-            TokenKind::IntCast => Self::Int,
-            TokenKind::DoubleCast => Self::Double,
-            _ => unreachable!()
+            TokenKind::StringCast => Ok(Self::String),
+            TokenKind::ObjectCast => Ok(Self::Object),
+            TokenKind::BoolCast => Ok(Self::Bool),
+            TokenKind::IntCast => Ok(Self::Int),
+            TokenKind::DoubleCast | TokenKind::RealCast => Ok(Self::Double),
+            TokenKind::ArrayCast => Ok(Self::Array),
+            _ => Err(kind),
         }
     }
 }
 
-impl From<&TokenKind> for CastKind {
-    fn from(kind: &TokenKind) -> Self {
-        kind.clone().into()
+impl TryFrom<&TokenKind> for CastKind {
+    type Error = TokenKind;
+
+    fn try_from(kind: &TokenKind) -> Result<Self, Self::Error> {
+        kind.clone().try_into()
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum BackedEnumType {
     String,
     Int,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Case {
     pub condition: Option<Expression>,
     pub body: Block,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Catch {
     pub types: Vec<Identifier>,
     pub var: Expression,
     pub body: Block,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ConstFlag {
     Final,
     Public,
@@ -323,49 +623,100 @@ pub enum ConstFlag {
     Private,
 }
 
-impl From<TokenKind> for ConstFlag {
-    fn from(k: TokenKind) -> Self {
+impl TryFrom<TokenKind> for ConstFlag {
+    type Error = TokenKind;
+
+    fn try_from(k: TokenKind) -> Result<Self, Self::Error> {
         match k {
-            TokenKind::Final => Self::Final,
-            TokenKind::Public => Self::Public,
-            TokenKind::Protected => Self::Protected,
-            TokenKind::Private => Self::Private,
-            _ => unreachable!("token {:?} can't be converted into const flag.", k),
+            TokenKind::Final => Ok(Self::Final),
+            TokenKind::Public => Ok(Self::Public),
+            TokenKind::Protected => Ok(Self::Protected),
+            TokenKind::Private => Ok(Self::Private),
+            _ => Err(k),
         }
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Use {
     pub name: Identifier,
     pub alias: Option<Identifier>,
 }
 
-#[derive(Debug, PartialEq, Clone, Serialize)]
-pub enum Expression {
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+    #[cfg(feature = "serde")]
+    #[serde(default, skip_serializing_if = "Metadata::is_empty")]
+    pub meta: Metadata,
+}
+
+impl Expression {
+    pub fn new(kind: ExpressionKind, span: Span) -> Self {
+        Self {
+            kind,
+            span,
+            #[cfg(feature = "serde")]
+            meta: Metadata::default(),
+        }
+    }
+}
+
+impl PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl Eq for Expression {}
+
+impl Hash for Expression {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.kind.hash(state);
+    }
+}
+
+impl From<ExpressionKind> for Expression {
+    fn from(kind: ExpressionKind) -> Self {
+        Self::new(kind, Span::default())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ExpressionKind {
     Static,
-    Increment {
-        value: Box<Self>,
+    PreIncrement {
+        value: Box<Expression>,
     },
-    Decrement {
-        value: Box<Self>,
+    PostIncrement {
+        value: Box<Expression>,
+    },
+    PreDecrement {
+        value: Box<Expression>,
+    },
+    PostDecrement {
+        value: Box<Expression>,
     },
     Int {
         i: i64
     },
     Float {
-        f: f64,
+        f: Float,
     },
     Variable {
         name: String
     },
     Infix {
-        lhs: Box<Self>,
+        lhs: Box<Expression>,
         op: InfixOp,
-        rhs: Box<Self>
+        rhs: Box<Expression>
     },
     Call {
-        target: Box<Self>,
+        target: Box<Expression>,
         args: Vec<Arg>
     },
     Identifier {
@@ -378,77 +729,126 @@ pub enum Expression {
         params: Vec<Param>,
         uses: Vec<ClosureUse>,
         return_type: Option<Type>,
-        body: Block
+        returns_by_ref: bool,
+        body: Block,
+        attributes: Vec<AttributeGroup>,
     },
     ArrowFunction {
         params: Vec<Param>,
         return_type: Option<Type>,
-        expr: Box<Self>
+        returns_by_ref: bool,
+        expr: Box<Expression>,
+        attributes: Vec<AttributeGroup>,
     },
     New {
-        target: Box<Self>,
+        target: Box<Expression>,
         args: Vec<Arg>,
     },
     ConstantString {
         value: String,
     },
+    InterpolatedString {
+        parts: Vec<StringPart>,
+    },
+    Heredoc {
+        label: String,
+        parts: Vec<StringPart>,
+    },
+    Nowdoc {
+        label: String,
+        value: String,
+    },
     PropertyFetch {
-        target: Box<Self>,
-        property: Box<Self>,
+        target: Box<Expression>,
+        property: Box<Expression>,
     },
     NullsafePropertyFetch {
-        target: Box<Self>,
-        property: Box<Self>,
+        target: Box<Expression>,
+        property: Box<Expression>,
     },
     StaticPropertyFetch {
-        target: Box<Self>,
-        property: Box<Self>
+        target: Box<Expression>,
+        property: Box<Expression>
+    },
+    Dynamic {
+        value: Box<Expression>,
     },
     ConstFetch {
-        target: Box<Self>,
+        target: Box<Expression>,
         constant: Identifier
     },
+    DynamicConstFetch {
+        target: Box<Expression>,
+        constant: Box<Expression>,
+    },
     MethodCall {
-        target: Box<Self>,
-        method: Box<Self>,
+        target: Box<Expression>,
+        method: Box<Expression>,
+        args: Vec<Arg>
+    },
+    NullsafeMethodCall {
+        target: Box<Expression>,
+        method: Box<Expression>,
         args: Vec<Arg>
     },
     StaticMethodCall {
-        target: Box<Self>,
+        target: Box<Expression>,
         method: Identifier,
         args: Vec<Arg>
     },
+    DynamicStaticMethodCall {
+        target: Box<Expression>,
+        method: Box<Expression>,
+        args: Vec<Arg>,
+    },
     AnonymousClass {
         extends: Option<Identifier>,
         implements: Vec<Identifier>,
-        body: Block
+        body: Block,
+        attributes: Vec<AttributeGroup>,
     },
     Bool { value: bool },
     ArrayIndex {
-        array: Box<Self>, 
-        index: Option<Box<Self>>
+        array: Box<Expression>, 
+        index: Option<Box<Expression>>
     },
     Null,
     BooleanNot {
-        value: Box<Self>
+        value: Box<Expression>
     },
     MagicConst {
         constant: MagicConst
     },
     Ternary {
-        condition: Box<Self>,
-        then: Option<Box<Self>>,
-        r#else: Box<Self>
+        condition: Box<Expression>,
+        then: Option<Box<Expression>>,
+        r#else: Box<Expression>
     },
     Coalesce {
-        lhs: Box<Self>, 
-        rhs: Box<Self>
+        lhs: Box<Expression>, 
+        rhs: Box<Expression>
+    },
+    Assign {
+        var: Box<Expression>,
+        value: Box<Expression>
+    },
+    CoalesceAssign {
+        var: Box<Expression>,
+        value: Box<Expression>
+    },
+    AssignRef {
+        var: Box<Expression>,
+        value: Box<Expression>
+    },
+    Instanceof {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>
     },
     Clone {
-        target: Box<Self>
+        target: Box<Expression>
     },
     Match {
-        condition: Box<Self>,
+        condition: Box<Expression>,
         arms: Vec<MatchArm>,
     },
     Throw {
@@ -460,48 +860,115 @@ pub enum Expression {
     Negate {
         value: Box<Expression>,
     },
+    UnaryPlus {
+        value: Box<Expression>,
+    },
+    BitwiseNot {
+        value: Box<Expression>,
+    },
+    ErrorSuppress {
+        value: Box<Expression>,
+    },
+    Print {
+        value: Box<Expression>,
+    },
+    Exit {
+        value: Option<Box<Expression>>,
+    },
+    Include {
+        path: Box<Expression>,
+    },
+    IncludeOnce {
+        path: Box<Expression>,
+    },
+    Require {
+        path: Box<Expression>,
+    },
+    RequireOnce {
+        path: Box<Expression>,
+    },
     Cast {
         kind: CastKind,
-        value: Box<Self>,
+        value: Box<Expression>,
     },
+    Error,
 }
 
-#[derive(Debug, PartialEq, Clone, Serialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Arg {
     pub name: Option<String>,
     pub value: Expression,
     pub unpack: bool,
 }
 
-#[derive(Debug, PartialEq, Clone, Serialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ClosureUse {
     pub var: Expression,
     pub by_ref: bool,
 }
 
-#[derive(Debug, PartialEq, Clone, Serialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MatchArm {
     pub conditions: Option<Vec<Expression>>,
     pub body: Expression,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum MagicConst {
+    Class,
     Dir,
+    File,
+    Function,
+    Line,
+    Method,
+    Namespace,
+    Trait,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+impl TryFrom<TokenKind> for MagicConst {
+    type Error = TokenKind;
+
+    fn try_from(k: TokenKind) -> Result<Self, Self::Error> {
+        match k {
+            TokenKind::ClassConstant => Ok(Self::Class),
+            TokenKind::DirConstant => Ok(Self::Dir),
+            TokenKind::FileConstant => Ok(Self::File),
+            TokenKind::FunctionConstant => Ok(Self::Function),
+            TokenKind::LineConstant => Ok(Self::Line),
+            TokenKind::MethodConstant => Ok(Self::Method),
+            TokenKind::NamespaceConstant => Ok(Self::Namespace),
+            TokenKind::TraitConstant => Ok(Self::Trait),
+            _ => Err(k),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ArrayItem {
     pub key: Option<Expression>,
     pub value: Expression,
+    pub unpack: bool,
+    pub by_ref: bool,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum InfixOp {
     Add,
     Sub,
     Div,
     Mul,
+    Mod,
+    LeftShift,
+    RightShift,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
     Concat,
     ConcatAssign,
     LessThan,
@@ -512,51 +979,111 @@ pub enum InfixOp {
     Identical,
     NotEquals,
     NotIdentical,
+    Spaceship,
     And,
     Or,
-    Assign,
+    LogicalAnd,
+    LogicalOr,
+    LogicalXor,
     AddAssign,
     Pow,
-    Instanceof,
-    CoalesceAssign,
     MulAssign,
     SubAssign,
     DivAssign,
+    ModAssign,
+    PowAssign,
+    BitwiseAndAssign,
+    BitwiseOrAssign,
+    BitwiseXorAssign,
+    LeftShiftAssign,
+    RightShiftAssign,
 }
 
-impl From<TokenKind> for InfixOp {
-    fn from(k: TokenKind) -> Self {
+impl InfixOp {
+    /// Whether this is a compound assignment such as `+=`, which assigns its result to the left-hand side.
+    pub fn is_assignment(&self) -> bool {
+        self.operator() != *self
+    }
+
+    /// The operator that a compound assignment applies, such as `Add` for `AddAssign`. Any other
+    /// operator is given back as it is.
+    pub fn operator(&self) -> Self {
+        match self {
+            Self::AddAssign => Self::Add,
+            Self::SubAssign => Self::Sub,
+            Self::MulAssign => Self::Mul,
+            Self::DivAssign => Self::Div,
+            Self::ConcatAssign => Self::Concat,
+            Self::ModAssign => Self::Mod,
+            Self::PowAssign => Self::Pow,
+            Self::BitwiseAndAssign => Self::BitwiseAnd,
+            Self::BitwiseOrAssign => Self::BitwiseOr,
+            Self::BitwiseXorAssign => Self::BitwiseXor,
+            Self::LeftShiftAssign => Self::LeftShift,
+            Self::RightShiftAssign => Self::RightShift,
+            _ => self.clone(),
+        }
+    }
+}
+
+impl TryFrom<TokenKind> for InfixOp {
+    type Error = TokenKind;
+
+    fn try_from(k: TokenKind) -> Result<Self, Self::Error> {
         match k {
-            TokenKind::Plus => Self::Add,
-            TokenKind::Minus => Self::Sub,
-            TokenKind::Asterisk => Self::Mul,
-            TokenKind::Slash => Self::Div,
-            TokenKind::LessThan => Self::LessThan,
-            TokenKind::GreaterThan => Self::GreaterThan,
-            TokenKind::LessThanEquals => Self::LessThanEquals,
-            TokenKind::GreaterThanEquals => Self::GreaterThanEquals,
-            TokenKind::Dot => Self::Concat,
-            TokenKind::DotEquals => Self::ConcatAssign,
-            TokenKind::DoubleEquals => Self::Equals,
-            TokenKind::TripleEquals => Self::Identical,
-            TokenKind::BangEquals => Self::NotEquals,
-            TokenKind::BangDoubleEquals => Self::NotIdentical,
-            TokenKind::BooleanAnd => Self::And,
-            TokenKind::BooleanOr => Self::Or,
-            TokenKind::Equals => Self::Assign,
-            TokenKind::PlusEquals => Self::AddAssign,
-            TokenKind::Pow => Self::Pow,
-            TokenKind::Instanceof => Self::Instanceof,
-            TokenKind::CoalesceEqual => Self::CoalesceAssign,
-            TokenKind::AsteriskEqual => Self::MulAssign,
-            TokenKind::MinusEquals => Self::SubAssign,
-            TokenKind::SlashEquals => Self::DivAssign,
-            _ => unreachable!()
+            TokenKind::Plus => Ok(Self::Add),
+            TokenKind::Minus => Ok(Self::Sub),
+            TokenKind::Asterisk => Ok(Self::Mul),
+            TokenKind::Slash => Ok(Self::Div),
+            TokenKind::Percent => Ok(Self::Mod),
+            TokenKind::LeftShift => Ok(Self::LeftShift),
+            TokenKind::RightShift => Ok(Self::RightShift),
+            TokenKind::Ampersand => Ok(Self::BitwiseAnd),
+            TokenKind::Pipe => Ok(Self::BitwiseOr),
+            TokenKind::Caret => Ok(Self::BitwiseXor),
+            TokenKind::LessThan => Ok(Self::LessThan),
+            TokenKind::GreaterThan => Ok(Self::GreaterThan),
+            TokenKind::LessThanEquals => Ok(Self::LessThanEquals),
+            TokenKind::GreaterThanEquals => Ok(Self::GreaterThanEquals),
+            TokenKind::Dot => Ok(Self::Concat),
+            TokenKind::DotEquals => Ok(Self::ConcatAssign),
+            TokenKind::DoubleEquals => Ok(Self::Equals),
+            TokenKind::TripleEquals => Ok(Self::Identical),
+            TokenKind::BangEquals | TokenKind::LessThanGreaterThan => Ok(Self::NotEquals),
+            TokenKind::BangDoubleEquals => Ok(Self::NotIdentical),
+            TokenKind::Spaceship => Ok(Self::Spaceship),
+            TokenKind::BooleanAnd => Ok(Self::And),
+            TokenKind::BooleanOr => Ok(Self::Or),
+            TokenKind::LogicalAnd => Ok(Self::LogicalAnd),
+            TokenKind::LogicalOr => Ok(Self::LogicalOr),
+            TokenKind::LogicalXor => Ok(Self::LogicalXor),
+            TokenKind::PlusEquals => Ok(Self::AddAssign),
+            TokenKind::Pow => Ok(Self::Pow),
+            TokenKind::AsteriskEqual => Ok(Self::MulAssign),
+            TokenKind::MinusEquals => Ok(Self::SubAssign),
+            TokenKind::SlashEquals => Ok(Self::DivAssign),
+            TokenKind::PercentEquals => Ok(Self::ModAssign),
+            TokenKind::PowEquals => Ok(Self::PowAssign),
+            TokenKind::AndEqual => Ok(Self::BitwiseAndAssign),
+            TokenKind::PipeEquals => Ok(Self::BitwiseOrAssign),
+            TokenKind::CaretEquals => Ok(Self::BitwiseXorAssign),
+            TokenKind::LeftShiftEquals => Ok(Self::LeftShiftAssign),
+            TokenKind::RightShiftEquals => Ok(Self::RightShiftAssign),
+            _ => Err(k),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+impl TryFrom<&TokenKind> for InfixOp {
+    type Error = TokenKind;
+
+    fn try_from(kind: &TokenKind) -> Result<Self, Self::Error> {
+        kind.clone().try_into()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ElseIf {
     pub condition: Expression,
     pub body: Block,
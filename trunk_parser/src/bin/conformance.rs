@@ -0,0 +1,383 @@
+//! Runs the lexer and parser over a corpus of real-world PHP, such as the `.phpt` tests in php-src,
+//! to find panics and track how much of the grammar is covered over time.
+//!
+//! ```text
+//! conformance <dirs...> [--snapshot <file>] [--update]
+//! ```
+//!
+//! Every `.php` file, and the `--FILE--` section of every `.phpt` file, below the directories is
+//! parsed. A file passes when it's accepted, or when it's rejected and it's a test that expects a
+//! parse error. With `--snapshot`, the results are compared with those recorded in the file, and
+//! files that used to pass and no longer do are reported as regressions. `--update` records the
+//! results in the snapshot instead. No snapshot is committed, so record one with `--update` before
+//! changing the parser.
+//!
+//! The exit code is `1` if anything panicked or regressed, and `2` if the arguments were invalid or
+//! a file couldn't be read.
+
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use serde_json::{json, Value};
+use trunk_lexer::Lexer;
+use trunk_parser::Parser;
+
+/// Parsing deeply nested code takes a lot of stack in debug builds.
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Outcome {
+    Accepted,
+    Rejected(String),
+    Panicked(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Report {
+    path: String,
+    outcome: Outcome,
+    /// Whether the file is a test that expects a parse error.
+    expects_error: bool,
+}
+
+impl Report {
+    fn passed(&self) -> bool {
+        match self.outcome {
+            Outcome::Accepted => ! self.expects_error,
+            Outcome::Rejected(_) => self.expects_error,
+            Outcome::Panicked(_) => false,
+        }
+    }
+
+    /// How the file is recorded in a snapshot.
+    fn status(&self) -> &'static str {
+        match self.outcome {
+            Outcome::Panicked(_) => "panic",
+            _ if self.passed() => "pass",
+            _ => "fail",
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut dirs = Vec::new();
+    let mut snapshot = None;
+    let mut update = false;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--snapshot" => match args.next() {
+                Some(path) => snapshot = Some(PathBuf::from(path)),
+                None => return usage_error("--snapshot needs a file"),
+            },
+            "--update" => update = true,
+            flag if flag.starts_with('-') => return usage_error(&format!("unknown option `{}`", flag)),
+            dir => dirs.push(PathBuf::from(dir)),
+        }
+    }
+
+    if dirs.is_empty() {
+        return usage_error("no directories given");
+    }
+
+    if update && snapshot.is_none() {
+        return usage_error("--update needs --snapshot");
+    }
+
+    let mut files = Vec::new();
+
+    for dir in &dirs {
+        if let Err(error) = discover(dir, &mut files) {
+            eprintln!("error: couldn't read {}: {}", dir.display(), error);
+            return ExitCode::from(2);
+        }
+    }
+
+    let results = match run(&files) {
+        Ok(results) => results,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return ExitCode::from(2);
+        },
+    };
+
+    let mut failed = false;
+
+    for result in &results {
+        match &result.outcome {
+            Outcome::Panicked(message) => {
+                println!("PANIC {}: {}", result.path, message);
+                failed = true;
+            },
+            // With a snapshot, only the changes since it are interesting.
+            Outcome::Rejected(message) if snapshot.is_none() && ! result.passed() => println!("FAIL {}: {}", result.path, message),
+            Outcome::Accepted if snapshot.is_none() && ! result.passed() => println!("FAIL {}: expected a parse error", result.path),
+            _ => {},
+        }
+    }
+
+    let current = to_snapshot(&results);
+
+    match (&snapshot, update) {
+        (Some(path), true) => {
+            let json = serde_json::to_string_pretty(&current).expect("snapshots are serializable");
+
+            if let Err(error) = std::fs::write(path, json + "\n") {
+                eprintln!("error: couldn't write {}: {}", path.display(), error);
+                return ExitCode::from(2);
+            }
+        },
+        (Some(path), false) => {
+            let previous = match std::fs::read_to_string(path).map(|json| serde_json::from_str::<Value>(&json)) {
+                Ok(Ok(previous)) => previous,
+                Ok(Err(error)) => {
+                    eprintln!("error: {} isn't a snapshot: {}", path.display(), error);
+                    return ExitCode::from(2);
+                },
+                Err(error) if error.kind() == ErrorKind::NotFound => {
+                    eprintln!("error: {} doesn't exist, record a baseline with --update first", path.display());
+                    return ExitCode::from(2);
+                },
+                Err(error) => {
+                    eprintln!("error: couldn't read {}: {}", path.display(), error);
+                    return ExitCode::from(2);
+                },
+            };
+
+            let (regressed, fixed) = compare(&previous, &current);
+
+            for path in &regressed {
+                println!("REGRESSED {}", path);
+            }
+
+            for path in &fixed {
+                println!("FIXED {}", path);
+            }
+
+            failed |= ! regressed.is_empty();
+        },
+        (None, _) => {},
+    }
+
+    println!(
+        "{} files: {} accepted, {} rejected, {} panicked; {} passed, {} failed",
+        current["files"], current["accepted"], current["rejected"], current["panicked"], current["passed"], current["failed"],
+    );
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("error: {}\n\nusage: conformance <dirs...> [--snapshot <file>] [--update]", message);
+    ExitCode::from(2)
+}
+
+/// Find the `.php` and `.phpt` files below a directory, in order of name.
+fn discover(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+
+        if entry.file_type()?.is_dir() {
+            discover(&path, files)?;
+        } else if path.extension().is_some_and(|extension| extension == "php" || extension == "phpt") {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse the files, spread across one thread per available core, returning the results in the same
+/// order as the files.
+fn run(files: &[PathBuf]) -> Result<Vec<Report>, String> {
+    let results: Vec<Mutex<Option<std::io::Result<Report>>>> = files.iter().map(|_| Mutex::new(None)).collect();
+    let next = AtomicUsize::new(0);
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()).min(files.len());
+
+    // Panics are expected and reported with the results, so the default message isn't wanted.
+    std::panic::set_hook(Box::new(|_| {}));
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let worker = || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = files.get(index) else { break };
+
+                let result = std::fs::read(path).map(|bytes| check(path, &String::from_utf8_lossy(&bytes)));
+
+                *results[index].lock().unwrap_or_else(|err| err.into_inner()) = Some(result);
+            };
+
+            thread::Builder::new().stack_size(STACK_SIZE).spawn_scoped(scope, worker).expect("threads can be spawned");
+        }
+    });
+
+    let _ = std::panic::take_hook();
+
+    files.iter()
+        .zip(results)
+        .map(|(path, result)| {
+            let result = result.into_inner().unwrap_or_else(|err| err.into_inner()).expect("every file is parsed");
+
+            result.map_err(|error| format!("couldn't read {}: {}", path.display(), error))
+        })
+        .collect()
+}
+
+/// Parse a file, or the code in a `.phpt` test, catching any panic.
+fn check(path: &Path, contents: &str) -> Report {
+    let (source, expects_error) = match path.extension().is_some_and(|extension| extension == "phpt") {
+        true => {
+            let sections = sections(contents);
+            let expected = ["EXPECT", "EXPECTF", "EXPECTREGEX"].iter().filter_map(|name| sections.get(*name)).any(|output| output.contains("Parse error"));
+
+            (sections.get("FILE").copied().unwrap_or_default(), expected)
+        },
+        false => (contents, false),
+    };
+
+    let parsed = catch_unwind(AssertUnwindSafe(|| {
+        let tokens = Lexer::new(None).tokenize(source).map_err(|error| error.to_string())?;
+
+        Parser::new(None).parse(&tokens).map(|_| ()).map_err(|error| error.to_string())
+    }));
+
+    let outcome = match parsed {
+        Ok(Ok(())) => Outcome::Accepted,
+        Ok(Err(message)) => Outcome::Rejected(message),
+        Err(payload) => {
+            let message = payload.downcast_ref::<&str>().map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".into());
+
+            Outcome::Panicked(message)
+        },
+    };
+
+    Report { path: path.to_string_lossy().replace('\\', "/"), outcome, expects_error }
+}
+
+/// Split a `.phpt` test into its sections, such as `FILE` and `EXPECT`, by the `--NAME--` lines that
+/// start them.
+fn sections(contents: &str) -> BTreeMap<&str, &str> {
+    let mut sections = BTreeMap::new();
+    let mut current: Option<(&str, usize)> = None;
+    let mut offset = 0;
+
+    for line in contents.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        let name = trimmed.strip_prefix("--").and_then(|name| name.strip_suffix("--"));
+
+        if let Some(name) = name.filter(|name| ! name.is_empty() && name.chars().all(|c| c.is_ascii_uppercase() || c == '_')) {
+            if let Some((previous, start)) = current {
+                sections.insert(previous, &contents[start..offset]);
+            }
+
+            current = Some((name, offset + line.len()));
+        }
+
+        offset += line.len();
+    }
+
+    if let Some((name, start)) = current {
+        sections.insert(name, &contents[start..]);
+    }
+
+    sections
+}
+
+fn to_snapshot(results: &[Report]) -> Value {
+    let count = |f: &dyn Fn(&Report) -> bool| results.iter().filter(|result| f(result)).count();
+    let files: serde_json::Map<String, Value> = results.iter().map(|result| (result.path.clone(), result.status().into())).collect();
+
+    json!({
+        "files": results.len(),
+        "accepted": count(&|result| result.outcome == Outcome::Accepted),
+        "rejected": count(&|result| matches!(result.outcome, Outcome::Rejected(_))),
+        "panicked": count(&|result| matches!(result.outcome, Outcome::Panicked(_))),
+        "passed": count(&|result| result.passed()),
+        "failed": count(&|result| ! result.passed()),
+        "results": files,
+    })
+}
+
+/// The files that passed in the previous snapshot but not in the current one, and those that didn't
+/// pass before but do now.
+fn compare(previous: &Value, current: &Value) -> (Vec<String>, Vec<String>) {
+    let mut regressed = Vec::new();
+    let mut fixed = Vec::new();
+
+    for (path, status) in current["results"].as_object().into_iter().flatten() {
+        match (previous["results"][path].as_str(), status.as_str()) {
+            (Some("pass"), Some(status)) if status != "pass" => regressed.push(path.clone()),
+            (Some(before), Some("pass")) if before != "pass" => fixed.push(path.clone()),
+            _ => {},
+        }
+    }
+
+    (regressed, fixed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::json;
+
+    use super::{check, compare, sections, to_snapshot, Outcome};
+
+    #[test]
+    fn phpt_sections() {
+        let test = "--TEST--\nBroken syntax\n--FILE--\n<?php\nif (;\n?>\n--EXPECTF--\nParse error: syntax error, unexpected token \";\" in %s on line %d\n";
+        let sections = sections(test);
+
+        assert_eq!(sections["TEST"], "Broken syntax\n");
+        assert_eq!(sections["FILE"], "<?php\nif (;\n?>\n");
+        assert!(sections["EXPECTF"].starts_with("Parse error"));
+
+        let result = check(Path::new("tests/broken.phpt"), test);
+        assert!(matches!(result.outcome, Outcome::Rejected(_)));
+        assert!(result.expects_error && result.passed());
+
+        let result = check(Path::new("tests/ok.phpt"), "--FILE--\n<?php echo 1;\n--EXPECT--\n1\n");
+        assert_eq!(result.outcome, Outcome::Accepted);
+        assert!(result.passed());
+
+        assert!(! check(Path::new("broken.php"), "<?php if (;").passed());
+    }
+
+    #[test]
+    fn snapshots() {
+        let results = [
+            check(Path::new("a.php"), "<?php echo 1;"),
+            check(Path::new("b.php"), "<?php if (;"),
+        ];
+        let snapshot = to_snapshot(&results);
+
+        assert_eq!(snapshot["accepted"], 1);
+        assert_eq!(snapshot["rejected"], 1);
+        assert_eq!(snapshot["passed"], 1);
+        assert_eq!(snapshot["results"], json!({ "a.php": "pass", "b.php": "fail" }));
+
+        let previous = json!({ "results": { "a.php": "pass", "b.php": "pass" } });
+        assert_eq!(compare(&previous, &snapshot), (vec!["b.php".to_string()], vec![]));
+
+        let previous = json!({ "results": { "a.php": "fail" } });
+        assert_eq!(compare(&previous, &snapshot), (vec![], vec!["a.php".to_string()]));
+    }
+}
@@ -0,0 +1,644 @@
+use std::fmt::Display;
+
+use serde::de::{self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor};
+use serde::ser::{self, Serialize};
+use serde::{Deserialize, Deserializer, Serializer};
+
+use crate::Program;
+
+/// The version of the cache format. Caches written with a different version, or by a different
+/// version of this crate, are rejected rather than decoded.
+pub const CACHE_VERSION: u32 = 3;
+
+const MAGIC: &[u8; 4] = b"TRNK";
+
+/// An error from reading a cached AST.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheError {
+    /// The bytes don't start with a cache header.
+    InvalidHeader,
+    /// The cache was written by a different version of the format or of this crate.
+    UnsupportedVersion,
+    /// The cache was written for different source code.
+    Stale,
+    Corrupt(String),
+}
+
+impl Display for CacheError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidHeader => write!(f, "Cache error: invalid header"),
+            Self::UnsupportedVersion => write!(f, "Cache error: unsupported version"),
+            Self::Stale => write!(f, "Cache error: the source has changed"),
+            Self::Corrupt(message) => write!(f, "Cache error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+impl ser::Error for CacheError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Corrupt(msg.to_string())
+    }
+}
+
+impl de::Error for CacheError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Corrupt(msg.to_string())
+    }
+}
+
+/// A 64-bit FNV-1a hash of the source code, which is stored in the cache header so that a cache
+/// can be checked against the current contents of a file without decoding it.
+pub fn content_hash(source: &str) -> u64 {
+    source.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Encode a program parsed from `source` in a compact binary form, for caching on disk.
+pub fn to_cache(source: &str, program: &Program) -> Vec<u8> {
+    let mut encoder = Encoder { output: Vec::new() };
+
+    encoder.output.extend_from_slice(MAGIC);
+    encoder.output.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+    encoder.string(env!("CARGO_PKG_VERSION"));
+    encoder.output.extend_from_slice(&content_hash(source).to_le_bytes());
+
+    // Every type in the AST can be serialized, and the encoder itself never fails.
+    program.serialize(&mut encoder).expect("the AST is serializable");
+
+    encoder.output
+}
+
+/// Decode a program from a cache written by `to_cache`. The cache is only decoded if it was
+/// written for exactly the same `source`, otherwise `CacheError::Stale` is returned.
+pub fn from_cache(source: &str, bytes: &[u8]) -> Result<Program, CacheError> {
+    let mut decoder = Decoder { input: bytes };
+
+    if decoder.take(MAGIC.len()).map_err(|_| CacheError::InvalidHeader)? != MAGIC {
+        return Err(CacheError::InvalidHeader);
+    }
+
+    let version = u32::from_le_bytes(decoder.array().map_err(|_| CacheError::InvalidHeader)?);
+    let crate_version = decoder.str().map_err(|_| CacheError::InvalidHeader)?;
+
+    if version != CACHE_VERSION || crate_version != env!("CARGO_PKG_VERSION") {
+        return Err(CacheError::UnsupportedVersion);
+    }
+
+    if u64::from_le_bytes(decoder.array().map_err(|_| CacheError::InvalidHeader)?) != content_hash(source) {
+        return Err(CacheError::Stale);
+    }
+
+    let program = Program::deserialize(&mut decoder)?;
+
+    if ! decoder.input.is_empty() {
+        return Err(CacheError::Corrupt("trailing bytes".into()));
+    }
+
+    Ok(program)
+}
+
+// Every value starts with one of these tags. Containers are prefixed by their length, and struct
+// fields are keyed by name, so that fields that are skipped when empty can be decoded.
+const NONE: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const SOME: u8 = 3;
+const UNSIGNED: u8 = 4;
+const SIGNED: u8 = 5;
+const FLOAT: u8 = 6;
+const STRING: u8 = 7;
+const BYTES: u8 = 8;
+const SEQ: u8 = 9;
+const MAP: u8 = 10;
+const VARIANT: u8 = 11;
+
+struct Encoder {
+    output: Vec<u8>,
+}
+
+impl Encoder {
+    fn varint(&mut self, mut n: u64) {
+        while n >= 0x80 {
+            self.output.push(n as u8 | 0x80);
+            n >>= 7;
+        }
+
+        self.output.push(n as u8);
+    }
+
+    fn string(&mut self, s: &str) {
+        self.varint(s.len() as u64);
+        self.output.extend_from_slice(s.as_bytes());
+    }
+
+    fn tagged(&mut self, tag: u8, len: usize) {
+        self.output.push(tag);
+        self.varint(len as u64);
+    }
+
+    fn variant(&mut self, index: u32) {
+        self.output.push(VARIANT);
+        self.varint(index as u64);
+    }
+}
+
+impl Serializer for &mut Encoder {
+    type Ok = ();
+    type Error = CacheError;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<(), CacheError> {
+        self.output.push(if v { TRUE } else { FALSE });
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), CacheError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), CacheError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), CacheError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), CacheError> {
+        self.output.push(SIGNED);
+        self.varint(((v << 1) ^ (v >> 63)) as u64);
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), CacheError> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), CacheError> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), CacheError> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), CacheError> {
+        self.output.push(UNSIGNED);
+        self.varint(v);
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), CacheError> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), CacheError> {
+        self.output.push(FLOAT);
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), CacheError> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), CacheError> {
+        self.output.push(STRING);
+        self.string(v);
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), CacheError> {
+        self.tagged(BYTES, v.len());
+        self.output.extend_from_slice(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), CacheError> {
+        self.output.push(NONE);
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), CacheError> {
+        self.output.push(SOME);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), CacheError> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), CacheError> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_variant(self, _: &'static str, index: u32, _: &'static str) -> Result<(), CacheError> {
+        self.variant(index);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _: &'static str, value: &T) -> Result<(), CacheError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _: &'static str, index: u32, _: &'static str, value: &T) -> Result<(), CacheError> {
+        self.variant(index);
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self, CacheError> {
+        let len = len.ok_or_else(|| CacheError::Corrupt("sequences must have a known length".into()))?;
+        self.tagged(SEQ, len);
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self, CacheError> {
+        self.tagged(SEQ, len);
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, len: usize) -> Result<Self, CacheError> {
+        self.tagged(SEQ, len);
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(self, _: &'static str, index: u32, _: &'static str, len: usize) -> Result<Self, CacheError> {
+        self.variant(index);
+        self.tagged(SEQ, len);
+        Ok(self)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self, CacheError> {
+        let len = len.ok_or_else(|| CacheError::Corrupt("maps must have a known length".into()))?;
+        self.tagged(MAP, len);
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _: &'static str, len: usize) -> Result<Self, CacheError> {
+        self.tagged(MAP, len);
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(self, _: &'static str, index: u32, _: &'static str, len: usize) -> Result<Self, CacheError> {
+        self.variant(index);
+        self.tagged(MAP, len);
+        Ok(self)
+    }
+}
+
+impl ser::SerializeSeq for &mut Encoder {
+    type Ok = ();
+    type Error = CacheError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CacheError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CacheError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for &mut Encoder {
+    type Ok = ();
+    type Error = CacheError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CacheError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CacheError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut Encoder {
+    type Ok = ();
+    type Error = CacheError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CacheError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CacheError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for &mut Encoder {
+    type Ok = ();
+    type Error = CacheError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CacheError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CacheError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for &mut Encoder {
+    type Ok = ();
+    type Error = CacheError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), CacheError> {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CacheError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CacheError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for &mut Encoder {
+    type Ok = ();
+    type Error = CacheError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), CacheError> {
+        self.output.push(STRING);
+        self.string(key);
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CacheError> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for &mut Encoder {
+    type Ok = ();
+    type Error = CacheError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), CacheError> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<(), CacheError> {
+        Ok(())
+    }
+}
+
+struct Decoder<'de> {
+    input: &'de [u8],
+}
+
+impl<'de> Decoder<'de> {
+    fn take(&mut self, len: usize) -> Result<&'de [u8], CacheError> {
+        if len > self.input.len() {
+            return Err(CacheError::Corrupt("unexpected end of input".into()));
+        }
+
+        let (taken, rest) = self.input.split_at(len);
+        self.input = rest;
+
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], CacheError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+
+        Ok(array)
+    }
+
+    fn byte(&mut self) -> Result<u8, CacheError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, CacheError> {
+        let mut n = 0u64;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            n |= ((byte & 0x7f) as u64) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+
+        Err(CacheError::Corrupt("invalid integer".into()))
+    }
+
+    fn len(&mut self) -> Result<usize, CacheError> {
+        usize::try_from(self.varint()?).map_err(|_| CacheError::Corrupt("invalid length".into()))
+    }
+
+    fn str(&mut self) -> Result<&'de str, CacheError> {
+        let len = self.len()?;
+
+        std::str::from_utf8(self.take(len)?).map_err(|_| CacheError::Corrupt("invalid string".into()))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.first().copied()
+    }
+}
+
+impl<'de> Deserializer<'de> for &mut Decoder<'de> {
+    type Error = CacheError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CacheError> {
+        match self.byte()? {
+            NONE => visitor.visit_unit(),
+            FALSE => visitor.visit_bool(false),
+            TRUE => visitor.visit_bool(true),
+            SOME => visitor.visit_some(self),
+            UNSIGNED => visitor.visit_u64(self.varint()?),
+            SIGNED => {
+                let n = self.varint()?;
+                visitor.visit_i64((n >> 1) as i64 ^ -((n & 1) as i64))
+            },
+            FLOAT => visitor.visit_f64(f64::from_le_bytes(self.array()?)),
+            STRING => visitor.visit_borrowed_str(self.str()?),
+            BYTES => {
+                let len = self.len()?;
+                visitor.visit_borrowed_bytes(self.take(len)?)
+            },
+            SEQ => {
+                let len = self.len()?;
+                visitor.visit_seq(Elements { decoder: self, len })
+            },
+            MAP => {
+                let len = self.len()?;
+                visitor.visit_map(Elements { decoder: self, len })
+            },
+            tag => Err(CacheError::Corrupt(format!("unexpected tag {}", tag))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CacheError> {
+        match self.peek() {
+            Some(NONE) => {
+                self.byte()?;
+                visitor.visit_none()
+            },
+            Some(SOME) => {
+                self.byte()?;
+                visitor.visit_some(self)
+            },
+            _ => Err(CacheError::Corrupt("expected an option".into())),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, CacheError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _: &'static str, _: &'static [&'static str], visitor: V) -> Result<V::Value, CacheError> {
+        if self.byte()? != VARIANT {
+            return Err(CacheError::Corrupt("expected an enum".into()));
+        }
+
+        visitor.visit_enum(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit unit_struct seq
+        tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> EnumAccess<'de> for &mut Decoder<'de> {
+    type Error = CacheError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), CacheError> {
+        let index = u32::try_from(self.varint()?).map_err(|_| CacheError::Corrupt("invalid variant".into()))?;
+        let variant = seed.deserialize(IntoDeserializer::<CacheError>::into_deserializer(index))?;
+
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for &mut Decoder<'de> {
+    type Error = CacheError;
+
+    fn unit_variant(self) -> Result<(), CacheError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, CacheError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value, CacheError> {
+        self.deserialize_any(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _: &'static [&'static str], visitor: V) -> Result<V::Value, CacheError> {
+        self.deserialize_any(visitor)
+    }
+}
+
+struct Elements<'a, 'de> {
+    decoder: &'a mut Decoder<'de>,
+    len: usize,
+}
+
+impl<'de> SeqAccess<'de> for Elements<'_, 'de> {
+    type Error = CacheError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, CacheError> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+
+        self.len -= 1;
+        seed.deserialize(&mut *self.decoder).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        // The length comes from the input, so don't let it cause a huge allocation up front.
+        Some(self.len.min(self.decoder.input.len()))
+    }
+}
+
+impl<'de> MapAccess<'de> for Elements<'_, 'de> {
+    type Error = CacheError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, CacheError> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+
+        self.len -= 1;
+        seed.deserialize(&mut *self.decoder).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, CacheError> {
+        seed.deserialize(&mut *self.decoder)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len.min(self.decoder.input.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_cache, to_cache, CacheError};
+    use crate::parse;
+
+    const SOURCE: &str = "<?php
+        namespace App;
+
+        final class A extends B implements C {
+            public const X = -1.5;
+            private ?int $a = -9223372036854775807;
+
+            public function __construct(protected string $b = 'é', int ...$c) {}
+
+            public static function f(): static|false {
+                return match (true) { $a instanceof A => fn &() => [1, 'a' => &$b, ...$c], default => \"{$d->e}\" };
+            }
+        }
+
+        enum D: int { case E = 1; }
+        __halt_compiler(); data";
+
+    #[test]
+    fn round_trips() {
+        let mut ast = parse(SOURCE).unwrap();
+        ast[0].meta.insert("checked", serde_json::json!({ "at": [1, -2, 3.5, null, true] }));
+
+        let bytes = to_cache(SOURCE, &ast);
+        let decoded = from_cache(SOURCE, &bytes).unwrap();
+
+        assert_eq!(decoded, ast);
+        assert_eq!(decoded[0].meta, ast[0].meta);
+        assert_eq!(decoded[1].span, ast[1].span);
+        assert!(bytes.len() < serde_json::to_vec(&ast).unwrap().len());
+    }
+
+    #[test]
+    fn rejects_invalid_caches() {
+        let ast = parse(SOURCE).unwrap();
+        let bytes = to_cache(SOURCE, &ast);
+
+        assert_eq!(from_cache("<?php", &bytes), Err(CacheError::Stale));
+        assert_eq!(from_cache(SOURCE, b"<?php"), Err(CacheError::InvalidHeader));
+
+        let mut version = bytes.clone();
+        version[4] += 1;
+        assert_eq!(from_cache(SOURCE, &version), Err(CacheError::UnsupportedVersion));
+
+        for len in 0..bytes.len() {
+            assert!(from_cache(SOURCE, &bytes[..len]).is_err());
+        }
+    }
+}
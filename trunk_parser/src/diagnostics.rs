@@ -0,0 +1,424 @@
+use std::fmt::{Display, Write};
+use std::path::{Path, PathBuf};
+
+use trunk_lexer::{LexerError, LexerErrorKind, LexerWarning, Span};
+
+use crate::parser::describe_expected;
+use crate::{Error, ParseError, ParseWarning, Suggestion};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// An error or warning about a piece of source code, which can be rendered along with the line of
+/// source that it refers to. Any of the crate's errors and warnings can be turned into one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// A stable identifier for the kind of problem, such as `expected-token`.
+    pub code: &'static str,
+    pub message: String,
+    pub span: Option<Span>,
+    /// A short description of the problem, shown next to the underlined source.
+    pub label: Option<String>,
+    pub notes: Vec<String>,
+    pub suggestion: Option<Suggestion>,
+    /// The file that the diagnostic is about, if it's known. A path given when rendering the
+    /// diagnostic takes precedence over it.
+    pub path: Option<PathBuf>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &'static str, message: impl Into<String>, span: Option<Span>) -> Self {
+        Self { severity, code, message: message.into(), span, label: None, notes: Vec::new(), suggestion: None, path: None }
+    }
+
+    /// Attribute the diagnostic to a file.
+    pub fn in_file(self, path: impl Into<PathBuf>) -> Self {
+        Self { path: Some(path.into()), ..self }
+    }
+
+    /// Serialize the diagnostic as a single line of JSON, for tools such as CI systems and editors.
+    /// Spans are given as byte offsets along with the line and column that they start and end on,
+    /// and a suggested fix is included as a related span.
+    ///
+    /// `source` has to be the source that the diagnostic's spans refer to.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self, source: &str, path: Option<&Path>) -> String {
+        self.to_json_value(source, path).to_string()
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn to_json_value(&self, source: &str, path: Option<&Path>) -> serde_json::Value {
+        let path = path.or(self.path.as_deref());
+        let span = |span: Span| {
+            let (start, end) = offsets(source, span);
+            let (line, column) = position(source, start);
+            let (end_line, end_column) = position(source, end);
+
+            serde_json::json!({ "start": start, "end": end, "line": line, "column": column, "end_line": end_line, "end_column": end_column })
+        };
+
+        let related: Vec<serde_json::Value> = self.suggestion.iter()
+            .map(|suggestion| serde_json::json!({ "message": suggestion.message, "span": span(suggestion.span), "replacement": suggestion.replacement }))
+            .collect();
+
+        serde_json::json!({
+            "severity": self.severity.to_string(),
+            "code": self.code,
+            "message": self.message,
+            "file": path.map(|path| path.display().to_string()),
+            "span": self.span.map(span),
+            "label": self.label,
+            "notes": self.notes,
+            "related": related,
+        })
+    }
+
+    /// Render the diagnostic in the style of rustc, with the source line that it refers to and the
+    /// problem underlined:
+    ///
+    /// ```text
+    /// error: expected `;`, found `foo`
+    ///  --> index.php:1:16
+    ///   |
+    /// 1 | <?php funciton foo() {}
+    ///   |                ^^^ expected `;`
+    ///   |
+    /// help: did you mean `function`?
+    ///   |
+    /// 1 | <?php function foo() {}
+    ///   |       ~~~~~~~~
+    /// ```
+    ///
+    /// `source` has to be the source that the diagnostic's spans refer to.
+    pub fn render(&self, source: &str, path: Option<&Path>) -> String {
+        let path = path.or(self.path.as_deref());
+        let mut output = String::new();
+        let _ = writeln!(output, "{}: {}", self.severity, self.message);
+
+        let span = self.span.map(|span| offsets(source, span));
+
+        let suggestion = self.suggestion.as_ref().filter(|suggestion| {
+            let Span { start, end, .. } = suggestion.span;
+
+            source.get(start..end).is_some_and(|replaced| ! replaced.contains('\n'))
+        });
+
+        let lines = [span.map(|(start, _)| start), suggestion.map(|s| s.span.start)].into_iter().flatten().map(|offset| Line::at(source, offset).number);
+        let gutter = " ".repeat(lines.max().unwrap_or(1).to_string().len());
+
+        if let Some((start, end)) = span {
+            let line = Line::at(source, start);
+            let location = match (path, position(source, start)) {
+                (Some(path), (line, col)) => format!("{}:{}:{}", path.display(), line, col),
+                (None, (line, col)) => format!("{}:{}", line, col),
+            };
+
+            let underline = "^".repeat(width(&source[start..end.min(line.end)]).max(1));
+            let label = self.label.as_deref().unwrap_or_default();
+
+            let _ = writeln!(output, "{}--> {}", gutter, location);
+            let _ = writeln!(output, "{} |", gutter);
+            let _ = writeln!(output, "{} | {}", line.number, expand_tabs(line.text(source)));
+            let _ = writeln!(output, "{}", format!("{} | {}{} {}", gutter, " ".repeat(width(&source[line.start..start])), underline, label).trim_end());
+        } else if let Some(path) = path {
+            let _ = writeln!(output, "{}--> {}", gutter, path.display());
+        }
+
+        if ! self.notes.is_empty() {
+            let _ = writeln!(output, "{} |", gutter);
+        }
+
+        for note in &self.notes {
+            let _ = writeln!(output, "{} = note: {}", gutter, note);
+        }
+
+        if let Some(suggestion) = suggestion {
+            let Span { start, end, .. } = suggestion.span;
+            let line = Line::at(source, start);
+            let fixed = format!("{}{}{}", &source[line.start..start], suggestion.replacement, &source[end..line.end]);
+            let marker = if start == end { "+" } else { "~" };
+
+            if self.notes.is_empty() && span.is_some() {
+                let _ = writeln!(output, "{} |", gutter);
+            }
+
+            let _ = writeln!(output, "help: {}", suggestion.message);
+            let _ = writeln!(output, "{} |", gutter);
+            let _ = writeln!(output, "{} | {}", line.number, expand_tabs(fixed.trim_end_matches('\r')));
+            let _ = writeln!(output, "{} | {}{}", gutter, " ".repeat(width(&source[line.start..start])), marker.repeat(width(&suggestion.replacement).max(1)));
+        }
+
+        output
+    }
+}
+
+/// The byte offsets that a span covers, kept within the source. The parser reports the end of file
+/// with an empty span on line 0.
+fn offsets(source: &str, span: Span) -> (usize, usize) {
+    match span.line {
+        0 => (source.len(), source.len()),
+        _ => (span.start.min(source.len()), span.end.min(source.len())),
+    }
+}
+
+/// The line and column of a byte offset, both counted from 1.
+fn position(source: &str, offset: usize) -> (usize, usize) {
+    let line = Line::at(source, offset);
+
+    (line.number, source[line.start..offset].chars().count() + 1)
+}
+
+/// A line of source, found by the byte offset of something on it.
+struct Line {
+    number: usize,
+    start: usize,
+    end: usize,
+}
+
+impl Line {
+    fn at(source: &str, offset: usize) -> Self {
+        let start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let end = source[offset..].find('\n').map_or(source.len(), |i| offset + i);
+
+        Self { number: source[..start].matches('\n').count() + 1, start, end }
+    }
+
+    fn text<'s>(&self, source: &'s str) -> &'s str {
+        source[self.start..self.end].trim_end_matches('\r')
+    }
+}
+
+/// Tabs are shown as four spaces, so that underlines line up with what they're underlining.
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', "    ")
+}
+
+fn width(text: &str) -> usize {
+    text.chars().map(|c| if c == '\t' { 4 } else { 1 }).sum()
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        let code = match error {
+            ParseError::ExpectedToken { .. } => "expected-token",
+            ParseError::UnexpectedToken(..) => "unexpected-token",
+            ParseError::UnexpectedEndOfFile => "unexpected-end-of-file",
+            ParseError::InvalidClassStatement(..) => "invalid-class-statement",
+            ParseError::InvalidAbstractFinalFlagCombination(_) => "abstract-final",
+            ParseError::ConstantCannotBeStatic(_) => "static-constant",
+            ParseError::ConstantCannotBePrivateFinal(_) => "private-final-constant",
+            ParseError::TraitCannotContainConstant(_) => "trait-constant",
+            ParseError::TryWithoutCatchOrFinally(_) => "try-without-catch",
+            ParseError::InvalidCatchArgumentType(_) => "invalid-catch-type",
+            ParseError::UnparenthesizedNestedTernary(_) => "nested-ternary",
+            ParseError::UnparenthesizedChainedComparison(_) => "chained-comparison",
+            ParseError::RequiresPhpVersion { .. } => "requires-php-version",
+            ParseError::TooDeep(_) => "too-deep",
+        };
+
+        let mut diagnostic = Diagnostic::new(Severity::Error, code, error.message(), error.span());
+
+        match error {
+            ParseError::ExpectedToken { expected, .. } => {
+                let mut expected: Vec<String> = expected.iter().map(describe_expected).collect();
+                expected.dedup();
+
+                diagnostic.label = Some(format!("expected {}", expected.join(" or ")));
+            },
+            ParseError::UnexpectedToken(..) => diagnostic.label = Some("unexpected token".into()),
+            ParseError::RequiresPhpVersion { .. } => diagnostic.notes.push("the targeted version is set by `ParserConfig::php_version`".into()),
+            ParseError::TooDeep(_) => diagnostic.notes.push("the limit is set by `ParserConfig::max_depth`".into()),
+            _ => {},
+        }
+
+        diagnostic.suggestion = error.suggestion().cloned();
+        diagnostic
+    }
+}
+
+impl From<&ParseWarning> for Diagnostic {
+    fn from(warning: &ParseWarning) -> Self {
+        let code = match warning {
+            ParseWarning::Deprecated { .. } => "deprecated",
+            ParseWarning::Removed { .. } => "removed",
+        };
+
+        Diagnostic::new(Severity::Warning, code, warning.message(), Some(warning.span()))
+    }
+}
+
+impl From<&LexerError> for Diagnostic {
+    fn from(error: &LexerError) -> Self {
+        let code = match error.kind {
+            LexerErrorKind::UnexpectedEndOfFile => "unexpected-end-of-file",
+            LexerErrorKind::UnexpectedCharacter(_) => "unexpected-character",
+            LexerErrorKind::InvalidNumber(_) => "invalid-number",
+            LexerErrorKind::InvalidEscape(_) => "invalid-escape",
+            LexerErrorKind::InvalidBodyIndentation(_) => "invalid-body-indentation",
+        };
+
+        Diagnostic::new(Severity::Error, code, error.kind.to_string(), Some(error.span))
+    }
+}
+
+impl From<&LexerWarning> for Diagnostic {
+    fn from(warning: &LexerWarning) -> Self {
+        match warning {
+            LexerWarning::IntegerOverflow(..) => Diagnostic {
+                notes: vec![format!("the largest integer is {}", i64::MAX)],
+                ..Diagnostic::new(Severity::Warning, "integer-overflow", warning.message(), Some(warning.span()))
+            },
+        }
+    }
+}
+
+impl From<&Error> for Diagnostic {
+    fn from(error: &Error) -> Self {
+        match error {
+            Error::Io(err) => Diagnostic::new(Severity::Error, "io", err.to_string(), None),
+            Error::Lexer(err) => err.into(),
+            Error::Parser(err) => err.into(),
+            Error::File { path, error } => Diagnostic::from(error.as_ref()).in_file(path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use trunk_lexer::Lexer;
+
+    use super::Diagnostic;
+    use crate::{Parser, ParserConfig, PhpVersion};
+
+    fn render(source: &str) -> String {
+        let error = crate::parse(source).unwrap_err();
+
+        Diagnostic::from(&error).render(source, Some(Path::new("index.php")))
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(render("<?php\nfunciton foo() {}"), "\
+error: expected `;`, found `foo`
+ --> index.php:2:10
+  |
+2 | funciton foo() {}
+  |          ^^^ expected `;`
+  |
+help: did you mean `function`?
+  |
+2 | function foo() {}
+  | ~~~~~~~~
+");
+
+        assert_eq!(render("<?php function f() {\n\treturn 1\n}"), "\
+error: expected `;`, found `}`
+ --> index.php:3:1
+  |
+3 | }
+  | ^ expected `;`
+  |
+help: add `;` here
+  |
+2 |     return 1;
+  |             +
+");
+
+        assert_eq!(render("<?php\n$a = 1__2;"), "\
+error: unexpected character `_`
+ --> index.php:2:8
+  |
+2 | $a = 1__2;
+  |        ^
+");
+    }
+
+    #[test]
+    fn end_of_file() {
+        assert_eq!(render("<?php\n$a = 1"), "\
+error: expected `;`, found end of file
+ --> index.php:2:7
+  |
+2 | $a = 1
+  |       ^ expected `;`
+  |
+help: add `;` here
+  |
+2 | $a = 1;
+  |       +
+");
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn json() {
+        let source = "<?php\nfunciton foo() {}";
+        let error = crate::parse(source).unwrap_err();
+        let json = Diagnostic::from(&error).to_json(source, Some(Path::new("index.php")));
+
+        assert!(! json.contains('\n'));
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&json).unwrap(), serde_json::json!({
+            "severity": "error",
+            "code": "expected-token",
+            "message": "expected `;`, found `foo`",
+            "file": "index.php",
+            "span": { "start": 15, "end": 18, "line": 2, "column": 10, "end_line": 2, "end_column": 13 },
+            "label": "expected `;`",
+            "notes": [],
+            "related": [{
+                "message": "did you mean `function`?",
+                "span": { "start": 6, "end": 14, "line": 2, "column": 1, "end_line": 2, "end_column": 9 },
+                "replacement": "function",
+            }],
+        }));
+
+        let json = Diagnostic::new(super::Severity::Error, "io", "file not found", None).to_json("", None);
+
+        assert_eq!(json, r#"{"code":"io","file":null,"label":null,"message":"file not found","notes":[],"related":[],"severity":"error","span":null}"#);
+    }
+
+    #[test]
+    fn warnings() {
+        let source = "<?php\n$a = (real) $b;";
+        let tokens = Lexer::new(None).tokenize(source).unwrap();
+        let mut parser = Parser::new(Some(ParserConfig { php_version: PhpVersion::Php74, ..Default::default() }));
+        parser.parse(&tokens).unwrap();
+
+        assert_eq!(Diagnostic::from(&parser.warnings()[0]).render(source, None), "\
+warning: the (real) cast is deprecated since PHP 7.4
+ --> 2:6
+  |
+2 | $a = (real) $b;
+  |      ^^^^^^
+");
+
+        let source = "<?php 99999999999999999999;";
+        let mut lexer = Lexer::new(None);
+        lexer.tokenize(source).unwrap();
+
+        assert_eq!(Diagnostic::from(&lexer.warnings()[0]).render(source, None), "\
+warning: integer literal `99999999999999999999` is too large and is treated as a float
+ --> 1:7
+  |
+1 | <?php 99999999999999999999;
+  |       ^^^^^^^^^^^^^^^^^^^^
+  |
+  = note: the largest integer is 9223372036854775807
+");
+    }
+}
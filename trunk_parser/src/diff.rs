@@ -0,0 +1,238 @@
+use std::mem::discriminant;
+
+use trunk_lexer::Span;
+
+use crate::{Block, Program, Statement, StatementKind};
+
+/// A single difference between two versions of a program. Spans refer to the old program, the new
+/// program, or both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Edit {
+    Added { new: Span },
+    Removed { old: Span },
+    /// The statement is unchanged but now appears in a different position within its block.
+    Moved { old: Span, new: Span },
+    Modified { old: Span, new: Span },
+}
+
+/// Compare two programs statement by statement, ignoring spans and formatting. Statements that
+/// contain a block, such as functions and classes, are compared recursively, so that a change
+/// to one method is reported as that method being modified rather than the whole class.
+pub fn diff(old: &Program, new: &Program) -> Vec<Edit> {
+    let mut edits = Vec::new();
+
+    diff_block(old, new, &mut edits);
+
+    edits
+}
+
+fn diff_block(old: &[Statement], new: &[Statement], edits: &mut Vec<Edit>) {
+    let mut old_matches: Vec<Option<usize>> = vec![None; old.len()];
+    let mut new_matches: Vec<Option<usize>> = vec![None; new.len()];
+
+    for (i, j) in longest_common_subsequence(old, new) {
+        old_matches[i] = Some(j);
+        new_matches[j] = Some(i);
+    }
+
+    // Statements that are identical but out of order have moved.
+    for j in 0..new.len() {
+        if new_matches[j].is_some() {
+            continue;
+        }
+
+        if let Some(i) = (0..old.len()).find(|&i| old_matches[i].is_none() && old[i] == new[j]) {
+            old_matches[i] = Some(j);
+            new_matches[j] = Some(i);
+
+            edits.push(Edit::Moved { old: old[i].span, new: new[j].span });
+        }
+    }
+
+    // Declarations are paired up by name wherever they are, and anything else is paired with a
+    // statement of the same kind between the same unchanged neighbours.
+    for j in 0..new.len() {
+        if new_matches[j].is_some() {
+            continue;
+        }
+
+        let gap = gap(&new_matches, &old_matches, j);
+        let paired = match new[j].kind.name() {
+            Some(_) => (0..old.len()).find(|&i| old_matches[i].is_none() && same_declaration(&old[i].kind, &new[j].kind)),
+            None => gap.into_iter().find(|&i| old_matches[i].is_none() && discriminant(&old[i].kind) == discriminant(&new[j].kind)),
+        };
+
+        if let Some(i) = paired {
+            old_matches[i] = Some(j);
+            new_matches[j] = Some(i);
+
+            modified(&old[i], &new[j], edits);
+        }
+    }
+
+    for (statement, matched) in old.iter().zip(&old_matches) {
+        if matched.is_none() {
+            edits.push(Edit::Removed { old: statement.span });
+        }
+    }
+
+    for (statement, matched) in new.iter().zip(&new_matches) {
+        if matched.is_none() {
+            edits.push(Edit::Added { new: statement.span });
+        }
+    }
+}
+
+/// Record the edits between two statements that are known to differ. When both have a block, the
+/// blocks are compared separately from the rest of the statement.
+fn modified(old: &Statement, new: &Statement, edits: &mut Vec<Edit>) {
+    let (Some(old_body), Some(new_body)) = (body(&old.kind), body(&new.kind)) else {
+        edits.push(Edit::Modified { old: old.span, new: new.span });
+        return;
+    };
+
+    if without_body(&old.kind) != without_body(&new.kind) {
+        edits.push(Edit::Modified { old: old.span, new: new.span });
+    }
+
+    diff_block(old_body, new_body, edits);
+}
+
+/// The range of old statements that lie between the unchanged neighbours of the new statement `j`.
+fn gap(new_matches: &[Option<usize>], old_matches: &[Option<usize>], j: usize) -> std::ops::Range<usize> {
+    let start = new_matches[..j].iter().rev().find_map(|i| *i).map_or(0, |i| i + 1);
+    let end = new_matches[j + 1..].iter().find_map(|i| *i).unwrap_or(old_matches.len());
+
+    start..end.max(start)
+}
+
+fn longest_common_subsequence(old: &[Statement], new: &[Statement]) -> Vec<(usize, usize)> {
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    pairs
+}
+
+fn same_declaration(old: &StatementKind, new: &StatementKind) -> bool {
+    discriminant(old) == discriminant(new) && old.name() == new.name()
+}
+
+fn body(kind: &StatementKind) -> Option<&Block> {
+    match kind {
+        StatementKind::Function { body, .. } | StatementKind::Class { body, .. } | StatementKind::Trait { body, .. } |
+        StatementKind::Interface { body, .. } | StatementKind::Method { body, .. } | StatementKind::Enum { body, .. } |
+        StatementKind::Namespace { body, .. } | StatementKind::While { body, .. } | StatementKind::Foreach { body, .. } |
+        StatementKind::Try { body, .. } | StatementKind::For { then: body, .. } => Some(body),
+        _ => None,
+    }
+}
+
+fn without_body(kind: &StatementKind) -> StatementKind {
+    let mut kind = kind.clone();
+
+    match &mut kind {
+        StatementKind::Function { body, .. } | StatementKind::Class { body, .. } | StatementKind::Trait { body, .. } |
+        StatementKind::Interface { body, .. } | StatementKind::Method { body, .. } | StatementKind::Enum { body, .. } |
+        StatementKind::Namespace { body, .. } | StatementKind::While { body, .. } | StatementKind::Foreach { body, .. } |
+        StatementKind::Try { body, .. } | StatementKind::For { then: body, .. } => body.clear(),
+        _ => {},
+    }
+
+    kind
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, Edit};
+    use crate::parse;
+
+    fn edits(old: &str, new: &str) -> Vec<Edit> {
+        diff(&parse(old).unwrap(), &parse(new).unwrap())
+    }
+
+    fn text(source: &str, span: trunk_lexer::Span) -> &str {
+        &source[span.start..span.end]
+    }
+
+    #[test]
+    fn unchanged() {
+        assert!(edits("<?php $a = 1; function f() { return 1; }", "<?php\n\n$a   =  1;\nfunction f()\n{\n    return 1;\n}").is_empty());
+    }
+
+    #[test]
+    fn statements() {
+        let old = "<?php $a = 1; $b = 2; $c = 3;";
+        let new = "<?php $a = 1; $b = 4; $c = 3; $d = 5;";
+
+        match edits(old, new).as_slice() {
+            [Edit::Modified { old: a, new: b }, Edit::Added { new: c }] => {
+                assert_eq!(text(old, *a), "$b = 2;");
+                assert_eq!(text(new, *b), "$b = 4;");
+                assert_eq!(text(new, *c), "$d = 5;");
+            },
+            edits => panic!("unexpected edits {:?}", edits),
+        }
+
+        match edits(old, "<?php $a = 1; $c = 3;").as_slice() {
+            [Edit::Removed { old: a }] => assert_eq!(text(old, *a), "$b = 2;"),
+            edits => panic!("unexpected edits {:?}", edits),
+        }
+    }
+
+    #[test]
+    fn moved() {
+        let old = "<?php function a() {} function b() {} function c() {}";
+        let new = "<?php function c() {} function a() {} function b() {}";
+
+        match edits(old, new).as_slice() {
+            [Edit::Moved { old: a, new: b }] => {
+                assert_eq!(text(old, *a), "function c() {}");
+                assert_eq!(text(new, *b), "function c() {}");
+            },
+            edits => panic!("unexpected edits {:?}", edits),
+        }
+    }
+
+    #[test]
+    fn nested() {
+        let old = "<?php class A { public function a() { return 1; } public function b() {} }";
+        let new = "<?php class A { public function b() {} public function a() { return 2; } }";
+
+        match edits(old, new).as_slice() {
+            [Edit::Modified { old: a, new: b }] => {
+                assert_eq!(text(old, *a), "return 1;");
+                assert_eq!(text(new, *b), "return 2;");
+            },
+            edits => panic!("unexpected edits {:?}", edits),
+        }
+
+        // A change to the declaration itself is reported along with any changes to its body.
+        let new = "<?php final class A { public function a() { return 1; } public function b() {} }";
+
+        assert_eq!(edits(old, new).len(), 1);
+    }
+}
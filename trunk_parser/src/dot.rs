@@ -0,0 +1,152 @@
+use std::fmt::Write;
+
+use serde_json::Value;
+
+use crate::Program;
+
+/// Render the program as a Graphviz graph, with a node for every statement and expression. Each
+/// node is labelled with its kind, its span and any plain values it holds, such as names and
+/// literals, and edges are labelled with the field that holds the child.
+pub fn to_dot(program: &Program) -> String {
+    let mut dot = Dot { output: String::new(), next: 0 };
+
+    dot.output.push_str("digraph ast {\n    node [shape=box, fontname=\"monospace\"];\n");
+
+    let value = serde_json::to_value(program).expect("the AST is serializable");
+    let root = dot.id();
+
+    let _ = writeln!(dot.output, "    {} [label=\"Program\"];", root);
+
+    for (i, statement) in value.as_array().into_iter().flatten().enumerate() {
+        let child = dot.node(statement);
+        dot.edge(root, child, &format!("[{}]", i));
+    }
+
+    dot.output.push_str("}\n");
+    dot.output
+}
+
+struct Dot {
+    output: String,
+    next: usize,
+}
+
+impl Dot {
+    fn id(&mut self) -> usize {
+        self.next += 1;
+        self.next - 1
+    }
+
+    /// Write a statement or expression node, along with everything below it. Fields are visited in
+    /// alphabetical order, since that is how `serde_json` stores them.
+    fn node(&mut self, value: &Value) -> usize {
+        let id = self.id();
+        let mut label = Vec::new();
+        let mut children = Vec::new();
+
+        match &value["kind"] {
+            Value::String(kind) => label.push(kind.clone()),
+            Value::Object(kind) => {
+                for (variant, fields) in kind {
+                    label.push(variant.clone());
+                    self.fields(fields, String::new(), &mut label, &mut children);
+                }
+            },
+            _ => {},
+        }
+
+        let span = &value["span"];
+        label.insert(1, format!("{}:{} [{}..{}]", span["line"], span["col"], span["start"], span["end"]));
+
+        if let Some(meta) = value.get("meta") {
+            self.fields(meta, "meta".into(), &mut label, &mut children);
+        }
+
+        let _ = writeln!(self.output, "    {} [label=\"{}\"];", id, escape(&label.join("\n")));
+
+        for (path, child) in children {
+            self.edge(id, child, &path);
+        }
+
+        id
+    }
+
+    /// Collect the plain values below a node into its label, and write nodes for any statements or
+    /// expressions, remembering the path to each of them.
+    fn fields(&mut self, value: &Value, path: String, label: &mut Vec<String>, children: &mut Vec<(String, usize)>) {
+        match value {
+            Value::Object(object) if object.contains_key("kind") && object.contains_key("span") => {
+                children.push((path, self.node(value)));
+            },
+            Value::Object(object) => {
+                // Only statements and expressions are labelled with their spans.
+                for (key, value) in object.iter().filter(|(key, _)| *key != "span") {
+                    let path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                    self.fields(value, path, label, children);
+                }
+            },
+            Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    self.fields(item, format!("{}[{}]", path, i), label, children);
+                }
+            },
+            Value::Null => {},
+            Value::String(s) if path.is_empty() => label.push(format!("{:?}", s)),
+            Value::String(s) => label.push(format!("{}: {:?}", path, s)),
+            scalar if path.is_empty() => label.push(scalar.to_string()),
+            scalar => label.push(format!("{}: {}", path, scalar)),
+        }
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: &str) {
+        let _ = writeln!(self.output, "    {} -> {} [label=\"{}\"];", from, to, escape(label));
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_dot;
+    use crate::parse;
+
+    #[test]
+    fn graph() {
+        let dot = to_dot(&parse("<?php $a = 1 + 2 * 3;").unwrap());
+
+        assert_eq!(dot, r#"digraph ast {
+    node [shape=box, fontname="monospace"];
+    0 [label="Program"];
+    4 [label="Int\n1:12 [11..12]\ni: 1"];
+    6 [label="Int\n1:16 [15..16]\ni: 2"];
+    7 [label="Int\n1:20 [19..20]\ni: 3"];
+    5 [label="Infix\n1:16 [15..20]\nop: \"Mul\""];
+    5 -> 6 [label="lhs"];
+    5 -> 7 [label="rhs"];
+    3 [label="Infix\n1:12 [11..20]\nop: \"Add\""];
+    3 -> 4 [label="lhs"];
+    3 -> 5 [label="rhs"];
+    8 [label="Variable\n1:7 [6..8]\nname: \"a\""];
+    2 [label="Assign\n1:7 [6..20]"];
+    2 -> 3 [label="value"];
+    2 -> 8 [label="var"];
+    1 [label="Expression\n1:7 [6..21]"];
+    1 -> 2 [label="expr"];
+    0 -> 1 [label="[0]"];
+}
+"#);
+    }
+
+    #[test]
+    fn labels() {
+        let dot = to_dot(&parse("<?php function f(int $a) { echo \"a\\\"b\"; }").unwrap());
+
+        assert!(dot.contains(r#"name.name: \"f\""#));
+        assert!(dot.contains(r#"[label="params[0].name"]"#));
+        assert!(dot.contains(r#"params[0].type.Plain: \"int\""#));
+        assert!(dot.contains(r#"[label="body[0]"]"#));
+        assert!(dot.contains(r#"value: \"a\\\"b\""#));
+    }
+}
@@ -0,0 +1,171 @@
+use std::fmt::Write;
+
+use serde_json::Value;
+
+use crate::Program;
+
+/// Which parts of the tree `dump` prints.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DumpOptions {
+    /// The kinds of statements and expressions to print, such as `Class` or `Call`, ignoring case.
+    /// Other nodes are left out, but their children can still be printed. Every node is printed if
+    /// this is empty.
+    pub kinds: Vec<String>,
+    /// How many levels of printed nodes to show, where top-level nodes are the first level.
+    pub depth: Option<usize>,
+}
+
+/// Print the program as an indented tree, one statement or expression per line, for reading while
+/// debugging. Each line has the field that holds the node, its kind and where it starts, along with
+/// any plain values it holds:
+///
+/// ```text
+/// [0]: Expression 1:7
+///   expr: Assign 1:7
+///     value: Int 1:12 i=1
+///     var: Variable 1:7 name="a"
+/// ```
+pub fn dump(program: &Program, options: &DumpOptions) -> String {
+    let mut dump = Dump { options, output: String::new() };
+    let value = serde_json::to_value(program).expect("the AST is serializable");
+
+    for (i, statement) in value.as_array().into_iter().flatten().enumerate() {
+        dump.node(statement, format!("[{}]", i), 0);
+    }
+
+    dump.output
+}
+
+struct Dump<'a> {
+    options: &'a DumpOptions,
+    output: String,
+}
+
+impl<'a> Dump<'a> {
+    /// Print a node, if it's selected, and then its children. `path` is the path to the node from
+    /// the closest printed node above it, and `level` is how many printed nodes are above it.
+    fn node(&mut self, value: &Value, path: String, level: usize) {
+        let mut kind = String::new();
+        let mut plain = Vec::new();
+        let mut children = Vec::new();
+
+        match &value["kind"] {
+            Value::String(variant) => kind = variant.clone(),
+            Value::Object(variants) => {
+                for (variant, fields) in variants {
+                    kind = variant.clone();
+                    fields_of(fields, String::new(), &mut plain, &mut children);
+                }
+            },
+            _ => {},
+        }
+
+        if let Some(meta) = value.get("meta") {
+            fields_of(meta, "meta".into(), &mut plain, &mut children);
+        }
+
+        let shown = self.options.kinds.is_empty() || self.options.kinds.iter().any(|k| k.eq_ignore_ascii_case(&kind));
+
+        if shown {
+            if self.options.depth.is_some_and(|depth| level >= depth) {
+                return;
+            }
+
+            let _ = write!(self.output, "{}{}: {} {}:{}", "  ".repeat(level), path, kind, value["span"]["line"], value["span"]["col"]);
+
+            for field in plain {
+                let _ = write!(self.output, " {}", field);
+            }
+
+            self.output.push('\n');
+        }
+
+        for (child_path, child) in children {
+            if shown {
+                self.node(child, child_path, level + 1);
+            } else {
+                self.node(child, join(&path, &child_path), level);
+            }
+        }
+    }
+}
+
+/// Collect the plain values below a node as `path=value`, and the statements and expressions below
+/// it along with the path to each of them.
+fn fields_of<'v>(value: &'v Value, path: String, plain: &mut Vec<String>, children: &mut Vec<(String, &'v Value)>) {
+    match value {
+        Value::Object(object) if object.contains_key("kind") && object.contains_key("span") => children.push((path, value)),
+        // Identifiers are shown as just their name.
+        Value::Object(object) if object.len() == 2 && object.contains_key("name") && object.contains_key("span") => {
+            fields_of(&object["name"], path, plain, children);
+        },
+        Value::Object(object) => {
+            for (key, value) in object.iter().filter(|(key, _)| *key != "span") {
+                fields_of(value, join(&path, key), plain, children);
+            }
+        },
+        Value::Array(items) if ! items.is_empty() && items.iter().all(|item| ! item.is_object() && ! item.is_array()) => {
+            plain.push(format!("{}={}", path, value));
+        },
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                fields_of(item, format!("{}[{}]", path, i), plain, children);
+            }
+        },
+        Value::Null => {},
+        scalar => plain.push(format!("{}={}", path, scalar)),
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() || key.starts_with('[') {
+        format!("{}{}", path, key)
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dump, DumpOptions};
+    use crate::parse;
+
+    #[test]
+    fn tree() {
+        assert_eq!(dump(&parse("<?php $a = 1 + 2;").unwrap(), &DumpOptions::default()), "\
+[0]: Expression 1:7
+  expr: Assign 1:7
+    value: Infix 1:12 op=\"Add\"
+      lhs: Int 1:12 i=1
+      rhs: Int 1:16 i=2
+    var: Variable 1:7 name=\"a\"
+");
+    }
+
+    #[test]
+    fn filters() {
+        let program = parse("<?php
+namespace App;
+
+final class A {
+    public function b(int $c) { return fn () => new class { function d() {} }; }
+}
+").unwrap();
+
+        let options = DumpOptions { kinds: vec!["class".into(), "Method".into()], depth: None };
+
+        assert_eq!(dump(&program, &options), "\
+[1]: Class 4:1 flag=\"Final\" name=\"A\"
+  body[0]: Method 5:5 flags=[\"Public\"] name=\"b\" params[0].by_ref=false params[0].type.Plain=\"int\" params[0].variadic=false returns_by_ref=false
+    body[0].value.expr.target.body[0]: Method 5:61 name=\"d\" returns_by_ref=false
+");
+
+        let options = DumpOptions { kinds: vec![], depth: Some(2) };
+
+        assert_eq!(dump(&program, &options), "\
+[0]: Namespace 2:1 name=\"App\"
+[1]: Class 4:1 flag=\"Final\" name=\"A\"
+  body[0]: Method 5:5 flags=[\"Public\"] name=\"b\" params[0].by_ref=false params[0].type.Plain=\"int\" params[0].variadic=false returns_by_ref=false
+");
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use trunk_lexer::{LexerError, Span};
+
+use crate::ParseError;
+
+/// An error from either stage of turning source code into an AST, or from reading the source
+/// in the first place.
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Lexer(LexerError),
+    Parser(ParseError),
+    /// An error in a particular file, such as one from `Parser::parse_file`.
+    File { path: PathBuf, error: Box<Error> },
+}
+
+impl Error {
+    /// Attribute the error to a file, unless it already belongs to one.
+    pub fn in_file(self, path: impl Into<PathBuf>) -> Self {
+        match self {
+            Self::File { .. } => self,
+            error => Self::File { path: path.into(), error: Box::new(error) },
+        }
+    }
+
+    /// The file that the error occurred in, if it's known.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::File { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The location in the source that the error refers to, if it has one.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::Io(_) => None,
+            Self::Lexer(err) => Some(err.span),
+            Self::Parser(err) => err.span(),
+            Self::File { error, .. } => error.span(),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<LexerError> for Error {
+    fn from(err: LexerError) -> Self {
+        Self::Lexer(err)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Self {
+        Self::Parser(err)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => err.fmt(f),
+            Self::Lexer(err) => err.fmt(f),
+            Self::Parser(err) => err.fmt(f),
+            Self::File { path, error } => match error.span() {
+                Some(span) => write!(f, "{}: {}", span.in_file(path), error),
+                None => write!(f, "{}: {}", path.display(), error),
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Lexer(err) => Some(err),
+            Self::Parser(err) => Some(err),
+            Self::File { error, .. } => Some(error),
+        }
+    }
+}
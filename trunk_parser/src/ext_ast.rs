@@ -0,0 +1,1289 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    Statement, StatementKind, Expression, ExpressionKind, Program, Block, Param, Identifier, Type, InfixOp, AttributeGroup, StringPart, Arg,
+    UseKind, CastKind, MagicConst, BackedEnumType, ClassFlag, MethodFlag, PropertyFlag, ConstFlag, Span, relative_name,
+};
+
+/// The php-ast version whose node layout is produced by `to_ext_ast`.
+pub const EXT_AST_VERSION: u32 = 90;
+
+// `ast\flags\MODIFIER_*`, which are the engine's `ZEND_ACC_*` bits.
+const MODIFIER_PUBLIC: u32 = 1;
+const MODIFIER_PROTECTED: u32 = 2;
+const MODIFIER_PRIVATE: u32 = 4;
+const MODIFIER_STATIC: u32 = 16;
+const MODIFIER_FINAL: u32 = 32;
+const MODIFIER_ABSTRACT: u32 = 64;
+const MODIFIER_READONLY: u32 = 128;
+const MODIFIER_PUBLIC_SET: u32 = 1 << 10;
+const MODIFIER_PROTECTED_SET: u32 = 1 << 11;
+const MODIFIER_PRIVATE_SET: u32 = 1 << 12;
+const FUNC_RETURNS_REF: u32 = 1 << 12;
+
+const CLASS_INTERFACE: u32 = 1;
+const CLASS_TRAIT: u32 = 2;
+const CLASS_ANONYMOUS: u32 = 4;
+const CLASS_FINAL: u32 = 32;
+const CLASS_ABSTRACT: u32 = 64;
+const CLASS_ENUM: u32 = 1 << 28;
+
+const PARAM_REF: u32 = 8;
+const PARAM_VARIADIC: u32 = 16;
+
+const NAME_FQ: u32 = 0;
+const NAME_NOT_FQ: u32 = 1;
+const NAME_RELATIVE: u32 = 2;
+
+const ARRAY_SYNTAX_SHORT: u32 = 3;
+const ARRAY_ELEM_REF: u32 = 1;
+const CLOSURE_USE_REF: u32 = 1;
+
+const TYPE_NULL: u32 = 1;
+const TYPE_FALSE: u32 = 2;
+const TYPE_TRUE: u32 = 3;
+const TYPE_LONG: u32 = 4;
+const TYPE_DOUBLE: u32 = 5;
+const TYPE_STRING: u32 = 6;
+const TYPE_ARRAY: u32 = 7;
+const TYPE_OBJECT: u32 = 8;
+const TYPE_CALLABLE: u32 = 12;
+const TYPE_ITERABLE: u32 = 13;
+const TYPE_VOID: u32 = 14;
+const TYPE_STATIC: u32 = 15;
+const TYPE_MIXED: u32 = 16;
+const TYPE_NEVER: u32 = 17;
+const TYPE_BOOL: u32 = 18;
+
+const UNARY_BITWISE_NOT: u32 = 13;
+const UNARY_BOOL_NOT: u32 = 14;
+const UNARY_SILENCE: u32 = 260;
+const UNARY_PLUS: u32 = 261;
+const UNARY_MINUS: u32 = 262;
+
+const BINARY_COALESCE: u32 = 260;
+
+const EXEC_INCLUDE: u32 = 2;
+const EXEC_INCLUDE_ONCE: u32 = 4;
+const EXEC_REQUIRE: u32 = 8;
+const EXEC_REQUIRE_ONCE: u32 = 16;
+
+// These flags are token ids, so they're only stable for a single PHP version. The values are
+// those of PHP 8.3.
+const USE_NORMAL: u32 = 333;
+const USE_FUNCTION: u32 = 310;
+const USE_CONST: u32 = 312;
+const MAGIC_LINE: u32 = 343;
+const MAGIC_FILE: u32 = 344;
+const MAGIC_DIR: u32 = 345;
+const MAGIC_CLASS: u32 = 346;
+const MAGIC_TRAIT: u32 = 347;
+const MAGIC_METHOD: u32 = 348;
+const MAGIC_FUNCTION: u32 = 349;
+const MAGIC_NAMESPACE: u32 = 350;
+
+const BINARY_FLAGS: &[(u32, &str)] = &[
+    (1, "BINARY_ADD"), (2, "BINARY_SUB"), (3, "BINARY_MUL"), (4, "BINARY_DIV"), (5, "BINARY_MOD"), (6, "BINARY_SHIFT_LEFT"),
+    (7, "BINARY_SHIFT_RIGHT"), (8, "BINARY_CONCAT"), (9, "BINARY_BITWISE_OR"), (10, "BINARY_BITWISE_AND"), (11, "BINARY_BITWISE_XOR"),
+    (12, "BINARY_POW"), (15, "BINARY_BOOL_XOR"), (16, "BINARY_IS_IDENTICAL"), (17, "BINARY_IS_NOT_IDENTICAL"), (18, "BINARY_IS_EQUAL"),
+    (19, "BINARY_IS_NOT_EQUAL"), (20, "BINARY_IS_SMALLER"), (21, "BINARY_IS_SMALLER_OR_EQUAL"), (170, "BINARY_SPACESHIP"),
+    (256, "BINARY_IS_GREATER"), (257, "BINARY_IS_GREATER_OR_EQUAL"), (258, "BINARY_BOOL_OR"), (259, "BINARY_BOOL_AND"),
+    (BINARY_COALESCE, "BINARY_COALESCE"),
+];
+
+const TYPE_FLAGS: &[(u32, &str)] = &[
+    (TYPE_NULL, "TYPE_NULL"), (TYPE_FALSE, "TYPE_FALSE"), (TYPE_TRUE, "TYPE_TRUE"), (TYPE_LONG, "TYPE_LONG"), (TYPE_DOUBLE, "TYPE_DOUBLE"),
+    (TYPE_STRING, "TYPE_STRING"), (TYPE_ARRAY, "TYPE_ARRAY"), (TYPE_OBJECT, "TYPE_OBJECT"), (TYPE_CALLABLE, "TYPE_CALLABLE"),
+    (TYPE_ITERABLE, "TYPE_ITERABLE"), (TYPE_VOID, "TYPE_VOID"), (TYPE_STATIC, "TYPE_STATIC"), (TYPE_MIXED, "TYPE_MIXED"),
+    (TYPE_NEVER, "TYPE_NEVER"), (TYPE_BOOL, "TYPE_BOOL"),
+];
+
+const MODIFIER_FLAGS: &[(u32, &str)] = &[
+    (MODIFIER_PUBLIC, "MODIFIER_PUBLIC"), (MODIFIER_PROTECTED, "MODIFIER_PROTECTED"), (MODIFIER_PRIVATE, "MODIFIER_PRIVATE"),
+    (MODIFIER_STATIC, "MODIFIER_STATIC"), (MODIFIER_FINAL, "MODIFIER_FINAL"), (MODIFIER_ABSTRACT, "MODIFIER_ABSTRACT"),
+];
+
+const FUNC_FLAGS: &[(u32, &str)] = &[
+    (MODIFIER_PUBLIC, "MODIFIER_PUBLIC"), (MODIFIER_PROTECTED, "MODIFIER_PROTECTED"), (MODIFIER_PRIVATE, "MODIFIER_PRIVATE"),
+    (MODIFIER_STATIC, "MODIFIER_STATIC"), (MODIFIER_FINAL, "MODIFIER_FINAL"), (MODIFIER_ABSTRACT, "MODIFIER_ABSTRACT"),
+    (FUNC_RETURNS_REF, "FUNC_RETURNS_REF"),
+];
+
+const PROPERTY_FLAGS: &[(u32, &str)] = &[
+    (MODIFIER_PUBLIC, "MODIFIER_PUBLIC"), (MODIFIER_PROTECTED, "MODIFIER_PROTECTED"), (MODIFIER_PRIVATE, "MODIFIER_PRIVATE"),
+    (MODIFIER_STATIC, "MODIFIER_STATIC"), (MODIFIER_FINAL, "MODIFIER_FINAL"), (MODIFIER_ABSTRACT, "MODIFIER_ABSTRACT"),
+    (MODIFIER_READONLY, "MODIFIER_READONLY"), (MODIFIER_PUBLIC_SET, "MODIFIER_PUBLIC_SET"), (MODIFIER_PROTECTED_SET, "MODIFIER_PROTECTED_SET"),
+    (MODIFIER_PRIVATE_SET, "MODIFIER_PRIVATE_SET"),
+];
+
+// Promoted constructor parameters carry their property's modifiers, but can never be static.
+const PARAM_FLAGS: &[(u32, &str)] = &[
+    (MODIFIER_PUBLIC, "MODIFIER_PUBLIC"), (MODIFIER_PROTECTED, "MODIFIER_PROTECTED"), (MODIFIER_PRIVATE, "MODIFIER_PRIVATE"),
+    (MODIFIER_READONLY, "MODIFIER_READONLY"), (MODIFIER_PUBLIC_SET, "MODIFIER_PUBLIC_SET"), (MODIFIER_PROTECTED_SET, "MODIFIER_PROTECTED_SET"),
+    (MODIFIER_PRIVATE_SET, "MODIFIER_PRIVATE_SET"), (PARAM_REF, "PARAM_REF"), (PARAM_VARIADIC, "PARAM_VARIADIC"),
+];
+
+const CLASS_FLAGS: &[(u32, &str)] = &[
+    (CLASS_INTERFACE, "CLASS_INTERFACE"), (CLASS_TRAIT, "CLASS_TRAIT"), (CLASS_ANONYMOUS, "CLASS_ANONYMOUS"), (CLASS_FINAL, "CLASS_FINAL"),
+    (CLASS_ABSTRACT, "CLASS_ABSTRACT"), (CLASS_ENUM, "CLASS_ENUM"),
+];
+
+/// The flag names for a node kind, and whether the flags are combined as a bit set rather than
+/// being a single exclusive value. This mirrors `get_flag_info()` in php-ast's `util.php`.
+fn flag_info(kind: &str) -> Option<(&'static [(u32, &'static str)], bool)> {
+    Some(match kind {
+        "AST_NAME" => (&[(NAME_FQ, "NAME_FQ"), (NAME_NOT_FQ, "NAME_NOT_FQ"), (NAME_RELATIVE, "NAME_RELATIVE")], false),
+        "AST_ARRAY" => (&[(1, "ARRAY_SYNTAX_LIST"), (2, "ARRAY_SYNTAX_LONG"), (ARRAY_SYNTAX_SHORT, "ARRAY_SYNTAX_SHORT")], false),
+        "AST_ARRAY_ELEM" => (&[(ARRAY_ELEM_REF, "ARRAY_ELEM_REF")], false),
+        "AST_CLOSURE_VAR" => (&[(CLOSURE_USE_REF, "CLOSURE_USE_REF")], false),
+        "AST_TYPE" | "AST_CAST" => (TYPE_FLAGS, false),
+        "AST_UNARY_OP" => (&[
+            (UNARY_BOOL_NOT, "UNARY_BOOL_NOT"), (UNARY_BITWISE_NOT, "UNARY_BITWISE_NOT"), (UNARY_MINUS, "UNARY_MINUS"),
+            (UNARY_PLUS, "UNARY_PLUS"), (UNARY_SILENCE, "UNARY_SILENCE"),
+        ], false),
+        "AST_BINARY_OP" | "AST_ASSIGN_OP" => (BINARY_FLAGS, false),
+        "AST_MAGIC_CONST" => (&[
+            (MAGIC_LINE, "MAGIC_LINE"), (MAGIC_FILE, "MAGIC_FILE"), (MAGIC_DIR, "MAGIC_DIR"), (MAGIC_NAMESPACE, "MAGIC_NAMESPACE"),
+            (MAGIC_FUNCTION, "MAGIC_FUNCTION"), (MAGIC_METHOD, "MAGIC_METHOD"), (MAGIC_CLASS, "MAGIC_CLASS"), (MAGIC_TRAIT, "MAGIC_TRAIT"),
+        ], false),
+        "AST_USE" | "AST_GROUP_USE" | "AST_USE_ELEM" => (&[(USE_NORMAL, "USE_NORMAL"), (USE_FUNCTION, "USE_FUNCTION"), (USE_CONST, "USE_CONST")], false),
+        "AST_INCLUDE_OR_EVAL" => (&[
+            (1, "EXEC_EVAL"), (EXEC_INCLUDE, "EXEC_INCLUDE"), (EXEC_INCLUDE_ONCE, "EXEC_INCLUDE_ONCE"), (EXEC_REQUIRE, "EXEC_REQUIRE"),
+            (EXEC_REQUIRE_ONCE, "EXEC_REQUIRE_ONCE"),
+        ], false),
+        "AST_METHOD" | "AST_FUNC_DECL" | "AST_CLOSURE" | "AST_ARROW_FUNC" => (FUNC_FLAGS, true),
+        "AST_PROP_GROUP" => (PROPERTY_FLAGS, true),
+        "AST_CLASS_CONST_GROUP" => (MODIFIER_FLAGS, true),
+        "AST_PARAM" => (PARAM_FLAGS, true),
+        "AST_CLASS" => (CLASS_FLAGS, true),
+        _ => return None,
+    })
+}
+
+/// A node in php-ast's format. Lists, such as `AST_STMT_LIST`, use their indices as child names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AstNode {
+    pub kind: &'static str,
+    pub flags: u32,
+    pub lineno: usize,
+    pub children: Vec<(String, AstValue)>,
+}
+
+impl AstNode {
+    fn new(kind: &'static str, flags: u32, span: Span, children: Vec<(&str, AstValue)>) -> Self {
+        Self { kind, flags, lineno: span.line, children: children.into_iter().map(|(name, value)| (name.to_string(), value)).collect() }
+    }
+
+    fn list(kind: &'static str, span: Span, children: impl IntoIterator<Item = AstValue>) -> Self {
+        Self { kind, flags: 0, lineno: span.line, children: children.into_iter().enumerate().map(|(i, value)| (i.to_string(), value)).collect() }
+    }
+
+    pub fn child(&self, name: &str) -> Option<&AstValue> {
+        self.children.iter().find(|(n, _)| n == name).map(|(_, value)| value)
+    }
+
+    fn format_flags(&self) -> String {
+        match flag_info(self.kind) {
+            Some((info, false)) => match info.iter().find(|(flag, _)| *flag == self.flags) {
+                Some((_, name)) => format!("{} ({})", name, self.flags),
+                None => self.flags.to_string(),
+            },
+            Some((info, true)) if self.flags != 0 => {
+                let names: Vec<_> = info.iter().filter(|(flag, _)| self.flags & flag != 0).map(|(_, name)| *name).collect();
+
+                format!("{} ({})", names.join(" | "), self.flags)
+            },
+            _ => self.flags.to_string(),
+        }
+    }
+}
+
+/// The output of `ast_dump()` from php-ast's `util.php`, without line numbers.
+impl Display for AstNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+
+        if flag_info(self.kind).is_some() || self.flags != 0 {
+            write!(f, "\n    flags: {}", self.format_flags())?;
+        }
+
+        for (name, child) in &self.children {
+            write!(f, "\n    {}: {}", name, child.to_string().replace('\n', "\n    "))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AstValue {
+    Null,
+    Int(i64),
+    Float(f64),
+    String(String),
+    Node(AstNode),
+}
+
+impl Display for AstValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => write!(f, "null"),
+            Self::Int(i) => write!(f, "{}", i),
+            Self::Float(float) => write!(f, "{}", float),
+            Self::String(s) => write!(f, "\"{}\"", s),
+            Self::Node(node) => write!(f, "{}", node),
+        }
+    }
+}
+
+impl From<AstNode> for AstValue {
+    fn from(node: AstNode) -> Self {
+        Self::Node(node)
+    }
+}
+
+impl From<&str> for AstValue {
+    fn from(s: &str) -> Self {
+        Self::String(s.to_string())
+    }
+}
+
+impl From<&String> for AstValue {
+    fn from(s: &String) -> Self {
+        Self::String(s.clone())
+    }
+}
+
+impl<T: Into<AstValue>> From<Option<T>> for AstValue {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Self::Null)
+    }
+}
+
+/// Convert a program into the tree that php-ast's `ast\parse_code()` returns for the same source,
+/// using the node layout of AST version 90. Comments and empty statements are dropped, like the
+/// engine does.
+pub fn to_ext_ast(program: &Program) -> AstNode {
+    ExtAst::default().statements(program, Span::default())
+}
+
+#[derive(Default)]
+struct ExtAst {
+    decl_id: i64,
+    in_interface: bool,
+}
+
+fn name(name: &str, span: Span) -> AstNode {
+    let (flags, name) = if let Some(name) = name.strip_prefix('\\') {
+        (NAME_FQ, name)
+    } else if let Some(name) = relative_name(name) {
+        (NAME_RELATIVE, name)
+    } else {
+        (NAME_NOT_FQ, name)
+    };
+
+    AstNode::new("AST_NAME", flags, span, vec![("name", name.into())])
+}
+
+fn name_list(names: &[Identifier], span: Span) -> AstValue {
+    if names.is_empty() {
+        return AstValue::Null;
+    }
+
+    AstNode::list("AST_NAME_LIST", span, names.iter().map(|n| name(&n.name, n.span).into())).into()
+}
+
+fn flags<T>(flags: &[T], bit: impl Fn(&T) -> u32) -> u32 {
+    flags.iter().fold(0, |acc, flag| acc | bit(flag))
+}
+
+fn property_flag(flag: &PropertyFlag) -> u32 {
+    match flag {
+        PropertyFlag::Public => MODIFIER_PUBLIC,
+        PropertyFlag::Protected => MODIFIER_PROTECTED,
+        PropertyFlag::Private => MODIFIER_PRIVATE,
+        PropertyFlag::PublicSet => MODIFIER_PUBLIC_SET,
+        PropertyFlag::ProtectedSet => MODIFIER_PROTECTED_SET,
+        PropertyFlag::PrivateSet => MODIFIER_PRIVATE_SET,
+        PropertyFlag::Static => MODIFIER_STATIC,
+        PropertyFlag::Readonly => MODIFIER_READONLY,
+    }
+}
+
+fn method_flag(flag: &MethodFlag) -> u32 {
+    match flag {
+        MethodFlag::Final => MODIFIER_FINAL,
+        MethodFlag::Abstract => MODIFIER_ABSTRACT,
+        MethodFlag::Public => MODIFIER_PUBLIC,
+        MethodFlag::Protected => MODIFIER_PROTECTED,
+        MethodFlag::Private => MODIFIER_PRIVATE,
+        MethodFlag::Static => MODIFIER_STATIC,
+    }
+}
+
+fn const_flag(flag: &ConstFlag) -> u32 {
+    match flag {
+        ConstFlag::Final => MODIFIER_FINAL,
+        ConstFlag::Public => MODIFIER_PUBLIC,
+        ConstFlag::Protected => MODIFIER_PROTECTED,
+        ConstFlag::Private => MODIFIER_PRIVATE,
+    }
+}
+
+fn r#type(r#type: &Type) -> AstNode {
+    match r#type {
+        Type::Plain(plain) => type_name(plain),
+        Type::Nullable(nullable) => AstNode::new("AST_NULLABLE_TYPE", 0, Span::default(), vec![("type", type_name(nullable).into())]),
+        Type::Union(types) => AstNode::list("AST_TYPE_UNION", Span::default(), types.iter().map(|t| self::r#type(t).into())),
+        Type::Intersection(types) => AstNode::list("AST_TYPE_INTERSECTION", Span::default(), types.iter().map(|t| self::r#type(t).into())),
+    }
+}
+
+/// Built-in types are `AST_TYPE` nodes, while class types are names.
+fn type_name(r#type: &str) -> AstNode {
+    let flags = match r#type.to_ascii_lowercase().as_str() {
+        "null" => TYPE_NULL,
+        "false" => TYPE_FALSE,
+        "true" => TYPE_TRUE,
+        "int" => TYPE_LONG,
+        "float" => TYPE_DOUBLE,
+        "string" => TYPE_STRING,
+        "array" => TYPE_ARRAY,
+        "object" => TYPE_OBJECT,
+        "callable" => TYPE_CALLABLE,
+        "iterable" => TYPE_ITERABLE,
+        "void" => TYPE_VOID,
+        "static" => TYPE_STATIC,
+        "mixed" => TYPE_MIXED,
+        "never" => TYPE_NEVER,
+        "bool" => TYPE_BOOL,
+        _ => return name(r#type, Span::default()),
+    };
+
+    AstNode::new("AST_TYPE", flags, Span::default(), vec![])
+}
+
+fn optional<T>(value: &Option<T>, f: impl FnOnce(&T) -> AstNode) -> AstValue {
+    value.as_ref().map(f).into()
+}
+
+impl ExtAst {
+    fn next_decl_id(&mut self) -> AstValue {
+        let id = self.decl_id;
+        self.decl_id += 1;
+
+        AstValue::Int(id)
+    }
+
+    fn statements(&mut self, statements: &[Statement], span: Span) -> AstNode {
+        let mut children = Vec::new();
+
+        for statement in statements {
+            self.statement(statement, &mut children);
+        }
+
+        AstNode::list("AST_STMT_LIST", span, children)
+    }
+
+    fn attributes(&mut self, groups: &[AttributeGroup]) -> AstValue {
+        if groups.is_empty() {
+            return AstValue::Null;
+        }
+
+        AstNode::list("AST_ATTRIBUTE_LIST", Span::default(), groups.iter().map(|group| {
+            AstNode::list("AST_ATTRIBUTE_GROUP", Span::default(), group.members.iter().map(|attribute| {
+                let args = if attribute.args.is_empty() { AstValue::Null } else { self.args(&attribute.args, attribute.name.span).into() };
+
+                AstNode::new("AST_ATTRIBUTE", 0, attribute.name.span, vec![
+                    ("class", name(&attribute.name.name, attribute.name.span).into()),
+                    ("args", args),
+                ]).into()
+            }).collect::<Vec<_>>()).into()
+        }).collect::<Vec<_>>()).into()
+    }
+
+    fn params(&mut self, params: &[Param], span: Span) -> AstNode {
+        AstNode::list("AST_PARAM_LIST", span, params.iter().map(|param| {
+            let mut flags = flags(&param.flags, property_flag);
+
+            if param.by_ref {
+                flags |= PARAM_REF;
+            }
+
+            if param.variadic {
+                flags |= PARAM_VARIADIC;
+            }
+
+            let name = match &param.name.kind {
+                ExpressionKind::Variable { name } => name.into(),
+                _ => AstValue::Null,
+            };
+
+            AstNode::new("AST_PARAM", flags, param.span, vec![
+                ("type", optional(&param.r#type, r#type)),
+                ("name", name),
+                ("default", self.optional_expression(param.default.as_ref())),
+                ("attributes", self.attributes(&param.attributes)),
+                ("docComment", AstValue::Null),
+            ]).into()
+        }).collect::<Vec<_>>())
+    }
+
+    fn args(&mut self, args: &[Arg], span: Span) -> AstNode {
+        AstNode::list("AST_ARG_LIST", span, args.iter().map(|arg| {
+            let value = self.expression(&arg.value);
+
+            match (&arg.name, arg.unpack) {
+                (Some(name), _) => AstNode::new("AST_NAMED_ARG", 0, arg.value.span, vec![("name", name.into()), ("expr", value)]).into(),
+                (None, true) => AstNode::new("AST_UNPACK", 0, arg.value.span, vec![("expr", value)]).into(),
+                (None, false) => value,
+            }
+        }).collect::<Vec<_>>())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn function(&mut self, kind: &'static str, flags: u32, name: &str, doc_comment: &Option<String>, params: &[Param], stmts: AstValue, return_type: &Option<Type>, attributes: &[AttributeGroup], span: Span) -> AstNode {
+        let mut children = vec![
+            ("name", name.into()),
+            ("docComment", doc_comment.as_ref().into()),
+            ("params", self.params(params, span).into()),
+        ];
+
+        if kind == "AST_CLOSURE" {
+            children.push(("uses", AstValue::Null));
+        }
+
+        children.extend([
+            ("stmts", stmts),
+            ("returnType", optional(return_type, r#type)),
+            ("attributes", self.attributes(attributes)),
+            ("__declId", self.next_decl_id()),
+        ]);
+
+        AstNode::new(kind, flags, span, children)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn class(&mut self, flags: u32, name: Option<&str>, doc_comment: &Option<String>, extends: AstValue, implements: AstValue, body: &Block, attributes: &[AttributeGroup], r#type: AstValue, span: Span) -> AstNode {
+        let stmts = self.statements(body, span);
+
+        AstNode::new("AST_CLASS", flags, span, vec![
+            ("name", name.into()),
+            ("docComment", doc_comment.as_ref().into()),
+            ("extends", extends),
+            ("implements", implements),
+            ("stmts", stmts.into()),
+            ("attributes", self.attributes(attributes)),
+            ("type", r#type),
+            ("__declId", self.next_decl_id()),
+        ])
+    }
+
+    fn statement(&mut self, statement: &Statement, output: &mut Vec<AstValue>) {
+        // Grouped the same way as expressions, to keep the stack frame for each level small.
+        match &statement.kind {
+            StatementKind::Namespace { .. } | StatementKind::Use { .. } | StatementKind::Const { .. } |
+            StatementKind::Function { .. } => self.declaration(statement, output),
+            StatementKind::Class { .. } | StatementKind::Trait { .. } | StatementKind::Interface { .. } | StatementKind::Enum { .. } => self.class_like(statement, output),
+            StatementKind::TraitUse { .. } | StatementKind::Method { .. } | StatementKind::Var { .. } |
+            StatementKind::Property { .. } | StatementKind::Constant { .. } | StatementKind::EnumCase { .. } => self.class_member(statement, output),
+            StatementKind::While { .. } | StatementKind::For { .. } | StatementKind::Foreach { .. } | StatementKind::If { .. } |
+            StatementKind::Switch { .. } | StatementKind::Try { .. } | StatementKind::Return { .. } | StatementKind::Break { .. } |
+            StatementKind::Continue { .. } => self.control_flow(statement, output),
+            StatementKind::InlineHtml(..) | StatementKind::Static { .. } | StatementKind::Global { .. } | StatementKind::Echo { .. } |
+            StatementKind::Expression { .. } | StatementKind::Comment { .. } | StatementKind::Noop | StatementKind::Error |
+            StatementKind::HaltCompiler { .. } => self.simple_statement(statement, output),
+        }
+    }
+
+    /// Namespaces, imports, constants and functions.
+    fn declaration(&mut self, statement: &Statement, output: &mut Vec<AstValue>) {
+        let span = statement.span;
+
+        let node = match &statement.kind {
+            StatementKind::Namespace { name, body } => {
+                let stmts = if body.is_empty() { AstValue::Null } else { self.statements(body, span).into() };
+
+                AstNode::new("AST_NAMESPACE", 0, span, vec![("name", name.into()), ("stmts", stmts)])
+            },
+            StatementKind::Use { uses, kind } => {
+                let flags = match kind {
+                    UseKind::Normal => USE_NORMAL,
+                    UseKind::Function => USE_FUNCTION,
+                    UseKind::Const => USE_CONST,
+                };
+
+                let mut node = AstNode::list("AST_USE", span, uses.iter().map(|r#use| {
+                    AstNode::new("AST_USE_ELEM", 0, r#use.name.span, vec![
+                        ("name", r#use.name.name.trim_start_matches('\\').into()),
+                        ("alias", r#use.alias.as_ref().map(|alias| &alias.name).into()),
+                    ]).into()
+                }).collect::<Vec<_>>());
+                node.flags = flags;
+
+                node
+            },
+            // The doc comment belongs to the first constant, as it's the one that follows it directly.
+            StatementKind::Const { constants, doc_comment } => AstNode::list("AST_CONST_DECL", span, constants.iter().enumerate().map(|(i, constant)| {
+                AstNode::new("AST_CONST_ELEM", 0, constant.name.span, vec![
+                    ("name", (&constant.name.name).into()),
+                    ("value", self.expression(&constant.value)),
+                    ("docComment", doc_comment.as_ref().filter(|_| i == 0).into()),
+                ]).into()
+            }).collect::<Vec<_>>()),
+            StatementKind::Function { name, params, body, return_type, returns_by_ref, attributes, doc_comment } => {
+                let flags = if *returns_by_ref { FUNC_RETURNS_REF } else { 0 };
+                let stmts = self.statements(body, span).into();
+
+                self.function("AST_FUNC_DECL", flags, &name.name, doc_comment, params, stmts, return_type, attributes, span)
+            },
+            _ => unreachable!(),
+        };
+
+        output.push(node.into());
+    }
+
+    /// Classes, traits, interfaces and enums.
+    fn class_like(&mut self, statement: &Statement, output: &mut Vec<AstValue>) {
+        let span = statement.span;
+
+        let node = match &statement.kind {
+            StatementKind::Class { name, extends, implements, body, flag, attributes, doc_comment } => {
+                let flags = match flag {
+                    Some(ClassFlag::Abstract) => CLASS_ABSTRACT,
+                    Some(ClassFlag::Final) => CLASS_FINAL,
+                    None => 0,
+                };
+                let extends = optional(extends, |extends| self::name(&extends.name, extends.span));
+
+                self.class(flags, Some(&name.name), doc_comment, extends, name_list(implements, span), body, attributes, AstValue::Null, span)
+            },
+            StatementKind::Trait { name, body, attributes } => {
+                self.class(CLASS_TRAIT, Some(&name.name), &None, AstValue::Null, AstValue::Null, body, attributes, AstValue::Null, span)
+            },
+            // An interface's parents are stored as the interfaces it implements.
+            StatementKind::Interface { name, extends, body, attributes } => {
+                let in_interface = std::mem::replace(&mut self.in_interface, true);
+                let class = self.class(CLASS_INTERFACE, Some(&name.name), &None, AstValue::Null, name_list(extends, span), body, attributes, AstValue::Null, span);
+                self.in_interface = in_interface;
+
+                class
+            },
+            StatementKind::Enum { name, implements, backed_type, body, attributes } => {
+                let r#type = optional(backed_type, |backed_type| AstNode::new("AST_TYPE", match backed_type {
+                    BackedEnumType::String => TYPE_STRING,
+                    BackedEnumType::Int => TYPE_LONG,
+                }, span, vec![]));
+
+                self.class(CLASS_ENUM | CLASS_FINAL, Some(&name.name), &None, AstValue::Null, name_list(implements, span), body, attributes, r#type, span)
+            },
+            _ => unreachable!(),
+        };
+
+        output.push(node.into());
+    }
+
+    /// Methods, properties, constants and other members of classes.
+    fn class_member(&mut self, statement: &Statement, output: &mut Vec<AstValue>) {
+        let span = statement.span;
+
+        let node = match &statement.kind {
+            StatementKind::TraitUse { traits } => AstNode::new("AST_USE_TRAIT", 0, span, vec![
+                ("traits", name_list(traits, span)),
+                ("adaptations", AstValue::Null),
+            ]),
+            StatementKind::Method { name, params, body, flags, return_type, returns_by_ref, attributes, doc_comment } => {
+                let mut flags = self::flags(flags, method_flag);
+
+                if *returns_by_ref {
+                    flags |= FUNC_RETURNS_REF;
+                }
+
+                let stmts = if self.in_interface || flags & MODIFIER_ABSTRACT != 0 {
+                    AstValue::Null
+                } else {
+                    let in_interface = std::mem::replace(&mut self.in_interface, false);
+                    let stmts = self.statements(body, span);
+                    self.in_interface = in_interface;
+
+                    stmts.into()
+                };
+
+                self.function("AST_METHOD", flags, &name.name, doc_comment, params, stmts, return_type, attributes, span)
+            },
+            StatementKind::Var { var, value, r#type, attributes } => self.property(MODIFIER_PUBLIC, var, value, r#type, attributes, &None, span),
+            // Property hooks only have nodes from version 110 of php-ast's AST.
+            StatementKind::Property { var, value, r#type, flags, attributes, doc_comment, .. } => {
+                self.property(self::flags(flags, property_flag), var, value, r#type, attributes, doc_comment, span)
+            },
+            StatementKind::Constant { name, value, flags, attributes, doc_comment } => AstNode::new("AST_CLASS_CONST_GROUP", self::flags(flags, const_flag), span, vec![
+                ("const", AstNode::list("AST_CLASS_CONST_DECL", span, [AstNode::new("AST_CONST_ELEM", 0, name.span, vec![
+                    ("name", (&name.name).into()),
+                    ("value", self.expression(value)),
+                    ("docComment", doc_comment.as_ref().into()),
+                ]).into()]).into()),
+                ("attributes", self.attributes(attributes)),
+            ]),
+            StatementKind::EnumCase { name, value, attributes } => AstNode::new("AST_ENUM_CASE", 0, span, vec![
+                ("name", (&name.name).into()),
+                ("expr", self.optional_expression(value.as_ref())),
+                ("docComment", AstValue::Null),
+                ("attributes", self.attributes(attributes)),
+            ]),
+            _ => unreachable!(),
+        };
+
+        output.push(node.into());
+    }
+
+    /// Loops, conditionals and jumps.
+    fn control_flow(&mut self, statement: &Statement, output: &mut Vec<AstValue>) {
+        let span = statement.span;
+
+        let node = match &statement.kind {
+            StatementKind::While { condition, body } => AstNode::new("AST_WHILE", 0, span, vec![
+                ("cond", self.expression(condition)),
+                ("stmts", self.statements(body, span).into()),
+            ]),
+            StatementKind::For { init, condition, r#loop, then } => {
+                let mut expressions = |expression: &Option<Expression>| optional(expression, |expression| {
+                    AstNode::list("AST_EXPR_LIST", expression.span, [self.expression(expression)])
+                });
+
+                AstNode::new("AST_FOR", 0, span, vec![
+                    ("init", expressions(init)),
+                    ("cond", expressions(condition)),
+                    ("loop", expressions(r#loop)),
+                    ("stmts", self.statements(then, span).into()),
+                ])
+            },
+            StatementKind::Foreach { expr, by_ref, key_var, value_var, body } => {
+                let mut value = self.expression(value_var);
+
+                if *by_ref {
+                    value = AstNode::new("AST_REF", 0, value_var.span, vec![("var", value)]).into();
+                }
+
+                AstNode::new("AST_FOREACH", 0, span, vec![
+                    ("expr", self.expression(expr)),
+                    ("value", value),
+                    ("key", self.optional_expression(key_var.as_ref())),
+                    ("stmts", self.statements(body, span).into()),
+                ])
+            },
+            StatementKind::If { condition, then, else_ifs, r#else } => {
+                let mut elements = vec![AstNode::new("AST_IF_ELEM", 0, span, vec![
+                    ("cond", self.expression(condition)),
+                    ("stmts", self.statements(then, span).into()),
+                ]).into()];
+
+                for else_if in else_ifs {
+                    elements.push(AstNode::new("AST_IF_ELEM", 0, else_if.condition.span, vec![
+                        ("cond", self.expression(&else_if.condition)),
+                        ("stmts", self.statements(&else_if.body, else_if.condition.span).into()),
+                    ]).into());
+                }
+
+                if let Some(r#else) = r#else {
+                    elements.push(AstNode::new("AST_IF_ELEM", 0, span, vec![
+                        ("cond", AstValue::Null),
+                        ("stmts", self.statements(r#else, span).into()),
+                    ]).into());
+                }
+
+                AstNode::list("AST_IF", span, elements)
+            },
+            StatementKind::Switch { condition, cases } => {
+                let cases = AstNode::list("AST_SWITCH_LIST", span, cases.iter().map(|case| {
+                    AstNode::new("AST_SWITCH_CASE", 0, span, vec![
+                        ("cond", self.optional_expression(case.condition.as_ref())),
+                        ("stmts", self.statements(&case.body, span).into()),
+                    ]).into()
+                }).collect::<Vec<_>>());
+
+                AstNode::new("AST_SWITCH", 0, span, vec![("cond", self.expression(condition)), ("stmts", cases.into())])
+            },
+            StatementKind::Try { body, catches, finally } => {
+                let catches = AstNode::list("AST_CATCH_LIST", span, catches.iter().map(|catch| {
+                    AstNode::new("AST_CATCH", 0, catch.var.span, vec![
+                        ("class", name_list(&catch.types, catch.var.span)),
+                        ("var", self.expression(&catch.var)),
+                        ("stmts", self.statements(&catch.body, catch.var.span).into()),
+                    ]).into()
+                }).collect::<Vec<_>>());
+
+                AstNode::new("AST_TRY", 0, span, vec![
+                    ("try", self.statements(body, span).into()),
+                    ("catches", catches.into()),
+                    ("finally", finally.as_ref().map(|finally| self.statements(finally, span)).into()),
+                ])
+            },
+            StatementKind::Return { value } => AstNode::new("AST_RETURN", 0, span, vec![
+                ("expr", self.optional_expression(value.as_ref())),
+            ]),
+            StatementKind::Break { num } => AstNode::new("AST_BREAK", 0, span, vec![("depth", self.optional_expression(num.as_ref()))]),
+            StatementKind::Continue { num } => AstNode::new("AST_CONTINUE", 0, span, vec![("depth", self.optional_expression(num.as_ref()))]),
+            _ => unreachable!(),
+        };
+
+        output.push(node.into());
+    }
+
+    /// Statements that don't contain other statements.
+    fn simple_statement(&mut self, statement: &Statement, output: &mut Vec<AstValue>) {
+        let span = statement.span;
+
+        let node = match &statement.kind {
+            StatementKind::InlineHtml(html) => AstNode::new("AST_ECHO", 0, span, vec![("expr", html.into())]),
+            StatementKind::Static { vars } => {
+                for var in vars {
+                    let name = match &var.var.kind {
+                        ExpressionKind::Variable { name } => name.into(),
+                        _ => AstValue::Null,
+                    };
+
+                    output.push(AstNode::new("AST_STATIC", 0, var.var.span, vec![
+                        ("var", AstNode::new("AST_VAR", 0, var.var.span, vec![("name", name)]).into()),
+                        ("default", self.optional_expression(var.default.as_ref())),
+                    ]).into());
+                }
+
+                return;
+            },
+            StatementKind::Global { vars } => {
+                for var in vars {
+                    output.push(AstNode::new("AST_GLOBAL", 0, var.span, vec![("var", self.expression(var))]).into());
+                }
+
+                return;
+            },
+            // Every value in an `echo` statement is compiled as a separate statement.
+            StatementKind::Echo { values } => {
+                for value in values {
+                    output.push(AstNode::new("AST_ECHO", 0, span, vec![("expr", self.expression(value))]).into());
+                }
+
+                return;
+            },
+            StatementKind::Expression { expr } => {
+                output.push(self.expression(expr));
+
+                return;
+            },
+            StatementKind::Comment { .. } | StatementKind::Noop | StatementKind::Error => return,
+            StatementKind::HaltCompiler { offset, .. } => AstNode::new("AST_HALT_COMPILER", 0, span, vec![("offset", AstValue::Int(*offset as i64))]),
+            _ => unreachable!(),
+        };
+
+        output.push(node.into());
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn property(&mut self, flags: u32, var: &str, value: &Option<Expression>, r#type: &Option<Type>, attributes: &[AttributeGroup], doc_comment: &Option<String>, span: Span) -> AstNode {
+        AstNode::new("AST_PROP_GROUP", flags, span, vec![
+            ("type", optional(r#type, self::r#type)),
+            ("props", AstNode::list("AST_PROP_DECL", span, [AstNode::new("AST_PROP_ELEM", 0, span, vec![
+                ("name", var.into()),
+                ("default", self.optional_expression(value.as_ref())),
+                ("docComment", doc_comment.as_ref().into()),
+            ]).into()]).into()),
+            ("attributes", self.attributes(attributes)),
+        ])
+    }
+
+    fn optional_expression(&mut self, expression: Option<&Expression>) -> AstValue {
+        match expression {
+            Some(expression) => self.expression(expression),
+            None => AstValue::Null,
+        }
+    }
+
+    /// The class in `new`, `instanceof` and static accesses is a name when it's written literally.
+    fn class_reference(&mut self, expression: &Expression) -> AstValue {
+        match &expression.kind {
+            ExpressionKind::Identifier { name } => self::name(name, expression.span).into(),
+            ExpressionKind::Static => self::name("static", expression.span).into(),
+            _ => self.expression(expression),
+        }
+    }
+
+    /// Property and method names are plain strings unless they're dynamic.
+    fn member(&mut self, member: &Expression) -> AstValue {
+        match &member.kind {
+            ExpressionKind::Identifier { name } => name.into(),
+            ExpressionKind::Dynamic { value } => self.expression(value),
+            _ => self.expression(member),
+        }
+    }
+
+    fn string_parts(&mut self, parts: &[StringPart], span: Span) -> AstValue {
+        let constant: Option<String> = parts.iter().map(|part| match part {
+            StringPart::Const(value) => Some(value.as_str()),
+            StringPart::Expr(_) => None,
+        }).collect();
+
+        if let Some(constant) = constant {
+            return AstValue::String(constant);
+        }
+
+        AstNode::list("AST_ENCAPS_LIST", span, parts.iter().map(|part| match part {
+            StringPart::Const(value) => value.into(),
+            StringPart::Expr(expression) => self.expression(expression),
+        }).collect::<Vec<_>>()).into()
+    }
+
+    fn unary_op(&mut self, flags: u32, value: &Expression, span: Span) -> AstValue {
+        AstNode::new("AST_UNARY_OP", flags, span, vec![("expr", self.expression(value))]).into()
+    }
+
+    fn expression(&mut self, expression: &Expression) -> AstValue {
+        // Each group of expressions is exported by its own function, which keeps the stack frame for
+        // each level of nesting small enough for the parser's `max_depth`.
+        match &expression.kind {
+            ExpressionKind::Static | ExpressionKind::Int { .. } | ExpressionKind::Float { .. } | ExpressionKind::Variable { .. } |
+            ExpressionKind::Identifier { .. } | ExpressionKind::ConstantString { .. } | ExpressionKind::InterpolatedString { .. } |
+            ExpressionKind::Heredoc { .. } | ExpressionKind::Nowdoc { .. } | ExpressionKind::Bool { .. } | ExpressionKind::Null |
+            ExpressionKind::Error | ExpressionKind::MagicConst { .. } => self.scalar(expression),
+            ExpressionKind::PreIncrement { .. } | ExpressionKind::PostIncrement { .. } | ExpressionKind::PreDecrement { .. } |
+            ExpressionKind::PostDecrement { .. } | ExpressionKind::Dynamic { .. } | ExpressionKind::BooleanNot { .. } |
+            ExpressionKind::Clone { .. } | ExpressionKind::Throw { .. } | ExpressionKind::Yield { .. } |
+            ExpressionKind::Negate { .. } | ExpressionKind::UnaryPlus { .. } | ExpressionKind::BitwiseNot { .. } |
+            ExpressionKind::ErrorSuppress { .. } | ExpressionKind::Print { .. } | ExpressionKind::Exit { .. } |
+            ExpressionKind::Include { .. } | ExpressionKind::IncludeOnce { .. } | ExpressionKind::Require { .. } |
+            ExpressionKind::RequireOnce { .. } | ExpressionKind::Cast { .. } => self.unary(expression),
+            ExpressionKind::Infix { .. } | ExpressionKind::Ternary { .. } | ExpressionKind::Coalesce { .. } |
+            ExpressionKind::Assign { .. } | ExpressionKind::CoalesceAssign { .. } | ExpressionKind::AssignRef { .. } |
+            ExpressionKind::Instanceof { .. } => self.binary(expression),
+            ExpressionKind::PropertyFetch { .. } | ExpressionKind::NullsafePropertyFetch { .. } |
+            ExpressionKind::StaticPropertyFetch { .. } | ExpressionKind::ConstFetch { .. } |
+            ExpressionKind::DynamicConstFetch { .. } | ExpressionKind::ArrayIndex { .. } => self.access(expression),
+            ExpressionKind::Call { .. } | ExpressionKind::New { .. } | ExpressionKind::MethodCall { .. } |
+            ExpressionKind::NullsafeMethodCall { .. } | ExpressionKind::StaticMethodCall { .. } |
+            ExpressionKind::DynamicStaticMethodCall { .. } => self.call(expression),
+            ExpressionKind::Array { .. } | ExpressionKind::Closure { .. } | ExpressionKind::ArrowFunction { .. } |
+            ExpressionKind::AnonymousClass { .. } | ExpressionKind::Match { .. } => self.compound(expression),
+        }
+    }
+
+    /// Literals, names and variables.
+    fn scalar(&mut self, expression: &Expression) -> AstValue {
+        let span = expression.span;
+
+        let node = match &expression.kind {
+            ExpressionKind::Static => name("static", span),
+            ExpressionKind::Int { i } => return AstValue::Int(*i),
+            ExpressionKind::Float { f } => return AstValue::Float(f.0),
+            ExpressionKind::Variable { name } => AstNode::new("AST_VAR", 0, span, vec![("name", name.into())]),
+            ExpressionKind::Identifier { name } => AstNode::new("AST_CONST", 0, span, vec![("name", self::name(name, span).into())]),
+            ExpressionKind::ConstantString { value } => return value.into(),
+            ExpressionKind::InterpolatedString { parts } | ExpressionKind::Heredoc { parts, .. } => return self.string_parts(parts, span),
+            ExpressionKind::Nowdoc { value, .. } => return value.into(),
+            ExpressionKind::Bool { value } => AstNode::new("AST_CONST", 0, span, vec![("name", name(if *value { "true" } else { "false" }, span).into())]),
+            ExpressionKind::Null => AstNode::new("AST_CONST", 0, span, vec![("name", name("null", span).into())]),
+            ExpressionKind::Error => return AstValue::Null,
+            ExpressionKind::MagicConst { constant } => AstNode::new("AST_MAGIC_CONST", magic_const(constant), span, vec![]),
+            _ => unreachable!(),
+        };
+
+        node.into()
+    }
+
+    /// Expressions with a single operand.
+    fn unary(&mut self, expression: &Expression) -> AstValue {
+        let span = expression.span;
+
+        let node = match &expression.kind {
+            ExpressionKind::PreIncrement { value } => AstNode::new("AST_PRE_INC", 0, span, vec![("var", self.expression(value))]),
+            ExpressionKind::PostIncrement { value } => AstNode::new("AST_POST_INC", 0, span, vec![("var", self.expression(value))]),
+            ExpressionKind::PreDecrement { value } => AstNode::new("AST_PRE_DEC", 0, span, vec![("var", self.expression(value))]),
+            ExpressionKind::PostDecrement { value } => AstNode::new("AST_POST_DEC", 0, span, vec![("var", self.expression(value))]),
+            ExpressionKind::Dynamic { value } => AstNode::new("AST_VAR", 0, span, vec![("name", self.expression(value))]),
+            ExpressionKind::BooleanNot { value } => return self.unary_op(UNARY_BOOL_NOT, value, span),
+            ExpressionKind::Clone { target } => AstNode::new("AST_CLONE", 0, span, vec![("expr", self.expression(target))]),
+            ExpressionKind::Throw { value } => AstNode::new("AST_THROW", 0, span, vec![("expr", self.expression(value))]),
+            ExpressionKind::Yield { value } => AstNode::new("AST_YIELD", 0, span, vec![("value", self.expression(value)), ("key", AstValue::Null)]),
+            ExpressionKind::Negate { value } => return self.unary_op(UNARY_MINUS, value, span),
+            ExpressionKind::UnaryPlus { value } => return self.unary_op(UNARY_PLUS, value, span),
+            ExpressionKind::BitwiseNot { value } => return self.unary_op(UNARY_BITWISE_NOT, value, span),
+            ExpressionKind::ErrorSuppress { value } => return self.unary_op(UNARY_SILENCE, value, span),
+            ExpressionKind::Print { value } => AstNode::new("AST_PRINT", 0, span, vec![("expr", self.expression(value))]),
+            ExpressionKind::Exit { value } => AstNode::new("AST_EXIT", 0, span, vec![("expr", self.optional_expression(value.as_deref()))]),
+            ExpressionKind::Include { path } => AstNode::new("AST_INCLUDE_OR_EVAL", EXEC_INCLUDE, span, vec![("expr", self.expression(path))]),
+            ExpressionKind::IncludeOnce { path } => AstNode::new("AST_INCLUDE_OR_EVAL", EXEC_INCLUDE_ONCE, span, vec![("expr", self.expression(path))]),
+            ExpressionKind::Require { path } => AstNode::new("AST_INCLUDE_OR_EVAL", EXEC_REQUIRE, span, vec![("expr", self.expression(path))]),
+            ExpressionKind::RequireOnce { path } => AstNode::new("AST_INCLUDE_OR_EVAL", EXEC_REQUIRE_ONCE, span, vec![("expr", self.expression(path))]),
+            ExpressionKind::Cast { kind, value } => {
+                let flags = match kind {
+                    CastKind::String => TYPE_STRING,
+                    CastKind::Object => TYPE_OBJECT,
+                    CastKind::Bool => TYPE_BOOL,
+                    CastKind::Int => TYPE_LONG,
+                    CastKind::Double => TYPE_DOUBLE,
+                    CastKind::Array => TYPE_ARRAY,
+                };
+
+                AstNode::new("AST_CAST", flags, span, vec![("expr", self.expression(value))])
+            },
+            _ => unreachable!(),
+        };
+
+        node.into()
+    }
+
+    /// Operators with two or three operands, including assignments.
+    fn binary(&mut self, expression: &Expression) -> AstValue {
+        let span = expression.span;
+
+        let node = match &expression.kind {
+            ExpressionKind::Infix { lhs, op, rhs } => {
+                let (flags, assign) = infix_op(op);
+
+                if assign {
+                    AstNode::new("AST_ASSIGN_OP", flags, span, vec![("var", self.expression(lhs)), ("expr", self.expression(rhs))])
+                } else {
+                    AstNode::new("AST_BINARY_OP", flags, span, vec![("left", self.expression(lhs)), ("right", self.expression(rhs))])
+                }
+            },
+            ExpressionKind::Ternary { condition, then, r#else } => AstNode::new("AST_CONDITIONAL", 0, span, vec![
+                ("cond", self.expression(condition)),
+                ("true", self.optional_expression(then.as_deref())),
+                ("false", self.expression(r#else)),
+            ]),
+            ExpressionKind::Coalesce { lhs, rhs } => AstNode::new("AST_BINARY_OP", BINARY_COALESCE, span, vec![
+                ("left", self.expression(lhs)),
+                ("right", self.expression(rhs)),
+            ]),
+            ExpressionKind::Assign { var, value } => AstNode::new("AST_ASSIGN", 0, span, vec![("var", self.expression(var)), ("expr", self.expression(value))]),
+            ExpressionKind::CoalesceAssign { var, value } => AstNode::new("AST_ASSIGN_OP", BINARY_COALESCE, span, vec![
+                ("var", self.expression(var)),
+                ("expr", self.expression(value)),
+            ]),
+            ExpressionKind::AssignRef { var, value } => AstNode::new("AST_ASSIGN_REF", 0, span, vec![("var", self.expression(var)), ("expr", self.expression(value))]),
+            ExpressionKind::Instanceof { lhs, rhs } => AstNode::new("AST_INSTANCEOF", 0, span, vec![
+                ("expr", self.expression(lhs)),
+                ("class", self.class_reference(rhs)),
+            ]),
+            _ => unreachable!(),
+        };
+
+        node.into()
+    }
+
+    /// Property, constant and array accesses.
+    fn access(&mut self, expression: &Expression) -> AstValue {
+        let span = expression.span;
+
+        let node = match &expression.kind {
+            ExpressionKind::PropertyFetch { target, property } => AstNode::new("AST_PROP", 0, span, vec![
+                ("expr", self.expression(target)),
+                ("prop", self.member(property)),
+            ]),
+            ExpressionKind::NullsafePropertyFetch { target, property } => AstNode::new("AST_NULLSAFE_PROP", 0, span, vec![
+                ("expr", self.expression(target)),
+                ("prop", self.member(property)),
+            ]),
+            ExpressionKind::StaticPropertyFetch { target, property } => {
+                let prop = match &property.kind {
+                    ExpressionKind::Variable { name } => name.into(),
+                    _ => self.member(property),
+                };
+
+                AstNode::new("AST_STATIC_PROP", 0, span, vec![("class", self.class_reference(target)), ("prop", prop)])
+            },
+            ExpressionKind::ConstFetch { target, constant } => AstNode::new("AST_CLASS_CONST", 0, span, vec![
+                ("class", self.class_reference(target)),
+                ("const", (&constant.name).into()),
+            ]),
+            ExpressionKind::DynamicConstFetch { target, constant } => AstNode::new("AST_CLASS_CONST", 0, span, vec![
+                ("class", self.class_reference(target)),
+                ("const", self.expression(constant)),
+            ]),
+            ExpressionKind::ArrayIndex { array, index } => AstNode::new("AST_DIM", 0, span, vec![
+                ("expr", self.expression(array)),
+                ("dim", self.optional_expression(index.as_deref())),
+            ]),
+            _ => unreachable!(),
+        };
+
+        node.into()
+    }
+
+    /// Function and method calls, and `new`.
+    fn call(&mut self, expression: &Expression) -> AstValue {
+        let span = expression.span;
+
+        let node = match &expression.kind {
+            ExpressionKind::Call { target, args } => AstNode::new("AST_CALL", 0, span, vec![
+                ("expr", self.class_reference(target)),
+                ("args", self.args(args, span).into()),
+            ]),
+            ExpressionKind::New { target, args } => {
+                let class = match &target.kind {
+                    ExpressionKind::AnonymousClass { extends, implements, body, attributes } => {
+                        let extends = optional(extends, |extends| self::name(&extends.name, extends.span));
+
+                        self.class(CLASS_ANONYMOUS, None, &None, extends, name_list(implements, span), body, attributes, AstValue::Null, target.span).into()
+                    },
+                    _ => self.class_reference(target),
+                };
+
+                AstNode::new("AST_NEW", 0, span, vec![("class", class), ("args", self.args(args, span).into())])
+            },
+            ExpressionKind::MethodCall { target, method, args } => AstNode::new("AST_METHOD_CALL", 0, span, vec![
+                ("expr", self.expression(target)),
+                ("method", self.member(method)),
+                ("args", self.args(args, span).into()),
+            ]),
+            ExpressionKind::NullsafeMethodCall { target, method, args } => AstNode::new("AST_NULLSAFE_METHOD_CALL", 0, span, vec![
+                ("expr", self.expression(target)),
+                ("method", self.member(method)),
+                ("args", self.args(args, span).into()),
+            ]),
+            ExpressionKind::StaticMethodCall { target, method, args } => AstNode::new("AST_STATIC_CALL", 0, span, vec![
+                ("class", self.class_reference(target)),
+                ("method", (&method.name).into()),
+                ("args", self.args(args, span).into()),
+            ]),
+            ExpressionKind::DynamicStaticMethodCall { target, method, args } => AstNode::new("AST_STATIC_CALL", 0, span, vec![
+                ("class", self.class_reference(target)),
+                ("method", self.expression(method)),
+                ("args", self.args(args, span).into()),
+            ]),
+            _ => unreachable!(),
+        };
+
+        node.into()
+    }
+
+    /// Arrays, closures, anonymous classes and `match`, which contain other statements or expressions.
+    fn compound(&mut self, expression: &Expression) -> AstValue {
+        let span = expression.span;
+
+        let node = match &expression.kind {
+            ExpressionKind::Array { items } => {
+                let mut node = AstNode::list("AST_ARRAY", span, items.iter().map(|item| {
+                    let value = self.expression(&item.value);
+
+                    if item.unpack {
+                        return AstNode::new("AST_UNPACK", 0, item.value.span, vec![("expr", value)]).into();
+                    }
+
+                    AstNode::new("AST_ARRAY_ELEM", if item.by_ref { ARRAY_ELEM_REF } else { 0 }, item.value.span, vec![
+                        ("value", value),
+                        ("key", self.optional_expression(item.key.as_ref())),
+                    ]).into()
+                }).collect::<Vec<_>>());
+                node.flags = ARRAY_SYNTAX_SHORT;
+
+                node
+            },
+            ExpressionKind::Closure { params, uses, return_type, returns_by_ref, body, attributes } => {
+                let flags = if *returns_by_ref { FUNC_RETURNS_REF } else { 0 };
+                let stmts = self.statements(body, span).into();
+                let mut closure = self.function("AST_CLOSURE", flags, "{closure}", &None, params, stmts, return_type, attributes, span);
+
+                if ! uses.is_empty() {
+                    let uses = AstNode::list("AST_CLOSURE_USES", span, uses.iter().map(|r#use| {
+                        let name = match &r#use.var.kind {
+                            ExpressionKind::Variable { name } => name.into(),
+                            _ => AstValue::Null,
+                        };
+
+                        AstNode::new("AST_CLOSURE_VAR", if r#use.by_ref { CLOSURE_USE_REF } else { 0 }, r#use.var.span, vec![("name", name)]).into()
+                    }).collect::<Vec<_>>());
+
+                    closure.children.iter_mut().find(|(name, _)| name == "uses").unwrap().1 = uses.into();
+                }
+
+                closure
+            },
+            // Arrow functions return their expression from an implicit statement list.
+            ExpressionKind::ArrowFunction { params, return_type, returns_by_ref, expr, attributes } => {
+                let flags = if *returns_by_ref { FUNC_RETURNS_REF } else { 0 };
+                let stmts = AstNode::new("AST_RETURN", 0, expr.span, vec![("expr", self.expression(expr))]).into();
+
+                self.function("AST_ARROW_FUNC", flags, "{closure}", &None, params, stmts, return_type, attributes, span)
+            },
+            ExpressionKind::AnonymousClass { extends, implements, body, attributes } => {
+                let extends = optional(extends, |extends| self::name(&extends.name, extends.span));
+
+                self.class(CLASS_ANONYMOUS, None, &None, extends, name_list(implements, span), body, attributes, AstValue::Null, span)
+            },
+            ExpressionKind::Match { condition, arms } => {
+                let arms = AstNode::list("AST_MATCH_ARM_LIST", span, arms.iter().map(|arm| {
+                    let conditions = optional(&arm.conditions, |conditions| {
+                        AstNode::list("AST_EXPR_LIST", arm.body.span, conditions.iter().map(|c| self.expression(c)).collect::<Vec<_>>())
+                    });
+
+                    AstNode::new("AST_MATCH_ARM", 0, arm.body.span, vec![("cond", conditions), ("expr", self.expression(&arm.body))]).into()
+                }).collect::<Vec<_>>());
+
+                AstNode::new("AST_MATCH", 0, span, vec![("cond", self.expression(condition)), ("stmts", arms.into())])
+            },
+            _ => unreachable!(),
+        };
+
+        node.into()
+    }
+}
+
+/// The `BINARY_*` flag for an infix operator, and whether it's a compound assignment.
+fn infix_op(op: &InfixOp) -> (u32, bool) {
+    match op {
+        InfixOp::Add => (1, false),
+        InfixOp::Sub => (2, false),
+        InfixOp::Mul => (3, false),
+        InfixOp::Div => (4, false),
+        InfixOp::Mod => (5, false),
+        InfixOp::LeftShift => (6, false),
+        InfixOp::RightShift => (7, false),
+        InfixOp::Concat => (8, false),
+        InfixOp::BitwiseOr => (9, false),
+        InfixOp::BitwiseAnd => (10, false),
+        InfixOp::BitwiseXor => (11, false),
+        InfixOp::Pow => (12, false),
+        InfixOp::LogicalXor => (15, false),
+        InfixOp::Identical => (16, false),
+        InfixOp::NotIdentical => (17, false),
+        InfixOp::Equals => (18, false),
+        InfixOp::NotEquals => (19, false),
+        InfixOp::LessThan => (20, false),
+        InfixOp::LessThanEquals => (21, false),
+        InfixOp::Spaceship => (170, false),
+        InfixOp::GreaterThan => (256, false),
+        InfixOp::GreaterThanEquals => (257, false),
+        InfixOp::Or | InfixOp::LogicalOr => (258, false),
+        InfixOp::And | InfixOp::LogicalAnd => (259, false),
+        InfixOp::AddAssign => (1, true),
+        InfixOp::SubAssign => (2, true),
+        InfixOp::MulAssign => (3, true),
+        InfixOp::DivAssign => (4, true),
+        InfixOp::ConcatAssign => (8, true),
+        InfixOp::ModAssign => (5, true),
+        InfixOp::LeftShiftAssign => (6, true),
+        InfixOp::RightShiftAssign => (7, true),
+        InfixOp::BitwiseOrAssign => (9, true),
+        InfixOp::BitwiseAndAssign => (10, true),
+        InfixOp::BitwiseXorAssign => (11, true),
+        InfixOp::PowAssign => (12, true),
+    }
+}
+
+fn magic_const(constant: &MagicConst) -> u32 {
+    match constant {
+        MagicConst::Line => MAGIC_LINE,
+        MagicConst::File => MAGIC_FILE,
+        MagicConst::Dir => MAGIC_DIR,
+        MagicConst::Class => MAGIC_CLASS,
+        MagicConst::Trait => MAGIC_TRAIT,
+        MagicConst::Method => MAGIC_METHOD,
+        MagicConst::Function => MAGIC_FUNCTION,
+        MagicConst::Namespace => MAGIC_NAMESPACE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_ext_ast;
+
+    fn dump(source: &str) -> String {
+        to_ext_ast(&crate::parse(source).unwrap()).to_string()
+    }
+
+    #[test]
+    fn statements() {
+        assert_eq!(dump("<?php $var = 42; echo $var, 'a' . \"b$c\";"), "\
+AST_STMT_LIST
+    0: AST_ASSIGN
+        var: AST_VAR
+            name: \"var\"
+        expr: 42
+    1: AST_ECHO
+        expr: AST_VAR
+            name: \"var\"
+    2: AST_ECHO
+        expr: AST_BINARY_OP
+            flags: BINARY_CONCAT (8)
+            left: \"a\"
+            right: AST_ENCAPS_LIST
+                0: \"b\"
+                1: AST_VAR
+                    name: \"c\"");
+    }
+
+    #[test]
+    fn declarations() {
+        assert_eq!(dump("<?php abstract class A extends \\B { public static function &c(int ...$d): ?string {} abstract function e(); }"), "\
+AST_STMT_LIST
+    0: AST_CLASS
+        flags: CLASS_ABSTRACT (64)
+        name: \"A\"
+        docComment: null
+        extends: AST_NAME
+            flags: NAME_FQ (0)
+            name: \"B\"
+        implements: null
+        stmts: AST_STMT_LIST
+            0: AST_METHOD
+                flags: MODIFIER_PUBLIC | MODIFIER_STATIC | FUNC_RETURNS_REF (4113)
+                name: \"c\"
+                docComment: null
+                params: AST_PARAM_LIST
+                    0: AST_PARAM
+                        flags: PARAM_VARIADIC (16)
+                        type: AST_TYPE
+                            flags: TYPE_LONG (4)
+                        name: \"d\"
+                        default: null
+                        attributes: null
+                        docComment: null
+                stmts: AST_STMT_LIST
+                returnType: AST_NULLABLE_TYPE
+                    type: AST_TYPE
+                        flags: TYPE_STRING (6)
+                attributes: null
+                __declId: 0
+            1: AST_METHOD
+                flags: MODIFIER_ABSTRACT (64)
+                name: \"e\"
+                docComment: null
+                params: AST_PARAM_LIST
+                stmts: null
+                returnType: null
+                attributes: null
+                __declId: 1
+        attributes: null
+        type: null
+        __declId: 2");
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(dump("<?php new abcdefghiéx; namespace\\Café();"), "\
+AST_STMT_LIST
+    0: AST_NEW
+        class: AST_NAME
+            flags: NAME_NOT_FQ (1)
+            name: \"abcdefghiéx\"
+        args: AST_ARG_LIST
+    1: AST_CALL
+        expr: AST_NAME
+            flags: NAME_RELATIVE (2)
+            name: \"Café\"
+        args: AST_ARG_LIST");
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let nested = [("(", "1", ");"), ("[", "1", "];"), ("!", "1;", ""), ("new A(", "", ");"), ("1 + ", "1;", ""), ("if ($a) { ", "", "}"), ("class A { function f() { ", "", "} }")];
+
+        // The deepest nesting that the parser accepts has to fit in a test thread's stack.
+        for (open, inner, close) in nested {
+            let program = (1..=128).rev()
+                .find_map(|depth| crate::parse(&format!("<?php {}{}{}", open.repeat(depth), inner, close.repeat(depth))).ok())
+                .unwrap();
+
+            to_ext_ast(&program);
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::{Diagnostic, Error, Parser, Program};
+
+/// The stack given to each parsing thread. Spawned threads only get 2MB by default, so this
+/// matches a main thread instead, which the default `ParserConfig::max_depth` fits in with room to spare.
+const STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// A file that was read and parsed by `Parser::parse_file`.
+#[derive(Debug, Clone)]
+pub struct ParsedFile {
+    pub path: PathBuf,
+    pub program: Program,
+    /// The warnings about the file, each attributed to its path.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Read, lex and parse many files at once, spreading the work across one thread per available
+/// core. The results are returned in the same order as the paths that were given, and any errors
+/// are `Error::File`s.
+pub fn parse_files<I, P>(paths: I) -> Vec<(PathBuf, Result<ParsedFile, Error>)>
+where
+    I: IntoIterator<Item = P>,
+    P: Into<PathBuf>,
+{
+    let paths: Vec<PathBuf> = paths.into_iter().map(Into::into).collect();
+    let results: Vec<Mutex<Option<Result<ParsedFile, Error>>>> = paths.iter().map(|_| Mutex::new(None)).collect();
+    let next = AtomicUsize::new(0);
+
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()).min(paths.len());
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let worker = || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(index) else { break };
+
+                let result = Parser::new(None).parse_file(path);
+
+                *results[index].lock().unwrap_or_else(|err| err.into_inner()) = Some(result);
+            };
+
+            thread::Builder::new().stack_size(STACK_SIZE).spawn_scoped(scope, worker).expect("threads can be spawned");
+        }
+    });
+
+    paths.into_iter()
+        .zip(results)
+        .map(|(path, result)| {
+            let result = result.into_inner().unwrap_or_else(|err| err.into_inner());
+
+            // Every index below `paths.len()` is claimed by exactly one thread before they're joined.
+            (path, result.expect("every file is parsed"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::parse_files;
+    use crate::{Diagnostic, Error, Parser, StatementKind};
+
+    #[test]
+    fn parses_in_order() {
+        let dir = std::env::temp_dir().join(format!("trunk_parse_files_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut paths = Vec::new();
+        for i in 0..20 {
+            let path = dir.join(format!("{}.php", i));
+            fs::write(&path, format!("<?php function f{}() {{}}", i)).unwrap();
+            paths.push(path);
+        }
+        fs::write(dir.join("broken.php"), "<?php if (;").unwrap();
+        paths.push(dir.join("broken.php"));
+        paths.push(dir.join("missing.php"));
+
+        let results = parse_files(&paths);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(results.len(), 22);
+
+        for (i, (path, result)) in results.iter().take(20).enumerate() {
+            assert_eq!(path, &paths[i]);
+
+            match &result.as_ref().unwrap().program[0].kind {
+                StatementKind::Function { name, .. } => assert_eq!(name.name, format!("f{}", i)),
+                _ => panic!("expected a function"),
+            }
+        }
+
+        match (&results[20].1, &results[21].1) {
+            (Err(Error::File { path: broken, error: parse }), Err(Error::File { path: missing, error: io })) => {
+                assert_eq!(broken, &paths[20]);
+                assert!(matches!(**parse, Error::Parser(_)));
+                assert_eq!(missing, &paths[21]);
+                assert!(matches!(**io, Error::Io(_)));
+            },
+            results => panic!("unexpected results {:?}", results),
+        }
+
+        let error = results[20].1.as_ref().unwrap_err();
+        assert!(error.to_string().starts_with(&format!("{}:1:11: Parse error:", paths[20].display())));
+        assert_eq!(error.path(), Some(paths[20].as_path()));
+        assert_eq!(Diagnostic::from(error).path.as_deref(), Some(paths[20].as_path()));
+    }
+
+    #[test]
+    fn warnings_have_paths() {
+        let source = "<?php $a = (real) 1;";
+        let path = std::env::temp_dir().join(format!("trunk_parse_file_{}.php", std::process::id()));
+        fs::write(&path, source).unwrap();
+
+        let file = Parser::new(None).parse_file(&path);
+        fs::remove_file(&path).unwrap();
+        let file = file.unwrap();
+
+        assert_eq!(file.path, path);
+
+        match file.diagnostics.as_slice() {
+            [warning] => {
+                assert_eq!(warning.code, "removed");
+                assert!(warning.render(source, None).contains(&format!("--> {}:1:12", path.display())));
+            },
+            diagnostics => panic!("unexpected diagnostics {:?}", diagnostics),
+        }
+    }
+}
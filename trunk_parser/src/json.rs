@@ -0,0 +1,95 @@
+use serde_json::{json, Value};
+use trunk_lexer::Lexer;
+
+use crate::{Diagnostic, Parser};
+
+/// Lex a source file, giving a JSON object with the `tokens` and any `diagnostics`. The tokens are
+/// `null` if the source couldn't be lexed.
+///
+/// This is meant for hosts that can only exchange strings with Rust, such as the WebAssembly
+/// bindings, so it never fails.
+pub fn tokenize_json(source: &str) -> String {
+    let mut lexer = Lexer::new(None);
+    let tokens = lexer.tokenize(source);
+    let mut diagnostics: Vec<Diagnostic> = lexer.warnings().iter().map(Diagnostic::from).collect();
+
+    let tokens = match tokens {
+        Ok(tokens) => serde_json::to_value(tokens).expect("tokens are serializable"),
+        Err(error) => {
+            diagnostics.push(Diagnostic::from(&error));
+            Value::Null
+        },
+    };
+
+    json!({ "tokens": tokens, "diagnostics": diagnostics_json(&diagnostics, source) }).to_string()
+}
+
+/// Parse a source file tolerantly, giving a JSON object with the `ast` and any `diagnostics`. Parse
+/// errors leave error nodes in the AST rather than discarding it, so a half-written file still has
+/// a tree. The AST is `null` only if the source couldn't be lexed.
+pub fn parse_json(source: &str) -> String {
+    let mut lexer = Lexer::new(None);
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+    let ast = match lexer.tokenize(source) {
+        Ok(tokens) => {
+            let mut parser = Parser::new(None);
+            let (program, errors) = parser.parse_tolerant(&tokens);
+
+            diagnostics.extend(lexer.warnings().iter().map(Diagnostic::from));
+            diagnostics.extend(errors.iter().map(Diagnostic::from));
+            diagnostics.extend(parser.warnings().iter().map(Diagnostic::from));
+
+            serde_json::to_value(program).expect("the AST is serializable")
+        },
+        Err(error) => {
+            diagnostics.push(Diagnostic::from(&error));
+            Value::Null
+        },
+    };
+
+    json!({ "ast": ast, "diagnostics": diagnostics_json(&diagnostics, source) }).to_string()
+}
+
+fn diagnostics_json(diagnostics: &[Diagnostic], source: &str) -> Vec<Value> {
+    diagnostics.iter().map(|diagnostic| diagnostic.to_json_value(source, None)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::{parse_json, tokenize_json};
+
+    fn decode(json: String) -> Value {
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn tokens() {
+        let json = decode(tokenize_json("<?php echo 1;"));
+
+        assert_eq!(json["tokens"].as_array().unwrap().len(), 4);
+        assert_eq!(json["tokens"][1]["kind"], "Echo");
+        assert_eq!(json["tokens"][1]["span"]["start"], 6);
+        assert_eq!(json["diagnostics"], Value::Array(vec![]));
+
+        let json = decode(tokenize_json("<?php $a ` $b"));
+
+        assert_eq!(json["tokens"], Value::Null);
+        assert_eq!(json["diagnostics"][0]["severity"], "error");
+    }
+
+    #[test]
+    fn ast() {
+        let json = decode(parse_json("<?php echo 1;"));
+
+        assert_eq!(json["ast"][0]["kind"]["Echo"]["values"][0]["kind"]["Int"]["i"], 1);
+        assert_eq!(json["diagnostics"], Value::Array(vec![]));
+
+        let json = decode(parse_json("<?php $a = ; echo 2;"));
+
+        assert_eq!(json["ast"].as_array().unwrap().len(), 2);
+        assert_eq!(json["diagnostics"][0]["span"]["line"], 1);
+    }
+}
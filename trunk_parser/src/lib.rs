@@ -1,7 +1,71 @@
 mod ast;
+#[cfg(feature = "cache")]
+mod cache;
+mod diagnostics;
+mod diff;
+#[cfg(feature = "serde")]
+mod dot;
+#[cfg(feature = "serde")]
+mod dump;
+mod error;
+mod ext_ast;
+mod files;
+#[cfg(feature = "serde")]
+mod json;
+#[cfg(feature = "serde")]
+mod nikic;
 mod parser;
+#[cfg(feature = "serde")]
+mod query;
+#[cfg(feature = "serde")]
+mod schema;
 mod traverser;
 
-pub use ast::{Statement, Expression, Program, Block, Param, Identifier, Type, InfixOp, MatchArm, Catch, Case};
-pub use parser::{Parser, ParseError};
-pub use traverser::*;
\ No newline at end of file
+pub use ast::{
+    Statement, StatementKind, Expression, ExpressionKind, Program, Block, Param, ParamList, Identifier, Type, InfixOp, MatchArm, Catch, Case, Attribute,
+    AttributeGroup, StringPart, Arg, ArrayItem, ClosureUse, ElseIf, StaticVar, Constant, PropertyHook, PropertyHookBody, Use, UseKind, CastKind, MagicConst, BackedEnumType,
+    ClassFlag, MethodFlag, PropertyFlag, ConstFlag, halt_offset, relative_name,
+};
+#[cfg(feature = "serde")]
+pub use ast::Metadata;
+#[cfg(feature = "cache")]
+pub use cache::{content_hash, from_cache, to_cache, CacheError, CACHE_VERSION};
+pub use diagnostics::{Diagnostic, Severity};
+pub use diff::{diff, Edit};
+#[cfg(feature = "serde")]
+pub use dot::to_dot;
+#[cfg(feature = "serde")]
+pub use dump::{dump, DumpOptions};
+pub use error::Error;
+pub use files::{parse_files, ParsedFile};
+pub use ext_ast::{to_ext_ast, AstNode, AstValue, EXT_AST_VERSION};
+#[cfg(feature = "serde")]
+pub use json::{parse_json, tokenize_json};
+#[cfg(feature = "serde")]
+pub use nikic::to_nikic_json;
+pub use trunk_lexer::{Location, Span};
+#[cfg(feature = "serde")]
+pub use query::{Query, QueryError, QueryMatch};
+pub use parser::{Parser, ParserConfig, TrailingCommas, PhpVersion, ParseError, ParseWarning, Suggestion, Statements, Precedence, Associativity};
+#[cfg(feature = "serde")]
+pub use schema::ast_schema;
+pub use traverser::*;
+
+/// Lex and parse a PHP source file with the default configuration.
+pub fn parse(source: &str) -> Result<Program, Error> {
+    let tokens = trunk_lexer::Lexer::new(None).tokenize(source)?;
+
+    Ok(Parser::new(None).parse(&tokens)?)
+}
+
+/// An entry point for fuzzers, which lexes and parses arbitrary bytes with `trunk_lexer::fuzz_lex`.
+/// The tokens are parsed tolerantly as well, to exercise error recovery, but only the result of
+/// the regular parse is returned. Any input gives either a program or an error, and never a panic,
+/// a stack overflow or a hang, since nesting is limited by `ParserConfig::max_depth`.
+pub fn fuzz_parse(bytes: &[u8]) -> Result<Program, Error> {
+    let tokens = trunk_lexer::fuzz_lex(bytes)?;
+
+    Parser::new(None).parse_tolerant(&tokens);
+
+    Ok(Parser::new(None).parse(&tokens)?)
+}
@@ -0,0 +1,1053 @@
+use serde_json::{json, Map, Value};
+use trunk_lexer::Span;
+
+use crate::{
+    Statement, StatementKind, Expression, ExpressionKind, Program, Block, Param, Identifier, Type, InfixOp, AttributeGroup, StringPart, Arg,
+    UseKind, CastKind, MagicConst, BackedEnumType, ClassFlag, MethodFlag, PropertyFlag, ConstFlag, PropertyHook, PropertyHookBody, relative_name,
+};
+
+// Modifier bits, as defined by `PhpParser\Modifiers`.
+const MODIFIER_PUBLIC: u32 = 1;
+const MODIFIER_PROTECTED: u32 = 2;
+const MODIFIER_PRIVATE: u32 = 4;
+const MODIFIER_STATIC: u32 = 8;
+const MODIFIER_ABSTRACT: u32 = 16;
+const MODIFIER_FINAL: u32 = 32;
+const MODIFIER_READONLY: u32 = 64;
+const MODIFIER_PUBLIC_SET: u32 = 128;
+const MODIFIER_PROTECTED_SET: u32 = 256;
+const MODIFIER_PRIVATE_SET: u32 = 512;
+
+/// Serialize a program using the node names and field layout of nikic/php-parser's JSON dump
+/// (version 5), so that the output can be compared against fixtures produced by PHP tooling.
+///
+/// Node positions are reported through the `startLine`, `startFilePos` and `endFilePos`
+/// attributes. Comments are attached to the following statement, the same way php-parser does.
+pub fn to_nikic_json(program: &Program) -> Value {
+    NikicJson::default().statements(program)
+}
+
+#[derive(Default)]
+struct NikicJson {
+    in_interface: bool,
+}
+
+fn node(node_type: &str, span: Span, fields: Value) -> Value {
+    let mut object = Map::new();
+    object.insert("nodeType".into(), node_type.into());
+    object.insert("attributes".into(), attributes(span));
+
+    if let Value::Object(fields) = fields {
+        object.extend(fields);
+    }
+
+    Value::Object(object)
+}
+
+fn attributes(span: Span) -> Value {
+    if span == Span::default() {
+        return json!({});
+    }
+
+    json!({
+        "startLine": span.line,
+        "startFilePos": span.start,
+        "endFilePos": span.end.saturating_sub(1),
+    })
+}
+
+fn identifier(identifier: &Identifier) -> Value {
+    node("Identifier", identifier.span, json!({ "name": identifier.name }))
+}
+
+fn name(name: &str, span: Span) -> Value {
+    if let Some(name) = name.strip_prefix('\\') {
+        node("Name_FullyQualified", span, json!({ "name": name }))
+    } else if let Some(name) = relative_name(name) {
+        node("Name_Relative", span, json!({ "name": name }))
+    } else {
+        node("Name", span, json!({ "name": name }))
+    }
+}
+
+fn names(identifiers: &[Identifier]) -> Value {
+    identifiers.iter().map(|i| name(&i.name, i.span)).collect()
+}
+
+fn optional<T>(value: &Option<T>, f: impl FnOnce(&T) -> Value) -> Value {
+    value.as_ref().map(f).unwrap_or(Value::Null)
+}
+
+fn flags<T>(flags: &[T], bit: impl Fn(&T) -> u32) -> u32 {
+    flags.iter().fold(0, |acc, flag| acc | bit(flag))
+}
+
+fn property_flag(flag: &PropertyFlag) -> u32 {
+    match flag {
+        PropertyFlag::Public => MODIFIER_PUBLIC,
+        PropertyFlag::Protected => MODIFIER_PROTECTED,
+        PropertyFlag::Private => MODIFIER_PRIVATE,
+        PropertyFlag::PublicSet => MODIFIER_PUBLIC_SET,
+        PropertyFlag::ProtectedSet => MODIFIER_PROTECTED_SET,
+        PropertyFlag::PrivateSet => MODIFIER_PRIVATE_SET,
+        PropertyFlag::Static => MODIFIER_STATIC,
+        PropertyFlag::Readonly => MODIFIER_READONLY,
+    }
+}
+
+fn method_flag(flag: &MethodFlag) -> u32 {
+    match flag {
+        MethodFlag::Final => MODIFIER_FINAL,
+        MethodFlag::Abstract => MODIFIER_ABSTRACT,
+        MethodFlag::Public => MODIFIER_PUBLIC,
+        MethodFlag::Protected => MODIFIER_PROTECTED,
+        MethodFlag::Private => MODIFIER_PRIVATE,
+        MethodFlag::Static => MODIFIER_STATIC,
+    }
+}
+
+fn const_flag(flag: &ConstFlag) -> u32 {
+    match flag {
+        ConstFlag::Final => MODIFIER_FINAL,
+        ConstFlag::Public => MODIFIER_PUBLIC,
+        ConstFlag::Protected => MODIFIER_PROTECTED,
+        ConstFlag::Private => MODIFIER_PRIVATE,
+    }
+}
+
+fn r#type(r#type: &Type) -> Value {
+    match r#type {
+        Type::Plain(plain) => type_name(plain),
+        Type::Nullable(nullable) => node("NullableType", Span::default(), json!({ "type": type_name(nullable) })),
+        Type::Union(types) => node("UnionType", Span::default(), json!({ "types": types.iter().map(self::r#type).collect::<Value>() })),
+        Type::Intersection(types) => node("IntersectionType", Span::default(), json!({ "types": types.iter().map(self::r#type).collect::<Value>() })),
+    }
+}
+
+/// Built-in types are represented as identifiers, while class types are names.
+fn type_name(r#type: &str) -> Value {
+    const BUILTIN: &[&str] = &[
+        "array", "callable", "bool", "int", "float", "string", "iterable", "void", "object", "null", "false", "true", "mixed", "never", "static",
+    ];
+
+    if BUILTIN.contains(&r#type.to_ascii_lowercase().as_str()) {
+        node("Identifier", Span::default(), json!({ "name": r#type }))
+    } else {
+        name(r#type, Span::default())
+    }
+}
+
+fn comment(text: &str, span: Span) -> Value {
+    let node_type = if text.starts_with("/**") { "Comment_Doc" } else { "Comment" };
+
+    json!({
+        "nodeType": node_type,
+        "text": text,
+        "line": span.line,
+        "filePos": span.start,
+    })
+}
+
+impl NikicJson {
+    fn statements(&mut self, statements: &[Statement]) -> Value {
+        let mut output = Vec::new();
+        let mut comments = Vec::new();
+        let mut iter = statements.iter().peekable();
+
+        while let Some(statement) = iter.next() {
+            if let StatementKind::Comment { comment } = &statement.kind {
+                comments.push(self::comment(comment, statement.span));
+                continue;
+            }
+
+            let mut value = match &statement.kind {
+                // An unbraced namespace owns every statement up to the next namespace declaration.
+                StatementKind::Namespace { name, body } if body.is_empty() => {
+                    let mut body = Vec::new();
+
+                    while let Some(next) = iter.next_if(|s| ! matches!(s.kind, StatementKind::Namespace { .. })) {
+                        body.push(next.clone());
+                    }
+
+                    self.namespace(name, &body, statement.span)
+                },
+                _ => self.statement(statement),
+            };
+
+            if ! comments.is_empty() {
+                let attributes = value["attributes"].as_object_mut().unwrap();
+                let existing = attributes.remove("comments").and_then(|c| c.as_array().cloned()).unwrap_or_default();
+
+                attributes.insert("comments".into(), comments.drain(..).chain(existing).collect());
+            }
+
+            output.push(value);
+        }
+
+        // Trailing comments are kept on an empty statement.
+        if ! comments.is_empty() {
+            let mut nop = node("Stmt_Nop", Span::default(), json!({}));
+            nop["attributes"]["comments"] = comments.into();
+            output.push(nop);
+        }
+
+        Value::Array(output)
+    }
+
+    fn namespace(&mut self, name: &str, body: &Block, span: Span) -> Value {
+        node("Stmt_Namespace", span, json!({
+            "name": self::name(name, Span::default()),
+            "stmts": self.statements(body),
+        }))
+    }
+
+    fn with_doc_comment(&self, mut value: Value, doc_comment: &Option<String>) -> Value {
+        if let Some(doc_comment) = doc_comment {
+            value["attributes"]["comments"] = json!([{ "nodeType": "Comment_Doc", "text": doc_comment }]);
+        }
+
+        value
+    }
+
+    fn attribute_groups(&mut self, groups: &[AttributeGroup]) -> Value {
+        groups.iter().map(|group| node("AttributeGroup", Span::default(), json!({
+            "attrs": group.members.iter().map(|attribute| node("Attribute", attribute.name.span, json!({
+                "name": name(&attribute.name.name, attribute.name.span),
+                "args": self.args(&attribute.args),
+            }))).collect::<Value>(),
+        }))).collect()
+    }
+
+    fn params(&mut self, params: &[Param]) -> Value {
+        params.iter().map(|param| node("Param", param.span, json!({
+            "attrGroups": self.attribute_groups(&param.attributes),
+            "flags": flags(&param.flags, property_flag),
+            "type": optional(&param.r#type, r#type),
+            "byRef": param.by_ref,
+            "variadic": param.variadic,
+            "var": self.expression(&param.name),
+            "default": optional(&param.default, |default| self.expression(default)),
+            "hooks": [],
+        }))).collect()
+    }
+
+    fn args(&mut self, args: &[Arg]) -> Value {
+        args.iter().map(|arg| node("Arg", arg.value.span, json!({
+            "name": optional(&arg.name, |name| node("Identifier", Span::default(), json!({ "name": name }))),
+            "value": self.expression(&arg.value),
+            "byRef": false,
+            "unpack": arg.unpack,
+        }))).collect()
+    }
+
+    fn class(&mut self, name: Value, flags: u32, extends: &Option<Identifier>, implements: &[Identifier], body: &Block, attributes: &[AttributeGroup]) -> Value {
+        json!({
+            "attrGroups": self.attribute_groups(attributes),
+            "flags": flags,
+            "name": name,
+            "extends": optional(extends, |extends| self::name(&extends.name, extends.span)),
+            "implements": names(implements),
+            "stmts": self.statements(body),
+        })
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Value {
+        // Grouped the same way as expressions, to keep the stack frame for each level small.
+        match &statement.kind {
+            StatementKind::Namespace { .. } | StatementKind::Use { .. } | StatementKind::Const { .. } |
+            StatementKind::Function { .. } => self.declaration(statement),
+            StatementKind::Class { .. } | StatementKind::Trait { .. } | StatementKind::Interface { .. } | StatementKind::Enum { .. } => self.class_like(statement),
+            StatementKind::TraitUse { .. } | StatementKind::Method { .. } | StatementKind::Var { .. } |
+            StatementKind::Property { .. } | StatementKind::Constant { .. } | StatementKind::EnumCase { .. } => self.class_member(statement),
+            StatementKind::While { .. } | StatementKind::For { .. } | StatementKind::Foreach { .. } | StatementKind::If { .. } |
+            StatementKind::Switch { .. } | StatementKind::Try { .. } | StatementKind::Return { .. } | StatementKind::Break { .. } |
+            StatementKind::Continue { .. } => self.control_flow(statement),
+            StatementKind::InlineHtml(..) | StatementKind::Static { .. } | StatementKind::Global { .. } | StatementKind::Echo { .. } |
+            StatementKind::Expression { .. } | StatementKind::Comment { .. } | StatementKind::HaltCompiler { .. } |
+            StatementKind::Noop | StatementKind::Error => self.simple_statement(statement),
+        }
+    }
+
+    /// Namespaces, imports, constants and functions.
+    fn declaration(&mut self, statement: &Statement) -> Value {
+        let span = statement.span;
+
+        match &statement.kind {
+            StatementKind::Namespace { name, body } => self.namespace(name, body, span),
+            StatementKind::Use { uses, kind } => node("Stmt_Use", span, json!({
+                "type": match kind {
+                    UseKind::Normal => 1,
+                    UseKind::Function => 2,
+                    UseKind::Const => 3,
+                },
+                "uses": uses.iter().map(|r#use| node("UseItem", r#use.name.span, json!({
+                    "type": 0,
+                    "name": name(r#use.name.name.trim_start_matches('\\'), r#use.name.span),
+                    "alias": optional(&r#use.alias, identifier),
+                }))).collect::<Value>(),
+            })),
+            StatementKind::Const { constants, doc_comment } => {
+                let constants = node("Stmt_Const", span, json!({
+                    "consts": constants.iter().map(|constant| node("Const", constant.name.span, json!({
+                        "name": identifier(&constant.name),
+                        "value": self.expression(&constant.value),
+                    }))).collect::<Value>(),
+                }));
+
+                self.with_doc_comment(constants, doc_comment)
+            },
+            StatementKind::Function { name, params, body, return_type, returns_by_ref, attributes, doc_comment } => {
+                let function = node("Stmt_Function", span, json!({
+                    "attrGroups": self.attribute_groups(attributes),
+                    "byRef": returns_by_ref,
+                    "name": identifier(name),
+                    "params": self.params(params),
+                    "returnType": optional(return_type, r#type),
+                    "stmts": self.statements(body),
+                }));
+
+                self.with_doc_comment(function, doc_comment)
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Classes, traits, interfaces and enums.
+    fn class_like(&mut self, statement: &Statement) -> Value {
+        let span = statement.span;
+
+        match &statement.kind {
+            StatementKind::Class { name, extends, implements, body, flag, attributes, doc_comment } => {
+                let flags = match flag {
+                    Some(ClassFlag::Abstract) => MODIFIER_ABSTRACT,
+                    Some(ClassFlag::Final) => MODIFIER_FINAL,
+                    None => 0,
+                };
+                let class = self.class(identifier(name), flags, extends, implements, body, attributes);
+
+                self.with_doc_comment(node("Stmt_Class", span, class), doc_comment)
+            },
+            StatementKind::Trait { name, body, attributes } => node("Stmt_Trait", span, json!({
+                "attrGroups": self.attribute_groups(attributes),
+                "name": identifier(name),
+                "stmts": self.statements(body),
+            })),
+            StatementKind::Interface { name, extends, body, attributes } => {
+                let in_interface = std::mem::replace(&mut self.in_interface, true);
+                let stmts = self.statements(body);
+                self.in_interface = in_interface;
+
+                node("Stmt_Interface", span, json!({
+                    "attrGroups": self.attribute_groups(attributes),
+                    "name": identifier(name),
+                    "extends": names(extends),
+                    "stmts": stmts,
+                }))
+            },
+            StatementKind::Enum { name, implements, backed_type, body, attributes } => node("Stmt_Enum", span, json!({
+                "attrGroups": self.attribute_groups(attributes),
+                "name": identifier(name),
+                "scalarType": optional(backed_type, |backed_type| node("Identifier", Span::default(), json!({
+                    "name": match backed_type {
+                        BackedEnumType::String => "string",
+                        BackedEnumType::Int => "int",
+                    },
+                }))),
+                "implements": names(implements),
+                "stmts": self.statements(body),
+            })),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Methods, properties, constants and other members of classes.
+    fn class_member(&mut self, statement: &Statement) -> Value {
+        let span = statement.span;
+
+        match &statement.kind {
+            StatementKind::TraitUse { traits } => node("Stmt_TraitUse", span, json!({
+                "traits": names(traits),
+                "adaptations": [],
+            })),
+            StatementKind::Method { name, params, body, flags, return_type, returns_by_ref, attributes, doc_comment } => {
+                // Abstract and interface methods have no body at all, rather than an empty one.
+                let stmts = if self.in_interface || flags.contains(&MethodFlag::Abstract) {
+                    Value::Null
+                } else {
+                    let in_interface = std::mem::replace(&mut self.in_interface, false);
+                    let stmts = self.statements(body);
+                    self.in_interface = in_interface;
+
+                    stmts
+                };
+
+                let method = node("Stmt_ClassMethod", span, json!({
+                    "attrGroups": self.attribute_groups(attributes),
+                    "flags": self::flags(flags, method_flag),
+                    "byRef": returns_by_ref,
+                    "name": identifier(name),
+                    "params": self.params(params),
+                    "returnType": optional(return_type, r#type),
+                    "stmts": stmts,
+                }));
+
+                self.with_doc_comment(method, doc_comment)
+            },
+            StatementKind::Var { var, value, r#type, attributes } => self.property(0, var, value, r#type, &[], attributes, span),
+            StatementKind::Property { var, value, r#type, flags, hooks, attributes, doc_comment } => {
+                let property = self.property(self::flags(flags, property_flag), var, value, r#type, hooks, attributes, span);
+
+                self.with_doc_comment(property, doc_comment)
+            },
+            StatementKind::Constant { name, value, flags, attributes, doc_comment } => {
+                let constant = node("Stmt_ClassConst", span, json!({
+                    "attrGroups": self.attribute_groups(attributes),
+                    "flags": self::flags(flags, const_flag),
+                    "type": null,
+                    "consts": [node("Const", name.span, json!({
+                        "name": identifier(name),
+                        "value": self.expression(value),
+                    }))],
+                }));
+
+                self.with_doc_comment(constant, doc_comment)
+            },
+            StatementKind::EnumCase { name, value, attributes } => node("Stmt_EnumCase", span, json!({
+                "attrGroups": self.attribute_groups(attributes),
+                "name": identifier(name),
+                "expr": optional(value, |value| self.expression(value)),
+            })),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Loops, conditionals and jumps.
+    fn control_flow(&mut self, statement: &Statement) -> Value {
+        let span = statement.span;
+
+        match &statement.kind {
+            StatementKind::While { condition, body } => node("Stmt_While", span, json!({
+                "cond": self.expression(condition),
+                "stmts": self.statements(body),
+            })),
+            StatementKind::For { init, condition, r#loop, then } => node("Stmt_For", span, json!({
+                "init": init.iter().map(|e| self.expression(e)).collect::<Value>(),
+                "cond": condition.iter().map(|e| self.expression(e)).collect::<Value>(),
+                "loop": r#loop.iter().map(|e| self.expression(e)).collect::<Value>(),
+                "stmts": self.statements(then),
+            })),
+            StatementKind::Foreach { expr, by_ref, key_var, value_var, body } => node("Stmt_Foreach", span, json!({
+                "expr": self.expression(expr),
+                "keyVar": optional(key_var, |key_var| self.expression(key_var)),
+                "byRef": by_ref,
+                "valueVar": self.expression(value_var),
+                "stmts": self.statements(body),
+            })),
+            StatementKind::If { condition, then, else_ifs, r#else } => node("Stmt_If", span, json!({
+                "cond": self.expression(condition),
+                "stmts": self.statements(then),
+                "elseifs": else_ifs.iter().map(|else_if| node("Stmt_ElseIf", else_if.condition.span, json!({
+                    "cond": self.expression(&else_if.condition),
+                    "stmts": self.statements(&else_if.body),
+                }))).collect::<Value>(),
+                "else": optional(r#else, |r#else| node("Stmt_Else", Span::default(), json!({ "stmts": self.statements(r#else) }))),
+            })),
+            StatementKind::Switch { condition, cases } => node("Stmt_Switch", span, json!({
+                "cond": self.expression(condition),
+                "cases": cases.iter().map(|case| node("Stmt_Case", Span::default(), json!({
+                    "cond": optional(&case.condition, |condition| self.expression(condition)),
+                    "stmts": self.statements(&case.body),
+                }))).collect::<Value>(),
+            })),
+            StatementKind::Try { body, catches, finally } => node("Stmt_TryCatch", span, json!({
+                "stmts": self.statements(body),
+                "catches": catches.iter().map(|catch| node("Stmt_Catch", catch.var.span, json!({
+                    "types": names(&catch.types),
+                    "var": self.expression(&catch.var),
+                    "stmts": self.statements(&catch.body),
+                }))).collect::<Value>(),
+                "finally": optional(finally, |finally| node("Stmt_Finally", Span::default(), json!({ "stmts": self.statements(finally) }))),
+            })),
+            StatementKind::Return { value } => node("Stmt_Return", span, json!({
+                "expr": optional(value, |value| self.expression(value)),
+            })),
+            StatementKind::Break { num } => node("Stmt_Break", span, json!({
+                "num": optional(num, |num| self.expression(num)),
+            })),
+            StatementKind::Continue { num } => node("Stmt_Continue", span, json!({
+                "num": optional(num, |num| self.expression(num)),
+            })),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Statements that don't contain other statements.
+    fn simple_statement(&mut self, statement: &Statement) -> Value {
+        let span = statement.span;
+
+        match &statement.kind {
+            StatementKind::InlineHtml(html) => node("Stmt_InlineHTML", span, json!({ "value": html })),
+            StatementKind::Static { vars } => node("Stmt_Static", span, json!({
+                "vars": vars.iter().map(|var| node("StaticVar", var.var.span, json!({
+                    "var": self.expression(&var.var),
+                    "default": optional(&var.default, |default| self.expression(default)),
+                }))).collect::<Value>(),
+            })),
+            StatementKind::Global { vars } => node("Stmt_Global", span, json!({
+                "vars": vars.iter().map(|var| self.expression(var)).collect::<Value>(),
+            })),
+            StatementKind::Echo { values } => node("Stmt_Echo", span, json!({
+                "exprs": values.iter().map(|value| self.expression(value)).collect::<Value>(),
+            })),
+            StatementKind::Expression { expr } => node("Stmt_Expression", span, json!({
+                "expr": self.expression(expr),
+            })),
+            StatementKind::Comment { comment } => {
+                let mut nop = node("Stmt_Nop", span, json!({}));
+                nop["attributes"]["comments"] = json!([self::comment(comment, span)]);
+
+                nop
+            },
+            StatementKind::HaltCompiler { data, .. } => node("Stmt_HaltCompiler", span, json!({ "remaining": String::from_utf8_lossy(data) })),
+            StatementKind::Noop | StatementKind::Error => node("Stmt_Nop", span, json!({})),
+            _ => unreachable!(),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn property(&mut self, flags: u32, var: &str, value: &Option<Expression>, r#type: &Option<Type>, hooks: &[PropertyHook], attributes: &[AttributeGroup], span: Span) -> Value {
+        node("Stmt_Property", span, json!({
+            "attrGroups": self.attribute_groups(attributes),
+            "flags": flags,
+            "type": optional(r#type, self::r#type),
+            "props": [node("PropertyItem", span, json!({
+                "name": node("VarLikeIdentifier", Span::default(), json!({ "name": var })),
+                "default": optional(value, |value| self.expression(value)),
+            }))],
+            "hooks": self.hooks(hooks),
+        }))
+    }
+
+    fn hooks(&mut self, hooks: &[PropertyHook]) -> Value {
+        hooks.iter().map(|hook| node("PropertyHook", hook.name.span, json!({
+            "attrGroups": self.attribute_groups(&hook.attributes),
+            "flags": flags(&hook.flags, method_flag),
+            "byRef": hook.by_ref,
+            "name": identifier(&hook.name),
+            "params": self.params(&hook.params),
+            "body": match &hook.body {
+                Some(PropertyHookBody::Expression(expression)) => self.expression(expression),
+                Some(PropertyHookBody::Block(block)) => self.statements(block),
+                None => Value::Null,
+            },
+        }))).collect()
+    }
+
+    /// The class in `new`, `instanceof` and static accesses is a name when it's written literally.
+    fn class_reference(&mut self, expression: &Expression) -> Value {
+        match &expression.kind {
+            ExpressionKind::Identifier { name } => self::name(name, expression.span),
+            _ => self.expression(expression),
+        }
+    }
+
+    fn member(&mut self, member: &Expression) -> Value {
+        match &member.kind {
+            ExpressionKind::Identifier { name } => node("Identifier", member.span, json!({ "name": name })),
+            ExpressionKind::Dynamic { value } => self.expression(value),
+            _ => self.expression(member),
+        }
+    }
+
+    fn string_parts(&mut self, parts: &[StringPart]) -> Value {
+        parts.iter().map(|part| match part {
+            StringPart::Const(value) => node("InterpolatedStringPart", Span::default(), json!({ "value": value })),
+            StringPart::Expr(expression) => self.expression(expression),
+        }).collect()
+    }
+
+    /// Strings without any interpolation are plain string scalars.
+    fn interpolated(&mut self, parts: &[StringPart], span: Span) -> Value {
+        let constant: Option<String> = parts.iter().map(|part| match part {
+            StringPart::Const(value) => Some(value.as_str()),
+            StringPart::Expr(_) => None,
+        }).collect();
+
+        match constant {
+            Some(value) => node("Scalar_String", span, json!({ "value": value })),
+            None => node("Scalar_InterpolatedString", span, json!({ "parts": self.string_parts(parts) })),
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) -> Value {
+        // Each group of expressions is exported by its own function, which keeps the stack frame for
+        // each level of nesting small enough for the parser's `max_depth`.
+        match &expression.kind {
+            ExpressionKind::Static | ExpressionKind::Int { .. } | ExpressionKind::Float { .. } | ExpressionKind::Variable { .. } |
+            ExpressionKind::Identifier { .. } | ExpressionKind::ConstantString { .. } | ExpressionKind::InterpolatedString { .. } |
+            ExpressionKind::Heredoc { .. } | ExpressionKind::Nowdoc { .. } | ExpressionKind::Bool { .. } | ExpressionKind::Null |
+            ExpressionKind::Error | ExpressionKind::MagicConst { .. } => self.scalar(expression),
+            ExpressionKind::PreIncrement { .. } | ExpressionKind::PostIncrement { .. } | ExpressionKind::PreDecrement { .. } |
+            ExpressionKind::PostDecrement { .. } | ExpressionKind::Dynamic { .. } | ExpressionKind::BooleanNot { .. } |
+            ExpressionKind::Clone { .. } | ExpressionKind::Throw { .. } | ExpressionKind::Yield { .. } |
+            ExpressionKind::Negate { .. } | ExpressionKind::UnaryPlus { .. } | ExpressionKind::BitwiseNot { .. } |
+            ExpressionKind::ErrorSuppress { .. } | ExpressionKind::Print { .. } | ExpressionKind::Exit { .. } |
+            ExpressionKind::Include { .. } | ExpressionKind::IncludeOnce { .. } | ExpressionKind::Require { .. } |
+            ExpressionKind::RequireOnce { .. } | ExpressionKind::Cast { .. } => self.unary(expression),
+            ExpressionKind::Infix { .. } | ExpressionKind::Ternary { .. } | ExpressionKind::Coalesce { .. } |
+            ExpressionKind::Assign { .. } | ExpressionKind::CoalesceAssign { .. } | ExpressionKind::AssignRef { .. } |
+            ExpressionKind::Instanceof { .. } => self.binary(expression),
+            ExpressionKind::PropertyFetch { .. } | ExpressionKind::NullsafePropertyFetch { .. } |
+            ExpressionKind::StaticPropertyFetch { .. } | ExpressionKind::ConstFetch { .. } |
+            ExpressionKind::DynamicConstFetch { .. } | ExpressionKind::ArrayIndex { .. } => self.access(expression),
+            ExpressionKind::Call { .. } | ExpressionKind::New { .. } | ExpressionKind::MethodCall { .. } |
+            ExpressionKind::NullsafeMethodCall { .. } | ExpressionKind::StaticMethodCall { .. } |
+            ExpressionKind::DynamicStaticMethodCall { .. } => self.call(expression),
+            ExpressionKind::Array { .. } | ExpressionKind::Closure { .. } | ExpressionKind::ArrowFunction { .. } |
+            ExpressionKind::AnonymousClass { .. } | ExpressionKind::Match { .. } => self.compound(expression),
+        }
+    }
+
+    /// Literals, names and variables.
+    fn scalar(&mut self, expression: &Expression) -> Value {
+        let span = expression.span;
+
+        match &expression.kind {
+            ExpressionKind::Static => name("static", span),
+            ExpressionKind::Int { i } => node("Scalar_Int", span, json!({ "value": i })),
+            ExpressionKind::Float { f } => node("Scalar_Float", span, json!({ "value": f.0 })),
+            ExpressionKind::Variable { name } => node("Expr_Variable", span, json!({ "name": name })),
+            ExpressionKind::Identifier { name } => node("Expr_ConstFetch", span, json!({ "name": self::name(name, span) })),
+            ExpressionKind::ConstantString { value } => node("Scalar_String", span, json!({ "value": value })),
+            ExpressionKind::InterpolatedString { parts } | ExpressionKind::Heredoc { parts, .. } => self.interpolated(parts, span),
+            ExpressionKind::Nowdoc { value, .. } => node("Scalar_String", span, json!({ "value": value })),
+            ExpressionKind::Bool { value } => node("Expr_ConstFetch", span, json!({ "name": name(if *value { "true" } else { "false" }, span) })),
+            ExpressionKind::Null => node("Expr_ConstFetch", span, json!({ "name": name("null", span) })),
+            ExpressionKind::Error => node("Expr_Error", span, json!({})),
+            ExpressionKind::MagicConst { constant } => node(magic_const(constant), span, json!({})),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Expressions with a single operand.
+    fn unary(&mut self, expression: &Expression) -> Value {
+        let span = expression.span;
+
+        match &expression.kind {
+            ExpressionKind::PreIncrement { value } => node("Expr_PreInc", span, json!({ "var": self.expression(value) })),
+            ExpressionKind::PostIncrement { value } => node("Expr_PostInc", span, json!({ "var": self.expression(value) })),
+            ExpressionKind::PreDecrement { value } => node("Expr_PreDec", span, json!({ "var": self.expression(value) })),
+            ExpressionKind::PostDecrement { value } => node("Expr_PostDec", span, json!({ "var": self.expression(value) })),
+            ExpressionKind::Dynamic { value } => node("Expr_Variable", span, json!({ "name": self.expression(value) })),
+            ExpressionKind::BooleanNot { value } => node("Expr_BooleanNot", span, json!({ "expr": self.expression(value) })),
+            ExpressionKind::Clone { target } => node("Expr_Clone", span, json!({ "expr": self.expression(target) })),
+            ExpressionKind::Throw { value } => node("Expr_Throw", span, json!({ "expr": self.expression(value) })),
+            ExpressionKind::Yield { value } => node("Expr_Yield", span, json!({ "key": null, "value": self.expression(value) })),
+            ExpressionKind::Negate { value } => node("Expr_UnaryMinus", span, json!({ "expr": self.expression(value) })),
+            ExpressionKind::UnaryPlus { value } => node("Expr_UnaryPlus", span, json!({ "expr": self.expression(value) })),
+            ExpressionKind::BitwiseNot { value } => node("Expr_BitwiseNot", span, json!({ "expr": self.expression(value) })),
+            ExpressionKind::ErrorSuppress { value } => node("Expr_ErrorSuppress", span, json!({ "expr": self.expression(value) })),
+            ExpressionKind::Print { value } => node("Expr_Print", span, json!({ "expr": self.expression(value) })),
+            ExpressionKind::Exit { value } => node("Expr_Exit", span, json!({ "expr": optional(value, |value| self.expression(value)) })),
+            ExpressionKind::Include { path } => self.include(path, 1, span),
+            ExpressionKind::IncludeOnce { path } => self.include(path, 2, span),
+            ExpressionKind::Require { path } => self.include(path, 3, span),
+            ExpressionKind::RequireOnce { path } => self.include(path, 4, span),
+            ExpressionKind::Cast { kind, value } => {
+                let node_type = match kind {
+                    CastKind::String => "Expr_Cast_String",
+                    CastKind::Object => "Expr_Cast_Object",
+                    CastKind::Bool => "Expr_Cast_Bool",
+                    CastKind::Int => "Expr_Cast_Int",
+                    CastKind::Double => "Expr_Cast_Double",
+                    CastKind::Array => "Expr_Cast_Array",
+                };
+
+                node(node_type, span, json!({ "expr": self.expression(value) }))
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Operators with two or three operands, including assignments.
+    fn binary(&mut self, expression: &Expression) -> Value {
+        let span = expression.span;
+
+        match &expression.kind {
+            ExpressionKind::Infix { lhs, op, rhs } => {
+                let (node_type, assign) = infix_op(op);
+
+                if assign {
+                    node(node_type, span, json!({ "var": self.expression(lhs), "expr": self.expression(rhs) }))
+                } else {
+                    node(node_type, span, json!({ "left": self.expression(lhs), "right": self.expression(rhs) }))
+                }
+            },
+            ExpressionKind::Ternary { condition, then, r#else } => node("Expr_Ternary", span, json!({
+                "cond": self.expression(condition),
+                "if": optional(then, |then| self.expression(then)),
+                "else": self.expression(r#else),
+            })),
+            ExpressionKind::Coalesce { lhs, rhs } => node("Expr_BinaryOp_Coalesce", span, json!({
+                "left": self.expression(lhs),
+                "right": self.expression(rhs),
+            })),
+            ExpressionKind::Assign { var, value } => node("Expr_Assign", span, json!({
+                "var": self.expression(var),
+                "expr": self.expression(value),
+            })),
+            ExpressionKind::CoalesceAssign { var, value } => node("Expr_AssignOp_Coalesce", span, json!({
+                "var": self.expression(var),
+                "expr": self.expression(value),
+            })),
+            ExpressionKind::AssignRef { var, value } => node("Expr_AssignRef", span, json!({
+                "var": self.expression(var),
+                "expr": self.expression(value),
+            })),
+            ExpressionKind::Instanceof { lhs, rhs } => node("Expr_Instanceof", span, json!({
+                "expr": self.expression(lhs),
+                "class": self.class_reference(rhs),
+            })),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Property, constant and array accesses.
+    fn access(&mut self, expression: &Expression) -> Value {
+        let span = expression.span;
+
+        match &expression.kind {
+            ExpressionKind::PropertyFetch { target, property } => node("Expr_PropertyFetch", span, json!({
+                "var": self.expression(target),
+                "name": self.member(property),
+            })),
+            ExpressionKind::NullsafePropertyFetch { target, property } => node("Expr_NullsafePropertyFetch", span, json!({
+                "var": self.expression(target),
+                "name": self.member(property),
+            })),
+            ExpressionKind::StaticPropertyFetch { target, property } => node("Expr_StaticPropertyFetch", span, json!({
+                "class": self.class_reference(target),
+                "name": match &property.kind {
+                    ExpressionKind::Variable { name } => node("VarLikeIdentifier", property.span, json!({ "name": name })),
+                    _ => self.member(property),
+                },
+            })),
+            ExpressionKind::ConstFetch { target, constant } => node("Expr_ClassConstFetch", span, json!({
+                "class": self.class_reference(target),
+                "name": identifier(constant),
+            })),
+            ExpressionKind::DynamicConstFetch { target, constant } => node("Expr_ClassConstFetch", span, json!({
+                "class": self.class_reference(target),
+                "name": self.expression(constant),
+            })),
+            ExpressionKind::ArrayIndex { array, index } => node("Expr_ArrayDimFetch", span, json!({
+                "var": self.expression(array),
+                "dim": optional(index, |index| self.expression(index)),
+            })),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Function and method calls, and `new`.
+    fn call(&mut self, expression: &Expression) -> Value {
+        let span = expression.span;
+
+        match &expression.kind {
+            ExpressionKind::Call { target, args } => node("Expr_FuncCall", span, json!({
+                "name": self.class_reference(target),
+                "args": self.args(args),
+            })),
+            ExpressionKind::New { target, args } => node("Expr_New", span, json!({
+                "class": self.class_reference(target),
+                "args": self.args(args),
+            })),
+            ExpressionKind::MethodCall { target, method, args } => node("Expr_MethodCall", span, json!({
+                "var": self.expression(target),
+                "name": self.member(method),
+                "args": self.args(args),
+            })),
+            ExpressionKind::NullsafeMethodCall { target, method, args } => node("Expr_NullsafeMethodCall", span, json!({
+                "var": self.expression(target),
+                "name": self.member(method),
+                "args": self.args(args),
+            })),
+            ExpressionKind::StaticMethodCall { target, method, args } => node("Expr_StaticCall", span, json!({
+                "class": self.class_reference(target),
+                "name": identifier(method),
+                "args": self.args(args),
+            })),
+            ExpressionKind::DynamicStaticMethodCall { target, method, args } => node("Expr_StaticCall", span, json!({
+                "class": self.class_reference(target),
+                "name": self.expression(method),
+                "args": self.args(args),
+            })),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Arrays, closures, anonymous classes and `match`, which contain other statements or expressions.
+    fn compound(&mut self, expression: &Expression) -> Value {
+        let span = expression.span;
+
+        match &expression.kind {
+            ExpressionKind::Array { items } => node("Expr_Array", span, json!({
+                "items": items.iter().map(|item| node("ArrayItem", item.value.span, json!({
+                    "key": optional(&item.key, |key| self.expression(key)),
+                    "value": self.expression(&item.value),
+                    "byRef": item.by_ref,
+                    "unpack": item.unpack,
+                }))).collect::<Value>(),
+            })),
+            ExpressionKind::Closure { params, uses, return_type, returns_by_ref, body, attributes } => node("Expr_Closure", span, json!({
+                "attrGroups": self.attribute_groups(attributes),
+                "static": false,
+                "byRef": returns_by_ref,
+                "params": self.params(params),
+                "uses": uses.iter().map(|r#use| node("ClosureUse", r#use.var.span, json!({
+                    "var": self.expression(&r#use.var),
+                    "byRef": r#use.by_ref,
+                }))).collect::<Value>(),
+                "returnType": optional(return_type, r#type),
+                "stmts": self.statements(body),
+            })),
+            ExpressionKind::ArrowFunction { params, return_type, returns_by_ref, expr, attributes } => node("Expr_ArrowFunction", span, json!({
+                "attrGroups": self.attribute_groups(attributes),
+                "static": false,
+                "byRef": returns_by_ref,
+                "params": self.params(params),
+                "returnType": optional(return_type, r#type),
+                "expr": self.expression(expr),
+            })),
+            ExpressionKind::AnonymousClass { extends, implements, body, attributes } => {
+                let class = self.class(Value::Null, 0, extends, implements, body, attributes);
+
+                node("Stmt_Class", span, class)
+            },
+            ExpressionKind::Match { condition, arms } => node("Expr_Match", span, json!({
+                "cond": self.expression(condition),
+                "arms": arms.iter().map(|arm| node("MatchArm", arm.body.span, json!({
+                    "conds": optional(&arm.conditions, |conditions| conditions.iter().map(|c| self.expression(c)).collect::<Value>()),
+                    "body": self.expression(&arm.body),
+                }))).collect::<Value>(),
+            })),
+            _ => unreachable!(),
+        }
+    }
+
+    fn include(&mut self, path: &Expression, r#type: u8, span: Span) -> Value {
+        node("Expr_Include", span, json!({ "expr": self.expression(path), "type": r#type }))
+    }
+}
+
+/// The node type for an infix operator, and whether it's a compound assignment.
+fn infix_op(op: &InfixOp) -> (&'static str, bool) {
+    match op {
+        InfixOp::Add => ("Expr_BinaryOp_Plus", false),
+        InfixOp::Sub => ("Expr_BinaryOp_Minus", false),
+        InfixOp::Div => ("Expr_BinaryOp_Div", false),
+        InfixOp::Mul => ("Expr_BinaryOp_Mul", false),
+        InfixOp::Mod => ("Expr_BinaryOp_Mod", false),
+        InfixOp::LeftShift => ("Expr_BinaryOp_ShiftLeft", false),
+        InfixOp::RightShift => ("Expr_BinaryOp_ShiftRight", false),
+        InfixOp::BitwiseAnd => ("Expr_BinaryOp_BitwiseAnd", false),
+        InfixOp::BitwiseOr => ("Expr_BinaryOp_BitwiseOr", false),
+        InfixOp::BitwiseXor => ("Expr_BinaryOp_BitwiseXor", false),
+        InfixOp::Concat => ("Expr_BinaryOp_Concat", false),
+        InfixOp::LessThan => ("Expr_BinaryOp_Smaller", false),
+        InfixOp::GreaterThan => ("Expr_BinaryOp_Greater", false),
+        InfixOp::LessThanEquals => ("Expr_BinaryOp_SmallerOrEqual", false),
+        InfixOp::GreaterThanEquals => ("Expr_BinaryOp_GreaterOrEqual", false),
+        InfixOp::Equals => ("Expr_BinaryOp_Equal", false),
+        InfixOp::Identical => ("Expr_BinaryOp_Identical", false),
+        InfixOp::NotEquals => ("Expr_BinaryOp_NotEqual", false),
+        InfixOp::NotIdentical => ("Expr_BinaryOp_NotIdentical", false),
+        InfixOp::Spaceship => ("Expr_BinaryOp_Spaceship", false),
+        InfixOp::And => ("Expr_BinaryOp_BooleanAnd", false),
+        InfixOp::Or => ("Expr_BinaryOp_BooleanOr", false),
+        InfixOp::LogicalAnd => ("Expr_BinaryOp_LogicalAnd", false),
+        InfixOp::LogicalOr => ("Expr_BinaryOp_LogicalOr", false),
+        InfixOp::LogicalXor => ("Expr_BinaryOp_LogicalXor", false),
+        InfixOp::Pow => ("Expr_BinaryOp_Pow", false),
+        InfixOp::ConcatAssign => ("Expr_AssignOp_Concat", true),
+        InfixOp::AddAssign => ("Expr_AssignOp_Plus", true),
+        InfixOp::SubAssign => ("Expr_AssignOp_Minus", true),
+        InfixOp::MulAssign => ("Expr_AssignOp_Mul", true),
+        InfixOp::DivAssign => ("Expr_AssignOp_Div", true),
+        InfixOp::ModAssign => ("Expr_AssignOp_Mod", true),
+        InfixOp::PowAssign => ("Expr_AssignOp_Pow", true),
+        InfixOp::BitwiseAndAssign => ("Expr_AssignOp_BitwiseAnd", true),
+        InfixOp::BitwiseOrAssign => ("Expr_AssignOp_BitwiseOr", true),
+        InfixOp::BitwiseXorAssign => ("Expr_AssignOp_BitwiseXor", true),
+        InfixOp::LeftShiftAssign => ("Expr_AssignOp_ShiftLeft", true),
+        InfixOp::RightShiftAssign => ("Expr_AssignOp_ShiftRight", true),
+    }
+}
+
+fn magic_const(constant: &MagicConst) -> &'static str {
+    match constant {
+        MagicConst::Class => "Scalar_MagicConst_Class",
+        MagicConst::Dir => "Scalar_MagicConst_Dir",
+        MagicConst::File => "Scalar_MagicConst_File",
+        MagicConst::Function => "Scalar_MagicConst_Function",
+        MagicConst::Line => "Scalar_MagicConst_Line",
+        MagicConst::Method => "Scalar_MagicConst_Method",
+        MagicConst::Namespace => "Scalar_MagicConst_Namespace",
+        MagicConst::Trait => "Scalar_MagicConst_Trait",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::to_nikic_json;
+    use crate::{Program, StatementKind};
+
+    /// Positions are checked separately, so that the expected trees stay readable.
+    fn without_attributes(value: Value) -> Value {
+        match value {
+            Value::Object(object) => object.into_iter()
+                .filter(|(key, _)| key != "attributes")
+                .map(|(key, value)| (key, without_attributes(value)))
+                .collect(),
+            Value::Array(values) => values.into_iter().map(without_attributes).collect(),
+            value => value,
+        }
+    }
+
+    fn parse(source: &str) -> Value {
+        without_attributes(to_nikic_json(&crate::parse(source).unwrap()))
+    }
+
+    #[test]
+    fn expressions() {
+        assert_eq!(parse("<?php echo $a + 1, foo(...$b);"), json!([{
+            "nodeType": "Stmt_Echo",
+            "exprs": [
+                {
+                    "nodeType": "Expr_BinaryOp_Plus",
+                    "left": { "nodeType": "Expr_Variable", "name": "a" },
+                    "right": { "nodeType": "Scalar_Int", "value": 1 },
+                },
+                {
+                    "nodeType": "Expr_FuncCall",
+                    "name": { "nodeType": "Name", "name": "foo" },
+                    "args": [{
+                        "nodeType": "Arg",
+                        "name": null,
+                        "value": { "nodeType": "Expr_Variable", "name": "b" },
+                        "byRef": false,
+                        "unpack": true,
+                    }],
+                },
+            ],
+        }]));
+
+        assert_eq!(parse("<?php \\Foo::$bar .= \"a{$b}\";"), json!([{
+            "nodeType": "Stmt_Expression",
+            "expr": {
+                "nodeType": "Expr_AssignOp_Concat",
+                "var": {
+                    "nodeType": "Expr_StaticPropertyFetch",
+                    "class": { "nodeType": "Name_FullyQualified", "name": "Foo" },
+                    "name": { "nodeType": "VarLikeIdentifier", "name": "bar" },
+                },
+                "expr": {
+                    "nodeType": "Scalar_InterpolatedString",
+                    "parts": [
+                        { "nodeType": "InterpolatedStringPart", "value": "a" },
+                        { "nodeType": "Expr_Variable", "name": "b" },
+                    ],
+                },
+            },
+        }]));
+    }
+
+    #[test]
+    fn declarations() {
+        assert_eq!(parse("<?php
+            interface A {
+                /** Docs. */
+                public static function b(?int $c): static;
+            }
+        "), json!([{
+            "nodeType": "Stmt_Interface",
+            "attrGroups": [],
+            "name": { "nodeType": "Identifier", "name": "A" },
+            "extends": [],
+            "stmts": [{
+                "nodeType": "Stmt_ClassMethod",
+                "attrGroups": [],
+                "flags": 9,
+                "byRef": false,
+                "name": { "nodeType": "Identifier", "name": "b" },
+                "params": [{
+                    "nodeType": "Param",
+                    "attrGroups": [],
+                    "flags": 0,
+                    "type": { "nodeType": "NullableType", "type": { "nodeType": "Identifier", "name": "int" } },
+                    "byRef": false,
+                    "variadic": false,
+                    "var": { "nodeType": "Expr_Variable", "name": "c" },
+                    "default": null,
+                    "hooks": [],
+                }],
+                "returnType": { "nodeType": "Identifier", "name": "static" },
+                "stmts": null,
+            }],
+        }]));
+    }
+
+    #[test]
+    fn namespaces() {
+        let json = to_nikic_json(&crate::parse("<?php\nnamespace A;\necho 1;\nnamespace B;\n").unwrap());
+
+        assert_eq!(json[0]["nodeType"], "Stmt_Namespace");
+        assert_eq!(json[0]["name"]["name"], "A");
+        assert_eq!(json[0]["stmts"][0]["nodeType"], "Stmt_Echo");
+        assert_eq!(json[0]["stmts"][0]["attributes"]["startLine"], 3);
+        assert_eq!(json[1]["name"]["name"], "B");
+        assert_eq!(json[1]["stmts"], json!([]));
+
+        let json = parse("<?php new abcdefghiéx; namespace\\Café();");
+        assert_eq!(json[0]["expr"]["class"]["name"], "abcdefghiéx");
+        assert_eq!(json[1]["expr"]["name"], json!({ "nodeType": "Name_Relative", "name": "Café" }));
+    }
+
+    #[test]
+    fn property_hooks() {
+        let json = parse("<?php class A { public readonly int $b { get => 1; set; } }");
+        let property = &json[0]["stmts"][0];
+
+        assert_eq!(property["flags"], 1 | 64);
+        assert_eq!(property["hooks"][0]["nodeType"], "PropertyHook");
+        assert_eq!(property["hooks"][0]["name"]["name"], "get");
+        assert_eq!(property["hooks"][0]["body"]["nodeType"], "Scalar_Int");
+        assert_eq!(property["hooks"][1]["body"], Value::Null);
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let nested = [("(", "1", ");"), ("[", "1", "];"), ("!", "1;", ""), ("new A(", "", ");"), ("1 + ", "1;", ""), ("if ($a) { ", "", "}"), ("class A { function f() { ", "", "} }")];
+
+        // The deepest nesting that the parser accepts has to fit in a test thread's stack.
+        for (open, inner, close) in nested {
+            let program = (1..=128).rev()
+                .find_map(|depth| crate::parse(&format!("<?php {}{}{}", open.repeat(depth), inner, close.repeat(depth))).ok())
+                .unwrap();
+
+            to_nikic_json(&program);
+        }
+    }
+
+    #[test]
+    fn comments() {
+        let comment = |comment: &str| StatementKind::Comment { comment: comment.into() }.into();
+        let program: Program = vec![comment("// a"), StatementKind::Noop.into(), comment("// b")];
+        let json = to_nikic_json(&program);
+
+        assert_eq!(json[0]["attributes"]["comments"][0]["text"], "// a");
+        assert_eq!(json[1]["nodeType"], "Stmt_Nop");
+        assert_eq!(json[1]["attributes"]["comments"][0]["text"], "// b");
+    }
+}
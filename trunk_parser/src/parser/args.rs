@@ -0,0 +1,38 @@
+use crate::{ast::Arg, ParseError};
+use trunk_lexer::TokenKind;
+
+use super::{punc::List, Parser, PhpVersion};
+
+impl<'a> Parser<'a> {
+    /// Parse a list of call arguments, stopping at the closing parenthesis.
+    pub(crate) fn args_list(&mut self) -> Result<Vec<Arg>, ParseError> {
+        let mut args = Vec::new();
+
+        while ! self.is_eof() && self.current.kind != TokenKind::RightParen {
+            let mut name = None;
+            let mut unpack = false;
+
+            if matches!(self.current.kind, TokenKind::Identifier(_)) && self.peek.kind == TokenKind::Colon {
+                self.requires(PhpVersion::Php80, "named arguments")?;
+
+                name = Some(self.ident_maybe_reserved()?);
+                self.next();
+            } else if self.current.kind == TokenKind::Ellipsis {
+                self.next();
+                unpack = true;
+            }
+
+            let value = self.expression(0)?;
+
+            args.push(Arg {
+                name,
+                value,
+                unpack,
+            });
+
+            self.list_comma(&TokenKind::RightParen, List::Args)?;
+        }
+
+        Ok(args)
+    }
+}
@@ -0,0 +1,95 @@
+use trunk_lexer::TokenKind;
+
+use crate::{Attribute, AttributeGroup, Parser};
+
+use super::{ParseError, ParseResult};
+
+/// What the attributes in front of a declaration are allowed to be attached to.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum AttributeTarget {
+    /// A function, class, interface, trait or enum, or a closure or arrow function.
+    Statement,
+    /// A method, property, class constant or enum case.
+    Member,
+    /// A closure or arrow function.
+    Function,
+    /// The class after `new`.
+    AnonymousClass,
+}
+
+impl<'a> Parser<'a> {
+    /// Parse any attribute groups in front of a declaration and hold on to them
+    /// until the declaration itself is parsed.
+    pub(crate) fn gather_attributes(&mut self) -> ParseResult<()> {
+        self.skip_comments();
+
+        while self.current.kind == TokenKind::Attribute {
+            self.next();
+
+            let mut members = Vec::new();
+            while ! self.is_eof() && self.current.kind != TokenKind::RightBracket {
+                let name = self.full_identifier()?;
+
+                let mut args = Vec::new();
+                if self.current.kind == TokenKind::LeftParen {
+                    self.lparen()?;
+
+                    args = self.args_list()?;
+
+                    self.rparen()?;
+                }
+
+                members.push(Attribute { name, args });
+
+                self.optional_comma()?;
+            }
+
+            self.rbracket()?;
+
+            self.attributes.push(AttributeGroup { members });
+
+            self.skip_comments();
+        }
+
+        Ok(())
+    }
+
+    /// Gather attributes, failing if they aren't followed by something they can be attached to
+    /// rather than holding on to them for a later declaration.
+    pub(crate) fn gather_attributes_for(&mut self, target: AttributeTarget) -> ParseResult<()> {
+        self.gather_attributes()?;
+
+        if self.attributes.is_empty() {
+            return Ok(());
+        }
+
+        let function = matches!(self.current.kind, TokenKind::Function | TokenKind::Fn) ||
+            (self.current.kind == TokenKind::Static && matches!(self.peek.kind, TokenKind::Function | TokenKind::Fn));
+
+        let allowed = match target {
+            AttributeTarget::Statement => function || match self.current.kind {
+                TokenKind::Abstract | TokenKind::Final | TokenKind::Class | TokenKind::Interface | TokenKind::Trait => true,
+                TokenKind::Enum => matches!(self.peek.kind, TokenKind::Identifier(_)),
+                _ => false,
+            },
+            AttributeTarget::Member => matches!(self.current.kind,
+                TokenKind::Function | TokenKind::Const | TokenKind::Var | TokenKind::Case | TokenKind::Final | TokenKind::Abstract |
+                TokenKind::Public | TokenKind::Protected | TokenKind::Private | TokenKind::Static |
+                TokenKind::PublicSet | TokenKind::ProtectedSet | TokenKind::PrivateSet | TokenKind::Readonly),
+            AttributeTarget::Function => function,
+            AttributeTarget::AnonymousClass => self.current.kind == TokenKind::Class,
+        };
+
+        if ! allowed {
+            self.attributes.clear();
+
+            return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn get_attributes(&mut self) -> Vec<AttributeGroup> {
+        std::mem::take(&mut self.attributes)
+    }
+}
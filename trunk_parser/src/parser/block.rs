@@ -4,16 +4,40 @@ use crate::Block;
 
 use super::{Parser, ParseResult};
 
-impl Parser {
+impl<'a> Parser<'a> {
+    /// Parse statements up to `until`. Each block counts as a level of nesting, since the
+    /// statements in it are parsed in a frame of their own.
     pub(crate) fn block(&mut self, until: &TokenKind) -> ParseResult<Block> {
+        self.nested(|parser| {
+            if ! parser.config.comments {
+                parser.skip_comments();
+            }
+
+            let mut block = Block::new();
+
+            while ! parser.is_eof() && &parser.current.kind != until {
+                parser.tolerant_statement(&mut block, Self::statement, until)?;
+            }
+
+            Ok(block)
+        })
+    }
+
+    /// Parse the body of a control structure, which is either a braced block
+    /// or a single statement without braces.
+    pub(crate) fn body(&mut self) -> ParseResult<Block> {
         self.skip_comments();
-        
-        let mut block = Block::new();
 
-        while ! self.is_eof() && &self.current.kind != until {
-            block.push(self.statement()?);
+        if self.current.kind != TokenKind::LeftBrace {
+            return self.statement().map(|statement| vec![statement]);
         }
 
+        self.lbrace()?;
+
+        let block = self.block(&TokenKind::RightBrace)?;
+
+        self.rbrace()?;
+
         Ok(block)
     }
 }
\ No newline at end of file
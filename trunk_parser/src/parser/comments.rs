@@ -1,24 +1,58 @@
 use trunk_lexer::{TokenKind, Token};
 
-use crate::Parser;
+use crate::{Parser, Statement, StatementKind};
 
-impl Parser {
+impl<'a> Parser<'a> {
     pub(crate) fn skip_comments(&mut self) {
         while matches!(self.current.kind, TokenKind::Comment(_) | TokenKind::DocComment(_)) {
             self.next();
         }
     }
 
+    /// Collect the comments in front of a statement. Comments that are kept as statements of their
+    /// own are left alone.
     pub(crate) fn gather_comments(&mut self) {
+        if self.config.comments {
+            return;
+        }
+
         while matches!(self.current.kind, TokenKind::Comment(_) | TokenKind::DocComment(_)) {
-            self.comments.push(self.current.clone());
+            self.comments.push(self.current);
             self.next();
         }
     }
 
-    pub(crate) fn clear_comments(&mut self) -> Vec<Token> {
-        let c = self.comments.clone();
-        self.comments = vec![];
-        c
+    pub(crate) fn clear_comments(&mut self) -> Vec<&'a Token> {
+        std::mem::take(&mut self.comments)
     }
-}
\ No newline at end of file
+
+    /// With `ParserConfig::comments`, turn a comment where a statement or class member could start
+    /// into a statement. A doc comment in front of a declaration is left for the declaration.
+    pub(crate) fn comment(&mut self) -> Option<Statement> {
+        if ! self.config.comments {
+            return None;
+        }
+
+        let is_doc = match &self.current.kind {
+            TokenKind::Comment(_) => false,
+            TokenKind::DocComment(_) if ! claims_doc_comment(&self.peek.kind) => true,
+            _ => return None,
+        };
+
+        let statement = Statement::new(StatementKind::Comment { comment: self.current.kind.to_string() }, self.current.span);
+        self.next();
+
+        if is_doc {
+            self.doc_comment = None;
+        }
+
+        Some(statement)
+    }
+}
+
+/// Whether a token starts a declaration that keeps the doc comment in front of it.
+fn claims_doc_comment(kind: &TokenKind) -> bool {
+    matches!(kind,
+        TokenKind::Function | TokenKind::Abstract | TokenKind::Final | TokenKind::Class | TokenKind::Public |
+        TokenKind::Protected | TokenKind::Private | TokenKind::Static | TokenKind::Var | TokenKind::Const | TokenKind::Attribute)
+}
@@ -1,30 +1,60 @@
-use crate::Parser;
+use crate::{Parser, Expression, ExpressionKind, Identifier};
 use trunk_lexer::TokenKind;
-use super::{ParseResult, ParseError};
+use super::ParseResult;
 
-impl Parser {
+impl<'a> Parser<'a> {
     /// Expect an unqualified identifier such as Foo or Bar.
     pub(crate) fn ident(&mut self) -> ParseResult<String> {
-        Ok(expect!(self, TokenKind::Identifier(i), i, "expected identifier"))
+        Ok(expect!(self, TokenKind::Identifier(i), i.to_string(), [TokenKind::Identifier(String::new())]))
+    }
+
+    /// Expect an unqualified identifier, keeping track of where it appeared.
+    pub(crate) fn identifier(&mut self) -> ParseResult<Identifier> {
+        self.skip_comments();
+
+        let start = self.current.span;
+        let name = self.ident()?;
+
+        Ok(Identifier { name, span: self.span_from(start) })
     }
 
     /// Expect an unqualified or qualified identifier such as Foo, Bar or Foo\Bar.
     pub(crate) fn name(&mut self) -> ParseResult<String> {
-        Ok(expect!(self, TokenKind::Identifier(i) | TokenKind::QualifiedIdentifier(i), i, "expected identifier"))
+        Ok(expect!(self, TokenKind::Identifier(i) | TokenKind::QualifiedIdentifier(i), i.to_string(), [TokenKind::Identifier(String::new()), TokenKind::QualifiedIdentifier(String::new())]))
     }
 
     /// Expect an unqualified, qualified or fully qualified identifier such as Foo, Foo\Bar or \Foo\Bar. 
     pub(crate) fn full_name(&mut self) -> ParseResult<String> {
-        Ok(expect!(self, TokenKind::Identifier(i) | TokenKind::QualifiedIdentifier(i) | TokenKind::FullyQualifiedIdentifier(i), i, "expected identifier"))
+        Ok(expect!(self, TokenKind::Identifier(i) | TokenKind::QualifiedIdentifier(i) | TokenKind::FullyQualifiedIdentifier(i), i.to_string(), [TokenKind::Identifier(String::new()), TokenKind::QualifiedIdentifier(String::new()), TokenKind::FullyQualifiedIdentifier(String::new())]))
+    }
+
+    /// Expect an unqualified, qualified or fully qualified identifier, keeping track of where it appeared.
+    pub(crate) fn full_identifier(&mut self) -> ParseResult<Identifier> {
+        self.skip_comments();
+
+        let start = self.current.span;
+        let name = self.full_name()?;
+
+        Ok(Identifier::new(name, self.span_from(start)))
     }
 
     pub(crate) fn var(&mut self) -> ParseResult<String> {
-        Ok(expect!(self, TokenKind::Variable(v), v, "expected variable name"))
+        Ok(expect!(self, TokenKind::Variable(v), v.to_string(), [TokenKind::Variable(String::new())]))
+    }
+
+    /// Expect a variable, returning it as an expression.
+    pub(crate) fn variable(&mut self) -> ParseResult<Expression> {
+        self.skip_comments();
+
+        let start = self.current.span;
+        let name = self.var()?;
+
+        Ok(Expression::new(ExpressionKind::Variable { name }, self.span_from(start)))
     }
 
     pub(crate) fn full_name_maybe_type_keyword(&mut self) -> ParseResult<String> {
         match self.current.kind {
-            TokenKind::Array | TokenKind::Callable => {
+            TokenKind::Array | TokenKind::Callable | TokenKind::Null | TokenKind::False | TokenKind::True => {
                 let r = Ok(self.current.kind.to_string());
                 self.next();
                 r
@@ -43,13 +73,25 @@ impl Parser {
         })
     }
 
+    /// Expect an identifier, allowing reserved keywords. Member names such as `$foo->class`
+    /// or `Foo::new()` are allowed to use any keyword.
     pub(crate) fn ident_maybe_reserved(&mut self) -> ParseResult<String> {
         match self.current.kind {
             TokenKind::Static | TokenKind::Abstract | TokenKind::Final | TokenKind::For |
             TokenKind::Private | TokenKind::Protected | TokenKind::Public | TokenKind::Require |
             TokenKind::RequireOnce | TokenKind::New | TokenKind::Clone | TokenKind::If |
             TokenKind::Else | TokenKind::ElseIf | TokenKind::Default | TokenKind::Enum |
-            TokenKind::Match | TokenKind::Catch | TokenKind::Finally | TokenKind::Namespace => {
+            TokenKind::Match | TokenKind::Catch | TokenKind::Finally | TokenKind::Namespace |
+            TokenKind::Array | TokenKind::As | TokenKind::Break | TokenKind::Callable |
+            TokenKind::Case | TokenKind::Class | TokenKind::Const | TokenKind::Continue |
+            TokenKind::Declare | TokenKind::Die | TokenKind::Do | TokenKind::Echo | TokenKind::Empty | TokenKind::Exit |
+            TokenKind::EndDeclare | TokenKind::EndFor | TokenKind::EndForeach | TokenKind::EndIf |
+            TokenKind::EndSwitch | TokenKind::EndWhile | TokenKind::Extends | TokenKind::False |
+            TokenKind::Fn | TokenKind::Foreach | TokenKind::Function | TokenKind::Global | TokenKind::Implements | TokenKind::Include | TokenKind::IncludeOnce |
+            TokenKind::Instanceof | TokenKind::Interface | TokenKind::LogicalAnd | TokenKind::LogicalOr |
+            TokenKind::LogicalXor | TokenKind::Null | TokenKind::Print | TokenKind::Return |
+            TokenKind::Switch | TokenKind::Throw | TokenKind::Trait | TokenKind::True |
+            TokenKind::Try | TokenKind::Use | TokenKind::Var | TokenKind::Yield | TokenKind::While | TokenKind::Readonly => {
                 let string = self.current.kind.to_string();
                 self.next();
                 Ok(string)
@@ -1,70 +1,295 @@
-use std::{vec::IntoIter, fmt::{Display}};
-use trunk_lexer::{Token, TokenKind, Span};
-use crate::{Program, Statement, Block, Expression, ast::{ArrayItem, Use, MethodFlag, ClassFlag, ElseIf, UseKind, MagicConst, BackedEnumType, ClosureUse, Arg, StaticVar}, Identifier, Type, MatchArm, Catch, Case};
+use std::{fmt::{Display}, path::Path, str::FromStr};
+use trunk_lexer::{Lexer, Token, TokenKind, Span};
+pub use precedence::{Precedence, Associativity};
+pub use statements::Statements;
+pub use suggestions::Suggestion;
+pub use warnings::ParseWarning;
+use punc::List;
+use attributes::AttributeTarget;
+use crate::{Diagnostic, Error, ParsedFile, Program, Statement, StatementKind, Block, Expression, ExpressionKind, AttributeGroup, ast::{ArrayItem, Use, ClassFlag, MethodFlag, ElseIf, UseKind, BackedEnumType, ClosureUse, StaticVar, Constant, ParamList, PropertyHook, PropertyHookBody}, Identifier, Type, MatchArm, Catch, Case};
 
 type ParseResult<T> = Result<T, ParseError>;
 
 macro_rules! expect {
-    ($parser:expr, $expected:pat, $out:expr, $message:literal) => {{
+    ($parser:expr, $expected:pat, $out:expr, [$($kind:expr),+ $(,)?]) => {{
         $parser.skip_comments();
-        match $parser.current.kind.clone() {
+        let current = $parser.current;
+        match &current.kind {
             $expected => {
                 $parser.next();
                 $out
             },
-            _ => return Err(ParseError::ExpectedToken($message.into(), $parser.current.span)),
+            _ => return Err($parser.expected(vec![$($kind),+])),
         }
     }};
-    ($parser:expr, $expected:pat, $message:literal) => {{
+    ($parser:expr, $expected:path) => {{
         $parser.skip_comments();
-        match $parser.current.kind.clone() {
+        match $parser.current.kind {
             $expected => { $parser.next(); },
-            _ => return Err(ParseError::ExpectedToken($message.into(), $parser.current.span)),
+            _ => return Err($parser.expected(vec![$expected])),
         }
     }};
 }
 
 mod params;
+mod args;
 mod block;
 mod punc;
 mod ident;
 mod comments;
+mod attributes;
+mod strings;
+mod precedence;
+mod statements;
+mod suggestions;
+mod warnings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PhpVersion {
+    Php73,
+    Php74,
+    Php80,
+    Php81,
+    Php82,
+    Php83,
+    Php84,
+}
+
+impl Display for PhpVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Php73 => write!(f, "7.3"),
+            Self::Php74 => write!(f, "7.4"),
+            Self::Php80 => write!(f, "8.0"),
+            Self::Php81 => write!(f, "8.1"),
+            Self::Php82 => write!(f, "8.2"),
+            Self::Php83 => write!(f, "8.3"),
+            Self::Php84 => write!(f, "8.4"),
+        }
+    }
+}
+
+impl FromStr for PhpVersion {
+    type Err = String;
+
+    /// Parse a version as written by `Display`, such as `8.1`.
+    fn from_str(version: &str) -> Result<Self, Self::Err> {
+        match version {
+            "7.3" => Ok(Self::Php73),
+            "7.4" => Ok(Self::Php74),
+            "8.0" => Ok(Self::Php80),
+            "8.1" => Ok(Self::Php81),
+            "8.2" => Ok(Self::Php82),
+            "8.3" => Ok(Self::Php83),
+            "8.4" => Ok(Self::Php84),
+            _ => Err(format!("unsupported PHP version `{}`, expected one of 7.3, 7.4, 8.0, 8.1, 8.2, 8.3 and 8.4", version)),
+        }
+    }
+}
 
+#[derive(Debug, Clone)]
 pub struct ParserConfig {
-    force_type_strings: bool,
+    pub force_type_strings: bool,
+    pub php_version: PhpVersion,
+    /// How deeply statements, blocks and expressions can be nested before parsing fails with
+    /// `ParseError::TooDeep`, rather than overflowing the stack. Each operator in a chain such as
+    /// `$a . $b . $c` counts as a level as well. The default of 128 fits in a 2MB thread stack,
+    /// even in debug builds.
+    pub max_depth: usize,
+    pub trailing_commas: TrailingCommas,
+    /// Keep comments that are found where a statement or class member could start, as
+    /// `StatementKind::Comment`s, rather than discarding them. Comments anywhere else, such as
+    /// inside expressions, are still discarded.
+    pub comments: bool,
 }
 
 #[allow(clippy::derivable_impls)]
 impl Default for ParserConfig {
     fn default() -> Self {
-        Self { force_type_strings: false }
+        Self { force_type_strings: false, php_version: PhpVersion::Php84, max_depth: 128, trailing_commas: TrailingCommas::default(), comments: false }
     }
 }
 
-pub struct Parser {
+/// Whether a comma is allowed after the last item of each kind of list. Settings that are `None`
+/// follow the PHP version being parsed, so trailing commas are always allowed in arguments and
+/// arrays, and in parameter and closure `use` lists from PHP 8.0.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrailingCommas {
+    pub args: Option<bool>,
+    pub arrays: Option<bool>,
+    pub params: Option<bool>,
+}
+
+/// Returned as the current token once the parser has run past the end of its input.
+static EOF: Token = Token { kind: TokenKind::Eof, span: Span { start: 0, end: 0, line: 0, col: 0 } };
+
+pub struct Parser<'a> {
     config: ParserConfig,
-    pub current: Token,
-    pub peek: Token,
-    iter: IntoIter<Token>,
-    comments: Vec<Token>,
+    pub current: &'a Token,
+    pub peek: &'a Token,
+    previous: Span,
+    tokens: &'a [Token],
+    /// The index of the token after `peek`.
+    cursor: usize,
+    comments: Vec<&'a Token>,
+    attributes: Vec<AttributeGroup>,
+    doc_comment: Option<String>,
+    depth: usize,
+    tolerant: bool,
+    errors: Vec<ParseError>,
+    warnings: Vec<ParseWarning>,
+    recovered_at: Option<usize>,
 }
 
 #[allow(dead_code)]
-impl Parser {
+impl<'a> Parser<'a> {
     pub fn new(config: Option<ParserConfig>) -> Self {
         Self {
             config: config.unwrap_or_default(),
-            current: Token::default(),
-            peek: Token::default(),
-            iter: vec![].into_iter(),
+            current: &EOF,
+            peek: &EOF,
+            previous: Span::default(),
+            tokens: &[],
+            cursor: 0,
             comments: vec![],
+            attributes: vec![],
+            doc_comment: None,
+            depth: 0,
+            tolerant: false,
+            errors: vec![],
+            warnings: vec![],
+            recovered_at: None,
+        }
+    }
+
+    pub fn parse(&mut self, tokens: &'a [Token]) -> Result<Program, ParseError> {
+        self.iter_statements(tokens).collect()
+    }
+
+    /// Parse the tokens as a single expression rather than a full program. A leading open tag and a
+    /// trailing semicolon are allowed, but anything else after the expression is an error.
+    pub fn parse_expression(&mut self, tokens: &'a [Token]) -> Result<Expression, ParseError> {
+        self.start(tokens);
+
+        if let TokenKind::OpenTag(_) = self.current.kind {
+            self.next();
+        }
+
+        self.skip_comments();
+
+        let expression = self.expression(0)?;
+
+        self.skip_comments();
+
+        if self.current.kind == TokenKind::SemiColon {
+            self.next();
+            self.skip_comments();
+        }
+
+        if ! self.is_eof() {
+            return Err(self.expected(vec![TokenKind::Eof]));
+        }
+
+        Ok(expression)
+    }
+
+    /// Lex and parse a snippet of PHP code, such as `1 + $a`, as a single expression. The source
+    /// doesn't need an open tag, and spans are relative to the start of the snippet.
+    pub fn parse_expr_str(&self, source: &str) -> Result<Expression, Error> {
+        const OPEN_TAG: &str = "<?php ";
+
+        let mut tokens = Lexer::new(None).tokenize(&format!("{}{}", OPEN_TAG, source))?;
+
+        for token in &mut tokens {
+            if token.span.line == 1 {
+                token.span.col = token.span.col.saturating_sub(OPEN_TAG.len());
+            }
+
+            token.span.start = token.span.start.saturating_sub(OPEN_TAG.len());
+            token.span.end = token.span.end.saturating_sub(OPEN_TAG.len());
+        }
+
+        Ok(self.fresh().parse_expression(&tokens)?)
+    }
+
+    /// Read, lex and parse a file. Errors, including failing to read the file, are returned as
+    /// `Error::File`, and warnings as diagnostics, so that they're displayed with the path, line
+    /// and column.
+    pub fn parse_file(&self, path: impl AsRef<Path>) -> Result<ParsedFile, Error> {
+        let path = path.as_ref();
+
+        let (program, diagnostics) = std::fs::read(path)
+            .map_err(Error::from)
+            .and_then(|source| self.parse_source(&source))
+            .map_err(|error| error.in_file(path))?;
+
+        Ok(ParsedFile {
+            path: path.to_path_buf(),
+            program,
+            diagnostics: diagnostics.into_iter().map(|diagnostic| diagnostic.in_file(path)).collect(),
+        })
+    }
+
+    /// Lex and parse source that doesn't have to be UTF-8 after `__halt_compiler();`, such as a
+    /// phar. The data after it is kept byte for byte.
+    pub fn parse_bytes(&self, source: &[u8]) -> Result<Program, Error> {
+        self.parse_source(source).map(|(program, _)| program)
+    }
+
+    /// Like `parse_bytes`, along with the lexer's and parser's warnings.
+    fn parse_source(&self, source: &[u8]) -> Result<(Program, Vec<Diagnostic>), Error> {
+        let parse = |source: &str| -> Result<(Program, Vec<Diagnostic>), Error> {
+            let mut lexer = Lexer::new(None);
+            let tokens = lexer.tokenize(source)?;
+            let mut parser = self.fresh();
+            let program = parser.parse(&tokens)?;
+
+            let warnings = lexer.warnings().iter().map(Diagnostic::from).chain(parser.warnings().iter().map(Diagnostic::from)).collect();
+
+            Ok((program, warnings))
+        };
+
+        let error = match std::str::from_utf8(source) {
+            Ok(source) => return parse(source),
+            Err(error) => error,
+        };
+
+        // Everything before the first invalid byte has to parse, and end in `__halt_compiler();`.
+        let invalid = || Error::from(std::io::Error::new(std::io::ErrorKind::InvalidData, error));
+        let valid = std::str::from_utf8(&source[..error.valid_up_to()]).expect("source is valid up to the error");
+        let (mut program, warnings) = parse(valid).map_err(|_| invalid())?;
+
+        match program.last_mut() {
+            Some(Statement { kind: StatementKind::HaltCompiler { offset, data }, .. }) => {
+                *data = source[*offset..].to_vec();
+
+                Ok((program, warnings))
+            },
+            _ => Err(invalid()),
         }
     }
 
-    pub fn parse(&mut self, tokens: Vec<Token>) -> Result<Program, ParseError> {
-        self.iter = tokens.into_iter();
+    /// Start parsing a new list of tokens, forgetting any warnings about the previous one.
+    fn start(&mut self, tokens: &'a [Token]) {
+        self.tokens = tokens;
+        self.cursor = 0;
+        self.warnings.clear();
+
         self.next();
         self.next();
+    }
+
+    /// A new parser with the same configuration, for tokens that only live as long as the call that
+    /// lexed them.
+    fn fresh<'b>(&self) -> Parser<'b> {
+        Parser::new(Some(self.config.clone()))
+    }
+
+    /// Parse the tokens without stopping at the first error. When a statement fails to parse, the
+    /// error is recorded and parsing resumes after the next `;` or `}`, so that every error in the
+    /// source can be reported at once.
+    pub fn parse_with_recovery(&mut self, tokens: &'a [Token]) -> (Program, Vec<ParseError>) {
+        self.start(tokens);
 
         let mut ast = Program::new();
 
@@ -80,12 +305,111 @@ impl Parser {
                 break;
             }
 
-            ast.push(self.statement()?);
-            
+            let start = self.current.span;
+
+            match self.statement() {
+                Ok(statement) => ast.push(statement),
+                Err(error) => {
+                    self.errors.push(error);
+
+                    self.synchronize();
+
+                    if self.tolerant {
+                        ast.push(Statement::new(StatementKind::Error, self.span_from(start)));
+                    }
+                },
+            }
+
             self.clear_comments();
         }
 
-        Ok(ast.to_vec())
+        (ast, std::mem::take(&mut self.errors))
+    }
+
+    /// Parse the tokens like `parse_with_recovery`, but leave a `StatementKind::Error` or
+    /// `ExpressionKind::Error` in the AST wherever recovery happened. Statements inside blocks and
+    /// class bodies are recovered individually, and a missing operand becomes an error expression,
+    /// so that the rest of a half-written file keeps its structure.
+    pub fn parse_tolerant(&mut self, tokens: &'a [Token]) -> (Program, Vec<ParseError>) {
+        self.tolerant = true;
+        self.recovered_at = None;
+        let result = self.parse_with_recovery(tokens);
+        self.tolerant = false;
+
+        result
+    }
+
+    /// Parse a statement in a block that ends with `until`, and add it to `block`. In tolerant mode a
+    /// statement that fails to parse is replaced by an error statement, and parsing resumes after it.
+    fn tolerant_statement(&mut self, block: &mut Block, parse: fn(&mut Self) -> ParseResult<Statement>, until: &TokenKind) -> ParseResult<()> {
+        let start = self.current.span;
+
+        let statement = match parse(self) {
+            Ok(statement) => statement,
+            Err(error) if self.tolerant => {
+                self.errors.push(error);
+
+                // Always make progress, even if the statement failed on its very first token.
+                if self.current.span == start {
+                    self.next();
+                }
+
+                self.synchronize_block(until);
+
+                Statement::new(StatementKind::Error, self.span_from(start))
+            },
+            Err(error) => return Err(error),
+        };
+
+        block.push(statement);
+
+        Ok(())
+    }
+
+    /// Skip to the end of the current statement inside a block, leaving the block's closing token
+    /// for the caller. Braces opened along the way are skipped along with their contents.
+    fn synchronize_block(&mut self, until: &TokenKind) {
+        self.attributes.clear();
+        self.doc_comment = None;
+
+        let mut depth = 0usize;
+
+        while ! self.is_eof() {
+            match self.current.kind {
+                TokenKind::LeftBrace => depth += 1,
+                TokenKind::RightBrace if depth > 0 => {
+                    depth -= 1;
+
+                    if depth == 0 {
+                        self.next();
+                        break;
+                    }
+                },
+                TokenKind::SemiColon if depth == 0 => {
+                    self.next();
+                    break;
+                },
+                ref kind if depth == 0 && (kind == until || kind == &TokenKind::RightBrace) => break,
+                _ => {},
+            }
+
+            self.next();
+        }
+    }
+
+    /// Skip to the end of the current statement after an error.
+    fn synchronize(&mut self) {
+        self.attributes.clear();
+
+        while ! self.is_eof() {
+            let kind = &self.current.kind;
+
+            self.next();
+
+            if matches!(kind, TokenKind::SemiColon | TokenKind::RightBrace) {
+                break;
+            }
+        }
     }
 
     fn type_string(&mut self) -> ParseResult<Type> {
@@ -95,998 +419,962 @@ impl Parser {
             return Ok(Type::Nullable(t));
         }
 
-        let id = self.type_with_static()?;
+        let ty = self.union_member_type()?;
 
         if self.current.kind == TokenKind::Pipe {
-            self.next();
+            let mut types = vec![ty];
 
-            let mut types = vec![id];
-
-            while ! self.is_eof() {
-                let id = self.type_with_static()?;
-                types.push(id);
+            while self.current.kind == TokenKind::Pipe {
+                self.next();
 
-                if self.current.kind != TokenKind::Pipe {
-                    break;
-                }
+                types.push(self.union_member_type()?);
             }
 
             return Ok(Type::Union(types))
         }
 
-        if self.current.kind == TokenKind::Ampersand {
-            self.next();
+        // A parenthesised intersection is only allowed as part of a union.
+        if matches!(ty, Type::Intersection(_)) {
+            return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span));
+        }
 
-            let mut types = vec![id];
+        if self.is_intersection_ampersand() {
+            let mut types = vec![ty];
 
-            while ! self.is_eof() {
-                let id = self.type_with_static()?;
-                types.push(id);
+            while self.is_intersection_ampersand() {
+                self.next();
 
-                if self.current.kind != TokenKind::Ampersand {
-                    break;
-                }
+                types.push(Type::Plain(self.type_with_static()?));
             }
 
             return Ok(Type::Intersection(types))
         }
 
-        Ok(Type::Plain(id))
+        Ok(ty)
     }
 
-    fn statement(&mut self) -> ParseResult<Statement> {
-        self.skip_comments();
-        
-        let statement = match &self.current.kind {
-            TokenKind::Static if matches!(self.peek.kind, TokenKind::Variable(_)) => {
-                self.next();
+    /// Parse a single member of a union type, which is either a plain type or a
+    /// parenthesised intersection such as `(A&B)`.
+    fn union_member_type(&mut self) -> ParseResult<Type> {
+        if self.current.kind != TokenKind::LeftParen {
+            return Ok(Type::Plain(self.type_with_static()?));
+        }
 
-                let mut vars = vec![];
+        self.lparen()?;
 
-                while self.current.kind != TokenKind::SemiColon {
-                    let var = Expression::Variable { name: self.var()? };
-                    let mut default = None;
-                    
-                    if self.current.kind == TokenKind::Equals {
-                        expect!(self, TokenKind::Equals, "expected =");
-                        default = Some(self.expression(0)?);
-                    }
+        let mut types = vec![Type::Plain(self.type_with_static()?)];
 
-                    self.optional_comma()?;
+        while self.current.kind == TokenKind::Ampersand {
+            self.next();
 
-                    vars.push(StaticVar { var, default })
-                }
+            types.push(Type::Plain(self.type_with_static()?));
+        }
 
-                self.semi()?;
-                
-                Statement::Static { vars }
-            },
-            TokenKind::InlineHtml(html) => {
-                let s = Statement::InlineHtml(html.to_string());
-                self.next();
-                s
-            },
-            TokenKind::Comment(comment) => {
-                let s = Statement::Comment { comment: comment.to_string() };
-                self.next();
-                s
-            },
-            TokenKind::While => {
-                self.next();
-                self.lparen()?;
+        self.rparen()?;
 
-                let condition = self.expression(0)?;
+        Ok(Type::Intersection(types))
+    }
 
-                self.rparen()?;
-                self.lbrace()?;
+    /// An `&` directly in front of a variable marks a by-reference parameter
+    /// rather than another intersection member.
+    fn is_intersection_ampersand(&self) -> bool {
+        self.current.kind == TokenKind::Ampersand && ! matches!(self.peek.kind, TokenKind::Variable(_) | TokenKind::Ellipsis)
+    }
 
-                let body = self.block(&TokenKind::RightBrace)?;
-                
-                self.rbrace()?;
+    fn statement(&mut self) -> ParseResult<Statement> {
+        self.nested(Self::unguarded_statement)
+    }
 
-                Statement::While { condition, body }
-            },
-            TokenKind::Require => {
-                self.next();
+    fn unguarded_statement(&mut self) -> ParseResult<Statement> {
+        if let Some(comment) = self.comment() {
+            return Ok(comment);
+        }
 
-                let path = self.expression(0)?;
+        self.skip_comments();
 
-                self.semi()?;
+        let start = self.current.span;
+
+        // Choosing the function that parses the statement, rather than calling it from each arm,
+        // keeps this frame small in unoptimised builds, where every call's result would otherwise
+        // get stack space of its own. This function is part of every level of nesting.
+        let parse: fn(&mut Self) -> ParseResult<StatementKind> = match &self.current.kind {
+            TokenKind::Attribute => Self::attributed_statement,
+            TokenKind::Const => Self::constants,
+            TokenKind::Static if matches!(self.peek.kind, TokenKind::Variable(_)) => Self::static_vars,
+            TokenKind::Global => Self::global,
+            TokenKind::InlineHtml(_) => Self::inline_html,
+            TokenKind::CloseTag => Self::close_tag,
+            TokenKind::Comment(_) => Self::comment_statement,
+            TokenKind::While => Self::while_statement,
+            TokenKind::HaltCompiler => Self::halt_compiler,
+            TokenKind::For => Self::for_statement,
+            TokenKind::Foreach => Self::foreach_statement,
+            TokenKind::Abstract => |parser| {
+                parser.next();
+
+                parser.class(Some(ClassFlag::Abstract))
+            },
+            TokenKind::Final => |parser| {
+                parser.next();
 
-                Statement::Require { path }
+                parser.class(Some(ClassFlag::Final))
             },
-            TokenKind::RequireOnce => {
-                self.next();
+            TokenKind::Trait => Self::trait_declaration,
+            TokenKind::Interface => Self::interface_declaration,
+            TokenKind::Enum if matches!(self.peek.kind, TokenKind::Identifier(_)) => Self::enum_declaration,
+            TokenKind::Use => Self::use_statement,
+            TokenKind::Switch => Self::switch_statement,
+            TokenKind::Namespace => Self::namespace,
+            TokenKind::If => Self::if_statement,
+            TokenKind::Class => |parser| parser.class(None),
+            TokenKind::Echo => Self::echo,
+            TokenKind::Continue | TokenKind::Break => Self::jump,
+            TokenKind::Return => Self::return_statement,
+            TokenKind::Function if matches!(self.peek.kind, TokenKind::Identifier(_)) => |parser| parser.function(None),
+            // `function &foo()` declares a function, but `function &()` is a closure.
+            TokenKind::Function if self.peek.kind == TokenKind::Ampersand && matches!(self.tokens.get(self.cursor), Some(Token { kind: TokenKind::Identifier(_), .. })) => |parser| parser.function(None),
+            TokenKind::SemiColon => |parser| {
+                parser.next();
+
+                Ok(StatementKind::Noop)
+            },
+            TokenKind::Try => Self::try_statement,
+            _ => Self::expression_statement,
+        };
 
-                let path = self.expression(0)?;
+        let kind = parse(self)?;
 
-                self.semi()?;
+        let span = self.span_from(start);
 
-                Statement::RequireOnce { path }
-            },
-            TokenKind::For => {
-                self.next();
+        if ! self.config.comments {
+            self.skip_comments();
+        }
 
-                self.lparen()?;
+        Ok(Statement::new(kind, span))
+    }
 
-                let mut init = None;
-                if self.current.kind != TokenKind::SemiColon {
-                    init = Some(self.expression(0)?);
-                }
-                self.semi()?;
+    fn attributed_statement(&mut self) -> ParseResult<StatementKind> {
+        self.gather_attributes_for(AttributeTarget::Statement)?;
 
-                let mut condition = None;
-                if self.current.kind != TokenKind::SemiColon {
-                    condition = Some(self.expression(0)?);
-                }
-                self.semi()?;
+        Ok(self.statement()?.kind)
+    }
 
-                let mut r#loop = None;
-                if self.current.kind != TokenKind::RightParen {
-                    r#loop = Some(self.expression(0)?);
-                }
+    fn inline_html(&mut self) -> ParseResult<StatementKind> {
+        let html = expect!(self, TokenKind::InlineHtml(html), html.to_string(), [TokenKind::InlineHtml(String::new())]);
 
-                self.rparen()?;
-                self.lbrace()?;
+        Ok(StatementKind::InlineHtml(html))
+    }
 
-                let then = self.block(&TokenKind::RightBrace)?;
+    /// A close tag acts as a statement terminator, so we can treat the inline HTML between it and
+    /// the next open tag as a regular statement. This is what makes the alternative control
+    /// structure syntax usable in templates.
+    fn close_tag(&mut self) -> ParseResult<StatementKind> {
+        self.next();
 
-                self.rbrace()?;
+        let s = match &self.current.kind {
+            TokenKind::InlineHtml(_) => self.inline_html()?,
+            _ => StatementKind::Noop,
+        };
 
-                Statement::For { init, condition, r#loop, then }
-            },
-            TokenKind::Foreach => {
-                self.next();
+        if let TokenKind::OpenTag(_) = self.current.kind {
+            self.next();
+        }
 
-                self.lparen()?;
+        Ok(s)
+    }
 
-                let expr = self.expression(0)?;
+    fn comment_statement(&mut self) -> ParseResult<StatementKind> {
+        let s = StatementKind::Comment { comment: self.current.kind.to_string() };
+        self.next();
+        Ok(s)
+    }
 
-                expect!(self, TokenKind::As, "expected 'as'");
+    /// Parse a `global` declaration of one or more variables.
+    fn global(&mut self) -> ParseResult<StatementKind> {
+        self.next();
 
-                let mut by_ref = self.current.kind == TokenKind::Ampersand;
-                if by_ref {
-                    self.next();
-                }
+        let mut vars = vec![self.variable()?];
 
-                let mut key_var = None;
-                let mut value_var = self.expression(0)?;
+        while self.current.kind == TokenKind::Comma {
+            self.next();
+            vars.push(self.variable()?);
+        }
 
-                if self.current.kind == TokenKind::DoubleArrow {
-                    self.next();
+        self.semi()?;
 
-                    key_var = Some(value_var.clone());
-                    
-                    by_ref = self.current.kind == TokenKind::Ampersand;
-                    if by_ref {
-                        self.next();
-                    }
+        Ok(StatementKind::Global { vars })
+    }
 
-                    value_var = self.expression(0)?;
-                }
+    fn echo(&mut self) -> ParseResult<StatementKind> {
+        self.next();
 
-                self.rparen()?;
-                self.lbrace()?;
+        // Unlike most lists, `echo` can't be empty or end with a comma.
+        let mut values = vec![self.expression(0)?];
+        while self.current.kind == TokenKind::Comma {
+            self.next();
+            values.push(self.expression(0)?);
+        }
+        self.semi()?;
+        Ok(StatementKind::Echo { values })
+    }
 
-                let body = self.block(&TokenKind::RightBrace)?;
+    /// Parse `continue` or `break`, with the optional number of loops to jump out of.
+    fn jump(&mut self) -> ParseResult<StatementKind> {
+        let kind = self.current.kind.clone();
 
-                self.rbrace()?;
+        self.next();
 
-                Statement::Foreach { expr, by_ref, key_var, value_var, body }
-            },
-            TokenKind::Abstract => {
-                self.next();  
+        let mut num = None;
+        if self.current.kind != TokenKind::SemiColon {
+            num = Some(self.expression(0)?);
+        }
 
-                match self.class()? {
-                    Statement::Class { name, extends, implements, body, .. } => {
-                        Statement::Class { name, extends, implements, body, flag: Some(ClassFlag::Abstract) }
-                    },
-                    _ => unreachable!(),
-                }
-            },
-            TokenKind::Final => {
-                self.next();  
+        self.semi()?;
 
-                match self.class()? {
-                    Statement::Class { name, extends, implements, body, .. } => {
-                        Statement::Class { name, extends, implements, body, flag: Some(ClassFlag::Final) }
-                    },
-                    _ => unreachable!(),
-                }
-            },
-            TokenKind::Trait => {
-                self.next();
+        Ok(match kind {
+            TokenKind::Continue => StatementKind::Continue { num },
+            _ => StatementKind::Break { num },
+        })
+    }
 
-                let name = self.ident()?;
+    fn return_statement(&mut self) -> ParseResult<StatementKind> {
+        self.next();
 
-                self.lbrace()?;
+        if let Token { kind: TokenKind::SemiColon, .. } = self.current {
+            let ret = StatementKind::Return { value: None };
+            self.semi()?;
+            Ok(ret)
+        } else {
+            let ret = StatementKind::Return { value: self.expression(0).ok() };
+            self.semi()?;
+            Ok(ret)
+        }
+    }
 
-                let mut body = Block::new();
-                while self.current.kind != TokenKind::RightBrace {
-                    match self.class_statement()? {
-                        Statement::Constant { .. } => {
-                            return Err(ParseError::TraitCannotContainConstant(self.current.span))
-                        },
-                        s => {
-                            body.push(s);
-                        },
-                    }
-                }
+    fn expression_statement(&mut self) -> ParseResult<StatementKind> {
+        let expr = self.expression(0)?;
 
-                self.rbrace()?;
+        self.semi()?;
 
-                Statement::Trait { name: name.into(), body }
-            },
-            TokenKind::Interface => {
-                self.next();
+        Ok(StatementKind::Expression { expr })
+    }
 
-                let name = self.ident()?;
+    /// Parse a top-level `const` declaration, starting at the `const` keyword.
+    fn constants(&mut self) -> ParseResult<StatementKind> {
+        let doc_comment = self.doc_comment.take();
 
-                let mut extends = vec![];
-                if self.current.kind == TokenKind::Extends {
-                    self.next();
+        self.next();
 
-                    while self.current.kind != TokenKind::LeftBrace {
-                        self.optional_comma()?;
+        let mut constants = vec![];
 
-                        let e = self.full_name()?;
+        while self.current.kind != TokenKind::SemiColon {
+            let name = self.identifier()?;
 
-                        extends.push(e.into());
-                    }
-                }
+            expect!(self, TokenKind::Equals);
 
-                self.lbrace()?;
+            let value = self.expression(0)?;
 
-                let mut body = Block::new();
-                self.skip_comments();
-                while self.current.kind != TokenKind::RightBrace {
-                    match self.current.kind {
-                        TokenKind::Public => {
-                            self.next();
+            self.optional_comma()?;
 
-                            self.next();
+            constants.push(Constant { name, value });
+        }
 
-                            let name = self.ident()?;
+        self.semi()?;
 
-                            self.lparen()?;
+        Ok(StatementKind::Const { constants, doc_comment })
+    }
 
-                            let params = self.param_list()?;
+    /// Parse `static` variable declarations inside a function.
+    fn static_vars(&mut self) -> ParseResult<StatementKind> {
+        self.next();
 
-                            self.rparen()?;
+        let mut vars = vec![];
 
-                            let mut return_type = None;
+        while self.current.kind != TokenKind::SemiColon {
+            let var = self.variable()?;
+            let mut default = None;
+            
+            if self.current.kind == TokenKind::Equals {
+                expect!(self, TokenKind::Equals);
+                default = Some(self.expression(0)?);
+            }
 
-                            if self.current.kind == TokenKind::Colon || self.config.force_type_strings {
-                                expect!(self, TokenKind::Colon, "expected :");
+            self.optional_comma()?;
 
-                                return_type = Some(self.type_string()?);
-                            }
+            vars.push(StaticVar { var, default })
+        }
 
-                            self.semi()?;
+        self.semi()?;
+        
+        Ok(StatementKind::Static { vars })
+    }
 
-                            body.push(Statement::Method { name: name.into(), params, body: vec![], return_type, flags: vec![MethodFlag::Public] })
-                        },
-                        TokenKind::Function => {
-                            self.next();
+    /// Parse a `while` loop, in either its braced or its alternative syntax.
+    fn while_statement(&mut self) -> ParseResult<StatementKind> {
+        self.next();
+        self.lparen()?;
 
-                            let name = self.ident()?;
+        let condition = self.expression(0)?;
 
-                            self.lparen()?;
+        self.rparen()?;
 
-                            let params = self.param_list()?;
+        let body = if self.current.kind == TokenKind::Colon {
+            self.next();
 
-                            self.rparen()?;
+            let body = self.block(&TokenKind::EndWhile)?;
 
-                            let mut return_type = None;
+            expect!(self, TokenKind::EndWhile);
+            self.semi()?;
 
-                            if self.current.kind == TokenKind::Colon || self.config.force_type_strings {
-                                expect!(self, TokenKind::Colon, "expected :");
+            body
+        } else {
+            self.body()?
+        };
 
-                                return_type = Some(self.type_string()?);
-                            }
+        Ok(StatementKind::While { condition, body })
+    }
 
-                            self.semi()?;
+    /// Parse `__halt_compiler();` along with the data after it.
+    fn halt_compiler(&mut self) -> ParseResult<StatementKind> {
+        self.next();
 
-                            body.push(Statement::Method { name: name.into(), params, body: vec![], return_type, flags: vec![] })
-                        },
-                        _ => return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span)),
-                    };
+        self.lparen()?;
+        self.rparen()?;
 
-                    self.skip_comments();
-                }
-
-                self.rbrace()?;
+        if self.current.kind == TokenKind::CloseTag {
+            self.next();
+        } else {
+            self.semi()?;
+        }
 
-                Statement::Interface { name: name.into(), extends, body }
-            },
-            TokenKind::Enum if matches!(self.peek.kind, TokenKind::Identifier(_)) => {
-                self.next();
+        // The lexer stops at this point, so this is always the last statement in the program.
+        let (offset, data) = expect!(self, TokenKind::HaltCompilerData(offset, data), (*offset, data.as_bytes().to_vec()), [TokenKind::HaltCompilerData(0, String::new())]);
 
-                let name = self.ident()?;
+        Ok(StatementKind::HaltCompiler { offset, data })
+    }
 
-                let mut is_backed = false;
-                let backed_type: Option<BackedEnumType> = if self.current.kind == TokenKind::Colon {
-                    expect!(self, TokenKind::Colon, "expected :");
-                    
-                    match self.current.kind.clone() {
-                        TokenKind::Identifier(s) if s == *"string" || s == *"int" => {
-                            self.next();
+    /// Parse a `for` loop, in either its braced or its alternative syntax.
+    fn for_statement(&mut self) -> ParseResult<StatementKind> {
+        self.next();
 
-                            is_backed = true;
+        self.lparen()?;
 
-                            Some(match s.as_str() {
-                                "string" => BackedEnumType::String,
-                                "int" => BackedEnumType::Int,
-                                _ => unreachable!()
-                            })
-                        },
-                        _ => return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span))
-                    }
-                } else {
-                    None
-                };
+        let mut init = None;
+        if self.current.kind != TokenKind::SemiColon {
+            init = Some(self.expression(0)?);
+        }
+        self.semi()?;
 
-                let mut implements = Vec::new();
-                if self.current.kind == TokenKind::Implements {
-                    self.next();
+        let mut condition = None;
+        if self.current.kind != TokenKind::SemiColon {
+            condition = Some(self.expression(0)?);
+        }
+        self.semi()?;
 
-                    while self.current.kind != TokenKind::LeftBrace {
-                        implements.push(self.full_name()?.into());
+        let mut r#loop = None;
+        if self.current.kind != TokenKind::RightParen {
+            r#loop = Some(self.expression(0)?);
+        }
 
-                        self.optional_comma()?;
-                    }
-                }
-                
-                self.lbrace()?;
+        self.rparen()?;
 
-                let mut body = Block::new();
-                while self.current.kind != TokenKind::RightBrace {
-                    match self.current.kind {
-                        TokenKind::Case => {
-                            self.next();
+        let then = if self.current.kind == TokenKind::Colon {
+            self.next();
 
-                            let name = self.ident()?;
-                            let mut value = None;
+            let then = self.block(&TokenKind::EndFor)?;
 
-                            if is_backed {
-                                expect!(self, TokenKind::Equals, "expected =");
+            expect!(self, TokenKind::EndFor);
+            self.semi()?;
 
-                                value = Some(self.expression(0)?);
-                            }
+            then
+        } else {
+            self.body()?
+        };
 
-                            self.semi()?;
-                            
-                            body.push(Statement::EnumCase { name: name.into(), value })
-                        },
-                        _ => {
-                            body.push(self.class_statement()?);
-                        }
-                    }
-                }
+        Ok(StatementKind::For { init, condition, r#loop, then })
+    }
 
-                self.rbrace()?;
+    /// Parse a `foreach` loop, in either its braced or its alternative syntax.
+    fn foreach_statement(&mut self) -> ParseResult<StatementKind> {
+        self.next();
 
-                Statement::Enum { name: name.into(), backed_type, implements, body }
-            },
-            TokenKind::Use => {
-                self.next();
+        self.lparen()?;
 
-                let kind = match self.current.kind {
-                    TokenKind::Function => {
-                        self.next();
-                        UseKind::Function
-                    },
-                    TokenKind::Const => {
-                        self.next();
-                        UseKind::Const
-                    },
-                    _ => UseKind::Normal,
-                };
+        let expr = self.expression(0)?;
 
-                let mut uses = Vec::new();
-                while ! self.is_eof() {
-                    let name = self.full_name()?;
-                    let mut alias = None;
+        expect!(self, TokenKind::As);
 
-                    if self.current.kind == TokenKind::As {
-                        self.next();
-                        alias = Some(self.ident()?.into());
-                    }
+        let mut by_ref = self.current.kind == TokenKind::Ampersand;
+        if by_ref {
+            self.next();
+        }
 
-                    uses.push(Use { name: name.into(), alias });
+        let mut key_var = None;
+        let mut value_var = self.expression(0)?;
 
-                    if self.current.kind == TokenKind::Comma {
-                        self.next();
-                        continue;
-                    }
+        if self.current.kind == TokenKind::DoubleArrow {
+            // Keys can never be taken by reference.
+            if by_ref {
+                return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span));
+            }
 
-                    self.semi()?;
-                    break;
-                }
+            self.next();
 
-                Statement::Use { uses, kind }
-            },
-            TokenKind::Switch => {
+            key_var = Some(value_var.clone());
+            
+            by_ref = self.current.kind == TokenKind::Ampersand;
+            if by_ref {
                 self.next();
+            }
 
-                self.lparen()?;
+            value_var = self.expression(0)?;
+        }
 
-                let condition = self.expression(0)?;
+        self.rparen()?;
 
-                self.rparen()?;
+        let body = if self.current.kind == TokenKind::Colon {
+            self.next();
 
-                self.lbrace()?;
+            let body = self.block(&TokenKind::EndForeach)?;
 
-                let mut cases = Vec::new();
-                loop {
-                    if self.current.kind == TokenKind::RightBrace {
-                        break;
-                    }
+            expect!(self, TokenKind::EndForeach);
+            self.semi()?;
 
-                    match self.current.kind {
-                        TokenKind::Case => {
-                            self.next();
+            body
+        } else {
+            self.body()?
+        };
 
-                            let condition = self.expression(0)?;
+        Ok(StatementKind::Foreach { expr, by_ref, key_var, value_var, body })
+    }
 
-                            expect!(self, TokenKind::Colon, "expected :");
+    /// Parse a trait declaration, starting at the `trait` keyword.
+    fn trait_declaration(&mut self) -> ParseResult<StatementKind> {
+        let attributes = self.get_attributes();
 
-                            let mut body = Block::new();
+        self.next();
 
-                            while self.current.kind != TokenKind::Case && self.current.kind != TokenKind::Default && self.current.kind != TokenKind::RightBrace {
-                                body.push(self.statement()?);
-                            }
+        let name = self.identifier()?;
 
-                            cases.push(Case {
-                                condition: Some(condition),
-                                body
-                            });
-                        },
-                        TokenKind::Default => {
-                            self.next();
+        self.lbrace()?;
 
-                            expect!(self, TokenKind::Colon, "expected :");
+        let mut body = Block::new();
+        while self.current.kind != TokenKind::RightBrace {
+            match self.class_statement()? {
+                // Traits can only declare constants since PHP 8.3.
+                Statement { kind: StatementKind::Constant { .. }, .. } if self.config.php_version < PhpVersion::Php83 => {
+                    return Err(ParseError::TraitCannotContainConstant(self.current.span))
+                },
+                s => {
+                    body.push(s);
+                },
+            }
+        }
 
-                            let mut body = Block::new();
+        self.rbrace()?;
 
-                            while self.current.kind != TokenKind::Case && self.current.kind != TokenKind::Default && self.current.kind != TokenKind::RightBrace {
-                                body.push(self.statement()?);
-                            }
+        Ok(StatementKind::Trait { name, body, attributes })
+    }
 
-                            cases.push(Case {
-                                condition: None,
-                                body
-                            });
-                        },
-                        _ => return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span))
-                    }
-                }
+    /// Parse an interface declaration, starting at the `interface` keyword.
+    fn interface_declaration(&mut self) -> ParseResult<StatementKind> {
+        let attributes = self.get_attributes();
 
-                self.rbrace()?;
+        self.next();
 
-                Statement::Switch { condition, cases }
-            },
-            TokenKind::Namespace => {
-                self.next();
-                
-                let name = self.name()?;
+        let name = self.identifier()?;
 
-                let mut braced = false;
-                if self.current.kind == TokenKind::LeftBrace {
-                    braced = true;
-                    self.next();
-                } else {
-                    self.semi()?;
-                }
+        let mut extends = vec![];
+        if self.current.kind == TokenKind::Extends {
+            self.next();
 
-                let body = if braced {
-                    self.block(&TokenKind::RightBrace)?
-                } else {
-                    Block::new()
-                };
+            while self.current.kind != TokenKind::LeftBrace {
+                self.optional_comma()?;
 
-                if braced {
-                    self.rbrace()?;
-                }
+                extends.push(self.full_identifier()?);
+            }
+        }
 
-                Statement::Namespace { name, body }
-            },
-            TokenKind::If => {
-                self.next();
+        self.lbrace()?;
 
-                self.lparen()?;
+        let mut body = Block::new();
+        while self.current.kind != TokenKind::RightBrace {
+            body.push(self.interface_statement()?);
+        }
+
+        self.rbrace()?;
+
+        Ok(StatementKind::Interface { name, extends, body, attributes })
+    }
 
-                let condition = self.expression(0)?;
+    /// Parse an enum declaration, starting at the `enum` keyword.
+    fn enum_declaration(&mut self) -> ParseResult<StatementKind> {
+        self.requires(PhpVersion::Php81, "enums")?;
 
-                self.rparen()?;
+        let attributes = self.get_attributes();
+
+        self.next();
+
+        let name = self.identifier()?;
+
+        let backed_type: Option<BackedEnumType> = if self.current.kind == TokenKind::Colon {
+            expect!(self, TokenKind::Colon);
+            
+            match &self.current.kind {
+                TokenKind::Identifier(s) if s == "string" => {
+                    self.next();
 
-                let body_end_token = if self.current.kind == TokenKind::LeftBrace {
+                    Some(BackedEnumType::String)
+                },
+                TokenKind::Identifier(s) if s == "int" => {
                     self.next();
 
-                    TokenKind::RightBrace
-                } else {
-                    TokenKind::SemiColon
-                };
+                    Some(BackedEnumType::Int)
+                },
+                _ => return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span))
+            }
+        } else {
+            None
+        };
 
-                let then = self.block(&body_end_token)?;
+        let mut implements = Vec::new();
+        if self.current.kind == TokenKind::Implements {
+            self.next();
 
-                if body_end_token == TokenKind::RightBrace {
-                    self.rbrace()?;
-                }
+            while self.current.kind != TokenKind::LeftBrace {
+                implements.push(self.full_identifier()?);
 
-                let mut else_ifs: Vec<ElseIf> = Vec::new();
-                loop {
-                    if self.current.kind == TokenKind::ElseIf {
-                        self.next();
+                self.optional_comma()?;
+            }
+        }
+        
+        self.lbrace()?;
+
+        let mut body = Block::new();
+        loop {
+            if let Some(comment) = self.comment() {
+                body.push(comment);
+                continue;
+            }
 
-                        self.lparen()?;
+            self.skip_comments();
 
-                        let condition = self.expression(0)?;
+            if self.current.kind == TokenKind::RightBrace {
+                break;
+            }
 
-                        self.rparen()?;
+            self.gather_attributes_for(AttributeTarget::Member)?;
 
-                        self.lbrace()?;
+            match self.current.kind {
+                TokenKind::Case => {
+                    let start = self.current.span;
+                    let attributes = self.get_attributes();
 
-                        let body = self.block(&TokenKind::RightBrace)?;
+                    self.next();
 
-                        self.rbrace()?;
+                    let name = self.identifier()?;
+                    let mut value = None;
 
-                        else_ifs.push(ElseIf { condition, body });
-                    } else {
-                        break;
+                    // Whether a case needs a value depends on the backing type, which is left to later passes to check.
+                    if self.current.kind == TokenKind::Equals {
+                        self.next();
+
+                        value = Some(self.expression(0)?);
                     }
-                }
 
-                if self.current.kind != TokenKind::Else {
-                    return Ok(Statement::If { condition, then, else_ifs, r#else: None });
+                    self.semi()?;
+                    
+                    body.push(Statement::new(StatementKind::EnumCase { name, value, attributes }, self.span_from(start)))
+                },
+                _ => {
+                    body.push(self.class_statement()?);
                 }
+            }
+        }
 
-                expect!(self, TokenKind::Else, "expected else");
-
-                self.lbrace()?;
+        self.rbrace()?;
 
-                let r#else = self.block(&TokenKind::RightBrace)?;
+        Ok(StatementKind::Enum { name, backed_type, implements, body, attributes })
+    }
 
-                self.rbrace()?;
+    /// Parse a `use` import, including group and function or constant imports.
+    fn use_statement(&mut self) -> ParseResult<StatementKind> {
+        self.next();
 
-                Statement::If { condition, then, else_ifs, r#else: Some(r#else) }
+        let kind = match self.current.kind {
+            TokenKind::Function => {
+                self.next();
+                UseKind::Function
             },
-            TokenKind::Class => self.class()?,
-            TokenKind::Echo => {
+            TokenKind::Const => {
                 self.next();
+                UseKind::Const
+            },
+            _ => UseKind::Normal,
+        };
 
-                let mut values = Vec::new();
-                while ! self.is_eof() && self.current.kind != TokenKind::SemiColon {
-                    values.push(self.expression(0)?);
+        let mut uses = Vec::new();
+        while ! self.is_eof() {
+            let start = self.current.span;
+            let name = Identifier { name: self.full_name()?, span: self.span_from(start) };
+            let mut alias = None;
 
-                    self.optional_comma()?;
-                }
-                self.semi()?;
-                Statement::Echo { values }
-            },
-            TokenKind::Continue => {
+            if self.current.kind == TokenKind::As {
                 self.next();
+                alias = Some(self.identifier()?);
+            }
 
-                let mut num = None;
-                if self.current.kind != TokenKind::SemiColon {
-                    num = Some(self.expression(0)?);
-                }
-
-                self.semi()?;
+            uses.push(Use { name, alias });
 
-                Statement::Continue { num }
-            },
-            TokenKind::Break => {
+            if self.current.kind == TokenKind::Comma {
                 self.next();
+                continue;
+            }
 
-                let mut num = None;
-                if self.current.kind != TokenKind::SemiColon {
-                    num = Some(self.expression(0)?);
-                }
+            self.semi()?;
+            break;
+        }
 
-                self.semi()?;
+        Ok(StatementKind::Use { uses, kind })
+    }
 
-                Statement::Break { num }
-            },
-            TokenKind::Return => {
-                self.next();
+    /// Parse a `switch` statement, in either its braced or its alternative syntax.
+    fn switch_statement(&mut self) -> ParseResult<StatementKind> {
+        self.next();
 
-                if let Token { kind: TokenKind::SemiColon, .. } = self.current {
-                    let ret = Statement::Return { value: None };
-                    self.semi()?;
-                    ret
-                } else {
-                    let ret = Statement::Return { value: self.expression(0).ok() };
-                    self.semi()?;
-                    ret
-                }
-            },
-            TokenKind::Function if matches!(self.peek.kind, TokenKind::Identifier(_)) => self.function()?,
-            TokenKind::SemiColon => {
-                self.next();
+        self.lparen()?;
 
-                Statement::Noop
-            },
-            TokenKind::Try => {
-                let start_span = self.current.span;
+        let condition = self.expression(0)?;
 
-                self.next();
-                self.lbrace()?;
+        self.rparen()?;
 
-                let body = self.block(&TokenKind::RightBrace)?;
+        let end_token = if self.current.kind == TokenKind::Colon {
+            self.next();
+            TokenKind::EndSwitch
+        } else {
+            self.lbrace()?;
+            TokenKind::RightBrace
+        };
 
-                self.rbrace()?;
+        let mut cases = Vec::new();
+        loop {
+            self.skip_comments();
 
-                let mut catches = Vec::new();
-                loop {
-                    if self.current.kind != TokenKind::Catch {
-                        break;
-                    }
+            if self.current.kind == end_token {
+                break;
+            }
 
+            match self.current.kind {
+                TokenKind::Case => {
                     self.next();
-                    self.lparen()?;
 
-                    let types = match self.type_string()? {
-                        Type::Plain(t) => vec![t.into()],
-                        Type::Union(ts) => ts.into_iter().map(|t| t.into()).collect::<Vec<Identifier>>(),
-                        _ => return Err(ParseError::InvalidCatchArgumentType(self.current.span)),
-                    };
+                    let condition = self.expression(0)?;
 
-                    let var = self.expression(0)?;
+                    expect!(self, TokenKind::Colon);
 
-                    self.rparen()?;
-                    self.lbrace()?;
-                    
-                    let body = self.block(&TokenKind::RightBrace)?;
-
-                    self.rbrace()?;
+                    let mut body = Block::new();
 
-                    catches.push(Catch {
-                        types,
-                        var,
-                        body,
-                    })
-                }
+                    while self.current.kind != TokenKind::Case && self.current.kind != TokenKind::Default && self.current.kind != end_token {
+                        body.push(self.statement()?);
+                    }
 
-                let mut finally = None;
-                if self.current.kind == TokenKind::Finally {
+                    cases.push(Case {
+                        condition: Some(condition),
+                        body
+                    });
+                },
+                TokenKind::Default => {
                     self.next();
-                    self.lbrace()?;
 
-                    finally = Some(self.block(&TokenKind::RightBrace)?);
+                    expect!(self, TokenKind::Colon);
 
-                    self.rbrace()?;
-                }
+                    let mut body = Block::new();
 
-                if catches.is_empty() && finally == None {
-                    return Err(ParseError::TryWithoutCatchOrFinally(start_span));
-                }
+                    while self.current.kind != TokenKind::Case && self.current.kind != TokenKind::Default && self.current.kind != end_token {
+                        body.push(self.statement()?);
+                    }
 
-                Statement::Try { body, catches, finally }
-            },
-            _ => {
-                let expr = self.expression(0)?;
+                    cases.push(Case {
+                        condition: None,
+                        body
+                    });
+                },
+                _ => return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span))
+            }
+        }
+
+        if end_token == TokenKind::EndSwitch {
+            expect!(self, TokenKind::EndSwitch);
+            self.semi()?;
+        } else {
+            self.rbrace()?;
+        }
 
-                self.semi()?;
+        Ok(StatementKind::Switch { condition, cases })
+    }
 
-                Statement::Expression { expr }
-            }
+    /// Parse a namespace declaration, with or without a braced body.
+    fn namespace(&mut self) -> ParseResult<StatementKind> {
+        self.next();
+        
+        let name = self.name()?;
+
+        let mut braced = false;
+        if self.current.kind == TokenKind::LeftBrace {
+            braced = true;
+            self.next();
+        } else {
+            self.semi()?;
+        }
+
+        let body = if braced {
+            self.block(&TokenKind::RightBrace)?
+        } else {
+            Block::new()
         };
 
-        self.skip_comments();
+        if braced {
+            self.rbrace()?;
+        }
 
-        Ok(statement)
+        Ok(StatementKind::Namespace { name, body })
     }
 
-    fn function(&mut self) -> ParseResult<Statement> {
+    /// Parse an `if` statement with its `elseif` and `else` branches, in either syntax.
+    fn if_statement(&mut self) -> ParseResult<StatementKind> {
         self.next();
 
-        let name = self.ident()?;
-
         self.lparen()?;
 
-        let params = self.param_list()?;
+        let condition = self.expression(0)?;
 
         self.rparen()?;
 
-        let mut return_type = None;
-
-        if self.current.kind == TokenKind::Colon || self.config.force_type_strings {
-            expect!(self, TokenKind::Colon, "expected :");
-
-            return_type = Some(self.type_string()?);
+        if self.current.kind == TokenKind::Colon {
+            return self.alternative_if(condition);
         }
 
-        self.lbrace()?;
+        let then = self.body()?;
+        let else_ifs = self.else_ifs()?;
 
-        let body = self.block(&TokenKind::RightBrace)?;
+        let mut r#else = None;
+        if self.current.kind == TokenKind::Else {
+            self.next();
 
-        self.rbrace()?;
+            r#else = Some(self.body()?);
+        }
 
-        Ok(Statement::Function { name: name.into(), params, body, return_type })
+        Ok(StatementKind::If { condition, then, else_ifs, r#else })
     }
 
-    fn class(&mut self) -> ParseResult<Statement> {
-        self.next();
+    fn else_ifs(&mut self) -> ParseResult<Vec<ElseIf>> {
+        let mut else_ifs: Vec<ElseIf> = Vec::new();
 
-        let name = self.ident()?;
-        let mut extends: Option<Identifier> = None;
+        // `else if` is treated the same as `elseif` so that the chain ends up flat.
+        while self.current.kind == TokenKind::ElseIf || (self.current.kind == TokenKind::Else && self.peek.kind == TokenKind::If) {
+            if self.current.kind == TokenKind::Else {
+                self.next();
+            }
 
-        if self.current.kind == TokenKind::Extends {
             self.next();
-            extends = Some(self.full_name()?.into());
-        }
 
-        let mut implements = Vec::new();
-        if self.current.kind == TokenKind::Implements {
-            self.next();
+            self.lparen()?;
 
-            while self.current.kind != TokenKind::LeftBrace {
-                self.optional_comma()?;
+            let condition = self.expression(0)?;
 
-                implements.push(self.full_name()?.into());
-            }
-        }
+            self.rparen()?;
 
-        self.lbrace()?;
+            let body = self.body()?;
 
-        let mut body = Vec::new();
-        self.gather_comments();
-        while self.current.kind != TokenKind::RightBrace && ! self.is_eof() {
-            body.push(self.class_statement()?);
+            else_ifs.push(ElseIf { condition, body });
         }
 
-        self.rbrace()?;
-
-        Ok(Statement::Class { name: name.into(), extends, implements, body, flag: None })
+        Ok(else_ifs)
     }
-    
-    fn class_statement(&mut self) -> ParseResult<Statement> {
-        self.gather_comments();
 
-        match self.current.kind {
-            TokenKind::Use => {
-                self.next();
+    /// Parse the rest of an `if` that uses the alternative `if (...): ... endif;` syntax.
+    fn alternative_if(&mut self, condition: Expression) -> ParseResult<StatementKind> {
+        self.next();
 
-                let mut traits = Vec::new();
+        let then = self.alternative_if_block()?;
 
-                while self.current.kind != TokenKind::SemiColon {
-                    self.optional_comma()?;
+        let mut else_ifs: Vec<ElseIf> = Vec::new();
+        while self.current.kind == TokenKind::ElseIf {
+            self.next();
 
-                    let t = self.full_name()?;
-                    traits.push(t.into());
-                }
+            self.lparen()?;
 
-                self.semi()?;
+            let condition = self.expression(0)?;
 
-                Ok(Statement::TraitUse { traits })
-            },
-            TokenKind::Const => {
-                self.next();
+            self.rparen()?;
 
-                let name = self.ident()?;
+            expect!(self, TokenKind::Colon);
 
-                expect!(self, TokenKind::Equals, "expected =");
+            let body = self.alternative_if_block()?;
 
-                let value = self.expression(0)?;
+            else_ifs.push(ElseIf { condition, body });
+        }
 
-                self.semi()?;
+        let mut r#else = None;
+        if self.current.kind == TokenKind::Else {
+            self.next();
 
-                Ok(Statement::Constant { name: name.into(), value, flags: vec![] })
-            },
-            TokenKind::Var => {
-                self.next();
+            expect!(self, TokenKind::Colon);
 
-                let mut var_type = None;
+            r#else = Some(self.block(&TokenKind::EndIf)?);
+        }
 
-                if ! matches!(self.current.kind, TokenKind::Variable(_)) || self.config.force_type_strings {
-                    var_type = Some(self.type_string()?);
-                }
+        expect!(self, TokenKind::EndIf);
+        self.semi()?;
 
-                let var = self.var()?;
-                let mut value = None;
+        Ok(StatementKind::If { condition, then, else_ifs, r#else })
+    }
 
-                if self.current.kind == TokenKind::Equals {
-                    self.next();
+    /// Parse a `try` statement with its `catch` and `finally` blocks.
+    fn try_statement(&mut self) -> ParseResult<StatementKind> {
+        let start_span = self.current.span;
 
-                    value = Some(self.expression(0)?);
-                }
+        self.next();
+        self.lbrace()?;
 
-                self.semi()?;
+        let body = self.block(&TokenKind::RightBrace)?;
 
-                Ok(Statement::Var { var, value, r#type: var_type })
-            },
-            TokenKind::Final | TokenKind::Abstract | TokenKind::Public | TokenKind::Private | TokenKind::Protected | TokenKind::Static => {
-                let mut flags = vec![self.current.kind.clone()];
-                self.next();
+        self.rbrace()?;
 
-                while ! self.is_eof() && [TokenKind::Final, TokenKind::Abstract, TokenKind::Public, TokenKind::Private, TokenKind::Protected, TokenKind::Static].contains(&self.current.kind) {
-                    if flags.contains(&self.current.kind) {
-                        return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span));
-                    }
+        let mut catches = Vec::new();
+        loop {
+            if self.current.kind != TokenKind::Catch {
+                break;
+            }
 
-                    flags.push(self.current.kind.clone());
-                    self.next();
-                }
+            self.next();
+            self.lparen()?;
 
-                if flags.contains(&TokenKind::Final) && flags.contains(&TokenKind::Abstract) {
-                    return Err(ParseError::InvalidAbstractFinalFlagCombination(self.current.span));
-                }
+            let types = self.catch_types()?;
 
-                match self.current.kind {
-                    TokenKind::Const => {
-                        if flags.contains(&TokenKind::Static) {
-                            return Err(ParseError::ConstantCannotBeStatic(self.current.span));
-                        }
+            let var = self.expression(0)?;
 
-                        if flags.contains(&TokenKind::Final) && flags.contains(&TokenKind::Private) {
-                            return Err(ParseError::ConstantCannotBePrivateFinal(self.current.span));
-                        }
+            self.rparen()?;
+            self.lbrace()?;
+            
+            let body = self.block(&TokenKind::RightBrace)?;
 
-                        self.next();
+            self.rbrace()?;
 
-                        let name = self.ident()?;
+            catches.push(Catch {
+                types,
+                var,
+                body,
+            })
+        }
 
-                        expect!(self, TokenKind::Equals, "expected =");
-        
-                        let value = self.expression(0)?;
-        
-                        self.semi()?;
-        
-                        Ok(Statement::Constant { name: name.into(), value, flags: flags.into_iter().map(|f| f.into()).collect() })
-                    },
-                    TokenKind::Function => {
-                        if flags.contains(&TokenKind::Abstract) {
-                            self.next();
+        let mut finally = None;
+        if self.current.kind == TokenKind::Finally {
+            self.next();
+            self.lbrace()?;
 
-                            let name = self.ident()?;
+            finally = Some(self.block(&TokenKind::RightBrace)?);
 
-                            self.lparen()?;
+            self.rbrace()?;
+        }
 
-                            let params = self.param_list()?;
+        if catches.is_empty() && finally.is_none() {
+            return Err(ParseError::TryWithoutCatchOrFinally(start_span));
+        }
 
-                            self.rparen()?;
+        Ok(StatementKind::Try { body, catches, finally })
+    }
 
-                            let mut return_type = None;
+    /// Parse the class names that a `catch` handles, which are separated by `|`.
+    fn catch_types(&mut self) -> ParseResult<Vec<Identifier>> {
+        let mut types = Vec::new();
 
-                            if self.current.kind == TokenKind::Colon || self.config.force_type_strings {
-                                expect!(self, TokenKind::Colon, "expected :");
+        loop {
+            if ! matches!(self.current.kind, TokenKind::Identifier(_) | TokenKind::QualifiedIdentifier(_) | TokenKind::FullyQualifiedIdentifier(_)) {
+                return Err(ParseError::InvalidCatchArgumentType(self.current.span));
+            }
 
-                                return_type = Some(self.type_string()?);
-                            }
+            types.push(self.full_identifier()?);
 
-                            self.semi()?;
+            if self.current.kind != TokenKind::Pipe {
+                return Ok(types);
+            }
 
-                            Ok(Statement::Method { name: name.into(), params, body: vec![], return_type, flags: flags.iter().map(|t| t.clone().into()).collect() })
-                        } else {
-                            match self.function()? {
-                                Statement::Function { name, params, body, return_type } => {
-                                    Ok(Statement::Method { name, params, body, flags: flags.iter().map(|t| t.clone().into()).collect(), return_type })
-                                },
-                                _ => unreachable!()
-                            }
-                        }
-                    },
-                    TokenKind::Question | TokenKind::Identifier(_) | TokenKind::QualifiedIdentifier(_) | TokenKind::FullyQualifiedIdentifier(_) | TokenKind::Array => {
-                        let prop_type = self.type_string()?;
-                        let var = self.var()?;
-                        let mut value = None;
-
-                        if self.current.kind == TokenKind::Equals {
-                            self.next();
-                            value = Some(self.expression(0)?);
-                        }
+            self.next();
+        }
+    }
 
-                        // TODO: Support comma-separated property declarations.
-                        //       nikic/php-parser does this with a single Property statement
-                        //       that is capable of holding multiple property declarations.
-                        self.semi()?;
+    /// Parse a named function, or a method when `flags` holds the method's modifiers.
+    fn function(&mut self, flags: Option<Vec<MethodFlag>>) -> ParseResult<StatementKind> {
+        let attributes = self.get_attributes();
+        let doc_comment = self.doc_comment.take();
 
-                        Ok(Statement::Property { var, value, r#type: Some(prop_type), flags: flags.into_iter().map(|f| f.into()).collect() })
-                    },
-                    TokenKind::Variable(_) => {
-                        let var = self.var()?;
-                        let mut value = None;
+        self.next();
 
-                        if self.current.kind == TokenKind::Equals {
-                            self.next();
-                            value = Some(self.expression(0)?);
-                        }
+        let returns_by_ref = self.optional_ampersand();
 
-                        self.semi()?;
+        let name = self.identifier()?;
 
-                        Ok(Statement::Property { var, value, r#type:None, flags: flags.into_iter().map(|f| f.into()).collect() })
-                    },
-                    _ => Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span))
-                }
-            },
-            TokenKind::Function => {
-                match self.function()? {
-                    Statement::Function { name, params, body, return_type } => {
-                        Ok(Statement::Method { name, params, body, flags: vec![], return_type })
-                    },
-                    _ => unreachable!(),
-                }
-            },
-            // TODO: Support use statements.
-            _ => Err(ParseError::UnexpectedToken(format!("{}", self.current.kind), self.current.span))
-        }
-    }
+        self.lparen()?;
 
-    fn expression(&mut self, bp: u8) -> Result<Expression, ParseError> {
-        if self.is_eof() {
-            return Err(ParseError::UnexpectedEndOfFile);
+        let params = self.param_list()?;
+
+        self.rparen()?;
+
+        let mut return_type = None;
+
+        if self.current.kind == TokenKind::Colon || self.config.force_type_strings {
+            expect!(self, TokenKind::Colon);
+
+            return_type = Some(self.type_string()?);
         }
 
-        self.skip_comments();
+        self.lbrace()?;
 
-        let mut lhs = match &self.current.kind {
-            TokenKind::Throw => {
-                self.next();
+        let body = self.block(&TokenKind::RightBrace)?;
 
-                let value = self.expression(0)?;
+        self.rbrace()?;
 
-                Expression::Throw { value: Box::new(value) }
-            },
-            TokenKind::Yield => {
-                self.next();
+        Ok(match flags {
+            Some(flags) => StatementKind::Method { name, params, body, flags, return_type, returns_by_ref, attributes, doc_comment },
+            None => StatementKind::Function { name, params, body, return_type, returns_by_ref, attributes, doc_comment },
+        })
+    }
 
-                let value = self.expression(0)?;
+    /// Parse the body of an `if`, `elseif` or `else` branch written using the alternative
+    /// syntax, stopping at the token that starts the next branch.
+    fn alternative_if_block(&mut self) -> ParseResult<Block> {
+        let mut block = Block::new();
 
-                // FIXME: Check for presence of => here to allow yielding key and value.
+        self.skip_comments();
 
-                Expression::Yield { value: Box::new(value) }
-            },
-            TokenKind::Clone => {
-                self.next();
+        while ! self.is_eof() && ! matches!(self.current.kind, TokenKind::ElseIf | TokenKind::Else | TokenKind::EndIf) {
+            block.push(self.statement()?);
+        }
 
-                let target = self.expression(0)?;
+        Ok(block)
+    }
 
-                Expression::Clone { target: Box::new(target) }
-            },
-            TokenKind::Variable(v) => {
-                let e = Expression::Variable { name: v.to_string() };
-                self.next();
-                e
-            },
-            TokenKind::Int(i) => {
-                let e = Expression::Int{ i: *i };
-                self.next();
-                e
-            },
-            TokenKind::Float(f) => {
-                let f = Expression::Float { f: *f };
-                self.next();
-                f
-            },
-            TokenKind::Identifier(i) | TokenKind::QualifiedIdentifier(i) | TokenKind::FullyQualifiedIdentifier(i) => {
-                let e = Expression::Identifier { name: i.to_string() };
-                self.next();
-                e
-            },
+    /// Parse the class reference used by `new` and `instanceof`. This is deliberately more
+    /// restrictive than a full expression since `new $foo->bar()` should instantiate the
+    /// class named by `$foo->bar` rather than call a method on it.
+    fn class_name_reference(&mut self) -> ParseResult<Expression> {
+        let start = self.current.span;
+        let kind = match &self.current.kind {
             TokenKind::Static => {
                 self.next();
-                Expression::Static
-            },
-            TokenKind::ConstantString(s) => {
-                let e = Expression::ConstantString { value: s.to_string() };
-                self.next();
-                e
+                ExpressionKind::Static
             },
-            TokenKind::True => {
-                let e = Expression::Bool { value: true };
+            TokenKind::Identifier(i) | TokenKind::QualifiedIdentifier(i) | TokenKind::FullyQualifiedIdentifier(i) => {
+                let e = ExpressionKind::Identifier { name: i.to_string() };
                 self.next();
                 e
             },
-            TokenKind::False => {
-                let e = Expression::Bool { value: false };
+            TokenKind::Variable(v) => {
+                let e = ExpressionKind::Variable { name: v.to_string() };
                 self.next();
                 e
             },
-            TokenKind::Null => {
-                self.next();
-                Expression::Null
-            },
             TokenKind::LeftParen => {
                 self.next();
 
@@ -1094,1467 +1382,5208 @@ impl Parser {
 
                 self.rparen()?;
 
-                e
+                return Ok(e);
             },
-            TokenKind::Match => {
-                self.next();
-                self.lparen()?;
+            _ => return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span)),
+        };
 
-                let condition = Box::new(self.expression(0)?);
+        let mut target = Expression::new(kind, self.span_from(start));
 
-                self.rparen()?;
-                self.lbrace()?;
+        loop {
+            match self.current.kind {
+                TokenKind::Arrow => {
+                    self.next();
 
-                let mut arms = Vec::new();
-                while self.current.kind != TokenKind::RightBrace {
-                    let mut conditions = Vec::new();
+                    let property = self.member_name()?;
 
-                    while self.current.kind != TokenKind::DoubleArrow {
-                        if self.current.kind == TokenKind::Default {
-                            self.next();
-                            break;
-                        }
+                    target = Expression::new(ExpressionKind::PropertyFetch { target: Box::new(target), property: Box::new(property) }, self.span_from(start));
+                },
+                TokenKind::DoubleColon if matches!(self.peek.kind, TokenKind::Variable(_)) => {
+                    self.next();
 
-                        conditions.push(self.expression(0)?);
+                    let property = self.variable()?;
 
-                        self.optional_comma()?;
-                    }
+                    target = Expression::new(ExpressionKind::StaticPropertyFetch { target: Box::new(target), property: Box::new(property) }, self.span_from(start));
+                },
+                TokenKind::LeftBracket => {
+                    self.next();
 
-                    expect!(self, TokenKind::DoubleArrow, "expected =>");
+                    let index = self.expression(0)?;
 
-                    let body = self.expression(0)?;
+                    self.rbracket()?;
 
-                    self.optional_comma()?;
+                    target = Expression::new(ExpressionKind::ArrayIndex { array: Box::new(target), index: Some(Box::new(index)) }, self.span_from(start));
+                },
+                _ => break,
+            }
+        }
 
-                    arms.push(MatchArm {
-                        conditions: if conditions.is_empty() { None } else { Some(conditions) },
-                        body,
-                    })
-                }
+        Ok(target)
+    }
 
+    /// Parse the name of a property or method after `->`. Names that are computed at runtime,
+    /// such as `$foo->$bar` or `$foo->{'bar'}`, are wrapped in `ExpressionKind::Dynamic`.
+    fn member_name(&mut self) -> ParseResult<Expression> {
+        let start = self.current.span;
+        let kind = match self.current.kind {
+            TokenKind::LeftBrace => {
+                self.lbrace()?;
+                let value = self.expression(0)?;
                 self.rbrace()?;
 
-                Expression::Match { condition, arms }
+                ExpressionKind::Dynamic { value: Box::new(value) }
             },
-            TokenKind::Array => {
-                let mut items = vec![];
+            TokenKind::Variable(_) => {
+                let value = self.variable()?;
 
-                self.next();
+                ExpressionKind::Dynamic { value: Box::new(value) }
+            },
+            _ => ExpressionKind::Identifier { name: self.ident_maybe_reserved()? },
+        };
 
-                self.lparen()?;
+        Ok(Expression::new(kind, self.span_from(start)))
+    }
 
-                while self.current.kind != TokenKind::RightParen {
-                    let mut key = None;
-                    let mut value = self.expression(0)?;
+    /// Parse the items of an array literal, stopping at the closing `)` or `]`.
+    fn array_items(&mut self, until: &TokenKind) -> ParseResult<Vec<ArrayItem>> {
+        let mut items = Vec::new();
 
-                    if self.current.kind == TokenKind::DoubleArrow {
-                        self.next();
+        self.skip_comments();
 
-                        key = Some(value);
-                        value = self.expression(0)?;
-                    }
+        while ! self.is_eof() && &self.current.kind != until {
+            if self.current.kind == TokenKind::Ellipsis {
+                self.next();
 
-                    items.push(ArrayItem { key, value });
+                let value = self.expression(0)?;
 
-                    self.optional_comma()?;
+                items.push(ArrayItem { key: None, value, unpack: true, by_ref: false });
+            } else {
+                let mut key = None;
 
-                    self.skip_comments();
+                let mut by_ref = self.current.kind == TokenKind::Ampersand;
+                if by_ref {
+                    self.next();
                 }
 
-                self.rparen()?;
+                let mut value = self.expression(0)?;
 
-                Expression::Array { items }
-            },
-            TokenKind::LeftBracket => {
-                let mut items = Vec::new();
-                self.next();
+                if self.current.kind == TokenKind::DoubleArrow {
+                    if by_ref {
+                        return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span));
+                    }
 
-                self.skip_comments();
+                    self.next();
 
-                while self.current.kind != TokenKind::RightBracket {
-                    let mut key = None;
-                    let mut value = self.expression(0)?;
+                    key = Some(value);
 
-                    if self.current.kind == TokenKind::DoubleArrow {
+                    by_ref = self.current.kind == TokenKind::Ampersand;
+                    if by_ref {
                         self.next();
-
-                        key = Some(value);
-                        value = self.expression(0)?;
                     }
 
-                    items.push(ArrayItem { key, value });
+                    value = self.expression(0)?;
+                }
 
-                    self.optional_comma()?;
+                items.push(ArrayItem { key, value, unpack: false, by_ref });
+            }
 
-                    self.skip_comments();
-                }
-                
-                self.rbracket()?;
+            self.list_comma(until, List::Arrays)?;
 
-                Expression::Array { items }
-            },
-            TokenKind::Function => {
-                self.next();
+            self.skip_comments();
+        }
 
-                self.lparen()?;
+        Ok(items)
+    }
 
-                let params = self.param_list()?;
+    fn class(&mut self, flag: Option<ClassFlag>) -> ParseResult<StatementKind> {
+        let attributes = self.get_attributes();
+        let doc_comment = self.doc_comment.take();
 
-                self.rparen()?;
+        expect!(self, TokenKind::Class);
 
-                let mut uses = vec![];
-                if self.current.kind == TokenKind::Use {
-                    self.next();
+        let name = self.identifier()?;
+        let mut extends: Option<Identifier> = None;
 
-                    self.lparen()?;
+        if self.current.kind == TokenKind::Extends {
+            self.next();
+            extends = Some(self.full_identifier()?);
+        }
 
-                    while self.current.kind != TokenKind::RightParen {
-                        let var = match self.current.kind {
-                            TokenKind::Ampersand => {
-                                self.next();
+        let mut implements = Vec::new();
+        if self.current.kind == TokenKind::Implements {
+            self.next();
 
-                                match self.expression(0)? {
-                                    s @ Expression::Variable { .. } => ClosureUse { var: s, by_ref: true },
-                                    _ => return Err(ParseError::UnexpectedToken("expected variable".into(), self.current.span))
-                                }
-                            },
-                            _ => match self.expression(0)? {
-                                s @ Expression::Variable { .. } => ClosureUse { var: s, by_ref: false },
-                                _ => return Err(ParseError::UnexpectedToken("expected variable".into(), self.current.span))
-                            }
-                        };
+            while self.current.kind != TokenKind::LeftBrace {
+                self.optional_comma()?;
 
-                        uses.push(var);
+                implements.push(self.full_identifier()?);
+            }
+        }
 
-                        self.optional_comma()?;
-                    }
+        self.lbrace()?;
 
-                    self.rparen()?;
-                }
+        let mut body = Vec::new();
+        self.gather_comments();
+        while self.current.kind != TokenKind::RightBrace && ! self.is_eof() {
+            self.tolerant_statement(&mut body, Self::class_statement, &TokenKind::RightBrace)?;
+        }
 
-                let mut return_type = None;
-                if self.current.kind == TokenKind::Colon || self.config.force_type_strings {
-                    expect!(self, TokenKind::Colon, "expected :");
+        self.rbrace()?;
 
-                    return_type = Some(self.type_string()?);
-                }
+        self.dynamic_properties(&body, &extends, &attributes);
 
-                self.lbrace()?;
+        Ok(StatementKind::Class { name, extends, implements, body, flag, attributes, doc_comment })
+    }
+    
+    fn interface_statement(&mut self) -> ParseResult<Statement> {
+        self.gather_comments();
 
-                let body = self.block(&TokenKind::RightBrace)?;
+        if let Some(comment) = self.comment() {
+            return Ok(comment);
+        }
 
-                self.rbrace()?;
+        self.skip_comments();
+        self.gather_attributes_for(AttributeTarget::Member)?;
 
-                Expression::Closure { params, uses, return_type, body }
-            },
-            TokenKind::Fn => {
-                self.next();
+        let start = self.current.span;
 
-                self.lparen()?;
+        let mut flags = vec![];
+        while matches!(self.current.kind, TokenKind::Public | TokenKind::Static | TokenKind::Final) {
+            if flags.contains(&self.current.kind) {
+                return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span));
+            }
 
-                let params = self.param_list()?;
+            flags.push(self.current.kind.clone());
+            self.next();
+        }
 
-                self.rparen()?;
-        
-                let mut return_type = None;
-        
-                if self.current.kind == TokenKind::Colon || self.config.force_type_strings {
-                    expect!(self, TokenKind::Colon, "expected :");
-        
-                    return_type = Some(self.type_string()?);
-                }
-                
-                expect!(self, TokenKind::DoubleArrow, "expected =>");
+        let kind = match self.current.kind {
+            TokenKind::Const => self.class_constant(flags)?,
+            TokenKind::Function => self.method_signature(flags)?,
+            _ => return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span)),
+        };
 
-                let value = self.expression(0)?;
+        let span = self.span_from(start);
 
-                Expression::ArrowFunction { params, return_type, expr: Box::new(value) }
-            },
-            TokenKind::New => {
-                self.next();
+        if ! self.config.comments {
+            self.skip_comments();
+        }
 
-                let mut args = vec![];
-                let target = if self.current.kind == TokenKind::Class {
-                    self.next();
+        Ok(Statement::new(kind, span))
+    }
 
-                    if self.current.kind == TokenKind::LeftParen {
-                        self.lparen()?;
-    
-                        while self.current.kind != TokenKind::RightParen {
-                            let mut name = None;
-                            let mut unpack = false;
-                            if matches!(self.current.kind, TokenKind::Identifier(_)) && self.peek.kind == TokenKind::Colon {
-                                name = Some(self.ident_maybe_reserved()?);
-                                self.next();
-                            } else if self.current.kind == TokenKind::Ellipsis {
-                                self.next();
-                                unpack = true;
-                            }
-
-                            let value = self.expression(0)?;
-    
-                            args.push(Arg {
-                                name,
-                                unpack,
-                                value
-                            });
-    
-                            self.optional_comma()?;
-                        }
-    
-                        self.rparen()?;
-                    }
+    /// Parse a constant declaration, starting at the `const` keyword.
+    fn class_constant(&mut self, flags: Vec<TokenKind>) -> ParseResult<StatementKind> {
+        if flags.contains(&TokenKind::Static) {
+            return Err(ParseError::ConstantCannotBeStatic(self.current.span));
+        }
 
-                    let mut extends: Option<Identifier> = None;
+        if flags.contains(&TokenKind::Final) && flags.contains(&TokenKind::Private) {
+            return Err(ParseError::ConstantCannotBePrivateFinal(self.current.span));
+        }
 
-                    if self.current.kind == TokenKind::Extends {
-                        self.next();
-                        extends = Some(self.full_name()?.into());
-                    }
+        let doc_comment = self.doc_comment.take();
+        let attributes = self.get_attributes();
 
-                    let mut implements = Vec::new();
-                    if self.current.kind == TokenKind::Implements {
-                        self.next();
+        self.next();
 
-                        while self.current.kind != TokenKind::LeftBrace {
-                            self.optional_comma()?;
+        let name = self.identifier()?;
 
-                            implements.push(self.full_name()?.into());
-                        }
-                    }
+        expect!(self, TokenKind::Equals);
 
-                    self.lbrace()?;
+        let value = self.expression(0)?;
 
-                    let mut body = Vec::new();
-                    while self.current.kind != TokenKind::RightBrace && ! self.is_eof() {
-                        body.push(self.class_statement()?);
-                    }
+        self.semi()?;
 
-                    self.rbrace()?;
+        Ok(StatementKind::Constant { name, value, flags: self.flags(flags)?, attributes, doc_comment })
+    }
 
-                    Expression::AnonymousClass { extends, implements, body }
-                } else {
-                    self.expression(20)?
-                };
+    /// Convert modifier tokens into the flags of a declaration, rejecting any that don't apply to it.
+    fn flags<T: TryFrom<TokenKind, Error = TokenKind>>(&self, flags: Vec<TokenKind>) -> ParseResult<Vec<T>> {
+        flags.into_iter()
+            .map(|flag| T::try_from(flag).map_err(|flag| ParseError::UnexpectedToken(flag.to_string(), self.current.span)))
+            .collect()
+    }
 
-                if self.current.kind == TokenKind::LeftParen {
-                    self.lparen()?;
+    /// Parse a method without a body, such as an abstract or interface method, starting at the `function` keyword.
+    fn method_signature(&mut self, flags: Vec<TokenKind>) -> ParseResult<StatementKind> {
+        let attributes = self.get_attributes();
+        let doc_comment = self.doc_comment.take();
 
-                    while self.current.kind != TokenKind::RightParen {
-                        let mut name = None;
-                        let mut unpack = false;
-                        if matches!(self.current.kind, TokenKind::Identifier(_)) && self.peek.kind == TokenKind::Colon {
-                            name = Some(self.ident_maybe_reserved()?);
-                            self.next();
-                        } else if self.current.kind == TokenKind::Ellipsis {
-                            self.next();
-                            unpack = true;
-                        }
+        self.next();
 
-                        let value = self.expression(0)?;
+        let returns_by_ref = self.optional_ampersand();
 
-                        args.push(Arg {
-                            name,
-                            unpack,
-                            value
-                        });
+        let name = self.identifier()?;
 
-                        self.optional_comma()?;
-                    }
+        self.lparen()?;
 
-                    self.rparen()?;
-                }
+        let params = self.param_list()?;
 
-                Expression::New { target: Box::new(target), args }
-            },
-            TokenKind::DirConstant => {
-                self.next();
-                Expression::MagicConst { constant: MagicConst::Dir }
-            },
-            _ if is_prefix(&self.current.kind) => {
-                let op = self.current.kind.clone();
+        self.rparen()?;
 
-                self.next();
+        let mut return_type = None;
 
-                let rbp = prefix_binding_power(&op);
-                let rhs = self.expression(rbp)?;
+        if self.current.kind == TokenKind::Colon || self.config.force_type_strings {
+            expect!(self, TokenKind::Colon);
 
-                prefix(&op, rhs)
-            },
-            _ => todo!("expr lhs: {:?}, line {} col {}", self.current.kind, self.current.span.0, self.current.span.1),
-        };
+            return_type = Some(self.type_string()?);
+        }
 
-        if self.current.kind == TokenKind::SemiColon {
-            return Ok(lhs);
+        self.semi()?;
+
+        Ok(StatementKind::Method { name, params, body: vec![], return_type, returns_by_ref, flags: self.flags(flags)?, attributes, doc_comment })
+    }
+
+    fn class_statement(&mut self) -> ParseResult<Statement> {
+        self.gather_comments();
+
+        if let Some(comment) = self.comment() {
+            return Ok(comment);
         }
 
         self.skip_comments();
 
-        loop {
+        let start = self.current.span;
+        // See `unguarded_statement` for why each member is parsed by its own function.
+        let parse: fn(&mut Self) -> ParseResult<StatementKind> = match self.current.kind {
+            TokenKind::Attribute => |parser| {
+                parser.gather_attributes_for(AttributeTarget::Member)?;
+
+                parser.class_statement().map(|statement| statement.kind)
+            },
+            TokenKind::Use => Self::trait_use,
+            TokenKind::Const => |parser| parser.class_constant(vec![]),
+            TokenKind::Var => Self::var_property,
+            TokenKind::Final | TokenKind::Abstract | TokenKind::Public | TokenKind::Private | TokenKind::Protected | TokenKind::Static |
+            TokenKind::PublicSet | TokenKind::ProtectedSet | TokenKind::PrivateSet | TokenKind::Readonly => Self::modified_member,
+            TokenKind::Function => |parser| parser.function(Some(vec![])),
+            // TODO: Support use statements.
+            TokenKind::Identifier(_) => return Err(self.expected(vec![TokenKind::Function, TokenKind::Const])),
+            _ => return Err(ParseError::UnexpectedToken(format!("{}", self.current.kind), self.current.span))
+        };
+
+        let kind = parse(self)?;
+        let span = self.span_from(start);
+
+        if ! self.config.comments {
             self.skip_comments();
+        }
 
-            let kind = match &self.current {
-                Token { kind: TokenKind::SemiColon | TokenKind::Eof, .. }  => break,
-                Token { kind, .. } => kind.clone()
-            };
+        Ok(Statement::new(kind, span))
+    }
 
-            if let Some(lbp) = postfix_binding_power(&kind) {
-                if lbp < bp {
-                    break;
-                }
+    /// Parse a `use` statement that pulls traits into a class.
+    fn trait_use(&mut self) -> ParseResult<StatementKind> {
+        self.next();
 
-                self.next();
+        let mut traits = Vec::new();
 
-                let op = kind.clone();
-                lhs = self.postfix(lhs, &op)?;
+        while self.current.kind != TokenKind::SemiColon {
+            self.optional_comma()?;
 
-                continue;
-            }
+            traits.push(self.full_identifier()?);
+        }
 
-            if let Some((lbp, rbp)) = infix_binding_power(&kind) {
-                if lbp < bp {
-                    break;
-                }
+        self.semi()?;
 
-                self.next();
+        Ok(StatementKind::TraitUse { traits })
+    }
 
-                let op = kind.clone();
-                match op {
-                    TokenKind::Question => {
-                        let then = self.expression(0)?;
-                        expect!(self, TokenKind::Colon, "expected :");
-                        let otherwise = self.expression(rbp)?;
-                        lhs = Expression::Ternary { condition: Box::new(lhs), then: Some(Box::new(then)), r#else: Box::new(otherwise) }
-                    },
-                    TokenKind::QuestionColon => {
-                        let r#else = self.expression(0)?;
-                        lhs = Expression::Ternary { condition: Box::new(lhs), then: None, r#else: Box::new(r#else) }
-                    },
-                    _ => {
-                        let rhs = self.expression(rbp)?;
-                        lhs = infix(lhs, op, rhs);
-                    },
-                }
+    /// Parse a property declared with `var`.
+    fn var_property(&mut self) -> ParseResult<StatementKind> {
+        let attributes = self.get_attributes();
 
-                continue;
-            }
+        self.next();
 
-            break;
+        let mut var_type = None;
+
+        if ! matches!(self.current.kind, TokenKind::Variable(_)) || self.config.force_type_strings {
+            var_type = Some(self.type_string()?);
         }
 
-        self.skip_comments();
+        let var = self.var()?;
+        let mut value = None;
 
-        Ok(lhs)
+        if self.current.kind == TokenKind::Equals {
+            self.next();
+
+            value = Some(self.expression(0)?);
+        }
+
+        self.semi()?;
+
+        Ok(StatementKind::Var { var, value, r#type: var_type, attributes })
     }
 
-    fn postfix(&mut self, lhs: Expression, op: &TokenKind) -> Result<Expression, ParseError> {
-        Ok(match op {
-            TokenKind::Coalesce => {
-                let rhs = self.expression(11)?;
+    /// Parse a constant, method or property that starts with one or more modifiers.
+    fn modified_member(&mut self) -> ParseResult<StatementKind> {
+        self.modifier()?;
 
-                Expression::Coalesce { lhs: Box::new(lhs), rhs: Box::new(rhs) }
-            },
-            TokenKind::LeftParen => {
-                let mut args = Vec::new();
-                while ! self.is_eof() && self.current.kind != TokenKind::RightParen {
-                    let mut name = None;
-                    let mut unpack = false;
-                    if matches!(self.current.kind, TokenKind::Identifier(_)) && self.peek.kind == TokenKind::Colon {
-                        name = Some(self.ident_maybe_reserved()?);
-                        self.next();
-                    } else if self.current.kind == TokenKind::Ellipsis {
-                        self.next();
-                        unpack = true;
-                    }
+        let mut flags = vec![self.current.kind.clone()];
+        self.next();
 
-                    let value = self.expression(0)?;
+        while ! self.is_eof() && [TokenKind::Final, TokenKind::Abstract, TokenKind::Public, TokenKind::Private, TokenKind::Protected, TokenKind::Static,
+            TokenKind::PublicSet, TokenKind::ProtectedSet, TokenKind::PrivateSet, TokenKind::Readonly].contains(&self.current.kind) {
+            if flags.contains(&self.current.kind) {
+                return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span));
+            }
 
-                    args.push(Arg {
-                        name,
-                        unpack,
-                        value
-                    });
+            self.modifier()?;
 
-                    self.optional_comma()?;
-                }
+            flags.push(self.current.kind.clone());
+            self.next();
+        }
 
-                self.rparen()?;
-    
-                Expression::Call { target: Box::new(lhs), args }
-            },
-            TokenKind::LeftBracket => {
-                if self.current.kind == TokenKind::RightBracket {
-                    self.next();
+        if flags.contains(&TokenKind::Final) && flags.contains(&TokenKind::Abstract) {
+            return Err(ParseError::InvalidAbstractFinalFlagCombination(self.current.span));
+        }
 
-                    Expression::ArrayIndex { array: Box::new(lhs), index: None }
-                } else {
-                    let index = self.expression(0)?;
+        // Set visibility and `readonly` only apply to properties.
+        if matches!(self.current.kind, TokenKind::Const | TokenKind::Function) {
+            if let Some(flag) = flags.iter().find(|f| matches!(f, TokenKind::PublicSet | TokenKind::ProtectedSet | TokenKind::PrivateSet | TokenKind::Readonly)) {
+                return Err(ParseError::UnexpectedToken(flag.to_string(), self.current.span));
+            }
+        }
 
-                    expect!(self, TokenKind::RightBracket, "expected ]");
+        match self.current.kind {
+            TokenKind::Const => self.class_constant(flags),
+            TokenKind::Function => {
+                if flags.contains(&TokenKind::Abstract) {
+                    self.method_signature(flags)
+                } else {
+                    let flags = self.flags(flags)?;
 
-                    Expression::ArrayIndex { array: Box::new(lhs), index: Some(Box::new(index)) }
+                    self.function(Some(flags))
                 }
             },
-            TokenKind::DoubleColon => {
-                match self.current.kind.clone() {
-                    TokenKind::Variable(_) => {
-                        let var = self.expression(0)?;
+            TokenKind::Question | TokenKind::LeftParen | TokenKind::Identifier(_) | TokenKind::QualifiedIdentifier(_) | TokenKind::FullyQualifiedIdentifier(_) | TokenKind::Array | TokenKind::Null | TokenKind::False | TokenKind::True => self.property(flags, true),
+            TokenKind::Variable(_) => self.property(flags, false),
+            _ => Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span))
+        }
+    }
 
-                        Expression::StaticPropertyFetch { target: Box::new(lhs), property: Box::new(var) }
-                    },
-                    _ => {
-                        let ident = if self.current.kind == TokenKind::Class {
-                            self.next();
-
-                            String::from("class")
-                        } else {
-                            self.ident_maybe_reserved()?
-                        };
-
-                        if self.current.kind == TokenKind::LeftParen {
-                            self.lparen()?;
-
-                            let mut args = vec![];
-                            while ! self.is_eof() && self.current.kind != TokenKind::RightParen {
-                                let mut name = None;
-                                let mut unpack = false;
-                                if matches!(self.current.kind, TokenKind::Identifier(_)) && self.peek.kind == TokenKind::Colon {
-                                    name = Some(self.ident_maybe_reserved()?);
-                                    self.next();
-                                } else if self.current.kind == TokenKind::Ellipsis {
-                                    self.next();
-                                    unpack = true;
-                                }
-            
-                                let value = self.expression(0)?;
-            
-                                args.push(Arg {
-                                    name,
-                                    unpack,
-                                    value
-                                });
-            
-                                self.optional_comma()?;
-                            }
+    /// Parse a property declaration after its modifiers, with a type if `typed` is set.
+    fn property(&mut self, flags: Vec<TokenKind>, typed: bool) -> ParseResult<StatementKind> {
+        let doc_comment = self.doc_comment.take();
+        let attributes = self.get_attributes();
+        let prop_type = if typed { Some(self.type_string()?) } else { None };
+        let var = self.var()?;
+        let mut value = None;
 
-                            self.rparen()?;
+        if self.current.kind == TokenKind::Equals {
+            self.next();
+            value = Some(self.expression(0)?);
+        }
 
-                            Expression::StaticMethodCall { target: Box::new(lhs), method: ident.into(), args }
-                        } else {
-                            Expression::ConstFetch { target: Box::new(lhs), constant: ident.into() }
-                        }
-                    },
-                }
-            },
-            TokenKind::Arrow | TokenKind::NullsafeArrow => {
-                let property = match self.current.kind {
-                    TokenKind::LeftBrace => {
-                        self.lbrace()?;
-                        let expr = self.expression(0)?;
-                        self.rbrace()?;
-                        expr
-                    },
-                    TokenKind::Variable(ref var) => {
-                        let var = Expression::Variable { name: var.to_string() };
-                        self.next();
-                        var
-                    },
-                    _ => {
-                        Expression::Identifier { name: self.ident_maybe_reserved()? }
-                    }
-                };
+        // TODO: Support comma-separated property declarations.
+        //       nikic/php-parser does this with a single Property statement
+        //       that is capable of holding multiple property declarations.
+        let hooks = if self.current.kind == TokenKind::LeftBrace {
+            self.requires(PhpVersion::Php84, "property hooks")?;
+            self.property_hooks()?
+        } else {
+            self.semi()?;
+            vec![]
+        };
 
-                if self.current.kind == TokenKind::LeftParen {
+        Ok(StatementKind::Property { var, value, r#type: prop_type, flags: self.flags(flags)?, hooks, attributes, doc_comment })
+    }
+
+    /// Parse the `get` and `set` hooks of a property, starting at the `{` after its name.
+    fn property_hooks(&mut self) -> ParseResult<Vec<PropertyHook>> {
+        self.lbrace()?;
+        self.skip_comments();
+
+        let mut hooks = Vec::new();
+
+        while ! self.is_eof() && self.current.kind != TokenKind::RightBrace {
+            self.gather_attributes()?;
+
+            let attributes = self.get_attributes();
+            let mut flags = vec![];
+
+            if self.current.kind == TokenKind::Final {
+                flags.push(MethodFlag::Final);
+                self.next();
+            }
+
+            let by_ref = self.optional_ampersand();
+            let name = self.identifier()?;
+
+            if ! name.name.eq_ignore_ascii_case("get") && ! name.name.eq_ignore_ascii_case("set") {
+                return Err(ParseError::UnexpectedToken(name.name, name.span));
+            }
+
+            let mut params = ParamList::new();
+
+            if self.current.kind == TokenKind::LeftParen {
+                self.lparen()?;
+                params = self.param_list()?;
+                self.rparen()?;
+            }
+
+            let body = match self.current.kind {
+                TokenKind::DoubleArrow => {
                     self.next();
 
-                    let mut args = Vec::new();
-                    while ! self.is_eof() && self.current.kind != TokenKind::RightParen {
-                        let mut name = None;
-                        let mut unpack = false;
-                        if matches!(self.current.kind, TokenKind::Identifier(_)) && self.peek.kind == TokenKind::Colon {
-                            name = Some(self.ident_maybe_reserved()?);
-                            self.next();
-                        } else if self.current.kind == TokenKind::Ellipsis {
-                            self.next();
-                            unpack = true;
-                        }
-    
-                        let value = self.expression(0)?;
-    
-                        args.push(Arg {
-                            name,
-                            value,
-                            unpack
-                        });
-    
-                        self.optional_comma()?;
-                    }
+                    let expression = self.expression(0)?;
+                    self.semi()?;
 
-                    self.rparen()?;
+                    Some(PropertyHookBody::Expression(expression))
+                },
+                TokenKind::LeftBrace => {
+                    self.next();
 
-                    Expression::MethodCall { target: Box::new(lhs), method: Box::new(property), args }
-                } else {
-                    if op == &TokenKind::NullsafeArrow {
-                        Expression::NullsafePropertyFetch { target: Box::new(lhs), property: Box::new(property) }
-                    } else {
-                        Expression::PropertyFetch { target: Box::new(lhs), property: Box::new(property) }
-                    }
-                }
-            },
-            TokenKind::Increment => {
-                Expression::Increment { value: Box::new(lhs) }
-            },
-            TokenKind::Decrement => {
-                Expression::Decrement { value: Box::new(lhs) }
-            },
-            _ => todo!("postfix: {:?}", op),
-        })
-    }
+                    let block = self.block(&TokenKind::RightBrace)?;
+                    self.rbrace()?;
 
-    fn is_eof(&self) -> bool {
-        self.current.kind == TokenKind::Eof
-    }
+                    Some(PropertyHookBody::Block(block))
+                },
+                _ => {
+                    self.semi()?;
 
-    pub fn next(&mut self) {
-        self.current = self.peek.clone();
-        self.peek = self.iter.next().unwrap_or_default()
-    }
-}
+                    None
+                },
+            };
 
-fn is_prefix(op: &TokenKind) -> bool {
-    matches!(op, TokenKind::Bang | TokenKind::Minus | TokenKind::StringCast | TokenKind::ObjectCast | TokenKind::BoolCast | TokenKind::IntCast | TokenKind::DoubleCast)
-}
+            hooks.push(PropertyHook { name, params, body, by_ref, flags, attributes });
+            self.skip_comments();
+        }
 
-fn prefix_binding_power(op: &TokenKind) -> u8 {
-    match op {
-        TokenKind::StringCast | TokenKind::ObjectCast | TokenKind::BoolCast | TokenKind::IntCast | TokenKind::DoubleCast => 101,
-        TokenKind::Minus => 100,
-        TokenKind::Bang => 99,
-        _ => unreachable!()
-    }
-}
+        self.rbrace()?;
 
-fn prefix(op: &TokenKind, rhs: Expression) -> Expression {
-    match op {
-        TokenKind::Bang => Expression::BooleanNot { value: Box::new(rhs) },
-        TokenKind::Minus => Expression::Negate { value: Box::new(rhs) },
-        TokenKind::StringCast | TokenKind::ObjectCast | TokenKind::BoolCast | TokenKind::IntCast |
-        TokenKind::DoubleCast => Expression::Cast { kind: op.into(), value: Box::new(rhs) },
-        _ => unreachable!()
+        Ok(hooks)
     }
-}
 
-fn infix(lhs: Expression, op: TokenKind, rhs: Expression) -> Expression {
-    Expression::Infix {
-        lhs: Box::new(lhs), 
-        op: op.into(),
-        rhs: Box::new(rhs)
+    fn expression(&mut self, bp: u8) -> Result<Expression, ParseError> {
+        self.nested(|parser| parser.unguarded_expression(bp))
     }
-}
 
-fn infix_binding_power(t: &TokenKind) -> Option<(u8, u8)> {
-    Some(match t {
-        TokenKind::Pow => (18, 19),
-        TokenKind::Instanceof => (16, 17),
-        TokenKind::Asterisk | TokenKind::Slash => (14, 15),
-        TokenKind::Plus | TokenKind::Minus => (12, 13),
-        TokenKind::Dot => (12, 12),
-        TokenKind::LessThan | TokenKind::GreaterThan | TokenKind::LessThanEquals | TokenKind::GreaterThanEquals => (10, 11),
-        TokenKind::DoubleEquals | TokenKind::TripleEquals | TokenKind::BangEquals | TokenKind::BangDoubleEquals => (8, 9),
-        TokenKind::Question | TokenKind::QuestionColon => (6, 7),
-        TokenKind::BooleanAnd => (4, 5),
-        TokenKind::BooleanOr => (2, 3),
-        TokenKind::Equals | TokenKind::PlusEquals | TokenKind::MinusEquals | TokenKind::DotEquals | TokenKind::CoalesceEqual | TokenKind::AsteriskEqual | TokenKind::SlashEquals => (0, 1),
-        _ => return None,
-    })
-}
+    fn unguarded_expression(&mut self, bp: u8) -> Result<Expression, ParseError> {
+        if self.is_eof() {
+            return Err(ParseError::UnexpectedEndOfFile);
+        }
 
-fn postfix_binding_power(t: &TokenKind) -> Option<u8> {
-    Some(match t {
-        TokenKind::Increment | TokenKind::Decrement => 77,
-        TokenKind::LeftParen | TokenKind::LeftBracket => 19,
-        TokenKind::Arrow | TokenKind::NullsafeArrow | TokenKind::DoubleColon => 18,
-        TokenKind::Coalesce => 11,
-        _ => return None
-    })
-}
+        self.skip_comments();
 
-#[derive(Debug)]
-pub enum ParseError {
-    ExpectedToken(String, Span),
-    UnexpectedToken(String, Span),
-    UnexpectedEndOfFile,
-    InvalidClassStatement(String, Span),
-    InvalidAbstractFinalFlagCombination(Span),
-    ConstantCannotBeStatic(Span),
-    ConstantCannotBePrivateFinal(Span),
-    TraitCannotContainConstant(Span),
-    TryWithoutCatchOrFinally(Span),
-    InvalidCatchArgumentType(Span),
-}
+        if self.current.kind == TokenKind::Attribute {
+            self.gather_attributes_for(AttributeTarget::Function)?;
+        }
 
-impl Display for ParseError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::ExpectedToken(message, span) => write!(f, "Parse error: {} on line {} column {}", message, span.0, span.1),
-            Self::InvalidClassStatement(message, span) => write!(f, "Parse error: {} on line {} column {}", message, span.0, span.1),
-            Self::UnexpectedEndOfFile => write!(f, "Parse error: unexpected end of file."),
-            Self::UnexpectedToken(message, span) => write!(f, "Parse error: unexpected token {} on line {} column {}", message, span.0, span.1),
-            Self::InvalidAbstractFinalFlagCombination(span) => write!(f, "Parse error: final cannot be used on an abstract class member on line {}", span.0),
-            Self::ConstantCannotBeStatic(span) => write!(f, "Parse error: class constant cannot be marked static on line {}", span.0),
-            Self::ConstantCannotBePrivateFinal(span) => write!(f, "Parse error: private class constant cannot be marked final since it is not visible to other classes on line {}", span.0),
-            Self::TraitCannotContainConstant(span) => write!(f, "Parse error: traits cannot contain constants on line {}", span.0),
-            Self::TryWithoutCatchOrFinally(span) => write!(f, "Parse error: cannot use try without catch or finally on line {}", span.0),
-            Self::InvalidCatchArgumentType(span) => write!(f, "Parse error: catch types must either describe a single type or union of types on line {}", span.0)
+        let start = self.current.span;
+        let lhs = self.operand()?;
+
+        if self.current.kind == TokenKind::SemiColon {
+            return Ok(lhs);
         }
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use trunk_lexer::Lexer;
-    use crate::{Statement, Param, Expression, ast::{InfixOp, ElseIf, MethodFlag, ArrayItem, Arg}, Type, Identifier};
-    use super::Parser;
+        // Operators applied to the left-hand side nest it one level deeper each time without any
+        // recursion, so they're counted here to keep long chains within the depth limit.
+        let depth = self.depth;
+        let result = self.operators(lhs, start, bp);
+        self.depth = depth;
 
-    macro_rules! function {
-        ($name:literal, $params:expr, $body:expr) => {
-            Statement::Function {
-                name: $name.to_string().into(),
-                params: $params.to_vec().into_iter().map(|p: &str| Param::from(p)).collect::<Vec<Param>>(),
-                body: $body.to_vec(),
-                return_type: None,
-            }
-        };
+        result
     }
 
-    macro_rules! class {
-        ($name:literal) => {
-            Statement::Class {
-                name: $name.to_string().into(),
-                body: vec![],
-                extends: None,
-                implements: vec![],
-                flag: None,
-            }
-        };
-        ($name:literal, $body:expr) => {
-            Statement::Class {
-                name: $name.to_string().into(),
-                body: $body.to_vec(),
-                extends: None,
-                implements: vec![],
-                flag: None,
-            }
-        };
-        ($name:literal, $extends:expr, $implements:expr, $body:expr) => {
-            Statement::Class {
-                name: $name.to_string().into(),
-                body: $body.to_vec(),
-                extends: $extends,
-                implements: $implements.to_vec(),
-                flag: None,
-            }
+    /// Parse the operand an expression starts with, including any prefix operators in front of it.
+    fn operand(&mut self) -> ParseResult<Expression> {
+        let start = self.current.span;
+
+        // As with statements, choosing the function keeps this frame small in unoptimised builds.
+        let parse: fn(&mut Self) -> ParseResult<ExpressionKind> = match &self.current.kind {
+            TokenKind::Throw => Self::throw,
+            TokenKind::Yield => Self::yield_expression,
+            TokenKind::Variable(name) if name.is_empty() => Self::variable_variable,
+            TokenKind::Variable(_) | TokenKind::Int(_) | TokenKind::Float(_) | TokenKind::Identifier(_) | TokenKind::QualifiedIdentifier(_) |
+            TokenKind::FullyQualifiedIdentifier(_) | TokenKind::Static | TokenKind::ConstantString(_) | TokenKind::True | TokenKind::False | TokenKind::Null => Self::atom,
+            TokenKind::DoubleQuote => Self::interpolated_string,
+            TokenKind::DocOpen(_) => Self::heredoc,
+            TokenKind::NowdocOpen(_) => Self::nowdoc,
+            TokenKind::LeftParen => Self::parenthesized,
+            TokenKind::Match => Self::match_expression,
+            TokenKind::Array | TokenKind::LeftBracket => Self::array,
+            TokenKind::Function => Self::closure,
+            TokenKind::Fn => Self::arrow_function,
+            TokenKind::New => Self::new_expression,
+            TokenKind::ClassConstant | TokenKind::DirConstant | TokenKind::FileConstant | TokenKind::FunctionConstant |
+            TokenKind::LineConstant | TokenKind::MethodConstant | TokenKind::NamespaceConstant | TokenKind::TraitConstant => Self::magic_constant,
+            TokenKind::Exit | TokenKind::Die => Self::exit,
+            _ => Self::prefix_operator,
         };
+
+        let kind = parse(self)?;
+
+        Ok(Expression::new(kind, self.span_from(start)))
     }
 
-    macro_rules! method {
-        ($name:literal, $params:expr, $flags:expr, $body:expr) => {
-            Statement::Method {
-                name: $name.to_string().into(),
-                params: $params.to_vec().into_iter().map(|p: &str| Param::from(p)).collect::<Vec<Param>>(),
-                flags: $flags.to_vec(),
-                body: $body.to_vec(),
-                return_type: None,
-            }
+    /// Parse a variable variable such as `$$name` or `${'name'}`, which starts with a `$` that isn't
+    /// followed by a name.
+    fn variable_variable(&mut self) -> ParseResult<ExpressionKind> {
+        self.next();
+
+        let start = self.current.span;
+        let kind = match &self.current.kind {
+            TokenKind::LeftBrace => {
+                self.next();
+
+                let value = self.expression(0)?;
+
+                self.rbrace()?;
+
+                return Ok(ExpressionKind::Dynamic { value: Box::new(value) });
+            },
+            TokenKind::Variable(name) if name.is_empty() => self.nested(Self::variable_variable)?,
+            TokenKind::Variable(name) => {
+                let name = name.to_string();
+                self.next();
+
+                ExpressionKind::Variable { name }
+            },
+            _ => return Err(self.expected(vec![TokenKind::Variable(String::new()), TokenKind::LeftBrace])),
+        };
+
+        Ok(ExpressionKind::Dynamic { value: Box::new(Expression::new(kind, self.span_from(start))) })
+    }
+
+    /// Parse a variable, a name or a literal that's made up of a single token.
+    fn atom(&mut self) -> ParseResult<ExpressionKind> {
+        let kind = match &self.current.kind {
+            TokenKind::Variable(v) => ExpressionKind::Variable { name: v.to_string() },
+            TokenKind::Int(i) => ExpressionKind::Int { i: *i },
+            TokenKind::Float(f) => ExpressionKind::Float { f: *f },
+            TokenKind::Identifier(i) | TokenKind::QualifiedIdentifier(i) | TokenKind::FullyQualifiedIdentifier(i) => ExpressionKind::Identifier { name: i.to_string() },
+            TokenKind::Static => ExpressionKind::Static,
+            TokenKind::ConstantString(s) => ExpressionKind::ConstantString { value: s.to_string() },
+            TokenKind::True => ExpressionKind::Bool { value: true },
+            TokenKind::False => ExpressionKind::Bool { value: false },
+            TokenKind::Null => ExpressionKind::Null,
+            _ => return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span)),
+        };
+
+        self.next();
+
+        Ok(kind)
+    }
+
+    /// Apply the postfix and infix operators that follow `lhs`, as long as they bind tighter than `bp`.
+    fn operators(&mut self, mut lhs: Expression, start: Span, bp: u8) -> ParseResult<Expression> {
+        self.skip_comments();
+
+        // PHP 8 no longer allows nested ternaries without explicit parentheses, so we need
+        // to remember whether the left-hand side was produced by a ternary in this loop.
+        let mut last_ternary: Option<&TokenKind> = None;
+        let mut last_non_associative: Option<Precedence> = None;
+
+        loop {
+            self.skip_comments();
+
+            let current = self.current;
+            let kind = match &current.kind {
+                TokenKind::SemiColon | TokenKind::Eof => break,
+                // `$a ? : $b` is a short ternary too, even with whitespace between the tokens.
+                TokenKind::Question if self.peek.kind == TokenKind::Colon => &TokenKind::QuestionColon,
+                kind => kind,
+            };
+
+            // `$s{0}` is the old syntax for `$s[0]`. The brace has to follow the variable directly, so
+            // that a missing `)` before a block is still reported as such.
+            if kind == &TokenKind::LeftBrace && current.span.start == self.previous.end && is_dereferencable(&lhs) && Precedence::Access.binding_power().0 >= bp {
+                self.deepen()?;
+
+                let kind = self.curly_offset(lhs)?;
+                lhs = Expression::new(kind, self.span_from(start));
+                last_ternary = None;
+                last_non_associative = None;
+
+                continue;
+            }
+
+            if let Some(precedence) = Precedence::postfix(kind) {
+                let (lbp, _) = precedence.binding_power();
+
+                if lbp < bp {
+                    break;
+                }
+
+                self.deepen()?;
+                self.next();
+
+                let kind = self.postfix(lhs, kind)?;
+                lhs = Expression::new(kind, self.span_from(start));
+                last_ternary = None;
+                last_non_associative = None;
+
+                continue;
+            }
+
+            if let Some(precedence) = Precedence::infix(kind) {
+                let (lbp, rbp) = precedence.binding_power();
+
+                // Assignments always bind to a variable on their left, so `!$a = foo()` negates the
+                // result of the assignment rather than trying to assign to `!$a`.
+                if lbp < bp && ! (is_assignment(kind) && is_assignable(&lhs)) {
+                    break;
+                }
+
+                if let Some(previous) = last_ternary {
+                    // Only short ternaries can be chained, e.g. `$a ?: $b ?: $c`.
+                    if matches!(kind, TokenKind::Question | TokenKind::QuestionColon) && ! (previous == &TokenKind::QuestionColon && kind == &TokenKind::QuestionColon) {
+                        return Err(ParseError::UnparenthesizedNestedTernary(self.current.span));
+                    }
+                }
+
+                // Comparisons are non-associative, so `1 < 2 < 3` needs parentheses. Ternaries are
+                // checked above instead, since short ternaries can be chained.
+                let non_associative = precedence.associativity() == Associativity::NonAssociative && precedence != Precedence::Ternary;
+                if non_associative && last_non_associative == Some(precedence) {
+                    return Err(ParseError::UnparenthesizedChainedComparison(self.current.span));
+                }
+
+                last_non_associative = if non_associative { Some(precedence) } else { None };
+
+                self.deepen()?;
+                self.next();
+
+                if current.kind != *kind {
+                    self.next();
+                }
+
+                last_ternary = match kind {
+                    TokenKind::Question | TokenKind::QuestionColon => Some(kind),
+                    _ => None,
+                };
+
+                let kind = self.infix_operator(lhs, kind, rbp, current.span)?;
+                lhs = Expression::new(kind, self.span_from(start));
+
+                continue;
+            }
+
+            break;
+        }
+
+        self.skip_comments();
+
+        Ok(lhs)
+    }
+
+    /// Count one more level of nesting for an operator applied in a loop rather than by recursion.
+    fn deepen(&mut self) -> ParseResult<()> {
+        if self.depth >= self.config.max_depth {
+            return Err(ParseError::TooDeep(self.current.span));
+        }
+
+        self.depth += 1;
+
+        Ok(())
+    }
+
+    /// Parse the right-hand side of an infix operator whose token has just been consumed.
+    fn infix_operator(&mut self, lhs: Expression, op: &TokenKind, rbp: u8, span: Span) -> ParseResult<ExpressionKind> {
+        match op {
+            TokenKind::Question => {
+                let then = self.expression(0)?;
+                expect!(self, TokenKind::Colon);
+                let otherwise = self.expression(rbp)?;
+                return Ok(ExpressionKind::Ternary { condition: Box::new(lhs), then: Some(Box::new(then)), r#else: Box::new(otherwise) });
+            },
+            TokenKind::Instanceof => {
+                let rhs = self.class_name_reference()?;
+                return Ok(ExpressionKind::Instanceof { lhs: Box::new(lhs), rhs: Box::new(rhs) });
+            },
+            _ => {},
+        }
+
+        let by_ref = op == &TokenKind::Equals && self.current.kind == TokenKind::Ampersand;
+        if by_ref {
+            self.next();
+        }
+
+        let rhs = self.expression(rbp)?;
+
+        Ok(match op {
+            TokenKind::QuestionColon => ExpressionKind::Ternary { condition: Box::new(lhs), then: None, r#else: Box::new(rhs) },
+            TokenKind::Coalesce => ExpressionKind::Coalesce { lhs: Box::new(lhs), rhs: Box::new(rhs) },
+            TokenKind::Equals if by_ref => ExpressionKind::AssignRef { var: Box::new(lhs), value: Box::new(rhs) },
+            TokenKind::Equals => ExpressionKind::Assign { var: Box::new(lhs), value: Box::new(rhs) },
+            TokenKind::CoalesceEqual => ExpressionKind::CoalesceAssign { var: Box::new(lhs), value: Box::new(rhs) },
+            _ => return infix(lhs, op, rhs).ok_or_else(|| ParseError::UnexpectedToken(op.to_string(), span)),
+        })
+    }
+
+    /// Parse `{$index}` after a variable, the old syntax for an array or string offset.
+    fn curly_offset(&mut self, lhs: Expression) -> ParseResult<ExpressionKind> {
+        let brace = self.current.span;
+
+        self.next();
+        let index = self.expression(0)?;
+        self.rbrace()?;
+
+        self.deprecated("array and string offset access with curly braces", PhpVersion::Php74, Some(PhpVersion::Php80), brace);
+
+        Ok(ExpressionKind::ArrayIndex { array: Box::new(lhs), index: Some(Box::new(index)) })
+    }
+
+    fn throw(&mut self) -> ParseResult<ExpressionKind> {
+        self.next();
+
+        let value = self.expression(0)?;
+
+        Ok(ExpressionKind::Throw { value: Box::new(value) })
+    }
+
+    fn yield_expression(&mut self) -> ParseResult<ExpressionKind> {
+        self.next();
+
+        let value = self.expression(0)?;
+
+        // FIXME: Check for presence of => here to allow yielding key and value.
+
+        Ok(ExpressionKind::Yield { value: Box::new(value) })
+    }
+
+    fn parenthesized(&mut self) -> ParseResult<ExpressionKind> {
+        self.next();
+
+        let e = self.expression(0)?;
+
+        self.rparen()?;
+
+        Ok(e.kind)
+    }
+
+    /// Parse an array literal, in either its `array()` or its `[]` form.
+    fn array(&mut self) -> ParseResult<ExpressionKind> {
+        let end = match self.current.kind {
+            TokenKind::Array => {
+                self.next();
+                self.lparen()?;
+
+                TokenKind::RightParen
+            },
+            _ => {
+                self.next();
+
+                TokenKind::RightBracket
+            },
+        };
+
+        let items = self.array_items(&end)?;
+
+        match end {
+            TokenKind::RightParen => self.rparen()?,
+            _ => self.rbracket()?,
+        }
+
+        Ok(ExpressionKind::Array { items })
+    }
+
+    fn magic_constant(&mut self) -> ParseResult<ExpressionKind> {
+        let constant = self.current.kind.clone().try_into()
+            .map_err(|kind: TokenKind| ParseError::UnexpectedToken(kind.to_string(), self.current.span))?;
+        self.next();
+        Ok(ExpressionKind::MagicConst { constant })
+    }
+
+    /// Parse a prefix operator such as `!` or a cast, along with its operand.
+    fn prefix_operator(&mut self) -> ParseResult<ExpressionKind> {
+        let Some(precedence) = Precedence::prefix(&self.current.kind) else {
+            return self.missing_operand();
         };
+
+        let op = &self.current.kind;
+        let span = self.current.span;
+
+        if op == &TokenKind::RealCast {
+            self.deprecated("the (real) cast", PhpVersion::Php74, Some(PhpVersion::Php80), span);
+        }
+
+        self.next();
+
+        let (rbp, _) = precedence.binding_power();
+        let rhs = self.expression(rbp)?;
+
+        prefix(op, rhs).ok_or_else(|| ParseError::UnexpectedToken(op.to_string(), span))
+    }
+
+    fn missing_operand(&mut self) -> ParseResult<ExpressionKind> {
+        // A stray `}` usually ends the enclosing block, so it's left to statement recovery.
+        if ! self.tolerant || self.current.kind == TokenKind::RightBrace {
+            return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span));
+        }
+
+        // Recovering twice at the same token means the caller is looping without making
+        // progress, so the enclosing statement is abandoned instead.
+        if self.recovered_at == Some(self.current.span.start) {
+            self.errors.pop();
+
+            return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span));
+        }
+
+        self.recovered_at = Some(self.current.span.start);
+        self.errors.push(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span));
+
+        // Leave closing tokens alone, so that the construct the operand belongs to can still be
+        // finished. The error then covers no source at all.
+        if ! matches!(self.current.kind, TokenKind::SemiColon | TokenKind::Comma | TokenKind::RightParen |
+            TokenKind::RightBracket | TokenKind::CloseTag | TokenKind::Eof) {
+            self.next();
+        }
+
+        Ok(ExpressionKind::Error)
+    }
+
+    /// Parse a `match` expression, starting at the `match` keyword.
+    fn match_expression(&mut self) -> ParseResult<ExpressionKind> {
+        self.requires(PhpVersion::Php80, "match expressions")?;
+
+        self.next();
+        self.lparen()?;
+
+        let condition = Box::new(self.expression(0)?);
+
+        self.rparen()?;
+        self.lbrace()?;
+
+        let mut arms = Vec::new();
+        while self.current.kind != TokenKind::RightBrace {
+            let mut conditions = Vec::new();
+
+            while self.current.kind != TokenKind::DoubleArrow {
+                if self.current.kind == TokenKind::Default {
+                    self.next();
+                    break;
+                }
+
+                conditions.push(self.expression(0)?);
+
+                self.optional_comma()?;
+            }
+
+            expect!(self, TokenKind::DoubleArrow);
+
+            let body = self.expression(0)?;
+
+            self.optional_comma()?;
+
+            arms.push(MatchArm {
+                conditions: if conditions.is_empty() { None } else { Some(conditions) },
+                body,
+            })
+        }
+
+        self.rbrace()?;
+
+        Ok(ExpressionKind::Match { condition, arms })
+    }
+
+    /// Parse a closure, starting at the `function` keyword.
+    fn closure(&mut self) -> ParseResult<ExpressionKind> {
+        let attributes = self.get_attributes();
+
+        self.next();
+
+        let returns_by_ref = self.optional_ampersand();
+
+        self.lparen()?;
+
+        let params = self.param_list()?;
+
+        self.rparen()?;
+
+        let mut uses = vec![];
+        if self.current.kind == TokenKind::Use {
+            self.next();
+
+            self.lparen()?;
+
+            while self.current.kind != TokenKind::RightParen {
+                let var = match self.current.kind {
+                    TokenKind::Ampersand => {
+                        self.next();
+
+                        match self.expression(0)? {
+                            s if matches!(s.kind, ExpressionKind::Variable { .. }) => ClosureUse { var: s, by_ref: true },
+                            _ => return Err(ParseError::UnexpectedToken("expected variable".into(), self.current.span))
+                        }
+                    },
+                    _ => match self.expression(0)? {
+                        s if matches!(s.kind, ExpressionKind::Variable { .. }) => ClosureUse { var: s, by_ref: false },
+                        _ => return Err(ParseError::UnexpectedToken("expected variable".into(), self.current.span))
+                    }
+                };
+
+                uses.push(var);
+
+                self.list_comma(&TokenKind::RightParen, List::Params)?;
+            }
+
+            self.rparen()?;
+        }
+
+        let mut return_type = None;
+        if self.current.kind == TokenKind::Colon || self.config.force_type_strings {
+            expect!(self, TokenKind::Colon);
+
+            return_type = Some(self.type_string()?);
+        }
+
+        self.lbrace()?;
+
+        let body = self.block(&TokenKind::RightBrace)?;
+
+        self.rbrace()?;
+
+        Ok(ExpressionKind::Closure { params, uses, return_type, returns_by_ref, body, attributes })
+    }
+
+    /// Parse an arrow function, starting at the `fn` keyword.
+    fn arrow_function(&mut self) -> ParseResult<ExpressionKind> {
+        self.requires(PhpVersion::Php74, "arrow functions")?;
+
+        let attributes = self.get_attributes();
+
+        self.next();
+
+        let returns_by_ref = self.optional_ampersand();
+
+        self.lparen()?;
+
+        let params = self.param_list()?;
+
+        self.rparen()?;
+
+        let mut return_type = None;
+
+        if self.current.kind == TokenKind::Colon || self.config.force_type_strings {
+            expect!(self, TokenKind::Colon);
+
+            return_type = Some(self.type_string()?);
+        }
+        
+        expect!(self, TokenKind::DoubleArrow);
+
+        let value = self.expression(0)?;
+
+        Ok(ExpressionKind::ArrowFunction { params, return_type, returns_by_ref, expr: Box::new(value), attributes })
+    }
+
+    /// Parse a `new` expression, including anonymous classes, starting at the `new` keyword.
+    fn new_expression(&mut self) -> ParseResult<ExpressionKind> {
+        self.next();
+
+        self.gather_attributes_for(AttributeTarget::AnonymousClass)?;
+
+        let mut args = vec![];
+        let target = if self.current.kind == TokenKind::Class {
+            let start = self.current.span;
+            let attributes = self.get_attributes();
+
+            self.next();
+
+            if self.current.kind == TokenKind::LeftParen {
+                self.lparen()?;
+
+                args = self.args_list()?;
+
+                self.rparen()?;
+            }
+
+            let mut extends: Option<Identifier> = None;
+
+            if self.current.kind == TokenKind::Extends {
+                self.next();
+                extends = Some(self.full_identifier()?);
+            }
+
+            let mut implements = Vec::new();
+            if self.current.kind == TokenKind::Implements {
+                self.next();
+
+                while self.current.kind != TokenKind::LeftBrace {
+                    self.optional_comma()?;
+
+                    implements.push(self.full_identifier()?);
+                }
+            }
+
+            self.lbrace()?;
+
+            let mut body = Vec::new();
+            while self.current.kind != TokenKind::RightBrace && ! self.is_eof() {
+                self.tolerant_statement(&mut body, Self::class_statement, &TokenKind::RightBrace)?;
+            }
+
+            self.rbrace()?;
+
+            Expression::new(ExpressionKind::AnonymousClass { extends, implements, body, attributes }, self.span_from(start))
+        } else {
+            self.class_name_reference()?
+        };
+
+        if self.current.kind == TokenKind::LeftParen {
+            self.lparen()?;
+
+            args = self.args_list()?;
+
+            self.rparen()?;
+        }
+
+        Ok(ExpressionKind::New { target: Box::new(target), args })
+    }
+
+    /// Parse `exit` or `die`, with its optional status in parentheses.
+    fn exit(&mut self) -> ParseResult<ExpressionKind> {
+        self.next();
+
+        let mut value = None;
+        if self.current.kind == TokenKind::LeftParen {
+            self.lparen()?;
+
+            if self.current.kind != TokenKind::RightParen {
+                value = Some(Box::new(self.expression(0)?));
+            }
+
+            self.rparen()?;
+        }
+
+        Ok(ExpressionKind::Exit { value })
+    }
+
+    fn postfix(&mut self, lhs: Expression, op: &TokenKind) -> Result<ExpressionKind, ParseError> {
+        match op {
+            TokenKind::LeftParen => self.call(lhs),
+            TokenKind::LeftBracket => self.array_index(lhs),
+            TokenKind::DoubleColon => self.static_member(lhs),
+            TokenKind::Arrow | TokenKind::NullsafeArrow => self.member(lhs, op == &TokenKind::NullsafeArrow),
+            TokenKind::Increment => Ok(ExpressionKind::PostIncrement { value: Box::new(lhs) }),
+            TokenKind::Decrement => Ok(ExpressionKind::PostDecrement { value: Box::new(lhs) }),
+            _ => Err(ParseError::UnexpectedToken(op.to_string(), self.current.span)),
+        }
+    }
+
+    fn call(&mut self, lhs: Expression) -> ParseResult<ExpressionKind> {
+        let args = self.args_list()?;
+
+        self.rparen()?;
+
+        Ok(ExpressionKind::Call { target: Box::new(lhs), args })
+    }
+
+    fn array_index(&mut self, lhs: Expression) -> ParseResult<ExpressionKind> {
+        if self.current.kind == TokenKind::RightBracket {
+            self.next();
+
+            return Ok(ExpressionKind::ArrayIndex { array: Box::new(lhs), index: None });
+        }
+
+        let index = self.expression(0)?;
+
+        self.rbracket()?;
+
+        Ok(ExpressionKind::ArrayIndex { array: Box::new(lhs), index: Some(Box::new(index)) })
+    }
+
+    /// Parse what follows `::`, which is a static property, a class constant or a static method call.
+    fn static_member(&mut self, lhs: Expression) -> ParseResult<ExpressionKind> {
+        Ok(match &self.current.kind {
+            // `Foo::$bar()` calls the method named by `$bar`, rather than the static property.
+            TokenKind::Variable(_) => {
+                let var = self.variable()?;
+
+                match self.current.kind {
+                    TokenKind::LeftParen => self.dynamic_static_method_call(lhs, var)?,
+                    _ => ExpressionKind::StaticPropertyFetch { target: Box::new(lhs), property: Box::new(var) },
+                }
+            },
+            TokenKind::LeftBrace => {
+                self.lbrace()?;
+                let constant = self.expression(0)?;
+                self.rbrace()?;
+
+                match self.current.kind {
+                    TokenKind::LeftParen => self.dynamic_static_method_call(lhs, constant)?,
+                    _ => ExpressionKind::DynamicConstFetch { target: Box::new(lhs), constant: Box::new(constant) },
+                }
+            },
+            _ => {
+                let start = self.current.span;
+                let name = if self.current.kind == TokenKind::Class {
+                    self.next();
+
+                    String::from("class")
+                } else {
+                    self.ident_maybe_reserved()?
+                };
+                let ident = Identifier::new(name, self.span_from(start));
+
+                if self.current.kind == TokenKind::LeftParen {
+                    self.lparen()?;
+
+                    let args = self.args_list()?;
+
+                    self.rparen()?;
+
+                    ExpressionKind::StaticMethodCall { target: Box::new(lhs), method: ident, args }
+                } else {
+                    ExpressionKind::ConstFetch { target: Box::new(lhs), constant: ident }
+                }
+            },
+        })
+    }
+
+    /// Parse the arguments of a static method call whose name is given by an expression.
+    fn dynamic_static_method_call(&mut self, lhs: Expression, method: Expression) -> ParseResult<ExpressionKind> {
+        self.lparen()?;
+
+        let args = self.args_list()?;
+
+        self.rparen()?;
+
+        Ok(ExpressionKind::DynamicStaticMethodCall { target: Box::new(lhs), method: Box::new(method), args })
+    }
+
+    /// Parse what follows `->` or `?->`, which is a property or a method call.
+    fn member(&mut self, lhs: Expression, nullsafe: bool) -> ParseResult<ExpressionKind> {
+        let property = self.member_name()?;
+
+        if self.current.kind != TokenKind::LeftParen {
+            return Ok(match nullsafe {
+                true => ExpressionKind::NullsafePropertyFetch { target: Box::new(lhs), property: Box::new(property) },
+                false => ExpressionKind::PropertyFetch { target: Box::new(lhs), property: Box::new(property) },
+            });
+        }
+
+        self.next();
+
+        let args = self.args_list()?;
+
+        self.rparen()?;
+
+        Ok(match nullsafe {
+            true => ExpressionKind::NullsafeMethodCall { target: Box::new(lhs), method: Box::new(property), args },
+            false => ExpressionKind::MethodCall { target: Box::new(lhs), method: Box::new(property), args },
+        })
+    }
+
+    /// Track how deeply statements and expressions are nested, failing once the configured limit
+    /// is reached.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> ParseResult<T>) -> ParseResult<T> {
+        if self.depth >= self.config.max_depth {
+            return Err(ParseError::TooDeep(self.current.span));
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+
+        result
+    }
+
+    /// Reject the current construct if the configured PHP version is older than `version`.
+    fn requires(&self, version: PhpVersion, feature: &'static str) -> ParseResult<()> {
+        if self.config.php_version < version {
+            return Err(ParseError::RequiresPhpVersion { feature, version, span: self.current.span });
+        }
+
+        Ok(())
+    }
+
+    /// Reject a modifier that the configured PHP version doesn't have, such as `readonly` or `public(set)`.
+    fn modifier(&self) -> ParseResult<()> {
+        match self.current.kind {
+            TokenKind::Readonly => self.requires(PhpVersion::Php81, "readonly properties"),
+            TokenKind::PublicSet | TokenKind::ProtectedSet | TokenKind::PrivateSet => self.requires(PhpVersion::Php84, "asymmetric visibility"),
+            _ => Ok(()),
+        }
+    }
+
+    /// Create a span that starts at `start` and ends with the last token that was consumed.
+    fn span_from(&self, start: Span) -> Span {
+        Span { end: self.previous.end.max(start.start), ..start }
+    }
+
+    fn is_eof(&self) -> bool {
+        self.current.kind == TokenKind::Eof
+    }
+
+    pub fn next(&mut self) {
+        if ! matches!(self.current.kind, TokenKind::Comment(_) | TokenKind::DocComment(_) | TokenKind::Eof) {
+            self.previous = self.current.span;
+        }
+
+        // Remember the most recent doc comment so that the declaration it belongs to can claim it.
+        // It's forgotten at the end of a statement or block so that it can't attach to a later one.
+        match &self.current.kind {
+            TokenKind::DocComment(doc) => self.doc_comment = Some(doc.to_string()),
+            TokenKind::SemiColon | TokenKind::LeftBrace | TokenKind::RightBrace | TokenKind::CloseTag => self.doc_comment = None,
+            _ => {},
+        }
+
+        self.current = self.peek;
+        self.peek = self.tokens.get(self.cursor).unwrap_or(&EOF);
+        self.cursor += 1;
+    }
+}
+
+fn prefix(op: &TokenKind, rhs: Expression) -> Option<ExpressionKind> {
+    Some(match op {
+        TokenKind::Bang => ExpressionKind::BooleanNot { value: Box::new(rhs) },
+        TokenKind::Tilde => ExpressionKind::BitwiseNot { value: Box::new(rhs) },
+        TokenKind::Minus => ExpressionKind::Negate { value: Box::new(rhs) },
+        TokenKind::Plus => ExpressionKind::UnaryPlus { value: Box::new(rhs) },
+        TokenKind::At => ExpressionKind::ErrorSuppress { value: Box::new(rhs) },
+        TokenKind::Print => ExpressionKind::Print { value: Box::new(rhs) },
+        TokenKind::Clone => ExpressionKind::Clone { target: Box::new(rhs) },
+        TokenKind::Include => ExpressionKind::Include { path: Box::new(rhs) },
+        TokenKind::IncludeOnce => ExpressionKind::IncludeOnce { path: Box::new(rhs) },
+        TokenKind::Require => ExpressionKind::Require { path: Box::new(rhs) },
+        TokenKind::RequireOnce => ExpressionKind::RequireOnce { path: Box::new(rhs) },
+        TokenKind::Increment => ExpressionKind::PreIncrement { value: Box::new(rhs) },
+        TokenKind::Decrement => ExpressionKind::PreDecrement { value: Box::new(rhs) },
+        TokenKind::StringCast | TokenKind::ObjectCast | TokenKind::BoolCast | TokenKind::IntCast |
+        TokenKind::DoubleCast | TokenKind::RealCast | TokenKind::ArrayCast => ExpressionKind::Cast { kind: op.try_into().ok()?, value: Box::new(rhs) },
+        _ => return None,
+    })
+}
+
+fn infix(lhs: Expression, op: &TokenKind, rhs: Expression) -> Option<ExpressionKind> {
+    Some(ExpressionKind::Infix {
+        lhs: Box::new(lhs),
+        op: op.try_into().ok()?,
+        rhs: Box::new(rhs)
+    })
+}
+
+fn is_dereferencable(e: &Expression) -> bool {
+    matches!(e.kind,
+        ExpressionKind::Variable { .. } | ExpressionKind::ArrayIndex { .. } | ExpressionKind::PropertyFetch { .. } |
+        ExpressionKind::StaticPropertyFetch { .. } | ExpressionKind::Dynamic { .. }
+    )
+}
+
+fn is_assignment(t: &TokenKind) -> bool {
+    matches!(t,
+        TokenKind::Equals | TokenKind::PlusEquals | TokenKind::MinusEquals | TokenKind::DotEquals |
+        TokenKind::CoalesceEqual | TokenKind::AsteriskEqual | TokenKind::SlashEquals | TokenKind::PercentEquals |
+        TokenKind::PowEquals | TokenKind::AndEqual | TokenKind::PipeEquals | TokenKind::CaretEquals |
+        TokenKind::LeftShiftEquals | TokenKind::RightShiftEquals
+    )
+}
+
+fn is_assignable(e: &Expression) -> bool {
+    matches!(e.kind,
+        ExpressionKind::Variable { .. } | ExpressionKind::ArrayIndex { .. } | ExpressionKind::PropertyFetch { .. } |
+        ExpressionKind::StaticPropertyFetch { .. } | ExpressionKind::Array { .. } | ExpressionKind::Dynamic { .. }
+    )
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    ExpectedToken { expected: Vec<TokenKind>, found: Token, suggestion: Option<Box<Suggestion>> },
+    UnexpectedToken(String, Span),
+    UnexpectedEndOfFile,
+    InvalidClassStatement(String, Span),
+    InvalidAbstractFinalFlagCombination(Span),
+    ConstantCannotBeStatic(Span),
+    ConstantCannotBePrivateFinal(Span),
+    TraitCannotContainConstant(Span),
+    TryWithoutCatchOrFinally(Span),
+    InvalidCatchArgumentType(Span),
+    UnparenthesizedNestedTernary(Span),
+    UnparenthesizedChainedComparison(Span),
+    RequiresPhpVersion { feature: &'static str, version: PhpVersion, span: Span },
+    TooDeep(Span),
+}
+
+impl ParseError {
+    /// The location in the source that the error refers to, if it has one.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::ExpectedToken { found, .. } => Some(found.span),
+            Self::UnexpectedToken(_, span) | Self::InvalidClassStatement(_, span) => Some(*span),
+            Self::InvalidAbstractFinalFlagCombination(span) | Self::ConstantCannotBeStatic(span) | Self::ConstantCannotBePrivateFinal(span) |
+            Self::TraitCannotContainConstant(span) | Self::TryWithoutCatchOrFinally(span) | Self::InvalidCatchArgumentType(span) |
+            Self::UnparenthesizedNestedTernary(span) | Self::UnparenthesizedChainedComparison(span) => Some(*span),
+            Self::RequiresPhpVersion { span, .. } | Self::TooDeep(span) => Some(*span),
+            Self::UnexpectedEndOfFile => None,
+        }
+    }
+
+    /// A description of the error, without the location that `Display` includes.
+    pub fn message(&self) -> String {
+        match self {
+            Self::ExpectedToken { expected, found, .. } => {
+                let mut expected: Vec<String> = expected.iter().map(describe_expected).collect();
+                expected.dedup();
+
+                match &found.kind {
+                    TokenKind::Eof => format!("expected {}, found end of file", expected.join(" or ")),
+                    kind => format!("expected {}, found `{}`", expected.join(" or "), kind),
+                }
+            },
+            Self::InvalidClassStatement(message, _) => message.clone(),
+            Self::UnexpectedEndOfFile => "unexpected end of file".into(),
+            Self::UnexpectedToken(message, _) => format!("unexpected token {}", message),
+            Self::InvalidAbstractFinalFlagCombination(_) => "final cannot be used on an abstract class member".into(),
+            Self::ConstantCannotBeStatic(_) => "class constant cannot be marked static".into(),
+            Self::ConstantCannotBePrivateFinal(_) => "private class constant cannot be marked final since it is not visible to other classes".into(),
+            Self::TraitCannotContainConstant(_) => "traits cannot contain constants".into(),
+            Self::TryWithoutCatchOrFinally(_) => "cannot use try without catch or finally".into(),
+            Self::InvalidCatchArgumentType(_) => "catch types must either describe a single type or union of types".into(),
+            Self::UnparenthesizedNestedTernary(_) => "nested ternary operators require explicit parentheses".into(),
+            Self::UnparenthesizedChainedComparison(_) => "comparison operators are non-associative and require explicit parentheses".into(),
+            // Features are named in the plural, such as "enums", unless they're uncountable.
+            Self::RequiresPhpVersion { feature, version, .. } if feature.ends_with('s') => format!("{} require PHP {} or newer", feature, version),
+            Self::RequiresPhpVersion { feature, version, .. } => format!("{} requires PHP {} or newer", feature, version),
+            Self::TooDeep(_) => "maximum nesting depth exceeded".into(),
+        }
+    }
+
+    /// A likely fix for the error, such as the keyword that a misspelled identifier was meant to be.
+    pub fn suggestion(&self) -> Option<&Suggestion> {
+        match self {
+            Self::ExpectedToken { suggestion, .. } => suggestion.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Tokens that carry a value are described by what they are rather than by their value.
+pub(crate) fn describe_expected(kind: &TokenKind) -> String {
+    match kind {
+        TokenKind::Identifier(_) | TokenKind::QualifiedIdentifier(_) | TokenKind::FullyQualifiedIdentifier(_) => "identifier".into(),
+        TokenKind::Variable(_) => "variable".into(),
+        TokenKind::DocOpen(_) => "heredoc".into(),
+        TokenKind::NowdocOpen(_) => "nowdoc".into(),
+        TokenKind::HaltCompilerData(..) => "data after __halt_compiler".into(),
+        TokenKind::DocClose => kind.to_string(),
+        TokenKind::Eof => "end of file".into(),
+        _ => format!("`{}`", kind),
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Parse error: {}", self.message())?;
+
+        match self {
+            Self::ExpectedToken { found, .. } if found.kind == TokenKind::Eof => Ok(()),
+            Self::UnexpectedEndOfFile => write!(f, "."),
+            Self::ExpectedToken { found: Token { span, .. }, .. } | Self::InvalidClassStatement(_, span) | Self::UnexpectedToken(_, span) => {
+                write!(f, " on line {} column {}", span.line, span.col)
+            },
+            _ => match self.span() {
+                Some(span) => write!(f, " on line {}", span.line),
+                None => Ok(()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_lexer::{Lexer, Span, TokenKind};
+    use crate::{Statement, StatementKind, Expression, Param, ExpressionKind, ast::{InfixOp, ElseIf, MethodFlag, ArrayItem, Arg, PropertyFlag, ClosureUse, CastKind, MagicConst, BackedEnumType, ConstFlag, Constant, PropertyHook, PropertyHookBody}, Type, Identifier, Case, Attribute, AttributeGroup, StringPart};
+    use super::{Parser, ParserConfig, PhpVersion, ParseError, ParseWarning, TrailingCommas};
+    use crate::Error;
+
+    macro_rules! function {
+        ($name:literal, $params:expr, $body:expr) => {
+            StatementKind::Function {
+                name: $name.to_string().into(),
+                params: $params.to_vec().into_iter().map(|p: &str| Param::from(p)).collect::<Vec<Param>>(),
+                body: $body.to_vec(),
+                return_type: None,
+                returns_by_ref: false,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        };
+    }
+
+    macro_rules! class {
+        ($name:literal) => {
+            StatementKind::Class {
+                name: $name.to_string().into(),
+                body: vec![],
+                extends: None,
+                implements: vec![],
+                flag: None,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        };
+        ($name:literal, $body:expr) => {
+            StatementKind::Class {
+                name: $name.to_string().into(),
+                body: $body.to_vec(),
+                extends: None,
+                implements: vec![],
+                flag: None,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        };
+        ($name:literal, $extends:expr, $implements:expr, $body:expr) => {
+            StatementKind::Class {
+                name: $name.to_string().into(),
+                body: $body.to_vec(),
+                extends: $extends,
+                implements: $implements.to_vec(),
+                flag: None,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        };
+    }
+
+    macro_rules! method {
+        ($name:literal, $params:expr, $flags:expr, $body:expr) => {
+            StatementKind::Method {
+                name: $name.to_string().into(),
+                params: $params.to_vec().into_iter().map(|p: &str| Param::from(p)).collect::<Vec<Param>>(),
+                flags: $flags.to_vec(),
+                body: $body.to_vec(),
+                return_type: None,
+                returns_by_ref: false,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        };
+    }
+
+    macro_rules! expr {
+        ($expr:expr) => {
+            StatementKind::Expression {
+                expr: $expr,
+            }.into()
+        };
+    }
+
+    #[test]
+    fn instanceof() {
+        assert_ast("<?php $foo instanceof Foo;", &[
+            expr!(ExpressionKind::Instanceof {
+                lhs: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                rhs: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into())
+            }.into())
+        ]);
+
+        assert_ast("<?php $foo instanceof Foo && $foo instanceof Foo;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Instanceof {
+                    lhs: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                    rhs: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into())
+                }.into()),
+                op: InfixOp::And,
+                rhs: Box::new(ExpressionKind::Instanceof {
+                    lhs: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                    rhs: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into())
+                }.into())
+            }.into())
+        ]);
+
+        assert_ast("<?php $foo instanceof $bar; $foo instanceof static;", &[
+            expr!(ExpressionKind::Instanceof {
+                lhs: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                rhs: Box::new(ExpressionKind::Variable { name: "bar".into() }.into())
+            }.into()),
+            expr!(ExpressionKind::Instanceof {
+                lhs: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                rhs: Box::new(ExpressionKind::Static.into())
+            }.into()),
+        ]);
+
+        assert_ast("<?php !$foo instanceof Foo;", &[
+            expr!(ExpressionKind::BooleanNot {
+                value: Box::new(ExpressionKind::Instanceof {
+                    lhs: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                    rhs: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into())
+                }.into())
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        assert_ast("<?php error_reporting(E_ALL & ~E_NOTICE);", &[
+            expr!(ExpressionKind::Call {
+                target: Box::new(ExpressionKind::Identifier { name: "error_reporting".into() }.into()),
+                args: vec![
+                    Arg {
+                        name: None,
+                        value: ExpressionKind::Infix {
+                            lhs: Box::new(ExpressionKind::Identifier { name: "E_ALL".into() }.into()),
+                            op: InfixOp::BitwiseAnd,
+                            rhs: Box::new(ExpressionKind::BitwiseNot {
+                                value: Box::new(ExpressionKind::Identifier { name: "E_NOTICE".into() }.into())
+                            }.into()),
+                        }.into(),
+                        unpack: false,
+                    },
+                ],
+            }.into())
+        ]);
+
+        assert_ast("<?php $a | $b ^ $c & $d == $e;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                op: InfixOp::BitwiseOr,
+                rhs: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::Variable { name: "b".into() }.into()),
+                    op: InfixOp::BitwiseXor,
+                    rhs: Box::new(ExpressionKind::Infix {
+                        lhs: Box::new(ExpressionKind::Variable { name: "c".into() }.into()),
+                        op: InfixOp::BitwiseAnd,
+                        rhs: Box::new(ExpressionKind::Infix {
+                            lhs: Box::new(ExpressionKind::Variable { name: "d".into() }.into()),
+                            op: InfixOp::Equals,
+                            rhs: Box::new(ExpressionKind::Variable { name: "e".into() }.into()),
+                        }.into()),
+                    }.into()),
+                }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php $a && $b | $c;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                op: InfixOp::And,
+                rhs: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::Variable { name: "b".into() }.into()),
+                    op: InfixOp::BitwiseOr,
+                    rhs: Box::new(ExpressionKind::Variable { name: "c".into() }.into()),
+                }.into()),
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn logical_operators() {
+        assert_ast("<?php $a && $b || !$c;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                    op: InfixOp::And,
+                    rhs: Box::new(ExpressionKind::Variable { name: "b".into() }.into()),
+                }.into()),
+                op: InfixOp::Or,
+                rhs: Box::new(ExpressionKind::BooleanNot {
+                    value: Box::new(ExpressionKind::Variable { name: "c".into() }.into())
+                }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php $a = true and false;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Assign {
+                    var: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                    value: Box::new(ExpressionKind::Bool { value: true }.into()),
+                }.into()),
+                op: InfixOp::LogicalAnd,
+                rhs: Box::new(ExpressionKind::Bool { value: false }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php $a or $b xor $c AND $d;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                op: InfixOp::LogicalOr,
+                rhs: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::Variable { name: "b".into() }.into()),
+                    op: InfixOp::LogicalXor,
+                    rhs: Box::new(ExpressionKind::Infix {
+                        lhs: Box::new(ExpressionKind::Variable { name: "c".into() }.into()),
+                        op: InfixOp::LogicalAnd,
+                        rhs: Box::new(ExpressionKind::Variable { name: "d".into() }.into()),
+                    }.into()),
+                }.into()),
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn increment_decrement() {
+        assert_ast("<?php ++$i; $i++; --$i; $i--;", &[
+            expr!(ExpressionKind::PreIncrement {
+                value: Box::new(ExpressionKind::Variable { name: "i".into() }.into())
+            }.into()),
+            expr!(ExpressionKind::PostIncrement {
+                value: Box::new(ExpressionKind::Variable { name: "i".into() }.into())
+            }.into()),
+            expr!(ExpressionKind::PreDecrement {
+                value: Box::new(ExpressionKind::Variable { name: "i".into() }.into())
+            }.into()),
+            expr!(ExpressionKind::PostDecrement {
+                value: Box::new(ExpressionKind::Variable { name: "i".into() }.into())
+            }.into()),
+        ]);
+
+        assert_ast("<?php ++$foo->bar; $foo['bar']--;", &[
+            expr!(ExpressionKind::PreIncrement {
+                value: Box::new(ExpressionKind::PropertyFetch {
+                    target: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                    property: Box::new(ExpressionKind::Identifier { name: "bar".into() }.into())
+                }.into())
+            }.into()),
+            expr!(ExpressionKind::PostDecrement {
+                value: Box::new(ExpressionKind::ArrayIndex {
+                    array: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                    index: Some(Box::new(ExpressionKind::ConstantString { value: "bar".into() }.into()))
+                }.into())
+            }.into()),
+        ]);
+    }
+
+    #[test]
+    fn pow() {
+        assert_ast("<?php 2 ** 2;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Int { i: 2 }.into()),
+                op: InfixOp::Pow,
+                rhs: Box::new(ExpressionKind::Int { i: 2 }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php 2 ** 3 ** 4;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Int { i: 2 }.into()),
+                op: InfixOp::Pow,
+                rhs: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::Int { i: 3 }.into()),
+                    op: InfixOp::Pow,
+                    rhs: Box::new(ExpressionKind::Int { i: 4 }.into()),
+                }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php -2 ** 2;", &[
+            expr!(ExpressionKind::Negate {
+                value: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::Int { i: 2 }.into()),
+                    op: InfixOp::Pow,
+                    rhs: Box::new(ExpressionKind::Int { i: 2 }.into()),
+                }.into()),
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn unary_minus_and_plus() {
+        assert_ast("<?php return -1;", &[
+            StatementKind::Return {
+                value: Some(ExpressionKind::Negate {
+                    value: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                }.into()),
+            }.into()
+        ]);
+
+        assert_ast("<?php -1 + 2; +$n * -$x;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Negate {
+                    value: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                }.into()),
+                op: InfixOp::Add,
+                rhs: Box::new(ExpressionKind::Int { i: 2 }.into()),
+            }.into()),
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::UnaryPlus {
+                    value: Box::new(ExpressionKind::Variable { name: "n".into() }.into()),
+                }.into()),
+                op: InfixOp::Mul,
+                rhs: Box::new(ExpressionKind::Negate {
+                    value: Box::new(ExpressionKind::Variable { name: "x".into() }.into()),
+                }.into()),
+            }.into()),
+        ]);
+    }
+
+    #[test]
+    fn modulo() {
+        assert_ast("<?php 1 + 2 % 3;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                op: InfixOp::Add,
+                rhs: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::Int { i: 2 }.into()),
+                    op: InfixOp::Mod,
+                    rhs: Box::new(ExpressionKind::Int { i: 3 }.into()),
+                }.into()),
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn ternary() {
+        assert_ast("<?php 1 ? 2 : 3;", &[
+            expr!(ExpressionKind::Ternary {
+                condition: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                then: Some(Box::new(ExpressionKind::Int { i: 2 }.into())),
+                r#else: Box::new(ExpressionKind::Int { i: 3 }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php 1 ? 2 ? 3 : 4 : 5;", &[
+            expr!(ExpressionKind::Ternary {
+                condition: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                then: Some(Box::new(ExpressionKind::Ternary {
+                    condition: Box::new(ExpressionKind::Int { i: 2 }.into()),
+                    then: Some(Box::new(ExpressionKind::Int { i: 3 }.into())),
+                    r#else: Box::new(ExpressionKind::Int { i: 4 }.into()),
+                }.into())),
+                r#else: Box::new(ExpressionKind::Int { i: 5 }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php (1 ? 2 : 3) ? 4 : 5;", &[
+            expr!(ExpressionKind::Ternary {
+                condition: Box::new(ExpressionKind::Ternary {
+                    condition: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                    then: Some(Box::new(ExpressionKind::Int { i: 2 }.into())),
+                    r#else: Box::new(ExpressionKind::Int { i: 3 }.into()),
+                }.into()),
+                then: Some(Box::new(ExpressionKind::Int { i: 4 }.into())),
+                r#else: Box::new(ExpressionKind::Int { i: 5 }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php 1 && 2 ? 3 : 4;", &[
+            expr!(ExpressionKind::Ternary {
+                condition: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                    op: InfixOp::And,
+                    rhs: Box::new(ExpressionKind::Int { i: 2 }.into()),
+                }.into()),
+                then: Some(Box::new(ExpressionKind::Int { i: 3 }.into())),
+                r#else: Box::new(ExpressionKind::Int { i: 4 }.into()),
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn short_ternary() {
+        assert_ast("<?php 1 ?: 2;", &[
+            expr!(ExpressionKind::Ternary {
+                condition: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                then: None,
+                r#else: Box::new(ExpressionKind::Int { i: 2 }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php 1 ? : 2;", &[
+            expr!(ExpressionKind::Ternary {
+                condition: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                then: None,
+                r#else: Box::new(ExpressionKind::Int { i: 2 }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php 1 ?: 2 ? : 3;", &[
+            expr!(ExpressionKind::Ternary {
+                condition: Box::new(ExpressionKind::Ternary {
+                    condition: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                    then: None,
+                    r#else: Box::new(ExpressionKind::Int { i: 2 }.into()),
+                }.into()),
+                then: None,
+                r#else: Box::new(ExpressionKind::Int { i: 3 }.into()),
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn unparenthesized_nested_ternary() {
+        assert_parse_error("<?php 1 ? 2 : 3 ? 4 : 5;");
+        assert_parse_error("<?php 1 ?: 2 ? 3 : 4;");
+        assert_parse_error("<?php 1 ? 2 : 3 ?: 4;");
+    }
+
+    #[test]
+    fn coalesce() {
+        assert_ast("<?php 1 ?? 2;", &[
+            expr!(ExpressionKind::Coalesce {
+                lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                rhs: Box::new(ExpressionKind::Int { i: 2 }.into())
+            }.into())
+        ]);
+
+        assert_ast("<?php 1 ?? 2 ?? 3;", &[
+            expr!(ExpressionKind::Coalesce {
+                lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                rhs: Box::new(ExpressionKind::Coalesce {
+                    lhs: Box::new(ExpressionKind::Int { i: 2 }.into()),
+                    rhs: Box::new(ExpressionKind::Int { i: 3 }.into())
+                }.into())
+            }.into())
+        ]);
+
+        assert_ast("<?php 1 || 2 ?? 3 ? 4 : 5;", &[
+            expr!(ExpressionKind::Ternary {
+                condition: Box::new(ExpressionKind::Coalesce {
+                    lhs: Box::new(ExpressionKind::Infix {
+                        lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                        op: InfixOp::Or,
+                        rhs: Box::new(ExpressionKind::Int { i: 2 }.into()),
+                    }.into()),
+                    rhs: Box::new(ExpressionKind::Int { i: 3 }.into())
+                }.into()),
+                then: Some(Box::new(ExpressionKind::Int { i: 4 }.into())),
+                r#else: Box::new(ExpressionKind::Int { i: 5 }.into()),
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn coalesce_assign() {
+        assert_ast("<?php $foo ??= $bar ?? 1;", &[
+            expr!(ExpressionKind::CoalesceAssign {
+                var: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                value: Box::new(ExpressionKind::Coalesce {
+                    lhs: Box::new(ExpressionKind::Variable { name: "bar".into() }.into()),
+                    rhs: Box::new(ExpressionKind::Int { i: 1 }.into())
+                }.into())
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn assign_ref() {
+        assert_ast("<?php $a = &$b;", &[
+            expr!(ExpressionKind::AssignRef {
+                var: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                value: Box::new(ExpressionKind::Variable { name: "b".into() }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php $a = &$b->c;", &[
+            expr!(ExpressionKind::AssignRef {
+                var: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                value: Box::new(ExpressionKind::PropertyFetch {
+                    target: Box::new(ExpressionKind::Variable { name: "b".into() }.into()),
+                    property: Box::new(ExpressionKind::Identifier { name: "c".into() }.into()),
+                }.into()),
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn interpolated_strings() {
+        assert_ast(r#"<?php "Hello $name, {$user->messages[0]} and $arr[key]";"#, &[
+            expr!(ExpressionKind::InterpolatedString {
+                parts: vec![
+                    StringPart::Const("Hello ".into()),
+                    StringPart::Expr(Box::new(ExpressionKind::Variable { name: "name".into() }.into())),
+                    StringPart::Const(", ".into()),
+                    StringPart::Expr(Box::new(ExpressionKind::ArrayIndex {
+                        array: Box::new(ExpressionKind::PropertyFetch {
+                            target: Box::new(ExpressionKind::Variable { name: "user".into() }.into()),
+                            property: Box::new(ExpressionKind::Identifier { name: "messages".into() }.into()),
+                        }.into()),
+                        index: Some(Box::new(ExpressionKind::Int { i: 0 }.into())),
+                    }.into())),
+                    StringPart::Const(" and ".into()),
+                    StringPart::Expr(Box::new(ExpressionKind::ArrayIndex {
+                        array: Box::new(ExpressionKind::Variable { name: "arr".into() }.into()),
+                        index: Some(Box::new(ExpressionKind::ConstantString { value: "key".into() }.into())),
+                    }.into())),
+                ],
+            }.into())
+        ]);
+
+        assert_ast(r#"<?php "$obj->prop()";"#, &[
+            expr!(ExpressionKind::InterpolatedString {
+                parts: vec![
+                    StringPart::Expr(Box::new(ExpressionKind::PropertyFetch {
+                        target: Box::new(ExpressionKind::Variable { name: "obj".into() }.into()),
+                        property: Box::new(ExpressionKind::Identifier { name: "prop".into() }.into()),
+                    }.into())),
+                    StringPart::Const("()".into()),
+                ],
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn heredocs_and_nowdocs() {
+        assert_ast("<?php function foo($a = <<<'EOT'\n    bar\n    EOT) {}", &[
+            StatementKind::Function {
+                name: "foo".to_string().into(),
+                params: vec![
+                    Param {
+                        name: ExpressionKind::Variable { name: "a".into() }.into(),
+                        r#type: None,
+                        by_ref: false,
+                        variadic: false,
+                        default: Some(ExpressionKind::Nowdoc { label: "EOT".into(), value: "bar".into() }.into()),
+                        flags: vec![],
+                        attributes: vec![],
+                        span: Span::default(),
+                    }
+                ],
+                body: vec![],
+                return_type: None,
+                returns_by_ref: false,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        ]);
+
+        assert_ast("<?php [<<<EOT\nHello $name\nEOT, 1];", &[
+            expr!(ExpressionKind::Array {
+                items: vec![
+                    ArrayItem {
+                        key: None,
+                        value: ExpressionKind::Heredoc {
+                            label: "EOT".into(),
+                            parts: vec![
+                                StringPart::Const("Hello ".into()),
+                                StringPart::Expr(Box::new(ExpressionKind::Variable { name: "name".into() }.into())),
+                            ],
+                        }.into(),
+                        unpack: false,
+                        by_ref: false,
+                    },
+                    ArrayItem { key: None, value: ExpressionKind::Int { i: 1 }.into(), unpack: false, by_ref: false },
+                ],
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn casts() {
+        assert_ast("<?php (int) $a + 1;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Cast {
+                    kind: CastKind::Int,
+                    value: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                }.into()),
+                op: InfixOp::Add,
+                rhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php (array) $a->b;", &[
+            expr!(ExpressionKind::Cast {
+                kind: CastKind::Array,
+                value: Box::new(ExpressionKind::PropertyFetch {
+                    target: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                    property: Box::new(ExpressionKind::Identifier { name: "b".into() }.into()),
+                }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php (string) (bool) $a;", &[
+            expr!(ExpressionKind::Cast {
+                kind: CastKind::String,
+                value: Box::new(ExpressionKind::Cast {
+                    kind: CastKind::Bool,
+                    value: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                }.into()),
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn error_suppress() {
+        assert_ast("<?php @file_get_contents($url);", &[
+            expr!(ExpressionKind::ErrorSuppress {
+                value: Box::new(ExpressionKind::Call {
+                    target: Box::new(ExpressionKind::Identifier { name: "file_get_contents".into() }.into()),
+                    args: vec![
+                        Arg { name: None, value: ExpressionKind::Variable { name: "url".into() }.into(), unpack: false },
+                    ],
+                }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php @$a['b'] . 'c';", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::ErrorSuppress {
+                    value: Box::new(ExpressionKind::ArrayIndex {
+                        array: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                        index: Some(Box::new(ExpressionKind::ConstantString { value: "b".into() }.into())),
+                    }.into()),
+                }.into()),
+                op: InfixOp::Concat,
+                rhs: Box::new(ExpressionKind::ConstantString { value: "c".into() }.into()),
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn print() {
+        assert_ast("<?php print 'a' . 'b';", &[
+            expr!(ExpressionKind::Print {
+                value: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::ConstantString { value: "a".into() }.into()),
+                    op: InfixOp::Concat,
+                    rhs: Box::new(ExpressionKind::ConstantString { value: "b".into() }.into()),
+                }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php $a && print $b = 1;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                op: InfixOp::And,
+                rhs: Box::new(ExpressionKind::Print {
+                    value: Box::new(ExpressionKind::Assign {
+                        var: Box::new(ExpressionKind::Variable { name: "b".into() }.into()),
+                        value: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                    }.into()),
+                }.into()),
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn exit() {
+        assert_ast("<?php exit; exit(); die(1);", &[
+            expr!(ExpressionKind::Exit { value: None }.into()),
+            expr!(ExpressionKind::Exit { value: None }.into()),
+            expr!(ExpressionKind::Exit { value: Some(Box::new(ExpressionKind::Int { i: 1 }.into())) }.into()),
+        ]);
+
+        assert_ast("<?php $a || die('error');", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                op: InfixOp::Or,
+                rhs: Box::new(ExpressionKind::Exit {
+                    value: Some(Box::new(ExpressionKind::ConstantString { value: "error".into() }.into())),
+                }.into()),
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn clone() {
+        assert_ast("<?php clone $a->b();", &[
+            expr!(ExpressionKind::Clone {
+                target: Box::new(ExpressionKind::MethodCall {
+                    target: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                    method: Box::new(ExpressionKind::Identifier { name: "b".into() }.into()),
+                    args: vec![],
+                }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php clone $a ?? $b;", &[
+            expr!(ExpressionKind::Coalesce {
+                lhs: Box::new(ExpressionKind::Clone {
+                    target: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                }.into()),
+                rhs: Box::new(ExpressionKind::Variable { name: "b".into() }.into()),
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn bool_and_null_literals() {
+        assert_ast("<?php true; FALSE; Null;", &[
+            expr!(ExpressionKind::Bool { value: true }.into()),
+            expr!(ExpressionKind::Bool { value: false }.into()),
+            expr!(ExpressionKind::Null.into()),
+        ]);
+    }
+
+    #[test]
+    fn magic_constants() {
+        assert_ast("<?php include __DIR__ . '/config.php'; __CLASS__; __LINE__;", &[
+            expr!(ExpressionKind::Include {
+                path: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::MagicConst { constant: MagicConst::Dir }.into()),
+                    op: InfixOp::Concat,
+                    rhs: Box::new(ExpressionKind::ConstantString { value: "/config.php".into() }.into()),
+                }.into()),
+            }.into()),
+            expr!(ExpressionKind::MagicConst { constant: MagicConst::Class }.into()),
+            expr!(ExpressionKind::MagicConst { constant: MagicConst::Line }.into()),
+        ]);
+    }
+
+    #[test]
+    fn includes() {
+        assert_ast("<?php require_once __DIR__ . '/a.php'; include 'b.php';", &[
+            expr!(ExpressionKind::RequireOnce {
+                path: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::MagicConst { constant: MagicConst::Dir }.into()),
+                    op: InfixOp::Concat,
+                    rhs: Box::new(ExpressionKind::ConstantString { value: "/a.php".into() }.into()),
+                }.into()),
+            }.into()),
+            expr!(ExpressionKind::Include {
+                path: Box::new(ExpressionKind::ConstantString { value: "b.php".into() }.into()),
+            }.into()),
+        ]);
+
+        assert_ast("<?php $config = require 'config.php';", &[
+            expr!(ExpressionKind::Assign {
+                var: Box::new(ExpressionKind::Variable { name: "config".into() }.into()),
+                value: Box::new(ExpressionKind::Require {
+                    path: Box::new(ExpressionKind::ConstantString { value: "config.php".into() }.into()),
+                }.into()),
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn array_literals() {
+        assert_ast("<?php [1, 2]; array(1, 2,);", &[
+            expr!(ExpressionKind::Array {
+                items: vec![
+                    ArrayItem { key: None, value: ExpressionKind::Int { i: 1 }.into(), unpack: false, by_ref: false },
+                    ArrayItem { key: None, value: ExpressionKind::Int { i: 2 }.into(), unpack: false, by_ref: false },
+                ]
+            }.into()),
+            expr!(ExpressionKind::Array {
+                items: vec![
+                    ArrayItem { key: None, value: ExpressionKind::Int { i: 1 }.into(), unpack: false, by_ref: false },
+                    ArrayItem { key: None, value: ExpressionKind::Int { i: 2 }.into(), unpack: false, by_ref: false },
+                ]
+            }.into()),
+        ]);
+
+        assert_ast("<?php ['a' => 1, ...$rest, 'b' => [&$c]];", &[
+            expr!(ExpressionKind::Array {
+                items: vec![
+                    ArrayItem {
+                        key: Some(ExpressionKind::ConstantString { value: "a".into() }.into()),
+                        value: ExpressionKind::Int { i: 1 }.into(),
+                        unpack: false,
+                        by_ref: false,
+                    },
+                    ArrayItem {
+                        key: None,
+                        value: ExpressionKind::Variable { name: "rest".into() }.into(),
+                        unpack: true,
+                        by_ref: false,
+                    },
+                    ArrayItem {
+                        key: Some(ExpressionKind::ConstantString { value: "b".into() }.into()),
+                        value: ExpressionKind::Array {
+                            items: vec![
+                                ArrayItem { key: None, value: ExpressionKind::Variable { name: "c".into() }.into(), unpack: false, by_ref: true },
+                            ]
+                        }.into(),
+                        unpack: false,
+                        by_ref: false,
+                    },
+                ]
+            }.into()),
+        ]);
+
+        assert_ast("<?php array('a' => &$b);", &[
+            expr!(ExpressionKind::Array {
+                items: vec![
+                    ArrayItem {
+                        key: Some(ExpressionKind::ConstantString { value: "a".into() }.into()),
+                        value: ExpressionKind::Variable { name: "b".into() }.into(),
+                        unpack: false,
+                        by_ref: true,
+                    },
+                ]
+            }.into()),
+        ]);
+    }
+
+    #[test]
+    fn array_index() {
+        assert_ast("<?php $foo['bar'];", &[
+            expr!(ExpressionKind::ArrayIndex {
+                array: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                index: Some(Box::new(ExpressionKind::ConstantString { value: "bar".into() }.into()))
+            }.into())
+        ]);
+
+        assert_ast("<?php $foo['bar']['baz'];", &[
+            expr!(ExpressionKind::ArrayIndex {
+                array: Box::new(ExpressionKind::ArrayIndex {
+                    array: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                    index: Some(Box::new(ExpressionKind::ConstantString { value: "bar".into() }.into()))
+                }.into()),
+                index: Some(Box::new(ExpressionKind::ConstantString { value: "baz".into() }.into()))
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn offsets_on_postfix_expressions() {
+        assert_ast("<?php foo()[0]; Foo::BARS[1]; 'literal'[2]; (new Foo)->items[0];", &[
+            expr!(ExpressionKind::ArrayIndex {
+                array: Box::new(ExpressionKind::Call {
+                    target: Box::new(ExpressionKind::Identifier { name: "foo".into() }.into()),
+                    args: vec![],
+                }.into()),
+                index: Some(Box::new(ExpressionKind::Int { i: 0 }.into()))
+            }.into()),
+            expr!(ExpressionKind::ArrayIndex {
+                array: Box::new(ExpressionKind::ConstFetch {
+                    target: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into()),
+                    constant: "BARS".to_string().into(),
+                }.into()),
+                index: Some(Box::new(ExpressionKind::Int { i: 1 }.into()))
+            }.into()),
+            expr!(ExpressionKind::ArrayIndex {
+                array: Box::new(ExpressionKind::ConstantString { value: "literal".into() }.into()),
+                index: Some(Box::new(ExpressionKind::Int { i: 2 }.into()))
+            }.into()),
+            expr!(ExpressionKind::ArrayIndex {
+                array: Box::new(ExpressionKind::PropertyFetch {
+                    target: Box::new(ExpressionKind::New {
+                        target: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into()),
+                        args: vec![],
+                    }.into()),
+                    property: Box::new(ExpressionKind::Identifier { name: "items".into() }.into()),
+                }.into()),
+                index: Some(Box::new(ExpressionKind::Int { i: 0 }.into()))
+            }.into()),
+        ]);
+    }
+
+    #[test]
+    fn assignments() {
+        assert_ast("<?php $a = $b = 1;", &[
+            expr!(ExpressionKind::Assign {
+                var: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                value: Box::new(ExpressionKind::Assign {
+                    var: Box::new(ExpressionKind::Variable { name: "b".into() }.into()),
+                    value: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php while ($row = fetch()) {}", &[
+            StatementKind::While {
+                condition: ExpressionKind::Assign {
+                    var: Box::new(ExpressionKind::Variable { name: "row".into() }.into()),
+                    value: Box::new(ExpressionKind::Call {
+                        target: Box::new(ExpressionKind::Identifier { name: "fetch".into() }.into()),
+                        args: vec![],
+                    }.into()),
+                }.into(),
+                body: vec![],
+            }.into()
+        ]);
+
+        assert_ast("<?php $this->foo = Foo::$bar = 1;", &[
+            expr!(ExpressionKind::Assign {
+                var: Box::new(ExpressionKind::PropertyFetch {
+                    target: Box::new(ExpressionKind::Variable { name: "this".into() }.into()),
+                    property: Box::new(ExpressionKind::Identifier { name: "foo".into() }.into()),
+                }.into()),
+                value: Box::new(ExpressionKind::Assign {
+                    var: Box::new(ExpressionKind::StaticPropertyFetch {
+                        target: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into()),
+                        property: Box::new(ExpressionKind::Variable { name: "bar".into() }.into()),
+                    }.into()),
+                    value: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php !$a = foo(); $b += $c = 2;", &[
+            expr!(ExpressionKind::BooleanNot {
+                value: Box::new(ExpressionKind::Assign {
+                    var: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                    value: Box::new(ExpressionKind::Call {
+                        target: Box::new(ExpressionKind::Identifier { name: "foo".into() }.into()),
+                        args: vec![],
+                    }.into()),
+                }.into()),
+            }.into()),
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Variable { name: "b".into() }.into()),
+                op: InfixOp::AddAssign,
+                rhs: Box::new(ExpressionKind::Assign {
+                    var: Box::new(ExpressionKind::Variable { name: "c".into() }.into()),
+                    value: Box::new(ExpressionKind::Int { i: 2 }.into()),
+                }.into()),
+            }.into()),
+        ]);
+
+        let compound = [("%=", InfixOp::ModAssign), ("**=", InfixOp::PowAssign), ("&=", InfixOp::BitwiseAndAssign), ("|=", InfixOp::BitwiseOrAssign),
+            ("^=", InfixOp::BitwiseXorAssign), ("<<=", InfixOp::LeftShiftAssign), (">>=", InfixOp::RightShiftAssign)];
+
+        for (token, op) in compound {
+            assert_ast(&format!("<?php $a {} $b;", token), &[
+                expr!(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                    op,
+                    rhs: Box::new(ExpressionKind::Variable { name: "b".into() }.into()),
+                }.into()),
+            ]);
+        }
+    }
+
+    #[test]
+    fn variable_variables() {
+        assert_ast("<?php $$a = ${'b'}; $$$c;", &[
+            expr!(ExpressionKind::Assign {
+                var: Box::new(ExpressionKind::Dynamic {
+                    value: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                }.into()),
+                value: Box::new(ExpressionKind::Dynamic {
+                    value: Box::new(ExpressionKind::ConstantString { value: "b".into() }.into()),
+                }.into()),
+            }.into()),
+            expr!(ExpressionKind::Dynamic {
+                value: Box::new(ExpressionKind::Dynamic {
+                    value: Box::new(ExpressionKind::Variable { name: "c".into() }.into()),
+                }.into()),
+            }.into()),
+        ]);
+
+        assert_parse_error("<?php $;");
+    }
+
+    #[test]
+    fn array_index_assign() {
+        assert_ast("<?php $foo['bar'] = 'baz';", &[
+            expr!(ExpressionKind::Assign {
+                var: Box::new(ExpressionKind::ArrayIndex {
+                    array: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                    index: Some(Box::new(ExpressionKind::ConstantString { value: "bar".into() }.into()))
+                }.into()),
+                value: Box::new(ExpressionKind::ConstantString { value: "baz".into() }.into())
+            }.into())
+        ]);
+
+        assert_ast("<?php $foo[] = 1; $foo[0][] = 2;", &[
+            expr!(ExpressionKind::Assign {
+                var: Box::new(ExpressionKind::ArrayIndex {
+                    array: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                    index: None
+                }.into()),
+                value: Box::new(ExpressionKind::Int { i: 1 }.into())
+            }.into()),
+            expr!(ExpressionKind::Assign {
+                var: Box::new(ExpressionKind::ArrayIndex {
+                    array: Box::new(ExpressionKind::ArrayIndex {
+                        array: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                        index: Some(Box::new(ExpressionKind::Int { i: 0 }.into()))
+                    }.into()),
+                    index: None
+                }.into()),
+                value: Box::new(ExpressionKind::Int { i: 2 }.into())
+            }.into()),
+        ]);
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_ast("<?php 1 == 1;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                op: InfixOp::Equals,
+                rhs: Box::new(ExpressionKind::Int { i: 1 }.into())
+            }.into())
+        ]);
+
+        assert_ast("<?php 1 === 1;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                op: InfixOp::Identical,
+                rhs: Box::new(ExpressionKind::Int { i: 1 }.into())
+            }.into())
+        ]);
+
+        assert_ast("<?php 1 != 1;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                op: InfixOp::NotEquals,
+                rhs: Box::new(ExpressionKind::Int { i: 1 }.into())
+            }.into())
+        ]);
+
+        assert_ast("<?php 1 !== 1;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                op: InfixOp::NotIdentical,
+                rhs: Box::new(ExpressionKind::Int { i: 1 }.into())
+            }.into())
+        ]);
+
+        assert_ast("<?php 1 <> 1;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                op: InfixOp::NotEquals,
+                rhs: Box::new(ExpressionKind::Int { i: 1 }.into())
+            }.into())
+        ]);
+
+        assert_ast("<?php 1 <=> 2;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                op: InfixOp::Spaceship,
+                rhs: Box::new(ExpressionKind::Int { i: 2 }.into())
+            }.into())
+        ]);
+
+        assert_ast("<?php 1 < 2 == 3 >= 4;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                    op: InfixOp::LessThan,
+                    rhs: Box::new(ExpressionKind::Int { i: 2 }.into())
+                }.into()),
+                op: InfixOp::Equals,
+                rhs: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::Int { i: 3 }.into()),
+                    op: InfixOp::GreaterThanEquals,
+                    rhs: Box::new(ExpressionKind::Int { i: 4 }.into())
+                }.into())
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn chained_comparisons() {
+        assert_parse_error("<?php 1 < 2 < 3;");
+        assert_parse_error("<?php 1 == 2 != 3;");
+        assert_parse_error("<?php $a <=> $b <=> $c;");
+        assert_parse_error("<?php 1 + 2 > 3 > 4;");
+    }
+
+    #[test]
+    fn operator_precedence() {
+        let var = |name: &str| -> Box<Expression> { Box::new(ExpressionKind::Variable { name: name.into() }.into()) };
+
+        assert_ast("<?php $a . $b << $c;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: var("a"),
+                op: InfixOp::Concat,
+                rhs: Box::new(ExpressionKind::Infix { lhs: var("b"), op: InfixOp::LeftShift, rhs: var("c") }.into())
+            }.into())
+        ]);
+
+        assert_ast("<?php $a ?? $b || $c;", &[
+            expr!(ExpressionKind::Coalesce {
+                lhs: var("a"),
+                rhs: Box::new(ExpressionKind::Infix { lhs: var("b"), op: InfixOp::Or, rhs: var("c") }.into())
+            }.into())
+        ]);
+
+        assert_ast("<?php $a && $b ?? $c;", &[
+            expr!(ExpressionKind::Coalesce {
+                lhs: Box::new(ExpressionKind::Infix { lhs: var("a"), op: InfixOp::And, rhs: var("b") }.into()),
+                rhs: var("c")
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn paren_expression() {
+        assert_ast("<?php (1 + 2);", &[
+            StatementKind::Expression { expr: ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                op: InfixOp::Add,
+                rhs: Box::new(ExpressionKind::Int { i: 2 }.into())
+            }.into()}.into()
+        ]);
+    }
+
+    #[test]
+    fn breaks() {
+        assert_ast("<?php break;", &[
+            StatementKind::Break { num: None }.into()
+        ]);
+
+        assert_ast("<?php break 2;", &[
+            StatementKind::Break { num: Some(ExpressionKind::Int { i: 2 }.into()) }.into()
+        ]);
+    }
+
+    #[test]
+    fn continues() {
+        assert_ast("<?php continue;", &[
+            StatementKind::Continue { num: None }.into()
+        ]);
+
+        assert_ast("<?php continue 2;", &[
+            StatementKind::Continue { num: Some(ExpressionKind::Int { i: 2 }.into()) }.into()
+        ]);
+    }
+
+    #[test]
+    fn math_precedence() {
+        assert_ast("<?php 1 + 2 * 3 / 4 - 5;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                    op: InfixOp::Add,
+                    rhs: Box::new(ExpressionKind::Infix {
+                        lhs: Box::new(ExpressionKind::Infix {
+                            lhs: Box::new(ExpressionKind::Int { i: 2 }.into()),
+                            op: InfixOp::Mul,
+                            rhs: Box::new(ExpressionKind::Int { i: 3 }.into())
+                        }.into()),
+                        op: InfixOp::Div,
+                        rhs: Box::new(ExpressionKind::Int { i: 4 }.into())
+                    }.into())
+                }.into()),
+                op: InfixOp::Sub,
+                rhs: Box::new(ExpressionKind::Int { i: 5 }.into())
+            }.into())
+        ]);
+
+        assert_ast("<?php 'a' . 1 + 2 << 3 >> 4;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::ConstantString { value: "a".into() }.into()),
+                op: InfixOp::Concat,
+                rhs: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::Infix {
+                        lhs: Box::new(ExpressionKind::Infix {
+                            lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                            op: InfixOp::Add,
+                            rhs: Box::new(ExpressionKind::Int { i: 2 }.into())
+                        }.into()),
+                        op: InfixOp::LeftShift,
+                        rhs: Box::new(ExpressionKind::Int { i: 3 }.into())
+                    }.into()),
+                    op: InfixOp::RightShift,
+                    rhs: Box::new(ExpressionKind::Int { i: 4 }.into())
+                }.into())
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn property_fetch() {
+        assert_ast("<?php $foo->bar; $foo->bar->baz;", &[
+            expr!(ExpressionKind::PropertyFetch {
+                target: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                property: Box::new(ExpressionKind::Identifier { name: "bar".into() }.into())
+            }.into()),
+            expr!(ExpressionKind::PropertyFetch {
+                target: Box::new(ExpressionKind::PropertyFetch {
+                    target: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                    property: Box::new(ExpressionKind::Identifier { name: "bar".into() }.into())
+                }.into()),
+                property: Box::new(ExpressionKind::Identifier { name: "baz".into() }.into())
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn dynamic_property_fetch() {
+        assert_ast("<?php $foo->$bar; $foo->class;", &[
+            expr!(ExpressionKind::PropertyFetch {
+                target: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                property: Box::new(ExpressionKind::Dynamic {
+                    value: Box::new(ExpressionKind::Variable { name: "bar".into() }.into())
+                }.into())
+            }.into()),
+            expr!(ExpressionKind::PropertyFetch {
+                target: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                property: Box::new(ExpressionKind::Identifier { name: "class".into() }.into())
+            }.into()),
+        ]);
+
+        assert_ast("<?php $foo->{$bar}; $foo->{'prop-' . $i};", &[
+            expr!(ExpressionKind::PropertyFetch {
+                target: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                property: Box::new(ExpressionKind::Dynamic {
+                    value: Box::new(ExpressionKind::Variable { name: "bar".into() }.into())
+                }.into())
+            }.into()),
+            expr!(ExpressionKind::PropertyFetch {
+                target: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                property: Box::new(ExpressionKind::Dynamic {
+                    value: Box::new(ExpressionKind::Infix {
+                        lhs: Box::new(ExpressionKind::ConstantString { value: "prop-".into() }.into()),
+                        op: InfixOp::Concat,
+                        rhs: Box::new(ExpressionKind::Variable { name: "i".into() }.into()),
+                    }.into())
+                }.into())
+            }.into()),
+        ]);
+    }
+
+    #[test]
+    fn method_calls() {
+        assert_ast("<?php $foo->bar();", &[
+            expr!(ExpressionKind::MethodCall {
+                target: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                method: Box::new(ExpressionKind::Identifier { name: "bar".into() }.into()),
+                args: vec![]
+            }.into())
+        ]);
+
+        assert_ast("<?php $foo->bar()->baz();", &[
+            expr!(ExpressionKind::MethodCall {
+                target: Box::new(ExpressionKind::MethodCall {
+                    target: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                    method: Box::new(ExpressionKind::Identifier { name: "bar".into() }.into()),
+                    args: vec![]
+                }.into()),
+                method: Box::new(ExpressionKind::Identifier { name: "baz".into() }.into()),
+                args: vec![]
+            }.into())
+        ]);
+
+        assert_ast("<?php $foo->bar($baz)->car;", &[
+            expr!(ExpressionKind::PropertyFetch {
+                target: Box::new(ExpressionKind::MethodCall {
+                    target: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                    method: Box::new(ExpressionKind::Identifier { name: "bar".into() }.into()),
+                    args: vec![
+                        Arg { name: None, value: ExpressionKind::Variable { name: "baz".into() }.into(), unpack: false },
+                    ]
+                }.into()),
+                property: Box::new(ExpressionKind::Identifier { name: "car".into() }.into())
+            }.into())
+        ]);
+
+        assert_ast("<?php $foo->$bar(); $foo->list();", &[
+            expr!(ExpressionKind::MethodCall {
+                target: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                method: Box::new(ExpressionKind::Dynamic {
+                    value: Box::new(ExpressionKind::Variable { name: "bar".into() }.into())
+                }.into()),
+                args: vec![]
+            }.into()),
+            expr!(ExpressionKind::MethodCall {
+                target: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                method: Box::new(ExpressionKind::Identifier { name: "list".into() }.into()),
+                args: vec![]
+            }.into()),
+        ]);
+
+        assert_ast("<?php $foo->bar()();", &[
+            expr!(ExpressionKind::Call {
+                target: Box::new(ExpressionKind::MethodCall {
+                    target: Box::new(ExpressionKind::Variable{ name: "foo".into() }.into()),
+                    method: Box::new(ExpressionKind::Identifier { name: "bar".into() }.into()),
+                    args: vec![]
+                }.into()),
+                args: vec![]
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn nullsafe_operator() {
+        assert_ast("<?php $user?->profile?->name;", &[
+            expr!(ExpressionKind::NullsafePropertyFetch {
+                target: Box::new(ExpressionKind::NullsafePropertyFetch {
+                    target: Box::new(ExpressionKind::Variable { name: "user".into() }.into()),
+                    property: Box::new(ExpressionKind::Identifier { name: "profile".into() }.into())
+                }.into()),
+                property: Box::new(ExpressionKind::Identifier { name: "name".into() }.into())
+            }.into())
+        ]);
+
+        assert_ast("<?php $user?->profile()->name();", &[
+            expr!(ExpressionKind::MethodCall {
+                target: Box::new(ExpressionKind::NullsafeMethodCall {
+                    target: Box::new(ExpressionKind::Variable { name: "user".into() }.into()),
+                    method: Box::new(ExpressionKind::Identifier { name: "profile".into() }.into()),
+                    args: vec![]
+                }.into()),
+                method: Box::new(ExpressionKind::Identifier { name: "name".into() }.into()),
+                args: vec![]
+            }.into())
+        ]);
+
+        assert_ast("<?php $user?->{$method}();", &[
+            expr!(ExpressionKind::NullsafeMethodCall {
+                target: Box::new(ExpressionKind::Variable { name: "user".into() }.into()),
+                method: Box::new(ExpressionKind::Dynamic {
+                    value: Box::new(ExpressionKind::Variable { name: "method".into() }.into())
+                }.into()),
+                args: vec![]
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn dynamic_class_constant_fetch() {
+        assert_ast("<?php Foo::{$constName}; static::{'BAR' . $i};", &[
+            expr!(ExpressionKind::DynamicConstFetch {
+                target: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into()),
+                constant: Box::new(ExpressionKind::Variable { name: "constName".into() }.into()),
+            }.into()),
+            expr!(ExpressionKind::DynamicConstFetch {
+                target: Box::new(ExpressionKind::Static.into()),
+                constant: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::ConstantString { value: "BAR".into() }.into()),
+                    op: InfixOp::Concat,
+                    rhs: Box::new(ExpressionKind::Variable { name: "i".into() }.into()),
+                }.into()),
+            }.into()),
+        ]);
+    }
+
+    #[test]
+    fn static_access() {
+        assert_ast("<?php Foo::BAR; Foo::$bar + 1; Foo::bar();", &[
+            expr!(ExpressionKind::ConstFetch {
+                target: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into()),
+                constant: "BAR".into(),
+            }.into()),
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::StaticPropertyFetch {
+                    target: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into()),
+                    property: Box::new(ExpressionKind::Variable { name: "bar".into() }.into()),
+                }.into()),
+                op: InfixOp::Add,
+                rhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+            }.into()),
+            expr!(ExpressionKind::StaticMethodCall {
+                target: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into()),
+                method: "bar".into(),
+                args: vec![],
+            }.into()),
+        ]);
+
+        assert_ast("<?php static::bar(); self::$bar; parent::__construct();", &[
+            expr!(ExpressionKind::StaticMethodCall {
+                target: Box::new(ExpressionKind::Static.into()),
+                method: "bar".into(),
+                args: vec![],
+            }.into()),
+            expr!(ExpressionKind::StaticPropertyFetch {
+                target: Box::new(ExpressionKind::Identifier { name: "self".into() }.into()),
+                property: Box::new(ExpressionKind::Variable { name: "bar".into() }.into()),
+            }.into()),
+            expr!(ExpressionKind::StaticMethodCall {
+                target: Box::new(ExpressionKind::Identifier { name: "parent".into() }.into()),
+                method: "__construct".into(),
+                args: vec![],
+            }.into()),
+        ]);
+
+        assert_ast("<?php Foo::$bar(1); Foo::{$baz}(); (Foo::$bar)();", &[
+            expr!(ExpressionKind::DynamicStaticMethodCall {
+                target: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into()),
+                method: Box::new(ExpressionKind::Variable { name: "bar".into() }.into()),
+                args: vec![Arg { name: None, value: ExpressionKind::Int { i: 1 }.into(), unpack: false }],
+            }.into()),
+            expr!(ExpressionKind::DynamicStaticMethodCall {
+                target: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into()),
+                method: Box::new(ExpressionKind::Variable { name: "baz".into() }.into()),
+                args: vec![],
+            }.into()),
+            expr!(ExpressionKind::Call {
+                target: Box::new(ExpressionKind::StaticPropertyFetch {
+                    target: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into()),
+                    property: Box::new(ExpressionKind::Variable { name: "bar".into() }.into()),
+                }.into()),
+                args: vec![],
+            }.into()),
+        ]);
+
+        assert_ast("<?php Foo::class; $obj::class; static::class;", &[
+            expr!(ExpressionKind::ConstFetch {
+                target: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into()),
+                constant: "class".into(),
+            }.into()),
+            expr!(ExpressionKind::ConstFetch {
+                target: Box::new(ExpressionKind::Variable { name: "obj".into() }.into()),
+                constant: "class".into(),
+            }.into()),
+            expr!(ExpressionKind::ConstFetch {
+                target: Box::new(ExpressionKind::Static.into()),
+                constant: "class".into(),
+            }.into()),
+        ]);
+    }
+
+    #[test]
+    fn argument_unpacking() {
+        assert_ast("<?php foo(...$args); foo($a, ...$b);", &[
+            expr!(ExpressionKind::Call {
+                target: Box::new(ExpressionKind::Identifier { name: "foo".into() }.into()),
+                args: vec![
+                    Arg { name: None, value: ExpressionKind::Variable { name: "args".into() }.into(), unpack: true },
+                ]
+            }.into()),
+            expr!(ExpressionKind::Call {
+                target: Box::new(ExpressionKind::Identifier { name: "foo".into() }.into()),
+                args: vec![
+                    Arg { name: None, value: ExpressionKind::Variable { name: "a".into() }.into(), unpack: false },
+                    Arg { name: None, value: ExpressionKind::Variable { name: "b".into() }.into(), unpack: true },
+                ]
+            }.into()),
+        ]);
+
+        assert_ast("<?php $foo->bar(...$args); new Foo(...$args);", &[
+            expr!(ExpressionKind::MethodCall {
+                target: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                method: Box::new(ExpressionKind::Identifier { name: "bar".into() }.into()),
+                args: vec![
+                    Arg { name: None, value: ExpressionKind::Variable { name: "args".into() }.into(), unpack: true },
+                ]
+            }.into()),
+            expr!(ExpressionKind::New {
+                target: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into()),
+                args: vec![
+                    Arg { name: None, value: ExpressionKind::Variable { name: "args".into() }.into(), unpack: true },
+                ]
+            }.into()),
+        ]);
+    }
+
+    #[test]
+    fn concat() {
+        assert_ast("<?php 'foo' . 'bar' . 'baz';", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::ConstantString { value: "foo".into() }.into()),
+                    op: InfixOp::Concat,
+                    rhs: Box::new(ExpressionKind::ConstantString { value: "bar".into() }.into()),
+                }.into()),
+                op: InfixOp::Concat,
+                rhs: Box::new(ExpressionKind::ConstantString { value: "baz".into() }.into()),
+            }.into())
+        ]);
+
+        assert_ast("<?php 'foo' . 1 + 2;", &[
+            expr!(ExpressionKind::Infix {
+                lhs: Box::new(ExpressionKind::ConstantString { value: "foo".into() }.into()),
+                op: InfixOp::Concat,
+                rhs: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                    op: InfixOp::Add,
+                    rhs: Box::new(ExpressionKind::Int { i: 2 }.into()),
+                }.into())
+            }.into())
+        ]);
+    }
+
+    #[test]
+    fn empty_fn() {
+        assert_ast("<?php function foo() {}", &[
+            function!("foo", &[], &[]),
+        ]);
+    }
+
+    #[test]
+    fn empty_fn_with_params() {
+        assert_ast("<?php function foo($n) {}", &[
+            function!("foo", &["n"], &[]),
+        ]);
+
+        assert_ast("<?php function foo($n, $m) {}", &[
+            function!("foo", &["n", "m"], &[]),
+        ]);
+    }
+
+    #[test]
+    fn fib() {
+        assert_ast("\
+        <?php
+
+        function fib($n) {
+            if ($n < 2) {
+                return $n;
+            }
+
+            return fib($n - 1) + fib($n - 2);
+        }", &[
+            function!("fib", &["n"], &[
+                StatementKind::If {
+                    condition: ExpressionKind::Infix {
+                        lhs: Box::new(ExpressionKind::Variable { name: "n".into() }.into()),
+                        op: InfixOp::LessThan,
+                        rhs: Box::new(ExpressionKind::Int { i: 2 }.into()),
+                    }.into(),
+                    then: vec![
+                        StatementKind::Return { value: Some(ExpressionKind::Variable { name: "n".into() }.into()) }.into()
+                    ],
+                    else_ifs: vec![],
+                    r#else: None
+                }.into(),
+                StatementKind::Return {
+                    value: Some(ExpressionKind::Infix {
+                        lhs: Box::new(ExpressionKind::Call {
+                            target: Box::new(ExpressionKind::Identifier { name: "fib".into() }.into()),
+                            args: vec![
+                                Arg {
+                                    name: None,
+                                    value: ExpressionKind::Infix {
+                                        lhs: Box::new(ExpressionKind::Variable { name: "n".into() }.into()),
+                                        op: InfixOp::Sub,
+                                        rhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                                    }.into(),
+                                    unpack: false,
+                                }
+                            ]
+                        }.into()),
+                        op: InfixOp::Add,
+                        rhs: Box::new(ExpressionKind::Call {
+                            target: Box::new(ExpressionKind::Identifier { name: "fib".into() }.into()),
+                            args: vec![
+                                Arg {
+                                    name: None,
+                                    value: ExpressionKind::Infix {
+                                        lhs: Box::new(ExpressionKind::Variable { name: "n".into() }.into()),
+                                        op: InfixOp::Sub,
+                                        rhs: Box::new(ExpressionKind::Int { i: 2 }.into()),
+                                    }.into(),
+                                    unpack: false,
+                                }
+                            ]
+                        }.into()),
+                    }.into())
+                }.into()
+            ])
+        ]);
+    }
+
+    #[test]
+    fn one_liner_if_statement() {
+        assert_ast("<?php if($foo) return $foo;", &[
+                StatementKind::If {
+                    condition: ExpressionKind::Variable { name: "foo".into() }.into(),
+                    then: vec![
+                        StatementKind::Return { value: Some(ExpressionKind::Variable { name: "foo".into() }.into()) }.into()
+                    ],
+                    else_ifs: vec![],
+                    r#else: None
+                }.into(),
+        ]);
+    }
+
+    #[test]
+    fn one_liner_if_else_statement() {
+        assert_ast("<?php if($foo) return $foo; else return $bar; $baz;", &[
+                StatementKind::If {
+                    condition: ExpressionKind::Variable { name: "foo".into() }.into(),
+                    then: vec![
+                        StatementKind::Return { value: Some(ExpressionKind::Variable { name: "foo".into() }.into()) }.into()
+                    ],
+                    else_ifs: vec![],
+                    r#else: Some(vec![
+                        StatementKind::Return { value: Some(ExpressionKind::Variable { name: "bar".into() }.into()) }.into()
+                    ])
+                }.into(),
+                expr!(ExpressionKind::Variable { name: "baz".into() }.into()),
+        ]);
+    }
+
+    #[test]
+    fn one_liner_loops() {
+        assert_ast("<?php while ($foo) $bar; foreach ($foo as $bar) $baz; for (;;) $foo;", &[
+            StatementKind::While {
+                condition: ExpressionKind::Variable { name: "foo".into() }.into(),
+                body: vec![expr!(ExpressionKind::Variable { name: "bar".into() }.into())],
+            }.into(),
+            StatementKind::Foreach {
+                expr: ExpressionKind::Variable { name: "foo".into() }.into(),
+                by_ref: false,
+                key_var: None,
+                value_var: ExpressionKind::Variable { name: "bar".into() }.into(),
+                body: vec![expr!(ExpressionKind::Variable { name: "baz".into() }.into())],
+            }.into(),
+            StatementKind::For {
+                init: None,
+                condition: None,
+                r#loop: None,
+                then: vec![expr!(ExpressionKind::Variable { name: "foo".into() }.into())],
+            }.into(),
+        ]);
+    }
+
+    #[test]
+    fn if_else_statement() {
+        assert_ast("<?php if($foo) { return $foo; } else { return $foo; }", &[
+                StatementKind::If {
+                    condition: ExpressionKind::Variable { name: "foo".into() }.into(),
+                    then: vec![
+                        StatementKind::Return { value: Some(ExpressionKind::Variable { name: "foo".into() }.into()) }.into()
+                    ],
+                    else_ifs: vec![],
+                    r#else: Some(vec![
+                        StatementKind::Return { value: Some(ExpressionKind::Variable { name: "foo".into() }.into()) }.into()
+                    ])
+                }.into(),
+        ]);
+    }
+
+    #[test]
+    fn if_elseif_else_statement() {
+        assert_ast("<?php if($foo) { return $foo; } elseif($foo) { return $foo; } else { return $foo; }", &[
+                StatementKind::If {
+                    condition: ExpressionKind::Variable { name: "foo".into() }.into(),
+                    then: vec![
+                        StatementKind::Return { value: Some(ExpressionKind::Variable { name: "foo".into() }.into()) }.into()
+                    ],
+                    else_ifs: vec![
+                        ElseIf {
+                            condition: ExpressionKind::Variable { name: "foo".into() }.into(),
+                            body: vec![
+                                StatementKind::Return { value: Some(ExpressionKind::Variable { name: "foo".into() }.into()) }.into()
+                            ]
+                        }
+                    ],
+                    r#else: Some(vec![
+                        StatementKind::Return { value: Some(ExpressionKind::Variable { name: "foo".into() }.into()) }.into()
+                    ])
+                }.into(),
+        ]);
+    }
+
+    #[test]
+    fn alternative_if_statement() {
+        assert_ast("<?php if ($foo): return $foo; elseif ($foo): return $foo; else: return $foo; endif;", &[
+            StatementKind::If {
+                condition: ExpressionKind::Variable { name: "foo".into() }.into(),
+                then: vec![
+                    StatementKind::Return { value: Some(ExpressionKind::Variable { name: "foo".into() }.into()) }.into()
+                ],
+                else_ifs: vec![
+                    ElseIf {
+                        condition: ExpressionKind::Variable { name: "foo".into() }.into(),
+                        body: vec![
+                            StatementKind::Return { value: Some(ExpressionKind::Variable { name: "foo".into() }.into()) }.into()
+                        ]
+                    },
+                ],
+                r#else: Some(vec![
+                    StatementKind::Return { value: Some(ExpressionKind::Variable { name: "foo".into() }.into()) }.into()
+                ])
+            }.into(),
+        ]);
+    }
+
+    #[test]
+    fn alternative_loops() {
+        assert_ast("<?php while ($foo): endwhile;", &[
+            StatementKind::While {
+                condition: ExpressionKind::Variable { name: "foo".into() }.into(),
+                body: vec![],
+            }.into()
+        ]);
+
+        assert_ast("<?php for (;;): endfor;", &[
+            StatementKind::For {
+                init: None,
+                condition: None,
+                r#loop: None,
+                then: vec![],
+            }.into()
+        ]);
+
+        assert_ast("<?php foreach ($foo as $bar): endforeach;", &[
+            StatementKind::Foreach {
+                expr: ExpressionKind::Variable { name: "foo".into() }.into(),
+                by_ref: false,
+                key_var: None,
+                value_var: ExpressionKind::Variable { name: "bar".into() }.into(),
+                body: vec![],
+            }.into()
+        ]);
+    }
+
+    #[test]
+    fn alternative_switch() {
+        assert_ast("<?php switch ($foo): case 1: break; default: break; endswitch;", &[
+            StatementKind::Switch {
+                condition: ExpressionKind::Variable { name: "foo".into() }.into(),
+                cases: vec![
+                    Case {
+                        condition: Some(ExpressionKind::Int { i: 1 }.into()),
+                        body: vec![StatementKind::Break { num: None }.into()],
+                    },
+                    Case {
+                        condition: None,
+                        body: vec![StatementKind::Break { num: None }.into()],
+                    },
+                ],
+            }.into()
+        ]);
+    }
+
+    #[test]
+    fn alternative_syntax_in_templates() {
+        assert_ast("<?php foreach ($foo as $bar): ?><li><?php echo $bar ?></li><?php endforeach; ?>", &[
+            StatementKind::Foreach {
+                expr: ExpressionKind::Variable { name: "foo".into() }.into(),
+                by_ref: false,
+                key_var: None,
+                value_var: ExpressionKind::Variable { name: "bar".into() }.into(),
+                body: vec![
+                    StatementKind::InlineHtml("<li>".into()).into(),
+                    StatementKind::Echo { values: vec![ExpressionKind::Variable { name: "bar".into() }.into()] }.into(),
+                    StatementKind::InlineHtml("</li>".into()).into(),
+                ],
+            }.into(),
+            StatementKind::Noop.into(),
+        ]);
+    }
+
+    #[test]
+    fn if_else_if_statement() {
+        assert_ast("<?php if($foo) { return $foo; } else if($bar) { return $bar; } elseif($baz) return $baz; else if($car) { return $car; } else { return $foo; }", &[
+            StatementKind::If {
+                condition: ExpressionKind::Variable { name: "foo".into() }.into(),
+                then: vec![
+                    StatementKind::Return { value: Some(ExpressionKind::Variable { name: "foo".into() }.into()) }.into()
+                ],
+                else_ifs: vec![
+                    ElseIf {
+                        condition: ExpressionKind::Variable { name: "bar".into() }.into(),
+                        body: vec![
+                            StatementKind::Return { value: Some(ExpressionKind::Variable { name: "bar".into() }.into()) }.into()
+                        ]
+                    },
+                    ElseIf {
+                        condition: ExpressionKind::Variable { name: "baz".into() }.into(),
+                        body: vec![
+                            StatementKind::Return { value: Some(ExpressionKind::Variable { name: "baz".into() }.into()) }.into()
+                        ]
+                    },
+                    ElseIf {
+                        condition: ExpressionKind::Variable { name: "car".into() }.into(),
+                        body: vec![
+                            StatementKind::Return { value: Some(ExpressionKind::Variable { name: "car".into() }.into()) }.into()
+                        ]
+                    },
+                ],
+                r#else: Some(vec![
+                    StatementKind::Return { value: Some(ExpressionKind::Variable { name: "foo".into() }.into()) }.into()
+                ])
+            }.into(),
+        ]);
+    }
+
+    #[test]
+    fn echo() {
+        assert_ast("<?php echo 1;", &[
+            StatementKind::Echo {
+                values: vec![
+                    ExpressionKind::Int { i: 1 }.into(),
+                ]
+            }.into()
+        ]);
+    }
+
+    #[test]
+    fn empty_class() {
+        assert_ast("<?php class Foo {}", &[
+            class!("Foo")
+        ]);
+    }
+
+    #[test]
+    fn class_with_basic_method() {
+        assert_ast("\
+        <?php
+        
+        class Foo {
+            function bar() {
+                echo 1;
+            }
+        }
+        ", &[
+            class!("Foo", &[
+                method!("bar", &[], &[], &[
+                    StatementKind::Echo { values: vec![
+                        ExpressionKind::Int { i: 1 }.into(),
+                    ] }.into()
+                ])
+            ])
+        ]);
+    }
+
+    #[test]
+    fn class_with_extends() {
+        assert_ast("\
+        <?php
+        
+        class Foo extends Bar {}
+        ", &[
+            class!("Foo", Some("Bar".to_string().into()), &[], &[]),
+        ]);
+    }
+    
+    #[test]
+    fn class_with_implements() {
+        assert_ast("\
+        <?php
+        
+        class Foo implements Bar, Baz {}
+        ", &[
+            class!("Foo", None, &["Bar".to_string().into(), "Baz".to_string().into()], &[]),
+        ]);
+    }
+
+    #[test]
+    fn plain_typestrings_test() {
+        assert_ast("<?php function foo(string $b) {}", &[
+            StatementKind::Function {
+                name: "foo".to_string().into(),
+                params: vec![
+                    Param {
+                        name: ExpressionKind::Variable { name: "b".into() }.into(),
+                        r#type: Some(Type::Plain("string".into())),
+                        by_ref: false,
+                        variadic: false,
+                        default: None,
+                        flags: vec![],
+                        attributes: vec![],
+                        span: Span::default(),
+                    }
+                ],
+                body: vec![],
+                return_type: None,
+                returns_by_ref: false,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        ]);
+    }
+
+    #[test]
+    fn variadic_params() {
+        assert_ast("<?php function foo(...$bar) {}", &[
+            StatementKind::Function {
+                name: "foo".to_string().into(),
+                params: vec![
+                    Param {
+                        name: ExpressionKind::Variable { name: "bar".into() }.into(),
+                        r#type: None,
+                        by_ref: false,
+                        variadic: true,
+                        default: None,
+                        flags: vec![],
+                        attributes: vec![],
+                        span: Span::default(),
+                    }
+                ],
+                body: vec![],
+                return_type: None,
+                returns_by_ref: false,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        ]);
+
+        assert_ast("<?php function foo(string ...$bar) {}", &[
+            StatementKind::Function {
+                name: "foo".to_string().into(),
+                params: vec![
+                    Param {
+                        name: ExpressionKind::Variable { name: "bar".into() }.into(),
+                        r#type: Some(Type::Plain("string".into())),
+                        by_ref: false,
+                        variadic: true,
+                        default: None,
+                        flags: vec![],
+                        attributes: vec![],
+                        span: Span::default(),
+                    }
+                ],
+                body: vec![],
+                return_type: None,
+                returns_by_ref: false,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        ]);
+
+        assert_ast("<?php function foo($bar, $baz, ...$car) {}", &[
+            StatementKind::Function {
+                name: "foo".to_string().into(),
+                params: vec![
+                    Param {
+                        name: ExpressionKind::Variable { name: "bar".into() }.into(),
+                        r#type: None,
+                        by_ref: false,
+                        variadic: false,
+                        default: None,
+                        flags: vec![],
+                        attributes: vec![],
+                        span: Span::default(),
+                    },
+                    Param {
+                        name: ExpressionKind::Variable { name: "baz".into() }.into(),
+                        r#type: None,
+                        by_ref: false,
+                        variadic: false,
+                        default: None,
+                        flags: vec![],
+                        attributes: vec![],
+                        span: Span::default(),
+                    },
+                    Param {
+                        name: ExpressionKind::Variable { name: "car".into() }.into(),
+                        r#type: None,
+                        by_ref: false,
+                        variadic: true,
+                        default: None,
+                        flags: vec![],
+                        attributes: vec![],
+                        span: Span::default(),
+                    }
+                ],
+                body: vec![],
+                return_type: None,
+                returns_by_ref: false,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        ]);
+    }
+
+    #[test]
+    fn nullable_typestrings_test() {
+        assert_ast("<?php function foo(?string $b) {}", &[
+            StatementKind::Function {
+                name: "foo".to_string().into(),
+                params: vec![
+                    Param {
+                        name: ExpressionKind::Variable { name: "b".into() }.into(),
+                        r#type: Some(Type::Nullable("string".into())),
+                        by_ref: false,
+                        variadic: false,
+                        default: None,
+                        flags: vec![],
+                        attributes: vec![],
+                        span: Span::default(),
+                    }
+                ],
+                body: vec![],
+                return_type: None,
+                returns_by_ref: false,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        ]);
+    }
+
+    #[test]
+    fn union_typestrings_test() {
+        assert_ast("<?php function foo(int|float $b) {}", &[
+            StatementKind::Function {
+                name: "foo".to_string().into(),
+                params: vec![
+                    Param {
+                        name: ExpressionKind::Variable { name: "b".into() }.into(),
+                        r#type: Some(Type::Union(vec![
+                            Type::Plain("int".into()),
+                            Type::Plain("float".into())
+                        ])),
+                        by_ref: false,
+                        variadic: false,
+                        default: None,
+                        flags: vec![],
+                        attributes: vec![],
+                        span: Span::default(),
+                    }
+                ],
+                body: vec![],
+                return_type: None,
+                returns_by_ref: false,
+                attributes: vec![],
+                doc_comment: None,
+            }.into(),
+        ]);
+    }
+
+    #[test]
+    fn intersection_typestrings_test() {
+        assert_ast("<?php function foo(Foo&Bar $b) {}", &[
+            StatementKind::Function {
+                name: "foo".to_string().into(),
+                params: vec![
+                    Param {
+                        name: ExpressionKind::Variable { name: "b".into() }.into(),
+                        r#type: Some(Type::Intersection(vec![
+                            Type::Plain("Foo".into()),
+                            Type::Plain("Bar".into())
+                        ])),
+                        by_ref: false,
+                        variadic: false,
+                        default: None,
+                        flags: vec![],
+                        attributes: vec![],
+                        span: Span::default(),
+                    }
+                ],
+                body: vec![],
+                return_type: None,
+                returns_by_ref: false,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        ]);
+    }
+
+    #[test]
+    fn dnf_typestrings_test() {
+        assert_ast("<?php function foo((A&B)|null $b): (A&B)|(C&D) {}", &[
+            StatementKind::Function {
+                name: "foo".to_string().into(),
+                params: vec![
+                    Param {
+                        name: ExpressionKind::Variable { name: "b".into() }.into(),
+                        r#type: Some(Type::Union(vec![
+                            Type::Intersection(vec![
+                                Type::Plain("A".into()),
+                                Type::Plain("B".into()),
+                            ]),
+                            Type::Plain("null".into()),
+                        ])),
+                        by_ref: false,
+                        variadic: false,
+                        default: None,
+                        flags: vec![],
+                        attributes: vec![],
+                        span: Span::default(),
+                    }
+                ],
+                body: vec![],
+                return_type: Some(Type::Union(vec![
+                    Type::Intersection(vec![
+                        Type::Plain("A".into()),
+                        Type::Plain("B".into()),
+                    ]),
+                    Type::Intersection(vec![
+                        Type::Plain("C".into()),
+                        Type::Plain("D".into()),
+                    ]),
+                ])),
+                returns_by_ref: false,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        ]);
+
+        assert_ast("<?php class Foo { public (A&B)|int $bar; }", &[
+            class!("Foo", &[
+                StatementKind::Property {
+                    var: "bar".into(),
+                    value: None,
+                    r#type: Some(Type::Union(vec![
+                        Type::Intersection(vec![
+                            Type::Plain("A".into()),
+                            Type::Plain("B".into()),
+                        ]),
+                        Type::Plain("int".into()),
+                    ])),
+                    flags: vec![PropertyFlag::Public],
+                    hooks: vec![],
+                    attributes: vec![],
+                    doc_comment: None,
+                }.into(),
+            ])
+        ]);
+
+        assert_parse_error("<?php function foo((A&B) $b) {}");
+        assert_parse_error("<?php function foo(A&B|null $b) {}");
+    }
+
+    #[test]
+    fn function_return_types() {
+        assert_ast("<?php function foo(): string {}", &[
+            StatementKind::Function {
+                name: "foo".to_string().into(),
+                params: vec![],
+                body: vec![],
+                return_type: Some(Type::Plain("string".into())),
+                returns_by_ref: false,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        ]);
+
+        assert_ast("<?php function foo(): ?int {}", &[
+            StatementKind::Function {
+                name: "foo".to_string().into(),
+                params: vec![],
+                body: vec![],
+                return_type: Some(Type::Nullable("int".into())),
+                returns_by_ref: false,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        ]);
+
+        assert_ast("<?php function foo(): never {}", &[
+            StatementKind::Function {
+                name: "foo".to_string().into(),
+                params: vec![],
+                body: vec![],
+                return_type: Some(Type::Plain("never".into())),
+                returns_by_ref: false,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        ]);
+
+        assert_ast("<?php function foo(): null {}", &[
+            StatementKind::Function {
+                name: "foo".to_string().into(),
+                params: vec![],
+                body: vec![],
+                return_type: Some(Type::Plain("null".into())),
+                returns_by_ref: false,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        ]);
+    }
+
+    #[test]
+    fn nullable_types() {
+        assert_ast("<?php function foo(?int $a, ?\\Foo\\Bar $b) {}", &[
+            StatementKind::Function {
+                name: "foo".to_string().into(),
+                params: vec![
+                    Param {
+                        name: ExpressionKind::Variable { name: "a".into() }.into(),
+                        r#type: Some(Type::Nullable("int".into())),
+                        by_ref: false,
+                        variadic: false,
+                        default: None,
+                        flags: vec![],
+                        attributes: vec![],
+                        span: Span::default(),
+                    },
+                    Param {
+                        name: ExpressionKind::Variable { name: "b".into() }.into(),
+                        r#type: Some(Type::Nullable("\\Foo\\Bar".into())),
+                        by_ref: false,
+                        variadic: false,
+                        default: None,
+                        flags: vec![],
+                        attributes: vec![],
+                        span: Span::default(),
+                    },
+                ],
+                body: vec![],
+                return_type: None,
+                returns_by_ref: false,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        ]);
+
+        assert_ast("<?php class Foo { public ?string $bar; var ?int $baz; }", &[
+            class!("Foo", &[
+                StatementKind::Property {
+                    var: "bar".into(),
+                    value: None,
+                    r#type: Some(Type::Nullable("string".into())),
+                    flags: vec![PropertyFlag::Public],
+                    hooks: vec![],
+                    attributes: vec![],
+                    doc_comment: None,
+                }.into(),
+                StatementKind::Var {
+                    var: "baz".into(),
+                    value: None,
+                    r#type: Some(Type::Nullable("int".into())),
+                    attributes: vec![],
+                }.into(),
+            ])
+        ]);
+
+        assert_parse_error("<?php function foo(?int|string $a) {}");
     }
 
-    macro_rules! expr {
-        ($expr:expr) => {
-            Statement::Expression {
-                expr: $expr,
-            }
-        };
+    #[test]
+    fn by_ref_functions() {
+        assert_ast("<?php function &foo() {}", &[
+            StatementKind::Function {
+                name: "foo".to_string().into(),
+                params: vec![],
+                body: vec![],
+                return_type: None,
+                returns_by_ref: true,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
+        ]);
+
+        assert_ast("<?php class Foo { public function &bar() {} abstract protected function &baz(); }", &[
+            class!("Foo", &[
+                StatementKind::Method {
+                    name: "bar".to_string().into(),
+                    params: vec![],
+                    flags: vec![MethodFlag::Public],
+                    body: vec![],
+                    return_type: None,
+                    returns_by_ref: true,
+                    attributes: vec![],
+                    doc_comment: None,
+                }.into(),
+                StatementKind::Method {
+                    name: "baz".to_string().into(),
+                    params: vec![],
+                    flags: vec![MethodFlag::Abstract, MethodFlag::Protected],
+                    body: vec![],
+                    return_type: None,
+                    returns_by_ref: true,
+                    attributes: vec![],
+                    doc_comment: None,
+                }.into(),
+            ])
+        ]);
+
+        assert_ast("<?php function &() {}; fn &() => $a;", &[
+            expr!(ExpressionKind::Closure {
+                params: vec![],
+                uses: vec![],
+                return_type: None,
+                returns_by_ref: true,
+                body: vec![],
+                attributes: vec![],
+            }.into()),
+            expr!(ExpressionKind::ArrowFunction {
+                params: vec![],
+                return_type: None,
+                returns_by_ref: true,
+                expr: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+                attributes: vec![],
+            }.into()),
+        ]);
     }
 
     #[test]
-    fn instanceof() {
-        assert_ast("<?php $foo instanceof Foo;", &[
-            expr!(Expression::Infix {
-                lhs: Box::new(Expression::Variable { name: "foo".into() }),
-                op: InfixOp::Instanceof,
-                rhs: Box::new(Expression::Identifier { name: "Foo".into() })
-            })
+    fn method_return_types() {
+        assert_ast("<?php class Foo { public function bar(): static {} abstract public function baz(): void; }", &[
+            class!("Foo", &[
+                StatementKind::Method {
+                    name: "bar".to_string().into(),
+                    params: vec![],
+                    flags: vec![MethodFlag::Public],
+                    body: vec![],
+                    return_type: Some(Type::Plain("static".into())),
+                    returns_by_ref: false,
+                    attributes: vec![],
+                    doc_comment: None,
+                }.into(),
+                StatementKind::Method {
+                    name: "baz".to_string().into(),
+                    params: vec![],
+                    flags: vec![MethodFlag::Abstract, MethodFlag::Public],
+                    body: vec![],
+                    return_type: Some(Type::Plain("void".into())),
+                    returns_by_ref: false,
+                    attributes: vec![],
+                    doc_comment: None,
+                }.into(),
+            ])
         ]);
+    }
 
-        assert_ast("<?php $foo instanceof Foo && $foo instanceof Foo;", &[
-            expr!(Expression::Infix {
-                lhs: Box::new(Expression::Infix {
-                    lhs: Box::new(Expression::Variable { name: "foo".into() }),
-                    op: InfixOp::Instanceof,
-                    rhs: Box::new(Expression::Identifier { name: "Foo".into() })
-                }),
-                op: InfixOp::And,
-                rhs: Box::new(Expression::Infix {
-                    lhs: Box::new(Expression::Variable { name: "foo".into() }),
-                    op: InfixOp::Instanceof,
-                    rhs: Box::new(Expression::Identifier { name: "Foo".into() })
-                })
-            })
+    #[test]
+    fn closure_return_types() {
+        assert_ast("<?php function () use ($a): ?int {};", &[
+            expr!(ExpressionKind::Closure {
+                params: vec![],
+                uses: vec![
+                    ClosureUse { var: ExpressionKind::Variable { name: "a".into() }.into(), by_ref: false },
+                ],
+                return_type: Some(Type::Nullable("int".into())),
+                body: vec![],
+                returns_by_ref: false,
+                attributes: vec![],
+            }.into())
+        ]);
+
+        assert_ast("<?php fn (): void => null;", &[
+            expr!(ExpressionKind::ArrowFunction {
+                params: vec![],
+                return_type: Some(Type::Plain("void".into())),
+                expr: Box::new(ExpressionKind::Null.into()),
+                returns_by_ref: false,
+                attributes: vec![],
+            }.into())
         ]);
     }
 
     #[test]
-    fn pow() {
-        assert_ast("<?php 2 ** 2;", &[
-            expr!(Expression::Infix {
-                lhs: Box::new(Expression::Int { i: 2 }),
-                op: InfixOp::Pow,
-                rhs: Box::new(Expression::Int { i: 2 }),
-            })
+    fn new_expressions() {
+        assert_ast("<?php new Foo;", &[
+            expr!(ExpressionKind::New {
+                target: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into()),
+                args: vec![],
+            }.into())
+        ]);
+
+        assert_ast("<?php new Foo(1, 2);", &[
+            expr!(ExpressionKind::New {
+                target: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into()),
+                args: vec![
+                    Arg { name: None, value: ExpressionKind::Int { i: 1 }.into(), unpack: false },
+                    Arg { name: None, value: ExpressionKind::Int { i: 2 }.into(), unpack: false },
+                ],
+            }.into())
+        ]);
+
+        assert_ast("<?php new $className();", &[
+            expr!(ExpressionKind::New {
+                target: Box::new(ExpressionKind::Variable { name: "className".into() }.into()),
+                args: vec![],
+            }.into())
+        ]);
+
+        assert_ast("<?php new static; new self(); new parent;", &[
+            expr!(ExpressionKind::New {
+                target: Box::new(ExpressionKind::Static.into()),
+                args: vec![],
+            }.into()),
+            expr!(ExpressionKind::New {
+                target: Box::new(ExpressionKind::Identifier { name: "self".into() }.into()),
+                args: vec![],
+            }.into()),
+            expr!(ExpressionKind::New {
+                target: Box::new(ExpressionKind::Identifier { name: "parent".into() }.into()),
+                args: vec![],
+            }.into()),
+        ]);
+
+        assert_ast("<?php new ($foo . 'Bar')();", &[
+            expr!(ExpressionKind::New {
+                target: Box::new(ExpressionKind::Infix {
+                    lhs: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                    op: InfixOp::Concat,
+                    rhs: Box::new(ExpressionKind::ConstantString { value: "Bar".into() }.into()),
+                }.into()),
+                args: vec![],
+            }.into())
         ]);
     }
 
     #[test]
-    fn ternary() {
-        assert_ast("<?php 1 ? 2 : 3;", &[
-            expr!(Expression::Ternary {
-                condition: Box::new(Expression::Int { i: 1 }),
-                then: Some(Box::new(Expression::Int { i: 2 })),
-                r#else: Box::new(Expression::Int { i: 3 }),
-            })
+    fn new_variable_class_name() {
+        assert_ast("<?php new $foo->bar();", &[
+            expr!(ExpressionKind::New {
+                target: Box::new(ExpressionKind::PropertyFetch {
+                    target: Box::new(ExpressionKind::Variable { name: "foo".into() }.into()),
+                    property: Box::new(ExpressionKind::Identifier { name: "bar".into() }.into()),
+                }.into()),
+                args: vec![],
+            }.into())
         ]);
 
-        assert_ast("<?php 1 ? 2 ? 3 : 4 : 5;", &[
-            expr!(Expression::Ternary {
-                condition: Box::new(Expression::Int { i: 1 }),
-                then: Some(Box::new(Expression::Ternary {
-                    condition: Box::new(Expression::Int { i: 2 }),
-                    then: Some(Box::new(Expression::Int { i: 3 })),
-                    r#else: Box::new(Expression::Int { i: 4 }),
-                })),
-                r#else: Box::new(Expression::Int { i: 5 }),
-            })
+        assert_ast("<?php (new Foo)->bar();", &[
+            expr!(ExpressionKind::MethodCall {
+                target: Box::new(ExpressionKind::New {
+                    target: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into()),
+                    args: vec![],
+                }.into()),
+                method: Box::new(ExpressionKind::Identifier { name: "bar".into() }.into()),
+                args: vec![],
+            }.into())
         ]);
     }
 
     #[test]
-    fn coalesce() {
-        assert_ast("<?php 1 ?? 2;", &[
-            expr!(Expression::Coalesce {
-                lhs: Box::new(Expression::Int { i: 1 }),
-                rhs: Box::new(Expression::Int { i: 2 })
-            })
+    fn new_anon_class() {
+        assert_ast("<?php new class{};", &[
+            expr!(ExpressionKind::New {
+                target: Box::new(ExpressionKind::AnonymousClass {
+                    extends: None,
+                    implements: vec![],
+                    body: vec![],
+                    attributes: vec![],
+                }.into()),
+                args: vec![],
+            }.into())
         ]);
 
-        assert_ast("<?php 1 ?? 2 ?? 3;", &[
-            expr!(Expression::Coalesce {
-                lhs: Box::new(Expression::Int { i: 1 }),
-                rhs: Box::new(Expression::Coalesce {
-                    lhs: Box::new(Expression::Int { i: 2 }),
-                    rhs: Box::new(Expression::Int { i: 3 })
-                })
-            })
+        assert_ast("<?php new class(1, 2) {};", &[
+            expr!(ExpressionKind::New {
+                target: Box::new(ExpressionKind::AnonymousClass {
+                    extends: None,
+                    implements: vec![],
+                    body: vec![],
+                    attributes: vec![],
+                }.into()),
+                args: vec![
+                    Arg {
+                        name: None,
+                        value: ExpressionKind::Int { i: 1 }.into(),
+                        unpack: false,
+                    },
+                    Arg {
+                        name: None,
+                        value: ExpressionKind::Int { i: 2 }.into(),
+                        unpack: false,
+                    },
+                ],
+            }.into())
+        ]);
+
+        assert_ast("<?php new class extends Foo {};", &[
+            expr!(ExpressionKind::New {
+                target: Box::new(ExpressionKind::AnonymousClass {
+                    extends: Some(Identifier::from("Foo")),
+                    implements: vec![],
+                    body: vec![],
+                    attributes: vec![],
+                }.into()),
+                args: vec![]
+            }.into())
+        ]);
+
+        assert_ast("<?php new class implements Foo, Bar {};", &[
+            expr!(ExpressionKind::New {
+                target: Box::new(ExpressionKind::AnonymousClass {
+                    extends: None,
+                    implements: vec![
+                        Identifier::from("Foo"),
+                        Identifier::from("Bar"),
+                    ],
+                    body: vec![],
+                    attributes: vec![],
+                }.into()),
+                args: vec![]
+            }.into())
+        ]);
+
+        assert_ast("<?php new class {
+            public function foo() {}
+        };", &[
+            expr!(ExpressionKind::New {
+                target: Box::new(ExpressionKind::AnonymousClass {
+                    extends: None,
+                    implements: vec![],
+                    body: vec![
+                        StatementKind::Method {
+                            name: "foo".into(),
+                            params: vec![],
+                            body: vec![],
+                            return_type: None,
+                            flags: vec![
+                                MethodFlag::Public,
+                            ],
+                            returns_by_ref: false,
+                            attributes: vec![],
+                            doc_comment: None,
+                        }.into()
+                    ],
+                    attributes: vec![],
+                }.into()),
+                args: vec![]
+            }.into())
         ]);
     }
 
     #[test]
-    fn array_index() {
-        assert_ast("<?php $foo['bar'];", &[
-            expr!(Expression::ArrayIndex {
-                array: Box::new(Expression::Variable { name: "foo".into() }),
-                index: Some(Box::new(Expression::ConstantString { value: "bar".into() }))
-            })
+    fn foreach() {
+        assert_ast("<?php foreach ($foo as $bar) {}", &[
+            StatementKind::Foreach {
+                expr: ExpressionKind::Variable { name: "foo".into() }.into(),
+                by_ref: false,
+                key_var: None,
+                value_var: ExpressionKind::Variable { name: "bar".into() }.into(),
+                body: vec![],
+            }.into()
         ]);
 
-        assert_ast("<?php $foo['bar']['baz'];", &[
-            expr!(Expression::ArrayIndex {
-                array: Box::new(Expression::ArrayIndex {
-                    array: Box::new(Expression::Variable { name: "foo".into() }),
-                    index: Some(Box::new(Expression::ConstantString { value: "bar".into() }))
-                }),
-                index: Some(Box::new(Expression::ConstantString { value: "baz".into() }))
-            })
+        assert_ast("<?php foreach ($foo as $bar => $baz) {}", &[
+            StatementKind::Foreach {
+                expr: ExpressionKind::Variable { name: "foo".into() }.into(),
+                by_ref: false,
+                key_var: Some(ExpressionKind::Variable { name: "bar".into() }.into()),
+                value_var: ExpressionKind::Variable { name: "baz".into() }.into(),
+                body: vec![],
+            }.into()
+        ]);
+
+        assert_ast("<?php foreach ($foo as [$baz, $car]) {}", &[
+            StatementKind::Foreach {
+                expr: ExpressionKind::Variable { name: "foo".into() }.into(),
+                by_ref: false,
+                key_var: None,
+                value_var: ExpressionKind::Array {
+                    items: vec![
+                        ArrayItem {
+                            key: None,
+                            value: ExpressionKind::Variable { name: "baz".into() }.into(),
+                            unpack: false,
+                            by_ref: false,
+                        },
+                        ArrayItem {
+                            key: None,
+                            value: ExpressionKind::Variable { name: "car".into() }.into(),
+                            unpack: false,
+                            by_ref: false,
+                        }
+                    ]
+                }.into(),
+                body: vec![],
+            }.into()
+        ]);
+
+        assert_ast("<?php foreach ($foo as &$bar) {}", &[
+            StatementKind::Foreach {
+                expr: ExpressionKind::Variable { name: "foo".into() }.into(),
+                by_ref: true,
+                key_var: None,
+                value_var: ExpressionKind::Variable { name: "bar".into() }.into(),
+                body: vec![],
+            }.into()
+        ]);
+
+        assert_ast("<?php foreach ($foo as $bar => &$baz) {}", &[
+            StatementKind::Foreach {
+                expr: ExpressionKind::Variable { name: "foo".into() }.into(),
+                by_ref: true,
+                key_var: Some(ExpressionKind::Variable { name: "bar".into() }.into()),
+                value_var: ExpressionKind::Variable { name: "baz".into() }.into(),
+                body: vec![],
+            }.into()
         ]);
+
+        assert_parse_error("<?php foreach ($foo as &$bar => $baz) {}");
     }
 
     #[test]
-    fn array_index_assign() {
-        assert_ast("<?php $foo['bar'] = 'baz';", &[
-            expr!(Expression::Infix{
-                lhs: Box::new(Expression::ArrayIndex {
-                    array: Box::new(Expression::Variable { name: "foo".into() }),
-                    index: Some(Box::new(Expression::ConstantString { value: "bar".into() }))
-                }),
-                op: InfixOp::Assign,
-                rhs: Box::new(Expression::ConstantString { value: "baz".into() })
-            })
+    fn halt_compiler() {
+        assert_ast("<?php echo 1; __halt_compiler(); <?php invalid {", &[
+            StatementKind::Echo { values: vec![ExpressionKind::Int { i: 1 }.into()] }.into(),
+            StatementKind::HaltCompiler { offset: 32, data: " <?php invalid {".into() }.into(),
         ]);
+
+        assert_ast("<?php __HALT_COMPILER() ?>\ndata", &[
+            StatementKind::HaltCompiler { offset: 27, data: "data".into() }.into(),
+        ]);
+
+        // Like a phar's archive, the data doesn't have to be UTF-8, but the code before it does.
+        let program = Parser::new(None).parse_bytes(b"<?php echo 1; __halt_compiler();\xff\x00data").unwrap();
+        assert_eq!(program[1].kind, StatementKind::HaltCompiler { offset: 32, data: b"\xff\x00data".to_vec() });
+        assert_eq!(crate::halt_offset(&program), Some(32));
+        assert_eq!(crate::halt_offset(&program[..1].to_vec()), None);
+        assert!(matches!(Parser::new(None).parse_bytes(b"<?php echo '\xff'; __halt_compiler();"), Err(Error::Io(_))));
     }
 
     #[test]
-    fn comparisons() {
-        assert_ast("<?php 1 == 1;", &[
-            expr!(Expression::Infix {
-                lhs: Box::new(Expression::Int { i: 1 }),
-                op: InfixOp::Equals,
-                rhs: Box::new(Expression::Int { i: 1 })
-            })
+    fn noop() {
+        assert_ast("<?php ;", &[
+            StatementKind::Noop.into(),
         ]);
+    }
 
-        assert_ast("<?php 1 === 1;", &[
-            expr!(Expression::Infix {
-                lhs: Box::new(Expression::Int { i: 1 }),
-                op: InfixOp::Identical,
-                rhs: Box::new(Expression::Int { i: 1 })
-            })
+    #[test]
+    fn by_ref_params() {
+        let program = crate::parse("<?php function f(&$a, int &...$b, A&B $c) {}").unwrap();
+        let StatementKind::Function { params, .. } = &program[0].kind else { panic!() };
+
+        assert_eq!(params.iter().map(|param| (param.by_ref, param.variadic)).collect::<Vec<_>>(), [(true, false), (true, true), (false, false)]);
+        assert_eq!(params[1].r#type, Some(Type::Plain("int".into())));
+        assert!(matches!(&params[2].r#type, Some(Type::Intersection(types)) if types.len() == 2));
+    }
+
+    #[test]
+    fn globals() {
+        assert_ast("<?php global $a, $b;", &[
+            StatementKind::Global {
+                vars: vec![
+                    ExpressionKind::Variable { name: "a".into() }.into(),
+                    ExpressionKind::Variable { name: "b".into() }.into(),
+                ],
+            }.into(),
         ]);
+    }
 
-        assert_ast("<?php 1 != 1;", &[
-            expr!(Expression::Infix {
-                lhs: Box::new(Expression::Int { i: 1 }),
-                op: InfixOp::NotEquals,
-                rhs: Box::new(Expression::Int { i: 1 })
-            })
+    #[test]
+    fn attributes_on_class() {
+        assert_ast("<?php #[Route('/home'), Cache(ttl: 60)] #[Final] class Foo {}", &[
+            StatementKind::Class {
+                name: "Foo".to_string().into(),
+                body: vec![],
+                extends: None,
+                implements: vec![],
+                flag: None,
+                attributes: vec![
+                    AttributeGroup {
+                        members: vec![
+                            Attribute {
+                                name: "Route".to_string().into(),
+                                args: vec![
+                                    Arg { name: None, value: ExpressionKind::ConstantString { value: "/home".into() }.into(), unpack: false },
+                                ],
+                            },
+                            Attribute {
+                                name: "Cache".to_string().into(),
+                                args: vec![
+                                    Arg { name: Some("ttl".into()), value: ExpressionKind::Int { i: 60 }.into(), unpack: false },
+                                ],
+                            },
+                        ],
+                    },
+                    AttributeGroup {
+                        members: vec![
+                            Attribute { name: "Final".to_string().into(), args: vec![] },
+                        ],
+                    },
+                ],
+                doc_comment: None,
+            }.into()
         ]);
+    }
 
-        assert_ast("<?php 1 !== 1;", &[
-            expr!(Expression::Infix {
-                lhs: Box::new(Expression::Int { i: 1 }),
-                op: InfixOp::NotIdentical,
-                rhs: Box::new(Expression::Int { i: 1 })
-            })
+    #[test]
+    fn attributes_on_members() {
+        assert_ast("<?php class Foo { #[Inject] public $bar; #[Test] public function baz() {} }", &[
+            class!("Foo", &[
+                StatementKind::Property {
+                    var: "bar".into(),
+                    value: None,
+                    r#type: None,
+                    flags: vec![PropertyFlag::Public],
+                    hooks: vec![],
+                    attributes: vec![
+                        AttributeGroup { members: vec![Attribute { name: "Inject".to_string().into(), args: vec![] }] },
+                    ],
+                    doc_comment: None,
+                }.into(),
+                StatementKind::Method {
+                    name: "baz".to_string().into(),
+                    params: vec![],
+                    flags: vec![MethodFlag::Public],
+                    body: vec![],
+                    return_type: None,
+                    returns_by_ref: false,
+                    attributes: vec![
+                        AttributeGroup { members: vec![Attribute { name: "Test".to_string().into(), args: vec![] }] },
+                    ],
+                    doc_comment: None,
+                }.into(),
+            ])
         ]);
     }
 
     #[test]
-    fn paren_expression() {
-        assert_ast("<?php (1 + 2);", &[
-            Statement::Expression { expr: Expression::Infix {
-                lhs: Box::new(Expression::Int { i: 1 }),
-                op: InfixOp::Add,
-                rhs: Box::new(Expression::Int { i: 2 })
-            }}
+    fn attributes_on_params() {
+        assert_ast("<?php function foo(#[Sensitive] $bar) {}", &[
+            StatementKind::Function {
+                name: "foo".to_string().into(),
+                params: vec![
+                    Param {
+                        name: ExpressionKind::Variable { name: "bar".into() }.into(),
+                        r#type: None,
+                        by_ref: false,
+                        variadic: false,
+                        default: None,
+                        flags: vec![],
+                        attributes: vec![
+                            AttributeGroup { members: vec![Attribute { name: "Sensitive".to_string().into(), args: vec![] }] },
+                        ],
+                        span: Span::default(),
+                    }
+                ],
+                body: vec![],
+                return_type: None,
+                returns_by_ref: false,
+                attributes: vec![],
+                doc_comment: None,
+            }.into()
         ]);
     }
 
     #[test]
-    fn breaks() {
-        assert_ast("<?php break;", &[
-            Statement::Break { num: None }
+    fn attributes_before_member_values() {
+        assert_ast("<?php class Foo { #[A] const B = [fn() => 1]; #[C] var $d = [fn() => 1]; #[E] public int $f = 1; }", &[
+            class!("Foo", &[
+                StatementKind::Constant {
+                    name: "B".to_string().into(),
+                    value: arrow_fn_array(),
+                    flags: vec![],
+                    attributes: vec![AttributeGroup { members: vec![Attribute { name: "A".to_string().into(), args: vec![] }] }],
+                    doc_comment: None,
+                }.into(),
+                StatementKind::Var {
+                    var: "d".into(),
+                    value: Some(arrow_fn_array()),
+                    r#type: None,
+                    attributes: vec![AttributeGroup { members: vec![Attribute { name: "C".to_string().into(), args: vec![] }] }],
+                }.into(),
+                StatementKind::Property {
+                    var: "f".into(),
+                    value: Some(ExpressionKind::Int { i: 1 }.into()),
+                    r#type: Some(Type::Plain("int".into())),
+                    flags: vec![PropertyFlag::Public],
+                    hooks: vec![],
+                    attributes: vec![AttributeGroup { members: vec![Attribute { name: "E".to_string().into(), args: vec![] }] }],
+                    doc_comment: None,
+                }.into(),
+            ])
+        ]);
+    }
+
+    fn arrow_fn_array() -> Expression {
+        ExpressionKind::Array {
+            items: vec![
+                ArrayItem {
+                    key: None,
+                    value: ExpressionKind::ArrowFunction {
+                        params: vec![],
+                        return_type: None,
+                        returns_by_ref: false,
+                        expr: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                        attributes: vec![],
+                    }.into(),
+                    unpack: false,
+                    by_ref: false,
+                },
+            ],
+        }.into()
+    }
+
+    #[test]
+    fn attributes_without_a_target() {
+        assert_parse_error("<?php #[A] echo 1; function f() {}");
+        assert_parse_error("<?php #[A] $a = 1;");
+        assert_parse_error("<?php class Foo { #[A] use Bar; }");
+        assert_parse_error("<?php enum Foo { #[A] use Bar; case B; }");
+        assert_parse_error("<?php $a = #[A] 1;");
+        assert_parse_error("<?php new #[A] Foo;");
+    }
+
+    #[test]
+    fn attributes_on_enum_cases() {
+        assert_ast("<?php enum Foo { #[Label('a')] case Bar; }", &[
+            StatementKind::Enum {
+                name: "Foo".to_string().into(),
+                implements: vec![],
+                backed_type: None,
+                body: vec![
+                    StatementKind::EnumCase {
+                        name: "Bar".to_string().into(),
+                        value: None,
+                        attributes: vec![
+                            AttributeGroup {
+                                members: vec![
+                                    Attribute {
+                                        name: "Label".to_string().into(),
+                                        args: vec![
+                                            Arg { name: None, value: ExpressionKind::ConstantString { value: "a".into() }.into(), unpack: false },
+                                        ],
+                                    },
+                                ],
+                            },
+                        ],
+                    }.into(),
+                ],
+                attributes: vec![],
+            }.into()
         ]);
+    }
 
-        assert_ast("<?php break 2;", &[
-            Statement::Break { num: Some(Expression::Int { i: 2 }) }
+    #[test]
+    fn enum_cases_with_constant_expressions() {
+        assert_ast("<?php enum Foo: int implements Bar, Baz { case A = 1 << 2; case B = self::PREFIX . 'b'; }", &[
+            StatementKind::Enum {
+                name: "Foo".to_string().into(),
+                implements: vec!["Bar".to_string().into(), "Baz".to_string().into()],
+                backed_type: Some(BackedEnumType::Int),
+                body: vec![
+                    StatementKind::EnumCase {
+                        name: "A".to_string().into(),
+                        value: Some(ExpressionKind::Infix {
+                            lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                            op: InfixOp::LeftShift,
+                            rhs: Box::new(ExpressionKind::Int { i: 2 }.into()),
+                        }.into()),
+                        attributes: vec![],
+                    }.into(),
+                    StatementKind::EnumCase {
+                        name: "B".to_string().into(),
+                        value: Some(ExpressionKind::Infix {
+                            lhs: Box::new(ExpressionKind::ConstFetch {
+                                target: Box::new(ExpressionKind::Identifier { name: "self".into() }.into()),
+                                constant: "PREFIX".to_string().into(),
+                            }.into()),
+                            op: InfixOp::Concat,
+                            rhs: Box::new(ExpressionKind::ConstantString { value: "b".into() }.into()),
+                        }.into()),
+                        attributes: vec![],
+                    }.into(),
+                ],
+                attributes: vec![],
+            }.into()
         ]);
     }
 
     #[test]
-    fn continues() {
-        assert_ast("<?php continue;", &[
-            Statement::Continue { num: None }
+    fn new_in_initializers() {
+        assert_ast("<?php #[Attr(new Foo)] function foo($bar = new Bar(1)) {}", &[
+            StatementKind::Function {
+                name: "foo".to_string().into(),
+                params: vec![
+                    Param {
+                        name: ExpressionKind::Variable { name: "bar".into() }.into(),
+                        r#type: None,
+                        by_ref: false,
+                        variadic: false,
+                        default: Some(ExpressionKind::New {
+                            target: Box::new(ExpressionKind::Identifier { name: "Bar".into() }.into()),
+                            args: vec![
+                                Arg { name: None, value: ExpressionKind::Int { i: 1 }.into(), unpack: false },
+                            ],
+                        }.into()),
+                        flags: vec![],
+                        attributes: vec![],
+                        span: Span::default(),
+                    },
+                ],
+                body: vec![],
+                return_type: None,
+                returns_by_ref: false,
+                attributes: vec![
+                    AttributeGroup {
+                        members: vec![
+                            Attribute {
+                                name: "Attr".to_string().into(),
+                                args: vec![
+                                    Arg {
+                                        name: None,
+                                        value: ExpressionKind::New {
+                                            target: Box::new(ExpressionKind::Identifier { name: "Foo".into() }.into()),
+                                            args: vec![],
+                                        }.into(),
+                                        unpack: false,
+                                    },
+                                ],
+                            },
+                        ],
+                    },
+                ],
+                doc_comment: None,
+            }.into()
         ]);
 
-        assert_ast("<?php continue 2;", &[
-            Statement::Continue { num: Some(Expression::Int { i: 2 }) }
+        assert_ast("<?php class Foo { const BAR = new Bar; public static $baz = new Baz(); }", &[
+            class!("Foo", &[
+                StatementKind::Constant {
+                    name: "BAR".to_string().into(),
+                    value: ExpressionKind::New {
+                        target: Box::new(ExpressionKind::Identifier { name: "Bar".into() }.into()),
+                        args: vec![],
+                    }.into(),
+                    flags: vec![],
+                    attributes: vec![],
+                    doc_comment: None,
+                }.into(),
+                StatementKind::Property {
+                    var: "baz".into(),
+                    value: Some(ExpressionKind::New {
+                        target: Box::new(ExpressionKind::Identifier { name: "Baz".into() }.into()),
+                        args: vec![],
+                    }.into()),
+                    r#type: None,
+                    flags: vec![PropertyFlag::Public, PropertyFlag::Static],
+                    hooks: vec![],
+                    attributes: vec![],
+                    doc_comment: None,
+                }.into(),
+            ])
         ]);
     }
 
     #[test]
-    fn math_precedence() {
-        assert_ast("<?php 1 + 2 * 3 / 4 - 5;", &[
-            expr!(Expression::Infix {
-                lhs: Box::new(Expression::Infix {
-                    lhs: Box::new(Expression::Int { i: 1 }),
-                    op: InfixOp::Add,
-                    rhs: Box::new(Expression::Infix {
-                        lhs: Box::new(Expression::Infix {
-                            lhs: Box::new(Expression::Int { i: 2 }),
-                            op: InfixOp::Mul,
-                            rhs: Box::new(Expression::Int { i: 3 })
-                        }),
-                        op: InfixOp::Div,
-                        rhs: Box::new(Expression::Int { i: 4 })
-                    })
-                }),
-                op: InfixOp::Sub,
-                rhs: Box::new(Expression::Int { i: 5 })
-            })
+    fn const_statements() {
+        assert_ast("<?php const FOO = 1, BAR = FOO + 1;", &[
+            StatementKind::Const {
+                constants: vec![
+                    Constant { name: "FOO".to_string().into(), value: ExpressionKind::Int { i: 1 }.into() },
+                    Constant {
+                        name: "BAR".to_string().into(),
+                        value: ExpressionKind::Infix {
+                            lhs: Box::new(ExpressionKind::Identifier { name: "FOO".into() }.into()),
+                            op: InfixOp::Add,
+                            rhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+                        }.into(),
+                    },
+                ],
+                doc_comment: None,
+            }.into()
         ]);
     }
 
     #[test]
-    fn property_fetch() {
-        assert_ast("<?php $foo->bar; $foo->bar->baz;", &[
-            expr!(Expression::PropertyFetch {
-                target: Box::new(Expression::Variable { name: "foo".into() }),
-                property: Box::new(Expression::Identifier { name: "bar".into() })
-            }),
-            expr!(Expression::PropertyFetch {
-                target: Box::new(Expression::PropertyFetch {
-                    target: Box::new(Expression::Variable { name: "foo".into() }),
-                    property: Box::new(Expression::Identifier { name: "bar".into() })
-                }),
-                property: Box::new(Expression::Identifier { name: "baz".into() })
-            })
+    fn conditional_declarations() {
+        assert_ast("<?php if (!function_exists('foo')) { function foo() {} class Foo {} const FOO = 1; }", &[
+            StatementKind::If {
+                condition: ExpressionKind::BooleanNot {
+                    value: Box::new(ExpressionKind::Call {
+                        target: Box::new(ExpressionKind::Identifier { name: "function_exists".into() }.into()),
+                        args: vec![
+                            Arg { name: None, value: ExpressionKind::ConstantString { value: "foo".into() }.into(), unpack: false },
+                        ],
+                    }.into()),
+                }.into(),
+                then: vec![
+                    function!("foo", &[], &[]),
+                    class!("Foo"),
+                    StatementKind::Const {
+                        constants: vec![
+                            Constant { name: "FOO".to_string().into(), value: ExpressionKind::Int { i: 1 }.into() },
+                        ],
+                        doc_comment: None,
+                    }.into(),
+                ],
+                else_ifs: vec![],
+                r#else: None,
+            }.into()
         ]);
-    }
 
-    #[test]
-    fn method_calls() {
-        assert_ast("<?php $foo->bar();", &[
-            expr!(Expression::MethodCall {
-                target: Box::new(Expression::Variable { name: "foo".into() }),
-                method: Box::new(Expression::Identifier { name: "bar".into() }),
-                args: vec![]
-            })
+        assert_ast("<?php function outer() { function inner() {} }", &[
+            function!("outer", &[], &[
+                function!("inner", &[], &[]),
+            ]),
         ]);
+    }
 
-        assert_ast("<?php $foo->bar()->baz();", &[
-            expr!(Expression::MethodCall {
-                target: Box::new(Expression::MethodCall {
-                    target: Box::new(Expression::Variable { name: "foo".into() }),
-                    method: Box::new(Expression::Identifier { name: "bar".into() }),
-                    args: vec![]
-                }),
-                method: Box::new(Expression::Identifier { name: "baz".into() }),
-                args: vec![]
-            })
+    #[test]
+    fn asymmetric_visibility() {
+        assert_ast("<?php class Foo { public private(set) int $x; protected(set) string $y; public function __construct(public protected(set) int $z) {} }", &[
+            class!("Foo", &[
+                StatementKind::Property {
+                    var: "x".into(),
+                    value: None,
+                    r#type: Some(Type::Plain("int".into())),
+                    flags: vec![PropertyFlag::Public, PropertyFlag::PrivateSet],
+                    hooks: vec![],
+                    attributes: vec![],
+                    doc_comment: None,
+                }.into(),
+                StatementKind::Property {
+                    var: "y".into(),
+                    value: None,
+                    r#type: Some(Type::Plain("string".into())),
+                    flags: vec![PropertyFlag::ProtectedSet],
+                    hooks: vec![],
+                    attributes: vec![],
+                    doc_comment: None,
+                }.into(),
+                StatementKind::Method {
+                    name: "__construct".to_string().into(),
+                    params: vec![
+                        Param {
+                            name: ExpressionKind::Variable { name: "z".into() }.into(),
+                            r#type: Some(Type::Plain("int".into())),
+                            by_ref: false,
+                            variadic: false,
+                            default: None,
+                            flags: vec![PropertyFlag::Public, PropertyFlag::ProtectedSet],
+                            attributes: vec![],
+                            span: Span::default(),
+                        },
+                    ],
+                    body: vec![],
+                    flags: vec![MethodFlag::Public],
+                    return_type: None,
+                    returns_by_ref: false,
+                    attributes: vec![],
+                    doc_comment: None,
+                }.into(),
+            ])
         ]);
 
-        assert_ast("<?php $foo->bar()();", &[
-            expr!(Expression::Call {
-                target: Box::new(Expression::MethodCall {
-                    target: Box::new(Expression::Variable{ name: "foo".into() }),
-                    method: Box::new(Expression::Identifier { name: "bar".into() }),
-                    args: vec![]
-                }),
-                args: vec![]
-            })
-        ]);
+        assert_parse_error("<?php class Foo { private(set) function bar() {} }");
+        assert_parse_error("<?php class Foo { public private(set) const BAR = 1; }");
     }
 
     #[test]
-    fn concat() {
-        assert_ast("<?php 'foo' . 'bar' . 'baz';", &[
-            expr!(Expression::Infix {
-                lhs: Box::new(Expression::ConstantString { value: "foo".into() }),
-                op: InfixOp::Concat,
-                rhs: Box::new(Expression::Infix {
-                    lhs: Box::new(Expression::ConstantString { value: "bar".into() }),
-                    op: InfixOp::Concat,
-                    rhs: Box::new(Expression::ConstantString { value: "baz".into() }),
-                })
-            })
+    fn readonly_properties() {
+        assert_ast("<?php class Foo { public readonly int $x; public function __construct(private readonly string $y) {} }", &[
+            class!("Foo", &[
+                StatementKind::Property {
+                    var: "x".into(),
+                    value: None,
+                    r#type: Some(Type::Plain("int".into())),
+                    flags: vec![PropertyFlag::Public, PropertyFlag::Readonly],
+                    hooks: vec![],
+                    attributes: vec![],
+                    doc_comment: None,
+                }.into(),
+                StatementKind::Method {
+                    name: "__construct".to_string().into(),
+                    params: vec![
+                        Param {
+                            name: ExpressionKind::Variable { name: "y".into() }.into(),
+                            r#type: Some(Type::Plain("string".into())),
+                            by_ref: false,
+                            variadic: false,
+                            default: None,
+                            flags: vec![PropertyFlag::Private, PropertyFlag::Readonly],
+                            attributes: vec![],
+                            span: Span::default(),
+                        },
+                    ],
+                    body: vec![],
+                    flags: vec![MethodFlag::Public],
+                    return_type: None,
+                    returns_by_ref: false,
+                    attributes: vec![],
+                    doc_comment: None,
+                }.into(),
+            ])
         ]);
+
+        assert_parse_error("<?php class Foo { readonly function bar() {} }");
     }
 
     #[test]
-    fn empty_fn() {
-        assert_ast("<?php function foo() {}", &[
-            function!("foo", &[], &[]),
+    fn property_hooks() {
+        assert_ast("<?php class Foo { public string $x { get => $this->y; final set(string $value) { $this->y = $value; } } }", &[
+            class!("Foo", &[
+                StatementKind::Property {
+                    var: "x".into(),
+                    value: None,
+                    r#type: Some(Type::Plain("string".into())),
+                    flags: vec![PropertyFlag::Public],
+                    hooks: vec![
+                        PropertyHook {
+                            name: "get".to_string().into(),
+                            params: vec![],
+                            body: Some(PropertyHookBody::Expression(ExpressionKind::PropertyFetch {
+                                target: Box::new(ExpressionKind::Variable { name: "this".into() }.into()),
+                                property: Box::new(ExpressionKind::Identifier { name: "y".into() }.into()),
+                            }.into())),
+                            by_ref: false,
+                            flags: vec![],
+                            attributes: vec![],
+                        },
+                        PropertyHook {
+                            name: "set".to_string().into(),
+                            params: vec![
+                                Param {
+                                    name: ExpressionKind::Variable { name: "value".into() }.into(),
+                                    r#type: Some(Type::Plain("string".into())),
+                                    by_ref: false,
+                                    variadic: false,
+                                    default: None,
+                                    flags: vec![],
+                                    attributes: vec![],
+                                    span: Span::default(),
+                                },
+                            ],
+                            body: Some(PropertyHookBody::Block(vec![
+                                StatementKind::Expression {
+                                    expr: ExpressionKind::Assign {
+                                        var: Box::new(ExpressionKind::PropertyFetch {
+                                            target: Box::new(ExpressionKind::Variable { name: "this".into() }.into()),
+                                            property: Box::new(ExpressionKind::Identifier { name: "y".into() }.into()),
+                                        }.into()),
+                                        value: Box::new(ExpressionKind::Variable { name: "value".into() }.into()),
+                                    }.into(),
+                                }.into(),
+                            ])),
+                            by_ref: false,
+                            flags: vec![MethodFlag::Final],
+                            attributes: vec![],
+                        },
+                    ],
+                    attributes: vec![],
+                    doc_comment: None,
+                }.into(),
+            ])
         ]);
+
+        assert!(crate::parse("<?php class Foo { public int $x { &get; set; } }").is_ok());
+        assert_parse_error("<?php class Foo { public int $x { foo => 1; } }");
     }
 
     #[test]
-    fn empty_fn_with_params() {
-        assert_ast("<?php function foo($n) {}", &[
-            function!("foo", &["n"], &[]),
+    fn interfaces() {
+        assert_ast("<?php interface Foo extends Bar { const A = 1; final public const B = 2; public static function create(): static; function &bar($baz); }", &[
+            StatementKind::Interface {
+                name: "Foo".to_string().into(),
+                extends: vec!["Bar".to_string().into()],
+                body: vec![
+                    StatementKind::Constant {
+                        name: "A".to_string().into(),
+                        value: ExpressionKind::Int { i: 1 }.into(),
+                        flags: vec![],
+                        attributes: vec![],
+                        doc_comment: None,
+                    }.into(),
+                    StatementKind::Constant {
+                        name: "B".to_string().into(),
+                        value: ExpressionKind::Int { i: 2 }.into(),
+                        flags: vec![ConstFlag::Final, ConstFlag::Public],
+                        attributes: vec![],
+                        doc_comment: None,
+                    }.into(),
+                    StatementKind::Method {
+                        name: "create".to_string().into(),
+                        params: vec![],
+                        body: vec![],
+                        flags: vec![MethodFlag::Public, MethodFlag::Static],
+                        return_type: Some(Type::Plain("static".into())),
+                        returns_by_ref: false,
+                        attributes: vec![],
+                        doc_comment: None,
+                    }.into(),
+                    StatementKind::Method {
+                        name: "bar".to_string().into(),
+                        params: vec![Param::from("baz")],
+                        body: vec![],
+                        flags: vec![],
+                        return_type: None,
+                        returns_by_ref: true,
+                        attributes: vec![],
+                        doc_comment: None,
+                    }.into(),
+                ],
+                attributes: vec![],
+            }.into()
         ]);
 
-        assert_ast("<?php function foo($n, $m) {}", &[
-            function!("foo", &["n", "m"], &[]),
-        ]);
+        assert_parse_error("<?php interface Foo { public function bar() {} }");
+        assert_parse_error("<?php interface Foo { static const A = 1; }");
     }
 
     #[test]
-    fn fib() {
-        assert_ast("\
-        <?php
+    fn trait_constants() {
+        assert_ast("<?php trait Foo { const BAR = 1; }", &[
+            StatementKind::Trait {
+                name: "Foo".to_string().into(),
+                body: vec![
+                    StatementKind::Constant {
+                        name: "BAR".to_string().into(),
+                        value: ExpressionKind::Int { i: 1 }.into(),
+                        flags: vec![],
+                        attributes: vec![],
+                        doc_comment: None,
+                    }.into(),
+                ],
+                attributes: vec![],
+            }.into()
+        ]);
 
-        function fib($n) {
-            if ($n < 2) {
-                return $n;
-            }
+        let tokens = Lexer::new(None).tokenize("<?php trait Foo { const BAR = 1; }").unwrap();
+        let mut parser = Parser::new(Some(ParserConfig { php_version: PhpVersion::Php82, ..Default::default() }));
 
-            return fib($n - 1) + fib($n - 2);
-        }", &[
-            function!("fib", &["n"], &[
-                Statement::If {
-                    condition: Expression::Infix {
-                        lhs: Box::new(Expression::Variable { name: "n".into() }),
-                        op: InfixOp::LessThan,
-                        rhs: Box::new(Expression::Int { i: 2 }),
-                    },
-                    then: vec![
-                        Statement::Return { value: Some(Expression::Variable { name: "n".into() }) }
-                    ],
-                    else_ifs: vec![],
-                    r#else: None
-                },
-                Statement::Return {
-                    value: Some(Expression::Infix {
-                        lhs: Box::new(Expression::Call {
-                            target: Box::new(Expression::Identifier { name: "fib".into() }),
-                            args: vec![
-                                Arg {
-                                    name: None,
-                                    value: Expression::Infix {
-                                        lhs: Box::new(Expression::Variable { name: "n".into() }),
-                                        op: InfixOp::Sub,
-                                        rhs: Box::new(Expression::Int { i: 1 }),
-                                    },
-                                    unpack: false,
-                                }
-                            ]
-                        }),
-                        op: InfixOp::Add,
-                        rhs: Box::new(Expression::Call {
-                            target: Box::new(Expression::Identifier { name: "fib".into() }),
-                            args: vec![
-                                Arg {
-                                    name: None,
-                                    value: Expression::Infix {
-                                        lhs: Box::new(Expression::Variable { name: "n".into() }),
-                                        op: InfixOp::Sub,
-                                        rhs: Box::new(Expression::Int { i: 2 }),
-                                    },
-                                    unpack: false,
-                                }
-                            ]
-                        }),
-                    })
-                }
-            ])
-        ]);
+        assert!(matches!(parser.parse(&tokens), Err(ParseError::TraitCannotContainConstant(_))));
     }
 
     #[test]
-    fn one_liner_if_statement() {
-        assert_ast("<?php if($foo) return $foo;", &[
-                Statement::If {
-                    condition: Expression::Variable { name: "foo".into() },
-                    then: vec![
-                        Statement::Return { value: Some(Expression::Variable { name: "foo".into() }) }
-                    ],
-                    else_ifs: vec![],
-                    r#else: None
+    fn php_version_targeting() {
+        let parse = |source: &str, php_version: PhpVersion| {
+            let tokens = Lexer::new(None).tokenize(source).unwrap();
+            Parser::new(Some(ParserConfig { php_version, ..Default::default() })).parse(&tokens)
+        };
+
+        match parse("<?php enum Foo {}", PhpVersion::Php80) {
+            Err(err @ ParseError::RequiresPhpVersion { version: PhpVersion::Php81, .. }) => {
+                assert_eq!(err.to_string(), "Parse error: enums require PHP 8.1 or newer on line 1");
+            },
+            result => panic!("expected version error, got {:?}", result),
+        }
+        assert!(parse("<?php enum Foo {}", PhpVersion::Php81).is_ok());
+
+        assert!(matches!(parse("<?php match ($a) { default => 1 };", PhpVersion::Php74), Err(ParseError::RequiresPhpVersion { .. })));
+        assert!(parse("<?php match ($a) { default => 1 };", PhpVersion::Php80).is_ok());
+
+        assert!(matches!(parse("<?php foo(bar: 1);", PhpVersion::Php74), Err(ParseError::RequiresPhpVersion { .. })));
+        assert!(parse("<?php foo(1);", PhpVersion::Php74).is_ok());
+
+        assert!(matches!(parse("<?php fn() => 1;", PhpVersion::Php73), Err(ParseError::RequiresPhpVersion { .. })));
+        assert!(parse("<?php fn() => 1;", PhpVersion::Php74).is_ok());
+
+        let promoted = "<?php class A { function __construct(private $a) {} }";
+        assert!(matches!(parse(promoted, PhpVersion::Php74), Err(ParseError::RequiresPhpVersion { .. })));
+        assert!(parse(promoted, PhpVersion::Php80).is_ok());
+
+        for source in ["<?php class A { public private(set) int $a; }", "<?php class A { protected(set) int $a; }", "<?php class A { function __construct(public private(set) int $a) {} }"] {
+            match parse(source, PhpVersion::Php83) {
+                Err(err @ ParseError::RequiresPhpVersion { version: PhpVersion::Php84, .. }) => {
+                    assert_eq!(err.to_string(), "Parse error: asymmetric visibility requires PHP 8.4 or newer on line 1");
                 },
-        ]);
-    }
+                result => panic!("expected version error for {}, got {:?}", source, result),
+            }
+            assert!(parse(source, PhpVersion::Php84).is_ok(), "{}", source);
+        }
 
-    #[test]
-    fn if_else_statement() {
-        assert_ast("<?php if($foo) { return $foo; } else { return $foo; }", &[
-                Statement::If {
-                    condition: Expression::Variable { name: "foo".into() },
-                    then: vec![
-                        Statement::Return { value: Some(Expression::Variable { name: "foo".into() }) }
-                    ],
-                    else_ifs: vec![],
-                    r#else: Some(vec![
-                        Statement::Return { value: Some(Expression::Variable { name: "foo".into() }) }
-                    ])
+        for source in ["<?php class A { public readonly int $a; }", "<?php class A { function __construct(readonly int $a) {} }"] {
+            match parse(source, PhpVersion::Php80) {
+                Err(err @ ParseError::RequiresPhpVersion { version: PhpVersion::Php81, .. }) => {
+                    assert_eq!(err.to_string(), "Parse error: readonly properties require PHP 8.1 or newer on line 1");
                 },
-        ]);
+                result => panic!("expected version error for {}, got {:?}", source, result),
+            }
+            assert!(parse(source, PhpVersion::Php81).is_ok(), "{}", source);
+        }
+
+        let hooked = "<?php class A { public int $a { get => 1; } }";
+        match parse(hooked, PhpVersion::Php83) {
+            Err(err @ ParseError::RequiresPhpVersion { version: PhpVersion::Php84, .. }) => {
+                assert_eq!(err.to_string(), "Parse error: property hooks require PHP 8.4 or newer on line 1");
+            },
+            result => panic!("expected version error, got {:?}", result),
+        }
+        assert!(parse(hooked, PhpVersion::Php84).is_ok());
+
+        assert_eq!("8.1".parse(), Ok(PhpVersion::Php81));
+        assert_eq!(PhpVersion::Php80.to_string().parse(), Ok(PhpVersion::Php80));
+        assert!("7.2".parse::<PhpVersion>().is_err());
     }
 
     #[test]
-    fn if_elseif_else_statement() {
-        assert_ast("<?php if($foo) { return $foo; } elseif($foo) { return $foo; } else { return $foo; }", &[
-                Statement::If {
-                    condition: Expression::Variable { name: "foo".into() },
-                    then: vec![
-                        Statement::Return { value: Some(Expression::Variable { name: "foo".into() }) }
-                    ],
-                    else_ifs: vec![
-                        ElseIf {
-                            condition: Expression::Variable { name: "foo".into() },
-                            body: vec![
-                                Statement::Return { value: Some(Expression::Variable { name: "foo".into() }) }
-                            ]
-                        }
-                    ],
-                    r#else: Some(vec![
-                        Statement::Return { value: Some(Expression::Variable { name: "foo".into() }) }
-                    ])
-                },
-        ]);
+    fn trailing_commas() {
+        let parse = |source: &str, php_version: PhpVersion, trailing_commas: TrailingCommas| {
+            let tokens = Lexer::new(None).tokenize(source).unwrap();
+            Parser::new(Some(ParserConfig { php_version, trailing_commas, ..Default::default() })).parse(&tokens)
+        };
+
+        for source in ["<?php foo(1, 2,);", "<?php $a = [1, 2,];", "<?php $a = array(1,);"] {
+            assert!(parse(source, PhpVersion::Php74, TrailingCommas::default()).is_ok(), "{}", source);
+        }
+
+        let source = "<?php function foo($a, $b,) {}";
+        match parse(source, PhpVersion::Php74, TrailingCommas::default()) {
+            Err(err @ ParseError::RequiresPhpVersion { version: PhpVersion::Php80, .. }) => {
+                assert_eq!(err.to_string(), "Parse error: trailing commas in parameter lists require PHP 8.0 or newer on line 1");
+            },
+            result => panic!("expected version error, got {:?}", result),
+        }
+        assert!(parse(source, PhpVersion::Php80, TrailingCommas::default()).is_ok());
+        assert!(parse(source, PhpVersion::Php74, TrailingCommas { params: Some(true), ..Default::default() }).is_ok());
+        assert!(matches!(parse("<?php function () use ($a,) {};", PhpVersion::Php74, TrailingCommas::default()), Err(ParseError::RequiresPhpVersion { .. })));
+
+        let deny = TrailingCommas { args: Some(false), arrays: Some(false), params: Some(false) };
+        for source in ["<?php foo(1, 2,);", "<?php $a = [1, 2,];", "<?php function foo($a,) {}"] {
+            assert!(matches!(parse(source, PhpVersion::Php83, deny.clone()), Err(ParseError::UnexpectedToken(..))), "{}", source);
+        }
+        assert!(parse("<?php foo(1, 2); $a = [1, 2]; function foo($a) {}", PhpVersion::Php83, deny).is_ok());
+
+        // Items always need to be separated, and `echo` never allows a trailing comma.
+        for source in ["<?php foo(1 2);", "<?php $a = [1 2];", "<?php function foo($a $b) {}", "<?php echo 1, 2,;", "<?php echo 1 2;", "<?php echo;"] {
+            assert!(parse(source, PhpVersion::Php83, TrailingCommas::default()).is_err(), "{}", source);
+        }
     }
 
     #[test]
-    fn echo() {
-        assert_ast("<?php echo 1;", &[
-            Statement::Echo {
-                values: vec![
-                    Expression::Int { i: 1 },
-                ]
+    fn keeping_comments() {
+        let comments = |source: &str, comments: bool| {
+            let tokens = Lexer::new(None).tokenize(source).unwrap();
+            let program = Parser::new(Some(ParserConfig { comments, ..Default::default() })).parse(&tokens).unwrap();
+
+            fn walk(statements: &[Statement], found: &mut Vec<String>) {
+                for statement in statements {
+                    match &statement.kind {
+                        StatementKind::Comment { comment } => found.push(comment.clone()),
+                        StatementKind::Function { body, .. } | StatementKind::Method { body, .. } | StatementKind::Class { body, .. }
+                        | StatementKind::Interface { body, .. } | StatementKind::Enum { body, .. } => walk(body, found),
+                        _ => {},
+                    }
+                }
             }
-        ]);
+
+            let mut found = Vec::new();
+            walk(&program, &mut found);
+            found
+        };
+
+        let source = "<?php
+// a
+$a = 1; // b
+/** c */
+$b = 2;
+/** Claimed by the function. */
+function f() { /* d */ }
+class A {
+    // e
+    /** Claimed by the constant. */
+    const B = 1;
+    # f
+}
+interface I { function g(); // i
+}
+enum E { case A; /* g */ }
+// h";
+
+        assert_eq!(comments(source, true), ["// a", "// b", "/** c */", "/* d */", "// e", "# f", "// i", "/* g */", "// h"]);
+        assert!(comments(source, false).is_empty());
     }
 
     #[test]
-    fn empty_class() {
-        assert_ast("<?php class Foo {}", &[
-            class!("Foo")
-        ]);
+    fn warnings() {
+        let warnings = |source: &str, php_version: PhpVersion| {
+            let tokens = Lexer::new(None).tokenize(source).unwrap();
+            let mut parser = Parser::new(Some(ParserConfig { php_version, ..Default::default() }));
+            parser.parse(&tokens).unwrap();
+            parser.warnings().to_vec()
+        };
+
+        for source in ["<?php $s{0};", "<?php $a = (real) $b;"] {
+            assert!(matches!(warnings(source, PhpVersion::Php74).as_slice(), [ParseWarning::Deprecated { since: PhpVersion::Php74, .. }]), "{}", source);
+            assert!(matches!(warnings(source, PhpVersion::Php80).as_slice(), [ParseWarning::Removed { version: PhpVersion::Php80, .. }]), "{}", source);
+        }
+
+        assert_eq!(
+            warnings("<?php
+$s{0};", PhpVersion::Php80)[0].to_string(),
+            "Warning: array and string offset access with curly braces is no longer supported as of PHP 8.0 on line 2",
+        );
+
+        assert!(warnings("<?php ${'a'}; $$b = ${'c' . 'd'}[0];", PhpVersion::Php80).is_empty());
+
+        let source = "<?php class A { public $b; function __construct() { $this->a = 1; $this->a = 2; $this->b = 3; } }";
+        match warnings(source, PhpVersion::Php82).as_slice() {
+            [warning @ ParseWarning::Deprecated { span, .. }] => {
+                assert_eq!(&source[span.start..span.end], "$this->a");
+                assert_eq!(warning.to_string(), "Deprecated: creating dynamic properties is deprecated since PHP 8.2 on line 1");
+            },
+            warnings => panic!("unexpected warnings {:?}", warnings),
+        }
+
+        assert!(warnings(source, PhpVersion::Php81).is_empty());
+
+        for source in [
+            "<?php #[AllowDynamicProperties] class A { function f() { $this->a = 1; } }",
+            "<?php class A extends B { function f() { $this->a = 1; } }",
+            "<?php class A { function __construct(private $a) { $this->a = 1; } }",
+            "<?php class A { function __set($k, $v) {} function f() { $this->a = 1; } }",
+        ] {
+            assert!(warnings(source, PhpVersion::Php82).is_empty(), "{}", source);
+        }
+
+        let (first, second) = (Lexer::new(None).tokenize("<?php $s{0};").unwrap(), Lexer::new(None).tokenize("<?php $s[0];").unwrap());
+        let mut parser = Parser::new(None);
+        parser.parse(&first).unwrap();
+        parser.parse(&second).unwrap();
+        assert!(parser.warnings().is_empty());
     }
 
     #[test]
-    fn class_with_basic_method() {
-        assert_ast("\
-        <?php
-        
-        class Foo {
-            function bar() {
-                echo 1;
-            }
-        }
-        ", &[
-            class!("Foo", &[
-                method!("bar", &[], &[], &[
-                    Statement::Echo { values: vec![
-                        Expression::Int { i: 1 },
-                    ] }
-                ])
-            ])
-        ]);
+    fn expression_entry_point() {
+        let expr = Parser::new(None).parse_expr_str("1 + $a").unwrap();
+
+        assert_eq!(expr, ExpressionKind::Infix {
+            lhs: Box::new(ExpressionKind::Int { i: 1 }.into()),
+            op: InfixOp::Add,
+            rhs: Box::new(ExpressionKind::Variable { name: "a".into() }.into()),
+        }.into());
+        assert_eq!((expr.span.start, expr.span.end, expr.span.col), (0, 6, 1));
+
+        let tokens = Lexer::new(None).tokenize("<?php foo();").unwrap();
+        assert!(Parser::new(None).parse_expression(&tokens).is_ok());
+
+        assert!(matches!(Parser::new(None).parse_expr_str("1 + 2; 3"), Err(Error::Parser(ParseError::ExpectedToken { .. }))));
+        assert!(Parser::new(None).parse_expr_str("echo 1").is_err());
     }
 
     #[test]
-    fn class_with_extends() {
-        assert_ast("\
-        <?php
-        
-        class Foo extends Bar {}
-        ", &[
-            class!("Foo", Some("Bar".to_string().into()), &[], &[]),
+    fn parse_source() {
+        assert_eq!(crate::parse("<?php echo 1;").unwrap(), &[
+            StatementKind::Echo { values: vec![ExpressionKind::Int { i: 1 }.into()] }.into()
         ]);
+
+        assert!(matches!(crate::parse("<?php echo 1"), Err(Error::Parser(_))));
+        assert!(matches!(crate::parse("<?php __halt_compiler;"), Err(Error::Lexer(_))));
     }
-    
+
     #[test]
-    fn class_with_implements() {
-        assert_ast("\
-        <?php
-        
-        class Foo implements Bar, Baz {}
-        ", &[
-            class!("Foo", None, &["Bar".to_string().into(), "Baz".to_string().into()], &[]),
-        ]);
+    fn nesting_depth_limit() {
+        let source = format!("<?php {}1{};", "(".repeat(100), ")".repeat(100));
+        assert!(crate::parse(&source).is_ok());
+
+        let source = format!("<?php {}1{};", "(".repeat(10_000), ")".repeat(10_000));
+        assert!(matches!(crate::parse(&source), Err(Error::Parser(ParseError::TooDeep(_)))));
+
+        let source = format!("<?php $a = {}{};", "[".repeat(10_000), "]".repeat(10_000));
+        assert!(matches!(crate::parse(&source), Err(Error::Parser(ParseError::TooDeep(_)))));
+
+        let source = format!("<?php {}{}", "if ($a) { ".repeat(10_000), "}".repeat(10_000));
+        assert!(matches!(crate::parse(&source), Err(Error::Parser(ParseError::TooDeep(_)))));
+
+        // Long operator chains nest to the left, so they count towards the limit too.
+        let source = format!("<?php 1{};", " + 1".repeat(100_000));
+        assert!(matches!(crate::parse(&source), Err(Error::Parser(ParseError::TooDeep(_)))));
+
+        let source = format!("<?php $a{};", "->b()".repeat(100_000));
+        assert!(matches!(crate::parse(&source), Err(Error::Parser(ParseError::TooDeep(_)))));
+
+        let tokens = Lexer::new(None).tokenize("<?php ((1));").unwrap();
+        let mut parser = Parser::new(Some(ParserConfig { max_depth: 2, ..Default::default() }));
+        assert!(matches!(parser.parse(&tokens), Err(ParseError::TooDeep(_))));
     }
 
     #[test]
-    fn plain_typestrings_test() {
-        assert_ast("<?php function foo(string $b) {}", &[
-            Statement::Function {
-                name: "foo".to_string().into(),
-                params: vec![
-                    Param {
-                        name: Expression::Variable { name: "b".into() },
-                        r#type: Some(Type::Plain("string".into())),
-                        variadic: false,
-                        default: None,
-                        flag: None,
-                    }
-                ],
-                body: vec![],
-                return_type: None,
+    fn fuzz_entry_point() {
+        let deep = [("(", ")"), ("[", "]"), ("if ($a) { ", "}"), ("class A { function f() { ", "} }"), ("f(", ")"), ("fn() => ", ""), ("1 + ", "")]
+            .map(|(open, close)| format!("<?php {}1{};", open.repeat(1000), close.repeat(1000)));
+
+        for source in &deep {
+            assert!(matches!(crate::fuzz_parse(source.as_bytes()), Err(Error::Parser(ParseError::TooDeep(_)))));
+        }
+
+        assert!(crate::fuzz_parse(b"<?php echo '\xff\xfe';").is_ok());
+        assert!(crate::fuzz_parse(b"<?php echo \"\xff{$a[").is_err());
+
+        // Mangle some valid source in a repeatable way, which should never panic.
+        let seed = b"<?php namespace A; #[B] final class C extends D implements E { use F; public function __construct(private readonly ?int $g = null) {} public static function h(int|string ...$i): static { return match ($i) { [] => new static(fn($j) => $j?->k ?? <<<EOT\n {$l[1]} ${m}\nEOT), default => yield from $n }; } } ?>html<?= $o ?>";
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+
+        for _ in 0..2000 {
+            let mut source = seed.to_vec();
+            state += 1;
+
+            while source.len() > 1 && ! state.is_multiple_of(5) {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+
+                let at = state as usize % source.len();
+                match state >> 62 {
+                    0 => { source.drain(at..(at + 8).min(source.len())); },
+                    1 => source.truncate(at.max(1)),
+                    2 => source[at] = (state >> 32) as u8,
+                    _ => { let chunk = source[at..(at + 16).min(source.len())].to_vec(); source.splice(at..at, chunk); },
+                }
             }
-        ]);
+
+            let _ = crate::fuzz_parse(&source);
+        }
     }
 
     #[test]
-    fn variadic_params() {
-        assert_ast("<?php function foo(...$bar) {}", &[
-            Statement::Function {
-                name: "foo".to_string().into(),
-                params: vec![
-                    Param {
-                        name: Expression::Variable { name: "bar".into() },
-                        r#type: None,
-                        variadic: true,
-                        default: None,
-                        flag: None,
-                    }
-                ],
-                body: vec![],
-                return_type: None,
-            }
-        ]);
+    fn statement_iterator() {
+        let tokens = Lexer::new(None).tokenize("<?php echo 1; // one\n echo 2; ?> html <?php $a = ; echo 3;").unwrap();
+        let mut parser = Parser::new(None);
+        let mut statements = parser.iter_statements(&tokens);
 
-        assert_ast("<?php function foo(string ...$bar) {}", &[
-            Statement::Function {
-                name: "foo".to_string().into(),
-                params: vec![
-                    Param {
-                        name: Expression::Variable { name: "bar".into() },
-                        r#type: Some(Type::Plain("string".into())),
-                        variadic: true,
-                        default: None,
-                        flag: None,
-                    }
-                ],
-                body: vec![],
-                return_type: None,
-            }
-        ]);
+        assert_eq!(statements.next().unwrap().unwrap(), StatementKind::Echo { values: vec![ExpressionKind::Int { i: 1 }.into()] }.into());
+        assert_eq!(statements.next().unwrap().unwrap(), StatementKind::Echo { values: vec![ExpressionKind::Int { i: 2 }.into()] }.into());
+        assert!(matches!(statements.next(), Some(Ok(Statement { kind: StatementKind::InlineHtml(_), .. }))));
+        assert!(matches!(statements.next(), Some(Err(_))));
+        assert!(statements.next().is_none());
+    }
 
-        assert_ast("<?php function foo($bar, $baz, ...$car) {}", &[
-            Statement::Function {
-                name: "foo".to_string().into(),
-                params: vec![
-                    Param {
-                        name: Expression::Variable { name: "bar".into() },
-                        r#type: None,
-                        variadic: false,
-                        default: None,
-                        flag: None,
-                    },
-                    Param {
-                        name: Expression::Variable { name: "baz".into() },
-                        r#type: None,
-                        variadic: false,
-                        default: None,
-                        flag: None,
-                    },
-                    Param {
-                        name: Expression::Variable { name: "car".into() },
-                        r#type: None,
-                        variadic: true,
-                        default: None,
-                        flag: None,
-                    }
-                ],
-                body: vec![],
-                return_type: None,
+    #[test]
+    fn doc_comments() {
+        let source = "<?php
+            /** Foo. */
+            #[Attr]
+            final class Foo {
+                /** The bar. */
+                public const BAR = 1;
+
+                /** @var int */
+                private int $baz;
+
+                /** Qux. */
+                public function qux() {
+                    /** Stray. */
+                    $a = 1;
+
+                    // Not a doc comment.
+                    function quux() {}
+                }
             }
-        ]);
+
+            /** Stray. */
+            echo 1;
+            function corge() {}
+
+            /** Grault. */
+            const GRAULT = 1;
+        ";
+
+        let ast = crate::parse(source).unwrap();
+
+        let docs = |statement: &Statement| match &statement.kind {
+            StatementKind::Class { doc_comment, .. } | StatementKind::Function { doc_comment, .. } | StatementKind::Method { doc_comment, .. } |
+            StatementKind::Property { doc_comment, .. } | StatementKind::Constant { doc_comment, .. } | StatementKind::Const { doc_comment, .. } => doc_comment.clone(),
+            _ => None,
+        };
+
+        let body = match &ast[0].kind {
+            StatementKind::Class { body, .. } => body,
+            _ => panic!("expected class"),
+        };
+        let method_body = match &body[2].kind {
+            StatementKind::Method { body, .. } => body,
+            _ => panic!("expected method"),
+        };
+
+        assert_eq!(docs(&ast[0]).as_deref(), Some("/** Foo. */"));
+        assert_eq!(docs(&body[0]).as_deref(), Some("/** The bar. */"));
+        assert_eq!(docs(&body[1]).as_deref(), Some("/** @var int */"));
+        assert_eq!(docs(&body[2]).as_deref(), Some("/** Qux. */"));
+        assert_eq!(docs(&method_body[1]), None);
+        assert_eq!(docs(&ast[2]), None);
+        assert_eq!(docs(&ast[3]).as_deref(), Some("/** Grault. */"));
     }
 
     #[test]
-    fn nullable_typestrings_test() {
-        assert_ast("<?php function foo(?string $b) {}", &[
-            Statement::Function {
-                name: "foo".to_string().into(),
-                params: vec![
-                    Param {
-                        name: Expression::Variable { name: "b".into() },
-                        r#type: Some(Type::Nullable("string".into())),
-                        variadic: false,
-                        default: None,
-                        flag: None,
-                    }
-                ],
-                body: vec![],
-                return_type: None,
-            }
+    fn error_recovery() {
+        let tokens = Lexer::new(None).tokenize("<?php $a = 1 < 2 < 3; $b = 1; $c = $d ? 1 : $e ? 2 : 3; $f = 4;").unwrap();
+        let (ast, errors) = Parser::new(None).parse_with_recovery(&tokens);
+
+        assert_eq!(ast, &[
+            expr!(ExpressionKind::Assign {
+                var: Box::new(ExpressionKind::Variable { name: "b".into() }.into()),
+                value: Box::new(ExpressionKind::Int { i: 1 }.into()),
+            }.into()),
+            expr!(ExpressionKind::Assign {
+                var: Box::new(ExpressionKind::Variable { name: "f".into() }.into()),
+                value: Box::new(ExpressionKind::Int { i: 4 }.into()),
+            }.into()),
         ]);
+
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(errors[0], ParseError::UnparenthesizedChainedComparison(_)));
+        assert!(matches!(errors[1], ParseError::UnparenthesizedNestedTernary(_)));
+
+        let tokens = Lexer::new(None).tokenize("<?php $a = 1; }").unwrap();
+        let (ast, errors) = Parser::new(None).parse_with_recovery(&tokens);
+
+        assert_eq!(ast.len(), 1);
+        assert_eq!(errors.len(), 1);
     }
 
     #[test]
-    fn union_typestrings_test() {
-        assert_ast("<?php function foo(int|float $b) {}", &[
-            Statement::Function {
-                name: "foo".to_string().into(),
-                params: vec![
-                    Param {
-                        name: Expression::Variable { name: "b".into() },
-                        r#type: Some(Type::Union(vec![
-                            "int".into(),
-                            "float".into()
-                        ])),
-                        variadic: false,
-                        default: None,
-                        flag: None,
-                    }
-                ],
-                body: vec![],
-                return_type: None,
+    fn accessors() {
+        let ast = crate::parse("<?php class A { public $b; public function c(int $d) {} }").unwrap();
+
+        assert_eq!(ast[0].kind.name(), Some("A"));
+
+        match &ast[0].kind {
+            StatementKind::Class { body, .. } => {
+                assert_eq!(body[0].kind.name(), Some("b"));
+                assert_eq!(body[1].kind.name(), Some("c"));
+
+                match &body[1].kind {
+                    StatementKind::Method { params, .. } => {
+                        assert_eq!(params[0].variable_name(), Some("d"));
+                        assert_eq!(params[0], Param { r#type: Some(Type::Plain("int".into())), ..Param::new("d", Span::default()) });
+                    },
+                    _ => panic!("expected a method"),
+                }
             },
-        ]);
+            _ => panic!("expected a class"),
+        }
+
+        assert_eq!(Identifier::new("a", Span::default()), Identifier::from("a"));
     }
 
     #[test]
-    fn intersection_typestrings_test() {
-        assert_ast("<?php function foo(Foo&Bar $b) {}", &[
-            Statement::Function {
-                name: "foo".to_string().into(),
-                params: vec![
-                    Param {
-                        name: Expression::Variable { name: "b".into() },
-                        r#type: Some(Type::Intersection(vec![
-                            "Foo".into(),
-                            "Bar".into()
-                        ])),
-                        variadic: false,
-                        default: None,
-                        flag: None,
-                    }
-                ],
-                body: vec![],
-                return_type: None,
-            }
-        ]);
+    fn nodes_can_be_hashed() {
+        use std::collections::HashSet;
+
+        let a = crate::parse("<?php $a = 1.5; function f() { return $a; }").unwrap();
+        let b = crate::parse("<?php\n\n$a   =  1.5;\nfunction f()\n{\n    return $a;\n}").unwrap();
+
+        let statements: HashSet<&Statement> = a.iter().chain(b.iter()).collect();
+        assert_eq!(statements.len(), 2);
+
+        let nan = Expression::from(ExpressionKind::Float { f: f64::NAN.into() });
+        assert_eq!(nan, nan.clone());
+        assert_ne!(ExpressionKind::Float { f: 0.0.into() }, ExpressionKind::Float { f: (-0.0).into() });
+
+        let tokens: HashSet<TokenKind> = Lexer::new(None).tokenize("<?php $a = $a + 1.5;").unwrap().into_iter().map(|token| token.kind).collect();
+        assert!(tokens.contains(&TokenKind::Variable("a".into())));
+        assert!(tokens.contains(&TokenKind::Float(1.5.into())));
     }
 
     #[test]
-    fn function_return_types() {
-        assert_ast("<?php function foo(): string {}", &[
-            Statement::Function {
-                name: "foo".to_string().into(),
-                params: vec![],
-                body: vec![],
-                return_type: Some(Type::Plain("string".into()))
+    #[cfg(feature = "serde")]
+    fn ast_round_trips_through_json() {
+        let ast = crate::parse("<?php
+            namespace App;
+
+            #[Attr(1)]
+            final class A extends B implements C {
+                public const X = 1.5;
+                private ?int $a = null;
+
+                public function __construct(protected string $b = 'b', int ...$c) {}
+
+                public static function f(): static|false {
+                    return match (true) { $a instanceof A => fn &() => [1, 'a' => &$b, ...$c], default => \"{$d->e}\" };
+                }
             }
-        ]);
+
+            enum D: int { case E = 1; }
+            foreach ($a as $k => &$v) { try { throw new E(); } catch (E|F $e) {} finally { echo 1; } }
+            __halt_compiler(); data
+        ").unwrap();
+
+        let json = serde_json::to_string(&ast).unwrap();
+        let decoded: crate::Program = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(decoded, ast);
+        assert_eq!(decoded[1].span, ast[1].span);
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
     }
 
     #[test]
-    fn new_anon_class() {
-        assert_ast("<?php new class{};", &[
-            expr!(Expression::New {
-                target: Box::new(Expression::AnonymousClass {
-                    extends: None,
-                    implements: vec![],
-                    body: vec![]
-                }),
-                args: vec![],
-            })
-        ]);
+    fn tolerant_parsing() {
+        let tokens = Lexer::new(None).tokenize("<?php function f() { $a = ; $b->; $c = 1; } class A { public function } $d = 1;").unwrap();
+        let (ast, errors) = Parser::new(None).parse_tolerant(&tokens);
+
+        assert_eq!(errors.len(), 3);
+        assert_eq!(ast.len(), 3);
+
+        match &ast[0].kind {
+            StatementKind::Function { body, .. } => {
+                assert!(matches!(&body[0].kind, StatementKind::Expression { expr: Expression { kind: ExpressionKind::Assign { value, .. }, .. } } if value.kind == ExpressionKind::Error));
+                assert_eq!(body[1].kind, StatementKind::Error);
+                assert!(matches!(body[2].kind, StatementKind::Expression { .. }));
+            },
+            _ => panic!("expected a function"),
+        }
 
-        assert_ast("<?php new class(1, 2) {};", &[
-            expr!(Expression::New {
-                target: Box::new(Expression::AnonymousClass {
-                    extends: None,
-                    implements: vec![],
-                    body: vec![]
-                }),
-                args: vec![
-                    Arg {
-                        name: None,
-                        value: Expression::Int { i: 1 },
-                        unpack: false,
-                    },
-                    Arg {
-                        name: None,
-                        value: Expression::Int { i: 2 },
-                        unpack: false,
+        match &ast[1].kind {
+            StatementKind::Class { body, .. } => assert_eq!(body[0].kind, StatementKind::Error),
+            _ => panic!("expected a class"),
+        }
+
+        let tokens = Lexer::new(None).tokenize("<?php $a = 1; } $b = 2;").unwrap();
+        let (ast, errors) = Parser::new(None).parse_tolerant(&tokens);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(ast.iter().map(|statement| statement.kind == StatementKind::Error).collect::<Vec<_>>(), &[false, true, false]);
+
+        // Recovery inside a loop that doesn't consume the offending token mustn't hang.
+        let tokens = Lexer::new(None).tokenize("<?php [ ) match [").unwrap();
+        let (_, errors) = Parser::new(None).parse_tolerant(&tokens);
+
+        assert!(! errors.is_empty());
+    }
+
+    #[test]
+    fn expected_token_errors() {
+        let tokens = Lexer::new(None).tokenize("<?php if ($a {}").unwrap();
+
+        match Parser::new(None).parse(&tokens) {
+            Err(error @ ParseError::ExpectedToken { .. }) => {
+                match &error {
+                    ParseError::ExpectedToken { expected, found, .. } => {
+                        assert_eq!(expected, &[TokenKind::RightParen]);
+                        assert_eq!(found.kind, TokenKind::LeftBrace);
                     },
-                ],
-            })
-        ]);
+                    _ => unreachable!(),
+                }
 
-        assert_ast("<?php new class extends Foo {};", &[
-            expr!(Expression::New {
-                target: Box::new(Expression::AnonymousClass {
-                    extends: Some(Identifier::from("Foo")),
-                    implements: vec![],
-                    body: vec![]
-                }),
-                args: vec![]
-            })
-        ]);
+                assert_eq!(error.span(), Some(Span { start: 13, end: 14, line: 1, col: 14 }));
+                assert_eq!(error.to_string(), "Parse error: expected `)`, found `{` on line 1 column 14");
+            },
+            result => panic!("expected a parse error, got {:?}", result),
+        }
 
-        assert_ast("<?php new class implements Foo, Bar {};", &[
-            expr!(Expression::New {
-                target: Box::new(Expression::AnonymousClass {
-                    extends: None,
-                    implements: vec![
-                        Identifier::from("Foo"),
-                        Identifier::from("Bar"),
-                    ],
-                    body: vec![]
-                }),
-                args: vec![]
-            })
-        ]);
+        let tokens = Lexer::new(None).tokenize("<?php class").unwrap();
 
-        assert_ast("<?php new class {
-            public function foo() {}
-        };", &[
-            expr!(Expression::New {
-                target: Box::new(Expression::AnonymousClass {
-                    extends: None,
-                    implements: vec![],
-                    body: vec![
-                        Statement::Method {
-                            name: "foo".into(),
-                            params: vec![],
-                            body: vec![],
-                            return_type: None,
-                            flags: vec![
-                                MethodFlag::Public,
-                            ]
-                        }
-                    ]
-                }),
-                args: vec![]
-            })
-        ]);
+        match Parser::new(None).parse(&tokens) {
+            Err(error) => assert_eq!(error.to_string(), "Parse error: expected identifier, found end of file"),
+            result => panic!("expected a parse error, got {:?}", result),
+        }
     }
 
     #[test]
-    fn foreach() {
-        assert_ast("<?php foreach ($foo as $bar) {}", &[
-            Statement::Foreach {
-                expr: Expression::Variable { name: "foo".into() },
-                by_ref: false,
-                key_var: None,
-                value_var: Expression::Variable { name: "bar".into() },
-                body: vec![],
-            }
-        ]);
+    fn spans() {
+        let tokens = Lexer::new(None).tokenize("<?php $a = 1 + 2;\nfunction foo($bar) {}").unwrap();
+        let ast = Parser::new(None).parse(&tokens).unwrap();
 
-        assert_ast("<?php foreach ($foo as $bar => $baz) {}", &[
-            Statement::Foreach {
-                expr: Expression::Variable { name: "foo".into() },
-                by_ref: false,
-                key_var: Some(Expression::Variable { name: "bar".into() }),
-                value_var: Expression::Variable { name: "baz".into() },
-                body: vec![],
-            }
-        ]);
+        assert_eq!(ast[0].span, Span { start: 6, end: 17, line: 1, col: 7 });
 
-        assert_ast("<?php foreach ($foo as [$baz, $car]) {}", &[
-            Statement::Foreach {
-                expr: Expression::Variable { name: "foo".into() },
-                by_ref: false,
-                key_var: None,
-                value_var: Expression::Array {
-                    items: vec![
-                        ArrayItem {
-                            key: None,
-                            value: Expression::Variable { name: "baz".into() }
+        match &ast[0].kind {
+            StatementKind::Expression { expr } => {
+                assert_eq!(expr.span, Span { start: 6, end: 16, line: 1, col: 7 });
+
+                match &expr.kind {
+                    ExpressionKind::Assign { var, value } => {
+                        assert_eq!(var.span, Span { start: 6, end: 8, line: 1, col: 7 });
+                        assert_eq!(value.span, Span { start: 11, end: 16, line: 1, col: 12 });
+                    },
+                    _ => panic!("expected an assignment"),
+                }
+            },
+            _ => panic!("expected an expression statement"),
+        }
+
+        assert_eq!(ast[1].span, Span { start: 18, end: 39, line: 2, col: 1 });
+
+        match &ast[1].kind {
+            StatementKind::Function { name, params, .. } => {
+                assert_eq!(name.span, Span { start: 27, end: 30, line: 2, col: 10 });
+                assert_eq!(params[0].span, Span { start: 31, end: 35, line: 2, col: 14 });
+            },
+            _ => panic!("expected a function"),
+        }
+    }
+
+    #[test]
+    fn name_spans() {
+        let source = "<?php class A extends B implements C, \\D\\E { use F, G; }\ninterface H extends I {}\nenum J implements K {}\n\
+            try {} catch (L|M\\N $e) {}\n$o = new class extends P implements Q {};\n#[R] function s() {}\nT::u(); T::V;";
+        let tokens = Lexer::new(None).tokenize(source).unwrap();
+        let ast = Parser::new(None).parse(&tokens).unwrap();
+
+        let mut names = Vec::new();
+
+        for statement in &ast {
+            match &statement.kind {
+                StatementKind::Class { extends, implements, body, .. } => {
+                    names.extend(extends.iter().chain(implements));
+
+                    match &body[0].kind {
+                        StatementKind::TraitUse { traits } => names.extend(traits),
+                        _ => panic!("expected a trait use"),
+                    }
+                },
+                StatementKind::Interface { extends, .. } => names.extend(extends),
+                StatementKind::Enum { implements, .. } => names.extend(implements),
+                StatementKind::Try { catches, .. } => names.extend(&catches[0].types),
+                StatementKind::Function { attributes, .. } => names.push(&attributes[0].members[0].name),
+                StatementKind::Expression { expr } => match &expr.kind {
+                    ExpressionKind::Assign { value, .. } => match &value.kind {
+                        ExpressionKind::New { target, .. } => match &target.kind {
+                            ExpressionKind::AnonymousClass { extends, implements, .. } => names.extend(extends.iter().chain(implements)),
+                            _ => panic!("expected an anonymous class"),
                         },
-                        ArrayItem {
-                            key: None,
-                            value: Expression::Variable { name: "car".into() }
-                        }
-                    ]
+                        _ => panic!("expected new"),
+                    },
+                    ExpressionKind::StaticMethodCall { method, .. } => names.push(method),
+                    ExpressionKind::ConstFetch { constant, .. } => names.push(constant),
+                    _ => panic!("unexpected expression {:?}", expr),
                 },
-                body: vec![],
+                _ => panic!("unexpected statement {:?}", statement),
             }
-        ]);
+        }
+
+        let spanned: Vec<&str> = names.iter().map(|name| &source[name.span.start..name.span.end]).collect();
+
+        assert_eq!(spanned, ["B", "C", "\\D\\E", "F", "G", "I", "K", "L", "M\\N", "P", "Q", "R", "u", "V"]);
+        assert_eq!(names[5].span.line, 2);
+        assert_eq!(names[12].span.col, 4);
     }
 
     #[test]
-    fn noop() {
-        assert_ast("<?php ;", &[
-            Statement::Noop,
-        ]);
+    fn misplaced_modifiers_are_errors() {
+        assert_parse_error("<?php class A { abstract const X = 1; }");
+        assert_parse_error("<?php class A { final $x; }");
+        assert_parse_error("<?php class A { abstract $x; }");
+        assert_parse_error("<?php abstract function f() {}");
+        assert_parse_error("<?php final interface A {}");
+        assert_parse_error("<?php \\if (true) {}");
     }
 
-    fn assert_ast(source: &str, expected: &[Statement]) {
+    fn assert_parse_error(source: &str) {
         let mut lexer = Lexer::new(None);
         let tokens = lexer.tokenize(source).unwrap();
 
         let mut parser = Parser::new(None);
-        let ast = parser.parse(tokens);
 
-        if ast.is_err() {
-            panic!("{}", ast.err().unwrap());
-        } else {
-            assert_eq!(ast.unwrap(), expected);
+        assert!(parser.parse(&tokens).is_err(), "expected a parse error for {}", source);
+    }
+
+    fn assert_ast(source: &str, expected: &[Statement]) {
+        let mut lexer = Lexer::new(None);
+        let tokens = lexer.tokenize(source).unwrap();
+
+        let mut parser = Parser::new(None);
+        match parser.parse(&tokens) {
+            Ok(ast) => assert_eq!(ast, expected),
+            Err(err) => panic!("{}", err),
         }
     }
 }
\ No newline at end of file
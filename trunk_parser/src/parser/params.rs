@@ -1,36 +1,56 @@
-use crate::{ast::{ParamList, PropertyFlag}, ParseError, Param, Expression};
+use crate::{ast::{ParamList, PropertyFlag}, ParseError, Param, PhpVersion, Expression, ExpressionKind};
 use trunk_lexer::TokenKind;
 
-use super::Parser;
+use super::{punc::List, Parser};
 
-impl Parser {
+impl<'a> Parser<'a> {
     pub(crate) fn param_list(&mut self) -> Result<ParamList, ParseError> {
         let mut params = ParamList::new();
 
         while ! self.is_eof() && self.current.kind != TokenKind::RightParen {
+            self.gather_attributes()?;
+
+            let start = self.current.span;
+            let attributes = self.get_attributes();
             let mut param_type = None;
 
-            let flag: Option<PropertyFlag> = if matches!(self.current.kind, TokenKind::Public | TokenKind::Protected | TokenKind::Private) {
-                let flag = self.current.kind.clone().into();
+            let mut flags: Vec<PropertyFlag> = vec![];
+            while matches!(self.current.kind, TokenKind::Public | TokenKind::Protected | TokenKind::Private | TokenKind::PublicSet | TokenKind::ProtectedSet | TokenKind::PrivateSet |
+                TokenKind::Readonly) {
+                let flag = self.current.kind.clone().try_into()
+                    .map_err(|kind: TokenKind| ParseError::UnexpectedToken(kind.to_string(), self.current.span))?;
+
+                if flags.contains(&flag) {
+                    return Err(ParseError::UnexpectedToken(self.current.kind.to_string(), self.current.span));
+                }
+
+                self.requires(PhpVersion::Php80, "constructor property promotion")?;
+                self.modifier()?;
+
+                flags.push(flag);
                 self.next();
-                Some(flag)
-            } else {
-                None
-            };
+            }
 
             // 1. If we don't see a variable, we should expect a type-string.
-            if ! matches!(self.current.kind, TokenKind::Variable(_) | TokenKind::Ellipsis) || self.config.force_type_strings {
+            if ! matches!(self.current.kind, TokenKind::Variable(_) | TokenKind::Ellipsis | TokenKind::Ampersand) || self.config.force_type_strings {
                 // 1a. Try to parse the type.
                 param_type = Some(self.type_string()?);
             }
 
+            let by_ref = if self.current.kind == TokenKind::Ampersand {
+                self.next();
+                true
+            } else { false };
+
             let variadic = if self.current.kind == TokenKind::Ellipsis {
                 self.next();
                 true
             } else { false };
 
             // 2. Then expect a variable.
-            let var = expect!(self, TokenKind::Variable(v), v, "expected variable");
+            let var_start = self.current.span;
+            let var = expect!(self, TokenKind::Variable(v), v.to_string(), [TokenKind::Variable(String::new())]);
+            let name = Expression::new(ExpressionKind::Variable { name: var }, self.span_from(var_start));
 
             let mut default = None;
             if self.current.kind == TokenKind::Equals {
@@ -40,14 +60,17 @@ impl Parser {
 
             // TODO: Support variable types and default values.
             params.push(Param {
-                name: Expression::Variable { name: var },
+                name,
                 r#type: param_type,
+                by_ref,
                 variadic,
                 default,
-                flag
+                flags,
+                attributes,
+                span: self.span_from(start),
             });
             
-            self.optional_comma()?;
+            self.list_comma(&TokenKind::RightParen, List::Params)?;
         }
 
         Ok(params)
@@ -0,0 +1,154 @@
+use trunk_lexer::TokenKind;
+
+use crate::{ExpressionKind, InfixOp};
+
+/// The precedence of every operator, from the loosest binding to the tightest. The order follows
+/// the operator precedence table in the PHP manual.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Precedence {
+    KeywordOr,
+    KeywordXor,
+    KeywordAnd,
+    // `print`, `include` and friends.
+    Print,
+    Assignment,
+    Ternary,
+    NullCoalesce,
+    Or,
+    And,
+    BitwiseOr,
+    BitwiseXor,
+    BitwiseAnd,
+    Equality,
+    Comparison,
+    Concat,
+    BitShift,
+    AddSub,
+    MulDivMod,
+    Bang,
+    Instanceof,
+    // Unary `+`, `-` and `~`, casts, `@` and prefix `++` / `--`.
+    Prefix,
+    Pow,
+    CloneOrNew,
+    // Postfix `++` / `--`.
+    Increment,
+    // Calls, offsets and member access.
+    Access,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
+    NonAssociative,
+}
+
+impl Precedence {
+    pub fn infix(kind: &TokenKind) -> Option<Self> {
+        Some(match kind {
+            TokenKind::Pow => Self::Pow,
+            TokenKind::Instanceof => Self::Instanceof,
+            TokenKind::Asterisk | TokenKind::Slash | TokenKind::Percent => Self::MulDivMod,
+            TokenKind::Plus | TokenKind::Minus => Self::AddSub,
+            TokenKind::LeftShift | TokenKind::RightShift => Self::BitShift,
+            TokenKind::Dot => Self::Concat,
+            TokenKind::LessThan | TokenKind::GreaterThan | TokenKind::LessThanEquals | TokenKind::GreaterThanEquals => Self::Comparison,
+            TokenKind::DoubleEquals | TokenKind::TripleEquals | TokenKind::BangEquals | TokenKind::BangDoubleEquals |
+            TokenKind::LessThanGreaterThan | TokenKind::Spaceship => Self::Equality,
+            TokenKind::Ampersand => Self::BitwiseAnd,
+            TokenKind::Caret => Self::BitwiseXor,
+            TokenKind::Pipe => Self::BitwiseOr,
+            TokenKind::BooleanAnd => Self::And,
+            TokenKind::BooleanOr => Self::Or,
+            TokenKind::Coalesce => Self::NullCoalesce,
+            TokenKind::Question | TokenKind::QuestionColon => Self::Ternary,
+            TokenKind::Equals | TokenKind::PlusEquals | TokenKind::MinusEquals | TokenKind::DotEquals | TokenKind::CoalesceEqual |
+            TokenKind::AsteriskEqual | TokenKind::SlashEquals | TokenKind::PercentEquals | TokenKind::PowEquals | TokenKind::AndEqual |
+            TokenKind::PipeEquals | TokenKind::CaretEquals | TokenKind::LeftShiftEquals | TokenKind::RightShiftEquals => Self::Assignment,
+            TokenKind::LogicalAnd => Self::KeywordAnd,
+            TokenKind::LogicalXor => Self::KeywordXor,
+            TokenKind::LogicalOr => Self::KeywordOr,
+            _ => return None,
+        })
+    }
+
+    pub fn prefix(kind: &TokenKind) -> Option<Self> {
+        Some(match kind {
+            TokenKind::Clone => Self::CloneOrNew,
+            TokenKind::StringCast | TokenKind::ObjectCast | TokenKind::BoolCast | TokenKind::IntCast | TokenKind::DoubleCast | TokenKind::RealCast | TokenKind::ArrayCast |
+            TokenKind::Minus | TokenKind::Plus | TokenKind::Tilde | TokenKind::At | TokenKind::Increment | TokenKind::Decrement => Self::Prefix,
+            TokenKind::Bang => Self::Bang,
+            TokenKind::Print | TokenKind::Include | TokenKind::IncludeOnce | TokenKind::Require | TokenKind::RequireOnce => Self::Print,
+            _ => return None,
+        })
+    }
+
+    pub fn postfix(kind: &TokenKind) -> Option<Self> {
+        Some(match kind {
+            TokenKind::LeftParen | TokenKind::LeftBracket | TokenKind::Arrow | TokenKind::NullsafeArrow | TokenKind::DoubleColon => Self::Access,
+            TokenKind::Increment | TokenKind::Decrement => Self::Increment,
+            _ => return None,
+        })
+    }
+
+    /// The precedence of the operator at the root of a parsed expression. Anything that isn't an
+    /// operator, such as a literal or a call, binds as tightly as possible.
+    pub fn of(expression: &ExpressionKind) -> Self {
+        match expression {
+            ExpressionKind::Infix { op, .. } => match op {
+                InfixOp::Pow => Self::Pow,
+                InfixOp::Mul | InfixOp::Div | InfixOp::Mod => Self::MulDivMod,
+                InfixOp::Add | InfixOp::Sub => Self::AddSub,
+                InfixOp::LeftShift | InfixOp::RightShift => Self::BitShift,
+                InfixOp::Concat => Self::Concat,
+                InfixOp::LessThan | InfixOp::GreaterThan | InfixOp::LessThanEquals | InfixOp::GreaterThanEquals => Self::Comparison,
+                InfixOp::Equals | InfixOp::Identical | InfixOp::NotEquals | InfixOp::NotIdentical | InfixOp::Spaceship => Self::Equality,
+                InfixOp::BitwiseAnd => Self::BitwiseAnd,
+                InfixOp::BitwiseXor => Self::BitwiseXor,
+                InfixOp::BitwiseOr => Self::BitwiseOr,
+                InfixOp::And => Self::And,
+                InfixOp::Or => Self::Or,
+                InfixOp::LogicalAnd => Self::KeywordAnd,
+                InfixOp::LogicalXor => Self::KeywordXor,
+                InfixOp::LogicalOr => Self::KeywordOr,
+                InfixOp::ConcatAssign | InfixOp::AddAssign | InfixOp::SubAssign | InfixOp::MulAssign | InfixOp::DivAssign |
+                InfixOp::ModAssign | InfixOp::PowAssign | InfixOp::BitwiseAndAssign | InfixOp::BitwiseOrAssign | InfixOp::BitwiseXorAssign |
+                InfixOp::LeftShiftAssign | InfixOp::RightShiftAssign => Self::Assignment,
+            },
+            ExpressionKind::Assign { .. } | ExpressionKind::CoalesceAssign { .. } | ExpressionKind::AssignRef { .. } |
+            ExpressionKind::ArrowFunction { .. } => Self::Assignment,
+            ExpressionKind::Ternary { .. } => Self::Ternary,
+            ExpressionKind::Coalesce { .. } => Self::NullCoalesce,
+            ExpressionKind::Instanceof { .. } => Self::Instanceof,
+            ExpressionKind::BooleanNot { .. } => Self::Bang,
+            ExpressionKind::Negate { .. } | ExpressionKind::UnaryPlus { .. } | ExpressionKind::BitwiseNot { .. } | ExpressionKind::ErrorSuppress { .. } |
+            ExpressionKind::Cast { .. } | ExpressionKind::PreIncrement { .. } | ExpressionKind::PreDecrement { .. } => Self::Prefix,
+            ExpressionKind::PostIncrement { .. } | ExpressionKind::PostDecrement { .. } => Self::Increment,
+            ExpressionKind::Clone { .. } | ExpressionKind::New { .. } => Self::CloneOrNew,
+            ExpressionKind::Print { .. } | ExpressionKind::Include { .. } | ExpressionKind::IncludeOnce { .. } | ExpressionKind::Require { .. } |
+            ExpressionKind::RequireOnce { .. } | ExpressionKind::Yield { .. } | ExpressionKind::Throw { .. } => Self::Print,
+            _ => Self::Access,
+        }
+    }
+
+    pub fn associativity(&self) -> Associativity {
+        match self {
+            Self::Pow | Self::NullCoalesce | Self::Assignment => Associativity::Right,
+            Self::Equality | Self::Comparison | Self::Ternary => Associativity::NonAssociative,
+            _ => Associativity::Left,
+        }
+    }
+
+    /// The left and right binding powers used when parsing an operator with this precedence.
+    /// Right-associative operators bind their right-hand side at the same power, so `$a = $b = 1`
+    /// nests to the right, while every other operator nests to the left.
+    pub fn binding_power(&self) -> (u8, u8) {
+        let bp = *self as u8 * 2;
+
+        match self.associativity() {
+            Associativity::Right => (bp, bp),
+            _ => (bp, bp + 1),
+        }
+    }
+}
@@ -2,44 +2,98 @@ use trunk_lexer::TokenKind;
 
 use crate::{Parser, ParseError};
 
-use super::ParseResult;
+use super::{ParseResult, PhpVersion};
 
-impl Parser {
+/// The kinds of list that have a `TrailingCommas` setting.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum List {
+    Args,
+    Arrays,
+    Params,
+}
+
+impl<'a> Parser<'a> {
     pub(crate) fn semi(&mut self) -> ParseResult<()> {
-        expect!(self, TokenKind::SemiColon, (), "expected semi colon");
+        // A close tag implies a semi colon, but it still needs to be seen by the
+        // statement parser so that any trailing inline HTML is handled.
+        if self.current.kind == TokenKind::CloseTag {
+            return Ok(());
+        }
+
+        expect!(self, TokenKind::SemiColon);
         Ok(())
     }
 
     pub(crate) fn lbrace(&mut self) -> ParseResult<()> {
-        expect!(self, TokenKind::LeftBrace, (), "expected {");
+        expect!(self, TokenKind::LeftBrace);
         Ok(())
     }
 
     pub(crate) fn rbrace(&mut self) -> ParseResult<()> {
-        expect!(self, TokenKind::RightBrace, (), "expected }");
+        expect!(self, TokenKind::RightBrace);
         Ok(())
     }
 
     pub(crate) fn lparen(&mut self) -> ParseResult<()> {
-        expect!(self, TokenKind::LeftParen, (), "expected (");
+        expect!(self, TokenKind::LeftParen);
         Ok(())
     }
 
     pub(crate) fn rparen(&mut self) -> ParseResult<()> {
-        expect!(self, TokenKind::RightParen, (), "expected )");
+        expect!(self, TokenKind::RightParen);
         Ok(())
     }
 
     pub(crate) fn rbracket(&mut self) -> ParseResult<()> {
-        expect!(self, TokenKind::RightBracket, (), "expected ]");
+        expect!(self, TokenKind::RightBracket);
         Ok(())
     }
 
     pub(crate) fn optional_comma(&mut self) -> ParseResult<()> {
         if self.current.kind == TokenKind::Comma {
-            expect!(self, TokenKind::Comma, (), "expected ,");
+            expect!(self, TokenKind::Comma);
         }
         
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Consume the comma after an item in a list that ends with `end`. Items must be separated by
+    /// commas, and a comma after the last item must be allowed for the kind of list.
+    pub(crate) fn list_comma(&mut self, end: &TokenKind, list: List) -> ParseResult<()> {
+        self.skip_comments();
+
+        if &self.current.kind == end {
+            return Ok(());
+        }
+
+        let comma = self.current.span;
+        expect!(self, TokenKind::Comma, (), [TokenKind::Comma, end.clone()]);
+        self.skip_comments();
+
+        if &self.current.kind != end {
+            return Ok(());
+        }
+
+        let (setting, since, feature) = match list {
+            List::Args => (self.config.trailing_commas.args, None, "trailing commas in argument lists"),
+            List::Arrays => (self.config.trailing_commas.arrays, None, "trailing commas in arrays"),
+            List::Params => (self.config.trailing_commas.params, Some(PhpVersion::Php80), "trailing commas in parameter lists"),
+        };
+
+        match (setting, since) {
+            (Some(false), _) => Err(ParseError::UnexpectedToken(TokenKind::Comma.to_string(), comma)),
+            (None, Some(version)) if self.config.php_version < version => Err(ParseError::RequiresPhpVersion { feature, version, span: comma }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Consume an optional `&`, returning whether it was present.
+    pub(crate) fn optional_ampersand(&mut self) -> bool {
+        if self.current.kind == TokenKind::Ampersand {
+            self.next();
+            return true;
+        }
+
+        false
+    }
+}
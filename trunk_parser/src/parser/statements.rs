@@ -0,0 +1,54 @@
+use trunk_lexer::{Token, TokenKind};
+
+use crate::{Parser, ParseError, Statement};
+
+/// An iterator over the top-level statements of a file, parsed one at a time. Iteration stops
+/// after the first error.
+pub struct Statements<'p, 'a> {
+    parser: &'p mut Parser<'a>,
+    done: bool,
+}
+
+impl<'a> Parser<'a> {
+    /// Parse the tokens lazily, yielding each top-level statement as soon as it has been parsed.
+    pub fn iter_statements(&mut self, tokens: &'a [Token]) -> Statements<'_, 'a> {
+        self.start(tokens);
+
+        Statements { parser: self, done: false }
+    }
+}
+
+impl Iterator for Statements<'_, '_> {
+    type Item = Result<Statement, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        loop {
+            if let TokenKind::OpenTag(_) = self.parser.current.kind {
+                self.parser.next();
+                continue;
+            }
+
+            self.parser.gather_comments();
+
+            if self.parser.is_eof() {
+                self.done = true;
+                return None;
+            }
+
+            break;
+        }
+
+        let statement = self.parser.statement();
+        self.parser.clear_comments();
+
+        if statement.is_err() {
+            self.done = true;
+        }
+
+        Some(statement)
+    }
+}
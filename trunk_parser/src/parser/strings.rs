@@ -0,0 +1,72 @@
+use trunk_lexer::TokenKind;
+
+use crate::{ExpressionKind, Parser, ast::StringPart};
+
+use super::ParseResult;
+
+impl<'a> Parser<'a> {
+    /// Parse a double quoted string that contains interpolation. The lexer has already
+    /// split the string into literal parts and the tokens of each embedded expression.
+    pub(crate) fn interpolated_string(&mut self) -> ParseResult<ExpressionKind> {
+        expect!(self, TokenKind::DoubleQuote);
+
+        let parts = self.string_parts(&TokenKind::DoubleQuote)?;
+
+        expect!(self, TokenKind::DoubleQuote);
+
+        Ok(ExpressionKind::InterpolatedString { parts })
+    }
+
+    pub(crate) fn heredoc(&mut self) -> ParseResult<ExpressionKind> {
+        let label = expect!(self, TokenKind::DocOpen(label), label.to_string(), [TokenKind::DocOpen(String::new())]);
+
+        let parts = self.string_parts(&TokenKind::DocClose)?;
+
+        expect!(self, TokenKind::DocClose);
+
+        Ok(ExpressionKind::Heredoc { label, parts })
+    }
+
+    pub(crate) fn nowdoc(&mut self) -> ParseResult<ExpressionKind> {
+        let label = expect!(self, TokenKind::NowdocOpen(label), label.to_string(), [TokenKind::NowdocOpen(String::new())]);
+
+        let mut value = String::new();
+        if let TokenKind::StringPart(s) = &self.current.kind {
+            value = s.to_string();
+            self.next();
+        }
+
+        expect!(self, TokenKind::DocClose);
+
+        Ok(ExpressionKind::Nowdoc { label, value })
+    }
+
+    fn string_parts(&mut self, until: &TokenKind) -> ParseResult<Vec<StringPart>> {
+        let mut parts = Vec::new();
+
+        while ! self.is_eof() && &self.current.kind != until {
+            match &self.current.kind {
+                TokenKind::StringPart(s) => {
+                    parts.push(StringPart::Const(s.to_string()));
+                    self.next();
+                },
+                TokenKind::CurlyOpen => {
+                    self.next();
+
+                    let e = self.expression(0)?;
+
+                    self.rbrace()?;
+
+                    parts.push(StringPart::Expr(Box::new(e)));
+                },
+                _ => {
+                    let e = self.expression(0)?;
+
+                    parts.push(StringPart::Expr(Box::new(e)));
+                },
+            }
+        }
+
+        Ok(parts)
+    }
+}
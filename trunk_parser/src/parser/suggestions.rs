@@ -0,0 +1,157 @@
+use std::fmt::Display;
+
+use trunk_lexer::{Span, Token, TokenKind};
+
+use super::{ParseError, Parser};
+
+/// Keywords that start a statement or a class member, and so are worth suggesting when an
+/// identifier in their place is spelled almost the same.
+const KEYWORDS: &[&str] = &[
+    "abstract", "break", "case", "catch", "class", "const", "continue", "declare", "default", "do", "echo", "else",
+    "elseif", "enum", "extends", "final", "finally", "for", "foreach", "function", "global", "if", "implements",
+    "include", "include_once", "interface", "namespace", "private", "protected", "public", "readonly", "require",
+    "require_once", "return", "static", "switch", "throw", "trait", "try", "use", "var", "while",
+];
+
+/// A likely fix for a parse error: replacing the source at `span` with `replacement`. An empty
+/// span means the replacement should be inserted there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+}
+
+impl Display for Suggestion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl<'a> Parser<'a> {
+    /// An error for finding the current token instead of one of `expected`.
+    pub(crate) fn expected(&self, expected: Vec<TokenKind>) -> ParseError {
+        let suggestion = self.suggestion(&expected).map(Box::new);
+
+        ParseError::ExpectedToken { expected, found: self.current.clone(), suggestion }
+    }
+
+    fn suggestion(&self, expected: &[TokenKind]) -> Option<Suggestion> {
+        // `current` is two tokens behind the cursor, or the end of file past the last token.
+        let end = self.cursor.saturating_sub(2).min(self.tokens.len());
+        let previous = self.tokens[..end].iter().rev().find(|token| ! matches!(token.kind, TokenKind::Comment(_) | TokenKind::DocComment(_)));
+
+        // A misspelled keyword is usually lexed as an identifier, and the error comes either at the
+        // token after it, such as the name in `funciton foo() {}`, or at the identifier itself.
+        for token in [previous, Some(self.current)].into_iter().flatten() {
+            if let TokenKind::Identifier(name) = &token.kind {
+                if let Some(keyword) = similar_keyword(name) {
+                    return Some(Suggestion { message: format!("did you mean `{}`?", keyword), span: token.span, replacement: keyword.into() });
+                }
+            }
+        }
+
+        // A semicolon is obviously missing when the statement is followed by the end of its block
+        // or by something on the next line.
+        let previous = previous?;
+        let missing_semicolon = expected.contains(&TokenKind::SemiColon)
+            && (matches!(self.current.kind, TokenKind::RightBrace | TokenKind::Eof) || self.current.span.line > previous.span.line);
+
+        missing_semicolon.then(|| Suggestion { message: "add `;` here".into(), span: after(previous), replacement: ";".into() })
+    }
+}
+
+/// An empty span just after the token. This assumes that the token is on a single line, which is
+/// true of everything except strings.
+fn after(token: &Token) -> Span {
+    let Span { end, line, col, start } = token.span;
+
+    Span { start: end, end, line, col: col + (end - start) }
+}
+
+/// The keyword that the identifier is most likely a typo of. Short words have to be within a single
+/// edit, and longer ones within two.
+fn similar_keyword(name: &str) -> Option<&'static str> {
+    let name = name.to_ascii_lowercase();
+    let max = if name.len() < 8 { 1 } else { 2 };
+
+    KEYWORDS.iter()
+        .map(|keyword| (edit_distance(&name, keyword), *keyword))
+        .filter(|(distance, _)| *distance > 0 && *distance <= max)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, keyword)| keyword)
+}
+
+/// The number of insertions, deletions, substitutions and swaps of adjacent characters needed to
+/// turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+
+            distances[i][j] = (distances[i - 1][j] + 1).min(distances[i][j - 1] + 1).min(distances[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distances[i][j] = distances[i][j].min(distances[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_lexer::Lexer;
+
+    use crate::{ParseError, Parser};
+
+    fn suggestion(source: &str) -> Option<(String, String, &str)> {
+        let tokens = Lexer::new(None).tokenize(source).unwrap();
+
+        match Parser::new(None).parse(&tokens) {
+            Err(error) => error.suggestion().map(|s| (s.message.clone(), s.replacement.clone(), &source[s.span.start..s.span.end])),
+            Ok(_) => panic!("expected a parse error in {}", source),
+        }
+    }
+
+    #[test]
+    fn misspelled_keywords() {
+        assert_eq!(suggestion("<?php funciton foo() {}"), Some(("did you mean `function`?".into(), "function".into(), "funciton")));
+        assert_eq!(suggestion("<?php retrun 1;"), Some(("did you mean `return`?".into(), "return".into(), "retrun")));
+        assert_eq!(suggestion("<?php class A { pubic function f() {} }"), Some(("did you mean `public`?".into(), "public".into(), "pubic")));
+        assert_eq!(suggestion("<?php class A { public fucntion f() {} }"), Some(("did you mean `function`?".into(), "function".into(), "fucntion")));
+
+        // Names that aren't close to any keyword don't get a suggestion.
+        assert_eq!(suggestion("<?php hello world;"), None);
+    }
+
+    #[test]
+    fn missing_semicolons() {
+        let source = "<?php function f() { return 1 }";
+
+        assert_eq!(suggestion(source), Some(("add `;` here".into(), ";".into(), "")));
+
+        let tokens = Lexer::new(None).tokenize(source).unwrap();
+        match Parser::new(None).parse(&tokens) {
+            Err(ParseError::ExpectedToken { suggestion: Some(suggestion), .. }) => assert_eq!(suggestion.span.start, source.find(" }").unwrap()),
+            result => panic!("expected a suggestion, got {:?}", result),
+        }
+
+        assert!(suggestion("<?php $a = 1\n$b = 2;").is_some());
+        assert_eq!(suggestion("<?php $a = 1 2;"), None);
+    }
+}
@@ -0,0 +1,118 @@
+use std::fmt::Display;
+
+use trunk_lexer::Span;
+
+use crate::{traverser::{walk_expression, Visitor}, AttributeGroup, Block, Expression, ExpressionKind, Identifier, StatementKind};
+
+use super::{Parser, PhpVersion};
+
+/// Syntax that parses, but that's deprecated or has been removed in the PHP version being
+/// targeted. Warnings don't stop parsing, and are collected by the parser as it goes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseWarning {
+    Deprecated { feature: &'static str, since: PhpVersion, span: Span },
+    Removed { feature: &'static str, version: PhpVersion, span: Span },
+}
+
+impl ParseWarning {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Deprecated { span, .. } | Self::Removed { span, .. } => *span,
+        }
+    }
+
+    /// A description of the warning, without the location that `Display` includes.
+    pub fn message(&self) -> String {
+        match self {
+            Self::Deprecated { feature, since, .. } => format!("{} is deprecated since PHP {}", feature, since),
+            Self::Removed { feature, version, .. } => format!("{} is no longer supported as of PHP {}", feature, version),
+        }
+    }
+}
+
+impl Display for ParseWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let level = match self {
+            Self::Deprecated { .. } => "Deprecated",
+            Self::Removed { .. } => "Warning",
+        };
+
+        write!(f, "{}: {} on line {}", level, self.message(), self.span().line)
+    }
+}
+
+impl<'a> Parser<'a> {
+    /// Warnings about the source from the most recent parse.
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    /// Warn about syntax that was deprecated in `since` and, if it has been, removed in `removed`,
+    /// as long as the targeted PHP version is affected.
+    pub(crate) fn deprecated(&mut self, feature: &'static str, since: PhpVersion, removed: Option<PhpVersion>, span: Span) {
+        let warning = match removed {
+            Some(version) if self.config.php_version >= version => ParseWarning::Removed { feature, version, span },
+            _ if self.config.php_version >= since => ParseWarning::Deprecated { feature, since, span },
+            _ => return,
+        };
+
+        self.warnings.push(warning);
+    }
+
+    /// Warn about properties that a class creates by assigning to `$this` without declaring them.
+    pub(crate) fn dynamic_properties(&mut self, body: &Block, extends: &Option<Identifier>, attributes: &[AttributeGroup]) {
+        // A parent class or `__set` could declare or handle the property, which can't be seen here.
+        let allowed = attributes.iter().flat_map(|group| &group.members).any(|attribute| attribute.name.name.trim_start_matches('\\') == "AllowDynamicProperties");
+        let magic = body.iter().any(|statement| matches!(&statement.kind, StatementKind::Method { name, .. } if name.name.eq_ignore_ascii_case("__set")));
+
+        if extends.is_some() || allowed || magic {
+            return;
+        }
+
+        let mut assignments = PropertyAssignments { declared: Vec::new(), assigned: Vec::new() };
+
+        for statement in body {
+            match &statement.kind {
+                StatementKind::Property { var, .. } | StatementKind::Var { var, .. } => assignments.declared.push(var.clone()),
+                StatementKind::Method { params, .. } => {
+                    let promoted = params.iter().filter(|param| ! param.flags.is_empty()).filter_map(|param| param.variable_name());
+
+                    assignments.declared.extend(promoted.map(String::from));
+                },
+                _ => {},
+            }
+        }
+
+        for statement in body {
+            if let StatementKind::Method { .. } = statement.kind {
+                assignments.visit_statement(statement);
+            }
+        }
+
+        for span in assignments.assigned {
+            self.deprecated("creating dynamic properties", PhpVersion::Php82, None, span);
+        }
+    }
+}
+
+struct PropertyAssignments {
+    declared: Vec<String>,
+    assigned: Vec<Span>,
+}
+
+impl Visitor for PropertyAssignments {
+    fn visit_expression(&mut self, expression: &Expression) {
+        if let ExpressionKind::Assign { var, .. } | ExpressionKind::AssignRef { var, .. } | ExpressionKind::CoalesceAssign { var, .. } = &expression.kind {
+            if let ExpressionKind::PropertyFetch { target, property } = &var.kind {
+                if let (ExpressionKind::Variable { name: this }, ExpressionKind::Identifier { name }) = (&target.kind, &property.kind) {
+                    if this == "this" && ! self.declared.contains(name) {
+                        self.declared.push(name.clone());
+                        self.assigned.push(var.span);
+                    }
+                }
+            }
+        }
+
+        walk_expression(self, expression);
+    }
+}
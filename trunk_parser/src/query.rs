@@ -0,0 +1,569 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use crate::{Program, Span};
+
+/// Lists of plain structs that are matched as nodes of their own, by the field that holds them.
+const LIST_KINDS: &[(&str, &str)] = &[
+    ("params", "Param"),
+    ("args", "Arg"),
+    ("catches", "Catch"),
+    ("cases", "Case"),
+    ("arms", "MatchArm"),
+    ("else_ifs", "ElseIf"),
+    ("items", "ArrayItem"),
+];
+
+/// A pattern of nodes to find in a program, written as a selector in the style of CSS, such as
+/// `Class Method[name=__construct] Param[default]`.
+///
+/// * A kind, such as `Call`, matches statements and expressions of that kind, ignoring case, and
+///   `*` matches any node. Parameters, arguments, `catch` clauses, `case`s, `match` arms, `elseif`s
+///   and array items are nodes as well, of the kinds `Param`, `Arg`, `Catch`, `Case`, `MatchArm`,
+///   `ElseIf` and `ArrayItem`.
+/// * `[field]` matches nodes where the field is set, and isn't `null`, `false` or empty.
+/// * `[field=value]` and `[field!=value]` compare a field with a value, which can be quoted. A field
+///   that's a node compares as its kind, and an identifier as its name. A list matches if any of its
+///   items do. Fields can be followed into nodes and lists with `.`, as in `[args.0.value=Variable]`.
+/// * `:has(selector)` matches nodes with a descendant that matches the selector, and
+///   `:not(selector)` matches nodes that the selector doesn't, where the selector is a single kind
+///   and its filters.
+/// * `A B` matches a `B` anywhere inside an `A`, and `A > B` a `B` directly inside one.
+/// * `A, B` matches either.
+///
+/// For example, a call to `eval` with an argument that isn't a literal string is
+/// `Call[target.name=eval]:not(:has(> Arg > ConstantString))`. A selector that starts with `>`
+/// only matches nodes at the top of the program, or directly inside the node that `:has` is
+/// applied to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    selectors: Vec<Selector>,
+}
+
+/// A node that a query matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryMatch {
+    pub kind: String,
+    /// Where the node is. Nodes without a span of their own, such as arguments, take the span of the
+    /// first node inside them.
+    pub span: Option<Span>,
+}
+
+/// A selector that couldn't be parsed, with the byte offset in it where the problem is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    pub message: String,
+    pub offset: usize,
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Compound selectors, each with how it relates to the one before it. A first compound with a
+/// `Child` combinator only matches nodes at the top, of the program or of the node that `:has` is
+/// applied to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Selector {
+    compounds: Vec<(Combinator, Compound)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Compound {
+    /// The kind to match, or `None` for any.
+    kind: Option<String>,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Filter {
+    Present(Vec<String>),
+    Equals(Vec<String>, String),
+    NotEquals(Vec<String>, String),
+    Has(Query),
+    Not(Compound),
+}
+
+impl Query {
+    pub fn parse(source: &str) -> Result<Self, QueryError> {
+        let mut parser = SelectorParser { source, offset: 0 };
+        let query = parser.query()?;
+
+        parser.whitespace();
+
+        match parser.peek() {
+            None => Ok(query),
+            Some(c) => Err(parser.error(format!("unexpected `{}`", c))),
+        }
+    }
+
+    /// Find every node in a program that the query matches, in the order that they appear.
+    pub fn find(&self, program: &Program) -> Vec<QueryMatch> {
+        let value = serde_json::to_value(program).expect("the AST is serializable");
+        let mut nodes = Vec::new();
+        children(&value, &mut nodes);
+
+        let mut matches = Vec::new();
+        let mut ancestors = Vec::new();
+
+        for node in &nodes {
+            self.search(node, &mut ancestors, &mut matches);
+        }
+
+        // Fields are serialized in order of name rather than of the source, so siblings are sorted,
+        // keeping each node before the nodes inside it that start in the same place.
+        matches.sort_by_key(|m| m.span.map_or(usize::MAX, |span| span.start));
+        matches
+    }
+
+    fn search<'n, 'v>(&self, node: &'n Node<'v>, ancestors: &mut Vec<&'n Node<'v>>, matches: &mut Vec<QueryMatch>) {
+        if self.matches(node, ancestors) {
+            matches.push(QueryMatch { kind: node.kind.to_string(), span: node.span });
+        }
+
+        ancestors.push(node);
+
+        for child in &node.children {
+            self.search(child, ancestors, matches);
+        }
+
+        ancestors.pop();
+    }
+
+    fn matches(&self, node: &Node, ancestors: &[&Node]) -> bool {
+        self.selectors.iter().any(|selector| selector.matches(selector.compounds.len(), node, ancestors))
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::parse(source)
+    }
+}
+
+impl Selector {
+    /// Whether the first `count` compounds match a node, with the last of them matching the node
+    /// itself.
+    fn matches(&self, count: usize, node: &Node, ancestors: &[&Node]) -> bool {
+        let (combinator, compound) = &self.compounds[count - 1];
+
+        if ! compound.matches(node) {
+            return false;
+        }
+
+        if count == 1 {
+            return *combinator == Combinator::Descendant || ancestors.is_empty();
+        }
+
+        match combinator {
+            Combinator::Child => ancestors.split_last().is_some_and(|(parent, rest)| self.matches(count - 1, parent, rest)),
+            Combinator::Descendant => (0..ancestors.len()).rev().any(|i| self.matches(count - 1, ancestors[i], &ancestors[..i])),
+        }
+    }
+}
+
+impl Compound {
+    fn matches(&self, node: &Node) -> bool {
+        self.kind.as_ref().is_none_or(|kind| kind.eq_ignore_ascii_case(node.kind)) && self.filters.iter().all(|filter| filter.matches(node))
+    }
+}
+
+impl Filter {
+    fn matches(&self, node: &Node) -> bool {
+        match self {
+            Self::Present(path) => field(node.fields, path).is_some_and(is_present),
+            Self::Equals(path, expected) => field(node.fields, path).is_some_and(|value| equals(value, expected)),
+            Self::NotEquals(path, expected) => ! field(node.fields, path).is_some_and(|value| equals(value, expected)),
+            Self::Has(query) => node.children.iter().any(|child| has(query, child, &mut Vec::new())),
+            Self::Not(compound) => ! compound.matches(node),
+        }
+    }
+}
+
+/// Whether a query matches a node or anything inside it, where the query only sees the ancestors
+/// below the node that `:has` is applied to.
+fn has<'n, 'v>(query: &Query, node: &'n Node<'v>, ancestors: &mut Vec<&'n Node<'v>>) -> bool {
+    if query.matches(node, ancestors) {
+        return true;
+    }
+
+    ancestors.push(node);
+    let found = node.children.iter().any(|child| has(query, child, ancestors));
+    ancestors.pop();
+
+    found
+}
+
+/// A statement, expression or other node in the serialized AST.
+struct Node<'v> {
+    kind: &'v str,
+    /// The node's fields, as an object, or `null` if it has none.
+    fields: &'v Value,
+    span: Option<Span>,
+    children: Vec<Node<'v>>,
+}
+
+fn is_node(value: &Value) -> bool {
+    value.get("kind").is_some() && value.get("span").is_some()
+}
+
+/// The kind and fields of a statement or expression.
+fn variant(value: &Value) -> (&str, &Value) {
+    match &value["kind"] {
+        Value::String(kind) => (kind, &Value::Null),
+        Value::Object(variants) => variants.iter().next().map_or(("", &Value::Null), |(kind, fields)| (kind, fields)),
+        _ => ("", &Value::Null),
+    }
+}
+
+fn node<'v>(kind: &'v str, fields: &'v Value, span: &Value) -> Node<'v> {
+    let mut nodes = Vec::new();
+    children(fields, &mut nodes);
+
+    let span = serde_json::from_value(span.clone()).ok().or_else(|| nodes.first().and_then(|node| node.span));
+
+    Node { kind, fields, span, children: nodes }
+}
+
+/// Collect the nodes below a value, without looking inside the nodes themselves.
+fn children<'v>(value: &'v Value, nodes: &mut Vec<Node<'v>>) {
+    match value {
+        Value::Object(_) if is_node(value) => {
+            let (kind, fields) = variant(value);
+            nodes.push(node(kind, fields, &value["span"]));
+        },
+        Value::Object(object) => {
+            for (key, value) in object {
+                match (LIST_KINDS.iter().find(|(field, _)| field == key), value) {
+                    (Some((_, kind)), Value::Array(items)) => {
+                        for item in items {
+                            match item {
+                                Value::Object(_) if ! is_node(item) => nodes.push(node(kind, item, &item["span"])),
+                                _ => children(item, nodes),
+                            }
+                        }
+                    },
+                    _ => children(value, nodes),
+                }
+            }
+        },
+        Value::Array(items) => items.iter().for_each(|item| children(item, nodes)),
+        _ => {},
+    }
+}
+
+/// Follow a path of fields and list indexes from a node's fields, looking through the nodes along
+/// the way to their fields.
+fn field<'v>(fields: &'v Value, path: &[String]) -> Option<&'v Value> {
+    path.iter().try_fold(fields, |value, segment| {
+        let value = if is_node(value) { variant(value).1 } else { value };
+
+        match value {
+            Value::Object(object) => object.get(segment),
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+            _ => None,
+        }
+    })
+}
+
+fn is_present(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::String(value) => ! value.is_empty(),
+        Value::Array(items) => ! items.is_empty(),
+        _ => true,
+    }
+}
+
+fn equals(value: &Value, expected: &str) -> bool {
+    match value {
+        Value::String(value) => value == expected,
+        Value::Bool(value) => value.to_string() == expected,
+        Value::Number(value) => value.to_string() == expected,
+        Value::Array(items) => items.iter().any(|item| equals(item, expected)),
+        Value::Object(_) if is_node(value) => variant(value).0 == expected,
+        // Identifiers compare as their name.
+        Value::Object(object) => object.get("name").is_some_and(|name| equals(name, expected)),
+        Value::Null => false,
+    }
+}
+
+struct SelectorParser<'a> {
+    source: &'a str,
+    offset: usize,
+}
+
+impl SelectorParser<'_> {
+    fn error(&self, message: impl Into<String>) -> QueryError {
+        QueryError { message: message.into(), offset: self.offset }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.offset..].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.offset += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), QueryError> {
+        match self.eat(c) {
+            true => Ok(()),
+            false => Err(self.error(format!("expected `{}`", c))),
+        }
+    }
+
+    fn whitespace(&mut self) -> bool {
+        let start = self.offset;
+
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.offset += 1;
+        }
+
+        self.offset > start
+    }
+
+    fn word(&mut self) -> &str {
+        let start = self.offset;
+
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+            self.offset += 1;
+        }
+
+        &self.source[start..self.offset]
+    }
+
+    fn query(&mut self) -> Result<Query, QueryError> {
+        let mut selectors = vec![self.selector()?];
+
+        while self.eat(',') {
+            selectors.push(self.selector()?);
+        }
+
+        Ok(Query { selectors })
+    }
+
+    fn selector(&mut self) -> Result<Selector, QueryError> {
+        self.whitespace();
+
+        let mut combinator = if self.eat('>') { Combinator::Child } else { Combinator::Descendant };
+        let mut compounds = Vec::new();
+
+        loop {
+            self.whitespace();
+            compounds.push((combinator, self.compound()?));
+
+            let spaced = self.whitespace();
+
+            combinator = match self.peek() {
+                Some('>') => {
+                    self.offset += 1;
+                    Combinator::Child
+                },
+                Some(',' | ')') | None => break,
+                Some(_) if spaced => Combinator::Descendant,
+                Some(c) => return Err(self.error(format!("unexpected `{}`", c))),
+            };
+        }
+
+        Ok(Selector { compounds })
+    }
+
+    fn compound(&mut self) -> Result<Compound, QueryError> {
+        let mut compound = Compound::default();
+        let any = self.eat('*');
+
+        if ! any {
+            let kind = self.word();
+
+            if ! kind.is_empty() {
+                compound.kind = Some(kind.to_string());
+            }
+        }
+
+        loop {
+            if self.eat('[') {
+                compound.filters.push(self.attribute()?);
+            } else if self.eat(':') {
+                compound.filters.push(self.pseudo()?);
+            } else {
+                break;
+            }
+        }
+
+        if ! any && compound.kind.is_none() && compound.filters.is_empty() {
+            return Err(self.error("expected a kind, `*`, `[` or `:`"));
+        }
+
+        Ok(compound)
+    }
+
+    fn attribute(&mut self) -> Result<Filter, QueryError> {
+        self.whitespace();
+
+        let mut path = Vec::new();
+
+        loop {
+            let segment = self.word();
+
+            if segment.is_empty() {
+                return Err(self.error("expected a field name"));
+            }
+
+            path.push(segment.to_string());
+
+            if ! self.eat('.') {
+                break;
+            }
+        }
+
+        self.whitespace();
+
+        let filter = if self.eat(']') {
+            return Ok(Filter::Present(path));
+        } else if self.eat('=') {
+            Filter::Equals(path, self.value()?)
+        } else if self.eat('!') {
+            self.expect('=')?;
+            Filter::NotEquals(path, self.value()?)
+        } else {
+            return Err(self.error("expected `]`, `=` or `!=`"));
+        };
+
+        self.whitespace();
+        self.expect(']')?;
+
+        Ok(filter)
+    }
+
+    fn value(&mut self) -> Result<String, QueryError> {
+        self.whitespace();
+
+        match self.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                self.offset += 1;
+                let start = self.offset;
+
+                match self.source[start..].find(quote) {
+                    Some(length) => {
+                        self.offset = start + length + 1;
+                        Ok(self.source[start..start + length].to_string())
+                    },
+                    None => Err(self.error("unterminated string")),
+                }
+            },
+            _ => {
+                let start = self.offset;
+
+                while self.peek().is_some_and(|c| c != ']' && ! c.is_whitespace()) {
+                    self.offset += self.peek().map_or(1, char::len_utf8);
+                }
+
+                match &self.source[start..self.offset] {
+                    "" => Err(self.error("expected a value")),
+                    value => Ok(value.to_string()),
+                }
+            },
+        }
+    }
+
+    fn pseudo(&mut self) -> Result<Filter, QueryError> {
+        let start = self.offset;
+
+        let filter = match self.word() {
+            "has" => {
+                self.expect('(')?;
+                Filter::Has(self.query()?)
+            },
+            "not" => {
+                self.expect('(')?;
+                self.whitespace();
+                let compound = self.compound()?;
+                self.whitespace();
+                Filter::Not(compound)
+            },
+            name => {
+                let name = name.to_string();
+                self.offset = start;
+                return Err(self.error(format!("unknown pseudo-class `:{}`", name)));
+            },
+        };
+
+        self.expect(')')?;
+
+        Ok(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse;
+
+    use super::Query;
+
+    fn find(selector: &str, source: &str) -> Vec<String> {
+        Query::parse(selector)
+            .unwrap()
+            .find(&parse(source).unwrap())
+            .iter()
+            .map(|m| format!("{}@{}:{}", m.kind, m.span.unwrap().line, m.span.unwrap().col))
+            .collect()
+    }
+
+    const SOURCE: &str = "<?php
+class User {
+    public function __construct(private $name = 'x', $age, $email = null) {}
+    public static function make($args) { return new static(...$args); }
+}
+
+eval($code);
+eval('return 1;');
+function f() { return [1, 'a' => 2]; }
+";
+
+    #[test]
+    fn selectors() {
+        assert_eq!(find("Class Method[name=__construct] Param[default]", SOURCE), ["Param@3:33", "Param@3:60"]);
+        assert_eq!(find("Method[flags=Static]", SOURCE), ["Method@4:5"]);
+        assert_eq!(find("method > param", SOURCE), find("Method > Param", SOURCE));
+        assert_eq!(find("Method > Param", SOURCE).len(), 4);
+        assert_eq!(find("> Function, > Class", SOURCE), ["Class@2:1", "Function@9:1"]);
+        assert_eq!(find("Class > Param", SOURCE), Vec::<String>::new());
+        assert_eq!(find("Call[target.name=eval][args.0.value!=ConstantString]", SOURCE), ["Call@7:1"]);
+        assert_eq!(find("Call[target.name=eval]:not(:has(> Arg > ConstantString))", SOURCE), ["Call@7:1"]);
+        assert_eq!(find("Function:has(ArrayItem[key]), New", SOURCE), ["New@4:49", "Function@9:1"]);
+        assert_eq!(find("Arg[unpack=true]", SOURCE), ["Arg@4:63"]);
+        assert_eq!(find("Method Variable[name=args]", SOURCE), ["Variable@4:33", "Variable@4:63"]);
+        assert_eq!(find("*[name='__construct']", SOURCE), ["Method@3:5"]);
+    }
+
+    #[test]
+    fn errors() {
+        for selector in ["", "Class >", "Param[", "Param[default", "Param[default=]", "Call:is(Foo)", "Call[a='b]", "Class $"] {
+            assert!(Query::parse(selector).is_err(), "{}", selector);
+        }
+
+        assert_eq!(Query::parse("Call:is(Foo)").unwrap_err().offset, 5);
+        assert_eq!("Class".parse::<Query>().unwrap(), Query::parse(" Class ").unwrap());
+    }
+}
@@ -0,0 +1,646 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use serde::de::{self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_json::{json, Map, Value};
+
+use crate::Program;
+
+/// Fields that are left out of the serialized AST when they're empty.
+const OPTIONAL_FIELDS: &[&str] = &["meta"];
+
+/// A JSON Schema describing the serialized form of a `Program`, with a definition for every
+/// struct and enum in the AST. The schema is derived from the AST's `Deserialize` impls, so it
+/// can't drift out of date.
+pub fn ast_schema() -> Value {
+    let mut tracer = Tracer::default();
+    let mut root = Format::Unknown;
+
+    // Every pass takes the next unexplored variant of each enum it comes across, so it takes
+    // several passes to explore every variant of every enum.
+    for _ in 0..10_000 {
+        let _ = Program::deserialize(Trace { tracer: &mut tracer, format: &mut root, safe: false });
+
+        if tracer.is_complete() {
+            break;
+        }
+    }
+
+    let definitions: Map<String, Value> = tracer.registry.iter()
+        .map(|(name, container)| (name.to_string(), container.schema()))
+        .collect();
+
+    let mut schema = root.schema();
+    let object = schema.as_object_mut().expect("the root schema is an object");
+
+    object.insert("$schema".into(), json!("https://json-schema.org/draft/2020-12/schema"));
+    object.insert("title".into(), json!("Program"));
+    object.insert("$defs".into(), Value::Object(definitions));
+
+    schema
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Format {
+    Unknown,
+    Unit,
+    Bool,
+    Int,
+    Unsigned,
+    Float,
+    String,
+    Bytes,
+    /// Any JSON value, such as node metadata.
+    Any,
+    Option(Box<Format>),
+    Seq(Box<Format>),
+    Map(Box<Format>, Box<Format>),
+    Tuple(Vec<Format>),
+    Named(&'static str),
+}
+
+impl Format {
+    fn schema(&self) -> Value {
+        match self {
+            Self::Unknown | Self::Any => json!({}),
+            Self::Unit => json!({ "type": "null" }),
+            Self::Bool => json!({ "type": "boolean" }),
+            Self::Int => json!({ "type": "integer" }),
+            Self::Unsigned => json!({ "type": "integer", "minimum": 0 }),
+            Self::Float => json!({ "type": "number" }),
+            Self::String => json!({ "type": "string" }),
+            Self::Bytes => json!({ "type": "array", "items": { "type": "integer", "minimum": 0, "maximum": 255 } }),
+            Self::Option(format) => json!({ "anyOf": [{ "type": "null" }, format.schema()] }),
+            Self::Seq(format) => json!({ "type": "array", "items": format.schema() }),
+            Self::Map(_, value) => json!({ "type": "object", "additionalProperties": value.schema() }),
+            Self::Tuple(formats) => json!({
+                "type": "array",
+                "prefixItems": formats.iter().map(Format::schema).collect::<Vec<_>>(),
+                "minItems": formats.len(),
+                "maxItems": formats.len(),
+            }),
+            Self::Named(name) => json!({ "$ref": format!("#/$defs/{}", name) }),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum VariantFormat {
+    Unit,
+    Newtype(Format),
+    Tuple(Vec<Format>),
+    Struct(Vec<(&'static str, Format)>),
+}
+
+#[derive(Debug)]
+enum Container {
+    Struct(Vec<(&'static str, Format)>),
+    Newtype(Format),
+    Enum {
+        variants: &'static [&'static str],
+        formats: BTreeMap<u32, VariantFormat>,
+        next: u32,
+    },
+}
+
+impl Container {
+    fn schema(&self) -> Value {
+        match self {
+            Self::Struct(fields) => object_schema(fields),
+            Self::Newtype(format) => format.schema(),
+            // Enums are externally tagged, so unit variants are plain strings and every other
+            // variant is an object with a single key.
+            Self::Enum { variants, formats, .. } => {
+                let mut units = Vec::new();
+                let mut schemas = Vec::new();
+
+                for (index, format) in formats {
+                    let name = variants[*index as usize];
+
+                    let content = match format {
+                        VariantFormat::Unit => {
+                            units.push(json!(name));
+                            continue;
+                        },
+                        VariantFormat::Newtype(format) => format.schema(),
+                        VariantFormat::Tuple(formats) => Format::Tuple(formats.clone()).schema(),
+                        VariantFormat::Struct(fields) => object_schema(fields),
+                    };
+
+                    schemas.push(json!({
+                        "type": "object",
+                        "properties": { name: content },
+                        "required": [name],
+                        "additionalProperties": false,
+                    }));
+                }
+
+                if ! units.is_empty() {
+                    schemas.insert(0, json!({ "enum": units }));
+                }
+
+                json!({ "oneOf": schemas })
+            },
+        }
+    }
+}
+
+fn object_schema(fields: &[(&'static str, Format)]) -> Value {
+    let properties: Map<String, Value> = fields.iter().map(|(name, format)| (name.to_string(), format.schema())).collect();
+    let required: Vec<&str> = fields.iter().map(|(name, _)| *name).filter(|name| ! OPTIONAL_FIELDS.contains(name)).collect();
+
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+#[derive(Debug)]
+struct TraceError(String);
+
+impl Display for TraceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl de::Error for TraceError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+/// Records the shape of every type that is deserialized through it, producing placeholder values
+/// along the way. A type that contains itself is only explored at its outermost occurrence, and
+/// nested occurrences are produced as cheaply as possible.
+#[derive(Debug, Default)]
+struct Tracer {
+    registry: BTreeMap<&'static str, Container>,
+    stack: Vec<&'static str>,
+}
+
+impl Tracer {
+    fn is_complete(&self) -> bool {
+        self.registry.values().all(|container| match container {
+            Container::Enum { variants, formats, .. } => formats.len() == variants.len(),
+            _ => true,
+        })
+    }
+
+    /// A variant of the enum that can be produced without recursing forever.
+    fn finite_variant(&self, name: &'static str, seen: &mut Vec<&'static str>) -> Option<u32> {
+        let Some(Container::Enum { formats, .. }) = self.registry.get(name) else {
+            return None;
+        };
+
+        seen.push(name);
+        let variant = formats.iter().find(|(_, format)| match format {
+            VariantFormat::Unit => true,
+            VariantFormat::Newtype(format) => self.is_finite(format, seen),
+            VariantFormat::Tuple(formats) => formats.iter().all(|format| self.is_finite(format, seen)),
+            VariantFormat::Struct(fields) => fields.iter().all(|(_, format)| self.is_finite(format, seen)),
+        }).map(|(index, _)| *index);
+        seen.pop();
+
+        variant
+    }
+
+    fn is_finite(&self, format: &Format, seen: &mut Vec<&'static str>) -> bool {
+        match format {
+            // Options, sequences and maps can always be empty.
+            Format::Option(_) | Format::Seq(_) | Format::Map(..) => true,
+            Format::Tuple(formats) => formats.iter().all(|format| self.is_finite(format, seen)),
+            Format::Named(name) if seen.contains(name) => false,
+            Format::Named(name) => match self.registry.get(name) {
+                Some(Container::Struct(fields)) => {
+                    seen.push(name);
+                    let finite = fields.iter().all(|(_, format)| self.is_finite(format, seen));
+                    seen.pop();
+
+                    finite
+                },
+                Some(Container::Newtype(format)) => {
+                    seen.push(name);
+                    let finite = self.is_finite(format, seen);
+                    seen.pop();
+
+                    finite
+                },
+                Some(Container::Enum { .. }) => self.finite_variant(name, seen).is_some(),
+                None => false,
+            },
+            _ => true,
+        }
+    }
+}
+
+/// Deserializes a single value, recording its format. In safe mode nothing is recorded, and the
+/// smallest possible value is produced instead.
+struct Trace<'a> {
+    tracer: &'a mut Tracer,
+    format: &'a mut Format,
+    safe: bool,
+}
+
+impl<'a> Trace<'a> {
+    fn record(self, format: Format) -> &'a mut Tracer {
+        *self.format = format;
+        self.tracer
+    }
+}
+
+macro_rules! primitive {
+    ($($method:ident => $format:expr, $visit:ident($($value:expr)?);)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+                self.record($format);
+                visitor.$visit($($value)?)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Trace<'_> {
+    type Error = TraceError;
+
+    primitive! {
+        deserialize_any => Format::Any, visit_unit();
+        deserialize_bool => Format::Bool, visit_bool(false);
+        deserialize_i8 => Format::Int, visit_i64(0);
+        deserialize_i16 => Format::Int, visit_i64(0);
+        deserialize_i32 => Format::Int, visit_i64(0);
+        deserialize_i64 => Format::Int, visit_i64(0);
+        deserialize_u8 => Format::Unsigned, visit_u64(0);
+        deserialize_u16 => Format::Unsigned, visit_u64(0);
+        deserialize_u32 => Format::Unsigned, visit_u64(0);
+        deserialize_u64 => Format::Unsigned, visit_u64(0);
+        deserialize_f32 => Format::Float, visit_f64(0.0);
+        deserialize_f64 => Format::Float, visit_f64(0.0);
+        deserialize_char => Format::String, visit_char('a');
+        deserialize_str => Format::String, visit_borrowed_str("");
+        deserialize_string => Format::String, visit_borrowed_str("");
+        deserialize_identifier => Format::String, visit_borrowed_str("");
+        deserialize_bytes => Format::Bytes, visit_borrowed_bytes(&[]);
+        deserialize_byte_buf => Format::Bytes, visit_borrowed_bytes(&[]);
+        deserialize_unit => Format::Unit, visit_unit();
+        deserialize_ignored_any => Format::Any, visit_unit();
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, TraceError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        if self.safe {
+            return visitor.visit_none();
+        }
+
+        let mut inner = Format::Unknown;
+        let value = visitor.visit_some(Trace { tracer: self.tracer, format: &mut inner, safe: false })?;
+        *self.format = Format::Option(Box::new(inner));
+
+        Ok(value)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value, TraceError> {
+        let safe = self.safe || self.tracer.stack.contains(&name);
+        let tracer = self.record(Format::Named(name));
+
+        let mut inner = Format::Unknown;
+        tracer.stack.push(name);
+        let value = visitor.visit_newtype_struct(Trace { tracer: &mut *tracer, format: &mut inner, safe });
+        tracer.stack.pop();
+
+        if ! safe {
+            tracer.registry.insert(name, Container::Newtype(inner));
+        }
+
+        value
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        let mut formats = vec![Format::Unknown];
+        let len = if self.safe { 0 } else { 1 };
+        let value = visitor.visit_seq(Elements { tracer: &mut *self.tracer, formats: &mut formats, index: 0, len, safe: self.safe })?;
+        let [format] = <[Format; 1]>::try_from(formats).expect("one element");
+        *self.format = Format::Seq(Box::new(format));
+
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, TraceError> {
+        let mut formats = vec![Format::Unknown; len];
+        let value = visitor.visit_seq(Elements { tracer: &mut *self.tracer, formats: &mut formats, index: 0, len, safe: self.safe })?;
+        *self.format = Format::Tuple(formats);
+
+        Ok(value)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, name: &'static str, len: usize, visitor: V) -> Result<V::Value, TraceError> {
+        let safe = self.safe || self.tracer.stack.contains(&name);
+        let tracer = self.record(Format::Named(name));
+
+        let mut formats = vec![Format::Unknown; len];
+        tracer.stack.push(name);
+        let value = visitor.visit_seq(Elements { tracer: &mut *tracer, formats: &mut formats, index: 0, len, safe });
+        tracer.stack.pop();
+
+        if ! safe {
+            tracer.registry.insert(name, Container::Newtype(Format::Tuple(formats)));
+        }
+
+        value
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        let mut formats = vec![Format::Unknown, Format::Unknown];
+        let len = if self.safe { 0 } else { 1 };
+        let value = visitor.visit_map(Entries { tracer: &mut *self.tracer, formats: &mut formats, fields: None, index: 0, len, safe: self.safe })?;
+        let [key, value_format] = <[Format; 2]>::try_from(formats).expect("a key and a value");
+        *self.format = Format::Map(Box::new(key), Box::new(value_format));
+
+        Ok(value)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, TraceError> {
+        let safe = self.safe || self.tracer.stack.contains(&name);
+        let tracer = self.record(Format::Named(name));
+
+        let mut formats = vec![Format::Unknown; fields.len()];
+        tracer.stack.push(name);
+        let value = visitor.visit_map(Entries { tracer: &mut *tracer, formats: &mut formats, fields: Some(fields), index: 0, len: fields.len(), safe });
+        tracer.stack.pop();
+
+        if ! safe && value.is_ok() {
+            tracer.registry.insert(name, Container::Struct(fields.iter().copied().zip(formats).collect()));
+        }
+
+        value
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, TraceError> {
+        let nested = self.safe || self.tracer.stack.contains(&name);
+        let tracer = self.record(Format::Named(name));
+
+        let container = tracer.registry.entry(name).or_insert(Container::Enum { variants, formats: BTreeMap::new(), next: 0 });
+        let Container::Enum { next, .. } = container else {
+            return Err(TraceError(format!("`{}` is both an enum and a struct", name)));
+        };
+
+        // Take the variants in turn, unless this enum is nested inside itself, in which case the
+        // cheapest variant that's known to terminate is used.
+        let (index, safe) = if nested {
+            let index = tracer.finite_variant(name, &mut vec![])
+                .ok_or_else(|| TraceError(format!("no finite variant of `{}` is known yet", name)))?;
+
+            (index, true)
+        } else {
+            let index = *next;
+            *next = (*next + 1) % variants.len() as u32;
+
+            (index, false)
+        };
+
+        let mut format = None;
+        tracer.stack.push(name);
+        let value = visitor.visit_enum(Variant { tracer: &mut *tracer, index, format: &mut format, safe });
+        tracer.stack.pop();
+
+        if let (Some(format), Some(Container::Enum { formats, .. })) = (format, tracer.registry.get_mut(name)) {
+            if value.is_ok() {
+                formats.insert(index, format);
+            }
+        }
+
+        value
+    }
+}
+
+struct Elements<'a> {
+    tracer: &'a mut Tracer,
+    formats: &'a mut Vec<Format>,
+    index: usize,
+    len: usize,
+    safe: bool,
+}
+
+impl<'de> SeqAccess<'de> for Elements<'_> {
+    type Error = TraceError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, TraceError> {
+        if self.index == self.len {
+            return Ok(None);
+        }
+
+        let format = &mut self.formats[self.index];
+        self.index += 1;
+
+        seed.deserialize(Trace { tracer: &mut *self.tracer, format, safe: self.safe }).map(Some)
+    }
+}
+
+/// The entries of a map, or the fields of a struct when `fields` is given.
+struct Entries<'a> {
+    tracer: &'a mut Tracer,
+    formats: &'a mut Vec<Format>,
+    fields: Option<&'static [&'static str]>,
+    index: usize,
+    len: usize,
+    safe: bool,
+}
+
+impl<'de> MapAccess<'de> for Entries<'_> {
+    type Error = TraceError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, TraceError> {
+        if self.index == self.len {
+            return Ok(None);
+        }
+
+        match self.fields {
+            Some(fields) => seed.deserialize(IntoDeserializer::<TraceError>::into_deserializer(fields[self.index])).map(Some),
+            None => seed.deserialize(Trace { tracer: &mut *self.tracer, format: &mut self.formats[0], safe: self.safe }).map(Some),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, TraceError> {
+        let format = match self.fields {
+            Some(_) => &mut self.formats[self.index],
+            None => &mut self.formats[1],
+        };
+        self.index += 1;
+
+        seed.deserialize(Trace { tracer: &mut *self.tracer, format, safe: self.safe })
+    }
+}
+
+struct Variant<'a> {
+    tracer: &'a mut Tracer,
+    index: u32,
+    format: &'a mut Option<VariantFormat>,
+    safe: bool,
+}
+
+impl<'de, 'a> EnumAccess<'de> for Variant<'a> {
+    type Error = TraceError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), TraceError> {
+        let variant = seed.deserialize(IntoDeserializer::<TraceError>::into_deserializer(self.index))?;
+
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Variant<'_> {
+    type Error = TraceError;
+
+    fn unit_variant(self) -> Result<(), TraceError> {
+        *self.format = Some(VariantFormat::Unit);
+
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, TraceError> {
+        let mut format = Format::Unknown;
+        let value = seed.deserialize(Trace { tracer: self.tracer, format: &mut format, safe: self.safe })?;
+        *self.format = Some(VariantFormat::Newtype(format));
+
+        Ok(value)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, TraceError> {
+        let mut formats = vec![Format::Unknown; len];
+        let value = visitor.visit_seq(Elements { tracer: self.tracer, formats: &mut formats, index: 0, len, safe: self.safe })?;
+        *self.format = Some(VariantFormat::Tuple(formats));
+
+        Ok(value)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, TraceError> {
+        let mut formats = vec![Format::Unknown; fields.len()];
+        let value = visitor.visit_map(Entries { tracer: self.tracer, formats: &mut formats, fields: Some(fields), index: 0, len: fields.len(), safe: self.safe })?;
+        *self.format = Some(VariantFormat::Struct(fields.iter().copied().zip(formats).collect()));
+
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::ast_schema;
+    use crate::parse;
+
+    /// Check a value against the subset of JSON Schema that `ast_schema` uses.
+    fn validate(schema: &Value, value: &Value, root: &Value) -> bool {
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            let name = reference.trim_start_matches("#/$defs/");
+            return validate(&root["$defs"][name], value, root);
+        }
+
+        if let Some(schemas) = schema.get("anyOf").and_then(Value::as_array) {
+            return schemas.iter().any(|schema| validate(schema, value, root));
+        }
+
+        if let Some(schemas) = schema.get("oneOf").and_then(Value::as_array) {
+            return schemas.iter().filter(|schema| validate(schema, value, root)).count() == 1;
+        }
+
+        if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+            return values.contains(value);
+        }
+
+        match schema.get("type").and_then(Value::as_str) {
+            None => true,
+            Some("null") => value.is_null(),
+            Some("boolean") => value.is_boolean(),
+            Some("integer") => value.is_i64() || value.is_u64(),
+            Some("number") => value.is_number(),
+            Some("string") => value.is_string(),
+            Some("array") => match value.as_array() {
+                Some(items) => items.iter().enumerate().all(|(i, item)| match schema.get("prefixItems") {
+                    Some(prefix) => validate(&prefix[i], item, root),
+                    None => validate(&schema["items"], item, root),
+                }),
+                None => false,
+            },
+            Some("object") => match value.as_object() {
+                Some(object) => {
+                    let required = schema.get("required").and_then(Value::as_array).map_or(&[][..], Vec::as_slice);
+
+                    required.iter().all(|key| object.contains_key(key.as_str().unwrap()))
+                        && object.iter().all(|(key, value)| match schema.get("properties").and_then(|properties| properties.get(key)) {
+                            Some(property) => validate(property, value, root),
+                            None => match schema.get("additionalProperties") {
+                                Some(Value::Bool(false)) => false,
+                                Some(additional) => validate(additional, value, root),
+                                None => true,
+                            },
+                        })
+                },
+                None => false,
+            },
+            Some(_) => false,
+        }
+    }
+
+    #[test]
+    fn describes_every_variant() {
+        let schema = ast_schema();
+        let definitions = schema["$defs"].as_object().unwrap();
+
+        for name in ["Statement", "StatementKind", "Expression", "ExpressionKind", "Span", "Type", "CastKind", "InfixOp"] {
+            assert!(definitions.contains_key(name), "missing {}", name);
+        }
+
+        let kinds = serde_json::to_string(&schema["$defs"]["ExpressionKind"]).unwrap();
+        for variant in ["\"Static\"", "\"Cast\"", "\"Error\"", "\"ArrowFunction\""] {
+            assert!(kinds.contains(variant), "missing {}", variant);
+        }
+
+        assert_eq!(schema["$defs"]["Statement"]["required"], serde_json::json!(["kind", "span"]));
+    }
+
+    #[test]
+    fn validates_serialized_programs() {
+        let schema = ast_schema();
+
+        let mut ast = parse("<?php
+            namespace App;
+
+            #[Attr(1)]
+            final class A extends B implements C {
+                public const X = 1.5;
+                private ?int $a = null;
+
+                public function __construct(protected string $b = 'b', int ...$c) {}
+
+                public static function f(): static|false {
+                    return match (true) { $a instanceof A => fn &() => [1, 'a' => &$b, ...$c], default => \"{$d->e}\" };
+                }
+            }
+
+            enum D: int { case E = 1; }
+            foreach ($a as $k => &$v) { try { throw new E(); } catch (E|F $e) {} finally { echo (int) $x ?? -1; } }
+        ").unwrap();
+        ast[0].meta.insert("note", "anything");
+
+        let value = serde_json::to_value(&ast).unwrap();
+        assert!(validate(&schema, &value, &schema));
+
+        let mut invalid = value.clone();
+        invalid[0]["span"]["line"] = "one".into();
+        assert!(! validate(&schema, &invalid, &schema));
+
+        let mut invalid = value;
+        invalid[0]["kind"] = "NotAStatement".into();
+        assert!(! validate(&schema, &invalid, &schema));
+    }
+}
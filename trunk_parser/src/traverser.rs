@@ -1,18 +1,574 @@
-use crate::{Statement, Program};
+use crate::{Statement, StatementKind, Expression, ExpressionKind, Program, Param, AttributeGroup, StringPart, PropertyHookBody, ast::Arg};
 
+/// A read-only pass over the AST. Each hook walks the node's children by default, so an
+/// implementation only needs to override the hooks it's interested in, calling the matching
+/// `walk_*` function if it still wants to descend into the node.
 pub trait Visitor {
-    fn visit(&mut self, statement: &Statement);
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program);
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        walk_expression(self, expression);
+    }
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    walk_block(visitor, program);
+}
+
+fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &[Statement]) {
+    for statement in block {
+        visitor.visit_statement(statement);
+    }
+}
+
+fn walk_optional<V: Visitor + ?Sized>(visitor: &mut V, expression: &Option<Expression>) {
+    if let Some(expression) = expression {
+        visitor.visit_expression(expression);
+    }
+}
+
+fn walk_args<V: Visitor + ?Sized>(visitor: &mut V, args: &[Arg]) {
+    for arg in args {
+        visitor.visit_expression(&arg.value);
+    }
+}
+
+fn walk_params<V: Visitor + ?Sized>(visitor: &mut V, params: &[Param]) {
+    for param in params {
+        walk_attributes(visitor, &param.attributes);
+        visitor.visit_expression(&param.name);
+        walk_optional(visitor, &param.default);
+    }
+}
+
+fn walk_attributes<V: Visitor + ?Sized>(visitor: &mut V, attributes: &[AttributeGroup]) {
+    for attribute in attributes.iter().flat_map(|group| &group.members) {
+        walk_args(visitor, &attribute.args);
+    }
+}
+
+fn walk_string_parts<V: Visitor + ?Sized>(visitor: &mut V, parts: &[StringPart]) {
+    for part in parts {
+        if let StringPart::Expr(expression) = part {
+            visitor.visit_expression(expression);
+        }
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &Statement) {
+    match &statement.kind {
+        StatementKind::Static { vars } => {
+            for var in vars {
+                visitor.visit_expression(&var.var);
+                walk_optional(visitor, &var.default);
+            }
+        },
+        StatementKind::Global { vars } => {
+            for var in vars {
+                visitor.visit_expression(var);
+            }
+        },
+        StatementKind::Const { constants, .. } => {
+            for constant in constants {
+                visitor.visit_expression(&constant.value);
+            }
+        },
+        StatementKind::While { condition, body } => {
+            visitor.visit_expression(condition);
+            walk_block(visitor, body);
+        },
+        StatementKind::For { init, condition, r#loop, then } => {
+            walk_optional(visitor, init);
+            walk_optional(visitor, condition);
+            walk_optional(visitor, r#loop);
+            walk_block(visitor, then);
+        },
+        StatementKind::Foreach { expr, key_var, value_var, body, .. } => {
+            visitor.visit_expression(expr);
+            walk_optional(visitor, key_var);
+            visitor.visit_expression(value_var);
+            walk_block(visitor, body);
+        },
+        StatementKind::Var { value, attributes, .. } => {
+            walk_attributes(visitor, attributes);
+            walk_optional(visitor, value);
+        },
+        StatementKind::Property { value, hooks, attributes, .. } => {
+            walk_attributes(visitor, attributes);
+            walk_optional(visitor, value);
+
+            for hook in hooks {
+                walk_attributes(visitor, &hook.attributes);
+                walk_params(visitor, &hook.params);
+
+                match &hook.body {
+                    Some(PropertyHookBody::Expression(expression)) => visitor.visit_expression(expression),
+                    Some(PropertyHookBody::Block(block)) => walk_block(visitor, block),
+                    None => {},
+                }
+            }
+        },
+        StatementKind::Constant { value, attributes, .. } => {
+            walk_attributes(visitor, attributes);
+            visitor.visit_expression(value);
+        },
+        StatementKind::Function { params, body, attributes, .. } | StatementKind::Method { params, body, attributes, .. } => {
+            walk_attributes(visitor, attributes);
+            walk_params(visitor, params);
+            walk_block(visitor, body);
+        },
+        StatementKind::Class { body, attributes, .. } | StatementKind::Trait { body, attributes, .. } |
+        StatementKind::Interface { body, attributes, .. } | StatementKind::Enum { body, attributes, .. } => {
+            walk_attributes(visitor, attributes);
+            walk_block(visitor, body);
+        },
+        StatementKind::EnumCase { value, attributes, .. } => {
+            walk_attributes(visitor, attributes);
+            walk_optional(visitor, value);
+        },
+        StatementKind::If { condition, then, else_ifs, r#else } => {
+            visitor.visit_expression(condition);
+            walk_block(visitor, then);
+
+            for else_if in else_ifs {
+                visitor.visit_expression(&else_if.condition);
+                walk_block(visitor, &else_if.body);
+            }
+
+            if let Some(r#else) = r#else {
+                walk_block(visitor, r#else);
+            }
+        },
+        StatementKind::Return { value } => walk_optional(visitor, value),
+        StatementKind::Switch { condition, cases } => {
+            visitor.visit_expression(condition);
+
+            for case in cases {
+                walk_optional(visitor, &case.condition);
+                walk_block(visitor, &case.body);
+            }
+        },
+        StatementKind::Break { num } | StatementKind::Continue { num } => walk_optional(visitor, num),
+        StatementKind::Echo { values } => {
+            for value in values {
+                visitor.visit_expression(value);
+            }
+        },
+        StatementKind::Expression { expr } => visitor.visit_expression(expr),
+        StatementKind::Namespace { body, .. } => walk_block(visitor, body),
+        StatementKind::Try { body, catches, finally } => {
+            walk_block(visitor, body);
+
+            for catch in catches {
+                visitor.visit_expression(&catch.var);
+                walk_block(visitor, &catch.body);
+            }
+
+            if let Some(finally) = finally {
+                walk_block(visitor, finally);
+            }
+        },
+        StatementKind::InlineHtml(_) | StatementKind::TraitUse { .. } | StatementKind::Use { .. } | StatementKind::Comment { .. } |
+        StatementKind::HaltCompiler { .. } | StatementKind::Noop | StatementKind::Error => {},
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &Expression) {
+    match &expression.kind {
+        ExpressionKind::PreIncrement { value } | ExpressionKind::PostIncrement { value } | ExpressionKind::PreDecrement { value } |
+        ExpressionKind::PostDecrement { value } | ExpressionKind::Dynamic { value } | ExpressionKind::BooleanNot { value } |
+        ExpressionKind::Throw { value } | ExpressionKind::Yield { value } | ExpressionKind::Negate { value } |
+        ExpressionKind::UnaryPlus { value } | ExpressionKind::BitwiseNot { value } | ExpressionKind::ErrorSuppress { value } |
+        ExpressionKind::Print { value } | ExpressionKind::Cast { value, .. } => visitor.visit_expression(value),
+        ExpressionKind::Clone { target } | ExpressionKind::ConstFetch { target, .. } => visitor.visit_expression(target),
+        ExpressionKind::Include { path } | ExpressionKind::IncludeOnce { path } | ExpressionKind::Require { path } |
+        ExpressionKind::RequireOnce { path } => visitor.visit_expression(path),
+        ExpressionKind::Infix { lhs, rhs, .. } | ExpressionKind::Coalesce { lhs, rhs } | ExpressionKind::Instanceof { lhs, rhs } => {
+            visitor.visit_expression(lhs);
+            visitor.visit_expression(rhs);
+        },
+        ExpressionKind::Assign { var, value } | ExpressionKind::CoalesceAssign { var, value } | ExpressionKind::AssignRef { var, value } => {
+            visitor.visit_expression(var);
+            visitor.visit_expression(value);
+        },
+        ExpressionKind::Call { target, args } | ExpressionKind::New { target, args } | ExpressionKind::StaticMethodCall { target, args, .. } => {
+            visitor.visit_expression(target);
+            walk_args(visitor, args);
+        },
+        ExpressionKind::MethodCall { target, method, args } | ExpressionKind::NullsafeMethodCall { target, method, args } |
+        ExpressionKind::DynamicStaticMethodCall { target, method, args } => {
+            visitor.visit_expression(target);
+            visitor.visit_expression(method);
+            walk_args(visitor, args);
+        },
+        ExpressionKind::PropertyFetch { target, property } | ExpressionKind::NullsafePropertyFetch { target, property } |
+        ExpressionKind::StaticPropertyFetch { target, property } => {
+            visitor.visit_expression(target);
+            visitor.visit_expression(property);
+        },
+        ExpressionKind::DynamicConstFetch { target, constant } => {
+            visitor.visit_expression(target);
+            visitor.visit_expression(constant);
+        },
+        ExpressionKind::Array { items } => {
+            for item in items {
+                walk_optional(visitor, &item.key);
+                visitor.visit_expression(&item.value);
+            }
+        },
+        ExpressionKind::Closure { params, uses, body, attributes, .. } => {
+            walk_attributes(visitor, attributes);
+            walk_params(visitor, params);
+
+            for r#use in uses {
+                visitor.visit_expression(&r#use.var);
+            }
+
+            walk_block(visitor, body);
+        },
+        ExpressionKind::ArrowFunction { params, expr, attributes, .. } => {
+            walk_attributes(visitor, attributes);
+            walk_params(visitor, params);
+            visitor.visit_expression(expr);
+        },
+        ExpressionKind::InterpolatedString { parts } | ExpressionKind::Heredoc { parts, .. } => walk_string_parts(visitor, parts),
+        ExpressionKind::AnonymousClass { body, attributes, .. } => {
+            walk_attributes(visitor, attributes);
+            walk_block(visitor, body);
+        },
+        ExpressionKind::ArrayIndex { array, index } => {
+            visitor.visit_expression(array);
+
+            if let Some(index) = index {
+                visitor.visit_expression(index);
+            }
+        },
+        ExpressionKind::Ternary { condition, then, r#else } => {
+            visitor.visit_expression(condition);
+
+            if let Some(then) = then {
+                visitor.visit_expression(then);
+            }
+
+            visitor.visit_expression(r#else);
+        },
+        ExpressionKind::Match { condition, arms } => {
+            visitor.visit_expression(condition);
+
+            for arm in arms {
+                for condition in arm.conditions.iter().flatten() {
+                    visitor.visit_expression(condition);
+                }
+
+                visitor.visit_expression(&arm.body);
+            }
+        },
+        ExpressionKind::Exit { value } => {
+            if let Some(value) = value {
+                visitor.visit_expression(value);
+            }
+        },
+        ExpressionKind::Static | ExpressionKind::Int { .. } | ExpressionKind::Float { .. } | ExpressionKind::Variable { .. } |
+        ExpressionKind::Identifier { .. } | ExpressionKind::ConstantString { .. } | ExpressionKind::Nowdoc { .. } |
+        ExpressionKind::Bool { .. } | ExpressionKind::Null | ExpressionKind::MagicConst { .. } | ExpressionKind::Error => {},
+    }
+}
+
+/// A pass that can rewrite the AST in place. Nodes can be modified, or replaced entirely by
+/// assigning to them, and the `walk_*_mut` functions descend into a node's children.
+pub trait VisitMut {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program);
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement);
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+    }
+
+    /// Run the pass over a whole program, returning the transformed program.
+    fn transform(&mut self, mut program: Program) -> Program {
+        self.visit_program_mut(&mut program);
+
+        program
+    }
+}
+
+pub fn walk_program_mut<V: VisitMut + ?Sized>(visitor: &mut V, program: &mut Program) {
+    walk_block_mut(visitor, program);
+}
+
+fn walk_block_mut<V: VisitMut + ?Sized>(visitor: &mut V, block: &mut [Statement]) {
+    for statement in block {
+        visitor.visit_statement_mut(statement);
+    }
+}
+
+fn walk_optional_mut<V: VisitMut + ?Sized>(visitor: &mut V, expression: &mut Option<Expression>) {
+    if let Some(expression) = expression {
+        visitor.visit_expression_mut(expression);
+    }
+}
+
+fn walk_args_mut<V: VisitMut + ?Sized>(visitor: &mut V, args: &mut [Arg]) {
+    for arg in args {
+        visitor.visit_expression_mut(&mut arg.value);
+    }
+}
 
-    fn traverse(&mut self, ast: Program) {
-        for statement in &ast {
-            self.visit(statement);
+fn walk_params_mut<V: VisitMut + ?Sized>(visitor: &mut V, params: &mut [Param]) {
+    for param in params {
+        walk_attributes_mut(visitor, &mut param.attributes);
+        visitor.visit_expression_mut(&mut param.name);
+        walk_optional_mut(visitor, &mut param.default);
+    }
+}
+
+fn walk_attributes_mut<V: VisitMut + ?Sized>(visitor: &mut V, attributes: &mut [AttributeGroup]) {
+    for attribute in attributes.iter_mut().flat_map(|group| &mut group.members) {
+        walk_args_mut(visitor, &mut attribute.args);
+    }
+}
+
+fn walk_string_parts_mut<V: VisitMut + ?Sized>(visitor: &mut V, parts: &mut [StringPart]) {
+    for part in parts {
+        if let StringPart::Expr(expression) = part {
+            visitor.visit_expression_mut(expression);
         }
     }
 }
 
+pub fn walk_statement_mut<V: VisitMut + ?Sized>(visitor: &mut V, statement: &mut Statement) {
+    match &mut statement.kind {
+        StatementKind::Static { vars } => {
+            for var in vars {
+                visitor.visit_expression_mut(&mut var.var);
+                walk_optional_mut(visitor, &mut var.default);
+            }
+        },
+        StatementKind::Global { vars } => {
+            for var in vars {
+                visitor.visit_expression_mut(var);
+            }
+        },
+        StatementKind::Const { constants, .. } => {
+            for constant in constants {
+                visitor.visit_expression_mut(&mut constant.value);
+            }
+        },
+        StatementKind::While { condition, body } => {
+            visitor.visit_expression_mut(condition);
+            walk_block_mut(visitor, body);
+        },
+        StatementKind::For { init, condition, r#loop, then } => {
+            walk_optional_mut(visitor, init);
+            walk_optional_mut(visitor, condition);
+            walk_optional_mut(visitor, r#loop);
+            walk_block_mut(visitor, then);
+        },
+        StatementKind::Foreach { expr, key_var, value_var, body, .. } => {
+            visitor.visit_expression_mut(expr);
+            walk_optional_mut(visitor, key_var);
+            visitor.visit_expression_mut(value_var);
+            walk_block_mut(visitor, body);
+        },
+        StatementKind::Var { value, attributes, .. } => {
+            walk_attributes_mut(visitor, attributes);
+            walk_optional_mut(visitor, value);
+        },
+        StatementKind::Property { value, hooks, attributes, .. } => {
+            walk_attributes_mut(visitor, attributes);
+            walk_optional_mut(visitor, value);
+
+            for hook in hooks {
+                walk_attributes_mut(visitor, &mut hook.attributes);
+                walk_params_mut(visitor, &mut hook.params);
+
+                match &mut hook.body {
+                    Some(PropertyHookBody::Expression(expression)) => visitor.visit_expression_mut(expression),
+                    Some(PropertyHookBody::Block(block)) => walk_block_mut(visitor, block),
+                    None => {},
+                }
+            }
+        },
+        StatementKind::Constant { value, attributes, .. } => {
+            walk_attributes_mut(visitor, attributes);
+            visitor.visit_expression_mut(value);
+        },
+        StatementKind::Function { params, body, attributes, .. } | StatementKind::Method { params, body, attributes, .. } => {
+            walk_attributes_mut(visitor, attributes);
+            walk_params_mut(visitor, params);
+            walk_block_mut(visitor, body);
+        },
+        StatementKind::Class { body, attributes, .. } | StatementKind::Trait { body, attributes, .. } |
+        StatementKind::Interface { body, attributes, .. } | StatementKind::Enum { body, attributes, .. } => {
+            walk_attributes_mut(visitor, attributes);
+            walk_block_mut(visitor, body);
+        },
+        StatementKind::EnumCase { value, attributes, .. } => {
+            walk_attributes_mut(visitor, attributes);
+            walk_optional_mut(visitor, value);
+        },
+        StatementKind::If { condition, then, else_ifs, r#else } => {
+            visitor.visit_expression_mut(condition);
+            walk_block_mut(visitor, then);
+
+            for else_if in else_ifs {
+                visitor.visit_expression_mut(&mut else_if.condition);
+                walk_block_mut(visitor, &mut else_if.body);
+            }
+
+            if let Some(r#else) = r#else {
+                walk_block_mut(visitor, r#else);
+            }
+        },
+        StatementKind::Return { value } => walk_optional_mut(visitor, value),
+        StatementKind::Switch { condition, cases } => {
+            visitor.visit_expression_mut(condition);
+
+            for case in cases {
+                walk_optional_mut(visitor, &mut case.condition);
+                walk_block_mut(visitor, &mut case.body);
+            }
+        },
+        StatementKind::Break { num } | StatementKind::Continue { num } => walk_optional_mut(visitor, num),
+        StatementKind::Echo { values } => {
+            for value in values {
+                visitor.visit_expression_mut(value);
+            }
+        },
+        StatementKind::Expression { expr } => visitor.visit_expression_mut(expr),
+        StatementKind::Namespace { body, .. } => walk_block_mut(visitor, body),
+        StatementKind::Try { body, catches, finally } => {
+            walk_block_mut(visitor, body);
+
+            for catch in catches {
+                visitor.visit_expression_mut(&mut catch.var);
+                walk_block_mut(visitor, &mut catch.body);
+            }
+
+            if let Some(finally) = finally {
+                walk_block_mut(visitor, finally);
+            }
+        },
+        StatementKind::InlineHtml(_) | StatementKind::TraitUse { .. } | StatementKind::Use { .. } | StatementKind::Comment { .. } |
+        StatementKind::HaltCompiler { .. } | StatementKind::Noop | StatementKind::Error => {},
+    }
+}
+
+pub fn walk_expression_mut<V: VisitMut + ?Sized>(visitor: &mut V, expression: &mut Expression) {
+    match &mut expression.kind {
+        ExpressionKind::PreIncrement { value } | ExpressionKind::PostIncrement { value } | ExpressionKind::PreDecrement { value } |
+        ExpressionKind::PostDecrement { value } | ExpressionKind::Dynamic { value } | ExpressionKind::BooleanNot { value } |
+        ExpressionKind::Throw { value } | ExpressionKind::Yield { value } | ExpressionKind::Negate { value } |
+        ExpressionKind::UnaryPlus { value } | ExpressionKind::BitwiseNot { value } | ExpressionKind::ErrorSuppress { value } |
+        ExpressionKind::Print { value } | ExpressionKind::Cast { value, .. } => visitor.visit_expression_mut(value),
+        ExpressionKind::Clone { target } | ExpressionKind::ConstFetch { target, .. } => visitor.visit_expression_mut(target),
+        ExpressionKind::Include { path } | ExpressionKind::IncludeOnce { path } | ExpressionKind::Require { path } |
+        ExpressionKind::RequireOnce { path } => visitor.visit_expression_mut(path),
+        ExpressionKind::Infix { lhs, rhs, .. } | ExpressionKind::Coalesce { lhs, rhs } | ExpressionKind::Instanceof { lhs, rhs } => {
+            visitor.visit_expression_mut(lhs);
+            visitor.visit_expression_mut(rhs);
+        },
+        ExpressionKind::Assign { var, value } | ExpressionKind::CoalesceAssign { var, value } | ExpressionKind::AssignRef { var, value } => {
+            visitor.visit_expression_mut(var);
+            visitor.visit_expression_mut(value);
+        },
+        ExpressionKind::Call { target, args } | ExpressionKind::New { target, args } | ExpressionKind::StaticMethodCall { target, args, .. } => {
+            visitor.visit_expression_mut(target);
+            walk_args_mut(visitor, args);
+        },
+        ExpressionKind::MethodCall { target, method, args } | ExpressionKind::NullsafeMethodCall { target, method, args } |
+        ExpressionKind::DynamicStaticMethodCall { target, method, args } => {
+            visitor.visit_expression_mut(target);
+            visitor.visit_expression_mut(method);
+            walk_args_mut(visitor, args);
+        },
+        ExpressionKind::PropertyFetch { target, property } | ExpressionKind::NullsafePropertyFetch { target, property } |
+        ExpressionKind::StaticPropertyFetch { target, property } => {
+            visitor.visit_expression_mut(target);
+            visitor.visit_expression_mut(property);
+        },
+        ExpressionKind::DynamicConstFetch { target, constant } => {
+            visitor.visit_expression_mut(target);
+            visitor.visit_expression_mut(constant);
+        },
+        ExpressionKind::Array { items } => {
+            for item in items {
+                walk_optional_mut(visitor, &mut item.key);
+                visitor.visit_expression_mut(&mut item.value);
+            }
+        },
+        ExpressionKind::Closure { params, uses, body, attributes, .. } => {
+            walk_attributes_mut(visitor, attributes);
+            walk_params_mut(visitor, params);
+
+            for r#use in uses {
+                visitor.visit_expression_mut(&mut r#use.var);
+            }
+
+            walk_block_mut(visitor, body);
+        },
+        ExpressionKind::ArrowFunction { params, expr, attributes, .. } => {
+            walk_attributes_mut(visitor, attributes);
+            walk_params_mut(visitor, params);
+            visitor.visit_expression_mut(expr);
+        },
+        ExpressionKind::InterpolatedString { parts } | ExpressionKind::Heredoc { parts, .. } => walk_string_parts_mut(visitor, parts),
+        ExpressionKind::AnonymousClass { body, attributes, .. } => {
+            walk_attributes_mut(visitor, attributes);
+            walk_block_mut(visitor, body);
+        },
+        ExpressionKind::ArrayIndex { array, index } => {
+            visitor.visit_expression_mut(array);
+
+            if let Some(index) = index {
+                visitor.visit_expression_mut(index);
+            }
+        },
+        ExpressionKind::Ternary { condition, then, r#else } => {
+            visitor.visit_expression_mut(condition);
+
+            if let Some(then) = then {
+                visitor.visit_expression_mut(then);
+            }
+
+            visitor.visit_expression_mut(r#else);
+        },
+        ExpressionKind::Match { condition, arms } => {
+            visitor.visit_expression_mut(condition);
+
+            for arm in arms {
+                for condition in arm.conditions.iter_mut().flatten() {
+                    visitor.visit_expression_mut(condition);
+                }
+
+                visitor.visit_expression_mut(&mut arm.body);
+            }
+        },
+        ExpressionKind::Exit { value } => {
+            if let Some(value) = value {
+                visitor.visit_expression_mut(value);
+            }
+        },
+        ExpressionKind::Static | ExpressionKind::Int { .. } | ExpressionKind::Float { .. } | ExpressionKind::Variable { .. } |
+        ExpressionKind::Identifier { .. } | ExpressionKind::ConstantString { .. } | ExpressionKind::Nowdoc { .. } |
+        ExpressionKind::Bool { .. } | ExpressionKind::Null | ExpressionKind::MagicConst { .. } | ExpressionKind::Error => {},
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{Parser, Program, Visitor};
+    use crate::{Parser, Program, Visitor, VisitMut, Statement, StatementKind, Expression, ExpressionKind, walk_statement, walk_expression, walk_statement_mut, walk_expression_mut};
     use trunk_lexer::Lexer;
 
     struct CountVisitor {
@@ -20,26 +576,131 @@ mod tests {
     }
 
     impl Visitor for CountVisitor {
-        fn visit(&mut self, statement: &crate::Statement) {
+        fn visit_statement(&mut self, _: &Statement) {
             self.count += 1;
         }
     }
 
+    #[derive(Default)]
+    struct VariableVisitor {
+        statements: usize,
+        variables: Vec<String>,
+    }
+
+    impl Visitor for VariableVisitor {
+        fn visit_statement(&mut self, statement: &Statement) {
+            self.statements += 1;
+
+            walk_statement(self, statement);
+        }
+
+        fn visit_expression(&mut self, expression: &Expression) {
+            if let ExpressionKind::Variable { name } = &expression.kind {
+                self.variables.push(name.clone());
+            }
+
+            walk_expression(self, expression);
+        }
+    }
+
+    struct InlineConstant;
+
+    impl VisitMut for InlineConstant {
+        fn visit_expression_mut(&mut self, expression: &mut Expression) {
+            match &expression.kind {
+                ExpressionKind::Identifier { name } if name == "BAR" => *expression = ExpressionKind::Int { i: 1 }.into(),
+                _ => walk_expression_mut(self, expression),
+            }
+        }
+    }
+
+    struct DesugarElseIf;
+
+    impl VisitMut for DesugarElseIf {
+        fn visit_statement_mut(&mut self, statement: &mut Statement) {
+            if let StatementKind::If { else_ifs, r#else, .. } = &mut statement.kind {
+                for else_if in std::mem::take(else_ifs).into_iter().rev() {
+                    *r#else = Some(vec![StatementKind::If { condition: else_if.condition, then: else_if.body, else_ifs: vec![], r#else: r#else.take() }.into()]);
+                }
+            }
+
+            walk_statement_mut(self, statement);
+        }
+    }
+
+    #[test]
+    fn it_can_rewrite_an_ast() {
+        let ast = InlineConstant.transform(get_ast("<?php $a = [BAR, fn () => BAR + BAZ];"));
+        assert_eq!(ast, get_ast("<?php $a = [1, fn () => 1 + BAZ];"));
+
+        let ast = DesugarElseIf.transform(get_ast("<?php if ($a) { a(); } elseif ($b) { b(); } elseif ($c) { c(); } else { d(); }"));
+        assert_eq!(ast, get_ast("<?php if ($a) { a(); } else { if ($b) { b(); } else { if ($c) { c(); } else { d(); } } }"));
+    }
+
+    #[cfg(feature = "serde")]
+    struct AnnotateVariables;
+
+    #[cfg(feature = "serde")]
+    impl VisitMut for AnnotateVariables {
+        fn visit_expression_mut(&mut self, expression: &mut Expression) {
+            if let ExpressionKind::Variable { name } = &expression.kind {
+                expression.meta.insert("type", if name == "a" { "int" } else { "mixed" });
+            }
+
+            walk_expression_mut(self, expression);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn it_can_annotate_an_ast() {
+        let ast = AnnotateVariables.transform(get_ast("<?php $a = $b;"));
+
+        match &ast[0].kind {
+            StatementKind::Expression { expr: Expression { kind: ExpressionKind::Assign { var, value }, meta, .. } } => {
+                assert_eq!(var.meta.get("type"), Some(&"int".into()));
+                assert_eq!(value.meta.get("type"), Some(&"mixed".into()));
+                assert!(meta.is_empty());
+            },
+            _ => panic!("expected an assignment"),
+        }
+
+        // Metadata doesn't affect equality, and is only serialized when there is some.
+        assert_eq!(ast, get_ast("<?php $a = $b;"));
+        assert!(serde_json::to_string(&ast).unwrap().contains(r#""meta":{"type":"int"}"#));
+        assert!(! serde_json::to_string(&get_ast("<?php $a = $b;")).unwrap().contains("meta"));
+    }
+
     #[test]
     fn it_can_walk_an_ast() {
         let ast = get_ast("<?php foo();");
         let mut visitor = CountVisitor { count: 0 };
-        visitor.traverse(ast);
+        visitor.visit_program(&ast);
         assert_eq!(visitor.count, 1);
     }
 
+    #[test]
+    fn it_walks_nested_nodes() {
+        let ast = get_ast("<?php
+            function foo($a = BAR) {
+                if ($a) {
+                    return array_map(fn ($b) => $b + $c, [$d, 'e' => \"{$f}\"]);
+                }
+            }
+        ");
+
+        let mut visitor = VariableVisitor::default();
+        visitor.visit_program(&ast);
+
+        assert_eq!(visitor.statements, 3);
+        assert_eq!(visitor.variables, ["a", "a", "b", "b", "c", "d", "f"]);
+    }
+
     fn get_ast(source: &str) -> Program {
         let mut lexer = Lexer::new(None);
         let tokens = lexer.tokenize(source).unwrap();
 
         let mut parser = Parser::new(None);
-        let ast = parser.parse(tokens).unwrap();
-
-        ast
+        parser.parse(&tokens).unwrap()
     }
-}
\ No newline at end of file
+}
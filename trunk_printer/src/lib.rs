@@ -0,0 +1,1815 @@
+use trunk_lexer::{Lexer, TokenKind};
+use trunk_parser::{
+    walk_statement, Visitor, Span, Statement, StatementKind, Expression, ExpressionKind, Program, Param, Type, InfixOp, AttributeGroup, StringPart, Arg, UseKind, CastKind,
+    MagicConst, BackedEnumType, ClassFlag, MethodFlag, PropertyFlag, ConstFlag, Identifier, Precedence, Associativity,
+    PropertyHook, PropertyHookBody,
+};
+
+/// Print a program as PHP source code. The output always starts with an open tag and uses a
+/// consistent layout, regardless of how the original source was formatted.
+pub fn print(program: &Program) -> String {
+    print_with_style(program, &Style::default())
+}
+
+/// Print a program as PHP source code, laid out in the given style.
+pub fn print_with_style(program: &Program, style: &Style) -> String {
+    let mut printer = Printer { style: style.clone(), ..Printer::default() };
+
+    printer.output.push_str("<?php\n");
+    printer.statements(program);
+    printer.end(program);
+
+    printer.output
+}
+
+/// Reformat a program that was parsed from `source`. Unlike [`print`], this keeps single blank lines
+/// between statements and comments at the end of a line where they were in the source, which is
+/// what a formatter wants. The program should be parsed with comments kept, or they'll be lost.
+///
+/// The AST has nowhere to keep a comment inside an expression, so a statement with one is left as
+/// it was in the source.
+pub fn format(program: &Program, source: &str, style: &Style) -> String {
+    let comments = Lexer::new(None).tokenize(source)
+        .map(|tokens| tokens.into_iter().filter(|token| matches!(token.kind, TokenKind::Comment(_) | TokenKind::DocComment(_))).map(|token| token.span).collect())
+        .unwrap_or_default();
+
+    let mut printer = Printer { style: style.clone(), source: Some(source), comments, ..Printer::default() };
+
+    printer.output.push_str("<?php\n");
+    printer.statements(program);
+    printer.end(program);
+
+    printer.output
+}
+
+/// Print a program as compactly as possible, for shipping as a single file or comparing printed
+/// output. Whitespace is only kept where tokens would otherwise run together, comments are dropped,
+/// and shorter equivalent syntax is used where it's safe, such as leaving out `public` on methods
+/// and printing heredocs as ordinary strings.
+pub fn minify(program: &Program) -> String {
+    let mut printer = Printer { compact: true, ..Printer::default() };
+
+    printer.output.push_str("<?php ");
+    printer.statements(program);
+
+    printer.output
+}
+
+/// How printed code is laid out. The default follows PSR-12.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Style {
+    /// What each level of indentation is made of.
+    pub indent: String,
+    /// Where the opening brace of a function, class or other declaration goes.
+    pub braces: BraceStyle,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            indent: "    ".into(),
+            braces: BraceStyle::NextLine,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BraceStyle {
+    /// On a line of its own, as PSR-12 requires.
+    NextLine,
+    /// At the end of the line, like control structures.
+    SameLine,
+}
+
+/// Print a single expression, without an open tag or a trailing semicolon.
+pub fn print_expression(expression: &Expression) -> String {
+    let mut printer = Printer::default();
+    printer.expression(expression);
+
+    printer.output
+}
+
+/// Strip a program down to the declarations that make up its API, as a stub for IDEs and other
+/// tools to index: namespaces, imports, constants, and functions, classes, interfaces, traits and
+/// enums with their members, but with every function and method body emptied. Everything else,
+/// including declarations made conditionally, such as in an `if`, is left out.
+pub fn stub(program: &Program) -> Program {
+    program.iter().filter_map(stub_statement).collect()
+}
+
+fn stub_statement(statement: &Statement) -> Option<Statement> {
+    let mut stubbed = statement.clone();
+
+    match &mut stubbed.kind {
+        StatementKind::Namespace { body, .. } => {
+            let was_braced = ! body.is_empty();
+            *body = stub(body);
+
+            // A braced namespace left empty would print as an unbraced one.
+            if was_braced && body.is_empty() {
+                return None;
+            }
+        },
+        StatementKind::Function { body, .. } | StatementKind::Method { body, .. } => body.clear(),
+        StatementKind::Class { body, .. } | StatementKind::Trait { body, .. } | StatementKind::Interface { body, .. } |
+        StatementKind::Enum { body, .. } => *body = stub(body),
+        StatementKind::Use { .. } | StatementKind::Const { .. } | StatementKind::Constant { .. } | StatementKind::Property { .. } |
+        StatementKind::Var { .. } | StatementKind::TraitUse { .. } | StatementKind::EnumCase { .. } => {},
+        _ => return None,
+    }
+
+    Some(stubbed)
+}
+
+#[derive(Default)]
+struct Printer<'a> {
+    output: String,
+    indent: usize,
+    in_interface: bool,
+    style: Style,
+    /// The source being reformatted, if any, to look at the whitespace between statements.
+    source: Option<&'a str>,
+    /// Where the comments in the source being reformatted are.
+    comments: Vec<Span>,
+    /// Whether to leave out whitespace and comments, for `minify`.
+    compact: bool,
+    /// Whether whitespace was left out since the last thing written in compact mode.
+    space: bool,
+}
+
+impl<'a> Printer<'a> {
+    fn write(&mut self, s: &str) {
+        if ! self.compact {
+            return self.output.push_str(s);
+        }
+
+        // Spaces are only kept where the characters either side would run together into
+        // different tokens.
+        for c in s.chars() {
+            if c == ' ' {
+                self.space = true;
+                continue;
+            }
+
+            if self.space && self.output.chars().next_back().is_some_and(|previous| runs_together(previous, c)) {
+                self.output.push(' ');
+            }
+
+            self.space = false;
+            self.output.push(c);
+        }
+    }
+
+    /// Write source text exactly as it is, such as inline HTML.
+    fn verbatim(&mut self, s: &str) {
+        self.space = false;
+        self.output.push_str(s);
+    }
+
+    /// End the output with a newline, unless it ends with the data after `__halt_compiler`, which
+    /// is kept exactly as it was.
+    fn end(&mut self, program: &Program) {
+        if ! matches!(program.last(), Some(Statement { kind: StatementKind::HaltCompiler { .. }, .. })) {
+            self.output.push('\n');
+        }
+    }
+
+    fn newline(&mut self) {
+        if self.compact {
+            self.space = true;
+            return;
+        }
+
+        self.output.push('\n');
+
+        for _ in 0..self.indent {
+            self.output.push_str(&self.style.indent);
+        }
+    }
+
+    fn separated<T>(&mut self, items: &[T], separator: &str, mut f: impl FnMut(&mut Self, &T)) {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.write(separator);
+            }
+
+            f(self, item);
+        }
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        let mut trailing = false;
+
+        for (i, statement) in statements.iter().enumerate() {
+            if self.compact {
+                if ! matches!(statement.kind, StatementKind::Comment { .. }) {
+                    self.statement(statement);
+                }
+
+                continue;
+            }
+
+            if i > 0 {
+                let previous = &statements[i - 1];
+                let between = self.source.and_then(|source| source.get(previous.span.end..statement.span.start));
+
+                // A comment on the same line as the statement before it stays there.
+                if matches!(statement.kind, StatementKind::Comment { .. }) && between.is_some_and(|between| ! between.contains('\n')) {
+                    self.write(" ");
+                    self.statement(statement);
+                    trailing = true;
+                    continue;
+                }
+
+                // Declarations are separated from their neighbours by a blank line, except from a
+                // comment just above them, and blank lines in the source are kept as one.
+                let comment_above = ! trailing && matches!(previous.kind, StatementKind::Comment { .. });
+
+                if (! comment_above && (is_declaration(previous) || is_declaration(statement)))
+                    || between.is_some_and(|between| between.matches('\n').count() > 1) {
+                    self.output.push('\n');
+                }
+            }
+
+            trailing = false;
+            self.newline();
+            self.statement(statement);
+        }
+    }
+
+    /// Whether a statement has a comment that printing it would drop, because it's somewhere other
+    /// than between the statements inside it, such as in an expression.
+    fn loses_comments(&self, statement: &Statement) -> bool {
+        let within = |span: &Span, outer: &Span| outer.start <= span.start && span.end <= outer.end;
+        let mut inside = self.comments.iter().filter(|comment| within(comment, &statement.span)).peekable();
+
+        if inside.peek().is_none() || matches!(statement.kind, StatementKind::Comment { .. }) {
+            return false;
+        }
+
+        let mut children = Children::default();
+        walk_statement(&mut children, statement);
+
+        // A declaration's doc comment comes just before it, outside of its span.
+        let doc_comments: Vec<&Span> = children.documented.iter()
+            .filter_map(|start| self.comments.iter().rfind(|comment| comment.end <= *start))
+            .collect();
+
+        inside.any(|comment| ! children.spans.iter().any(|child| within(comment, child)) && ! doc_comments.contains(&comment))
+    }
+
+    fn block(&mut self, block: &[Statement]) {
+        self.write("{");
+        self.indent += 1;
+        self.statements(block);
+        self.indent -= 1;
+        self.newline();
+        self.write("}");
+    }
+
+    /// Functions, classes and other declarations put their opening brace on its own line, unless the
+    /// style says otherwise.
+    fn declaration_block(&mut self, block: &[Statement]) {
+        match self.style.braces {
+            BraceStyle::NextLine => self.newline(),
+            BraceStyle::SameLine => self.write(" "),
+        }
+
+        self.block(block);
+    }
+
+    fn doc_comment(&mut self, doc_comment: &Option<String>) {
+        if self.compact {
+            return;
+        }
+
+        if let Some(doc_comment) = doc_comment {
+            self.write(doc_comment);
+            self.newline();
+        }
+    }
+
+    fn attributes(&mut self, attributes: &[AttributeGroup], inline: bool) {
+        for group in attributes {
+            self.write("#[");
+            self.separated(&group.members, ", ", |p, attribute| {
+                p.write(&attribute.name.name);
+
+                if ! attribute.args.is_empty() {
+                    p.args(&attribute.args);
+                }
+            });
+            self.write("]");
+
+            if inline {
+                self.write(" ");
+            } else {
+                self.newline();
+            }
+        }
+    }
+
+    fn names(&mut self, names: &[Identifier]) {
+        self.separated(names, ", ", |p, name| p.write(&name.name));
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        if let Some(text) = self.source.filter(|_| self.loses_comments(statement)) {
+            self.doc_comment(doc_comment(statement));
+            self.verbatim(&text.chars().skip(statement.span.start).take(statement.span.end - statement.span.start).collect::<String>());
+            return;
+        }
+
+        match &statement.kind {
+            StatementKind::InlineHtml(html) => {
+                self.write("?>");
+
+                // The newline straight after a close tag is swallowed, so one at the start of the
+                // HTML needs another in front of it.
+                if html.starts_with('\n') {
+                    self.verbatim("\n");
+                }
+
+                self.verbatim(html);
+                self.verbatim(if self.compact { "<?php " } else { "<?php" });
+            },
+            StatementKind::Static { vars } => {
+                self.write("static ");
+                self.separated(vars, ", ", |p, var| {
+                    p.expression(&var.var);
+
+                    if let Some(default) = &var.default {
+                        p.write(" = ");
+                        p.expression(default);
+                    }
+                });
+                self.write(";");
+            },
+            StatementKind::Global { vars } => {
+                self.write("global ");
+                self.separated(vars, ", ", |p, var| p.expression(var));
+                self.write(";");
+            },
+            StatementKind::Const { constants, doc_comment } => {
+                self.doc_comment(doc_comment);
+                self.write("const ");
+                self.separated(constants, ", ", |p, constant| {
+                    p.write(&constant.name.name);
+                    p.write(" = ");
+                    p.expression(&constant.value);
+                });
+                self.write(";");
+            },
+            StatementKind::While { condition, body } => {
+                self.write("while (");
+                self.expression(condition);
+                self.write(") ");
+                self.block(body);
+            },
+            StatementKind::For { init, condition, r#loop, then } => {
+                self.write("for (");
+                self.optional(init);
+                self.write(";");
+
+                if condition.is_some() {
+                    self.write(" ");
+                    self.optional(condition);
+                }
+
+                self.write(";");
+
+                if r#loop.is_some() {
+                    self.write(" ");
+                    self.optional(r#loop);
+                }
+
+                self.write(") ");
+                self.block(then);
+            },
+            StatementKind::Foreach { expr, by_ref, key_var, value_var, body } => {
+                self.write("foreach (");
+                self.expression(expr);
+                self.write(" as ");
+
+                if let Some(key_var) = key_var {
+                    self.expression(key_var);
+                    self.write(" => ");
+                }
+
+                if *by_ref {
+                    self.write("&");
+                }
+
+                self.expression(value_var);
+                self.write(") ");
+                self.block(body);
+            },
+            StatementKind::Var { var, value, r#type, attributes } => {
+                self.attributes(attributes, false);
+                self.write("var ");
+                self.property(var, value, r#type);
+                self.write(";");
+            },
+            StatementKind::Property { var, value, r#type, flags, hooks, attributes, doc_comment } => {
+                self.doc_comment(doc_comment);
+                self.attributes(attributes, false);
+
+                for flag in flags {
+                    self.write(property_flag(flag));
+                    self.write(" ");
+                }
+
+                self.property(var, value, r#type);
+
+                if hooks.is_empty() {
+                    self.write(";");
+                } else {
+                    self.hooks(hooks);
+                }
+            },
+            StatementKind::Constant { name, value, flags, attributes, doc_comment } => {
+                self.doc_comment(doc_comment);
+                self.attributes(attributes, false);
+
+                // Constants are public unless they say otherwise.
+                let compact = self.compact;
+
+                for flag in flags.iter().filter(|flag| ! compact || **flag != ConstFlag::Public) {
+                    self.write(const_flag(flag));
+                    self.write(" ");
+                }
+
+                self.write("const ");
+                self.write(&name.name);
+                self.write(" = ");
+                self.expression(value);
+                self.write(";");
+            },
+            StatementKind::Function { name, params, body, return_type, returns_by_ref, attributes, doc_comment } => {
+                self.doc_comment(doc_comment);
+                self.attributes(attributes, false);
+                self.signature(&name.name, params, return_type, *returns_by_ref);
+                self.declaration_block(body);
+            },
+            StatementKind::Class { name, extends, implements, body, flag, attributes, doc_comment } => {
+                self.doc_comment(doc_comment);
+                self.attributes(attributes, false);
+
+                match flag {
+                    Some(ClassFlag::Abstract) => self.write("abstract "),
+                    Some(ClassFlag::Final) => self.write("final "),
+                    None => {},
+                }
+
+                self.write("class ");
+                self.write(&name.name);
+                self.class_header(extends, implements);
+                self.declaration_block(body);
+            },
+            StatementKind::Trait { name, body, attributes } => {
+                self.attributes(attributes, false);
+                self.write("trait ");
+                self.write(&name.name);
+                self.declaration_block(body);
+            },
+            StatementKind::TraitUse { traits } => {
+                self.write("use ");
+                self.names(traits);
+                self.write(";");
+            },
+            StatementKind::Interface { name, extends, body, attributes } => {
+                self.attributes(attributes, false);
+                self.write("interface ");
+                self.write(&name.name);
+
+                if ! extends.is_empty() {
+                    self.write(" extends ");
+                    self.names(extends);
+                }
+
+                let in_interface = std::mem::replace(&mut self.in_interface, true);
+                self.declaration_block(body);
+                self.in_interface = in_interface;
+            },
+            StatementKind::Method { name, params, body, flags, return_type, returns_by_ref, attributes, doc_comment } => {
+                self.doc_comment(doc_comment);
+                self.attributes(attributes, false);
+
+                // As are methods.
+                let compact = self.compact;
+
+                for flag in flags.iter().filter(|flag| ! compact || **flag != MethodFlag::Public) {
+                    self.write(method_flag(flag));
+                    self.write(" ");
+                }
+
+                self.signature(&name.name, params, return_type, *returns_by_ref);
+
+                if self.in_interface || flags.contains(&MethodFlag::Abstract) {
+                    self.write(";");
+                } else {
+                    let in_interface = std::mem::replace(&mut self.in_interface, false);
+                    self.declaration_block(body);
+                    self.in_interface = in_interface;
+                }
+            },
+            StatementKind::If { condition, then, else_ifs, r#else } => {
+                self.write("if (");
+                self.expression(condition);
+                self.write(") ");
+                self.block(then);
+
+                for else_if in else_ifs {
+                    self.write(" elseif (");
+                    self.expression(&else_if.condition);
+                    self.write(") ");
+                    self.block(&else_if.body);
+                }
+
+                if let Some(r#else) = r#else {
+                    self.write(" else ");
+                    self.block(r#else);
+                }
+            },
+            StatementKind::Return { value } => {
+                self.write("return");
+
+                if let Some(value) = value {
+                    self.write(" ");
+                    self.expression(value);
+                }
+
+                self.write(";");
+            },
+            StatementKind::Switch { condition, cases } => {
+                self.write("switch (");
+                self.expression(condition);
+                self.write(") {");
+                self.indent += 1;
+
+                for case in cases {
+                    self.newline();
+
+                    match &case.condition {
+                        Some(condition) => {
+                            self.write("case ");
+                            self.expression(condition);
+                            self.write(":");
+                        },
+                        None => self.write("default:"),
+                    }
+
+                    self.indent += 1;
+                    self.statements(&case.body);
+                    self.indent -= 1;
+                }
+
+                self.indent -= 1;
+                self.newline();
+                self.write("}");
+            },
+            StatementKind::Break { num } | StatementKind::Continue { num } => {
+                self.write(if matches!(statement.kind, StatementKind::Break { .. }) { "break" } else { "continue" });
+
+                if let Some(num) = num {
+                    self.write(" ");
+                    self.expression(num);
+                }
+
+                self.write(";");
+            },
+            StatementKind::Echo { values } => {
+                self.write("echo ");
+                self.separated(values, ", ", |p, value| p.expression(value));
+                self.write(";");
+            },
+            StatementKind::Expression { expr } => {
+                self.expression(expr);
+                self.write(";");
+            },
+            StatementKind::Namespace { name, body } => {
+                self.write("namespace ");
+                self.write(name);
+
+                // An unbraced namespace doesn't own the statements that follow it.
+                if body.is_empty() {
+                    self.write(";");
+                } else {
+                    self.write(" ");
+                    self.block(body);
+                }
+            },
+            StatementKind::Use { uses, kind } => {
+                self.write(match kind {
+                    UseKind::Normal => "use ",
+                    UseKind::Function => "use function ",
+                    UseKind::Const => "use const ",
+                });
+                self.separated(uses, ", ", |p, r#use| {
+                    p.write(&r#use.name.name);
+
+                    if let Some(alias) = &r#use.alias {
+                        p.write(" as ");
+                        p.write(&alias.name);
+                    }
+                });
+                self.write(";");
+            },
+            StatementKind::Comment { comment } => self.write(comment),
+            StatementKind::Try { body, catches, finally } => {
+                self.write("try ");
+                self.block(body);
+
+                for catch in catches {
+                    self.write(" catch (");
+                    self.separated(&catch.types, " | ", |p, name| p.write(&name.name));
+                    self.write(" ");
+                    self.expression(&catch.var);
+                    self.write(") ");
+                    self.block(&catch.body);
+                }
+
+                if let Some(finally) = finally {
+                    self.write(" finally ");
+                    self.block(finally);
+                }
+            },
+            StatementKind::Enum { name, implements, backed_type, body, attributes } => {
+                self.attributes(attributes, false);
+                self.write("enum ");
+                self.write(&name.name);
+
+                match backed_type {
+                    Some(BackedEnumType::String) => self.write(": string"),
+                    Some(BackedEnumType::Int) => self.write(": int"),
+                    None => {},
+                }
+
+                if ! implements.is_empty() {
+                    self.write(" implements ");
+                    self.names(implements);
+                }
+
+                self.declaration_block(body);
+            },
+            StatementKind::EnumCase { name, value, attributes } => {
+                self.attributes(attributes, false);
+                self.write("case ");
+                self.write(&name.name);
+
+                if let Some(value) = value {
+                    self.write(" = ");
+                    self.expression(value);
+                }
+
+                self.write(";");
+            },
+            StatementKind::HaltCompiler { data, .. } => {
+                self.write("__halt_compiler();");
+                // Printed source is a string, so data that isn't UTF-8 can't be kept exactly.
+                self.verbatim(&String::from_utf8_lossy(data));
+            },
+            StatementKind::Noop => self.write(";"),
+            // The source of a statement that failed to parse isn't kept, so there's nothing to print.
+            StatementKind::Error => self.write("/* error */"),
+        }
+    }
+
+    fn property(&mut self, var: &str, value: &Option<Expression>, r#type: &Option<Type>) {
+        if let Some(r#type) = r#type {
+            self.r#type(r#type);
+            self.write(" ");
+        }
+
+        self.write("$");
+        self.write(var);
+
+        if let Some(value) = value {
+            self.write(" = ");
+            self.expression(value);
+        }
+    }
+
+    fn hooks(&mut self, hooks: &[PropertyHook]) {
+        self.write(" {");
+        self.indent += 1;
+
+        for hook in hooks {
+            self.newline();
+            self.attributes(&hook.attributes, false);
+
+            for flag in &hook.flags {
+                self.write(method_flag(flag));
+                self.write(" ");
+            }
+
+            if hook.by_ref {
+                self.write("&");
+            }
+
+            self.write(&hook.name.name);
+
+            if ! hook.params.is_empty() {
+                self.params(&hook.params);
+            }
+
+            match &hook.body {
+                Some(PropertyHookBody::Expression(expression)) => {
+                    self.write(" => ");
+                    self.expression(expression);
+                    self.write(";");
+                },
+                Some(PropertyHookBody::Block(block)) => {
+                    self.write(" ");
+                    self.block(block);
+                },
+                None => self.write(";"),
+            }
+        }
+
+        self.indent -= 1;
+        self.newline();
+        self.write("}");
+    }
+
+    fn class_header(&mut self, extends: &Option<Identifier>, implements: &[Identifier]) {
+        if let Some(extends) = extends {
+            self.write(" extends ");
+            self.write(&extends.name);
+        }
+
+        if ! implements.is_empty() {
+            self.write(" implements ");
+            self.names(implements);
+        }
+    }
+
+    fn signature(&mut self, name: &str, params: &[Param], return_type: &Option<Type>, returns_by_ref: bool) {
+        self.write("function ");
+
+        if returns_by_ref {
+            self.write("&");
+        }
+
+        self.write(name);
+        self.params(params);
+        self.return_type(return_type);
+    }
+
+    fn params(&mut self, params: &[Param]) {
+        self.write("(");
+        self.separated(params, ", ", |p, param| {
+            p.attributes(&param.attributes, true);
+
+            for flag in &param.flags {
+                p.write(property_flag(flag));
+                p.write(" ");
+            }
+
+            if let Some(r#type) = &param.r#type {
+                p.r#type(r#type);
+                p.write(" ");
+            }
+
+            if param.by_ref {
+                p.write("&");
+            }
+
+            if param.variadic {
+                p.write("...");
+            }
+
+            p.expression(&param.name);
+
+            if let Some(default) = &param.default {
+                p.write(" = ");
+                p.expression(default);
+            }
+        });
+        self.write(")");
+    }
+
+    fn return_type(&mut self, return_type: &Option<Type>) {
+        if let Some(return_type) = return_type {
+            self.write(": ");
+            self.r#type(return_type);
+        }
+    }
+
+    fn r#type(&mut self, r#type: &Type) {
+        match r#type {
+            Type::Plain(name) => self.write(name),
+            Type::Nullable(name) => {
+                self.write("?");
+                self.write(name);
+            },
+            Type::Union(types) => self.separated(types, "|", |p, r#type| match r#type {
+                Type::Intersection(_) => {
+                    p.write("(");
+                    p.r#type(r#type);
+                    p.write(")");
+                },
+                _ => p.r#type(r#type),
+            }),
+            Type::Intersection(types) => self.separated(types, "&", |p, r#type| p.r#type(r#type)),
+        }
+    }
+
+    fn args(&mut self, args: &[Arg]) {
+        self.write("(");
+        self.separated(args, ", ", |p, arg| {
+            if let Some(name) = &arg.name {
+                p.write(name);
+                p.write(": ");
+            }
+
+            if arg.unpack {
+                p.write("...");
+            }
+
+            p.expression(&arg.value);
+        });
+        self.write(")");
+    }
+
+    fn optional(&mut self, expression: &Option<Expression>) {
+        if let Some(expression) = expression {
+            self.expression(expression);
+        }
+    }
+
+    /// Print an operand, wrapping it in parentheses when the tree's shape can't be expressed
+    /// by precedence alone.
+    fn operand(&mut self, expression: &Expression, parenthesize: bool) {
+        if parenthesize {
+            self.write("(");
+            self.expression(expression);
+            self.write(")");
+        } else {
+            self.expression(expression);
+        }
+    }
+
+    /// Print the left-hand side of `->`, `::`, `[]` or a call.
+    fn target(&mut self, target: &Expression) {
+        let parenthesize = precedence(target) < Precedence::Access || matches!(target.kind,
+            ExpressionKind::Closure { .. } | ExpressionKind::AnonymousClass { .. } | ExpressionKind::Int { .. } | ExpressionKind::Float { .. }
+        );
+
+        self.operand(target, parenthesize);
+    }
+
+    fn prefix(&mut self, op: &str, value: &Expression, precedence: Precedence) {
+        self.write(op);
+
+        // `- -$a` would otherwise be printed as a decrement.
+        let repeated_sign = matches!(op, "-" | "+") && matches!(value.kind,
+            ExpressionKind::Negate { .. } | ExpressionKind::UnaryPlus { .. } | ExpressionKind::PreIncrement { .. } | ExpressionKind::PreDecrement { .. }
+        ) || self::precedence(value) == Precedence::Prefix && is_negative(value);
+
+        self.operand(value, self::precedence(value) < precedence || repeated_sign);
+    }
+
+    fn keyword(&mut self, keyword: &str, value: &Expression) {
+        self.write(keyword);
+        self.write(" ");
+        self.operand(value, precedence(value) < Precedence::Print);
+    }
+
+    fn binary(&mut self, lhs: &Expression, op: &str, rhs: &Expression, precedence: Precedence) {
+        let associativity = precedence.associativity();
+        let (lhs_precedence, rhs_precedence) = (self::precedence(lhs), self::precedence(rhs));
+
+        self.operand(lhs, lhs_precedence < precedence || lhs_precedence == precedence && associativity != Associativity::Left);
+        self.write(" ");
+        self.write(op);
+        self.write(" ");
+        self.operand(rhs, rhs_precedence < precedence || rhs_precedence == precedence && associativity != Associativity::Right);
+    }
+
+    fn member(&mut self, member: &Expression) {
+        match &member.kind {
+            ExpressionKind::Identifier { name } => self.write(name),
+            ExpressionKind::Dynamic { value } if matches!(value.kind, ExpressionKind::Variable { .. }) => self.expression(value),
+            ExpressionKind::Dynamic { value } => {
+                self.write("{");
+                self.expression(value);
+                self.write("}");
+            },
+            _ => self.expression(member),
+        }
+    }
+
+    fn string_parts(&mut self, parts: &[StringPart], escape: &[char]) {
+        for part in parts {
+            match part {
+                StringPart::Const(value) => {
+                    for c in value.chars() {
+                        if escape.contains(&c) {
+                            self.output.push('\\');
+                        }
+
+                        self.output.push(c);
+                    }
+                },
+                StringPart::Expr(expression) => {
+                    self.write("{");
+                    self.expression(expression);
+                    self.write("}");
+                },
+            }
+        }
+    }
+
+    fn single_quoted(&mut self, value: &str) {
+        self.write("'");
+
+        for c in value.chars() {
+            if c == '\'' || c == '\\' {
+                self.output.push('\\');
+            }
+
+            self.output.push(c);
+        }
+
+        self.write("'");
+    }
+
+    fn double_quoted(&mut self, parts: &[StringPart]) {
+        self.write("\"");
+        self.string_parts(parts, &['\\', '"', '$']);
+        self.write("\"");
+    }
+
+    /// Print a class reference for `new` or `instanceof`, which only accept a restricted set of
+    /// expressions without parentheses.
+    fn class_reference(&mut self, target: &Expression) {
+        self.operand(target, ! is_class_reference(target));
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        let precedence = precedence(expression);
+
+        match &expression.kind {
+            ExpressionKind::Static => self.write("static"),
+            ExpressionKind::PreIncrement { value } => self.prefix("++", value, precedence),
+            ExpressionKind::PreDecrement { value } => self.prefix("--", value, precedence),
+            ExpressionKind::PostIncrement { value } | ExpressionKind::PostDecrement { value } => {
+                self.operand(value, self::precedence(value) < precedence);
+                self.write(if matches!(expression.kind, ExpressionKind::PostIncrement { .. }) { "++" } else { "--" });
+            },
+            ExpressionKind::Int { i } => self.write(&i.to_string()),
+            ExpressionKind::Float { f } => {
+                // Floats are written without an exponent, since that's the only form the lexer
+                // understands, and always with a decimal point so that they stay floats.
+                let mut f = f.0.to_string();
+
+                if ! f.contains('.') {
+                    f.push_str(".0");
+                }
+
+                self.write(&f);
+            },
+            ExpressionKind::Variable { name } => {
+                self.write("$");
+                self.write(name);
+            },
+            ExpressionKind::Infix { lhs, op, rhs } => self.binary(lhs, infix_op(op), rhs, precedence),
+            // `Foo::$bar()` would call the method named by `$bar` rather than the property.
+            ExpressionKind::Call { target, args } if matches!(target.kind, ExpressionKind::StaticPropertyFetch { .. }) => {
+                self.operand(target, true);
+                self.args(args);
+            },
+            ExpressionKind::Call { target, args } => {
+                self.target(target);
+                self.args(args);
+            },
+            ExpressionKind::Identifier { name } => self.write(name),
+            ExpressionKind::Array { items } => {
+                self.write("[");
+                self.separated(items, ", ", |p, item| {
+                    if let Some(key) = &item.key {
+                        p.expression(key);
+                        p.write(" => ");
+                    }
+
+                    if item.unpack {
+                        p.write("...");
+                    }
+
+                    if item.by_ref {
+                        p.write("&");
+                    }
+
+                    p.expression(&item.value);
+                });
+                self.write("]");
+            },
+            ExpressionKind::Closure { params, uses, return_type, returns_by_ref, body, attributes } => {
+                self.attributes(attributes, true);
+                self.write("function ");
+
+                if *returns_by_ref {
+                    self.write("&");
+                }
+
+                self.params(params);
+
+                if ! uses.is_empty() {
+                    self.write(" use (");
+                    self.separated(uses, ", ", |p, r#use| {
+                        if r#use.by_ref {
+                            p.write("&");
+                        }
+
+                        p.expression(&r#use.var);
+                    });
+                    self.write(")");
+                }
+
+                self.return_type(return_type);
+                self.write(" ");
+                self.block(body);
+            },
+            ExpressionKind::ArrowFunction { params, return_type, returns_by_ref, expr, attributes } => {
+                self.attributes(attributes, true);
+                self.write("fn ");
+
+                if *returns_by_ref {
+                    self.write("&");
+                }
+
+                self.params(params);
+                self.return_type(return_type);
+                self.write(" => ");
+                self.expression(expr);
+            },
+            ExpressionKind::New { target, args } => {
+                self.write("new ");
+
+                match &target.kind {
+                    ExpressionKind::AnonymousClass { extends, implements, body, attributes } => {
+                        self.attributes(attributes, true);
+                        self.write("class");
+                        self.args(args);
+                        self.class_header(extends, implements);
+                        self.write(" ");
+                        self.block(body);
+                    },
+                    _ => {
+                        self.class_reference(target);
+                        self.args(args);
+                    },
+                }
+            },
+            ExpressionKind::ConstantString { value } => self.single_quoted(value),
+            ExpressionKind::InterpolatedString { parts } => self.double_quoted(parts),
+            // Heredocs and nowdocs are shorter as the equivalent quoted strings.
+            ExpressionKind::Heredoc { parts, .. } if self.compact => match parts.as_slice() {
+                [] => self.single_quoted(""),
+                [StringPart::Const(value)] => self.single_quoted(value),
+                _ => self.double_quoted(parts),
+            },
+            ExpressionKind::Nowdoc { value, .. } if self.compact => self.single_quoted(value),
+            ExpressionKind::Heredoc { label, parts } => {
+                self.write("<<<");
+                self.write(label);
+                self.output.push('\n');
+
+                if ! parts.is_empty() {
+                    self.string_parts(parts, &['\\', '$']);
+                    self.output.push('\n');
+                }
+
+                self.write(label);
+            },
+            ExpressionKind::Nowdoc { label, value } => {
+                self.write("<<<'");
+                self.write(label);
+                self.write("'\n");
+
+                if ! value.is_empty() {
+                    self.write(value);
+                    self.output.push('\n');
+                }
+
+                self.write(label);
+            },
+            ExpressionKind::PropertyFetch { target, property } | ExpressionKind::NullsafePropertyFetch { target, property } => {
+                self.target(target);
+                self.write(if matches!(expression.kind, ExpressionKind::PropertyFetch { .. }) { "->" } else { "?->" });
+                self.member(property);
+            },
+            ExpressionKind::StaticPropertyFetch { target, property } => {
+                self.target(target);
+                self.write("::");
+                self.member(property);
+            },
+            ExpressionKind::Dynamic { value } => {
+                self.write("$");
+
+                match value.kind {
+                    ExpressionKind::Variable { .. } | ExpressionKind::Dynamic { .. } => self.expression(value),
+                    _ => {
+                        self.write("{");
+                        self.expression(value);
+                        self.write("}");
+                    },
+                }
+            },
+            ExpressionKind::ConstFetch { target, constant } => {
+                self.target(target);
+                self.write("::");
+                self.write(&constant.name);
+            },
+            ExpressionKind::DynamicConstFetch { target, constant } => {
+                self.target(target);
+                self.write("::{");
+                self.expression(constant);
+                self.write("}");
+            },
+            ExpressionKind::MethodCall { target, method, args } | ExpressionKind::NullsafeMethodCall { target, method, args } => {
+                self.target(target);
+                self.write(if matches!(expression.kind, ExpressionKind::MethodCall { .. }) { "->" } else { "?->" });
+                self.member(method);
+                self.args(args);
+            },
+            ExpressionKind::StaticMethodCall { target, method, args } => {
+                self.target(target);
+                self.write("::");
+                self.write(&method.name);
+                self.args(args);
+            },
+            ExpressionKind::DynamicStaticMethodCall { target, method, args } => {
+                self.target(target);
+                self.write("::");
+
+                if matches!(method.kind, ExpressionKind::Variable { .. }) {
+                    self.expression(method);
+                } else {
+                    self.write("{");
+                    self.expression(method);
+                    self.write("}");
+                }
+
+                self.args(args);
+            },
+            ExpressionKind::AnonymousClass { extends, implements, body, attributes } => {
+                self.attributes(attributes, true);
+                self.write("class");
+                self.class_header(extends, implements);
+                self.write(" ");
+                self.block(body);
+            },
+            ExpressionKind::Bool { value } => self.write(if *value { "true" } else { "false" }),
+            ExpressionKind::ArrayIndex { array, index } => {
+                self.target(array);
+                self.write("[");
+
+                if let Some(index) = index {
+                    self.expression(index);
+                }
+
+                self.write("]");
+            },
+            ExpressionKind::Null => self.write("null"),
+            ExpressionKind::Error => self.write("/* error */"),
+            ExpressionKind::BooleanNot { value } => self.prefix("!", value, precedence),
+            ExpressionKind::MagicConst { constant } => self.write(magic_const(constant)),
+            ExpressionKind::Ternary { condition, then, r#else } => {
+                // Nested ternaries have to be parenthesized.
+                self.operand(condition, self::precedence(condition) <= precedence);
+
+                match then {
+                    Some(then) => {
+                        self.write(" ? ");
+                        self.operand(then, self::precedence(then) <= precedence);
+                        self.write(" : ");
+                    },
+                    None => self.write(" ?: "),
+                }
+
+                self.operand(r#else, self::precedence(r#else) <= precedence);
+            },
+            ExpressionKind::Coalesce { lhs, rhs } => self.binary(lhs, "??", rhs, precedence),
+            ExpressionKind::Assign { var, value } => self.binary(var, "=", value, precedence),
+            ExpressionKind::CoalesceAssign { var, value } => self.binary(var, "??=", value, precedence),
+            ExpressionKind::AssignRef { var, value } => {
+                self.expression(var);
+                self.write(" = &");
+                self.operand(value, self::precedence(value) < Precedence::Access);
+            },
+            ExpressionKind::Instanceof { lhs, rhs } => {
+                self.operand(lhs, self::precedence(lhs) < precedence);
+                self.write(" instanceof ");
+                self.class_reference(rhs);
+            },
+            ExpressionKind::Clone { target } => self.keyword("clone", target),
+            ExpressionKind::Match { condition, arms } => {
+                self.write("match (");
+                self.expression(condition);
+                self.write(") {");
+                self.indent += 1;
+
+                for arm in arms {
+                    self.newline();
+
+                    match &arm.conditions {
+                        Some(conditions) => self.separated(conditions, ", ", |p, condition| p.expression(condition)),
+                        None => self.write("default"),
+                    }
+
+                    self.write(" => ");
+                    self.expression(&arm.body);
+                    self.write(",");
+                }
+
+                self.indent -= 1;
+                self.newline();
+                self.write("}");
+            },
+            ExpressionKind::Throw { value } => self.keyword("throw", value),
+            ExpressionKind::Yield { value } => self.keyword("yield", value),
+            ExpressionKind::Negate { value } => self.prefix("-", value, precedence),
+            ExpressionKind::UnaryPlus { value } => self.prefix("+", value, precedence),
+            ExpressionKind::BitwiseNot { value } => self.prefix("~", value, precedence),
+            ExpressionKind::ErrorSuppress { value } => self.prefix("@", value, precedence),
+            ExpressionKind::Print { value } => self.keyword("print", value),
+            ExpressionKind::Exit { value } => {
+                self.write("exit");
+
+                if let Some(value) = value {
+                    self.write("(");
+                    self.expression(value);
+                    self.write(")");
+                }
+            },
+            ExpressionKind::Include { path } => self.keyword("include", path),
+            ExpressionKind::IncludeOnce { path } => self.keyword("include_once", path),
+            ExpressionKind::Require { path } => self.keyword("require", path),
+            ExpressionKind::RequireOnce { path } => self.keyword("require_once", path),
+            ExpressionKind::Cast { kind, value } => {
+                let cast = match kind {
+                    CastKind::String => "(string) ",
+                    CastKind::Object => "(object) ",
+                    CastKind::Bool => "(bool) ",
+                    CastKind::Int => "(int) ",
+                    CastKind::Double => "(float) ",
+                    CastKind::Array => "(array) ",
+                };
+
+                self.prefix(cast, value, precedence);
+            },
+        }
+    }
+}
+
+/// Whether two characters would be lexed differently if they were written next to each other, so
+/// that compact output has to keep a space between them: words such as keywords, names and
+/// variables, signs that would become `++` or `--`, a heredoc after `<`, and numbers next to `.`.
+fn runs_together(previous: char, next: char) -> bool {
+    let word = |c: char| c.is_alphanumeric() || matches!(c, '_' | '$' | '\\') || ! c.is_ascii();
+
+    word(previous) && word(next)
+        || matches!((previous, next), ('+', '+') | ('-', '-') | ('<', '<') | ('.', '0'..='9') | ('0'..='9', '.'))
+}
+
+/// The spans of the statements directly inside another, including those in the bodies of its
+/// closures, and where the ones with doc comments start.
+#[derive(Default)]
+struct Children {
+    spans: Vec<Span>,
+    documented: Vec<usize>,
+}
+
+impl Visitor for Children {
+    fn visit_statement(&mut self, statement: &Statement) {
+        self.spans.push(statement.span);
+
+        if doc_comment(statement).is_some() {
+            self.documented.push(statement.span.start);
+        }
+    }
+}
+
+fn doc_comment(statement: &Statement) -> &Option<String> {
+    match &statement.kind {
+        StatementKind::Const { doc_comment, .. } | StatementKind::Property { doc_comment, .. } | StatementKind::Constant { doc_comment, .. } |
+        StatementKind::Function { doc_comment, .. } | StatementKind::Class { doc_comment, .. } | StatementKind::Method { doc_comment, .. } => doc_comment,
+        _ => &None,
+    }
+}
+
+fn is_declaration(statement: &Statement) -> bool {
+    matches!(statement.kind,
+        StatementKind::Function { .. } | StatementKind::Class { .. } | StatementKind::Trait { .. } | StatementKind::Interface { .. } |
+        StatementKind::Enum { .. } | StatementKind::Method { .. } | StatementKind::Namespace { .. }
+    )
+}
+
+fn is_negative(expression: &Expression) -> bool {
+    match expression.kind {
+        ExpressionKind::Int { i } => i < 0,
+        ExpressionKind::Float { f } => f.0.is_sign_negative(),
+        _ => false,
+    }
+}
+
+/// Negative numbers are printed with a leading `-`, so they bind like a negation.
+fn precedence(expression: &Expression) -> Precedence {
+    if is_negative(expression) {
+        return Precedence::Prefix;
+    }
+
+    Precedence::of(&expression.kind)
+}
+
+fn is_class_reference(expression: &Expression) -> bool {
+    match &expression.kind {
+        ExpressionKind::Static | ExpressionKind::Identifier { .. } | ExpressionKind::Variable { .. } => true,
+        ExpressionKind::PropertyFetch { target, .. } | ExpressionKind::StaticPropertyFetch { target, .. } => is_class_reference(target),
+        ExpressionKind::ArrayIndex { array, index: Some(_) } => is_class_reference(array),
+        _ => false,
+    }
+}
+
+fn infix_op(op: &InfixOp) -> &'static str {
+    match op {
+        InfixOp::Add => "+",
+        InfixOp::Sub => "-",
+        InfixOp::Div => "/",
+        InfixOp::Mul => "*",
+        InfixOp::Mod => "%",
+        InfixOp::LeftShift => "<<",
+        InfixOp::RightShift => ">>",
+        InfixOp::BitwiseAnd => "&",
+        InfixOp::BitwiseOr => "|",
+        InfixOp::BitwiseXor => "^",
+        InfixOp::Concat => ".",
+        InfixOp::ConcatAssign => ".=",
+        InfixOp::LessThan => "<",
+        InfixOp::GreaterThan => ">",
+        InfixOp::LessThanEquals => "<=",
+        InfixOp::GreaterThanEquals => ">=",
+        InfixOp::Equals => "==",
+        InfixOp::Identical => "===",
+        InfixOp::NotEquals => "!=",
+        InfixOp::NotIdentical => "!==",
+        InfixOp::Spaceship => "<=>",
+        InfixOp::And => "&&",
+        InfixOp::Or => "||",
+        InfixOp::LogicalAnd => "and",
+        InfixOp::LogicalOr => "or",
+        InfixOp::LogicalXor => "xor",
+        InfixOp::AddAssign => "+=",
+        InfixOp::Pow => "**",
+        InfixOp::MulAssign => "*=",
+        InfixOp::SubAssign => "-=",
+        InfixOp::DivAssign => "/=",
+        InfixOp::ModAssign => "%=",
+        InfixOp::PowAssign => "**=",
+        InfixOp::BitwiseAndAssign => "&=",
+        InfixOp::BitwiseOrAssign => "|=",
+        InfixOp::BitwiseXorAssign => "^=",
+        InfixOp::LeftShiftAssign => "<<=",
+        InfixOp::RightShiftAssign => ">>=",
+    }
+}
+
+fn magic_const(constant: &MagicConst) -> &'static str {
+    match constant {
+        MagicConst::Class => "__CLASS__",
+        MagicConst::Dir => "__DIR__",
+        MagicConst::File => "__FILE__",
+        MagicConst::Function => "__FUNCTION__",
+        MagicConst::Line => "__LINE__",
+        MagicConst::Method => "__METHOD__",
+        MagicConst::Namespace => "__NAMESPACE__",
+        MagicConst::Trait => "__TRAIT__",
+    }
+}
+
+fn property_flag(flag: &PropertyFlag) -> &'static str {
+    match flag {
+        PropertyFlag::Public => "public",
+        PropertyFlag::Protected => "protected",
+        PropertyFlag::Private => "private",
+        PropertyFlag::PublicSet => "public(set)",
+        PropertyFlag::ProtectedSet => "protected(set)",
+        PropertyFlag::PrivateSet => "private(set)",
+        PropertyFlag::Static => "static",
+        PropertyFlag::Readonly => "readonly",
+    }
+}
+
+fn method_flag(flag: &MethodFlag) -> &'static str {
+    match flag {
+        MethodFlag::Final => "final",
+        MethodFlag::Abstract => "abstract",
+        MethodFlag::Public => "public",
+        MethodFlag::Protected => "protected",
+        MethodFlag::Private => "private",
+        MethodFlag::Static => "static",
+    }
+}
+
+fn const_flag(flag: &ConstFlag) -> &'static str {
+    match flag {
+        ConstFlag::Final => "final",
+        ConstFlag::Public => "public",
+        ConstFlag::Protected => "protected",
+        ConstFlag::Private => "private",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trunk_lexer::Lexer;
+    use trunk_parser::{Parser, ParserConfig, Program, Expression, ExpressionKind, InfixOp};
+
+    use super::{format, minify, print, print_expression, stub, BraceStyle, Style};
+
+    fn parse(source: &str) -> Program {
+        let tokens = Lexer::new(None).tokenize(source).unwrap();
+
+        Parser::new(None).parse(&tokens).unwrap()
+    }
+
+    /// Printing a parsed program and parsing it again should give back the same tree.
+    fn assert_round_trip(source: &str) {
+        let ast = parse(source);
+        let printed = print(&ast);
+
+        assert_eq!(parse(&printed), ast, "printed source:\n{}", printed);
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(print(&parse("<?php function foo($a, int ...$b): ?string { if ($a) { return $b; } }")), "\
+<?php
+
+function foo($a, int ...$b): ?string
+{
+    if ($a) {
+        return $b;
+    }
+}
+");
+
+        assert_eq!(print(&parse("<?php $a=1;$b=[1,'a'=>2];")), "<?php\n\n$a = 1;\n$b = [1, 'a' => 2];\n");
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!(print(&parse("<?php ($a + $b) * $c; $a + $b * $c; $a - ($b - $c); ($a - $b) - $c;")), "\
+<?php
+
+($a + $b) * $c;
+$a + $b * $c;
+$a - ($b - $c);
+$a - $b - $c;
+");
+
+        assert_eq!(print(&parse("<?php ($a = $b) + 1; !($a && $b); (new Foo)->bar(); -(-$a); ($a ? $b : $c) ? $d : $e;")), "\
+<?php
+
+($a = $b) + 1;
+!($a && $b);
+(new Foo())->bar();
+-(-$a);
+($a ? $b : $c) ? $d : $e;
+");
+
+        let negative: Expression = ExpressionKind::Infix {
+            lhs: Box::new(ExpressionKind::Int { i: -2 }.into()),
+            op: InfixOp::Pow,
+            rhs: Box::new(ExpressionKind::Int { i: 2 }.into()),
+        }.into();
+        assert_eq!(print_expression(&negative), "(-2) ** 2");
+    }
+
+    #[test]
+    fn round_trips() {
+        assert_round_trip("<?php
+            namespace App\\Models;
+
+            use Foo\\Bar as Baz, Qux;
+            use function strlen;
+
+            #[Entity(table: 'users')]
+            abstract class User extends Model implements JsonSerializable, Countable
+            {
+                use HasName;
+
+                /** The table. */
+                final public const TABLE = 'users';
+
+                public static ?int $count = null;
+                private array $items = [];
+
+                public function __construct(private string $name, protected int $age = 18) {}
+
+                abstract protected function build(): static;
+
+                public function &items(int|string $key, A&B $b, array &$seen, ...$rest): array|null
+                {
+                    global $config, $cache;
+                    foreach ($this->items as $k => &$v) {
+                        $v = $k <=> $key ? $v->{$key} : static::$count;
+                    }
+
+                    return $this->items[$key] ?? throw new Exception(\"Missing {$key} in $this->name\");
+                }
+            }
+
+            interface HasItems extends Countable
+            {
+                public function items(): array;
+            }
+
+            enum Suit: string implements HasColor
+            {
+                case Hearts = 'H';
+                case Spades = 'S';
+
+                const Wild = self::Spades;
+            }
+
+            trait HasName
+            {
+                public function name() { return $this->name; }
+            }
+        ");
+
+        assert_round_trip("<?php
+            $a = function ($x) use (&$y, $z): int { return $x + $y * $z; };
+            $b = fn ($x) => $x ** 2;
+            $c = new class(1, 2) extends Foo { public $a = 1; };
+            $d = match ($e) { 1, 2 => 'a', default => 'b' };
+            $f = $a?->b?->c() ?: 'd';
+            $g = (string) $h . (int) '1' . @$i['j'][] . -$k++ . ~$l . !$m instanceof N;
+            $n ??= $o ?? $p ?? $q;
+            $r .= $s and $t or $u xor $v;
+            $r %= 2; $r **= 2; $r &= 1; $r |= 2; $r ^= 3; $r <<= 1; $r >>= 1;
+            $w = &$x;
+            [$y, [$z]] = foo(...$args, named: true);
+            print $a;
+            exit(1);
+            echo __DIR__ . '/foo\\\\bar\\'s', \"\\$a\", 1.5, 2.0, 99999999999999999999, null, false;
+            require_once __DIR__ . '/vendor/autoload.php';
+            static $count = 0, $total;
+            /** Limits. */
+            const A = 1, B = 2;
+            yield $a;
+            clone $a->b;
+            Foo::{$bar}; Foo::BAR; Foo::bar(); Foo::$bar; $foo->$bar();
+            $$a = ${'b' . $c}[0]; $$$d;
+            Foo::$bar(1); Foo::{'b' . $c}(); (Foo::$bar)();
+        ");
+
+        assert_round_trip("<?php
+            if ($a) { a(); } elseif ($b) { b(); } else { c(); }
+            while ($a--) { continue; }
+            for ($i = 0; $i < 10; $i++) { break 1; }
+            for (;;) {}
+            switch ($a) { case 1: echo 1; break; default: echo 2; }
+            try { a(); } catch (A | B $e) { b(); } catch (C $e) {} finally { c(); }
+            $a = <<<EOT
+              Hello $name
+              {$a->b} \\$c
+              EOT;
+            $b = <<<'EOT'
+            Raw $text
+            EOT;
+            ?>
+            <p>Hello</p>
+            <?php
+            echo 1;
+        ");
+    }
+
+    #[test]
+    fn property_hooks() {
+        let source = "<?php class Foo { public readonly int $a; public string $b { get => $this->c; final set(string $value) { $this->c = $value; } } public int $d { &get; set; } }";
+
+        assert_round_trip(source);
+        assert!(print(&parse(source)).contains("    public string $b {\n        get => $this->c;\n        final set(string $value) {\n"));
+    }
+
+    #[test]
+    fn variable_names() {
+        assert_round_trip("<?php $n5 = 1; $a1b2 = $n5; echo \"{$n5} $a1b2\";");
+    }
+
+    #[test]
+    fn inline_html_and_halt_compiler() {
+        assert_round_trip("<?php echo 1; ?>\n\n<p>Hello</p>\n<?php echo 2;");
+        assert!(print(&parse("<?php echo 1; ?>\n\nHello")).ends_with("?>\n\nHello<?php\n"));
+
+        let printed = print(&parse("<?php echo 1;\n__halt_compiler();raw\ndata"));
+        assert!(printed.ends_with("__halt_compiler();raw\ndata"), "printed source:\n{}", printed);
+    }
+
+    #[test]
+    fn formatting_source() {
+        let source = "<?php
+// Setup.
+$a = 1; // one
+
+
+$b = 2;
+/** Docs. */
+function foo() { /* nothing */ }
+class Foo {
+    // Constants.
+    const A = 1;
+
+    public function bar() {}
+}
+";
+        let tokens = Lexer::new(None).tokenize(source).unwrap();
+        let program = Parser::new(Some(ParserConfig { comments: true, ..Default::default() })).parse(&tokens).unwrap();
+
+        assert_eq!(format(&program, source, &Style::default()), "\
+<?php
+
+// Setup.
+$a = 1; // one
+
+$b = 2;
+
+/** Docs. */
+function foo()
+{
+    /* nothing */
+}
+
+class Foo
+{
+    // Constants.
+    const A = 1;
+
+    public function bar()
+    {
+    }
+}
+");
+
+        let style = Style { indent: "\t".into(), braces: BraceStyle::SameLine };
+
+        assert_eq!(format(&program, source, &style).lines().skip(8).take(3).collect::<Vec<_>>(), [
+            "function foo() {",
+            "\t/* nothing */",
+            "}",
+        ]);
+    }
+
+    #[test]
+    fn formatting_comments_in_expressions() {
+        let source = "<?php
+/** Docs. */
+function foo() {
+  $a = [1, // one
+    2];
+  if($a){echo 1;}
+}
+";
+        let tokens = Lexer::new(None).tokenize(source).unwrap();
+        let program = Parser::new(Some(ParserConfig { comments: true, ..Default::default() })).parse(&tokens).unwrap();
+
+        // Only the statement with the comment in it is left as it was.
+        assert_eq!(format(&program, source, &Style::default()), "\
+<?php
+
+/** Docs. */
+function foo()
+{
+    $a = [1, // one
+    2];
+    if ($a) {
+        echo 1;
+    }
+}
+");
+    }
+
+    #[test]
+    fn minifying() {
+        let source = "<?php
+            namespace App;
+
+            /** A user. */
+            final class User extends \\Base
+            {
+                public const A = 1 . 2;
+                private ?int $id = null;
+
+                // Make one.
+                public static function make(int ...$args): static
+                {
+                    $a = $b - -1 + +$c - --$d;
+                    echo <<<EOT
+                    Hello \"{$name}\"
+                    EOT, <<<'EOT'
+                    it's
+                    EOT;
+                    return new \\Foo\\Bar() instanceof Baz ? $a : null;
+                }
+            }
+            ?>
+            <b>html</b>
+            <?php
+            foreach ($a as $k => &$v) { if (! $v) { continue; } }
+        ";
+
+        let minified = minify(&parse(source));
+        assert_eq!(minified, "<?php namespace App;final class User extends \\Base{const A=1 . 2;private?int $id=null;\
+static function make(int...$args):static{$a=$b- -1+ +$c- --$d;echo\"Hello \\\"{$name}\\\"\",'it\\'s';\
+return new \\Foo\\Bar()instanceof Baz?$a:null;}}?>            <b>html</b>
+            <?php foreach($a as $k=>&$v){if(!$v){continue;}}");
+
+        // Minifying again changes nothing.
+        assert_eq!(minify(&parse(&minified)), minified);
+    }
+
+    #[test]
+    fn stubs() {
+        let source = "<?php
+namespace App;
+
+use Foo\\Bar;
+
+const VERSION = '1.0';
+
+if (! function_exists('helper')) {
+    function helper() {}
+}
+
+/** Says hello. */
+function greet(string $name = 'world'): string {
+    return \"Hello, $name\";
+}
+
+abstract class Greeter extends Base implements Contract {
+    use Loud;
+
+    public const PREFIX = 'Hi';
+    protected ?string $name = null;
+
+    public function __construct(private Bar $bar) {
+        $this->name = 'x';
+    }
+
+    abstract public function greet(): string;
+}
+
+enum Suit: string {
+    case Hearts = 'H';
+
+    public function label(): string { return ucfirst($this->name); }
+}
+
+echo greet();
+";
+        let stubbed = stub(&parse(source));
+
+        assert_eq!(print(&stubbed), "\
+<?php
+
+namespace App;
+
+use Foo\\Bar;
+const VERSION = '1.0';
+
+/** Says hello. */
+function greet(string $name = 'world'): string
+{
+}
+
+abstract class Greeter extends Base implements Contract
+{
+    use Loud;
+    public const PREFIX = 'Hi';
+    protected ?string $name = null;
+
+    public function __construct(private Bar $bar)
+    {
+    }
+
+    abstract public function greet(): string;
+}
+
+enum Suit: string
+{
+    case Hearts = 'H';
+
+    public function label(): string
+    {
+    }
+}
+");
+
+        assert_round_trip(&print(&stubbed));
+        assert!(stub(&parse("<?php namespace A { echo 1; } namespace B { class C {} }")).len() == 1);
+    }
+}
@@ -0,0 +1,15 @@
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// Lex PHP source, giving a JSON object with the `tokens`, or `null` if the source couldn't be
+/// lexed, along with any `diagnostics`.
+#[wasm_bindgen]
+pub fn tokenize(source: &str) -> String {
+    trunk_parser::tokenize_json(source)
+}
+
+/// Parse PHP source tolerantly, giving a JSON object with the `ast`, which has error nodes where
+/// parsing recovered, along with any `diagnostics`.
+#[wasm_bindgen]
+pub fn parse(source: &str) -> String {
+    trunk_parser::parse_json(source)
+}